@@ -1,10 +1,10 @@
 use crate::{deepgram::stream_transcription_deepgram, AudioStream};
 use anyhow::Result;
-use chrono::{DateTime, Utc};
 use screenpipe_core::Language;
-use serde::{Deserialize, Serialize};
 use std::sync::{atomic::AtomicBool, Arc};
 
+pub use screenpipe_events::RealtimeTranscriptionEvent;
+
 pub async fn realtime_stt(
     stream: Arc<AudioStream>,
     languages: Vec<Language>,
@@ -15,13 +15,3 @@ pub async fn realtime_stt(
 
     Ok(())
 }
-
-#[derive(Serialize, Deserialize, Clone)]
-#[serde(rename_all = "camelCase")]
-pub struct RealtimeTranscriptionEvent {
-    pub timestamp: DateTime<Utc>,
-    pub device: String,
-    pub transcription: String,
-    pub is_final: bool,
-    pub is_input: bool,
-}
@@ -24,6 +24,38 @@ pub use llama::*;
 pub mod pipes;
 #[cfg(feature = "pipes")]
 pub use pipes::*;
+#[cfg(feature = "pipes")]
+pub mod pipe_manifest;
+#[cfg(feature = "pipes")]
+pub use pipe_manifest::*;
+#[cfg(feature = "pipes")]
+pub mod pipe_runtime;
+#[cfg(feature = "pipes")]
+pub use pipe_runtime::*;
+#[cfg(feature = "pipes")]
+pub mod pipe_sandbox;
+#[cfg(feature = "pipes")]
+pub use pipe_sandbox::*;
+#[cfg(feature = "pipes")]
+pub mod pipe_logs;
+#[cfg(feature = "pipes")]
+pub use pipe_logs::*;
+#[cfg(feature = "pipes")]
+pub mod pipe_cron;
+#[cfg(feature = "pipes")]
+pub use pipe_cron::*;
+#[cfg(feature = "pipes")]
+pub mod pipe_source;
+#[cfg(feature = "pipes")]
+pub use pipe_source::*;
+#[cfg(feature = "pipes")]
+pub mod pipe_versions;
+#[cfg(feature = "pipes")]
+pub use pipe_versions::*;
+#[cfg(feature = "pipes")]
+pub mod pipe_secrets;
+#[cfg(feature = "pipes")]
+pub use pipe_secrets::*;
 mod language;
 #[cfg(feature = "security")]
 pub mod pii_removal;
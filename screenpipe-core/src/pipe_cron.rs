@@ -0,0 +1,576 @@
+//! Runs the `crons` of `pipe.json`: http routes of a running pipe and scripts spawned per
+//! run with the pipe's runtime, with a timeout, an overlap policy per job and a limit on
+//! runs going at once. Every run ends up in the [`CronHistory`].
+
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use reqwest::header::AUTHORIZATION;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{watch, Mutex, Semaphore};
+use tracing::{debug, error, info, warn};
+
+use crate::pipe_manifest::{CronOverlap, PipeCron, PipeManifest};
+use crate::pipe_runtime::PipeRuntime;
+use crate::pipe_sandbox::{PipePermissions, PipeSandbox};
+use crate::pipe_secrets::get_pipe_secrets;
+
+pub const DEFAULT_CRON_TIMEOUT_SECS: u64 = 300;
+/// Cron runs of all pipes going at once, later ones wait for a slot.
+pub const MAX_CONCURRENT_CRON_RUNS: usize = 4;
+/// Bytes of output kept with each run, the end of it.
+const CRON_OUTPUT_EXCERPT_BYTES: usize = 4096;
+const MEMORY_HISTORY_RUNS_PER_PIPE: usize = 100;
+
+static SCHEDULED: Lazy<Mutex<HashMap<String, ScheduledPipe>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+static RUN_SLOTS: Lazy<Arc<Semaphore>> =
+    Lazy::new(|| Arc::new(Semaphore::new(MAX_CONCURRENT_CRON_RUNS)));
+/// Jobs finishing together would otherwise read and rewrite `.cron_state.json` over each other.
+static CRON_STATE_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+static HISTORY: Lazy<RwLock<Arc<dyn CronHistory>>> =
+    Lazy::new(|| RwLock::new(Arc::new(MemoryCronHistory::default())));
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CronTrigger {
+    Schedule,
+    Manual,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CronRunStatus {
+    Success,
+    Failed,
+    TimedOut,
+    /// Due while the previous run was still going, with the `skip` overlap policy.
+    Skipped,
+}
+
+impl CronTrigger {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CronTrigger::Schedule => "schedule",
+            CronTrigger::Manual => "manual",
+        }
+    }
+}
+
+impl FromStr for CronTrigger {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "schedule" => Ok(CronTrigger::Schedule),
+            "manual" => Ok(CronTrigger::Manual),
+            _ => Err(anyhow::anyhow!("unknown cron trigger: {}", s)),
+        }
+    }
+}
+
+impl CronRunStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CronRunStatus::Success => "success",
+            CronRunStatus::Failed => "failed",
+            CronRunStatus::TimedOut => "timed_out",
+            CronRunStatus::Skipped => "skipped",
+        }
+    }
+}
+
+impl FromStr for CronRunStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "success" => Ok(CronRunStatus::Success),
+            "failed" => Ok(CronRunStatus::Failed),
+            "timed_out" => Ok(CronRunStatus::TimedOut),
+            "skipped" => Ok(CronRunStatus::Skipped),
+            _ => Err(anyhow::anyhow!("unknown cron run status: {}", s)),
+        }
+    }
+}
+
+/// One execution of a cron job.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct CronRun {
+    /// Set once stored, by stores that number their rows.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    pub pipe_id: String,
+    /// The route or script of the job, see [`PipeCron::job`].
+    pub job: String,
+    pub trigger: CronTrigger,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    pub status: CronRunStatus,
+    /// End of the response body or process output, or the error.
+    pub output: Option<String>,
+}
+
+/// Where cron runs are kept. Screenpipe stores them in its database, until one is set
+/// the last runs are only kept in memory.
+#[async_trait]
+pub trait CronHistory: Send + Sync {
+    async fn record_cron_run(&self, run: &CronRun) -> Result<()>;
+
+    /// Most recent runs of `pipe_id` first, only of `job` when set.
+    async fn cron_runs(&self, pipe_id: &str, job: Option<&str>, limit: u32)
+        -> Result<Vec<CronRun>>;
+}
+
+#[derive(Default)]
+pub struct MemoryCronHistory {
+    runs: Mutex<HashMap<String, VecDeque<CronRun>>>,
+}
+
+#[async_trait]
+impl CronHistory for MemoryCronHistory {
+    async fn record_cron_run(&self, run: &CronRun) -> Result<()> {
+        let mut runs = self.runs.lock().await;
+        let pipe_runs = runs.entry(run.pipe_id.clone()).or_default();
+        pipe_runs.push_front(run.clone());
+        pipe_runs.truncate(MEMORY_HISTORY_RUNS_PER_PIPE);
+        Ok(())
+    }
+
+    async fn cron_runs(
+        &self,
+        pipe_id: &str,
+        job: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<CronRun>> {
+        Ok(self
+            .runs
+            .lock()
+            .await
+            .get(pipe_id)
+            .into_iter()
+            .flatten()
+            .filter(|run| job.is_none_or(|job| run.job == job))
+            .take(limit as usize)
+            .cloned()
+            .collect())
+    }
+}
+
+/// Replaces where cron runs are recorded, done once at startup.
+pub fn set_cron_history(history: Arc<dyn CronHistory>) {
+    *HISTORY.write().unwrap() = history;
+}
+
+fn cron_history() -> Arc<dyn CronHistory> {
+    HISTORY.read().unwrap().clone()
+}
+
+pub async fn get_cron_runs(pipe_id: &str, job: Option<&str>, limit: u32) -> Result<Vec<CronRun>> {
+    cron_history().cron_runs(pipe_id, job, limit).await
+}
+
+/// Runs the jobs of pipes that aren't processes, such as wasm pipes, in place of routes
+/// and scripts.
+#[async_trait]
+pub trait CronHandler: Send + Sync + std::fmt::Debug {
+    /// Output of a successful run, errors describe the failure.
+    async fn run(&self, cron: &PipeCron) -> Result<Option<String>>;
+}
+
+/// What a pipe's jobs need to run, the same for all of its jobs.
+#[derive(Clone, Debug)]
+pub struct CronContext {
+    pub pipe: String,
+    pub pipe_dir: PathBuf,
+    /// Where the running pipe serves http, route jobs fail without it.
+    pub base_url: Option<String>,
+    /// Sent as bearer token to routes, also in the `CRON_SECRET` variable of the pipe.
+    pub secret: String,
+    pub permissions: Option<PipePermissions>,
+    /// Screenpipe variables (`PIPE_DIR`, `SCREENPIPE_DIR`, ...) scripts are started with.
+    pub env: Vec<(String, String)>,
+    /// Runs the scripts.
+    pub runtime: PipeRuntime,
+    /// Runs the jobs instead of calling routes and spawning scripts when set.
+    pub handler: Option<Arc<dyn CronHandler>>,
+}
+
+struct CronJob {
+    context: Arc<CronContext>,
+    cron: PipeCron,
+    /// Held while a run is going, for the overlap policy.
+    running: Arc<Mutex<()>>,
+}
+
+struct ScheduledPipe {
+    shutdown: watch::Sender<bool>,
+    jobs: HashMap<String, Arc<CronJob>>,
+}
+
+pub fn generate_cron_secret() -> String {
+    thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect()
+}
+
+/// Starts the schedules of `crons`, replacing the ones of a previous start of the pipe.
+pub async fn schedule_pipe_crons(context: CronContext, crons: &[PipeCron]) {
+    let pipe = context.pipe.clone();
+    cleanup_pipe_crons(&pipe).await;
+    if crons.is_empty() {
+        return;
+    }
+    info!("[{}] scheduling {} cron jobs", pipe, crons.len());
+
+    let context = Arc::new(context);
+    let (shutdown, _) = watch::channel(false);
+    let mut jobs = HashMap::new();
+    for cron in crons {
+        let job = Arc::new(CronJob {
+            context: context.clone(),
+            cron: cron.clone(),
+            running: Arc::new(Mutex::new(())),
+        });
+        jobs.insert(cron.job(), job.clone());
+        tokio::spawn(run_cron_schedule(job, shutdown.subscribe()));
+    }
+
+    SCHEDULED
+        .lock()
+        .await
+        .insert(pipe, ScheduledPipe { shutdown, jobs });
+}
+
+/// Stops the schedules of `pipe`, runs already going finish.
+pub async fn cleanup_pipe_crons(pipe: &str) {
+    if let Some(scheduled) = SCHEDULED.lock().await.remove(pipe) {
+        info!(
+            "stopping {} cron jobs for pipe {}",
+            scheduled.jobs.len(),
+            pipe
+        );
+        let _ = scheduled.shutdown.send(true);
+    }
+}
+
+/// Runs `job` of `pipe` now and waits for it. Jobs of a pipe that isn't running are read
+/// from its `pipe.json`, only scripts can run then. `None` when the pipe has no such job.
+pub async fn run_cron_now(pipe: &str, job: &str, screenpipe_dir: &Path) -> Result<Option<CronRun>> {
+    let scheduled = SCHEDULED
+        .lock()
+        .await
+        .get(pipe)
+        .and_then(|scheduled| scheduled.jobs.get(job).cloned());
+
+    let job = match scheduled {
+        Some(job) => job,
+        None => {
+            let pipe_dir = screenpipe_dir.join("pipes").join(pipe);
+            let Some(manifest) = PipeManifest::load_from_dir(&pipe_dir).await? else {
+                return Ok(None);
+            };
+            let Some(cron) = manifest.crons.iter().find(|cron| cron.job() == job) else {
+                return Ok(None);
+            };
+            let secret = generate_cron_secret();
+            let mut env = vec![
+                (
+                    "SCREENPIPE_DIR".to_string(),
+                    screenpipe_dir.to_string_lossy().into_owned(),
+                ),
+                ("PIPE_ID".to_string(), pipe.to_string()),
+                (
+                    "PIPE_DIR".to_string(),
+                    pipe_dir.to_string_lossy().into_owned(),
+                ),
+                ("CRON_SECRET".to_string(), secret.clone()),
+            ];
+            env.extend(get_pipe_secrets(screenpipe_dir, pipe).await?);
+            Arc::new(CronJob {
+                context: Arc::new(CronContext {
+                    pipe: pipe.to_string(),
+                    pipe_dir,
+                    base_url: None,
+                    secret,
+                    permissions: manifest
+                        .permissions
+                        .map(PipePermissions::with_expanded_paths),
+                    env,
+                    runtime: manifest.runtime,
+                    handler: None,
+                }),
+                cron: cron.clone(),
+                running: Arc::new(Mutex::new(())),
+            })
+        }
+    };
+
+    Ok(Some(execute(&job, CronTrigger::Manual).await))
+}
+
+async fn run_cron_schedule(job: Arc<CronJob>, mut shutdown: watch::Receiver<bool>) {
+    let pipe = &job.context.pipe;
+    let name = job.cron.job();
+    let schedule = match cron::Schedule::from_str(&job.cron.schedule) {
+        Ok(schedule) => schedule,
+        Err(e) => {
+            // validated with the manifest, only reachable with a hand-built context
+            error!("[{}] invalid cron schedule for {}: {}", pipe, name, e);
+            return;
+        }
+    };
+
+    loop {
+        let now = chrono::Local::now();
+        let Some(next) = schedule.after(&now).next() else {
+            info!("[{}] cron job {} has no upcoming runs", pipe, name);
+            break;
+        };
+        let delay = (next - now).to_std().unwrap_or_default();
+        debug!(
+            "[{}] next run of cron job {} in {} seconds",
+            pipe,
+            name,
+            delay.as_secs()
+        );
+
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {
+                // runs in the background so a slow run doesn't delay the schedule, the
+                // overlap policy decides what happens if it is still going next time
+                let job = job.clone();
+                tokio::spawn(async move {
+                    execute(&job, CronTrigger::Schedule).await;
+                });
+            }
+            _ = shutdown.changed() => {
+                debug!("[{}] stopped cron job {}", pipe, name);
+                break;
+            }
+        }
+    }
+}
+
+/// Runs the job once under its overlap policy, the concurrency limit and timeout, and
+/// records the run.
+async fn execute(job: &CronJob, trigger: CronTrigger) -> CronRun {
+    let context = &job.context;
+    let name = job.cron.job();
+    let started_at = Utc::now();
+
+    let _running = match job.cron.overlap {
+        CronOverlap::Skip => match job.running.clone().try_lock_owned() {
+            Ok(guard) => Some(guard),
+            Err(_) => {
+                info!(
+                    "[{}] skipping cron job {}, the previous run is still going",
+                    context.pipe, name
+                );
+                let run = CronRun {
+                    id: None,
+                    pipe_id: context.pipe.clone(),
+                    job: name,
+                    trigger,
+                    started_at,
+                    ended_at: started_at,
+                    status: CronRunStatus::Skipped,
+                    output: None,
+                };
+                record(&run).await;
+                return run;
+            }
+        },
+        CronOverlap::Queue => Some(job.running.clone().lock_owned().await),
+        CronOverlap::Allow => None,
+    };
+    let _slot = RUN_SLOTS
+        .acquire()
+        .await
+        .expect("cron run slots are never closed");
+
+    info!("[{}] running cron job {}", context.pipe, name);
+    if let Err(e) = save_cron_execution(&context.pipe_dir, &name).await {
+        warn!("[{}] failed to save cron state: {}", context.pipe, e);
+    }
+
+    let timeout = Duration::from_secs(job.cron.timeout_secs.unwrap_or(DEFAULT_CRON_TIMEOUT_SECS));
+    // a queued run starts once it got its turn
+    let started_at = Utc::now();
+    let (status, output) = match tokio::time::timeout(timeout, run_job(job)).await {
+        Ok(Ok(output)) => (CronRunStatus::Success, output),
+        Ok(Err(e)) => (CronRunStatus::Failed, Some(format!("{:#}", e))),
+        Err(_) => (
+            CronRunStatus::TimedOut,
+            Some(format!("cancelled after {} seconds", timeout.as_secs())),
+        ),
+    };
+    match status {
+        CronRunStatus::Success => info!("[{}] cron job {} succeeded", context.pipe, name),
+        _ => {
+            let message = format!(
+                "[{}] cron job {} {}: {}",
+                context.pipe,
+                name,
+                status.as_str(),
+                output.as_deref().unwrap_or_default()
+            );
+            error!("{}", message);
+            sentry::capture_message(&message, sentry::Level::Error);
+        }
+    }
+
+    let run = CronRun {
+        id: None,
+        pipe_id: context.pipe.clone(),
+        job: name,
+        trigger,
+        started_at,
+        ended_at: Utc::now(),
+        status,
+        output: output.map(|output| excerpt(&output)),
+    };
+    record(&run).await;
+    run
+}
+
+async fn record(run: &CronRun) {
+    if let Err(e) = cron_history().record_cron_run(run).await {
+        warn!("[{}] failed to record cron run: {}", run.pipe_id, e);
+    }
+}
+
+/// Output of a successful run, errors describe the failure.
+async fn run_job(job: &CronJob) -> Result<Option<String>> {
+    let context = &job.context;
+    if let Some(handler) = &context.handler {
+        return handler.run(&job.cron).await;
+    }
+    if let Some(path) = &job.cron.path {
+        let base_url = context
+            .base_url
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("pipe is not running or serves no http"))?;
+        let response = reqwest::Client::new()
+            .get(format!("{}{}", base_url, path))
+            .header(AUTHORIZATION, format!("Bearer {}", context.secret))
+            .send()
+            .await?;
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        if !status.is_success() {
+            anyhow::bail!("{} returned {}: {}", path, status, excerpt(&body));
+        }
+        return Ok(Some(body).filter(|body| !body.is_empty()));
+    }
+
+    let script = job
+        .cron
+        .script
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("cron job has neither a path nor a script"))?;
+    let (mut command, runtime_path) = context
+        .runtime
+        .command(&context.pipe_dir, &context.pipe_dir.join(script))?;
+    command
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        // a timed out run is dropped, which has to end the script too
+        .kill_on_drop(true);
+    let sandbox = PipeSandbox::prepare(
+        &context.pipe,
+        &context.pipe_dir,
+        &runtime_path,
+        context.permissions.clone(),
+        context.env.clone(),
+    )
+    .await?;
+    let (child, _sandbox) = sandbox.spawn_oneshot(&mut command)?;
+
+    let result = child.wait_with_output().await?;
+    let mut output = String::from_utf8_lossy(&result.stdout).into_owned();
+    output.push_str(&String::from_utf8_lossy(&result.stderr));
+    if !result.status.success() {
+        anyhow::bail!(
+            "{} failed with {}: {}",
+            script.display(),
+            result.status,
+            excerpt(&output)
+        );
+    }
+    Ok(Some(output).filter(|output| !output.is_empty()))
+}
+
+/// The last [`CRON_OUTPUT_EXCERPT_BYTES`] of `output`.
+fn excerpt(output: &str) -> String {
+    let output = output.trim_end();
+    if output.len() <= CRON_OUTPUT_EXCERPT_BYTES {
+        return output.to_string();
+    }
+    let mut start = output.len() - CRON_OUTPUT_EXCERPT_BYTES;
+    while !output.is_char_boundary(start) {
+        start += 1;
+    }
+    format!("…{}", &output[start..])
+}
+
+/// When `job` of the pipe in `pipe_dir` last started, kept across restarts.
+pub async fn get_last_cron_execution(pipe_dir: &Path, job: &str) -> Result<Option<SystemTime>> {
+    let state_file = pipe_dir.join(".cron_state.json");
+
+    if !state_file.exists() {
+        return Ok(None);
+    }
+
+    let content = tokio::fs::read_to_string(state_file).await?;
+    let state: Value = serde_json::from_str(&content)?;
+
+    if let Some(last_run) = state.get(job).and_then(|v| v.as_u64()) {
+        Ok(Some(UNIX_EPOCH + Duration::from_secs(last_run)))
+    } else {
+        Ok(None)
+    }
+}
+
+pub async fn save_cron_execution(pipe_dir: &Path, job: &str) -> Result<()> {
+    let _guard = CRON_STATE_LOCK.lock().await;
+    let state_file = pipe_dir.join(".cron_state.json");
+
+    let mut state: Value = if state_file.exists() {
+        let content = tokio::fs::read_to_string(&state_file).await?;
+        serde_json::from_str(&content)?
+    } else {
+        json!({})
+    };
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    if let Some(obj) = state.as_object_mut() {
+        obj.insert(job.to_string(), json!(now));
+    }
+
+    // a crash mid-write must not leave a truncated state behind
+    let tmp_file = pipe_dir.join(".cron_state.json.tmp");
+    let mut file = tokio::fs::File::create(&tmp_file).await?;
+    file.write_all(serde_json::to_string_pretty(&state)?.as_bytes())
+        .await?;
+    file.sync_all().await?;
+    tokio::fs::rename(&tmp_file, &state_file).await?;
+    Ok(())
+}
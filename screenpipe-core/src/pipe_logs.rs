@@ -0,0 +1,333 @@
+//! Per-pipe logs: everything a pipe writes to stdout and stderr is kept as json lines in
+//! `<pipe dir>/logs/pipe.log`, rotated by size, and broadcast to live tails.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use tokio::fs::{self, File, OpenOptions};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::{broadcast, Mutex};
+use tracing::warn;
+
+pub const PIPE_LOG_DIR: &str = "logs";
+const PIPE_LOG_FILE: &str = "pipe.log";
+pub const DEFAULT_MAX_LOG_FILE_BYTES: u64 = 5 * 1024 * 1024;
+/// Rotated files kept next to the current one, `pipe.log.1` being the newest.
+const MAX_ROTATED_LOG_FILES: usize = 3;
+const DEFAULT_LOG_QUERY_LIMIT: usize = 1000;
+const LIVE_LOG_CAPACITY: usize = 1024;
+
+static LIVE_LOGS: Lazy<std::sync::Mutex<HashMap<String, broadcast::Sender<PipeLogRecord>>>> =
+    Lazy::new(|| std::sync::Mutex::new(HashMap::new()));
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PipeLogStream {
+    Stdout,
+    Stderr,
+}
+
+impl fmt::Display for PipeLogStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            PipeLogStream::Stdout => "stdout",
+            PipeLogStream::Stderr => "stderr",
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum PipeLogLevel {
+    Debug,
+    Info,
+    #[serde(alias = "warning")]
+    Warn,
+    Error,
+}
+
+impl PipeLogLevel {
+    /// Guesses the level of a line. Stdout is info, bun and next.js write most of their
+    /// progress to stderr so stderr is matched against known patterns and warn otherwise.
+    pub fn guess(stream: PipeLogStream, line: &str) -> Self {
+        const INFO_PATTERNS: &[&str] = &[
+            // development related
+            "download",
+            "task dev",
+            "$ next dev",
+            "ready started server",
+            "local:",
+            "webpack is configured",
+            "see instructions",
+            "https://nextjs.org",
+            "$ next start",
+            "[bun install]",
+            "saved lockfile",
+            "resolved, downloaded",
+            "installing",
+            "successfully installed",
+            "packages installed",
+            "fetching",
+            "resolving",
+            // frontend console patterns
+            "[log]",
+            "console.log",
+            "] ",
+            "›",
+            "<",
+            "warning:",
+            "render@",
+            "webpack",
+            "hmr",
+        ];
+        const ERROR_PATTERNS: &[&str] = &[
+            "typeerror:",
+            "referenceerror:",
+            "syntaxerror:",
+            "error:",
+            "uncaught",
+            "failed to compile",
+            "enoent",
+            "fatal",
+        ];
+
+        if stream == PipeLogStream::Stdout || line.trim().is_empty() || line.contains("console.") {
+            return PipeLogLevel::Info;
+        }
+
+        let line = line.to_lowercase();
+        if ERROR_PATTERNS.iter().any(|pattern| line.contains(pattern)) {
+            PipeLogLevel::Error
+        } else if INFO_PATTERNS.iter().any(|pattern| line.contains(pattern)) {
+            PipeLogLevel::Info
+        } else {
+            PipeLogLevel::Warn
+        }
+    }
+}
+
+impl fmt::Display for PipeLogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self {
+            PipeLogLevel::Debug => "debug",
+            PipeLogLevel::Info => "info",
+            PipeLogLevel::Warn => "warn",
+            PipeLogLevel::Error => "error",
+        };
+        f.pad(level)
+    }
+}
+
+impl FromStr for PipeLogLevel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "debug" => Ok(PipeLogLevel::Debug),
+            "info" => Ok(PipeLogLevel::Info),
+            "warn" | "warning" => Ok(PipeLogLevel::Warn),
+            "error" => Ok(PipeLogLevel::Error),
+            _ => Err(anyhow::anyhow!("unknown log level: {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct PipeLogRecord {
+    pub timestamp: DateTime<Utc>,
+    pub stream: PipeLogStream,
+    pub level: PipeLogLevel,
+    pub message: String,
+}
+
+/// Filters for reading and tailing pipe logs.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PipeLogQuery {
+    /// Only records after this time.
+    pub since: Option<DateTime<Utc>>,
+    /// Only records at or above this level.
+    pub level: Option<PipeLogLevel>,
+    /// Most recent records to return, 1000 when unset.
+    pub limit: Option<usize>,
+}
+
+impl PipeLogQuery {
+    pub fn matches(&self, record: &PipeLogRecord) -> bool {
+        self.since.is_none_or(|since| record.timestamp > since)
+            && self.level.is_none_or(|level| record.level >= level)
+    }
+}
+
+/// Receives the records of `pipe` as they are written. Works before the pipe is started, the
+/// tail picks up once it runs.
+pub fn subscribe_pipe_logs(pipe: &str) -> broadcast::Receiver<PipeLogRecord> {
+    live_logs(pipe).subscribe()
+}
+
+fn live_logs(pipe: &str) -> broadcast::Sender<PipeLogRecord> {
+    LIVE_LOGS
+        .lock()
+        .unwrap()
+        .entry(pipe.to_string())
+        .or_insert_with(|| broadcast::channel(LIVE_LOG_CAPACITY).0)
+        .clone()
+}
+
+pub fn pipe_log_dir(pipe_dir: &Path) -> PathBuf {
+    pipe_dir.join(PIPE_LOG_DIR)
+}
+
+struct LogFile {
+    dir: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+}
+
+impl LogFile {
+    async fn open(dir: PathBuf, max_size: u64) -> Result<Self> {
+        fs::create_dir_all(&dir).await?;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(PIPE_LOG_FILE))
+            .await?;
+        let size = file.metadata().await?.len();
+        Ok(LogFile {
+            dir,
+            file,
+            size,
+            max_size,
+        })
+    }
+
+    async fn append(&mut self, line: &[u8]) -> Result<()> {
+        if self.size > 0 && self.size + line.len() as u64 > self.max_size {
+            self.rotate().await?;
+        }
+        self.file.write_all(line).await?;
+        // tokio buffers the write otherwise, readers should see every line written so far
+        self.file.flush().await?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    /// pipe.log becomes pipe.log.1, pipe.log.1 becomes pipe.log.2 and so on, dropping the oldest.
+    async fn rotate(&mut self) -> Result<()> {
+        self.file.flush().await?;
+        for index in (1..MAX_ROTATED_LOG_FILES).rev() {
+            let from = self.dir.join(format!("{}.{}", PIPE_LOG_FILE, index));
+            if fs::try_exists(&from).await.unwrap_or(false) {
+                fs::rename(
+                    &from,
+                    self.dir.join(format!("{}.{}", PIPE_LOG_FILE, index + 1)),
+                )
+                .await?;
+            }
+        }
+        fs::rename(
+            self.dir.join(PIPE_LOG_FILE),
+            self.dir.join(format!("{}.1", PIPE_LOG_FILE)),
+        )
+        .await?;
+
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(PIPE_LOG_FILE))
+            .await?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+/// Writes the output of one pipe to its log files and live tails.
+#[derive(Clone)]
+pub struct PipeLogger {
+    file: Arc<Mutex<LogFile>>,
+    live: broadcast::Sender<PipeLogRecord>,
+}
+
+impl PipeLogger {
+    pub async fn open(pipe: &str, pipe_dir: &Path) -> Result<Self> {
+        Self::open_with_max_size(pipe, pipe_dir, DEFAULT_MAX_LOG_FILE_BYTES).await
+    }
+
+    /// Like `open`, rotating once the current file would grow past `max_file_bytes`.
+    pub async fn open_with_max_size(
+        pipe: &str,
+        pipe_dir: &Path,
+        max_file_bytes: u64,
+    ) -> Result<Self> {
+        let file = LogFile::open(pipe_log_dir(pipe_dir), max_file_bytes).await?;
+        Ok(PipeLogger {
+            file: Arc::new(Mutex::new(file)),
+            live: live_logs(pipe),
+        })
+    }
+
+    pub async fn log(&self, stream: PipeLogStream, level: PipeLogLevel, message: &str) {
+        let record = PipeLogRecord {
+            timestamp: Utc::now(),
+            stream,
+            level,
+            message: message.to_string(),
+        };
+
+        match serde_json::to_vec(&record) {
+            Ok(mut line) => {
+                line.push(b'\n');
+                if let Err(e) = self.file.lock().await.append(&line).await {
+                    warn!("failed to write pipe log: {}", e);
+                }
+            }
+            Err(e) => warn!("failed to serialize pipe log: {}", e),
+        }
+
+        // nobody tailing is fine
+        let _ = self.live.send(record);
+    }
+}
+
+/// Reads the records of the pipe in `pipe_dir` matching `query`, oldest first.
+pub async fn read_pipe_logs(pipe_dir: &Path, query: &PipeLogQuery) -> Result<Vec<PipeLogRecord>> {
+    let dir = pipe_log_dir(pipe_dir);
+    let limit = query.limit.unwrap_or(DEFAULT_LOG_QUERY_LIMIT);
+    let mut records = VecDeque::new();
+
+    let files = (1..=MAX_ROTATED_LOG_FILES)
+        .rev()
+        .map(|index| dir.join(format!("{}.{}", PIPE_LOG_FILE, index)))
+        .chain(std::iter::once(dir.join(PIPE_LOG_FILE)));
+    for path in files {
+        let file = match File::open(&path).await {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut lines = BufReader::new(file).lines();
+        while let Some(line) = lines.next_line().await? {
+            // a line cut short by a crash is skipped rather than failing the whole read
+            let Ok(record) = serde_json::from_str::<PipeLogRecord>(&line) else {
+                continue;
+            };
+            if !query.matches(&record) {
+                continue;
+            }
+            records.push_back(record);
+            if records.len() > limit {
+                records.pop_front();
+            }
+        }
+    }
+
+    Ok(records.into())
+}
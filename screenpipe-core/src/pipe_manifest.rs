@@ -0,0 +1,481 @@
+//! Typed `pipe.json`.
+//!
+//! The manifest is written by pipe authors (fields, crons, entrypoints, permissions) and
+//! updated by screenpipe at runtime (enabled, port, field values). It's checked against
+//! [`PIPE_MANIFEST_SCHEMA`] whenever a pipe is installed, updated or started.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::pipe_runtime::PipeRuntime;
+use crate::pipe_sandbox::PipePermissions;
+use crate::pipe_secrets::validate_secret_name;
+
+/// Newest manifest format this version of screenpipe understands.
+pub const PIPE_MANIFEST_VERSION: u32 = 1;
+
+/// JSON Schema of `pipe.json`, pipe authors can point `$schema` at a copy of it.
+pub const PIPE_MANIFEST_SCHEMA: &str = include_str!("pipe.schema.json");
+
+static SCHEMA_VALIDATOR: Lazy<jsonschema::Validator> = Lazy::new(|| {
+    let schema: Value =
+        serde_json::from_str(PIPE_MANIFEST_SCHEMA).expect("pipe manifest schema is valid json");
+    jsonschema::validator_for(&schema).expect("pipe manifest schema is a valid json schema")
+});
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct PipeManifest {
+    #[serde(default = "legacy_manifest_version")]
+    pub manifest_version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Version of the pipe itself.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_screenpipe_version: Option<String>,
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_nextjs: bool,
+    /// What runs the main module and cron scripts.
+    #[serde(default, skip_serializing_if = "PipeRuntime::is_default")]
+    pub runtime: PipeRuntime,
+    #[serde(default, skip_serializing_if = "PipeEntrypoints::is_empty")]
+    pub entrypoints: PipeEntrypoints,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<PipeField>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub crons: Vec<PipeCron>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub permissions: Option<PipePermissions>,
+    #[serde(default, skip_serializing_if = "RestartConfig::is_default")]
+    pub restart: RestartConfig,
+    /// Http probe for pipes serving on a port, only the process is watched without it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub health_check: Option<HealthCheckConfig>,
+    /// Keys screenpipe doesn't know about, kept as they are.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+fn legacy_manifest_version() -> u32 {
+    1
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct PipeEntrypoints {
+    /// Main module run with the pipe's runtime (the program itself for executables),
+    /// relative to the pipe directory.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub main: Option<PathBuf>,
+    /// `package.json` script serving a built next.js pipe.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start: Option<String>,
+    /// `package.json` script used when the next.js build fails.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dev: Option<String>,
+}
+
+impl PipeEntrypoints {
+    pub fn is_empty(&self) -> bool {
+        *self == PipeEntrypoints::default()
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct PipeField {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub field_type: PipeFieldType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<Value>,
+    /// Value set by the user, `default` applies when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<Value>,
+    /// The value is kept in the secrets store instead of `pipe.json`, see `pipe_secrets`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub secret: bool,
+}
+
+impl PipeField {
+    pub fn current_value(&self) -> Option<&Value> {
+        self.value
+            .as_ref()
+            .filter(|value| !value.is_null())
+            .or(self.default.as_ref())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum PipeFieldType {
+    String,
+    Text,
+    Number,
+    Boolean,
+    /// `HH:MM`
+    Time,
+    Window,
+    App,
+    /// One of `all`, `ocr`, `audio`, `ui`.
+    ContentType,
+    Path,
+}
+
+/// When the supervisor starts a pipe again after its process exited.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    Never,
+    /// Restart after a non-zero exit, a failed start or a failing health check.
+    #[default]
+    OnFailure,
+    Always,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct RestartConfig {
+    pub policy: RestartPolicy,
+    /// Restarts allowed within `window_secs` before the pipe is marked as crashed.
+    pub max_restarts: u32,
+    pub window_secs: u64,
+    /// Delay before the first restart, doubled for every restart in a row.
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+}
+
+impl Default for RestartConfig {
+    fn default() -> Self {
+        RestartConfig {
+            policy: RestartPolicy::default(),
+            max_restarts: 5,
+            window_secs: 600,
+            initial_backoff_ms: 1000,
+            max_backoff_ms: 60_000,
+        }
+    }
+}
+
+impl RestartConfig {
+    pub fn is_default(&self) -> bool {
+        *self == RestartConfig::default()
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct HealthCheckConfig {
+    /// Route probed with a GET, any 2xx response counts as healthy.
+    pub path: String,
+    #[serde(default = "default_health_interval_secs")]
+    pub interval_secs: u64,
+    #[serde(default = "default_health_timeout_secs")]
+    pub timeout_secs: u64,
+    /// Failed probes in a row before the pipe is restarted.
+    #[serde(default = "default_health_failure_threshold")]
+    pub failure_threshold: u32,
+    /// Time the pipe gets to come up before the first probe.
+    #[serde(default = "default_health_initial_delay_secs")]
+    pub initial_delay_secs: u64,
+}
+
+fn default_health_interval_secs() -> u64 {
+    30
+}
+
+fn default_health_timeout_secs() -> u64 {
+    5
+}
+
+fn default_health_failure_threshold() -> u32 {
+    3
+}
+
+fn default_health_initial_delay_secs() -> u64 {
+    30
+}
+
+/// A scheduled job, either an http route of the running pipe or a script run on its own.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct PipeCron {
+    /// Route of the pipe called on schedule.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// Script run with the pipe's runtime in a fresh process, relative to the pipe directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script: Option<PathBuf>,
+    /// Cron expression with seconds.
+    pub schedule: String,
+    /// Runs taking longer are cancelled, 300 when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+    #[serde(default, skip_serializing_if = "CronOverlap::is_default")]
+    pub overlap: CronOverlap,
+}
+
+impl PipeCron {
+    /// Name of the job in the run history: its route or script.
+    pub fn job(&self) -> String {
+        match (&self.path, &self.script) {
+            (Some(path), _) => path.clone(),
+            (None, Some(script)) => script.to_string_lossy().into_owned(),
+            (None, None) => String::new(),
+        }
+    }
+}
+
+/// What happens when a job is due while its previous run is still going.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CronOverlap {
+    /// The new run is recorded as skipped.
+    #[default]
+    Skip,
+    /// The new run waits for the previous one.
+    Queue,
+    /// Both run at the same time.
+    Allow,
+}
+
+impl CronOverlap {
+    fn is_default(&self) -> bool {
+        *self == CronOverlap::Skip
+    }
+}
+
+fn is_inside_pipe_dir(path: &Path) -> bool {
+    !path.is_absolute() && !path.components().any(|c| c.as_os_str() == "..")
+}
+
+/// One problem found in a `pipe.json`.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ManifestIssue {
+    /// JSON pointer to the offending value, empty for the whole manifest.
+    pub path: String,
+    pub message: String,
+}
+
+impl ManifestIssue {
+    fn new(path: impl Into<String>, message: impl Into<String>) -> Self {
+        ManifestIssue {
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ManifestIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+/// A `pipe.json` that doesn't match the manifest schema.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ManifestError {
+    pub issues: Vec<ManifestIssue>,
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid pipe.json")?;
+        for (i, issue) in self.issues.iter().enumerate() {
+            write!(f, "{} {}", if i == 0 { ":" } else { ";" }, issue)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ManifestError {}
+
+impl PipeManifest {
+    /// Checks `config` against the schema and returns the typed manifest, collecting every
+    /// problem instead of stopping at the first one.
+    pub fn validate(config: &Value) -> Result<Self, ManifestError> {
+        let mut issues: Vec<ManifestIssue> = SCHEMA_VALIDATOR
+            .iter_errors(config)
+            .map(|error| ManifestIssue::new(error.instance_path.to_string(), error.to_string()))
+            .collect();
+        if !issues.is_empty() {
+            return Err(ManifestError { issues });
+        }
+
+        let manifest: PipeManifest =
+            serde_json::from_value(config.clone()).map_err(|e| ManifestError {
+                issues: vec![ManifestIssue::new("", e.to_string())],
+            })?;
+
+        if manifest.manifest_version > PIPE_MANIFEST_VERSION {
+            issues.push(ManifestIssue::new(
+                "/manifest_version",
+                format!(
+                    "manifest version {} needs a newer screenpipe, this one supports up to {}",
+                    manifest.manifest_version, PIPE_MANIFEST_VERSION
+                ),
+            ));
+        }
+
+        if let Some(required) = &manifest.min_screenpipe_version {
+            let current = semver::Version::parse(env!("CARGO_PKG_VERSION"))
+                .expect("crate version is valid semver");
+            match semver::Version::parse(required) {
+                Ok(required) if required > current => issues.push(ManifestIssue::new(
+                    "/min_screenpipe_version",
+                    format!(
+                        "pipe requires screenpipe {} or newer, this is {}",
+                        required, current
+                    ),
+                )),
+                Ok(_) => {}
+                Err(e) => issues.push(ManifestIssue::new(
+                    "/min_screenpipe_version",
+                    format!("{:?} is not a valid version: {}", required, e),
+                )),
+            }
+        }
+
+        let mut names = HashSet::new();
+        for (i, field) in manifest.fields.iter().enumerate() {
+            if !names.insert(field.name.as_str()) {
+                issues.push(ManifestIssue::new(
+                    format!("/fields/{}/name", i),
+                    format!("duplicate field {:?}", field.name),
+                ));
+            }
+            if field.secret {
+                if !matches!(
+                    field.field_type,
+                    PipeFieldType::String | PipeFieldType::Text
+                ) {
+                    issues.push(ManifestIssue::new(
+                        format!("/fields/{}/secret", i),
+                        "only string and text fields can be secret",
+                    ));
+                }
+                if let Err(e) = validate_secret_name(&field.name) {
+                    issues.push(ManifestIssue::new(
+                        format!("/fields/{}/name", i),
+                        e.to_string(),
+                    ));
+                }
+            }
+        }
+
+        for (i, cron) in manifest.crons.iter().enumerate() {
+            if let Err(e) = cron::Schedule::from_str(&cron.schedule) {
+                issues.push(ManifestIssue::new(
+                    format!("/crons/{}/schedule", i),
+                    format!("{:?} is not a valid cron expression: {}", cron.schedule, e),
+                ));
+            }
+        }
+
+        let mut jobs = HashSet::new();
+        for (i, cron) in manifest.crons.iter().enumerate() {
+            if let Some(script) = &cron.script {
+                if !is_inside_pipe_dir(script) {
+                    issues.push(ManifestIssue::new(
+                        format!("/crons/{}/script", i),
+                        "must be a path inside the pipe directory",
+                    ));
+                }
+            }
+            if !jobs.insert(cron.job()) {
+                issues.push(ManifestIssue::new(
+                    format!("/crons/{}", i),
+                    format!("duplicate cron job {:?}", cron.job()),
+                ));
+            }
+        }
+
+        if let Some(main) = &manifest.entrypoints.main {
+            if !is_inside_pipe_dir(main) {
+                issues.push(ManifestIssue::new(
+                    "/entrypoints/main",
+                    "must be a path inside the pipe directory",
+                ));
+            }
+        }
+
+        if issues.is_empty() {
+            Ok(manifest)
+        } else {
+            Err(ManifestError { issues })
+        }
+    }
+
+    /// Reads and validates the manifest at `path`.
+    pub async fn load(path: &Path) -> Result<Self> {
+        let content = tokio::fs::read_to_string(path).await?;
+        let config: Value = serde_json::from_str(&content).map_err(|e| ManifestError {
+            issues: vec![ManifestIssue::new("", e.to_string())],
+        })?;
+        Ok(Self::validate(&config)?)
+    }
+
+    /// Reads and validates the `pipe.json` of `pipe_dir`, `None` when there is none.
+    pub async fn load_from_dir(pipe_dir: &Path) -> Result<Option<Self>> {
+        let path = pipe_dir.join("pipe.json");
+        if !path.exists() {
+            return Ok(None);
+        }
+        Self::load(&path).await.map(Some)
+    }
+
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        tokio::fs::write(path, serde_json::to_string_pretty(self)?).await?;
+        Ok(())
+    }
+
+    pub fn field(&self, name: &str) -> Option<&PipeField> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+}
+
+impl Default for PipeManifest {
+    fn default() -> Self {
+        PipeManifest {
+            manifest_version: PIPE_MANIFEST_VERSION,
+            id: None,
+            name: None,
+            description: None,
+            version: None,
+            min_screenpipe_version: None,
+            enabled: false,
+            source: None,
+            port: None,
+            is_nextjs: false,
+            runtime: PipeRuntime::default(),
+            entrypoints: PipeEntrypoints::default(),
+            fields: Vec::new(),
+            crons: Vec::new(),
+            permissions: None,
+            restart: RestartConfig::default(),
+            health_check: None,
+            extra: Map::new(),
+        }
+    }
+}
@@ -0,0 +1,219 @@
+//! What runs a pipe, picked with `runtime` in `pipe.json`: bun (the default, also the only
+//! runtime of next.js pipes), deno, python in a virtualenv made from the pipe's
+//! `requirements.txt`, an executable shipped with the pipe, or a wasm module.
+//!
+//! Whatever the runtime, pipes are started with the same environment and go through the
+//! same sandbox, supervision, crons and logs. Wasm pipes are the exception: they run inside
+//! `screenpipe-server` instead of a process, confined by the host functions they are given.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+use tracing::{debug, info};
+use which::which;
+
+use crate::pipes::{find_bun_path, retry_install};
+
+/// Virtualenv of python pipes, inside the pipe directory.
+pub const PYTHON_VENV_DIR: &str = ".venv";
+/// Key-value data of wasm pipes, inside the pipe directory.
+pub const PIPE_KV_FILE: &str = ".kv.json";
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum PipeRuntime {
+    #[default]
+    Bun,
+    Deno,
+    Python,
+    /// `entrypoints.main` is run as it is.
+    Executable,
+    /// Run inside `screenpipe-server`, see its `pipe_wasm` module.
+    Wasm,
+}
+
+impl PipeRuntime {
+    pub fn is_default(&self) -> bool {
+        *self == PipeRuntime::Bun
+    }
+
+    /// Main modules looked for when `entrypoints.main` is unset, in order.
+    fn default_mains(self) -> &'static [&'static str] {
+        match self {
+            PipeRuntime::Bun => &["pipe.js", "pipe.ts"],
+            PipeRuntime::Deno => &["pipe.ts", "pipe.js", "main.ts"],
+            PipeRuntime::Python => &["pipe.py", "main.py"],
+            PipeRuntime::Executable => &["pipe", "pipe.exe"],
+            PipeRuntime::Wasm => &["pipe.wasm"],
+        }
+    }
+
+    /// The main module of the pipe in `pipe_dir`, `main` when the manifest names one.
+    pub fn find_main(self, pipe_dir: &Path, main: Option<&Path>) -> Result<PathBuf> {
+        if let Some(main) = main {
+            return Ok(pipe_dir.join(main));
+        }
+        self.default_mains()
+            .iter()
+            .map(|name| pipe_dir.join(name))
+            .find(|path| path.is_file())
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "no {} found in the pipe directory for the {} runtime",
+                    self.default_mains().join("/"),
+                    self
+                )
+            })
+    }
+
+    /// Command running `script` of the pipe in `pipe_dir`, and the binary it starts, which
+    /// the sandbox lets the pipe read.
+    pub fn command(self, pipe_dir: &Path, script: &Path) -> Result<(Command, PathBuf)> {
+        let (mut command, program) = match self {
+            PipeRuntime::Bun => {
+                let bun_path = find_bun_path().ok_or_else(|| anyhow::anyhow!("bun not found"))?;
+                let mut command = Command::new(&bun_path);
+                command.arg("run").arg("--bun").arg(script);
+                (command, bun_path)
+            }
+            PipeRuntime::Deno => {
+                let deno_path =
+                    find_deno_path().ok_or_else(|| anyhow::anyhow!("deno not found"))?;
+                let mut command = Command::new(&deno_path);
+                // what the pipe may touch is up to the sandbox, not deno's permissions
+                command.arg("run").arg("--allow-all").arg(script);
+                (command, deno_path)
+            }
+            PipeRuntime::Python => {
+                let venv_python = venv_python(pipe_dir);
+                let python_path = if venv_python.exists() {
+                    venv_python
+                } else {
+                    find_python_path().ok_or_else(|| anyhow::anyhow!("python not found"))?
+                };
+                let mut command = Command::new(&python_path);
+                // unbuffered, so logs show up as they are written
+                command.arg("-u").arg(script);
+                (command, python_path)
+            }
+            PipeRuntime::Executable => {
+                if !script.is_file() {
+                    anyhow::bail!("executable {:?} not found", script);
+                }
+                (Command::new(script), script.to_path_buf())
+            }
+            PipeRuntime::Wasm => anyhow::bail!("wasm pipes run inside screenpipe-server"),
+        };
+        command.current_dir(pipe_dir);
+        Ok((command, program))
+    }
+
+    /// Installs what the pipe in `pipe_dir` depends on: `bun install` or `deno install` for a
+    /// `package.json` (or `deno.json`), a virtualenv with the packages of `requirements.txt`.
+    pub async fn install_dependencies(self, pipe_dir: &Path) -> Result<()> {
+        match self {
+            PipeRuntime::Bun => {
+                if !pipe_dir.join("package.json").exists() {
+                    return Ok(());
+                }
+                let bun_path = find_bun_path().ok_or_else(|| anyhow::anyhow!("bun not found"))?;
+                retry_install(&bun_path, pipe_dir, 3).await
+            }
+            PipeRuntime::Deno => {
+                if !["deno.json", "deno.jsonc", "package.json"]
+                    .iter()
+                    .any(|name| pipe_dir.join(name).exists())
+                {
+                    return Ok(());
+                }
+                let deno_path =
+                    find_deno_path().ok_or_else(|| anyhow::anyhow!("deno not found"))?;
+                run_install(Command::new(deno_path).arg("install"), pipe_dir).await
+            }
+            PipeRuntime::Python => {
+                let requirements = pipe_dir.join("requirements.txt");
+                if !requirements.exists() {
+                    return Ok(());
+                }
+                let venv_python = venv_python(pipe_dir);
+                if !venv_python.exists() {
+                    let python_path =
+                        find_python_path().ok_or_else(|| anyhow::anyhow!("python not found"))?;
+                    info!(
+                        "creating virtualenv in {:?}",
+                        pipe_dir.join(PYTHON_VENV_DIR)
+                    );
+                    run_install(
+                        Command::new(python_path)
+                            .args(["-m", "venv"])
+                            .arg(PYTHON_VENV_DIR),
+                        pipe_dir,
+                    )
+                    .await?;
+                }
+                run_install(
+                    Command::new(venv_python)
+                        .args(["-m", "pip", "install", "--disable-pip-version-check", "-r"])
+                        .arg(requirements),
+                    pipe_dir,
+                )
+                .await
+            }
+            PipeRuntime::Executable | PipeRuntime::Wasm => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for PipeRuntime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            PipeRuntime::Bun => "bun",
+            PipeRuntime::Deno => "deno",
+            PipeRuntime::Python => "python",
+            PipeRuntime::Executable => "executable",
+            PipeRuntime::Wasm => "wasm",
+        };
+        f.write_str(name)
+    }
+}
+
+async fn run_install(command: &mut Command, pipe_dir: &Path) -> Result<()> {
+    debug!("running {:?} in {:?}", command, pipe_dir);
+    let output = command.current_dir(pipe_dir).output().await?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "installing dependencies failed with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        );
+    }
+    Ok(())
+}
+
+fn venv_python(pipe_dir: &Path) -> PathBuf {
+    let venv = pipe_dir.join(PYTHON_VENV_DIR);
+    if cfg!(windows) {
+        venv.join("Scripts").join("python.exe")
+    } else {
+        venv.join("bin").join("python")
+    }
+}
+
+fn find_deno_path() -> Option<PathBuf> {
+    which("deno").ok().or_else(|| {
+        let installed = dirs::home_dir()?
+            .join(".deno")
+            .join("bin")
+            .join(if cfg!(windows) { "deno.exe" } else { "deno" });
+        installed.exists().then_some(installed)
+    })
+}
+
+fn find_python_path() -> Option<PathBuf> {
+    ["python3", "python"]
+        .iter()
+        .find_map(|name| which(name).ok())
+}
@@ -0,0 +1,1141 @@
+//! Isolation for pipe processes.
+//!
+//! Every pipe gets an explicit environment instead of a copy of ours and is confined on
+//! linux: landlock restricts the filesystem to `PIPE_DIR`, read-only system paths and its
+//! own `/proc/<pid>`. The `permissions` block of `pipe.json` adds the declared paths, limits
+//! tcp connections to the ports of the allowlist proxy and allowed loopback services, caps
+//! its resources with cgroups v2 and its open files with rlimits.
+//!
+//! ```json
+//! "permissions": {
+//!     "env": ["OPENAI_API_KEY"],
+//!     "read": ["~/Documents/notes"],
+//!     "write": ["~/Documents/notes/screenpipe"],
+//!     "network": ["api.openai.com", "localhost:3030"],
+//!     "limits": { "memory_mb": 512, "cpu_percent": 50, "max_processes": 64 }
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::process::Command;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+/// Host variables every pipe gets, everything else has to be listed in `permissions.env`.
+const BASE_ENV: &[&str] = &[
+    "PATH",
+    "HOME",
+    "USER",
+    "LANG",
+    "LC_ALL",
+    "TZ",
+    "TERM",
+    // needed by bun and node on windows
+    "SYSTEMROOT",
+    "WINDIR",
+    "COMSPEC",
+    "PATHEXT",
+    "USERPROFILE",
+    "APPDATA",
+    "LOCALAPPDATA",
+];
+
+/// Permissions a pipe declares in the `permissions` block of its `pipe.json`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct PipePermissions {
+    /// Captured content the pipe reads through the api.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub content_types: Vec<ContentPermission>,
+    /// Whether the pipe runs raw sql against the database.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub raw_sql: bool,
+    /// Whether the pipe drives the keyboard and mouse.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub input_control: bool,
+    /// Host environment variables passed through to the pipe.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub env: Vec<String>,
+    /// Paths the pipe may read besides its own directory.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub read: Vec<PathBuf>,
+    /// Paths the pipe may read and write besides its own directory.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub write: Vec<PathBuf>,
+    /// `host` or `host:port` entries the pipe may connect to, `*` allows any host.
+    /// Unset leaves the network unrestricted, except for wasm pipes which get none.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<Vec<String>>,
+    /// Events a wasm pipe may subscribe to and send.
+    #[serde(skip_serializing_if = "EventPermissions::is_empty")]
+    pub events: EventPermissions,
+    /// Whether a wasm pipe keeps key-value data.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub storage: bool,
+    #[serde(skip_serializing_if = "ResourceLimits::is_empty")]
+    pub limits: ResourceLimits,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentPermission {
+    All,
+    Ocr,
+    Audio,
+    Ui,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ResourceLimits {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_mb: Option<u64>,
+    /// Share of one cpu core, 200 allows two full cores.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_percent: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_processes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_open_files: Option<u64>,
+    /// Instructions a wasm pipe may run per call into it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fuel: Option<u64>,
+}
+
+impl ResourceLimits {
+    pub fn is_empty(&self) -> bool {
+        *self == ResourceLimits::default()
+    }
+}
+
+/// Event names, `*` matches any event.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct EventPermissions {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub subscribe: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub send: Vec<String>,
+}
+
+impl EventPermissions {
+    pub fn is_empty(&self) -> bool {
+        self.subscribe.is_empty() && self.send.is_empty()
+    }
+
+    pub fn can_subscribe(&self, event: &str) -> bool {
+        matches_event(&self.subscribe, event)
+    }
+
+    pub fn can_send(&self, event: &str) -> bool {
+        matches_event(&self.send, event)
+    }
+}
+
+fn matches_event(names: &[String], event: &str) -> bool {
+    names.iter().any(|name| name == "*" || name == event)
+}
+
+impl PipePermissions {
+    /// Reads the `permissions` block of a `pipe.json`, `None` when the pipe declares none.
+    pub fn from_pipe_config(config: &Value) -> Result<Option<Self>> {
+        match config.get("permissions") {
+            None | Some(Value::Null) => Ok(None),
+            Some(permissions) => {
+                let permissions: PipePermissions = serde_json::from_value(permissions.clone())
+                    .map_err(|e| anyhow::anyhow!("invalid permissions in pipe.json: {}", e))?;
+                Ok(Some(permissions.with_expanded_paths()))
+            }
+        }
+    }
+
+    /// Resolves `~` in the declared paths to the home directory.
+    pub fn with_expanded_paths(mut self) -> Self {
+        self.read = self.read.iter().map(|p| expand_home(p)).collect();
+        self.write = self.write.iter().map(|p| expand_home(p)).collect();
+        self
+    }
+
+    /// Whether the network allowlist has an entry for `host:port`, false without an
+    /// allowlist.
+    pub fn allowlist_allows(&self, host: &str, port: u16) -> bool {
+        self.network
+            .iter()
+            .flatten()
+            .any(|entry| HostRule::parse(entry).allows(host, port))
+    }
+}
+
+/// How a restriction ended up being applied to a running pipe.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Enforcement {
+    /// Nothing was requested.
+    Unrestricted,
+    Enforced {
+        by: String,
+    },
+    /// Requested but this system can't enforce it.
+    NotEnforced {
+        reason: String,
+    },
+}
+
+impl Enforcement {
+    fn enforced(by: &str) -> Self {
+        Enforcement::Enforced { by: by.to_string() }
+    }
+
+    fn not_enforced(reason: impl Into<String>) -> Self {
+        Enforcement::NotEnforced {
+            reason: reason.into(),
+        }
+    }
+}
+
+/// What the sandbox of a running pipe looks like, reported in the pipe info.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct SandboxReport {
+    pub permissions: Option<PipePermissions>,
+    /// Names of the environment variables the pipe was started with.
+    pub env: Vec<String>,
+    pub filesystem: Enforcement,
+    pub network: Enforcement,
+    pub memory: Enforcement,
+    pub cpu: Enforcement,
+    pub processes: Enforcement,
+    pub open_files: Enforcement,
+}
+
+struct SandboxState {
+    report: SandboxReport,
+    proxy: Option<JoinHandle<()>>,
+    cgroup: Option<PathBuf>,
+}
+
+static SANDBOXES: Lazy<Mutex<HashMap<String, SandboxState>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Sandbox of the last started instance of `pipe`.
+pub async fn get_sandbox_report(pipe: &str) -> Option<SandboxReport> {
+    SANDBOXES
+        .lock()
+        .await
+        .get(pipe)
+        .map(|state| state.report.clone())
+}
+
+/// Stops the network proxy of `pipe`, removes its cgroup and forgets its sandbox.
+pub async fn cleanup_pipe_sandbox(pipe: &str) {
+    if let Some(state) = SANDBOXES.lock().await.remove(pipe) {
+        state.release();
+    }
+}
+
+impl SandboxState {
+    fn release(self) {
+        if let Some(proxy) = self.proxy {
+            proxy.abort();
+        }
+        // only succeeds once every process of the pipe exited
+        if let Some(cgroup) = self.cgroup {
+            if let Err(e) = std::fs::remove_dir(&cgroup) {
+                debug!("failed to remove pipe cgroup {:?}: {}", cgroup, e);
+            }
+        }
+    }
+}
+
+/// Sandbox for one pipe process, prepared before spawning it.
+pub struct PipeSandbox {
+    pipe: String,
+    env: Vec<(String, String)>,
+    report: SandboxReport,
+    proxy: Option<JoinHandle<()>>,
+    #[cfg(target_os = "linux")]
+    confinement: linux::Confinement,
+}
+
+impl PipeSandbox {
+    /// Builds the environment and restrictions for `pipe`. `env` holds the screenpipe
+    /// variables (`PIPE_DIR`, `PORT`, ...), `runtime` the binary running the pipe.
+    pub async fn prepare(
+        pipe: &str,
+        pipe_dir: &Path,
+        runtime: &Path,
+        permissions: Option<PipePermissions>,
+        env: Vec<(String, String)>,
+    ) -> Result<Self> {
+        let tmp_dir = pipe_dir.join(".tmp");
+        tokio::fs::create_dir_all(&tmp_dir).await?;
+
+        let passthrough = permissions.iter().flat_map(|p| p.env.iter());
+        let mut env_vars: Vec<(String, String)> = BASE_ENV
+            .iter()
+            .map(|name| name.to_string())
+            .chain(passthrough.cloned())
+            .filter_map(|name| std::env::var(&name).ok().map(|value| (name, value)))
+            .collect();
+        for key in ["TMPDIR", "TEMP", "TMP"] {
+            env_vars.push((key.to_string(), tmp_dir.to_string_lossy().into_owned()));
+        }
+        env_vars.extend(env);
+
+        let mut report = SandboxReport {
+            permissions: permissions.clone(),
+            env: Vec::new(),
+            filesystem: Enforcement::Unrestricted,
+            network: Enforcement::Unrestricted,
+            memory: Enforcement::Unrestricted,
+            cpu: Enforcement::Unrestricted,
+            processes: Enforcement::Unrestricted,
+            open_files: Enforcement::Unrestricted,
+        };
+
+        // without a block the pipe only gets its own directory and the system paths, the
+        // network and its resources stay unrestricted
+        let permissions = permissions.unwrap_or_else(|| {
+            warn!(
+                "[{}] pipe.json declares no permissions, the pipe only gets its own directory",
+                pipe
+            );
+            PipePermissions::default()
+        });
+
+        // hosts other than loopback go through a local proxy enforcing the allowlist
+        let mut proxy = None;
+        let mut direct_ports = Vec::new();
+        if let Some(allow) = &permissions.network {
+            let rules: Vec<HostRule> = allow.iter().map(|entry| HostRule::parse(entry)).collect();
+            direct_ports = rules.iter().filter_map(HostRule::loopback_port).collect();
+            if rules.iter().any(|rule| !rule.is_loopback()) {
+                let listener = TcpListener::bind("127.0.0.1:0").await?;
+                let proxy_port = listener.local_addr()?.port();
+                info!(
+                    "[{}] network allowlist proxy listening on port {}",
+                    pipe, proxy_port
+                );
+                let proxy_url = format!("http://127.0.0.1:{}", proxy_port);
+                for key in ["HTTP_PROXY", "HTTPS_PROXY", "http_proxy", "https_proxy"] {
+                    env_vars.push((key.to_string(), proxy_url.clone()));
+                }
+                for key in ["NO_PROXY", "no_proxy"] {
+                    env_vars.push((key.to_string(), "localhost,127.0.0.1,::1".to_string()));
+                }
+                direct_ports.push(proxy_port);
+                proxy = Some(tokio::spawn(run_allowlist_proxy(
+                    pipe.to_string(),
+                    listener,
+                    Arc::new(rules),
+                )));
+            }
+        }
+        report.env = env_vars.iter().map(|(name, _)| name.clone()).collect();
+
+        #[cfg(target_os = "linux")]
+        let confinement = linux::Confinement::prepare(
+            pipe,
+            pipe_dir,
+            runtime,
+            &permissions,
+            &direct_ports,
+            &mut report,
+        );
+
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = (runtime, direct_ports);
+            let unsupported = || Enforcement::not_enforced("sandboxing is only supported on linux");
+            report.filesystem = unsupported();
+            if permissions.network.is_some() {
+                report.network = Enforcement::not_enforced(
+                    "only proxied, sandboxing is only supported on linux",
+                );
+            }
+            let limits = &permissions.limits;
+            if limits.memory_mb.is_some() {
+                report.memory = unsupported();
+            }
+            if limits.cpu_percent.is_some() {
+                report.cpu = unsupported();
+            }
+            if limits.max_processes.is_some() {
+                report.processes = unsupported();
+            }
+            if limits.max_open_files.is_some() {
+                report.open_files = unsupported();
+            }
+        }
+
+        Ok(Self {
+            pipe: pipe.to_string(),
+            env: env_vars,
+            report,
+            proxy,
+            #[cfg(target_os = "linux")]
+            confinement,
+        })
+    }
+
+    pub fn env(&self) -> &[(String, String)] {
+        &self.env
+    }
+
+    pub fn report(&self) -> &SandboxReport {
+        &self.report
+    }
+
+    /// Spawns `command` inside the sandbox, replacing its environment.
+    pub async fn spawn(self, command: &mut Command) -> Result<tokio::process::Child> {
+        command.env_clear().envs(self.env.iter().cloned());
+
+        #[cfg(target_os = "linux")]
+        self.confinement.apply(command);
+        #[cfg(target_os = "linux")]
+        let cgroup = self.confinement.cgroup_path();
+        #[cfg(not(target_os = "linux"))]
+        let cgroup = None;
+
+        let child = command.spawn()?;
+
+        debug!("[{}] sandbox: {:?}", self.pipe, self.report);
+        let previous = SANDBOXES.lock().await.insert(
+            self.pipe.clone(),
+            SandboxState {
+                report: self.report,
+                proxy: self.proxy,
+                cgroup,
+            },
+        );
+        // a restarted pipe reuses its cgroup, only the old proxy has to go
+        if let Some(proxy) = previous.and_then(|state| state.proxy) {
+            proxy.abort();
+        }
+
+        Ok(child)
+    }
+
+    /// Spawns a short-lived process of the pipe, e.g. a cron script, without replacing the
+    /// sandbox reported for the pipe. Its proxy stops once the guard is dropped.
+    pub fn spawn_oneshot(
+        self,
+        command: &mut Command,
+    ) -> Result<(tokio::process::Child, SandboxGuard)> {
+        command.env_clear().envs(self.env.iter().cloned());
+
+        #[cfg(target_os = "linux")]
+        self.confinement.apply(command);
+
+        let child = command.spawn()?;
+        Ok((child, SandboxGuard(self.proxy)))
+    }
+}
+
+/// Keeps the network proxy of a one-off run alive, see [`PipeSandbox::spawn_oneshot`].
+pub struct SandboxGuard(Option<JoinHandle<()>>);
+
+impl Drop for SandboxGuard {
+    fn drop(&mut self) {
+        if let Some(proxy) = self.0.take() {
+            proxy.abort();
+        }
+    }
+}
+
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// One network allowlist entry.
+#[derive(Debug, Clone, PartialEq)]
+struct HostRule {
+    host: String,
+    port: Option<u16>,
+}
+
+impl HostRule {
+    fn parse(entry: &str) -> Self {
+        let entry = entry.trim().to_lowercase();
+        match entry.rsplit_once(':') {
+            Some((host, port)) if !host.ends_with(':') => match port.parse() {
+                Ok(port) => HostRule {
+                    host: host.trim_matches(['[', ']']).to_string(),
+                    port: Some(port),
+                },
+                Err(_) => HostRule {
+                    host: entry,
+                    port: None,
+                },
+            },
+            _ => HostRule {
+                host: entry,
+                port: None,
+            },
+        }
+    }
+
+    fn is_loopback(&self) -> bool {
+        matches!(self.host.as_str(), "localhost" | "127.0.0.1" | "::1")
+    }
+
+    /// Loopback entries are reached directly, without the proxy.
+    fn loopback_port(&self) -> Option<u16> {
+        self.port.filter(|_| self.is_loopback())
+    }
+
+    fn allows(&self, host: &str, port: u16) -> bool {
+        let host = host.to_lowercase();
+        let host_matches = match self.host.strip_prefix("*.") {
+            _ if self.host == "*" => true,
+            Some(domain) => host.ends_with(&format!(".{}", domain)),
+            None => host == self.host,
+        };
+        host_matches && self.port.is_none_or(|p| p == port)
+    }
+}
+
+/// Minimal http proxy for `HTTP(S)_PROXY`, tunnels `CONNECT` and forwards plain http
+/// requests to allowed hosts only.
+async fn run_allowlist_proxy(pipe: String, listener: TcpListener, rules: Arc<Vec<HostRule>>) {
+    loop {
+        let (client, _) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                warn!("[{}] network proxy accept failed: {}", pipe, e);
+                continue;
+            }
+        };
+        let pipe = pipe.clone();
+        let rules = rules.clone();
+        tokio::spawn(async move {
+            if let Err(e) = proxy_connection(&pipe, client, &rules).await {
+                debug!("[{}] network proxy connection failed: {}", pipe, e);
+            }
+        });
+    }
+}
+
+async fn proxy_connection(pipe: &str, mut client: TcpStream, rules: &[HostRule]) -> Result<()> {
+    // read the request head
+    let mut buf = Vec::with_capacity(4096);
+    let head_len = loop {
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        if buf.len() > 64 * 1024 {
+            anyhow::bail!("request head too large");
+        }
+        let mut chunk = [0u8; 4096];
+        let n = client.read(&mut chunk).await?;
+        if n == 0 {
+            anyhow::bail!("connection closed before request head");
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+
+    let head = String::from_utf8_lossy(&buf[..head_len]).into_owned();
+    let request_line = head.lines().next().unwrap_or_default();
+    let mut parts = request_line.split_whitespace();
+    let (method, target, version) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(target), Some(version)) => (method, target, version),
+        _ => anyhow::bail!("malformed request line: {}", request_line),
+    };
+
+    let (host, port, forwarded_head) = if method.eq_ignore_ascii_case("CONNECT") {
+        let (host, port) = target
+            .rsplit_once(':')
+            .and_then(|(host, port)| Some((host.to_string(), port.parse().ok()?)))
+            .ok_or_else(|| anyhow::anyhow!("invalid connect target: {}", target))?;
+        (host, port, None)
+    } else {
+        let url = url::Url::parse(target)?;
+        let host = url
+            .host_str()
+            .ok_or_else(|| anyhow::anyhow!("missing host in {}", target))?
+            .to_string();
+        let port = url.port_or_known_default().unwrap_or(80);
+        let path = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+        // upstream servers expect origin-form request targets
+        let rest = &head[request_line.len()..];
+        (
+            host,
+            port,
+            Some(format!("{} {} {}{}", method, path, version, rest)),
+        )
+    };
+
+    let host = host.trim_matches(['[', ']']);
+    if !rules.iter().any(|rule| rule.allows(host, port)) {
+        warn!(
+            "[{}] blocked connection to {}:{}, not in the pipe's network allowlist",
+            pipe, host, port
+        );
+        client
+            .write_all(b"HTTP/1.1 403 Forbidden\r\ncontent-length: 0\r\n\r\n")
+            .await?;
+        return Ok(());
+    }
+
+    let mut upstream = TcpStream::connect((host, port)).await?;
+    match forwarded_head {
+        None => {
+            client
+                .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")
+                .await?;
+        }
+        Some(forwarded_head) => {
+            upstream.write_all(forwarded_head.as_bytes()).await?;
+        }
+    }
+    upstream.write_all(&buf[head_len..]).await?;
+    tokio::io::copy_bidirectional(&mut client, &mut upstream).await?;
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::{Enforcement, PipePermissions, SandboxReport};
+    use once_cell::sync::Lazy;
+    use std::ffi::CString;
+    use std::io;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::{Path, PathBuf};
+    use tokio::process::Command;
+    use tracing::{debug, warn};
+
+    // landlock uapi, see linux/landlock.h
+    const CREATE_RULESET_VERSION: u32 = 1;
+    const RULE_PATH_BENEATH: u32 = 1;
+    const RULE_NET_PORT: u32 = 2;
+
+    const FS_EXECUTE: u64 = 1 << 0;
+    const FS_WRITE_FILE: u64 = 1 << 1;
+    const FS_READ_FILE: u64 = 1 << 2;
+    const FS_READ_DIR: u64 = 1 << 3;
+    const FS_REFER: u64 = 1 << 13;
+    const FS_TRUNCATE: u64 = 1 << 14;
+    const FS_IOCTL_DEV: u64 = 1 << 15;
+    /// Everything abi v1 handles.
+    const FS_ABI_V1: u64 = (1 << 13) - 1;
+    const FS_FILE_ONLY: u64 =
+        FS_EXECUTE | FS_WRITE_FILE | FS_READ_FILE | FS_TRUNCATE | FS_IOCTL_DEV;
+    const FS_READ: u64 = FS_EXECUTE | FS_READ_FILE | FS_READ_DIR;
+
+    const NET_CONNECT_TCP: u64 = 1 << 1;
+
+    #[repr(C)]
+    struct RulesetAttr {
+        handled_access_fs: u64,
+        handled_access_net: u64,
+    }
+
+    #[repr(C, packed)]
+    struct PathBeneathAttr {
+        allowed_access: u64,
+        parent_fd: i32,
+    }
+
+    #[repr(C)]
+    struct NetPortAttr {
+        allowed_access: u64,
+        port: u64,
+    }
+
+    /// Read-only system locations the runtime needs to start.
+    const SYSTEM_READ_PATHS: &[&str] = &[
+        "/usr",
+        "/lib",
+        "/lib64",
+        "/lib32",
+        "/bin",
+        "/sbin",
+        "/etc",
+        "/opt",
+        "/nix/store",
+        "/sys",
+        "/run",
+    ];
+
+    /// System-wide parts of /proc. The pid directories are left out so a pipe can't read
+    /// the environment or memory of other processes, its own is added in the child.
+    const PROC_READ_PATHS: &[&str] = &[
+        "/proc/cpuinfo",
+        "/proc/meminfo",
+        "/proc/stat",
+        "/proc/loadavg",
+        "/proc/uptime",
+        "/proc/version",
+        "/proc/filesystems",
+        "/proc/sys",
+    ];
+
+    /// Restrictions applied in the child between fork and exec.
+    #[derive(Default)]
+    pub(super) struct Confinement {
+        ruleset: Option<OwnedFd>,
+        /// Access to `/proc/self` of the child, only known after the fork.
+        proc_access: u64,
+        cgroup: Option<PathBuf>,
+        cgroup_procs: Option<CString>,
+        rlimit_nofile: Option<u64>,
+    }
+
+    impl Confinement {
+        pub(super) fn prepare(
+            pipe: &str,
+            pipe_dir: &Path,
+            runtime: &Path,
+            permissions: &PipePermissions,
+            direct_ports: &[u16],
+            report: &mut SandboxReport,
+        ) -> Self {
+            let mut confinement = Confinement::default();
+
+            match landlock_abi() {
+                Some(abi) => {
+                    match build_ruleset(abi, pipe_dir, runtime, permissions, direct_ports) {
+                        Ok(ruleset) => {
+                            confinement.ruleset = Some(ruleset);
+                            confinement.proc_access = FS_READ & handled_fs(abi);
+                            report.filesystem = Enforcement::enforced("landlock");
+                            if permissions.network.is_some() {
+                                // landlock only matches ports, any host is reachable on them
+                                report.network = if abi >= 4 {
+                                    Enforcement::enforced("landlock (ports only) + allowlist proxy")
+                                } else {
+                                    Enforcement::not_enforced(
+                                        "only proxied, landlock network rules need linux 6.7",
+                                    )
+                                };
+                            }
+                        }
+                        Err(e) => {
+                            warn!("[{}] failed to build landlock ruleset: {}", pipe, e);
+                            report.filesystem =
+                                Enforcement::not_enforced(format!("landlock failed: {}", e));
+                        }
+                    }
+                }
+                None => {
+                    report.filesystem =
+                        Enforcement::not_enforced("landlock is not available on this kernel");
+                }
+            }
+            if permissions.network.is_some() && confinement.ruleset.is_none() {
+                report.network = Enforcement::not_enforced("only proxied, landlock unavailable");
+            }
+
+            let limits = &permissions.limits;
+            let cgroup = Cgroup::create(
+                pipe,
+                limits.memory_mb,
+                limits.cpu_percent,
+                limits.max_processes,
+            );
+            if let Some(cgroup) = &cgroup {
+                confinement.cgroup = Some(cgroup.path.clone());
+                confinement.cgroup_procs =
+                    CString::new(cgroup.path.join("cgroup.procs").as_os_str().as_bytes()).ok();
+            }
+            let cgroup_has = |controller: &str| {
+                cgroup
+                    .as_ref()
+                    .is_some_and(|c| c.controllers.iter().any(|name| name == controller))
+            };
+
+            if limits.memory_mb.is_some() {
+                // RLIMIT_DATA is no substitute, js runtimes reserve far more address space
+                // than they use and fail to start under it
+                report.memory = if cgroup_has("memory") {
+                    Enforcement::enforced("cgroup")
+                } else {
+                    Enforcement::not_enforced(
+                        "needs a delegated cgroup v2 with the memory controller",
+                    )
+                };
+            }
+            if limits.cpu_percent.is_some() {
+                report.cpu = if cgroup_has("cpu") {
+                    Enforcement::enforced("cgroup")
+                } else {
+                    Enforcement::not_enforced("needs a delegated cgroup v2 with the cpu controller")
+                };
+            }
+            if limits.max_processes.is_some() {
+                report.processes = if cgroup_has("pids") {
+                    Enforcement::enforced("cgroup")
+                } else {
+                    Enforcement::not_enforced(
+                        "needs a delegated cgroup v2 with the pids controller",
+                    )
+                };
+            }
+            if let Some(max_open_files) = limits.max_open_files {
+                confinement.rlimit_nofile = Some(max_open_files);
+                report.open_files = Enforcement::enforced("rlimit");
+            }
+
+            confinement
+        }
+
+        pub(super) fn cgroup_path(&self) -> Option<PathBuf> {
+            self.cgroup.clone()
+        }
+
+        pub(super) fn apply(&self, command: &mut Command) {
+            let ruleset: Option<RawFd> = self.ruleset.as_ref().map(|fd| fd.as_raw_fd());
+            let proc_access = self.proc_access;
+            let cgroup_procs = self.cgroup_procs.clone();
+            let rlimit_nofile = self.rlimit_nofile;
+            if ruleset.is_none() && cgroup_procs.is_none() && rlimit_nofile.is_none() {
+                return;
+            }
+
+            // SAFETY: only async-signal-safe calls on memory prepared before the fork
+            unsafe {
+                command.pre_exec(move || {
+                    if let Some(path) = &cgroup_procs {
+                        join_cgroup(path)?;
+                    }
+                    if let Some(files) = rlimit_nofile {
+                        set_rlimit(libc::RLIMIT_NOFILE, files)?;
+                    }
+                    if let Some(fd) = ruleset {
+                        allow_own_proc(fd, proc_access)?;
+                        restrict_self(fd)?;
+                    }
+                    Ok(())
+                });
+            }
+        }
+    }
+
+    fn landlock_abi() -> Option<i64> {
+        // SAFETY: querying the abi version takes no attributes
+        let abi = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_create_ruleset,
+                std::ptr::null::<RulesetAttr>(),
+                0usize,
+                CREATE_RULESET_VERSION,
+            )
+        };
+        (abi > 0).then_some(abi)
+    }
+
+    fn handled_fs(abi: i64) -> u64 {
+        let mut handled = FS_ABI_V1;
+        if abi >= 2 {
+            handled |= FS_REFER;
+        }
+        if abi >= 3 {
+            handled |= FS_TRUNCATE;
+        }
+        if abi >= 5 {
+            handled |= FS_IOCTL_DEV;
+        }
+        handled
+    }
+
+    fn build_ruleset(
+        abi: i64,
+        pipe_dir: &Path,
+        runtime: &Path,
+        permissions: &PipePermissions,
+        direct_ports: &[u16],
+    ) -> io::Result<OwnedFd> {
+        let handled = handled_fs(abi);
+        let restrict_network = permissions.network.is_some() && abi >= 4;
+        let attr = RulesetAttr {
+            handled_access_fs: handled,
+            handled_access_net: if restrict_network { NET_CONNECT_TCP } else { 0 },
+        };
+        // abi < 4 kernels only know the fs field
+        let attr_size = if abi >= 4 {
+            std::mem::size_of::<RulesetAttr>()
+        } else {
+            std::mem::size_of::<u64>()
+        };
+        // SAFETY: attr outlives the call and attr_size never exceeds it
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_create_ruleset,
+                &attr as *const RulesetAttr,
+                attr_size,
+                0u32,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: the syscall returned a fresh fd we now own
+        let ruleset = unsafe { OwnedFd::from_raw_fd(fd as RawFd) };
+
+        let read = FS_READ & handled;
+        let write = handled;
+        let mut rules: Vec<(PathBuf, u64)> = SYSTEM_READ_PATHS
+            .iter()
+            .chain(PROC_READ_PATHS)
+            .map(|path| (PathBuf::from(path), read))
+            .collect();
+        // devices like /dev/null and /dev/urandom
+        rules.push((
+            "/dev".into(),
+            read | FS_WRITE_FILE | (handled & FS_IOCTL_DEV),
+        ));
+        if let Some(runtime_dir) = runtime.parent() {
+            rules.push((runtime_dir.to_path_buf(), read));
+        }
+        rules.push((pipe_dir.to_path_buf(), write));
+        rules.extend(permissions.read.iter().map(|path| (path.clone(), read)));
+        rules.extend(permissions.write.iter().map(|path| (path.clone(), write)));
+
+        for (path, access) in rules {
+            let Ok(metadata) = std::fs::metadata(&path) else {
+                debug!("skipping missing sandbox path {:?}", path);
+                continue;
+            };
+            let access = if metadata.is_dir() {
+                access
+            } else {
+                access & FS_FILE_ONLY
+            };
+            add_path_rule(&ruleset, &path, access)?;
+        }
+
+        if restrict_network {
+            for port in direct_ports {
+                let attr = NetPortAttr {
+                    allowed_access: NET_CONNECT_TCP,
+                    port: u64::from(*port),
+                };
+                add_rule(
+                    &ruleset,
+                    RULE_NET_PORT,
+                    &attr as *const NetPortAttr as *const _,
+                )?;
+            }
+        }
+
+        Ok(ruleset)
+    }
+
+    fn add_path_rule(ruleset: &OwnedFd, path: &Path, access: u64) -> io::Result<()> {
+        let path = CString::new(path.as_os_str().as_bytes())?;
+        // SAFETY: path is a valid nul terminated string
+        let fd = unsafe { libc::open(path.as_ptr(), libc::O_PATH | libc::O_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: fd was just opened by us
+        let parent = unsafe { OwnedFd::from_raw_fd(fd) };
+        let attr = PathBeneathAttr {
+            allowed_access: access,
+            parent_fd: parent.as_raw_fd(),
+        };
+        add_rule(
+            ruleset,
+            RULE_PATH_BENEATH,
+            &attr as *const PathBeneathAttr as *const _,
+        )
+    }
+
+    fn add_rule(ruleset: &OwnedFd, rule_type: u32, attr: *const libc::c_void) -> io::Result<()> {
+        // SAFETY: attr points to the struct matching rule_type
+        let res = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_add_rule,
+                ruleset.as_raw_fd(),
+                rule_type,
+                attr,
+                0u32,
+            )
+        };
+        if res < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Lets the process read its own `/proc/<pid>`, called in the child so `/proc/self`
+    /// resolves to it.
+    fn allow_own_proc(ruleset: RawFd, access: u64) -> io::Result<()> {
+        // SAFETY: plain syscalls on a static nul terminated path
+        unsafe {
+            let fd = libc::open(
+                b"/proc/self\0".as_ptr() as *const libc::c_char,
+                libc::O_PATH | libc::O_CLOEXEC,
+            );
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let attr = PathBeneathAttr {
+                allowed_access: access,
+                parent_fd: fd,
+            };
+            let res = libc::syscall(
+                libc::SYS_landlock_add_rule,
+                ruleset,
+                RULE_PATH_BENEATH,
+                &attr as *const PathBeneathAttr,
+                0u32,
+            );
+            libc::close(fd);
+            if res < 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+
+    fn restrict_self(ruleset: RawFd) -> io::Result<()> {
+        // SAFETY: plain syscalls, called in the child right before exec
+        unsafe {
+            if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if libc::syscall(libc::SYS_landlock_restrict_self, ruleset, 0u32) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            libc::close(ruleset);
+        }
+        Ok(())
+    }
+
+    fn set_rlimit(resource: RlimitResource, value: u64) -> io::Result<()> {
+        let limit = libc::rlimit {
+            rlim_cur: value as libc::rlim_t,
+            rlim_max: value as libc::rlim_t,
+        };
+        // SAFETY: limit is a valid rlimit
+        if unsafe { libc::setrlimit(resource, &limit) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    #[cfg(target_env = "gnu")]
+    type RlimitResource = libc::__rlimit_resource_t;
+    #[cfg(not(target_env = "gnu"))]
+    type RlimitResource = libc::c_int;
+
+    fn join_cgroup(procs: &CString) -> io::Result<()> {
+        // SAFETY: procs is a valid nul terminated path, writing "0" moves the caller
+        unsafe {
+            let fd = libc::open(procs.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let written = libc::write(fd, b"0".as_ptr() as *const libc::c_void, 1);
+            libc::close(fd);
+            if written != 1 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+
+    /// The cgroup we started in, pipe cgroups are created in it. cgroups v2 only hand
+    /// controllers down from a cgroup without processes of its own, so the server first
+    /// moves itself into a `screenpipe-server` leaf next to the pipes.
+    static PIPES_PARENT: Lazy<Option<PathBuf>> = Lazy::new(|| {
+        let root = Path::new("/sys/fs/cgroup");
+        if !root.join("cgroup.controllers").exists() {
+            debug!("cgroups v2 not mounted, pipe resource limits disabled");
+            return None;
+        }
+        let own = std::fs::read_to_string("/proc/self/cgroup").ok()?;
+        let own = own.lines().find_map(|line| line.strip_prefix("0::"))?;
+        let own = root.join(own.trim_start_matches('/'));
+        if own.ends_with(SERVER_CGROUP) {
+            return own.parent().map(Path::to_path_buf);
+        }
+
+        let leaf = own.join(SERVER_CGROUP);
+        let moved = std::fs::create_dir_all(&leaf).and_then(|()| {
+            std::fs::write(leaf.join("cgroup.procs"), std::process::id().to_string())
+        });
+        if let Err(e) = moved {
+            debug!("can't move into leaf cgroup {:?}: {}", leaf, e);
+            return None;
+        }
+        Some(own)
+    });
+
+    const SERVER_CGROUP: &str = "screenpipe-server";
+
+    /// Child cgroup of ours holding one pipe.
+    struct Cgroup {
+        path: PathBuf,
+        /// Controllers the limits were written for.
+        controllers: Vec<String>,
+    }
+
+    impl Cgroup {
+        /// Creates the pipe's cgroup next to ours, `None` when cgroups v2 isn't usable.
+        fn create(
+            pipe: &str,
+            memory_mb: Option<u64>,
+            cpu_percent: Option<u32>,
+            max_processes: Option<u64>,
+        ) -> Option<Self> {
+            if memory_mb.is_none() && cpu_percent.is_none() && max_processes.is_none() {
+                return None;
+            }
+
+            let parent = PIPES_PARENT.as_ref()?;
+            let path = parent.join(format!("screenpipe-pipe-{}", pipe));
+
+            if let Err(e) = std::fs::create_dir_all(&path) {
+                debug!("can't create pipe cgroup {:?}: {}", path, e);
+                return None;
+            }
+
+            let limits = [
+                (
+                    "memory",
+                    "memory.max",
+                    memory_mb.map(|mb| (mb * 1024 * 1024).to_string()),
+                ),
+                (
+                    "cpu",
+                    "cpu.max",
+                    cpu_percent.map(|percent| format!("{} 100000", u64::from(percent) * 1000)),
+                ),
+                ("pids", "pids.max", max_processes.map(|max| max.to_string())),
+            ];
+            let mut controllers = Vec::new();
+            for (controller, file, value) in limits {
+                let Some(value) = value else { continue };
+                // controllers have to be enabled for children of our cgroup first
+                let _ = std::fs::write(
+                    parent.join("cgroup.subtree_control"),
+                    format!("+{}", controller),
+                );
+                match std::fs::write(path.join(file), value) {
+                    Ok(()) => controllers.push(controller.to_string()),
+                    Err(e) => warn!("failed to set {} for pipe {}: {}", file, pipe, e),
+                }
+            }
+
+            Some(Cgroup { path, controllers })
+        }
+    }
+}
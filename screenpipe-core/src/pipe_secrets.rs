@@ -0,0 +1,352 @@
+//! Encrypted store for the secrets of pipes (api keys, tokens): values of the `secret`
+//! fields of `pipe.json` and the ones set through `/pipes/:id/secrets` or
+//! `screenpipe pipe secret set`. They never land in `pipe.json`, pipes get them as
+//! environment variables named after the secret when they start.
+//!
+//! All secrets are kept in one file, `pipe_secrets.enc` in the screenpipe directory, sealed
+//! with ChaCha20-Poly1305 under a key derived from a random master key kept in the keyring
+//! of the OS (keychain, credential manager, secret service). Where no keyring is reachable,
+//! on headless linux for instance, or with [`SecretsKeySource::File`], the master key is kept
+//! in `pipe_secrets.key` next to the store, readable by the user only.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use anyhow::Result;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+
+pub const PIPE_SECRETS_FILE: &str = "pipe_secrets.enc";
+/// Shown in place of the value of secret fields.
+pub const REDACTED_SECRET: &str = "********";
+
+const PIPE_SECRETS_KEY_FILE: &str = "pipe_secrets.key";
+const KEYRING_SERVICE: &str = "screenpipe";
+const KEYRING_USER: &str = "pipe-secrets";
+const SEALED_VERSION: u32 = 1;
+/// Prefix of the values returned by [`seal_secret`].
+const SEALED_PREFIX: &str = "sealed:v1:";
+/// Variables screenpipe sets itself, a secret can't replace them.
+const RESERVED_NAMES: &[&str] = &[
+    "SCREENPIPE_DIR",
+    "PIPE_ID",
+    "PIPE_DIR",
+    "PIPE_FILE",
+    "PORT",
+    "CRON_SECRET",
+    "PATH",
+    "HOME",
+];
+
+/// Where the master key of the store comes from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SecretsKeySource {
+    /// The keyring of the OS, a key file when it can't be reached.
+    #[default]
+    Keyring,
+    /// Always the key file of the screenpipe directory, for tests and throwaway directories
+    /// that shouldn't leave an entry in the keyring.
+    File,
+}
+
+static KEY_SOURCE: Lazy<RwLock<SecretsKeySource>> =
+    Lazy::new(|| RwLock::new(SecretsKeySource::default()));
+/// Master keys by screenpipe directory, the keyring is only asked once.
+static MASTER_KEYS: Lazy<Mutex<HashMap<PathBuf, Vec<u8>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+/// Held while the store is read and rewritten.
+static STORE_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// Secrets by pipe, then by name.
+type Secrets = BTreeMap<String, BTreeMap<String, String>>;
+
+#[derive(Serialize, Deserialize)]
+struct SealedSecrets {
+    version: u32,
+    nonce: String,
+    ciphertext: String,
+}
+
+struct SecretsStore {
+    path: PathBuf,
+    cipher: ChaCha20Poly1305,
+}
+
+impl SecretsStore {
+    /// The store of `screenpipe_dir`, `None` when no secret was ever set, which doesn't touch
+    /// the keyring.
+    async fn open_existing(screenpipe_dir: &Path) -> Result<Option<Self>> {
+        if !screenpipe_dir.join(PIPE_SECRETS_FILE).exists() {
+            return Ok(None);
+        }
+        Ok(Some(Self::open(screenpipe_dir).await?))
+    }
+
+    async fn open(screenpipe_dir: &Path) -> Result<Self> {
+        let master_key = master_key(screenpipe_dir).await?;
+        let key = Sha256::new()
+            .chain_update(b"screenpipe pipe secrets")
+            .chain_update(&master_key)
+            .finalize();
+        Ok(SecretsStore {
+            path: screenpipe_dir.join(PIPE_SECRETS_FILE),
+            cipher: ChaCha20Poly1305::new(Key::from_slice(&key)),
+        })
+    }
+
+    async fn load(&self) -> Result<Secrets> {
+        let content = match tokio::fs::read(&self.path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Secrets::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let sealed: SealedSecrets = serde_json::from_slice(&content)?;
+        if sealed.version != SEALED_VERSION {
+            anyhow::bail!("unsupported pipe secrets version {}", sealed.version);
+        }
+        let nonce = BASE64.decode(sealed.nonce)?;
+        if nonce.len() != 12 {
+            anyhow::bail!("invalid pipe secrets nonce");
+        }
+        let plaintext = self
+            .cipher
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                BASE64.decode(sealed.ciphertext)?.as_ref(),
+            )
+            .map_err(|_| {
+                anyhow::anyhow!(
+                    "failed to decrypt pipe secrets, the file is corrupted or the key changed"
+                )
+            })?;
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+
+    async fn save(&self, secrets: &Secrets) -> Result<()> {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, serde_json::to_vec(secrets)?.as_ref())
+            .map_err(|_| anyhow::anyhow!("failed to encrypt pipe secrets"))?;
+        let sealed = SealedSecrets {
+            version: SEALED_VERSION,
+            nonce: BASE64.encode(nonce),
+            ciphertext: BASE64.encode(ciphertext),
+        };
+        write_private(&self.path, &serde_json::to_vec_pretty(&sealed)?).await
+    }
+}
+
+/// Secrets of `pipe` by name.
+pub async fn get_pipe_secrets(
+    screenpipe_dir: &Path,
+    pipe: &str,
+) -> Result<BTreeMap<String, String>> {
+    let Some(store) = SecretsStore::open_existing(screenpipe_dir).await? else {
+        return Ok(BTreeMap::new());
+    };
+    Ok(store.load().await?.remove(pipe).unwrap_or_default())
+}
+
+/// Names of the secrets set for each pipe, never their values.
+pub async fn list_pipe_secrets(screenpipe_dir: &Path) -> Result<BTreeMap<String, Vec<String>>> {
+    let Some(store) = SecretsStore::open_existing(screenpipe_dir).await? else {
+        return Ok(BTreeMap::new());
+    };
+    Ok(store
+        .load()
+        .await?
+        .into_iter()
+        .map(|(pipe, secrets)| (pipe, secrets.into_keys().collect()))
+        .collect())
+}
+
+pub async fn set_pipe_secret(
+    screenpipe_dir: &Path,
+    pipe: &str,
+    name: &str,
+    value: &str,
+) -> Result<()> {
+    validate_secret_name(name)?;
+    let store = SecretsStore::open(screenpipe_dir).await?;
+    let _lock = STORE_LOCK.lock().await;
+    let mut secrets = store.load().await?;
+    secrets
+        .entry(pipe.to_string())
+        .or_default()
+        .insert(name.to_string(), value.to_string());
+    store.save(&secrets).await?;
+    debug!("set secret {} of pipe {}", name, pipe);
+    Ok(())
+}
+
+/// Removes secret `name` of `pipe`, false when it wasn't set.
+pub async fn remove_pipe_secret(screenpipe_dir: &Path, pipe: &str, name: &str) -> Result<bool> {
+    let Some(store) = SecretsStore::open_existing(screenpipe_dir).await? else {
+        return Ok(false);
+    };
+    let _lock = STORE_LOCK.lock().await;
+    let mut secrets = store.load().await?;
+    let Some(pipe_secrets) = secrets.get_mut(pipe) else {
+        return Ok(false);
+    };
+    if pipe_secrets.remove(name).is_none() {
+        return Ok(false);
+    }
+    if pipe_secrets.is_empty() {
+        secrets.remove(pipe);
+    }
+    store.save(&secrets).await?;
+    Ok(true)
+}
+
+/// Removes every secret of `pipe`, when it's deleted.
+pub async fn remove_pipe_secrets(screenpipe_dir: &Path, pipe: &str) -> Result<()> {
+    let Some(store) = SecretsStore::open_existing(screenpipe_dir).await? else {
+        return Ok(());
+    };
+    let _lock = STORE_LOCK.lock().await;
+    let mut secrets = store.load().await?;
+    if secrets.remove(pipe).is_some() {
+        store.save(&secrets).await?;
+    }
+    Ok(())
+}
+
+/// Seals a secret kept outside the store under the same master key, like the signing
+/// secret of a webhook in the database.
+pub async fn seal_secret(screenpipe_dir: &Path, value: &str) -> Result<String> {
+    let cipher = sealing_cipher(screenpipe_dir).await?;
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, value.as_bytes())
+        .map_err(|_| anyhow::anyhow!("failed to encrypt secret"))?;
+    let mut sealed = nonce.to_vec();
+    sealed.extend(ciphertext);
+    Ok(format!("{}{}", SEALED_PREFIX, BASE64.encode(sealed)))
+}
+
+/// The value of a secret sealed by [`seal_secret`]. Values that were never sealed are
+/// returned as they are.
+pub async fn open_secret(screenpipe_dir: &Path, value: &str) -> Result<String> {
+    let Some(encoded) = value.strip_prefix(SEALED_PREFIX) else {
+        return Ok(value.to_string());
+    };
+    let sealed = BASE64.decode(encoded)?;
+    if sealed.len() < 12 {
+        anyhow::bail!("invalid sealed secret");
+    }
+    let (nonce, ciphertext) = sealed.split_at(12);
+    let plaintext = sealing_cipher(screenpipe_dir)
+        .await?
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow::anyhow!("failed to decrypt secret, the key changed"))?;
+    Ok(String::from_utf8(plaintext)?)
+}
+
+async fn sealing_cipher(screenpipe_dir: &Path) -> Result<ChaCha20Poly1305> {
+    let master_key = master_key(screenpipe_dir).await?;
+    let key = Sha256::new()
+        .chain_update(b"screenpipe sealed secrets")
+        .chain_update(&master_key)
+        .finalize();
+    Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+}
+
+/// Secrets have to be usable as environment variable names.
+pub fn validate_secret_name(name: &str) -> Result<()> {
+    let mut chars = name.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        anyhow::bail!(
+            "invalid secret name {:?}, use letters, digits and underscores only",
+            name
+        );
+    }
+    if RESERVED_NAMES.contains(&name) {
+        anyhow::bail!("{} is set by screenpipe and can't be a secret", name);
+    }
+    Ok(())
+}
+
+/// Sets where master keys are read from, directories whose key was already read keep it.
+pub fn set_secrets_key_source(source: SecretsKeySource) {
+    *KEY_SOURCE.write().unwrap() = source;
+}
+
+async fn master_key(screenpipe_dir: &Path) -> Result<Vec<u8>> {
+    let mut keys = MASTER_KEYS.lock().await;
+    if let Some(key) = keys.get(screenpipe_dir) {
+        return Ok(key.clone());
+    }
+
+    // a key file means the keyring wasn't reachable when the store was created, keep using it
+    let key_file = screenpipe_dir.join(PIPE_SECRETS_KEY_FILE);
+    let source = *KEY_SOURCE.read().unwrap();
+    let key = if key_file.exists() {
+        BASE64.decode(tokio::fs::read_to_string(&key_file).await?.trim())?
+    } else if source == SecretsKeySource::File {
+        let key = random_key();
+        write_private(&key_file, BASE64.encode(&key).as_bytes()).await?;
+        key
+    } else {
+        match tokio::task::spawn_blocking(keyring_master_key).await? {
+            Ok(key) => key,
+            Err(e) => {
+                warn!(
+                    "keyring unavailable ({}), keeping the pipe secrets key in {:?}",
+                    e, key_file
+                );
+                let key = random_key();
+                write_private(&key_file, BASE64.encode(&key).as_bytes()).await?;
+                key
+            }
+        }
+    };
+    keys.insert(screenpipe_dir.to_path_buf(), key.clone());
+    Ok(key)
+}
+
+fn keyring_master_key() -> Result<Vec<u8>> {
+    let entry = keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)?;
+    match entry.get_password() {
+        Ok(encoded) => Ok(BASE64.decode(encoded)?),
+        Err(keyring::Error::NoEntry) => {
+            let key = random_key();
+            entry.set_password(&BASE64.encode(&key))?;
+            Ok(key)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn random_key() -> Vec<u8> {
+    ChaCha20Poly1305::generate_key(&mut OsRng).to_vec()
+}
+
+/// Replaces `path` with `content`, readable and writable by the user only.
+async fn write_private(path: &Path, content: &[u8]) -> Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(&tmp).await?;
+    tokio::io::AsyncWriteExt::write_all(&mut file, content).await?;
+    file.sync_all().await?;
+    drop(file);
+    tokio::fs::rename(&tmp, path).await?;
+    Ok(())
+}
@@ -0,0 +1,631 @@
+//! Where pipes are installed from and how installs are checked.
+//!
+//! Besides github folders and local directories a pipe can come from a `.tar.gz` or `.zip`
+//! archive (by url or path) or from any git remote at a commit or tag. What was fetched is
+//! hashed, optionally checked against a SHA-256 and an ed25519 signature given with the
+//! install, and recorded in a [`PipeLock`] next to the pipe so the exact same files can be
+//! installed again later.
+
+use std::fmt;
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use ed25519_dalek::{Signature, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tempfile::TempDir;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tracing::debug;
+use url::Url;
+
+pub const PIPE_LOCK_FILE: &str = "pipe.lock";
+const ARCHIVE_EXTENSIONS: &[&str] = &[".tar.gz", ".tgz", ".zip"];
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+/// Largest archive downloaded for an install.
+const MAX_ARCHIVE_BYTES: u64 = 200 * 1024 * 1024;
+/// Most an archive may unpack to, a small archive can expand to anything.
+const MAX_UNPACKED_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// A parsed install source.
+///
+/// Git sources are written `git+<remote>#<rev>:<subdir>`, both `rev` (a commit, tag or
+/// branch) and `subdir` being optional. Remotes ending in `.git` don't need the prefix.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PipeSource {
+    /// A folder of a github repository, `https://github.com/<owner>/<repo>/tree/<branch>/<path>`.
+    GithubFolder(Url),
+    /// Any other http(s) url, a `.tar.gz` or `.zip` told apart by its content.
+    Archive(Url),
+    Git {
+        remote: String,
+        rev: Option<String>,
+        subdir: Option<String>,
+    },
+    LocalArchive(PathBuf),
+    LocalDir(PathBuf),
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PipeSourceKind {
+    Github,
+    Archive,
+    Git,
+    Local,
+}
+
+impl PipeSource {
+    pub fn parse(source: &str) -> Result<Self> {
+        if let Some(git) = source.strip_prefix("git+") {
+            return Self::parse_git(git);
+        }
+
+        // a windows path such as `C:/pipes/my-pipe` parses as a url with a one letter scheme
+        if let Some(url) = Url::parse(source).ok().filter(|url| url.scheme().len() > 1) {
+            return match url.scheme() {
+                "http" | "https" if url.path().ends_with(".git") => Self::parse_git(source),
+                "http" | "https" if url.host_str() == Some("github.com") => {
+                    Ok(PipeSource::GithubFolder(url))
+                }
+                "http" | "https" => Ok(PipeSource::Archive(url)),
+                "ssh" | "git" => Self::parse_git(source),
+                "file" => {
+                    let path = url
+                        .to_file_path()
+                        .map_err(|_| anyhow::anyhow!("Invalid local source path"))?;
+                    Self::parse_local(path)
+                }
+                _ => anyhow::bail!("Unsupported URL format"),
+            };
+        }
+
+        // scp-like remotes, `git@host:owner/repo.git`
+        let remote = source.split_once('#').map_or(source, |(remote, _)| remote);
+        if remote.ends_with(".git") {
+            return Self::parse_git(source);
+        }
+
+        Self::parse_local(PathBuf::from(source))
+    }
+
+    fn parse_git(source: &str) -> Result<Self> {
+        let (remote, fragment) = source.split_once('#').unwrap_or((source, ""));
+        // refs can't contain a colon, so it's free to separate the subdirectory
+        let (rev, subdir) = fragment.split_once(':').unwrap_or((fragment, ""));
+        if remote.is_empty() {
+            anyhow::bail!("git source without a remote: {}", source);
+        }
+        check_git_arg("remote", remote)?;
+        check_git_arg("rev", rev)?;
+        let subdir_path = Path::new(subdir);
+        if subdir_path.is_absolute() || subdir_path.components().any(|c| c.as_os_str() == "..") {
+            anyhow::bail!(
+                "git subdirectory must stay inside the repository: {}",
+                subdir
+            );
+        }
+        Ok(PipeSource::Git {
+            remote: remote.to_string(),
+            rev: (!rev.is_empty()).then(|| rev.to_string()),
+            subdir: (!subdir.is_empty()).then(|| subdir.trim_matches('/').to_string()),
+        })
+    }
+
+    fn parse_local(path: PathBuf) -> Result<Self> {
+        if path.is_dir() {
+            Ok(PipeSource::LocalDir(path))
+        } else if path.is_file() && archive_stem(&path.to_string_lossy()).is_some() {
+            Ok(PipeSource::LocalArchive(path))
+        } else {
+            anyhow::bail!("Invalid local source path")
+        }
+    }
+
+    pub fn kind(&self) -> PipeSourceKind {
+        match self {
+            PipeSource::GithubFolder(_) => PipeSourceKind::Github,
+            PipeSource::Archive(_) | PipeSource::LocalArchive(_) => PipeSourceKind::Archive,
+            PipeSource::Git { .. } => PipeSourceKind::Git,
+            PipeSource::LocalDir(_) => PipeSourceKind::Local,
+        }
+    }
+
+    /// Name the pipe is installed under, before sanitizing: the folder, the archive without
+    /// its extension and version, or the repository (or subdirectory) of a git remote.
+    pub fn pipe_name(&self) -> String {
+        let last_segment = |s: &str| {
+            s.trim_end_matches('/')
+                .rsplit(['/', '\\', ':'])
+                .next()
+                .unwrap_or_default()
+                .to_string()
+        };
+        match self {
+            PipeSource::GithubFolder(url) => last_segment(url.path()),
+            PipeSource::Archive(url) => {
+                let file = last_segment(url.path());
+                strip_version(archive_stem(&file).unwrap_or(&file)).to_string()
+            }
+            PipeSource::LocalArchive(path) => {
+                let file = last_segment(&path.to_string_lossy());
+                strip_version(archive_stem(&file).unwrap_or(&file)).to_string()
+            }
+            PipeSource::Git { remote, subdir, .. } => match subdir {
+                Some(subdir) => last_segment(subdir),
+                None => last_segment(remote).trim_end_matches(".git").to_string(),
+            },
+            PipeSource::LocalDir(path) => last_segment(&path.to_string_lossy()),
+        }
+    }
+}
+
+/// `name` without its archive extension, `None` when it isn't an archive.
+fn archive_stem(name: &str) -> Option<&str> {
+    ARCHIVE_EXTENSIONS
+        .iter()
+        .find_map(|extension| name.strip_suffix(extension))
+}
+
+/// `my-pipe-1.2.0` and `my-pipe-v1.2.0` become `my-pipe`, so upgrades land in the same pipe.
+fn strip_version(name: &str) -> &str {
+    match name.rsplit_once('-') {
+        Some((base, version))
+            if !base.is_empty()
+                && semver::Version::parse(version.trim_start_matches('v')).is_ok() =>
+        {
+            base
+        }
+        _ => name,
+    }
+}
+
+/// Integrity requirements of an install, all optional.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct PipeInstallOptions {
+    /// Expected SHA-256 in hex: of the archive for archive sources, of the fetched files
+    /// (see [`hash_pipe_dir`]) for the others.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Base64 ed25519 signature of the 32 bytes of that SHA-256.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    /// Base64 ed25519 public key the signature is checked with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+}
+
+/// An install that didn't match what the request asked for.
+#[derive(Clone, Debug, PartialEq)]
+pub enum IntegrityError {
+    ChecksumMismatch {
+        expected: String,
+        actual: String,
+    },
+    /// A signature without a public key to check it, or the other way round.
+    IncompleteSignature,
+    InvalidPublicKey(String),
+    InvalidSignature(String),
+}
+
+impl fmt::Display for IntegrityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntegrityError::ChecksumMismatch { expected, actual } => {
+                write!(f, "sha256 mismatch: expected {}, got {}", expected, actual)
+            }
+            IntegrityError::IncompleteSignature => {
+                write!(f, "a signature check needs both signature and public_key")
+            }
+            IntegrityError::InvalidPublicKey(e) => write!(f, "invalid public key: {}", e),
+            IntegrityError::InvalidSignature(e) => write!(f, "invalid signature: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for IntegrityError {}
+
+/// Checks `sha256`, the hash of what was fetched, against `options`.
+pub fn verify_pipe_integrity(
+    sha256: &str,
+    options: &PipeInstallOptions,
+) -> Result<(), IntegrityError> {
+    if let Some(expected) = &options.sha256 {
+        let expected = expected.trim().trim_start_matches("sha256:").to_lowercase();
+        if expected != sha256 {
+            return Err(IntegrityError::ChecksumMismatch {
+                expected,
+                actual: sha256.to_string(),
+            });
+        }
+    }
+
+    match (&options.signature, &options.public_key) {
+        (None, None) => Ok(()),
+        (Some(signature), Some(public_key)) => {
+            let engine = base64::engine::general_purpose::STANDARD;
+            let public_key: [u8; 32] = engine
+                .decode(public_key.trim())
+                .map_err(|e| IntegrityError::InvalidPublicKey(e.to_string()))?
+                .try_into()
+                .map_err(|_| IntegrityError::InvalidPublicKey("expected 32 bytes".to_string()))?;
+            let public_key = VerifyingKey::from_bytes(&public_key)
+                .map_err(|e| IntegrityError::InvalidPublicKey(e.to_string()))?;
+            let signature = engine
+                .decode(signature.trim())
+                .map_err(|e| IntegrityError::InvalidSignature(e.to_string()))?;
+            let signature = Signature::from_slice(&signature)
+                .map_err(|e| IntegrityError::InvalidSignature(e.to_string()))?;
+            let digest =
+                hex::decode(sha256).map_err(|e| IntegrityError::InvalidSignature(e.to_string()))?;
+            public_key
+                .verify_strict(&digest, &signature)
+                .map_err(|e| IntegrityError::InvalidSignature(e.to_string()))
+        }
+        _ => Err(IntegrityError::IncompleteSignature),
+    }
+}
+
+/// What a pipe was installed from, kept in `pipe.lock` in its directory.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct PipeLock {
+    /// The source as given at install time.
+    pub source: String,
+    pub kind: PipeSourceKind,
+    /// Source that fetches exactly these files again, a git source is pinned to its commit.
+    pub resolved: String,
+    /// SHA-256 the install was checked against, see [`PipeInstallOptions::sha256`].
+    pub sha256: String,
+    /// Set when the install was signature checked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+    pub installed_at: DateTime<Utc>,
+}
+
+impl PipeLock {
+    /// Options reinstalling `resolved` with, so anything but the locked files is refused.
+    pub fn install_options(&self) -> PipeInstallOptions {
+        PipeInstallOptions {
+            sha256: Some(self.sha256.clone()),
+            signature: self.signature.clone(),
+            public_key: self.public_key.clone(),
+        }
+    }
+}
+
+pub async fn read_pipe_lock(pipe_dir: &Path) -> Result<Option<PipeLock>> {
+    match tokio::fs::read_to_string(pipe_dir.join(PIPE_LOCK_FILE)).await {
+        Ok(content) => Ok(Some(serde_json::from_str(&content)?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn write_pipe_lock(pipe_dir: &Path, lock: &PipeLock) -> Result<()> {
+    tokio::fs::write(
+        pipe_dir.join(PIPE_LOCK_FILE),
+        serde_json::to_string_pretty(lock)?,
+    )
+    .await?;
+    Ok(())
+}
+
+/// SHA-256 of the files under `dir`: every file's path relative to `dir` (with `/`
+/// separators), a zero byte, its length as little endian u64 and its content, in path order.
+pub async fn hash_pipe_dir(dir: &Path) -> Result<String> {
+    let dir = dir.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let mut files = Vec::new();
+        collect_files(&dir, &dir, &mut files)?;
+        files.sort();
+
+        let mut hasher = Sha256::new();
+        for relative in files {
+            let content = std::fs::read(dir.join(&relative))?;
+            hasher.update(relative.as_bytes());
+            hasher.update([0]);
+            hasher.update((content.len() as u64).to_le_bytes());
+            hasher.update(&content);
+        }
+        Ok(format!("{:x}", hasher.finalize()))
+    })
+    .await?
+}
+
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<String>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            collect_files(root, &path, files)?;
+        } else {
+            let relative = path.strip_prefix(root)?;
+            files.push(
+                relative
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/"),
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Files of an archive or git source, in a staging directory removed when this is dropped.
+pub struct FetchedPipe {
+    /// Root of the pipe inside the staging directory.
+    pub dir: PathBuf,
+    /// See [`PipeLock::resolved`].
+    pub resolved: String,
+    /// Hash of the archive, `None` for git sources whose files are hashed instead.
+    pub archive_sha256: Option<String>,
+    _staging: TempDir,
+}
+
+/// Downloads the archive at `url` into the staging directory, at most [`MAX_ARCHIVE_BYTES`].
+pub async fn fetch_archive_url(url: &Url) -> Result<FetchedPipe> {
+    debug!("downloading pipe archive: {}", url);
+    let too_large = || {
+        anyhow::anyhow!(
+            "pipe archive is larger than {} MB",
+            MAX_ARCHIVE_BYTES / 1024 / 1024
+        )
+    };
+    let mut response = reqwest::Client::new()
+        .get(url.clone())
+        .header("User-Agent", "screenpipe")
+        .send()
+        .await?
+        .error_for_status()?;
+    if response
+        .content_length()
+        .is_some_and(|length| length > MAX_ARCHIVE_BYTES)
+    {
+        return Err(too_large());
+    }
+
+    let staging = tempfile::tempdir()?;
+    let archive = staging.path().join("archive");
+    let mut file = tokio::fs::File::create(&archive).await?;
+    let mut size = 0;
+    while let Some(chunk) = response.chunk().await? {
+        size += chunk.len() as u64;
+        if size > MAX_ARCHIVE_BYTES {
+            return Err(too_large());
+        }
+        file.write_all(&chunk).await?;
+    }
+    file.flush().await?;
+    drop(file);
+
+    unpack_archive(staging, archive, url.to_string()).await
+}
+
+pub async fn fetch_local_archive(path: &Path) -> Result<FetchedPipe> {
+    let resolved = std::fs::canonicalize(path)?.to_string_lossy().into_owned();
+    unpack_archive(tempfile::tempdir()?, path.to_path_buf(), resolved).await
+}
+
+/// Unpacks the archive file at `archive` into `staging`.
+async fn unpack_archive(
+    staging: TempDir,
+    archive: PathBuf,
+    resolved: String,
+) -> Result<FetchedPipe> {
+    let target = staging.path().join("files");
+
+    let (sha256, target) = tokio::task::spawn_blocking(move || -> Result<(String, PathBuf)> {
+        let mut file = std::fs::File::open(&archive)?;
+        let mut hasher = Sha256::new();
+        std::io::copy(&mut file, &mut hasher)?;
+        let sha256 = format!("{:x}", hasher.finalize());
+
+        let mut magic = Vec::new();
+        file.rewind()?;
+        (&mut file).take(4).read_to_end(&mut magic)?;
+        file.rewind()?;
+        std::fs::create_dir_all(&target)?;
+
+        if magic.starts_with(GZIP_MAGIC) {
+            unpack_tar_gz(file, &target)?;
+        } else if magic.starts_with(ZIP_MAGIC) {
+            unpack_zip(file, &target)?;
+        } else {
+            let mut start = Vec::new();
+            file.take(64).read_to_end(&mut start)?;
+            anyhow::bail!(
+                "unsupported archive, expected a .tar.gz or .zip (starts with {:?})",
+                String::from_utf8_lossy(&start)
+            );
+        }
+        Ok((sha256, target))
+    })
+    .await??;
+
+    Ok(FetchedPipe {
+        dir: archive_root(&target)?,
+        resolved,
+        archive_sha256: Some(sha256),
+        _staging: staging,
+    })
+}
+
+fn too_large_unpacked() -> anyhow::Error {
+    anyhow::anyhow!(
+        "pipe archive unpacks to more than {} MB",
+        MAX_UNPACKED_BYTES / 1024 / 1024
+    )
+}
+
+fn unpack_tar_gz(file: std::fs::File, target: &Path) -> Result<()> {
+    let tar = flate2::read::GzDecoder::new(std::io::BufReader::new(file));
+    let mut archive = tar::Archive::new(tar);
+    let mut unpacked = 0u64;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        // tar reads exactly the size in the header off the stream
+        unpacked += entry.size();
+        if unpacked > MAX_UNPACKED_BYTES {
+            return Err(too_large_unpacked());
+        }
+        // entries escaping the target through `..` or absolute paths are skipped by tar
+        entry.unpack_in(target)?;
+    }
+    Ok(())
+}
+
+fn unpack_zip(file: std::fs::File, target: &Path) -> Result<()> {
+    let mut archive = zip::ZipArchive::new(file)?;
+    let mut unpacked = 0u64;
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        // only entries whose names stay inside the target are written
+        let Some(path) = entry.enclosed_name().map(|name| target.join(name)) else {
+            continue;
+        };
+        if entry.is_dir() {
+            std::fs::create_dir_all(&path)?;
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // the sizes in a zip are not to be trusted, the copy is capped instead
+        let budget = MAX_UNPACKED_BYTES - unpacked;
+        let mut out = std::fs::File::create(&path)?;
+        unpacked += std::io::copy(&mut (&mut entry).take(budget + 1), &mut out)?;
+        if unpacked > MAX_UNPACKED_BYTES {
+            return Err(too_large_unpacked());
+        }
+        #[cfg(unix)]
+        if let Some(mode) = entry.unix_mode() {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode))?;
+        }
+    }
+    Ok(())
+}
+
+/// Archives made from a folder (and github release tarballs) hold a single top directory,
+/// the pipe is that directory then.
+fn archive_root(dir: &Path) -> Result<PathBuf> {
+    let entries = std::fs::read_dir(dir)?.collect::<std::io::Result<Vec<_>>>()?;
+    match entries.as_slice() {
+        [entry] if entry.file_type()?.is_dir() => Ok(entry.path()),
+        _ => Ok(dir.to_path_buf()),
+    }
+}
+
+/// Clones `remote` at `rev` (its default branch when unset) with the `git` command.
+pub async fn fetch_git(
+    remote: &str,
+    rev: Option<&str>,
+    subdir: Option<&str>,
+) -> Result<FetchedPipe> {
+    // an option in place of the remote or rev would be run by git, `--upload-pack=...`
+    check_git_arg("remote", remote)?;
+    check_git_arg("rev", rev.unwrap_or_default())?;
+
+    let staging = tempfile::tempdir()?;
+    let checkout = staging.path().join("checkout");
+    let checkout_arg = checkout.to_string_lossy();
+
+    match rev {
+        Some(rev) => {
+            git(
+                &[
+                    "clone",
+                    "--quiet",
+                    "--no-checkout",
+                    "--",
+                    remote,
+                    &checkout_arg,
+                ],
+                None,
+            )
+            .await?;
+            // `--` after the rev, before it would make it a path
+            git(&["checkout", "--quiet", rev, "--"], Some(&checkout)).await?;
+        }
+        None => {
+            git(
+                &[
+                    "clone",
+                    "--quiet",
+                    "--depth",
+                    "1",
+                    "--",
+                    remote,
+                    &checkout_arg,
+                ],
+                None,
+            )
+            .await?;
+        }
+    }
+    let commit = git(&["rev-parse", "HEAD"], Some(&checkout)).await?;
+    debug!("fetched {} at {}", remote, commit);
+
+    let dir = match subdir {
+        Some(subdir) => checkout.join(subdir),
+        None => checkout.clone(),
+    };
+    if !dir.is_dir() {
+        anyhow::bail!("{} not found in {}", subdir.unwrap_or_default(), remote);
+    }
+
+    Ok(FetchedPipe {
+        dir,
+        resolved: format!(
+            "git+{}#{}{}",
+            remote,
+            commit,
+            subdir.map(|s| format!(":{}", s)).unwrap_or_default()
+        ),
+        archive_sha256: None,
+        _staging: staging,
+    })
+}
+
+fn check_git_arg(what: &str, value: &str) -> Result<()> {
+    if value.starts_with('-') {
+        anyhow::bail!("git {} can't start with '-': {}", what, value);
+    }
+    Ok(())
+}
+
+async fn git(args: &[&str], dir: Option<&Path>) -> Result<String> {
+    let mut command = Command::new("git");
+    command
+        // `ext::` remotes run arbitrary commands
+        .args(["-c", "protocol.ext.allow=never"])
+        .args(args)
+        // fail instead of waiting for credentials nobody will type
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .kill_on_drop(true);
+    if let Some(dir) = dir {
+        command.current_dir(dir);
+    }
+
+    let output = command.output().await.map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => {
+            anyhow::anyhow!("git not found, it's needed for git sources")
+        }
+        _ => e.into(),
+    })?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "git {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
@@ -0,0 +1,325 @@
+//! Installed versions of a pipe, kept so a bad upgrade can be rolled back.
+//!
+//! Every install or update snapshots the pipe's files together with where they came from
+//! and its `pipe.json` at the time into `pipes/.versions/<pipe>/<n>/`. Snapshots are never
+//! modified, only the oldest are dropped once there are more than [`MAX_PIPE_VERSIONS`].
+//! Restoring one swaps it in as the pipe directory and carries the user's settings over.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::debug;
+
+use crate::{read_pipe_lock, PIPE_KV_FILE, PIPE_LOG_DIR, PYTHON_VENV_DIR};
+
+/// Snapshots kept per pipe, the active one is never dropped.
+pub const MAX_PIPE_VERSIONS: usize = 5;
+const VERSIONS_DIR: &str = ".versions";
+const SNAPSHOT_FILE: &str = "snapshot.json";
+const SNAPSHOT_FILES_DIR: &str = "files";
+const CURRENT_FILE: &str = "current";
+const RESTORE_DIR: &str = "restore";
+/// Rebuilt after a restore (dependencies, next.js builds) or belonging to the pipe rather
+/// than to one of its versions.
+const UNVERSIONED: &[&str] = &[
+    "node_modules",
+    PYTHON_VENV_DIR,
+    ".next",
+    PIPE_LOG_DIR,
+    PIPE_KV_FILE,
+    "pipe.json",
+];
+
+/// One installed version of a pipe.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct PipeVersion {
+    /// Position in the pipe's history, starting at 1.
+    pub version: u32,
+    /// `version` of the pipe's package.json, if it has one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pipe_version: Option<String>,
+    pub source: String,
+    /// See [`crate::PipeLock::resolved`], unset for pipes installed without a lock.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolved: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// `pipe.json` right after this version was installed.
+    pub config: Value,
+    pub created_at: DateTime<Utc>,
+}
+
+/// History of a pipe, newest first.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct PipeVersions {
+    /// The version the pipe directory holds.
+    pub current: Option<u32>,
+    pub versions: Vec<PipeVersion>,
+}
+
+pub fn pipe_versions_dir(screenpipe_dir: &Path, pipe: &str) -> PathBuf {
+    screenpipe_dir.join("pipes").join(VERSIONS_DIR).join(pipe)
+}
+
+/// Records what `pipe` currently holds as its newest version and makes it the current one.
+pub async fn snapshot_pipe_version(screenpipe_dir: &Path, pipe: &str) -> Result<PipeVersion> {
+    let pipe_dir = screenpipe_dir.join("pipes").join(pipe);
+    if !pipe_dir.is_dir() {
+        anyhow::bail!("pipe '{}' does not exist", pipe);
+    }
+    let versions_dir = pipe_versions_dir(screenpipe_dir, pipe);
+    tokio::fs::create_dir_all(&versions_dir).await?;
+
+    let config = read_json(&pipe_dir.join("pipe.json"))
+        .await?
+        .unwrap_or_else(|| Value::Object(Default::default()));
+    let pipe_version = read_json(&pipe_dir.join("package.json"))
+        .await?
+        .and_then(|package| package.get("version")?.as_str().map(str::to_string));
+    let lock = read_pipe_lock(&pipe_dir).await?;
+
+    let version = PipeVersion {
+        version: snapshot_numbers(&versions_dir).await?.last().unwrap_or(&0) + 1,
+        pipe_version,
+        source: match &lock {
+            Some(lock) => lock.source.clone(),
+            None => config
+                .get("source")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+        },
+        resolved: lock.as_ref().map(|lock| lock.resolved.clone()),
+        sha256: lock.map(|lock| lock.sha256),
+        config,
+        created_at: Utc::now(),
+    };
+
+    let snapshot_dir = versions_dir.join(version.version.to_string());
+    copy_tree(
+        &pipe_dir,
+        &snapshot_dir.join(SNAPSHOT_FILES_DIR),
+        UNVERSIONED,
+    )
+    .await?;
+    // written last, a snapshot without it is an interrupted one and is ignored
+    tokio::fs::write(
+        snapshot_dir.join(SNAPSHOT_FILE),
+        serde_json::to_string_pretty(&version)?,
+    )
+    .await?;
+    set_current(&versions_dir, version.version).await?;
+    debug!("pipe {} snapshotted as version {}", pipe, version.version);
+
+    prune_versions(&versions_dir, version.version).await?;
+    Ok(version)
+}
+
+pub async fn list_pipe_versions(screenpipe_dir: &Path, pipe: &str) -> Result<PipeVersions> {
+    let versions_dir = pipe_versions_dir(screenpipe_dir, pipe);
+    let mut versions = Vec::new();
+    for number in snapshot_numbers(&versions_dir).await?.into_iter().rev() {
+        let snapshot = versions_dir.join(number.to_string()).join(SNAPSHOT_FILE);
+        if let Some(version) = read_json(&snapshot).await? {
+            versions.push(serde_json::from_value(version)?);
+        }
+    }
+    let current = match tokio::fs::read_to_string(versions_dir.join(CURRENT_FILE)).await {
+        Ok(current) => current.trim().parse().ok(),
+        Err(_) => None,
+    };
+    Ok(PipeVersions { current, versions })
+}
+
+/// The version of `pipe` that [`restore_pipe_version`] puts back.
+///
+/// `version` is a snapshot number or a package version (its newest snapshot), without it
+/// it is the version installed before the current one.
+pub async fn find_pipe_version(
+    screenpipe_dir: &Path,
+    pipe: &str,
+    version: Option<&str>,
+) -> Result<PipeVersion> {
+    let history = list_pipe_versions(screenpipe_dir, pipe).await?;
+    let found = match version {
+        Some(wanted) => history
+            .versions
+            .iter()
+            .find(|v| v.version.to_string() == wanted)
+            .or_else(|| {
+                history
+                    .versions
+                    .iter()
+                    .find(|v| v.pipe_version.as_deref() == Some(wanted))
+            })
+            .ok_or_else(|| anyhow::anyhow!("pipe '{}' has no version {}", pipe, wanted))?,
+        None => history
+            .versions
+            .iter()
+            .find(|v| history.current.is_none_or(|current| v.version < current))
+            .ok_or_else(|| anyhow::anyhow!("pipe '{}' has no earlier version", pipe))?,
+    };
+    Ok(found.clone())
+}
+
+/// Replaces the files of `pipe` with a snapshot (see [`find_pipe_version`]) and makes it
+/// the current version.
+///
+/// The pipe's logs are kept and its `pipe.json` is migrated with [`migrate_pipe_config`].
+/// The pipe must not be running, and its dependencies have to be installed again afterwards.
+pub async fn restore_pipe_version(
+    screenpipe_dir: &Path,
+    pipe: &str,
+    version: Option<&str>,
+) -> Result<PipeVersion> {
+    let pipe_dir = screenpipe_dir.join("pipes").join(pipe);
+    let versions_dir = pipe_versions_dir(screenpipe_dir, pipe);
+    let target = find_pipe_version(screenpipe_dir, pipe, version).await?;
+
+    let current_config = read_json(&pipe_dir.join("pipe.json"))
+        .await?
+        .unwrap_or(Value::Null);
+
+    // assemble the restored pipe next to the snapshots, then swap it in
+    let staging = versions_dir.join(RESTORE_DIR);
+    if staging.exists() {
+        tokio::fs::remove_dir_all(&staging).await?;
+    }
+    copy_tree(
+        &versions_dir
+            .join(target.version.to_string())
+            .join(SNAPSHOT_FILES_DIR),
+        &staging,
+        &[],
+    )
+    .await?;
+    let logs = pipe_dir.join(PIPE_LOG_DIR);
+    if logs.is_dir() {
+        copy_tree(&logs, &staging.join(PIPE_LOG_DIR), &[]).await?;
+    }
+    let kv = pipe_dir.join(PIPE_KV_FILE);
+    if kv.is_file() {
+        tokio::fs::copy(&kv, staging.join(PIPE_KV_FILE)).await?;
+    }
+    tokio::fs::write(
+        staging.join("pipe.json"),
+        serde_json::to_string_pretty(&migrate_pipe_config(&target.config, &current_config))?,
+    )
+    .await?;
+
+    if pipe_dir.exists() {
+        tokio::fs::remove_dir_all(&pipe_dir).await?;
+    }
+    tokio::fs::rename(&staging, &pipe_dir).await?;
+    set_current(&versions_dir, target.version).await?;
+
+    debug!("pipe {} restored to version {}", pipe, target.version);
+    Ok(target)
+}
+
+/// `pipe.json` for going back to a version whose config was `snapshot` from one whose
+/// config is `current`.
+///
+/// The snapshot's config is kept, as it matches that version's code, with whether the pipe
+/// is enabled, its port and the values of fields both versions have taken from `current`.
+pub fn migrate_pipe_config(snapshot: &Value, current: &Value) -> Value {
+    let mut migrated = snapshot.clone();
+    let (Some(migrated_obj), Some(current_obj)) = (migrated.as_object_mut(), current.as_object())
+    else {
+        return migrated;
+    };
+
+    for key in ["enabled", "port"] {
+        if let Some(value) = current_obj.get(key) {
+            migrated_obj.insert(key.to_string(), value.clone());
+        }
+    }
+
+    if let (Some(fields), Some(current_fields)) = (
+        migrated_obj.get_mut("fields").and_then(Value::as_array_mut),
+        current_obj.get("fields").and_then(Value::as_array),
+    ) {
+        for field in fields {
+            let Some(name) = field.get("name").and_then(Value::as_str) else {
+                continue;
+            };
+            let value = current_fields
+                .iter()
+                .find(|f| f.get("name").and_then(Value::as_str) == Some(name))
+                .and_then(|f| f.get("value"))
+                .cloned();
+            if let (Some(value), Some(field)) = (value, field.as_object_mut()) {
+                field.insert("value".to_string(), value);
+            }
+        }
+    }
+
+    migrated
+}
+
+async fn read_json(path: &Path) -> Result<Option<Value>> {
+    match tokio::fs::read_to_string(path).await {
+        Ok(content) => Ok(Some(serde_json::from_str(&content)?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Numbers of the snapshots in `versions_dir`, ascending.
+async fn snapshot_numbers(versions_dir: &Path) -> Result<Vec<u32>> {
+    let mut numbers = Vec::new();
+    let mut entries = match tokio::fs::read_dir(versions_dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(numbers),
+        Err(e) => return Err(e.into()),
+    };
+    while let Some(entry) = entries.next_entry().await? {
+        if let Some(number) = entry.file_name().to_str().and_then(|n| n.parse().ok()) {
+            numbers.push(number);
+        }
+    }
+    numbers.sort_unstable();
+    Ok(numbers)
+}
+
+async fn set_current(versions_dir: &Path, version: u32) -> Result<()> {
+    tokio::fs::write(versions_dir.join(CURRENT_FILE), version.to_string()).await?;
+    Ok(())
+}
+
+async fn prune_versions(versions_dir: &Path, current: u32) -> Result<()> {
+    let numbers = snapshot_numbers(versions_dir).await?;
+    let excess = numbers.len().saturating_sub(MAX_PIPE_VERSIONS);
+    for number in numbers.into_iter().filter(|n| *n != current).take(excess) {
+        debug!("dropping pipe version {:?}/{}", versions_dir, number);
+        tokio::fs::remove_dir_all(versions_dir.join(number.to_string())).await?;
+    }
+    Ok(())
+}
+
+/// Copies `src` into `dst` (which is created), leaving out top level entries named in `skip`.
+async fn copy_tree(src: &Path, dst: &Path, skip: &'static [&'static str]) -> Result<()> {
+    fn copy(src: &Path, dst: &Path, skip: &[&str]) -> std::io::Result<()> {
+        std::fs::create_dir_all(dst)?;
+        for entry in std::fs::read_dir(src)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            if skip.iter().any(|s| name == *s) {
+                continue;
+            }
+            if entry.file_type()?.is_dir() {
+                copy(&entry.path(), &dst.join(&name), &[])?;
+            } else {
+                std::fs::copy(entry.path(), dst.join(&name))?;
+            }
+        }
+        Ok(())
+    }
+
+    let (src, dst) = (src.to_path_buf(), dst.to_path_buf());
+    tokio::task::spawn_blocking(move || copy(&src, &dst, skip)).await??;
+    Ok(())
+}
@@ -5,18 +5,15 @@ mod pipes {
     use regex::Regex;
     use sentry;
     use serde_json::Value;
-    use std::collections::HashMap;
     use std::future::Future;
     use std::path::PathBuf;
     use std::pin::Pin;
-    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    use std::time::Duration;
     use tokio::process::Command;
-    use tokio::sync::watch;
 
     use anyhow::Result;
-    use std::fs;
     use std::path::Path;
-    use tokio::io::{AsyncBufReadExt, BufReader};
+    use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
     use tracing::{debug, error, info, warn};
     use url::Url;
     use which::which;
@@ -27,17 +24,22 @@ mod pipes {
     use tokio::io::AsyncWriteExt;
 
     use crate::pick_unused_port;
+    use crate::pipe_cron::{generate_cron_secret, schedule_pipe_crons, CronContext};
+    use crate::pipe_logs::{PipeLogLevel, PipeLogStream, PipeLogger};
+    use crate::pipe_manifest::PipeManifest;
+    use crate::pipe_runtime::PipeRuntime;
+    use crate::pipe_sandbox::{PipePermissions, PipeSandbox};
+    use crate::pipe_secrets::get_pipe_secrets;
+    use crate::pipe_source::{
+        fetch_archive_url, fetch_git, fetch_local_archive, hash_pipe_dir, verify_pipe_integrity,
+        write_pipe_lock, FetchedPipe, PipeInstallOptions, PipeLock, PipeSource,
+    };
     use once_cell::sync::Lazy;
 
     // Add near other imports
     use http_cache_reqwest::{CACacheManager, Cache, CacheMode, HttpCache, HttpCacheOptions};
-    use rand::distributions::Alphanumeric;
-    use rand::{thread_rng, Rng};
-    use reqwest_middleware::reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
     use reqwest_middleware::reqwest::Client;
     use reqwest_middleware::ClientBuilder;
-    use std::collections::HashSet;
-    use std::str::FromStr;
 
     // Add at top of file with other imports
     #[cfg(windows)]
@@ -49,28 +51,6 @@ mod pipes {
         Pid(i32),
     }
 
-    pub struct CronHandle {
-        shutdown: watch::Sender<bool>,
-    }
-
-    impl CronHandle {
-        pub fn stop(&self) {
-            let _ = self.shutdown.send(true);
-        }
-    }
-
-    static CRON_HANDLES: Lazy<tokio::sync::Mutex<HashMap<String, Vec<CronHandle>>>> =
-        Lazy::new(|| tokio::sync::Mutex::new(HashMap::new()));
-
-    // Add this function to generate a secure cron secret
-    fn generate_cron_secret() -> String {
-        thread_rng()
-            .sample_iter(&Alphanumeric)
-            .take(32)
-            .map(char::from)
-            .collect()
-    }
-
     // Update this function near the top of the file
     fn sanitize_pipe_name(name: &str) -> String {
         let re = Regex::new(r"[^a-zA-Z0-9_-]").unwrap();
@@ -326,26 +306,58 @@ done
         Ok(child)
     }
 
+    /// How [`run_pipe_with`] starts a pipe, [`run_pipe`] uses the defaults.
+    #[derive(Clone, Debug, Default)]
+    pub struct PipeRunOptions {
+        /// Development run (`screenpipe pipe dev`): `enabled` is ignored, next.js pipes run
+        /// their dev server instead of a production build and `pipe.json` is left untouched.
+        pub dev: bool,
+        /// Variables added to the environment of the pipe.
+        pub env: Vec<(String, String)>,
+    }
+
     pub async fn run_pipe(
         pipe: &str,
         screenpipe_dir: PathBuf,
     ) -> Result<(tokio::process::Child, PipeState)> {
-        let bun_path = find_bun_path().ok_or_else(|| {
-            let err = anyhow::anyhow!("bun not found");
-            sentry::capture_error(&err.source().unwrap());
-            err
-        })?;
-        let pipe_dir = screenpipe_dir.join("pipes").join(pipe);
+        run_pipe_with(pipe, screenpipe_dir, &PipeRunOptions::default()).await
+    }
+
+    pub async fn run_pipe_with(
+        pipe: &str,
+        screenpipe_dir: PathBuf,
+        options: &PipeRunOptions,
+    ) -> Result<(tokio::process::Child, PipeState)> {
+        let mut pipe_dir = screenpipe_dir.join("pipes").join(pipe);
+        if options.dev {
+            // dev pipes are linked from their working directory, which the sandbox has to see
+            pipe_dir = tokio::fs::canonicalize(&pipe_dir).await?;
+        }
         let pipe_json_path = pipe_dir.join("pipe.json");
         let package_json_path = pipe_dir.join("package.json");
 
+        // Check if pipe is still enabled
+        let manifest = PipeManifest::load_from_dir(&pipe_dir).await?;
+        if let Some(manifest) = manifest.as_ref().filter(|_| !options.dev) {
+            debug!("checking if pipe is enabled from: {:?}", pipe_json_path);
+            if !manifest.enabled {
+                debug!("pipe {} is disabled, stopping", pipe);
+                anyhow::bail!("pipe is disabled");
+            }
+            debug!("pipe {} is enabled, continuing", pipe);
+        }
+        let runtime = manifest
+            .as_ref()
+            .map(|manifest| manifest.runtime)
+            .unwrap_or_default();
+
         debug!(
             "checking if pipe is a next.js project at: {:?}",
             package_json_path
         );
 
-        // First check if it's a Next.js project by looking at package.json
-        let is_nextjs = if package_json_path.exists() {
+        // First check if it's a Next.js project by looking at package.json, only bun runs those
+        let is_nextjs = if runtime == PipeRuntime::Bun && package_json_path.exists() {
             debug!("found package.json, checking for next.js dependency");
             let package_json = tokio::fs::read_to_string(&package_json_path).await?;
             let package_data: Value = serde_json::from_str(&package_json)?;
@@ -356,26 +368,18 @@ done
             false
         };
 
-        // Check if pipe is still enabled
-        if pipe_json_path.exists() {
-            debug!("checking if pipe is enabled from: {:?}", pipe_json_path);
-            let pipe_json = tokio::fs::read_to_string(&pipe_json_path).await?;
-            let pipe_config: Value = serde_json::from_str(&pipe_json)?;
-
-            if !pipe_config
-                .get("enabled")
-                .and_then(Value::as_bool)
-                .unwrap_or(false)
-            {
-                debug!("pipe {} is disabled, stopping", pipe);
-                anyhow::bail!("pipe is disabled");
-            }
-            debug!("pipe {} is enabled, continuing", pipe);
-        }
+        let permissions = manifest
+            .as_ref()
+            .and_then(|manifest| manifest.permissions.clone())
+            .map(PipePermissions::with_expanded_paths);
+        let entrypoints = manifest
+            .as_ref()
+            .map(|manifest| manifest.entrypoints.clone())
+            .unwrap_or_default();
 
-        // Prepare environment variables
+        // Prepare environment variables, the sandbox adds the allowed host variables
         debug!("preparing environment variables for pipe: {}", pipe);
-        let mut env_vars = std::env::vars().collect::<Vec<(String, String)>>();
+        let mut env_vars = Vec::new();
         env_vars.push((
             "SCREENPIPE_DIR".to_string(),
             screenpipe_dir.to_str().unwrap().to_string(),
@@ -385,27 +389,33 @@ done
             "PIPE_DIR".to_string(),
             pipe_dir.to_str().unwrap().to_string(),
         ));
+        // secrets only ever reach the pipe through its environment
+        env_vars.extend(get_pipe_secrets(&screenpipe_dir, pipe).await?);
+        env_vars.extend(options.env.iter().cloned());
 
         if is_nextjs {
             debug!(
                 "setting up next.js specific configuration for pipe: {}",
                 pipe
             );
+            let bun_path = find_bun_path().ok_or_else(|| {
+                let err = anyhow::anyhow!("bun not found");
+                sentry::capture_error(&err.source().unwrap());
+                err
+            })?;
 
             let mut assigned_port = None;
 
             // Handle Next.js specific setup including crons
-            if pipe_json_path.exists() {
+            if let Some(mut manifest) = manifest {
                 debug!("reading pipe.json for next.js configuration");
-                let pipe_json = tokio::fs::read_to_string(&pipe_json_path).await?;
-                let pipe_config: Value = serde_json::from_str(&pipe_json)?;
 
                 // Try to use user-configured port first
-                if let Some(user_port) = pipe_config.get("port").and_then(|p| p.as_u64()) {
+                if let Some(user_port) = manifest.port {
                     debug!("found user-configured port: {}", user_port);
                     // Verify port is available
-                    if is_port_available(user_port as u16) {
-                        assigned_port = Some(user_port as u16);
+                    if is_port_available(user_port) {
+                        assigned_port = Some(user_port);
                         debug!("user-configured port {} is available", user_port);
                     } else {
                         debug!(
@@ -421,71 +431,34 @@ done
                 info!("[{}] using port {} for next.js pipe", pipe, port);
 
                 // Update pipe.json with the actual port being used
-                let mut updated_config = pipe_config.clone();
-                updated_config["port"] = json!(port);
-                let updated_pipe_json = serde_json::to_string_pretty(&updated_config)?;
-                let mut file = File::create(&pipe_json_path).await?;
-                file.write_all(updated_pipe_json.as_bytes()).await?;
-                info!(
-                    "[{}] updated pipe.json with port configuration: {}",
-                    pipe, updated_pipe_json
-                );
-
-                env_vars.push(("PORT".to_string(), port.to_string()));
-
-                // Handle cron jobs if they exist
-                if let Some(crons) = pipe_config.get("crons").and_then(Value::as_array) {
+                if !options.dev {
+                    manifest.port = Some(port);
+                    manifest.save(&pipe_json_path).await?;
                     info!(
-                        "[{}] found {} cron jobs in configuration",
-                        pipe,
-                        crons.len()
+                        "[{}] updated pipe.json with port configuration: {}",
+                        pipe, port
                     );
-                    let base_url = format!("http://localhost:{}", port);
-                    debug!("[{}] using base url: {} for cron jobs", pipe, base_url);
-
-                    let cron_secret = generate_cron_secret();
-                    debug!("[{}] generated cron secret: {}", pipe, cron_secret);
-                    env_vars.push(("CRON_SECRET".to_string(), cron_secret.clone()));
-
-                    let mut handles = Vec::new();
-
-                    for cron in crons {
-                        let path = cron["path"]
-                            .as_str()
-                            .ok_or_else(|| anyhow::anyhow!("missing path"))?
-                            .to_string();
-                        let schedule = cron["schedule"]
-                            .as_str()
-                            .ok_or_else(|| anyhow::anyhow!("missing schedule"))?
-                            .to_string();
-
-                        let (tx, rx) = watch::channel(false);
-                        let handle = CronHandle { shutdown: tx };
-                        handles.push(handle);
-
-                        let base_url = base_url.clone();
-                        let pipe_clone = pipe.to_string();
-                        let secret_clone = cron_secret.clone();
-                        let screenpipe_dir = screenpipe_dir.clone();
-
-                        tokio::spawn(async move {
-                            run_cron_schedule(
-                                &pipe_clone,
-                                &base_url,
-                                &path,
-                                &secret_clone,
-                                &schedule,
-                                &screenpipe_dir,
-                                rx,
-                            )
-                            .await;
-                        });
-                    }
-
-                    // Store handles for later cleanup
-                    CRON_HANDLES.lock().await.insert(pipe.to_string(), handles);
                 }
 
+                env_vars.push(("PORT".to_string(), port.to_string()));
+
+                let cron_secret = generate_cron_secret();
+                env_vars.push(("CRON_SECRET".to_string(), cron_secret.clone()));
+                schedule_pipe_crons(
+                    CronContext {
+                        pipe: pipe.to_string(),
+                        pipe_dir: pipe_dir.clone(),
+                        base_url: Some(format!("http://localhost:{}", port)),
+                        secret: cron_secret,
+                        permissions: permissions.clone(),
+                        env: env_vars.clone(),
+                        runtime,
+                        handler: None,
+                    },
+                    &manifest.crons,
+                )
+                .await;
+
                 // Install dependencies using bun
                 info!("[{}] installing dependencies for next.js pipe", pipe);
 
@@ -520,8 +493,8 @@ done
                 env_vars.push(("PORT".to_string(), port.to_string()));
             }
 
-            // Try to build the Next.js project
-            let build_success = try_build_nextjs(&pipe_dir, &bun_path).await?;
+            // Try to build the Next.js project, dev runs rely on the dev server's reloads
+            let build_success = !options.dev && try_build_nextjs(&pipe_dir, &bun_path).await?;
 
             let port = env_vars
                 .iter()
@@ -545,24 +518,27 @@ done
             command.arg("run").arg("--bun");
 
             if build_success {
-                command.arg("start");
+                command.arg(entrypoints.start.as_deref().unwrap_or("start"));
             } else {
-                info!("[{}] falling back to dev mode due to build failure", pipe);
-                command.arg("dev");
+                if !options.dev {
+                    info!("[{}] falling back to dev mode due to build failure", pipe);
+                }
+                command.arg(entrypoints.dev.as_deref().unwrap_or("dev"));
             }
 
             command
                 .arg("--port")
                 .arg(port.to_string())
                 .current_dir(&pipe_dir)
-                .envs(env_vars)
                 .stdout(std::process::Stdio::piped())
                 .stderr(std::process::Stdio::piped());
 
-            let mut child = command.spawn()?;
+            let sandbox =
+                PipeSandbox::prepare(pipe, &pipe_dir, &bun_path, permissions, env_vars).await?;
+            let mut child = sandbox.spawn(&mut command).await?;
 
             debug!("[{}] streaming logs for next.js pipe", pipe);
-            stream_logs(pipe, &mut child).await?;
+            stream_logs(pipe, open_pipe_logger(pipe, &pipe_dir).await, &mut child).await?;
 
             let child_pid = child.id().expect("Failed to get child PID") as u32;
             let parent_pid = std::process::id();
@@ -586,142 +562,140 @@ done
         }
 
         // If it's not a Next.js project, run as regular pipe
-        let main_module = find_pipe_file(&pipe_dir)?;
-        info!("[{}] executing pipe: {:?}", pipe, main_module);
+        let main_module = runtime.find_main(&pipe_dir, entrypoints.main.as_deref())?;
+        info!(
+            "[{}] executing pipe with {}: {:?}",
+            pipe, runtime, main_module
+        );
+
+        // plain pipes serving http declare their port in pipe.json
+        let cron_secret = generate_cron_secret();
+        env_vars.push(("CRON_SECRET".to_string(), cron_secret.clone()));
+        if let Some(manifest) = &manifest {
+            if let Some(port) = manifest.port {
+                env_vars.push(("PORT".to_string(), port.to_string()));
+            }
+            schedule_pipe_crons(
+                CronContext {
+                    pipe: pipe.to_string(),
+                    pipe_dir: pipe_dir.clone(),
+                    base_url: manifest
+                        .port
+                        .map(|port| format!("http://localhost:{}", port)),
+                    secret: cron_secret,
+                    permissions: permissions.clone(),
+                    env: env_vars.clone(),
+                    runtime,
+                    handler: None,
+                },
+                &manifest.crons,
+            )
+            .await;
+        }
 
         env_vars.push((
             "PIPE_FILE".to_string(),
             main_module.to_str().unwrap().to_string(),
         ));
 
-        let mut child = Command::new(&bun_path)
-            .arg("run")
-            .arg("--bun")
-            .arg(&main_module)
-            .envs(env_vars)
+        let (mut command, runtime_path) = runtime.command(&pipe_dir, &main_module)?;
+        command
             .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::piped())
-            .spawn()?;
+            .stderr(std::process::Stdio::piped());
+
+        let sandbox =
+            PipeSandbox::prepare(pipe, &pipe_dir, &runtime_path, permissions, env_vars).await?;
+        let mut child = sandbox.spawn(&mut command).await?;
 
         // Stream logs
-        stream_logs(pipe, &mut child).await?;
+        stream_logs(pipe, open_pipe_logger(pipe, &pipe_dir).await, &mut child).await?;
 
         let child_id = child.id().unwrap();
         Ok((child, PipeState::Pid(child_id as i32))) // Return 0 or handle port differently for non-Next.js projects
     }
 
-    async fn stream_logs(pipe: &str, child: &mut tokio::process::Child) -> Result<()> {
+    /// Forwards the output of `child` to tracing and, for pipes, to their log files.
+    async fn stream_logs(
+        pipe: &str,
+        logger: Option<PipeLogger>,
+        child: &mut tokio::process::Child,
+    ) -> Result<()> {
         let stdout = child.stdout.take().expect("failed to get stdout");
         let stderr = child.stderr.take().expect("failed to get stderr");
 
-        let pipe_clone = pipe.to_string();
-
-        // Spawn tasks to handle stdout and stderr
-        let _stdout_handle = tokio::spawn(async move {
-            let reader = BufReader::new(stdout);
-            let mut lines = reader.lines();
-            while let Ok(Some(line)) = lines.next_line().await {
-                info!("[{}] {}", pipe_clone, line);
-            }
-        });
-
-        let pipe_clone = pipe.to_string();
-
-        let _stderr_handle = tokio::spawn(async move {
-            // Create static HashMaps for efficient lookups
-            static INFO_PATTERNS: Lazy<HashSet<&'static str>> = Lazy::new(|| {
-                let mut set = HashSet::new();
-                // Development related
-                set.insert("Download");
-                set.insert("Task dev");
-                set.insert("$ next dev");
-                set.insert("ready started server");
-                set.insert("Local:");
-                set.insert("Webpack is configured");
-                set.insert("See instructions");
-                set.insert("https://nextjs.org");
-                set.insert("⚠ See instructions");
-                set.insert("$ next start");
-                set.insert("[bun install]");
-                set.insert("Saved lockfile");
-                set.insert("Resolved, downloaded");
-                set.insert("Installing");
-                set.insert("Successfully installed");
-                set.insert("packages installed");
-                set.insert("Fetching");
-                set.insert("Resolving");
-                // Frontend console patterns
-                set.insert("[LOG]");
-                set.insert("console.log");
-                set.insert("] ");
-                set.insert("›");
-                set.insert("<");
-                set.insert("Warning:");
-                set.insert("render@");
-                set.insert("webpack");
-                set.insert("HMR");
-                set.insert("[HMR]");
-                set
-            });
-
-            static ERROR_PATTERNS: Lazy<HashSet<&'static str>> = Lazy::new(|| {
-                let mut set = HashSet::new();
-                set.insert("TypeError:");
-                set.insert("ReferenceError:");
-                set.insert("SyntaxError:");
-                set.insert("Error:");
-                set.insert("Uncaught");
-                set.insert("Failed to compile");
-                set.insert("ENOENT");
-                set.insert("FATAL");
-                set
-            });
+        forward_output(pipe, logger, Box::new(stdout), Box::new(stderr));
 
-            let reader = BufReader::new(stderr);
-            let mut lines = reader.lines();
-
-            while let Ok(Some(line)) = lines.next_line().await {
-                let line_lower = line.to_lowercase(); // Convert once for case-insensitive matching
-
-                // Quick checks first
-                if line.trim().is_empty() || line.contains("console.") {
-                    info!("[{}] {}", pipe_clone, line);
-                    continue;
-                }
+        info!("pipe execution completed successfully [{}]", pipe);
+        Ok(())
+    }
 
-                // Check for error patterns first (higher priority)
-                if ERROR_PATTERNS
-                    .iter()
-                    .any(|&pattern| line_lower.contains(&pattern.to_lowercase()))
-                {
-                    error!("[{}] {}", pipe_clone, line);
-                    sentry::capture_message(
-                        &format!("[{}] {}", pipe_clone, line),
-                        sentry::Level::Error,
-                    );
-                    continue;
-                }
+    /// Forwards the output of a pipe that isn't a child process, such as a wasm pipe, to
+    /// tracing and the logs of the pipe in `pipe_dir`.
+    pub async fn stream_pipe_output(
+        pipe: &str,
+        pipe_dir: &Path,
+        stdout: impl AsyncRead + Send + Unpin + 'static,
+        stderr: impl AsyncRead + Send + Unpin + 'static,
+    ) {
+        let logger = open_pipe_logger(pipe, pipe_dir).await;
+        forward_output(pipe, logger, Box::new(stdout), Box::new(stderr));
+    }
 
-                // Then check for info patterns
-                if INFO_PATTERNS
-                    .iter()
-                    .any(|&pattern| line_lower.contains(&pattern.to_lowercase()))
-                {
-                    info!("[{}] {}", pipe_clone, line);
-                    continue;
+    fn forward_output(
+        pipe: &str,
+        logger: Option<PipeLogger>,
+        stdout: Box<dyn AsyncRead + Send + Unpin>,
+        stderr: Box<dyn AsyncRead + Send + Unpin>,
+    ) {
+        for (stream, output) in [
+            (PipeLogStream::Stdout, stdout),
+            (PipeLogStream::Stderr, stderr),
+        ] {
+            let pipe = pipe.to_string();
+            let logger = logger.clone();
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(output).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    let level = PipeLogLevel::guess(stream, &line);
+                    match level {
+                        PipeLogLevel::Error => {
+                            error!("[{}] {}", pipe, line);
+                            sentry::capture_message(
+                                &format!("[{}] {}", pipe, line),
+                                sentry::Level::Error,
+                            );
+                        }
+                        PipeLogLevel::Warn => warn!("[{}] {}", pipe, line),
+                        PipeLogLevel::Info => info!("[{}] {}", pipe, line),
+                        PipeLogLevel::Debug => debug!("[{}] {}", pipe, line),
+                    }
+                    if let Some(logger) = &logger {
+                        logger.log(stream, level, &line).await;
+                    }
                 }
+            });
+        }
+    }
 
-                // Default to warning for unknown patterns
-                warn!("[{}] {}", pipe_clone, line);
+    async fn open_pipe_logger(pipe: &str, pipe_dir: &Path) -> Option<PipeLogger> {
+        match PipeLogger::open(pipe, pipe_dir).await {
+            Ok(logger) => Some(logger),
+            Err(e) => {
+                warn!(
+                    "[{}] failed to open pipe logs, only forwarding to tracing: {}",
+                    pipe, e
+                );
+                None
             }
-        });
-
-        info!("pipe execution completed successfully [{}]", pipe);
-        Ok(())
+        }
     }
 
     // Add this helper function for retrying installations
-    async fn retry_install(bun_path: &Path, dest_dir: &Path, max_retries: u32) -> Result<()> {
+    pub(crate) async fn retry_install(
+        bun_path: &Path,
+        dest_dir: &Path,
+        max_retries: u32,
+    ) -> Result<()> {
         let mut attempt = 0;
         let mut last_error = None;
 
@@ -734,7 +708,7 @@ done
                 .spawn()?;
 
             // Stream logs for npm install
-            if let Ok(()) = stream_logs("bun install", &mut install_child).await {
+            if let Ok(()) = stream_logs("bun install", None, &mut install_child).await {
                 let status = install_child.wait().await?;
                 if status.success() {
                     return Ok(());
@@ -758,11 +732,30 @@ done
         Err(last_error.unwrap_or_else(|| anyhow::anyhow!("installation failed")))
     }
 
+    /// Installs the dependencies of the pipe in `pipe_dir` for its runtime, see
+    /// [`PipeRuntime::install_dependencies`].
+    pub async fn install_pipe_dependencies(pipe_dir: &Path) -> Result<()> {
+        let runtime = PipeManifest::load_from_dir(pipe_dir)
+            .await?
+            .map(|manifest| manifest.runtime)
+            .unwrap_or_default();
+        runtime.install_dependencies(pipe_dir).await
+    }
+
     pub async fn download_pipe(source: &str, screenpipe_dir: PathBuf) -> anyhow::Result<PathBuf> {
+        install_pipe(source, &PipeInstallOptions::default(), screenpipe_dir).await
+    }
+
+    /// Installs the pipe at `source` (see [`PipeSource`]) into `screenpipe_dir`, refusing it
+    /// unless it matches `options`, and records what was installed in its `pipe.lock`.
+    pub async fn install_pipe(
+        source: &str,
+        options: &PipeInstallOptions,
+        screenpipe_dir: PathBuf,
+    ) -> anyhow::Result<PathBuf> {
         info!("Processing pipe from source: {}", source);
 
-        let pipe_name =
-            sanitize_pipe_name(Path::new(source).file_name().unwrap().to_str().unwrap());
+        let pipe_name = sanitize_pipe_name(&PipeSource::parse(source)?.pipe_name());
         let dest_dir = screenpipe_dir.join("pipes").join(&pipe_name);
 
         debug!("Destination directory: {:?}", dest_dir);
@@ -778,38 +771,29 @@ done
             None
         };
 
-        // Create temp directory for download
-        let temp_dir = dest_dir.with_extension("_temp");
-        tokio::fs::create_dir_all(&temp_dir).await?;
-
         // Download to temp directory first
-        let download_result = if let Ok(parsed_url) = Url::parse(source) {
-            debug!("Source is a URL: {}", parsed_url);
-            if parsed_url.host_str() == Some("github.com") {
-                download_github_folder(&parsed_url, &temp_dir).await
-            } else {
-                anyhow::bail!("Unsupported URL format");
-            }
-        } else {
-            debug!("Source is a local path");
-            let source_path = Path::new(source);
-            if !source_path.exists() || !source_path.is_dir() {
-                anyhow::bail!("Invalid local source path");
-            }
-            copy_dir_all(source_path, &temp_dir).await
-        };
-
-        // remove temp dir if download failed
-        if let Err(e) = download_result {
+        let temp_dir = dest_dir.with_extension("_temp");
+        if temp_dir.exists() {
             tokio::fs::remove_dir_all(&temp_dir).await?;
-            error!("Failed to download pipe: {}", e);
         }
+        let lock = match fetch_pipe(source, options, &temp_dir).await {
+            Ok(lock) => lock,
+            Err(e) => {
+                // remove temp dir if download failed
+                if temp_dir.exists() {
+                    tokio::fs::remove_dir_all(&temp_dir).await?;
+                }
+                error!("Failed to download pipe: {}", e);
+                return Err(e);
+            }
+        };
 
         // If download successful, move temp dir to final location
         if dest_dir.exists() {
             tokio::fs::remove_dir_all(&dest_dir).await?;
         }
         tokio::fs::rename(&temp_dir, &dest_dir).await?;
+        write_pipe_lock(&dest_dir, &lock).await?;
 
         // Restore or merge pipe.json if needed
         if let Some(ref existing_config) = existing_config {
@@ -866,17 +850,19 @@ done
             }
         }
 
+        // Dependencies are mandatory, bun install is retried
+        install_pipe_dependencies(&dest_dir).await?;
+
         // After downloading/copying the pipe, check if it's a Next.js project
         let package_json_path = dest_dir.join("package.json");
-        if package_json_path.exists() {
+        let runtime = PipeManifest::load_from_dir(&dest_dir)
+            .await?
+            .map(|manifest| manifest.runtime)
+            .unwrap_or_default();
+        if runtime == PipeRuntime::Bun && package_json_path.exists() {
             let package_json = tokio::fs::read_to_string(&package_json_path).await?;
             let package_data: Value = serde_json::from_str(&package_json)?;
 
-            let bun_path = find_bun_path().ok_or_else(|| anyhow::anyhow!("bun not found"))?;
-
-            // Make bun install mandatory for all package.json pipes with retries
-            retry_install(&bun_path, &dest_dir, 3).await?;
-
             if package_data["dependencies"].get("next").is_some() {
                 info!("Detected Next.js project, setting up for production");
                 // Update pipe.json to indicate it's a Next.js project
@@ -900,6 +886,72 @@ done
         Ok(dest_dir)
     }
 
+    /// Fetches `source` into `dest` and checks it against `options` and the manifest schema,
+    /// returning the lock entry of what was fetched. `dest` is left for the caller to remove
+    /// on error.
+    pub async fn fetch_pipe(
+        source: &str,
+        options: &PipeInstallOptions,
+        dest: &Path,
+    ) -> anyhow::Result<PipeLock> {
+        let parsed = PipeSource::parse(source)?;
+        tokio::fs::create_dir_all(dest).await?;
+
+        let (resolved, archive_sha256) = match &parsed {
+            PipeSource::GithubFolder(url) => {
+                download_github_folder(url, dest).await?;
+                (source.to_string(), None)
+            }
+            PipeSource::LocalDir(path) => {
+                copy_dir_all(path, dest).await?;
+                (source.to_string(), None)
+            }
+            PipeSource::Archive(url) => copy_fetched(fetch_archive_url(url).await?, dest).await?,
+            PipeSource::LocalArchive(path) => {
+                copy_fetched(fetch_local_archive(path).await?, dest).await?
+            }
+            PipeSource::Git {
+                remote,
+                rev,
+                subdir,
+            } => {
+                copy_fetched(
+                    fetch_git(remote, rev.as_deref(), subdir.as_deref()).await?,
+                    dest,
+                )
+                .await?
+            }
+        };
+
+        let sha256 = match archive_sha256 {
+            Some(sha256) => sha256,
+            None => hash_pipe_dir(dest).await?,
+        };
+        verify_pipe_integrity(&sha256, options)?;
+        debug!("pipe from {} has sha256 {}", source, sha256);
+
+        // reject invalid manifests before replacing the installed pipe
+        PipeManifest::load_from_dir(dest).await?;
+
+        Ok(PipeLock {
+            source: source.to_string(),
+            kind: parsed.kind(),
+            resolved,
+            sha256,
+            signature: options.signature.clone(),
+            public_key: options.public_key.clone(),
+            installed_at: chrono::Utc::now(),
+        })
+    }
+
+    async fn copy_fetched(
+        fetched: FetchedPipe,
+        dest: &Path,
+    ) -> anyhow::Result<(String, Option<String>)> {
+        copy_dir_all(&fetched.dir, dest).await?;
+        Ok((fetched.resolved, fetched.archive_sha256))
+    }
+
     async fn copy_dir_all(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> anyhow::Result<()> {
         let src = src.as_ref();
         let dst = dst.as_ref();
@@ -1119,27 +1171,6 @@ done
         anyhow::bail!("Invalid GitHub URL format")
     }
 
-    fn find_pipe_file(pipe_dir: &Path) -> anyhow::Result<PathBuf> {
-        for entry in fs::read_dir(pipe_dir)? {
-            let entry = entry?;
-            let file_name = entry.file_name();
-            let file_name_str = file_name.to_str().unwrap();
-            if (file_name_str == "pipe.js" || file_name_str == "pipe.ts")
-                && !is_hidden_file(&file_name)
-            {
-                return Ok(entry.path());
-            }
-        }
-        anyhow::bail!("No pipe.js/pipe.ts found in the pipe/dist directory")
-    }
-
-    fn is_hidden_file(file_name: &std::ffi::OsStr) -> bool {
-        file_name
-            .to_str()
-            .map(|s| s.starts_with('.') || s == "Thumbs.db")
-            .unwrap_or(false)
-    }
-
     #[cfg(not(windows))]
     const BUN_EXECUTABLE_NAME: &str = "bun";
 
@@ -1215,190 +1246,6 @@ done
         None
     }
 
-    // Add this function to handle cron state persistence
-    pub async fn get_last_cron_execution(
-        pipe_dir: &Path,
-        path: &str,
-    ) -> Result<Option<SystemTime>> {
-        let state_file = pipe_dir.join(".cron_state.json");
-
-        if !state_file.exists() {
-            return Ok(None);
-        }
-
-        let content = tokio::fs::read_to_string(state_file).await?;
-        let state: Value = serde_json::from_str(&content)?;
-
-        if let Some(last_run) = state.get(path).and_then(|v| v.as_u64()) {
-            Ok(Some(UNIX_EPOCH + std::time::Duration::from_secs(last_run)))
-        } else {
-            Ok(None)
-        }
-    }
-
-    // Add this function to save cron execution time
-    pub async fn save_cron_execution(pipe_dir: &Path, path: &str) -> Result<()> {
-        let state_file = pipe_dir.join(".cron_state.json");
-
-        let mut state: Value = if state_file.exists() {
-            let content = tokio::fs::read_to_string(&state_file).await?;
-            serde_json::from_str(&content)?
-        } else {
-            json!({})
-        };
-
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-
-        if let Some(obj) = state.as_object_mut() {
-            obj.insert(path.to_string(), json!(now));
-        }
-
-        let mut file = tokio::fs::OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(state_file)
-            .await?;
-
-        file.write_all(serde_json::to_string_pretty(&state)?.as_bytes())
-            .await?;
-        Ok(())
-    }
-
-    // Update the run_cron_schedule function
-    async fn run_cron_schedule(
-        pipe: &str,
-        base_url: &str,
-        path: &str,
-        secret: &str,
-        schedule: &str,
-        screenpipe_dir: &Path,
-        mut shutdown: watch::Receiver<bool>,
-    ) {
-        let schedule = match cron::Schedule::from_str(schedule) {
-            Ok(s) => s,
-            Err(e) => {
-                let err_msg = format!("invalid cron schedule: {}", e);
-                error!("{}", err_msg);
-                sentry::capture_error(&anyhow::anyhow!(err_msg).source().unwrap());
-                return;
-            }
-        };
-
-        let client = Client::new();
-        let mut headers = HeaderMap::new();
-        headers.insert(
-            AUTHORIZATION,
-            HeaderValue::from_str(&format!("Bearer {}", secret)).unwrap(),
-        );
-
-        // Get pipe directory for state persistence
-        let pipe_dir = screenpipe_dir.join("pipes").join(pipe);
-
-        loop {
-            // Get last execution time at the start of each loop
-            let last_run = match get_last_cron_execution(&pipe_dir, path).await {
-                Ok(time) => time,
-                Err(e) => {
-                    error!("failed to get last cron execution: {}", e);
-                    None
-                }
-            };
-
-            let now = chrono::Local::now();
-            let next = if let Some(last) = last_run {
-                // Get next occurrence after the last execution
-                let last_chrono = chrono::DateTime::<chrono::Local>::from(last);
-                schedule.after(&last_chrono).next()
-            } else {
-                schedule.after(&now).next()
-            };
-
-            let next = match next {
-                Some(next) => next,
-                None => {
-                    error!("no next execution time found for cron schedule");
-                    break;
-                }
-            };
-
-            if next <= now {
-                info!(
-                    "next execution time is before or equal to the current time, recalculating..."
-                );
-                continue;
-            }
-            let duration = match (next - now).to_std() {
-                Ok(duration) => duration,
-                Err(e) => {
-                    error!("invalid duration: {}", e);
-                    continue; // falling back to minute is messing with cron schedule
-                }
-            };
-
-            info!(
-                "next cron execution for pipe {} at path {} in {} seconds",
-                pipe,
-                path,
-                duration.as_secs()
-            );
-
-            // Wait for either the next execution time or shutdown signal
-            tokio::select! {
-                _ = tokio::time::sleep(duration) => {
-                    info!("executing cron job for pipe {} at path {}", pipe, path);
-                    match client
-                        .get(format!("{}{}", base_url, path))
-                        .headers(headers.clone())
-                        .send()
-                        .await
-                    {
-                        Ok(res) => {
-                            if !res.status().is_success() {
-                                let err_msg = format!("cron job failed with status: {}", res.status());
-                                error!("{}", err_msg);
-                                if let Ok(text) = res.text().await {
-                                    error!("error response: {}", text);
-                                    sentry::capture_message(
-                                        &format!("{}: {}", err_msg, text),
-                                        sentry::Level::Error
-                                    );
-                                } else {
-                                    sentry::capture_message(&err_msg, sentry::Level::Error);
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            let err_msg = format!("failed to execute cron job: {}", e);
-                            error!("{}", err_msg);
-                            sentry::capture_error(&e);
-                        }
-                    }
-                }
-                Ok(()) = shutdown.changed() => {
-                    if *shutdown.borrow() {
-                        info!("shutting down cron job for pipe at path: {}", path);
-                        break;
-                    }
-                }
-            }
-        }
-    }
-
-    pub async fn cleanup_pipe_crons(pipe: &str) -> Result<()> {
-        if let Some(handles) = CRON_HANDLES.lock().await.remove(pipe) {
-            info!("cleaning up {} cron jobs for pipe {}", handles.len(), pipe);
-            for handle in handles {
-                handle.stop();
-            }
-            info!("stopped all cron jobs for pipe: {}", pipe);
-        }
-        Ok(())
-    }
-
     async fn try_build_nextjs(pipe_dir: &Path, bun_path: &Path) -> Result<bool> {
         info!(
             "checking if i need to build the next.js project in: {:?}",
@@ -1502,6 +1349,12 @@ done
         // Remove the temporary zip file
         tokio::fs::remove_file(&temp_zip).await?;
 
+        // reject invalid manifests before replacing the installed pipe
+        if let Err(e) = PipeManifest::load_from_dir(&temp_dir).await {
+            tokio::fs::remove_dir_all(&temp_dir).await?;
+            return Err(e);
+        }
+
         // Move temp dir to final location
         if dest_dir.exists() {
             tokio::fs::remove_dir_all(&dest_dir).await?;
@@ -2,7 +2,18 @@
 #[cfg(test)]
 mod tests {
     use chrono::{TimeZone, Utc};
-    use screenpipe_core::{download_pipe, get_last_cron_execution, run_pipe, save_cron_execution};
+    use screenpipe_core::{
+        cleanup_pipe_crons, download_pipe, get_cron_runs, get_last_cron_execution,
+        get_pipe_secrets, install_pipe, list_pipe_secrets, list_pipe_versions, open_secret,
+        pipe_log_dir, read_pipe_lock, read_pipe_logs, remove_pipe_secret, remove_pipe_secrets,
+        restore_pipe_version, run_cron_now, run_pipe, save_cron_execution, schedule_pipe_crons,
+        seal_secret, set_pipe_secret, set_secrets_key_source, snapshot_pipe_version,
+        subscribe_pipe_logs, ContentPermission, CronContext, CronOverlap, CronRunStatus,
+        CronTrigger, Enforcement, IntegrityError, PipeCron, PipeFieldType, PipeInstallOptions,
+        PipeLogLevel, PipeLogQuery, PipeLogStream, PipeLogger, PipeManifest, PipePermissions,
+        PipeSandbox, PipeSource, PipeSourceKind, SecretsKeySource, MAX_PIPE_VERSIONS,
+        PIPE_SECRETS_FILE,
+    };
     use serde_json::json;
     use std::sync::Arc;
     use std::time::{Duration, SystemTime, UNIX_EPOCH};
@@ -18,6 +29,8 @@ mod tests {
 
     fn init() {
         INIT.call_once(|| {
+            // keep the secrets key next to the temp stores, not in the keyring of whoever runs this
+            set_secrets_key_source(SecretsKeySource::File);
             let subscriber = Subscriber::builder()
                 .with_env_filter("debug")
                 .with_test_writer()
@@ -495,4 +508,1118 @@ mod tests {
             time_diff
         );
     }
+
+    #[test]
+    fn test_pipe_permissions_parsing() {
+        assert_eq!(
+            PipePermissions::from_pipe_config(&json!({ "enabled": true })).unwrap(),
+            None
+        );
+
+        let permissions = PipePermissions::from_pipe_config(&json!({
+            "permissions": {
+                "env": ["OPENAI_API_KEY"],
+                "read": ["~/notes", "/srv/data"],
+                "network": ["api.openai.com", "localhost:3030"],
+                "limits": { "memory_mb": 256 }
+            }
+        }))
+        .unwrap()
+        .unwrap();
+        assert_eq!(permissions.env, vec!["OPENAI_API_KEY".to_string()]);
+        assert_eq!(permissions.read[0], dirs::home_dir().unwrap().join("notes"));
+        assert_eq!(permissions.read[1], PathBuf::from("/srv/data"));
+        assert!(permissions.write.is_empty());
+        assert_eq!(permissions.limits.memory_mb, Some(256));
+        assert_eq!(permissions.limits.cpu_percent, None);
+
+        assert!(
+            PipePermissions::from_pipe_config(&json!({ "permissions": { "env": "PATH" } }))
+                .is_err()
+        );
+    }
+
+    async fn run_sandboxed(sandbox: PipeSandbox, script: &str) -> std::process::Output {
+        let mut command = tokio::process::Command::new("/bin/sh");
+        command
+            .arg("-c")
+            .arg(script)
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped());
+        sandbox
+            .spawn(&mut command)
+            .await
+            .unwrap()
+            .wait_with_output()
+            .await
+            .unwrap()
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_pipe_sandbox_env() {
+        let temp_dir = TempDir::new().unwrap();
+        let pipe_dir = setup_test_pipe(&temp_dir, "sandbox-env", "").await;
+        std::env::set_var("SCREENPIPE_SANDBOX_TEST_SECRET", "hunter2");
+        let pipe_env = vec![("PIPE_ID".to_string(), "sandbox-env".to_string())];
+        let script = "echo \"$PIPE_ID:$SCREENPIPE_SANDBOX_TEST_SECRET:$TMPDIR\"";
+
+        let sandbox = PipeSandbox::prepare(
+            "sandbox-env",
+            &pipe_dir,
+            &PathBuf::from("/bin/sh"),
+            None,
+            pipe_env.clone(),
+        )
+        .await
+        .unwrap();
+        assert!(sandbox.report().env.contains(&"PIPE_ID".to_string()));
+        assert!(!sandbox
+            .report()
+            .env
+            .contains(&"SCREENPIPE_SANDBOX_TEST_SECRET".to_string()));
+        let output = run_sandboxed(sandbox, script).await;
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout).trim(),
+            format!("sandbox-env::{}", pipe_dir.join(".tmp").display())
+        );
+
+        // declared variables are passed through
+        let permissions = PipePermissions {
+            env: vec!["SCREENPIPE_SANDBOX_TEST_SECRET".to_string()],
+            ..Default::default()
+        };
+        let sandbox = PipeSandbox::prepare(
+            "sandbox-env",
+            &pipe_dir,
+            &PathBuf::from("/bin/sh"),
+            Some(permissions),
+            pipe_env,
+        )
+        .await
+        .unwrap();
+        let output = run_sandboxed(sandbox, script).await;
+        assert!(String::from_utf8_lossy(&output.stdout).starts_with("sandbox-env:hunter2:"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_pipe_sandbox_filesystem() {
+        let temp_dir = TempDir::new().unwrap();
+        let pipe_dir = setup_test_pipe(&temp_dir, "sandbox-fs", "").await;
+        let outside = TempDir::new().unwrap();
+        tokio::fs::write(outside.path().join("secret.txt"), "secret")
+            .await
+            .unwrap();
+        let shared = TempDir::new().unwrap();
+        tokio::fs::write(shared.path().join("shared.txt"), "shared")
+            .await
+            .unwrap();
+
+        let permissions = PipePermissions {
+            read: vec![shared.path().to_path_buf()],
+            limits: screenpipe_core::ResourceLimits {
+                max_open_files: Some(64),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let sandbox = PipeSandbox::prepare(
+            "sandbox-fs",
+            &pipe_dir,
+            &PathBuf::from("/bin/sh"),
+            Some(permissions),
+            Vec::new(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            sandbox.report().open_files,
+            Enforcement::Enforced {
+                by: "rlimit".to_string()
+            }
+        );
+        if !matches!(sandbox.report().filesystem, Enforcement::Enforced { .. }) {
+            eprintln!("landlock not available, skipping filesystem sandbox test");
+            return;
+        }
+
+        let script = format!(
+            "echo own > {pipe}/own.txt && cat {pipe}/pipe.ts {shared}/shared.txt \
+             && ulimit -n && ! cat {outside}/secret.txt && ! touch {shared}/new.txt",
+            pipe = pipe_dir.display(),
+            shared = shared.path().display(),
+            outside = outside.path().display(),
+        );
+        let output = run_sandboxed(sandbox, &script).await;
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("shared"));
+        assert!(stdout.contains("64"));
+        assert!(!stdout.contains("secret"));
+        assert!(pipe_dir.join("own.txt").exists());
+        assert!(!shared.path().join("new.txt").exists());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_pipe_sandbox_without_permissions() {
+        let temp_dir = TempDir::new().unwrap();
+        let pipe_dir = setup_test_pipe(&temp_dir, "sandbox-default", "").await;
+        let outside = TempDir::new().unwrap();
+        tokio::fs::write(outside.path().join("secret.txt"), "secret")
+            .await
+            .unwrap();
+
+        let sandbox = PipeSandbox::prepare(
+            "sandbox-default",
+            &pipe_dir,
+            &PathBuf::from("/bin/sh"),
+            None,
+            Vec::new(),
+        )
+        .await
+        .unwrap();
+        if !matches!(sandbox.report().filesystem, Enforcement::Enforced { .. }) {
+            eprintln!("landlock not available, skipping default sandbox test");
+            return;
+        }
+
+        // the environment of other processes, here the test's, stays out of reach
+        let script = format!(
+            "echo own > {pipe}/own.txt && cat /proc/self/status > /dev/null \
+             && ! cat {outside}/secret.txt && ! cat /proc/{parent}/environ",
+            pipe = pipe_dir.display(),
+            outside = outside.path().display(),
+            parent = std::process::id(),
+        );
+        let output = run_sandboxed(sandbox, &script).await;
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert!(!String::from_utf8_lossy(&output.stdout).contains("secret"));
+        assert!(pipe_dir.join("own.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn test_pipe_sandbox_network_allowlist() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let temp_dir = TempDir::new().unwrap();
+        let pipe_dir = setup_test_pipe(&temp_dir, "sandbox-net", "").await;
+        let permissions = PipePermissions {
+            network: Some(vec![
+                "*.screenpi.pe".to_string(),
+                "localhost:3030".to_string(),
+            ]),
+            ..Default::default()
+        };
+        let sandbox = PipeSandbox::prepare(
+            "sandbox-net",
+            &pipe_dir,
+            &PathBuf::from("/bin/sh"),
+            Some(permissions),
+            Vec::new(),
+        )
+        .await
+        .unwrap();
+        let proxy = sandbox
+            .env()
+            .iter()
+            .find(|(name, _)| name == "HTTPS_PROXY")
+            .map(|(_, value)| value.trim_start_matches("http://").to_string())
+            .expect("network allowlist should set a proxy");
+        // the proxy keeps running with the pipe
+        assert!(run_sandboxed(sandbox, "exit 0").await.status.success());
+
+        let mut stream = tokio::net::TcpStream::connect(proxy).await.unwrap();
+        stream
+            .write_all(b"CONNECT example.com:443 HTTP/1.1\r\nHost: example.com:443\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 403"), "{}", response);
+
+        screenpipe_core::cleanup_pipe_sandbox("sandbox-net").await;
+    }
+
+    #[test]
+    fn test_pipe_manifest_roundtrip() {
+        let config = json!({
+            "manifest_version": 1,
+            "enabled": true,
+            "port": 3100,
+            "min_screenpipe_version": "0.1.0",
+            "entrypoints": { "main": "src/index.ts" },
+            "fields": [
+                { "name": "interval", "type": "number", "default": 60 },
+                { "name": "contentType", "type": "contentType", "default": "ocr", "value": "audio" },
+                { "name": "summaryTime", "type": "time", "default": "09:30" }
+            ],
+            "crons": [{ "path": "/api/log", "schedule": "0 */5 * * * *" }],
+            "permissions": { "content_types": ["ocr", "audio"], "raw_sql": true },
+            "customKey": { "kept": true }
+        });
+
+        let manifest = PipeManifest::validate(&config).unwrap();
+        assert!(manifest.enabled);
+        assert_eq!(manifest.port, Some(3100));
+        assert_eq!(manifest.fields[1].field_type, PipeFieldType::ContentType);
+        assert_eq!(
+            manifest.field("contentType").unwrap().current_value(),
+            Some(&json!("audio"))
+        );
+        assert_eq!(
+            manifest.field("interval").unwrap().current_value(),
+            Some(&json!(60))
+        );
+        let permissions = manifest.permissions.as_ref().unwrap();
+        assert_eq!(
+            permissions.content_types,
+            vec![ContentPermission::Ocr, ContentPermission::Audio]
+        );
+        assert!(permissions.raw_sql);
+        assert!(!permissions.input_control);
+
+        // saving keeps unknown keys and doesn't add empty ones
+        let saved = serde_json::to_value(&manifest).unwrap();
+        assert_eq!(saved["customKey"], json!({ "kept": true }));
+        assert_eq!(saved["permissions"], config["permissions"]);
+        assert!(saved.get("source").is_none());
+        assert_eq!(PipeManifest::validate(&saved).unwrap(), manifest);
+
+        // manifests without a version are read as version 1
+        let legacy = PipeManifest::validate(&json!({ "enabled": false })).unwrap();
+        assert_eq!(legacy.manifest_version, 1);
+    }
+
+    #[test]
+    fn test_pipe_manifest_errors() {
+        let issue_paths = |config: serde_json::Value| -> Vec<String> {
+            PipeManifest::validate(&config)
+                .unwrap_err()
+                .issues
+                .into_iter()
+                .map(|issue| issue.path)
+                .collect()
+        };
+
+        let paths = issue_paths(json!({
+            "enabled": "yes",
+            "fields": [
+                { "name": "color", "type": "colour" },
+                { "name": "notify", "type": "boolean", "default": "yes" },
+                { "name": "at", "type": "time", "value": "25:00" }
+            ],
+            "crons": [{ "path": "api/log", "schedule": "0 * * * * *" }],
+            "permissions": { "raw_sql": "yes", "content_types": ["screenshots"] }
+        }));
+        for expected in [
+            "/enabled",
+            "/fields/0/type",
+            "/fields/1/default",
+            "/fields/2/value",
+            "/crons/0/path",
+            "/permissions/raw_sql",
+            "/permissions/content_types/0",
+        ] {
+            assert!(
+                paths.iter().any(|path| path == expected),
+                "missing issue for {} in {:?}",
+                expected,
+                paths
+            );
+        }
+
+        let paths = issue_paths(json!({
+            "manifest_version": 99,
+            "min_screenpipe_version": "99.0.0",
+            "entrypoints": { "main": "../outside.ts" },
+            "fields": [
+                { "name": "interval", "type": "number" },
+                { "name": "interval", "type": "number" }
+            ],
+            "crons": [{ "path": "/api/log", "schedule": "every five minutes" }]
+        }));
+        assert_eq!(
+            paths,
+            vec![
+                "/manifest_version",
+                "/min_screenpipe_version",
+                "/fields/1/name",
+                "/crons/0/schedule",
+                "/entrypoints/main",
+            ]
+        );
+
+        let message = PipeManifest::validate(&json!({ "port": 0 }))
+            .unwrap_err()
+            .to_string();
+        assert!(
+            message.starts_with("invalid pipe.json: /port: "),
+            "{}",
+            message
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bundled_pipe_manifests_are_valid() {
+        let pipes_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../pipes");
+        let mut entries = tokio::fs::read_dir(&pipes_dir).await.unwrap();
+        while let Some(entry) = entries.next_entry().await.unwrap() {
+            if let Err(e) = PipeManifest::load_from_dir(&entry.path()).await {
+                panic!("{:?}: {}", entry.path(), e);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pipe_logs_rotation_and_query() {
+        let temp_dir = TempDir::new().unwrap();
+        let pipe_dir = temp_dir.path().join("log-pipe");
+        create_dir_all(&pipe_dir).await.unwrap();
+
+        let mut live = subscribe_pipe_logs("log-pipe");
+        let logger = PipeLogger::open_with_max_size("log-pipe", &pipe_dir, 512)
+            .await
+            .unwrap();
+        for i in 0..50 {
+            let stream = if i % 10 == 0 {
+                PipeLogStream::Stderr
+            } else {
+                PipeLogStream::Stdout
+            };
+            let message = if i % 10 == 0 {
+                format!("Error: failure {}", i)
+            } else {
+                format!("line {}", i)
+            };
+            let level = PipeLogLevel::guess(stream, &message);
+            logger.log(stream, level, &message).await;
+        }
+
+        let first = live.recv().await.unwrap();
+        assert_eq!(first.message, "Error: failure 0");
+        assert_eq!(first.level, PipeLogLevel::Error);
+
+        // rotation keeps the current file and three older ones, dropping the oldest lines
+        let log_dir = pipe_log_dir(&pipe_dir);
+        assert!(log_dir.join("pipe.log.3").exists());
+        assert!(!log_dir.join("pipe.log.4").exists());
+
+        let all = read_pipe_logs(&pipe_dir, &PipeLogQuery::default())
+            .await
+            .unwrap();
+        assert!(all.len() < 50);
+        assert_eq!(all.last().unwrap().message, "line 49");
+        assert!(all.windows(2).all(|w| w[0].timestamp <= w[1].timestamp));
+
+        let errors = read_pipe_logs(
+            &pipe_dir,
+            &PipeLogQuery {
+                level: Some(PipeLogLevel::Error),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert!(!errors.is_empty());
+        assert!(errors
+            .iter()
+            .all(|r| r.stream == PipeLogStream::Stderr && r.message.starts_with("Error:")));
+
+        let last_two = read_pipe_logs(
+            &pipe_dir,
+            &PipeLogQuery {
+                since: Some(all[all.len() - 3].timestamp),
+                limit: Some(2),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(last_two.len(), 2);
+        assert_eq!(last_two[1].message, "line 49");
+
+        assert_eq!(
+            PipeLogLevel::guess(PipeLogStream::Stderr, "$ next start"),
+            PipeLogLevel::Info
+        );
+        assert_eq!(
+            PipeLogLevel::guess(PipeLogStream::Stderr, "something odd"),
+            PipeLogLevel::Warn
+        );
+        assert_eq!(
+            "warning".parse::<PipeLogLevel>().unwrap(),
+            PipeLogLevel::Warn
+        );
+    }
+
+    /// Serves /ok, /fail and /slow for the cron tests.
+    async fn serve_cron_routes() -> u16 {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut buf = [0u8; 1024];
+                    let n = stream.read(&mut buf).await.unwrap_or(0);
+                    let request = String::from_utf8_lossy(&buf[..n]).to_string();
+                    let authorized = request.contains("Bearer secret");
+                    let (status, body) = match request.split(' ').nth(1) {
+                        _ if !authorized => ("401 Unauthorized", "no secret"),
+                        Some("/ok") => ("200 OK", "done"),
+                        Some("/slow") => {
+                            sleep(Duration::from_millis(1500)).await;
+                            ("200 OK", "slow done")
+                        }
+                        _ => ("500 Internal Server Error", "boom"),
+                    };
+                    let response = format!(
+                        "HTTP/1.1 {}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                        status,
+                        body.len(),
+                        body
+                    );
+                    let _ = stream.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+        port
+    }
+
+    #[tokio::test]
+    async fn test_pipe_cron_runs() {
+        let temp_dir = TempDir::new().unwrap();
+        let pipe_dir = temp_dir.path().join("pipes").join("cron-pipe");
+        create_dir_all(&pipe_dir).await.unwrap();
+        let port = serve_cron_routes().await;
+
+        // yearly, so only manual runs happen during the test
+        let cron = |path: &str, timeout_secs: u64| PipeCron {
+            path: Some(path.to_string()),
+            script: None,
+            schedule: "0 0 0 1 1 *".to_string(),
+            timeout_secs: Some(timeout_secs),
+            overlap: CronOverlap::Skip,
+        };
+        schedule_pipe_crons(
+            CronContext {
+                pipe: "cron-pipe".to_string(),
+                pipe_dir: pipe_dir.clone(),
+                base_url: Some(format!("http://127.0.0.1:{}", port)),
+                secret: "secret".to_string(),
+                permissions: None,
+                env: Vec::new(),
+                runtime: Default::default(),
+                handler: None,
+            },
+            &[cron("/ok", 5), cron("/fail", 5), cron("/slow", 5)],
+        )
+        .await;
+        let run = |job: &'static str| {
+            let screenpipe_dir = temp_dir.path().to_path_buf();
+            async move {
+                run_cron_now("cron-pipe", job, &screenpipe_dir)
+                    .await
+                    .unwrap()
+                    .unwrap()
+            }
+        };
+
+        let ok = run("/ok").await;
+        assert_eq!(ok.status, CronRunStatus::Success);
+        assert_eq!(ok.trigger, CronTrigger::Manual);
+        assert_eq!(ok.output.as_deref(), Some("done"));
+        assert!(get_last_cron_execution(&pipe_dir, "/ok")
+            .await
+            .unwrap()
+            .is_some());
+
+        let failed = run("/fail").await;
+        assert_eq!(failed.status, CronRunStatus::Failed);
+        assert!(failed.output.unwrap().contains("boom"));
+
+        // the second run is due while the first is still going
+        let (first, second) = tokio::join!(run("/slow"), async {
+            sleep(Duration::from_millis(300)).await;
+            run("/slow").await
+        });
+        assert_eq!(first.status, CronRunStatus::Success);
+        assert_eq!(second.status, CronRunStatus::Skipped);
+
+        assert!(run_cron_now("cron-pipe", "/missing", temp_dir.path())
+            .await
+            .unwrap()
+            .is_none());
+
+        let history = get_cron_runs("cron-pipe", None, 10).await.unwrap();
+        let statuses: Vec<_> = history.iter().map(|run| run.status).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                CronRunStatus::Success,
+                CronRunStatus::Skipped,
+                CronRunStatus::Failed,
+                CronRunStatus::Success
+            ]
+        );
+        assert_eq!(
+            get_cron_runs("cron-pipe", Some("/fail"), 10)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+
+        // once stopped, route jobs come from pipe.json and fail without a running pipe
+        cleanup_pipe_crons("cron-pipe").await;
+        let timed = cron("/slow", 1);
+        tokio::fs::write(
+            pipe_dir.join("pipe.json"),
+            json!({ "crons": [{ "path": "/slow", "schedule": "0 0 0 1 1 *", "timeout_secs": 1 }] })
+                .to_string(),
+        )
+        .await
+        .unwrap();
+        let stopped = run("/slow").await;
+        assert_eq!(stopped.status, CronRunStatus::Failed);
+        assert!(stopped.output.unwrap().contains("not running"));
+
+        schedule_pipe_crons(
+            CronContext {
+                pipe: "cron-pipe".to_string(),
+                pipe_dir: pipe_dir.clone(),
+                base_url: Some(format!("http://127.0.0.1:{}", port)),
+                secret: "secret".to_string(),
+                permissions: None,
+                env: Vec::new(),
+                runtime: Default::default(),
+                handler: None,
+            },
+            &[timed],
+        )
+        .await;
+        assert_eq!(run("/slow").await.status, CronRunStatus::TimedOut);
+        cleanup_pipe_crons("cron-pipe").await;
+    }
+
+    #[test]
+    fn test_pipe_source_parsing() {
+        let parse = |source: &str| PipeSource::parse(source).unwrap();
+
+        assert_eq!(
+            parse("https://github.com/mediar-ai/screenpipe/tree/main/pipes/search").kind(),
+            PipeSourceKind::Github
+        );
+        let archive = parse("https://example.com/releases/my-pipe-v1.2.0.tar.gz");
+        assert_eq!(archive.kind(), PipeSourceKind::Archive);
+        assert_eq!(archive.pipe_name(), "my-pipe");
+        assert_eq!(
+            parse("https://example.com/download?id=42").kind(),
+            PipeSourceKind::Archive
+        );
+
+        assert_eq!(
+            parse("git+https://git.example.com/tools/pipes#v1.0.0:pipes/standup"),
+            PipeSource::Git {
+                remote: "https://git.example.com/tools/pipes".to_string(),
+                rev: Some("v1.0.0".to_string()),
+                subdir: Some("pipes/standup".to_string()),
+            }
+        );
+        assert_eq!(
+            parse("git+https://git.example.com/tools/pipes#v1.0.0:pipes/standup").pipe_name(),
+            "standup"
+        );
+        let scp = parse("git@git.example.com:tools/standup-pipe.git");
+        assert_eq!(scp.kind(), PipeSourceKind::Git);
+        assert_eq!(scp.pipe_name(), "standup-pipe");
+
+        assert!(PipeSource::parse("git+https://git.example.com/tools/pipes#main:../etc").is_err());
+        assert!(PipeSource::parse("git+--upload-pack=touch /tmp/pwned#main").is_err());
+        assert!(PipeSource::parse("git+https://git.example.com/tools/pipes#--orphan").is_err());
+        assert!(PipeSource::parse("/definitely/not/a/pipe").is_err());
+        assert!(PipeSource::parse("ftp://example.com/pipe.zip").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_install_pipe_integrity() {
+        use base64::Engine;
+        use ed25519_dalek::{Signer, SigningKey};
+        use sha2::{Digest, Sha256};
+
+        init();
+        let temp_dir = TempDir::new().unwrap();
+        let screenpipe_dir = temp_dir.path().join(".screenpipe");
+        let write_pipe = |dir: &std::path::Path, body: &str| {
+            std::fs::create_dir_all(dir).unwrap();
+            std::fs::write(dir.join("pipe.json"), r#"{"crons": []}"#).unwrap();
+            std::fs::write(dir.join("pipe.ts"), body).unwrap();
+        };
+
+        // a tar.gz holding a single top directory, as made by `tar czf`
+        let archive_path = temp_dir.path().join("standup-1.0.0.tar.gz");
+        {
+            let source = temp_dir.path().join("standup");
+            write_pipe(&source, "console.log('v1')");
+            let gz = flate2::write::GzEncoder::new(
+                std::fs::File::create(&archive_path).unwrap(),
+                flate2::Compression::default(),
+            );
+            let mut tar = tar::Builder::new(gz);
+            tar.append_dir_all("standup", &source).unwrap();
+            tar.into_inner().unwrap().finish().unwrap();
+        }
+        let archive = archive_path.to_str().unwrap();
+        let sha256 = format!(
+            "{:x}",
+            Sha256::digest(std::fs::read(&archive_path).unwrap())
+        );
+
+        let err = install_pipe(
+            archive,
+            &PipeInstallOptions {
+                sha256: Some("00".repeat(32)),
+                ..Default::default()
+            },
+            screenpipe_dir.clone(),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<IntegrityError>(),
+            Some(IntegrityError::ChecksumMismatch { .. })
+        ));
+        assert!(!screenpipe_dir.join("pipes").join("standup").exists());
+
+        let signing_key = SigningKey::from_bytes(&[7; 32]);
+        let engine = base64::engine::general_purpose::STANDARD;
+        let signature = signing_key.sign(&Sha256::digest(std::fs::read(&archive_path).unwrap()));
+        let options = PipeInstallOptions {
+            sha256: Some(sha256.to_uppercase()),
+            signature: Some(engine.encode(signature.to_bytes())),
+            public_key: Some(engine.encode(signing_key.verifying_key().to_bytes())),
+        };
+        let pipe_dir = install_pipe(archive, &options, screenpipe_dir.clone())
+            .await
+            .unwrap();
+        assert_eq!(pipe_dir, screenpipe_dir.join("pipes").join("standup"));
+        assert_eq!(
+            std::fs::read_to_string(pipe_dir.join("pipe.ts")).unwrap(),
+            "console.log('v1')"
+        );
+        let lock = read_pipe_lock(&pipe_dir).await.unwrap().unwrap();
+        assert_eq!(lock.kind, PipeSourceKind::Archive);
+        assert_eq!(lock.sha256, sha256);
+        assert_eq!(lock.public_key, options.public_key);
+
+        // signed by another key
+        let other_key = SigningKey::from_bytes(&[8; 32]);
+        let err = install_pipe(
+            archive,
+            &PipeInstallOptions {
+                public_key: Some(engine.encode(other_key.verifying_key().to_bytes())),
+                ..options.clone()
+            },
+            screenpipe_dir.clone(),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<IntegrityError>(),
+            Some(IntegrityError::InvalidSignature(_))
+        ));
+
+        // git remote pinned to a tag, the lock pins the commit
+        let repo = temp_dir.path().join("repo");
+        write_pipe(&repo.join("pipes").join("digest"), "console.log('tagged')");
+        let git = |args: &[&str]| {
+            let status = std::process::Command::new("git")
+                .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+                .args(args)
+                .current_dir(&repo)
+                .output()
+                .unwrap();
+            assert!(status.status.success(), "git {:?} failed", args);
+            String::from_utf8(status.stdout).unwrap().trim().to_string()
+        };
+        git(&["init", "--quiet"]);
+        git(&["add", "."]);
+        git(&["commit", "--quiet", "-m", "v1"]);
+        git(&["tag", "v1"]);
+        let tagged_commit = git(&["rev-parse", "HEAD"]);
+        write_pipe(&repo.join("pipes").join("digest"), "console.log('main')");
+        git(&["commit", "--quiet", "-am", "v2"]);
+
+        let source = format!("git+{}#v1:pipes/digest", repo.display());
+        let pipe_dir = install_pipe(
+            &source,
+            &PipeInstallOptions::default(),
+            screenpipe_dir.clone(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(pipe_dir, screenpipe_dir.join("pipes").join("digest"));
+        assert_eq!(
+            std::fs::read_to_string(pipe_dir.join("pipe.ts")).unwrap(),
+            "console.log('tagged')"
+        );
+        let lock = read_pipe_lock(&pipe_dir).await.unwrap().unwrap();
+        assert_eq!(lock.kind, PipeSourceKind::Git);
+        assert_eq!(
+            lock.resolved,
+            format!("git+{}#{}:pipes/digest", repo.display(), tagged_commit)
+        );
+
+        // reinstalling the lock gets the same files even though the branch moved on
+        let reinstalled = install_pipe(&lock.resolved, &lock.install_options(), screenpipe_dir)
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(reinstalled.join("pipe.ts")).unwrap(),
+            "console.log('tagged')"
+        );
+        assert_eq!(
+            read_pipe_lock(&reinstalled).await.unwrap().unwrap().sha256,
+            lock.sha256
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pipe_version_rollback() {
+        init();
+        let temp_dir = TempDir::new().unwrap();
+        let screenpipe_dir = temp_dir.path().join(".screenpipe");
+        let install = |version: &str, fields: serde_json::Value| {
+            let source = temp_dir.path().join(version).join("standup");
+            std::fs::create_dir_all(&source).unwrap();
+            std::fs::write(
+                source.join("pipe.json"),
+                json!({ "fields": fields }).to_string(),
+            )
+            .unwrap();
+            std::fs::write(
+                source.join("pipe.ts"),
+                format!("console.log('{}')", version),
+            )
+            .unwrap();
+            source
+        };
+
+        let v1 = install(
+            "v1",
+            json!([{ "name": "interval", "type": "number", "default": 60 }]),
+        );
+        let pipe_dir = install_pipe(
+            v1.to_str().unwrap(),
+            &Default::default(),
+            screenpipe_dir.clone(),
+        )
+        .await
+        .unwrap();
+        let pipe = pipe_dir.file_name().unwrap().to_str().unwrap().to_string();
+        let first = snapshot_pipe_version(&screenpipe_dir, &pipe).await.unwrap();
+        assert_eq!(first.version, 1);
+
+        let v2 = install(
+            "v2",
+            json!([
+                { "name": "interval", "type": "number", "default": 30 },
+                { "name": "channel", "type": "string", "default": "#general" }
+            ]),
+        );
+        install_pipe(
+            v2.to_str().unwrap(),
+            &Default::default(),
+            screenpipe_dir.clone(),
+        )
+        .await
+        .unwrap();
+        snapshot_pipe_version(&screenpipe_dir, &pipe).await.unwrap();
+
+        // the user configures and enables the new version, which writes some logs
+        let mut config: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(pipe_dir.join("pipe.json")).unwrap())
+                .unwrap();
+        config["enabled"] = json!(true);
+        config["fields"][0]["value"] = json!(15);
+        config["fields"][1]["value"] = json!("#standup");
+        std::fs::write(pipe_dir.join("pipe.json"), config.to_string()).unwrap();
+        std::fs::create_dir_all(pipe_dir.join("logs")).unwrap();
+        std::fs::write(pipe_dir.join("logs").join("pipe.log"), "{}\n").unwrap();
+
+        let history = list_pipe_versions(&screenpipe_dir, &pipe).await.unwrap();
+        assert_eq!(history.current, Some(2));
+        assert_eq!(
+            history
+                .versions
+                .iter()
+                .map(|v| v.version)
+                .collect::<Vec<_>>(),
+            vec![2, 1]
+        );
+        assert!(history.versions[0].sha256.is_some());
+
+        let restored = restore_pipe_version(&screenpipe_dir, &pipe, None)
+            .await
+            .unwrap();
+        assert_eq!(restored.version, 1);
+        assert_eq!(
+            std::fs::read_to_string(pipe_dir.join("pipe.ts")).unwrap(),
+            "console.log('v1')"
+        );
+        assert!(pipe_dir.join("logs").join("pipe.log").exists());
+        let config: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(pipe_dir.join("pipe.json")).unwrap())
+                .unwrap();
+        assert_eq!(config["enabled"], json!(true));
+        assert_eq!(config["fields"].as_array().unwrap().len(), 1);
+        assert_eq!(config["fields"][0]["value"], json!(15));
+        assert_eq!(config["fields"][0]["default"], json!(60));
+        assert_eq!(
+            list_pipe_versions(&screenpipe_dir, &pipe)
+                .await
+                .unwrap()
+                .current,
+            Some(1)
+        );
+
+        // nothing before the first version, but any version can be picked
+        assert!(restore_pipe_version(&screenpipe_dir, &pipe, None)
+            .await
+            .is_err());
+        restore_pipe_version(&screenpipe_dir, &pipe, Some("2"))
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(pipe_dir.join("pipe.ts")).unwrap(),
+            "console.log('v2')"
+        );
+
+        // history is bounded
+        for _ in 0..MAX_PIPE_VERSIONS {
+            snapshot_pipe_version(&screenpipe_dir, &pipe).await.unwrap();
+        }
+        let history = list_pipe_versions(&screenpipe_dir, &pipe).await.unwrap();
+        assert_eq!(history.versions.len(), MAX_PIPE_VERSIONS);
+        assert_eq!(history.current, Some(2 + MAX_PIPE_VERSIONS as u32));
+    }
+
+    #[tokio::test]
+    async fn test_pipe_runtimes() {
+        init();
+        let temp_dir = TempDir::new().unwrap();
+        let screenpipe_dir = temp_dir.path().join(".screenpipe");
+        let run = |pipe: &'static str| {
+            let screenpipe_dir = screenpipe_dir.clone();
+            async move {
+                let (child, _) = run_pipe(pipe, screenpipe_dir.clone()).await.unwrap();
+                let output = child.wait_with_output().await.unwrap();
+                assert!(output.status.success());
+                // log lines are written by background tasks
+                sleep(Duration::from_millis(200)).await;
+                read_pipe_logs(
+                    &screenpipe_dir.join("pipes").join(pipe),
+                    &PipeLogQuery::default(),
+                )
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|record| record.message)
+                .collect::<Vec<_>>()
+            }
+        };
+
+        // python, in a virtualenv made from requirements.txt
+        let source = temp_dir.path().join("py-pipe");
+        create_dir_all(&source).await.unwrap();
+        tokio::fs::write(
+            source.join("pipe.json"),
+            json!({ "enabled": true, "runtime": "python", "port": 4321 }).to_string(),
+        )
+        .await
+        .unwrap();
+        tokio::fs::write(source.join("requirements.txt"), "")
+            .await
+            .unwrap();
+        tokio::fs::write(
+            source.join("pipe.py"),
+            "import os, sys\nprint(os.environ['PIPE_ID'], os.environ['PORT'], sys.prefix == sys.base_prefix)\n",
+        )
+        .await
+        .unwrap();
+        let pipe_dir = install_pipe(
+            source.to_str().unwrap(),
+            &Default::default(),
+            screenpipe_dir.clone(),
+        )
+        .await
+        .unwrap();
+        assert!(pipe_dir.join(PYTHON_VENV_DIR).exists());
+        assert_eq!(run("py-pipe").await, vec!["py-pipe 4321 False"]);
+
+        // an executable shipped with the pipe
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let pipe_dir = screenpipe_dir.join("pipes").join("bin-pipe");
+            create_dir_all(pipe_dir.join("bin")).await.unwrap();
+            tokio::fs::write(
+                pipe_dir.join("pipe.json"),
+                json!({
+                    "enabled": true,
+                    "runtime": "executable",
+                    "entrypoints": { "main": "bin/run" }
+                })
+                .to_string(),
+            )
+            .await
+            .unwrap();
+            let main = pipe_dir.join("bin").join("run");
+            tokio::fs::write(&main, "#!/bin/sh\necho \"$PIPE_ID in $PIPE_DIR\"\n")
+                .await
+                .unwrap();
+            std::fs::set_permissions(&main, std::fs::Permissions::from_mode(0o755)).unwrap();
+            assert_eq!(
+                run("bin-pipe").await,
+                vec![format!("bin-pipe in {}", pipe_dir.display())]
+            );
+        }
+
+        // the runtime is part of the manifest
+        assert!(PipeManifest::validate(&json!({ "runtime": "cobol" })).is_err());
+        assert_eq!(
+            PipeManifest::validate(&json!({ "runtime": "deno" }))
+                .unwrap()
+                .runtime,
+            PipeRuntime::Deno
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pipe_secrets() {
+        init();
+        let temp_dir = TempDir::new().unwrap();
+        let screenpipe_dir = temp_dir.path().join(".screenpipe");
+        create_dir_all(&screenpipe_dir).await.unwrap();
+
+        // nothing is created until a secret is set
+        assert!(get_pipe_secrets(&screenpipe_dir, "notion")
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(!screenpipe_dir.join(PIPE_SECRETS_FILE).exists());
+
+        set_pipe_secret(&screenpipe_dir, "notion", "NOTION_TOKEN", "secret_abc")
+            .await
+            .unwrap();
+        set_pipe_secret(&screenpipe_dir, "notion", "OPENAI_API_KEY", "sk-123")
+            .await
+            .unwrap();
+        set_pipe_secret(&screenpipe_dir, "slack", "SLACK_TOKEN", "xoxb-456")
+            .await
+            .unwrap();
+        let secrets = get_pipe_secrets(&screenpipe_dir, "notion").await.unwrap();
+        assert_eq!(secrets["NOTION_TOKEN"], "secret_abc");
+        assert_eq!(secrets["OPENAI_API_KEY"], "sk-123");
+        assert_eq!(
+            list_pipe_secrets(&screenpipe_dir).await.unwrap()["notion"],
+            vec!["NOTION_TOKEN", "OPENAI_API_KEY"]
+        );
+
+        // the store holds no plaintext
+        let sealed = tokio::fs::read_to_string(screenpipe_dir.join(PIPE_SECRETS_FILE))
+            .await
+            .unwrap();
+        assert!(!sealed.contains("secret_abc") && !sealed.contains("NOTION_TOKEN"));
+        assert!(screenpipe_dir.join("pipe_secrets.key").exists());
+
+        // secrets kept elsewhere are sealed under the same key
+        let sealed = seal_secret(&screenpipe_dir, "whsec_789").await.unwrap();
+        assert!(!sealed.contains("whsec_789"));
+        assert_eq!(
+            open_secret(&screenpipe_dir, &sealed).await.unwrap(),
+            "whsec_789"
+        );
+        assert_eq!(
+            open_secret(&screenpipe_dir, "plain").await.unwrap(),
+            "plain"
+        );
+
+        // names have to work as environment variables and can't shadow screenpipe's own
+        assert!(set_pipe_secret(&screenpipe_dir, "notion", "api key", "x")
+            .await
+            .is_err());
+        assert!(set_pipe_secret(&screenpipe_dir, "notion", "PIPE_DIR", "x")
+            .await
+            .is_err());
+
+        assert!(
+            remove_pipe_secret(&screenpipe_dir, "notion", "OPENAI_API_KEY")
+                .await
+                .unwrap()
+        );
+        assert!(
+            !remove_pipe_secret(&screenpipe_dir, "notion", "OPENAI_API_KEY")
+                .await
+                .unwrap()
+        );
+        remove_pipe_secrets(&screenpipe_dir, "slack").await.unwrap();
+        let listed = list_pipe_secrets(&screenpipe_dir).await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed["notion"], vec!["NOTION_TOKEN"]);
+
+        // secret fields are flagged in the manifest, only for text
+        let manifest = PipeManifest::validate(&json!({
+            "fields": [{ "name": "NOTION_TOKEN", "type": "string", "secret": true }]
+        }))
+        .unwrap();
+        assert!(manifest.field("NOTION_TOKEN").unwrap().secret);
+        assert!(PipeManifest::validate(&json!({
+            "fields": [{ "name": "LIMIT", "type": "number", "secret": true }]
+        }))
+        .is_err());
+        assert!(PipeManifest::validate(&json!({
+            "fields": [{ "name": "notion token", "type": "string", "secret": true }]
+        }))
+        .is_err());
+
+        // and reach the pipe through its environment
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let pipe_dir = screenpipe_dir.join("pipes").join("notion");
+            create_dir_all(&pipe_dir).await.unwrap();
+            tokio::fs::write(
+                pipe_dir.join("pipe.json"),
+                json!({ "enabled": true, "runtime": "executable" }).to_string(),
+            )
+            .await
+            .unwrap();
+            let main = pipe_dir.join("pipe");
+            tokio::fs::write(&main, "#!/bin/sh\necho \"token $NOTION_TOKEN\"\n")
+                .await
+                .unwrap();
+            std::fs::set_permissions(&main, std::fs::Permissions::from_mode(0o755)).unwrap();
+            let (child, _) = run_pipe("notion", screenpipe_dir.clone()).await.unwrap();
+            assert!(child.wait_with_output().await.unwrap().status.success());
+            sleep(Duration::from_millis(200)).await;
+            let logs = read_pipe_logs(&pipe_dir, &PipeLogQuery::default())
+                .await
+                .unwrap();
+            assert_eq!(logs[0].message, "token secret_abc");
+        }
+    }
 }
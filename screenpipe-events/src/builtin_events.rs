@@ -0,0 +1,45 @@
+//! Payloads of the built-in events, as declared in the schema catalogue.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// `meeting_started` and `meeting_ended`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MeetingEvent {
+    pub app: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// `transcription`, partial until `is_final`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RealtimeTranscriptionEvent {
+    pub timestamp: DateTime<Utc>,
+    pub device: String,
+    pub transcription: String,
+    pub is_final: bool,
+    pub is_input: bool,
+}
+
+/// `ui_frame`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UIFrame {
+    pub window: String,
+    pub app: String,
+    pub text_output: String,
+    pub initial_traversal_at: String,
+}
+
+/// `window_ocr`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WindowOcr {
+    pub window_name: String,
+    pub app_name: String,
+    pub text: String,
+    pub text_json: Vec<HashMap<String, String>>,
+    pub focused: bool,
+    pub confidence: f64,
+    pub timestamp: DateTime<Utc>,
+}
@@ -1,10 +1,13 @@
-use crate::{send_event, subscribe_to_all_events};
+use crate::{
+    send_event, subscribe_to_all_events, MeetingEvent, RealtimeTranscriptionEvent, UIFrame,
+    WindowOcr,
+};
 use anyhow::Result;
-use chrono::{DateTime, Utc};
+use chrono::Utc;
 use futures::StreamExt;
-use serde::{Deserialize, Serialize};
-use serde_json;
-use std::collections::{HashMap, HashSet};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::HashSet;
 use std::time::{Duration, Instant};
 
 const MEETING_APPS: &[&str] = &["zoom", "teams", "meet", "webex", "skype", "slack"];
@@ -41,7 +44,9 @@ pub async fn poll_meetings_events() -> Result<()> {
         let event = event.data;
         match name.as_str() {
             "ui_frame" => {
-                let ui_frame: UIFrame = serde_json::from_value(event).unwrap();
+                let Some(ui_frame) = parse_payload::<UIFrame>(&name, event) else {
+                    continue;
+                };
                 tracing::debug!("ui_frame: {:?}", ui_frame.app);
 
                 let is_meeting_app = MEETING_APPS
@@ -86,7 +91,9 @@ pub async fn poll_meetings_events() -> Result<()> {
                 }
             }
             "window_ocr" => {
-                let window_ocr: WindowOcr = serde_json::from_value(event).unwrap();
+                let Some(window_ocr) = parse_payload::<WindowOcr>(&name, event) else {
+                    continue;
+                };
                 tracing::debug!("window_ocr: {:?}", window_ocr.app_name);
 
                 // Method 1: Meeting App Detection
@@ -142,8 +149,11 @@ pub async fn poll_meetings_events() -> Result<()> {
                 }
             }
 
-            "realtime_transcription" => {
-                let transcript: RealtimeTranscriptionEvent = serde_json::from_value(event).unwrap();
+            "transcription" => {
+                let Some(transcript) = parse_payload::<RealtimeTranscriptionEvent>(&name, event)
+                else {
+                    continue;
+                };
                 tracing::debug!("realtime_transcription: {:?}", transcript.transcription);
                 // Method 4: Multiple Speaker Detection
                 if transcript.is_final {
@@ -203,36 +213,10 @@ pub async fn poll_meetings_events() -> Result<()> {
     Ok(())
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct MeetingEvent {
-    app: String,
-    timestamp: DateTime<Utc>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct WindowOcr {
-    pub window_name: String,
-    pub app_name: String,
-    pub text: String,
-    pub text_json: Vec<HashMap<String, String>>, // Change this line
-    pub focused: bool,
-    pub confidence: f64,
-    pub timestamp: DateTime<Utc>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct UIFrame {
-    pub window: String,
-    pub app: String,
-    pub text_output: String,
-    pub initial_traversal_at: String,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct RealtimeTranscriptionEvent {
-    pub timestamp: DateTime<Utc>,
-    pub device: String,
-    pub transcription: String,
-    pub is_final: bool,
-    pub is_input: bool,
+/// A payload that doesn't match its type is skipped, a producer changing its schema
+/// must not take meeting detection down.
+fn parse_payload<T: DeserializeOwned>(name: &str, data: Value) -> Option<T> {
+    serde_json::from_value(data)
+        .map_err(|e| tracing::warn!("skipping malformed {} event: {}", name, e))
+        .ok()
 }
@@ -0,0 +1,330 @@
+//! Append-only log of the events sent on the bus, so subscribers can replay what they
+//! missed while lagging or down.
+//!
+//! Events are json lines in segment files named after the offset of their first event.
+//! Offsets only ever grow: the segment being written is never deleted, older ones are
+//! once the log is over `max_bytes` or they are older than `max_age`.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use anyhow::Result;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::Event;
+
+const SEGMENT_EXTENSION: &str = "log";
+
+#[derive(Debug, Clone)]
+pub struct EventLogConfig {
+    pub dir: PathBuf,
+    /// A new segment is started once the current one is this big.
+    pub segment_bytes: u64,
+    /// Oldest segments are deleted past this total size.
+    pub max_bytes: Option<u64>,
+    /// Segments last written longer ago than this are deleted.
+    pub max_age: Option<Duration>,
+    /// Events that are not logged, e.g. frames carrying images.
+    pub exclude: Vec<String>,
+}
+
+impl EventLogConfig {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            segment_bytes: 16 * 1024 * 1024,
+            max_bytes: Some(512 * 1024 * 1024),
+            max_age: Some(Duration::from_secs(7 * 24 * 60 * 60)),
+            exclude: Vec::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Record {
+    offset: u64,
+    name: String,
+    data: Value,
+}
+
+/// How far a reader got in the log, see [`EventLog::read_at`].
+#[derive(Debug, Clone)]
+pub struct LogCursor {
+    /// Offset of the next event to read.
+    pub next_offset: u64,
+    /// Segment base offset and byte position after the last event read.
+    position: Option<(u64, u64)>,
+}
+
+impl LogCursor {
+    pub fn new(from: u64) -> Self {
+        Self {
+            next_offset: from,
+            position: None,
+        }
+    }
+}
+
+struct Segment {
+    base_offset: u64,
+    path: PathBuf,
+    bytes: u64,
+}
+
+struct LogState {
+    /// Oldest first, the last one is written to.
+    segments: Vec<Segment>,
+    file: File,
+    next_offset: u64,
+}
+
+pub struct EventLog {
+    config: EventLogConfig,
+    state: Mutex<LogState>,
+}
+
+impl EventLog {
+    /// Opens the log in `config.dir`, continuing after the last event written there.
+    pub fn open(config: EventLogConfig) -> Result<Self> {
+        fs::create_dir_all(&config.dir)?;
+        let mut segments = Vec::new();
+        for entry in fs::read_dir(&config.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(SEGMENT_EXTENSION) {
+                continue;
+            }
+            let Some(base_offset) = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| s.parse().ok())
+            else {
+                continue;
+            };
+            segments.push(Segment {
+                base_offset,
+                bytes: fs::metadata(&path)?.len(),
+                path,
+            });
+        }
+        segments.sort_by_key(|s| s.base_offset);
+
+        let next_offset = match segments.last_mut() {
+            Some(last) => {
+                let (next_offset, valid_bytes) = scan_segment(&last.path, last.base_offset)?;
+                // drop an event torn by a crash mid write
+                if valid_bytes < last.bytes {
+                    OpenOptions::new()
+                        .write(true)
+                        .open(&last.path)?
+                        .set_len(valid_bytes)?;
+                    last.bytes = valid_bytes;
+                }
+                next_offset
+            }
+            None => {
+                let path = segment_path(&config.dir, 0);
+                File::create(&path)?;
+                segments.push(Segment {
+                    base_offset: 0,
+                    path,
+                    bytes: 0,
+                });
+                0
+            }
+        };
+        let file = OpenOptions::new()
+            .append(true)
+            .open(&segments.last().unwrap().path)?;
+
+        let log = Self {
+            config,
+            state: Mutex::new(LogState {
+                segments,
+                file,
+                next_offset,
+            }),
+        };
+        log.enforce_retention(&mut log.state.lock());
+        Ok(log)
+    }
+
+    pub fn config(&self) -> &EventLogConfig {
+        &self.config
+    }
+
+    pub fn is_logged(&self, name: &str) -> bool {
+        !self.config.exclude.iter().any(|e| e == name)
+    }
+
+    /// Appends an event and returns its offset.
+    pub fn append(&self, name: &str, data: &Value) -> Result<u64> {
+        let mut state = self.state.lock();
+        if state.segments.last().unwrap().bytes >= self.config.segment_bytes {
+            self.roll(&mut state)?;
+        }
+        let offset = state.next_offset;
+        let mut line = serde_json::to_vec(&Record {
+            offset,
+            name: name.to_string(),
+            data: data.clone(),
+        })?;
+        line.push(b'\n');
+        state.file.write_all(&line)?;
+        state.segments.last_mut().unwrap().bytes += line.len() as u64;
+        state.next_offset += 1;
+        Ok(offset)
+    }
+
+    /// Up to `limit` events from `from` on. Events deleted by retention are skipped, the
+    /// first one returned tells where the log really starts.
+    pub fn read(&self, from: u64, limit: usize) -> Result<Vec<Event>> {
+        self.read_at(&mut LogCursor::new(from), limit)
+    }
+
+    /// Like [`EventLog::read`] from `cursor.next_offset`, seeking to where the previous
+    /// read stopped instead of scanning its segment again. Moves the cursor past the
+    /// events returned.
+    pub fn read_at(&self, cursor: &mut LogCursor, limit: usize) -> Result<Vec<Event>> {
+        let segments: Vec<(u64, PathBuf)> = {
+            let state = self.state.lock();
+            let resumed = cursor.position.and_then(|(base_offset, _)| {
+                state
+                    .segments
+                    .iter()
+                    .position(|s| s.base_offset == base_offset)
+            });
+            let start = resumed.unwrap_or_else(|| {
+                // retention deleted the segment the cursor was in
+                cursor.position = None;
+                state
+                    .segments
+                    .iter()
+                    .rposition(|s| s.base_offset <= cursor.next_offset)
+                    .unwrap_or(0)
+            });
+            state.segments[start..]
+                .iter()
+                .map(|s| (s.base_offset, s.path.clone()))
+                .collect()
+        };
+
+        let mut events = Vec::new();
+        for (base_offset, path) in segments {
+            let mut byte = match cursor.position {
+                Some((base, byte)) if base == base_offset => byte,
+                _ => 0,
+            };
+            // retention may have deleted it since
+            let Ok(mut file) = File::open(&path) else {
+                continue;
+            };
+            file.seek(SeekFrom::Start(byte))?;
+            let mut reader = BufReader::new(file);
+            let mut line = String::new();
+            loop {
+                line.clear();
+                let read = reader.read_line(&mut line)?;
+                // the end of an event still being written
+                if read == 0 || !line.ends_with('\n') {
+                    break;
+                }
+                let Ok(record) = serde_json::from_str::<Record>(line.trim_end()) else {
+                    break;
+                };
+                byte += read as u64;
+                cursor.position = Some((base_offset, byte));
+                if record.offset < cursor.next_offset {
+                    continue;
+                }
+                cursor.next_offset = record.offset + 1;
+                events.push(Event {
+                    name: record.name,
+                    data: record.data,
+                    offset: Some(record.offset),
+                });
+                if events.len() >= limit {
+                    return Ok(events);
+                }
+            }
+        }
+        Ok(events)
+    }
+
+    /// Offset of the oldest event still in the log.
+    pub fn first_offset(&self) -> u64 {
+        self.state.lock().segments[0].base_offset
+    }
+
+    /// Offset the next event will get.
+    pub fn next_offset(&self) -> u64 {
+        self.state.lock().next_offset
+    }
+
+    fn roll(&self, state: &mut LogState) -> Result<()> {
+        let path = segment_path(&self.config.dir, state.next_offset);
+        state.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)?;
+        state.segments.push(Segment {
+            base_offset: state.next_offset,
+            path,
+            bytes: 0,
+        });
+        self.enforce_retention(state);
+        Ok(())
+    }
+
+    fn enforce_retention(&self, state: &mut LogState) {
+        let mut total: u64 = state.segments.iter().map(|s| s.bytes).sum();
+        while state.segments.len() > 1 {
+            let oldest = &state.segments[0];
+            let too_big = self.config.max_bytes.is_some_and(|max| total > max);
+            let too_old = self.config.max_age.is_some_and(|max_age| {
+                fs::metadata(&oldest.path)
+                    .and_then(|m| m.modified())
+                    .ok()
+                    .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+                    .is_some_and(|age| age > max_age)
+            });
+            if !too_big && !too_old {
+                break;
+            }
+            if let Err(e) = fs::remove_file(&oldest.path) {
+                tracing::warn!("failed to delete event log segment {:?}: {}", oldest.path, e);
+                break;
+            }
+            total -= oldest.bytes;
+            state.segments.remove(0);
+        }
+    }
+}
+
+fn segment_path(dir: &Path, base_offset: u64) -> PathBuf {
+    dir.join(format!("{:020}.{}", base_offset, SEGMENT_EXTENSION))
+}
+
+/// Offset after the last complete event of a segment, and the bytes up to it.
+fn scan_segment(path: &Path, base_offset: u64) -> Result<(u64, u64)> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut next_offset = base_offset;
+    let mut valid_bytes = 0;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        let read = reader.read_line(&mut line)?;
+        if read == 0 || !line.ends_with('\n') {
+            break;
+        }
+        match serde_json::from_str::<Record>(line.trim_end()) {
+            Ok(record) => next_offset = record.offset + 1,
+            Err(_) => break,
+        }
+        valid_bytes += read as u64;
+    }
+    Ok((next_offset, valid_bytes))
+}
@@ -6,21 +6,37 @@ use serde::Deserialize;
 use serde::{de::DeserializeOwned, Serialize};
 use serde_json::Value;
 use std::any::Any;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{SyncSender, TrySendError};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
 use tokio::time::interval;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
 use tokio_stream::wrappers::BroadcastStream;
 
+use crate::event_log::{EventLog, EventLogConfig, LogCursor};
+use crate::schema::EventRegistry;
+
 static EVENT_MANAGER: Lazy<EventManager> = Lazy::new(EventManager::new);
 
 const CLEANUP_INTERVAL: Duration = Duration::from_secs(60);
 const SUBSCRIPTION_TIMEOUT: Duration = Duration::from_secs(600); // 10 minutes
+/// Events read from the log at once when a subscriber replays or catches up.
+const REPLAY_BATCH: usize = 256;
+/// Events waiting for the log writer, further ones are dropped and counted.
+const LOG_QUEUE: usize = 10000;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Event<T = Value> {
     pub name: String,
     pub data: T,
+    /// Position in the event log, when it is enabled and the event is logged.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u64>,
 }
 
 struct SubscriptionEntry {
@@ -31,6 +47,16 @@ struct SubscriptionEntry {
 pub struct EventManager {
     sender: broadcast::Sender<Event>,
     subscriptions: RwLock<HashMap<String, SubscriptionEntry>>,
+    log: RwLock<Option<EventLogHandle>>,
+}
+
+struct EventLogHandle {
+    log: Arc<EventLog>,
+    /// The log writer thread, which appends events and then broadcasts them so offsets
+    /// go out in order without senders waiting on the disk.
+    writer: SyncSender<Event>,
+    /// Events dropped because the queue was full, reported by the writer.
+    dropped: Arc<AtomicU64>,
 }
 
 // #[macro_export]
@@ -49,18 +75,56 @@ pub fn send_event<T: Serialize + 'static>(event: impl Into<String>, data: T) ->
 pub struct EventSubscription<T = Value> {
     stream: std::pin::Pin<Box<BroadcastStream<Event>>>,
     event_name: String,
+    /// Offset after the last logged event seen, live events before it were replayed already.
+    next_offset: Option<u64>,
+    /// Read from the log until it is caught up with, then back to live events.
+    replaying: bool,
+    backlog: VecDeque<Event>,
+    /// Where the last replay read stopped in the log.
+    cursor: Option<LogCursor>,
+    /// Replay read running on the blocking pool.
+    pending_read: Option<JoinHandle<(Result<Vec<Event>>, LogCursor)>>,
+    /// Events lost to lag that the log could not give back.
+    dropped: u64,
     _phantom: std::marker::PhantomData<T>,
 }
 
-impl<T: DeserializeOwned + Send + 'static> Clone for EventSubscription<T> {
-    fn clone(&self) -> Self {
-        let rx = EVENT_MANAGER.sender.subscribe();
+impl<T> EventSubscription<T> {
+    fn new(event_name: String, from: Option<u64>) -> Self {
         Self {
-            stream: Box::pin(BroadcastStream::new(rx)),
-            event_name: self.event_name.clone(),
+            stream: Box::pin(BroadcastStream::new(EVENT_MANAGER.sender.subscribe())),
+            event_name,
+            next_offset: from,
+            replaying: from.is_some(),
+            backlog: VecDeque::new(),
+            cursor: None,
+            pending_read: None,
+            dropped: 0,
             _phantom: std::marker::PhantomData,
         }
     }
+
+    /// Events dropped because this subscriber lagged, since the last call.
+    pub fn take_dropped(&mut self) -> u64 {
+        std::mem::take(&mut self.dropped)
+    }
+
+    /// Takes the event if it is one for this subscriber, remembering how far it got.
+    fn accept(&mut self, event: Event) -> Option<Event> {
+        if let Some(offset) = event.offset {
+            if self.next_offset.is_some_and(|next| offset < next) {
+                return None;
+            }
+            self.next_offset = Some(offset + 1);
+        }
+        (event.name == self.event_name || self.event_name.is_empty()).then_some(event)
+    }
+}
+
+impl<T: DeserializeOwned + Send + 'static> Clone for EventSubscription<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.event_name.clone(), None)
+    }
 }
 
 impl<T: DeserializeOwned + Unpin + 'static> Stream for EventSubscription<T> {
@@ -78,20 +142,73 @@ impl<T: DeserializeOwned + Unpin + 'static> Stream for EventSubscription<T> {
         }
 
         loop {
-            match me.stream.as_mut().poll_next(cx) {
-                std::task::Poll::Ready(Some(Ok(event))) => {
-                    if event.name == me.event_name || me.event_name.is_empty() {
-                        if let Ok(data) = serde_json::from_value::<T>(event.data) {
-                            return std::task::Poll::Ready(Some(Event {
-                                name: event.name,
-                                data,
-                            }));
+            let event = if let Some(event) = me.backlog.pop_front() {
+                event
+            } else if me.replaying {
+                let from = me.next_offset.unwrap_or(0);
+                let Some(log) = EVENT_MANAGER.log() else {
+                    me.replaying = false;
+                    continue;
+                };
+                let read = me.pending_read.get_or_insert_with(|| {
+                    let mut cursor = me.cursor.take().unwrap_or_else(|| LogCursor::new(from));
+                    // live events may have moved past the last replay
+                    cursor.next_offset = cursor.next_offset.max(from);
+                    tokio::task::spawn_blocking(move || {
+                        let events = log.read_at(&mut cursor, REPLAY_BATCH);
+                        (events, cursor)
+                    })
+                });
+                let result = match std::pin::Pin::new(read).poll(cx) {
+                    std::task::Poll::Ready(result) => result,
+                    std::task::Poll::Pending => return std::task::Poll::Pending,
+                };
+                me.pending_read = None;
+                let events = match result {
+                    Ok((Ok(events), cursor)) => {
+                        me.cursor = Some(cursor);
+                        events
+                    }
+                    Ok((Err(e), _)) => {
+                        tracing::error!("failed to replay events from {}: {}", from, e);
+                        Vec::new()
+                    }
+                    Err(e) => {
+                        tracing::error!("event replay task failed: {}", e);
+                        Vec::new()
+                    }
+                };
+                if events.is_empty() {
+                    me.replaying = false;
+                }
+                me.backlog.extend(events);
+                continue;
+            } else {
+                match me.stream.as_mut().poll_next(cx) {
+                    std::task::Poll::Ready(Some(Ok(event))) => event,
+                    std::task::Poll::Ready(Some(Err(BroadcastStreamRecvError::Lagged(n)))) => {
+                        // the log still has what the channel dropped
+                        if me.next_offset.is_some() && EVENT_MANAGER.log().is_some() {
+                            tracing::debug!("subscriber lagged by {} events, replaying", n);
+                            me.replaying = true;
+                        } else {
+                            tracing::warn!("subscriber lagged, {} events dropped", n);
+                            me.dropped += n;
                         }
+                        continue;
                     }
+                    std::task::Poll::Ready(None) => return std::task::Poll::Ready(None),
+                    std::task::Poll::Pending => return std::task::Poll::Pending,
+                }
+            };
+            if let Some(event) = me.accept(event) {
+                if let Ok(data) = serde_json::from_value::<T>(event.data) {
+                    return std::task::Poll::Ready(Some(Event {
+                        name: event.name,
+                        data,
+                        offset: event.offset,
+                    }));
                 }
-                std::task::Poll::Ready(Some(_)) => continue,
-                std::task::Poll::Ready(None) => return std::task::Poll::Ready(None),
-                std::task::Poll::Pending => return std::task::Poll::Pending,
             }
         }
     }
@@ -103,6 +220,7 @@ impl EventManager {
         let manager = Self {
             sender,
             subscriptions: RwLock::new(HashMap::new()),
+            log: RwLock::new(None),
         };
 
         // spawn cleanup task
@@ -126,14 +244,80 @@ impl EventManager {
         &EVENT_MANAGER
     }
 
+    /// Logs every event sent from now on (except `config.exclude`) so subscribers can
+    /// replay them, see [`EventManager::subscribe_from`].
+    pub fn enable_log(&self, config: EventLogConfig) -> Result<()> {
+        let log = Arc::new(EventLog::open(config)?);
+        let (writer, events) = std::sync::mpsc::sync_channel::<Event>(LOG_QUEUE);
+        let writer_log = log.clone();
+        let dropped = Arc::new(AtomicU64::new(0));
+        let writer_dropped = dropped.clone();
+        let sender = self.sender.clone();
+        std::thread::Builder::new()
+            .name("event-log".to_string())
+            .spawn(move || {
+                for mut event in events {
+                    let dropped = writer_dropped.swap(0, Ordering::Relaxed);
+                    if dropped > 0 {
+                        tracing::warn!("event log queue was full, {} events dropped", dropped);
+                    }
+                    match writer_log.append(&event.name, &event.data) {
+                        Ok(offset) => event.offset = Some(offset),
+                        Err(e) => tracing::error!("failed to log event {}: {}", event.name, e),
+                    }
+                    // no subscribers is fine
+                    let _ = sender.send(event);
+                }
+            })?;
+        *self.log.write() = Some(EventLogHandle {
+            log,
+            writer,
+            dropped,
+        });
+        Ok(())
+    }
+
+    pub fn log(&self) -> Option<Arc<EventLog>> {
+        self.log.read().as_ref().map(|handle| handle.log.clone())
+    }
+
+    /// Sends an event to every subscriber. Fails when it doesn't match its registered
+    /// schema, see [`EventRegistry`].
     pub fn send<T: Serialize + 'static>(&self, event: impl Into<String>, data: T) -> Result<()> {
         let event_name = event.into();
         let value = serde_json::to_value(data)?;
         tracing::debug!("sending event {} ", event_name);
-        match self.sender.send(Event {
+        if let Err(e) = EventRegistry::instance().validate(&event_name, &value) {
+            tracing::error!("not sending event: {}", e);
+            return Err(e.into());
+        }
+        let event = Event {
             name: event_name.clone(),
             data: value,
-        }) {
+            offset: None,
+        };
+        // logged events go through the writer while the log is on, to keep their order,
+        // excluded ones are broadcast right away
+        let writer = self.log.read().as_ref().and_then(|handle| {
+            handle
+                .log
+                .is_logged(&event_name)
+                .then(|| (handle.writer.clone(), handle.dropped.clone()))
+        });
+        if let Some((writer, dropped)) = writer {
+            // never waits on the disk, senders may run on the async runtime
+            return match writer.try_send(event) {
+                Ok(()) => Ok(()),
+                Err(TrySendError::Full(_)) => {
+                    dropped.fetch_add(1, Ordering::Relaxed);
+                    Ok(())
+                }
+                Err(TrySendError::Disconnected(_)) => {
+                    Err(anyhow::anyhow!("event log writer stopped"))
+                }
+            };
+        }
+        match self.sender.send(event) {
             Ok(_) => Ok(()),
             Err(e) => {
                 if !e.to_string().contains("channel closed") {
@@ -165,12 +349,7 @@ impl EventManager {
             }
         }
 
-        let rx = self.sender.subscribe();
-        let sub = EventSubscription {
-            stream: Box::pin(BroadcastStream::new(rx)),
-            event_name: event_name.clone(),
-            _phantom: std::marker::PhantomData,
-        };
+        let sub = EventSubscription::new(event_name.clone(), None);
 
         let mut subs = self.subscriptions.write();
         subs.insert(
@@ -182,6 +361,19 @@ impl EventManager {
         );
         sub
     }
+
+    /// Subscribes with a replay of the logged events from `offset` on, then live events.
+    /// Fails when the log is not enabled.
+    pub fn subscribe_from<T: DeserializeOwned + Unpin + Clone + Send + Sync + 'static>(
+        &self,
+        event: impl Into<String>,
+        offset: u64,
+    ) -> Result<EventSubscription<T>> {
+        if self.log().is_none() {
+            anyhow::bail!("the event log is not enabled");
+        }
+        Ok(EventSubscription::new(event.into(), Some(offset)))
+    }
 }
 
 pub fn subscribe_to_event<T: DeserializeOwned + Unpin + Clone + Send + Sync + 'static>(
@@ -193,3 +385,18 @@ pub fn subscribe_to_event<T: DeserializeOwned + Unpin + Clone + Send + Sync + 's
 pub fn subscribe_to_all_events() -> EventSubscription<serde_json::Value> {
     EventManager::instance().subscribe::<serde_json::Value>("")
 }
+
+pub fn enable_event_log(config: EventLogConfig) -> Result<()> {
+    EventManager::instance().enable_log(config)
+}
+
+pub fn subscribe_to_event_from<T: DeserializeOwned + Unpin + Clone + Send + Sync + 'static>(
+    event: impl Into<String>,
+    offset: u64,
+) -> Result<EventSubscription<T>> {
+    EventManager::instance().subscribe_from::<T>(event, offset)
+}
+
+pub fn subscribe_to_all_events_from(offset: u64) -> Result<EventSubscription<serde_json::Value>> {
+    EventManager::instance().subscribe_from::<serde_json::Value>("", offset)
+}
@@ -0,0 +1,253 @@
+//! Which events a subscriber wants: glob patterns on names and predicates on payloads.
+//!
+//! Predicates look like `app_name == "Slack"` or `speaker.id >= 3`, several are joined with
+//! `&&`. The left side is a path into the event data (`a.b[0].c`, an optional `$.` or `data.`
+//! prefix is ignored), the right side a json value, or a bare word taken as a string.
+//! Operators are `==`, `!=`, `~=` (string contains, case insensitive), `>`, `>=`, `<`, `<=`.
+
+use anyhow::Result;
+use serde_json::Value;
+
+use crate::Event;
+
+#[derive(Debug, Clone, Default)]
+pub struct EventFilter {
+    /// Any of them must match the name, no pattern matches every event.
+    patterns: Vec<String>,
+    predicates: Vec<Predicate>,
+}
+
+#[derive(Debug, Clone)]
+struct Predicate {
+    path: Vec<Segment>,
+    op: Op,
+    value: Value,
+}
+
+#[derive(Debug, Clone)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Contains,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+/// Longest first, so `>=` isn't read as `>`.
+const OPS: [(&str, Op); 7] = [
+    ("==", Op::Eq),
+    ("!=", Op::Ne),
+    ("~=", Op::Contains),
+    (">=", Op::Ge),
+    ("<=", Op::Le),
+    (">", Op::Gt),
+    ("<", Op::Lt),
+];
+
+impl EventFilter {
+    /// Events named like one of `patterns` (`*` and `?` globs) whose data matches
+    /// `predicates`. Fails on a predicate that doesn't parse.
+    pub fn new(patterns: Vec<String>, predicates: Option<&str>) -> Result<Self> {
+        let predicates = match predicates.map(str::trim) {
+            Some(predicates) if !predicates.is_empty() => split_outside_quotes(predicates, "&&")
+                .into_iter()
+                .map(Predicate::parse)
+                .collect::<Result<_>>()?,
+            _ => Vec::new(),
+        };
+        Ok(Self {
+            patterns: patterns
+                .into_iter()
+                .map(|p| p.trim().to_string())
+                .filter(|p| !p.is_empty())
+                .collect(),
+            predicates,
+        })
+    }
+
+    pub fn matches(&self, event: &Event) -> bool {
+        (self.patterns.is_empty() || self.patterns.iter().any(|p| glob_match(p, &event.name)))
+            && self.predicates.iter().all(|p| p.matches(&event.data))
+    }
+}
+
+impl Predicate {
+    fn parse(source: &str) -> Result<Self> {
+        let (at, op_str, op) = OPS
+            .iter()
+            .filter_map(|(op_str, op)| {
+                find_outside_quotes(source, op_str).map(|at| (at, *op_str, *op))
+            })
+            // the leftmost operator, the longest one when two start there
+            .min_by_key(|(at, op_str, _)| (*at, usize::MAX - op_str.len()))
+            .ok_or_else(|| anyhow::anyhow!("no operator in predicate `{}`", source))?;
+
+        let path = source[..at].trim();
+        let path = path
+            .strip_prefix("$.")
+            .or_else(|| path.strip_prefix("data."))
+            .unwrap_or(path);
+        if path.is_empty() {
+            anyhow::bail!("no path in predicate `{}`", source);
+        }
+        let raw = source[at + op_str.len()..].trim();
+        if raw.is_empty() {
+            anyhow::bail!("no value in predicate `{}`", source);
+        }
+        let value =
+            serde_json::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_string()));
+
+        Ok(Self {
+            path: parse_path(path)?,
+            op,
+            value,
+        })
+    }
+
+    fn matches(&self, data: &Value) -> bool {
+        let mut current = data;
+        for segment in &self.path {
+            let next = match segment {
+                Segment::Key(key) => current.get(key),
+                Segment::Index(index) => current.get(index),
+            };
+            match next {
+                Some(next) => current = next,
+                // a missing field only differs from anything
+                None => return self.op == Op::Ne,
+            }
+        }
+
+        match self.op {
+            Op::Eq => values_equal(current, &self.value),
+            Op::Ne => !values_equal(current, &self.value),
+            Op::Contains => match (current, &self.value) {
+                (Value::String(s), Value::String(needle)) => {
+                    s.to_lowercase().contains(&needle.to_lowercase())
+                }
+                (Value::Array(items), value) => items.iter().any(|i| values_equal(i, value)),
+                _ => false,
+            },
+            Op::Gt | Op::Ge | Op::Lt | Op::Le => {
+                let ordering = match (current, &self.value) {
+                    (Value::Number(a), Value::Number(b)) => {
+                        a.as_f64().zip(b.as_f64()).and_then(|(a, b)| a.partial_cmp(&b))
+                    }
+                    (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+                    _ => None,
+                };
+                ordering.is_some_and(|ordering| match self.op {
+                    Op::Gt => ordering.is_gt(),
+                    Op::Ge => ordering.is_ge(),
+                    Op::Lt => ordering.is_lt(),
+                    _ => ordering.is_le(),
+                })
+            }
+        }
+    }
+}
+
+/// Numbers compare by value, so `3 == 3.0`.
+fn values_equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        _ => a == b,
+    }
+}
+
+fn parse_path(path: &str) -> Result<Vec<Segment>> {
+    let mut segments = Vec::new();
+    for part in path.split('.') {
+        let (key, mut indexes) = match part.find('[') {
+            Some(at) => (&part[..at], &part[at..]),
+            None => (part, ""),
+        };
+        if !key.is_empty() {
+            segments.push(Segment::Key(key.to_string()));
+        }
+        while let Some(rest) = indexes.strip_prefix('[') {
+            let close = rest
+                .find(']')
+                .ok_or_else(|| anyhow::anyhow!("unclosed `[` in path `{}`", path))?;
+            let index = rest[..close]
+                .trim()
+                .parse()
+                .map_err(|_| anyhow::anyhow!("bad index in path `{}`", path))?;
+            segments.push(Segment::Index(index));
+            indexes = &rest[close + 1..];
+        }
+        if !indexes.is_empty() || (key.is_empty() && segments.is_empty()) {
+            anyhow::bail!("bad path `{}`", path);
+        }
+    }
+    Ok(segments)
+}
+
+fn find_outside_quotes(source: &str, needle: &str) -> Option<usize> {
+    let mut in_quotes = false;
+    let mut escaped = false;
+    for (i, c) in source.char_indices() {
+        if in_quotes {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_quotes = false,
+                _ => {}
+            }
+        } else if c == '"' {
+            in_quotes = true;
+        } else if source[i..].starts_with(needle) {
+            return Some(i);
+        }
+    }
+    None
+}
+
+fn split_outside_quotes<'a>(source: &'a str, separator: &str) -> Vec<&'a str> {
+    let mut parts = Vec::new();
+    let mut rest = source;
+    while let Some(at) = find_outside_quotes(rest, separator) {
+        parts.push(rest[..at].trim());
+        rest = &rest[at + separator.len()..];
+    }
+    parts.push(rest.trim());
+    parts
+}
+
+/// `*` matches any run of characters, `?` a single one.
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // where the last `*` was and the name position it matched up to
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
@@ -1,6 +1,14 @@
+mod builtin_events;
+mod event_log;
 mod events_manager;
+mod filter;
+mod schema;
 
+pub use builtin_events::*;
+pub use event_log::{EventLog, EventLogConfig, LogCursor};
 pub use events_manager::*;
+pub use filter::{glob_match, EventFilter};
+pub use schema::{EventRegistry, EventSchema, EventValidationError, BUILTIN_EVENTS};
 
 mod custom_events;
 
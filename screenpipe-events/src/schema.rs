@@ -0,0 +1,149 @@
+//! Registry of event schemas.
+//!
+//! Every built-in event is declared in [`BUILTIN_EVENTS`] with a version, the Rust type of
+//! its payload and a JSON Schema. Events with a registered schema are validated when they
+//! are sent, events nobody registered go through as they are. The catalogue is served at
+//! `/events/schema` so pipes and the js sdk can generate their types.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Catalogue of the events screenpipe itself sends.
+pub const BUILTIN_EVENTS: &str = include_str!("events.schema.json");
+
+static EVENT_REGISTRY: Lazy<EventRegistry> = Lazy::new(|| {
+    let registry = EventRegistry::default();
+    let builtins: Vec<EventSchema> =
+        serde_json::from_str(BUILTIN_EVENTS).expect("built-in event catalogue is valid json");
+    for schema in builtins {
+        registry
+            .register(schema)
+            .expect("built-in event schemas are valid json schemas");
+    }
+    registry
+});
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct EventSchema {
+    pub name: String,
+    /// Bumped on every change of the payload that could break a consumer.
+    pub version: u32,
+    #[serde(default)]
+    pub description: String,
+    /// Rust type of the payload, for events sent from Rust.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rust_type: Option<String>,
+    /// JSON Schema of the payload.
+    pub schema: Value,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventValidationError {
+    pub event: String,
+    pub version: u32,
+    pub issues: Vec<String>,
+}
+
+impl fmt::Display for EventValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid {} event (v{}): {}",
+            self.event,
+            self.version,
+            self.issues.join("; ")
+        )
+    }
+}
+
+impl std::error::Error for EventValidationError {}
+
+struct RegisteredEvent {
+    schema: EventSchema,
+    validator: Arc<jsonschema::Validator>,
+}
+
+#[derive(Default)]
+pub struct EventRegistry {
+    events: RwLock<HashMap<String, RegisteredEvent>>,
+}
+
+impl EventRegistry {
+    /// The registry `send_event` validates against, with the built-in events.
+    pub fn instance() -> &'static EventRegistry {
+        &EVENT_REGISTRY
+    }
+
+    /// Declares an event, or a newer version of one. Fails on an invalid JSON Schema or a
+    /// version older than the registered one.
+    pub fn register(&self, schema: EventSchema) -> Result<()> {
+        let validator = jsonschema::validator_for(&schema.schema)
+            .map_err(|e| anyhow::anyhow!("invalid schema for event {}: {}", schema.name, e))?;
+        let mut events = self.events.write();
+        if let Some(existing) = events.get(&schema.name) {
+            if existing.schema.version > schema.version {
+                anyhow::bail!(
+                    "event {} is already registered at v{}, can't go back to v{}",
+                    schema.name,
+                    existing.schema.version,
+                    schema.version
+                );
+            }
+        }
+        events.insert(
+            schema.name.clone(),
+            RegisteredEvent {
+                schema,
+                validator: Arc::new(validator),
+            },
+        );
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<EventSchema> {
+        self.events.read().get(name).map(|e| e.schema.clone())
+    }
+
+    /// Every registered event, by name.
+    pub fn catalogue(&self) -> Vec<EventSchema> {
+        let mut schemas: Vec<EventSchema> = self
+            .events
+            .read()
+            .values()
+            .map(|e| e.schema.clone())
+            .collect();
+        schemas.sort_by(|a, b| a.name.cmp(&b.name));
+        schemas
+    }
+
+    /// Checks `data` against the schema of `name`, unknown events are valid.
+    pub fn validate(&self, name: &str, data: &Value) -> Result<(), EventValidationError> {
+        let (version, validator) = match self.events.read().get(name) {
+            Some(event) => (event.schema.version, event.validator.clone()),
+            None => return Ok(()),
+        };
+        let issues: Vec<String> = validator
+            .iter_errors(data)
+            .map(|error| match error.instance_path.to_string() {
+                path if path.is_empty() => error.to_string(),
+                path => format!("{}: {}", path, error),
+            })
+            .collect();
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(EventValidationError {
+                event: name.to_string(),
+                version,
+                issues,
+            })
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use futures::StreamExt;
+use screenpipe_events::{
+    enable_event_log, send_event, subscribe_to_all_events_from, subscribe_to_event_from, EventLog,
+    EventLogConfig, EventManager, LogCursor,
+};
+use serde_json::json;
+use std::io::Write;
+use tokio::time::{timeout, Duration};
+
+#[test]
+fn test_log_continues_after_reopen() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = EventLogConfig::new(dir.path());
+
+    let log = EventLog::open(config.clone()).unwrap();
+    assert_eq!(log.append("a", &json!(1)).unwrap(), 0);
+    assert_eq!(log.append("b", &json!(2)).unwrap(), 1);
+    drop(log);
+
+    // a crash in the middle of an event
+    let segment = std::fs::read_dir(dir.path())
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .path();
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(&segment)
+        .unwrap()
+        .write_all(b"{\"offset\":2,\"na")
+        .unwrap();
+
+    let log = EventLog::open(config).unwrap();
+    assert_eq!(log.next_offset(), 2);
+    assert_eq!(log.append("c", &json!(3)).unwrap(), 2);
+
+    let events = log.read(1, 10).unwrap();
+    assert_eq!(
+        events
+            .iter()
+            .map(|e| (e.offset.unwrap(), e.name.as_str(), e.data.clone()))
+            .collect::<Vec<_>>(),
+        vec![(1, "b", json!(2)), (2, "c", json!(3))]
+    );
+    assert_eq!(log.read(0, 1).unwrap().len(), 1);
+}
+
+#[test]
+fn test_log_retention() {
+    let dir = tempfile::tempdir().unwrap();
+    let log = EventLog::open(EventLogConfig {
+        segment_bytes: 200,
+        max_bytes: Some(1000),
+        ..EventLogConfig::new(dir.path())
+    })
+    .unwrap();
+
+    for i in 0..200 {
+        log.append("retention", &json!({ "i": i })).unwrap();
+    }
+    assert_eq!(log.next_offset(), 200);
+    assert!(log.first_offset() > 0);
+
+    // reading from the start gets what is left
+    let events = log.read(0, 1000).unwrap();
+    assert_eq!(events[0].offset, Some(log.first_offset()));
+    assert_eq!(events.last().unwrap().offset, Some(199));
+    let offsets: Vec<u64> = events.iter().map(|e| e.offset.unwrap()).collect();
+    assert!(offsets.windows(2).all(|w| w[1] == w[0] + 1));
+}
+
+#[test]
+fn test_log_cursor() {
+    let dir = tempfile::tempdir().unwrap();
+    let log = EventLog::open(EventLogConfig {
+        segment_bytes: 200,
+        max_bytes: None,
+        ..EventLogConfig::new(dir.path())
+    })
+    .unwrap();
+    for i in 0..50 {
+        log.append("cursor", &json!(i)).unwrap();
+    }
+
+    // batches pick up where the last one stopped, across segments
+    let mut cursor = LogCursor::new(5);
+    let mut offsets = Vec::new();
+    loop {
+        let events = log.read_at(&mut cursor, 7).unwrap();
+        if events.is_empty() {
+            break;
+        }
+        offsets.extend(events.iter().map(|e| e.offset.unwrap()));
+    }
+    assert_eq!(offsets, (5..50).collect::<Vec<u64>>());
+
+    log.append("cursor", &json!(50)).unwrap();
+    let events = log.read_at(&mut cursor, 7).unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].data, json!(50));
+}
+
+#[tokio::test]
+async fn test_replay_and_lag() {
+    let dir = tempfile::tempdir().unwrap();
+    enable_event_log(EventLogConfig {
+        exclude: vec!["not_logged".to_string()],
+        ..EventLogConfig::new(dir.path())
+    })
+    .unwrap();
+    let start = EventManager::instance().log().unwrap().next_offset();
+
+    // sent while nobody listens
+    for i in 0..3 {
+        send_event("replayed", i).unwrap();
+    }
+    send_event("not_logged", "skipped").unwrap();
+
+    let mut replay = subscribe_to_event_from::<i32>("replayed", start).unwrap();
+    for i in 0..3 {
+        let event = replay.next().await.unwrap();
+        assert_eq!(event.data, i);
+        assert_eq!(event.offset, Some(start + i as u64));
+    }
+    send_event("replayed", 3).unwrap();
+    assert_eq!(replay.next().await.unwrap().data, 3);
+
+    // more than the channel holds, the subscriber catches up from the log
+    let from = EventManager::instance().log().unwrap().next_offset();
+    let mut lagging = subscribe_to_all_events_from(from).unwrap();
+    for i in 0..15000 {
+        send_event("lagging", i).unwrap();
+    }
+    for i in 0..15000 {
+        let event = timeout(Duration::from_secs(5), lagging.next())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.data, json!(i));
+        assert_eq!(event.offset, Some(from + i));
+    }
+}
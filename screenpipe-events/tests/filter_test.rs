@@ -0,0 +1,78 @@
+use screenpipe_events::{glob_match, Event, EventFilter};
+use serde_json::{json, Value};
+
+fn event(name: &str, data: Value) -> Event {
+    Event {
+        name: name.to_string(),
+        data,
+        offset: None,
+    }
+}
+
+#[test]
+fn test_glob_match() {
+    assert!(glob_match("meeting_*", "meeting_started"));
+    assert!(glob_match("*", "anything"));
+    assert!(glob_match("*_result", "ocr_result"));
+    assert!(glob_match("ui_?rame", "ui_frame"));
+    assert!(glob_match("a*b*c", "aXXbYYc"));
+    assert!(!glob_match("meeting_*", "transcription"));
+    assert!(!glob_match("a*b", "aXXbc"));
+    assert!(!glob_match("ocr", "ocr_result"));
+}
+
+#[test]
+fn test_name_patterns() {
+    let filter = EventFilter::new(vec!["meeting_*".into(), "transcription".into()], None).unwrap();
+    assert!(filter.matches(&event("meeting_ended", json!({}))));
+    assert!(filter.matches(&event("transcription", json!({}))));
+    assert!(!filter.matches(&event("ocr_result", json!({}))));
+
+    let all = EventFilter::new(Vec::new(), Some("  ")).unwrap();
+    assert!(all.matches(&event("ocr_result", json!(null))));
+}
+
+#[test]
+fn test_predicates() {
+    let data = json!({
+        "app_name": "Slack",
+        "window_name": "#general - acme",
+        "speaker": { "id": 3, "name": "Alice" },
+        "tags": ["meeting", "important"],
+        "lines": [{ "text": "hello" }],
+    });
+    let matches = |predicates: &str| {
+        EventFilter::new(Vec::new(), Some(predicates))
+            .unwrap()
+            .matches(&event("ocr_result", data.clone()))
+    };
+
+    assert!(matches(r#"app_name == "Slack""#));
+    assert!(matches("app_name == Slack"));
+    assert!(matches(r#"$.app_name != "Arc""#));
+    assert!(matches(r#"data.window_name ~= "GENERAL""#));
+    assert!(matches("speaker.id >= 3 && speaker.id < 4"));
+    assert!(matches("speaker.id == 3.0"));
+    assert!(matches(r#"tags ~= "important""#));
+    assert!(matches(r#"lines[0].text == "hello""#));
+    assert!(matches("missing != 1"));
+    assert!(matches(r##"window_name == "#general - acme" && app_name == Slack"##));
+
+    assert!(!matches(r#"app_name == "Arc""#));
+    assert!(!matches("speaker.id > 3"));
+    assert!(!matches("missing == 1"));
+    assert!(!matches(r#"app_name == "Slack" && speaker.name == "Bob""#));
+    // operators inside quotes are part of the value
+    assert!(!matches(r#"app_name == "a && b == c""#));
+}
+
+#[test]
+fn test_invalid_predicates() {
+    for predicates in ["app_name", "== 3", "app_name ==", "lines[x] == 1", "lines[0 == 1"] {
+        assert!(
+            EventFilter::new(Vec::new(), Some(predicates)).is_err(),
+            "{} parsed",
+            predicates
+        );
+    }
+}
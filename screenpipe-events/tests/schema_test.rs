@@ -0,0 +1,92 @@
+use chrono::Utc;
+use futures::StreamExt;
+use screenpipe_events::{
+    send_event, subscribe_to_event, EventRegistry, EventSchema, MeetingEvent,
+    RealtimeTranscriptionEvent, BUILTIN_EVENTS,
+};
+use serde_json::json;
+
+#[test]
+fn test_builtin_catalogue() {
+    let catalogue = EventRegistry::instance().catalogue();
+    let names: Vec<&str> = catalogue.iter().map(|e| e.name.as_str()).collect();
+    for name in ["meeting_started", "meeting_ended", "transcription", "pipe_state_changed"] {
+        assert!(names.contains(&name), "{} missing", name);
+    }
+    let builtins: Vec<EventSchema> = serde_json::from_str(BUILTIN_EVENTS).unwrap();
+    assert_eq!(builtins.len(), catalogue.len());
+    assert!(names.windows(2).all(|w| w[0] < w[1]));
+}
+
+#[test]
+fn test_builtin_types_match_their_schema() {
+    let registry = EventRegistry::instance();
+    let meeting = serde_json::to_value(MeetingEvent {
+        app: "zoom.us".to_string(),
+        timestamp: Utc::now(),
+    })
+    .unwrap();
+    registry.validate("meeting_started", &meeting).unwrap();
+    registry.validate("meeting_ended", &meeting).unwrap();
+
+    let transcription = serde_json::to_value(RealtimeTranscriptionEvent {
+        timestamp: Utc::now(),
+        device: "MacBook Pro Microphone".to_string(),
+        transcription: "hello".to_string(),
+        is_final: true,
+        is_input: true,
+    })
+    .unwrap();
+    registry.validate("transcription", &transcription).unwrap();
+
+    let error = registry
+        .validate("transcription", &json!({ "device": 1 }))
+        .unwrap_err();
+    assert_eq!(error.event, "transcription");
+    assert!(error.issues.len() >= 2);
+}
+
+#[test]
+fn test_register_versions() {
+    let registry = EventRegistry::default();
+    let schema = |version: u32, required: &str| EventSchema {
+        name: "pipe_custom".to_string(),
+        version,
+        description: String::new(),
+        rust_type: None,
+        schema: json!({
+            "type": "object",
+            "required": [required],
+        }),
+    };
+
+    // unknown events are not validated
+    registry.validate("pipe_custom", &json!(42)).unwrap();
+
+    registry.register(schema(1, "a")).unwrap();
+    assert!(registry.validate("pipe_custom", &json!({ "b": 1 })).is_err());
+    registry.register(schema(2, "b")).unwrap();
+    registry.validate("pipe_custom", &json!({ "b": 1 })).unwrap();
+    assert_eq!(registry.get("pipe_custom").unwrap().version, 2);
+
+    assert!(registry.register(schema(1, "a")).is_err());
+    assert!(registry
+        .register(EventSchema {
+            schema: json!({ "type": 12 }),
+            ..schema(3, "c")
+        })
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_invalid_events_are_not_sent() {
+    let mut stream = subscribe_to_event::<serde_json::Value>("meeting_started");
+    assert!(send_event("meeting_started", json!({ "app": 1 })).is_err());
+
+    let meeting = MeetingEvent {
+        app: "zoom.us".to_string(),
+        timestamp: Utc::now(),
+    };
+    send_event("meeting_started", meeting).unwrap();
+    assert_eq!(stream.next().await.unwrap().data["app"], "zoom.us");
+}
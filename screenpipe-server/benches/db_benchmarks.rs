@@ -3,7 +3,11 @@
 use criterion::{criterion_group, criterion_main, Criterion};
 use rand::Rng;
 use screenpipe_audio::AudioDevice;
-use screenpipe_server::{db_types::ContentType, DatabaseManager};
+use screenpipe_server::{
+    db_types::ContentType,
+    seed::{seed_database, SeedConfig},
+    DatabaseManager,
+};
 use screenpipe_vision::OcrEngine;
 use std::sync::Arc;
 use tokio::runtime::Runtime;
@@ -99,5 +103,65 @@ fn bench_search(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, bench_search);
+async fn setup_seeded_db(days: u32) -> DatabaseManager {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    let media_dir = tempfile::tempdir().unwrap();
+    seed_database(
+        &db,
+        media_dir.path(),
+        &SeedConfig {
+            days,
+            until: "2024-05-31T19:00:00Z".parse().unwrap(),
+            seed: 42,
+            frame_interval: 10,
+            media: false,
+        },
+    )
+    .await
+    .unwrap();
+    db
+}
+
+// searches over weeks of realistic activity, the db is seeded once per size
+fn bench_search_seeded(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+
+    let mut group = c.benchmark_group("seeded_search_benchmarks");
+    group.sample_size(10);
+
+    for days in [7, 30] {
+        let db = rt.block_on(setup_seeded_db(days));
+        for content_type in [ContentType::OCR, ContentType::Audio, ContentType::All] {
+            for query in ["invoice", "standup", "nothing matches this"] {
+                group.bench_function(
+                    format!("{:?}_days_{}_query_{}", content_type, days, query),
+                    |b| {
+                        b.to_async(&rt).iter(|| async {
+                            db.search(
+                                query,
+                                content_type.clone(),
+                                100,
+                                0,
+                                None,
+                                None,
+                                None,
+                                None,
+                                None,
+                                None,
+                                None,
+                                None,
+                            )
+                            .await
+                            .unwrap()
+                        });
+                    },
+                );
+            }
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_search, bench_search_seeded);
 criterion_main!(benches);
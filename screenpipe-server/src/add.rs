@@ -24,9 +24,9 @@ use walkdir::WalkDir;
 
 use crate::{
     cli::CliOcrEngine,
+    store::Store,
     text_embeds::generate_embedding,
     video_utils::{extract_frames_from_video, get_video_metadata, VideoMetadataOverrides},
-    DatabaseManager,
 };
 
 #[allow(clippy::too_many_arguments)]
@@ -34,7 +34,7 @@ pub async fn handle_index_command(
     screenpipe_dir: PathBuf,
     path: String,
     pattern: Option<String>,
-    db: Arc<DatabaseManager>,
+    db: Arc<dyn Store>,
     output_format: crate::cli::OutputFormat,
     ocr_engine: Option<CliOcrEngine>,
     metadata_override: Option<PathBuf>,
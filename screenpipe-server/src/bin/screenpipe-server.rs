@@ -9,15 +9,27 @@ use screenpipe_audio::{
     default_input_device, default_output_device, list_audio_devices, parse_audio_device,
     AudioDevice, DeviceControl,
 };
-use screenpipe_core::find_ffmpeg_path;
+use screenpipe_core::{
+    find_ffmpeg_path, PipeInstallOptions, PipeLogLevel, PipeLogQuery, PipeLogRecord,
+};
+use screenpipe_events::{enable_event_log, EventLogConfig};
 use screenpipe_server::{
     cli::{
         AudioCommand, Cli, CliAudioTranscriptionEngine, CliOcrEngine, Command, OutputFormat,
-        PipeCommand, VisionCommand,
+        PipeCommand, PipeSecretCommand, VisionCommand,
     },
-    handle_index_command,
-    pipe_manager::PipeInfo,
-    start_continuous_recording, watch_pid, DatabaseManager, PipeManager, ResourceMonitor, Server,
+    db_maintenance::{start_db_maintenance, MaintenanceConfig},
+    handle_index_command, open_store,
+    pipe_dev::{run_pipe_dev, PipeDevOptions},
+    pipe_manager::{PipeHost, PipeInfo},
+    seed::{seed_database, SeedConfig},
+    start_continuous_recording,
+    storage::{MediaStorage, S3ChunkStore, S3Config},
+    watch_pid,
+    webhooks::set_webhook_secrets_dir,
+    ConversationConfig, ConversationSegmenter, DatabaseManager, MeetingDetectorConfig,
+    MeetingTracker, MqttBridge, MqttConfig, PipeManager, ResourceMonitor, RuleEngine,
+    RuleEngineConfig, Server, Store, StoreCronHistory, StoreUrl, WebhookConfig, WebhookDispatcher,
 };
 use screenpipe_vision::monitor::list_monitors;
 #[cfg(target_os = "macos")]
@@ -173,12 +185,19 @@ async fn main() -> anyhow::Result<()> {
                     | PipeCommand::Update { .. }
                     | PipeCommand::Purge { .. }
                     | PipeCommand::Delete { .. }
+                    | PipeCommand::Rollback { .. }
+                    | PipeCommand::Secret { .. }
+                    | PipeCommand::Dev { .. }
             )
         }
         Some(Command::Add {
             output: OutputFormat::Text,
             ..
         }) => true,
+        Some(Command::Seed {
+            output: OutputFormat::Json,
+            ..
+        }) => false,
         _ => true,
     };
 
@@ -319,15 +338,76 @@ async fn main() -> anyhow::Result<()> {
             }
             Command::Migrate => {
                 info!("running database migrations...");
-                DatabaseManager::new(&format!("{}/db.sqlite", local_data_dir.to_string_lossy()))
-                    .await
-                    .map_err(|e| {
-                        error!("failed to initialize database: {:?}", e);
-                        e
-                    })?;
+                let database_url = cli
+                    .database_url
+                    .clone()
+                    .unwrap_or_else(|| format!("{}/db.sqlite", local_data_dir.to_string_lossy()));
+                open_store(&database_url).await.map_err(|e| {
+                    error!("failed to initialize database: {:?}", e);
+                    e
+                })?;
                 info!("database migrations completed successfully");
                 return Ok(());
             }
+            Command::Seed {
+                path,
+                days,
+                seed,
+                until,
+                frame_interval,
+                no_media,
+                output,
+            } => {
+                let db_path = path.join("db.sqlite");
+                if cli.database_url.is_none() && db_path.exists() {
+                    return Err(anyhow::anyhow!(
+                        "{} already has a database, seed into an empty directory",
+                        path.display()
+                    ));
+                }
+                let media_dir = path.join("data");
+                fs::create_dir_all(&media_dir)?;
+                let database_url = cli
+                    .database_url
+                    .clone()
+                    .unwrap_or_else(|| db_path.to_string_lossy().into_owned());
+                let store = open_store(&database_url).await.map_err(|e| {
+                    error!("failed to initialize database: {:?}", e);
+                    e
+                })?;
+                let summary = seed_database(
+                    store.as_ref(),
+                    &media_dir,
+                    &SeedConfig {
+                        days: *days,
+                        until: until.unwrap_or_else(chrono::Utc::now),
+                        seed: *seed,
+                        frame_interval: *frame_interval,
+                        media: !*no_media,
+                    },
+                )
+                .await?;
+                match output {
+                    OutputFormat::Json => println!(
+                        "{}",
+                        serde_json::to_string_pretty(&json!({
+                            "data": summary,
+                            "success": true
+                        }))?
+                    ),
+                    OutputFormat::Text => {
+                        println!("seeded {} with {} days of activity:", path.display(), days);
+                        println!("  video chunks: {}", summary.video_chunks);
+                        println!("  frames: {}", summary.frames);
+                        println!("  audio chunks: {}", summary.audio_chunks);
+                        println!("  transcriptions: {}", summary.transcriptions);
+                        println!("  speakers: {}", summary.speakers);
+                        println!("  ui monitoring rows: {}", summary.ui_monitoring);
+                        println!("  tags: {}", summary.tags);
+                    }
+                }
+                return Ok(());
+            }
             Command::Add {
                 path,
                 output,
@@ -355,17 +435,14 @@ async fn main() -> anyhow::Result<()> {
                     debug!("debug logging enabled");
                 }
 
-                let db = Arc::new(
-                    DatabaseManager::new(&format!(
-                        "{}/db.sqlite",
-                        local_data_dir.to_string_lossy()
-                    ))
-                    .await
-                    .map_err(|e| {
-                        error!("failed to initialize database: {:?}", e);
-                        e
-                    })?,
-                );
+                let database_url = cli
+                    .database_url
+                    .clone()
+                    .unwrap_or_else(|| format!("{}/db.sqlite", local_data_dir.to_string_lossy()));
+                let db = open_store(&database_url).await.map_err(|e| {
+                    error!("failed to initialize database: {:?}", e);
+                    e
+                })?;
                 handle_index_command(
                     local_data_dir,
                     path.to_string(),
@@ -492,17 +569,148 @@ async fn main() -> anyhow::Result<()> {
     let resource_monitor = ResourceMonitor::new(!cli.disable_telemetry);
     resource_monitor.start_monitoring(Duration::from_secs(10), Some(Duration::from_secs(60)));
 
-    let db = Arc::new(
-        DatabaseManager::new(&format!("{}/db.sqlite", local_data_dir.to_string_lossy()))
-            .await
-            .map_err(|e| {
+    if cli.enable_event_log {
+        // frames carry screenshots, far too big to keep around
+        enable_event_log(EventLogConfig {
+            max_bytes: Some(cli.event_log_max_size_mb * 1024 * 1024),
+            max_age: Some(Duration::from_secs(cli.event_log_retention_days * 24 * 60 * 60)),
+            exclude: vec!["ocr_result".to_string(), "ui_frame".to_string()],
+            ..EventLogConfig::new(local_data_dir.join("events"))
+        })?;
+        info!("event log enabled in {}", local_data_dir.join("events").display());
+    }
+
+    #[cfg(unix)]
+    if !cli.disable_event_socket {
+        let path = cli
+            .event_socket
+            .clone()
+            .unwrap_or_else(|| local_data_dir.join("events.sock"));
+        // other ways to reach the bus still work without it
+        if let Err(e) = screenpipe_server::start_event_socket(&path) {
+            warn!("failed to listen on event socket {}: {}", path.display(), e);
+        }
+    }
+
+    if let Some(url) = &cli.mqtt_url {
+        let mut config = MqttConfig::from_url(url)?;
+        config.events = cli.mqtt_event.clone();
+        config.topic_prefix = cli.mqtt_topic_prefix.clone();
+        config.accept_commands = cli.mqtt_accept_commands;
+        if let Some(device) = &cli.mqtt_device {
+            config.device = device.clone();
+        }
+        info!(
+            "mirroring events to mqtt broker {}:{} as {}",
+            config.host, config.port, config.device
+        );
+        MqttBridge::new(config)?.start();
+    }
+
+    let database_url = cli
+        .database_url
+        .clone()
+        .unwrap_or_else(|| format!("{}/db.sqlite", local_data_dir.to_string_lossy()));
+    let db: Arc<dyn Store> = match StoreUrl::parse(&database_url) {
+        StoreUrl::Sqlite(path) => {
+            let sqlite = Arc::new(DatabaseManager::new(path).await.map_err(|e| {
                 eprintln!("failed to initialize database: {:?}", e);
                 e
-            })?,
-    );
+            })?);
+            // maintenance tasks are sqlite specific, postgres has its own autovacuum
+            if !cli.disable_db_maintenance {
+                start_db_maintenance(sqlite.clone(), MaintenanceConfig::default());
+            }
+            sqlite
+        }
+        StoreUrl::Postgres(_) => open_store(&database_url).await.map_err(|e| {
+            eprintln!("failed to initialize database: {:?}", e);
+            e
+        })?,
+    };
+    info!("using {} database", db.backend());
+    // before any pipe starts, so every cron run lands in the database
+    screenpipe_core::set_cron_history(Arc::new(StoreCronHistory(db.clone())));
+    // webhook secrets are sealed with the key of the pipe secrets
+    set_webhook_secrets_dir(local_data_dir.clone());
+
+    // also delivers the webhook actions of automation rules
+    let webhooks = WebhookDispatcher::new(
+        db.clone(),
+        WebhookConfig {
+            max_attempts: cli.webhook_max_attempts,
+            ..Default::default()
+        },
+    )?;
+    if !cli.disable_webhooks {
+        webhooks.clone().start();
+    }
+
+    if !cli.disable_automation_rules {
+        RuleEngine::new(
+            db.clone(),
+            Some(pipe_manager.clone()),
+            webhooks,
+            RuleEngineConfig::default(),
+        )
+        .start();
+    }
+
+    if !cli.disable_meeting_detection {
+        MeetingTracker::new(db.clone(), MeetingDetectorConfig::default()).start();
+    }
+
+    if !cli.disable_conversation_segmentation {
+        ConversationSegmenter::new(db.clone(), ConversationConfig::default()).start();
+    }
 
     let db_server = db.clone();
 
+    let media_storage = match (&cli.s3_bucket, &cli.s3_access_key, &cli.s3_secret_key) {
+        (Some(bucket), Some(access_key), Some(secret_key)) => {
+            let store = S3ChunkStore::new(S3Config {
+                endpoint: cli.s3_endpoint.clone(),
+                bucket: bucket.clone(),
+                region: cli.s3_region.clone(),
+                access_key: access_key.clone(),
+                secret_key: secret_key.clone(),
+                prefix: cli.s3_prefix.clone(),
+            })?;
+            Arc::new(
+                MediaStorage::with_remote(
+                    db.clone(),
+                    Arc::new(store),
+                    local_data_dir.join("media_cache"),
+                    cli.media_cache_size_mb * 1024 * 1024,
+                )
+                .await?,
+            )
+        }
+        (Some(_), _, _) => {
+            eprintln!(
+                "--s3-bucket requires --s3-access-key and --s3-secret-key, media offload disabled"
+            );
+            Arc::new(MediaStorage::local(db.clone()))
+        }
+        _ => Arc::new(MediaStorage::local(db.clone())),
+    };
+
+    if let Some(days) = cli.offload_after_days {
+        if media_storage.has_remote() {
+            media_storage.clone().start_offload_task(
+                chrono::Duration::days(days as i64),
+                Duration::from_secs(60 * 60),
+            );
+        } else {
+            eprintln!("--offload-after-days requires an s3 bucket, media offload disabled");
+        }
+    }
+    // wasm pipes reach the database and media through the pipe manager
+    pipe_manager.set_host(PipeHost {
+        store: db.clone(),
+        media: media_storage.clone(),
+    });
+
     // Channel for controlling the recorder ! TODO RENAME SHIT
     let vision_control = Arc::new(AtomicBool::new(true));
 
@@ -634,7 +842,8 @@ async fn main() -> anyhow::Result<()> {
         cli.disable_vision,
         cli.disable_audio,
         cli.enable_ui_monitoring,
-    );
+    )
+    .with_media_storage(media_storage.clone());
 
     let mut rx = audio_devices_tx.subscribe();
     let audio_devices_control_for_spawn = audio_devices_control.clone();
@@ -752,6 +961,14 @@ async fn main() -> anyhow::Result<()> {
         "│ frame cache            │ {:<34} │",
         cli.enable_frame_cache
     );
+    println!(
+        "│ media offload          │ {:<34} │",
+        match (&cli.s3_bucket, cli.offload_after_days) {
+            (Some(bucket), Some(days)) =>
+                format_cell(&format!("{} after {} days", bucket, days), VALUE_WIDTH),
+            _ => "disabled".to_string(),
+        }
+    );
 
     const VALUE_WIDTH: usize = 34;
 
@@ -1140,11 +1357,35 @@ async fn handle_pipe_command(
         }
 
         #[allow(deprecated)]
-        PipeCommand::Download { url, output, port }
-        | PipeCommand::Install { url, output, port } => {
+        PipeCommand::Download {
+            url,
+            sha256,
+            signature,
+            public_key,
+            output,
+            port,
+        }
+        | PipeCommand::Install {
+            url,
+            sha256,
+            signature,
+            public_key,
+            output,
+            port,
+        } => {
+            let options = PipeInstallOptions {
+                sha256,
+                signature,
+                public_key,
+            };
             match client
                 .post(format!("{}:{}/pipes/download", server_url, port))
-                .json(&json!({ "url": url }))
+                .json(&json!({
+                    "url": url,
+                    "sha256": options.sha256,
+                    "signature": options.signature,
+                    "public_key": options.public_key,
+                }))
                 .send()
                 .await
             {
@@ -1158,7 +1399,28 @@ async fn handle_pipe_command(
                         ),
                     }
                 }
-                _ => match pipe_manager.download_pipe(&url).await {
+                // the server rejected the pipe.json or its checksum, installing locally would
+                // fail the same way
+                Ok(response) if response.status() == reqwest::StatusCode::BAD_REQUEST => {
+                    let data: Value = response.json().await?;
+                    match output {
+                        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&data)?),
+                        OutputFormat::Text => {
+                            eprintln!(
+                                "{}",
+                                data["error"].as_str().unwrap_or("failed to download pipe")
+                            );
+                            for issue in data["details"].as_array().into_iter().flatten() {
+                                eprintln!(
+                                    "  {}: {}",
+                                    issue["path"].as_str().unwrap_or_default(),
+                                    issue["message"].as_str().unwrap_or_default()
+                                );
+                            }
+                        }
+                    }
+                }
+                _ => match pipe_manager.download_pipe(&url, &options).await {
                     Ok(pipe_id) => match output {
                         OutputFormat::Json => println!(
                             "{}",
@@ -1300,6 +1562,218 @@ async fn handle_pipe_command(
             }
         }
 
+        PipeCommand::Rollback { id, version, port } => {
+            match client
+                .post(format!("{}:{}/pipes/rollback", server_url, port))
+                .json(&json!({ "pipe_id": id, "version": version }))
+                .send()
+                .await
+            {
+                Ok(response) if response.status().is_success() => {
+                    let data: Value = response.json().await?;
+                    println!(
+                        "pipe '{}' rolled back to version {} in running server",
+                        id, data["data"]["version"]["version"]
+                    );
+                }
+                Ok(response) => {
+                    let data: Value = response.json().await?;
+                    println!("failed to roll back pipe: {}", data["error"]);
+                }
+                Err(_) => match pipe_manager.restore_pipe(id, version.as_deref()).await {
+                    Ok(restored) => {
+                        println!(
+                            "pipe '{}' rolled back to version {} in local files",
+                            id, restored.version
+                        );
+                        println!("note: server not running, pipe will start on next server launch");
+                    }
+                    Err(e) => println!("failed to roll back pipe: {}", e),
+                },
+            }
+        }
+
+        PipeCommand::Dev {
+            path,
+            fixtures,
+            fixtures_port,
+            port,
+        } => {
+            run_pipe_dev(
+                PipeDevOptions {
+                    path: path.clone(),
+                    screenpipe_dir: pipe_manager.screenpipe_dir().to_path_buf(),
+                    server_url: format!("{}:{}", server_url, port),
+                    fixtures_port: fixtures.then_some(*fixtures_port),
+                },
+                async {
+                    let _ = signal::ctrl_c().await;
+                },
+            )
+            .await?;
+        }
+
+        PipeCommand::Secret { subcommand } => match subcommand {
+            PipeSecretCommand::Set {
+                id,
+                name,
+                value,
+                port,
+            } => {
+                // read from stdin by default, so the value stays out of the shell history
+                let value = match value {
+                    Some(value) => value.clone(),
+                    None => {
+                        print!("value of {}: ", name);
+                        std::io::stdout().flush()?;
+                        let mut input = String::new();
+                        std::io::stdin().read_line(&mut input)?;
+                        input.trim_end_matches(['\r', '\n']).to_string()
+                    }
+                };
+                match client
+                    .post(format!("{}:{}/pipes/{}/secrets", server_url, port, id))
+                    .json(&json!({ "name": name, "value": value }))
+                    .send()
+                    .await
+                {
+                    Ok(response) if response.status().is_success() => {
+                        println!("secret {} of pipe '{}' set in running server", name, id);
+                        println!("note: restart the pipe to apply it");
+                    }
+                    Ok(response) => {
+                        let data: Value = response.json().await?;
+                        println!("failed to set secret: {}", data["error"]);
+                    }
+                    Err(_) => match pipe_manager.set_secret(id, name, &value).await {
+                        Ok(()) => {
+                            println!("secret {} of pipe '{}' set in local files", name, id);
+                        }
+                        Err(e) => println!("failed to set secret: {}", e),
+                    },
+                }
+            }
+            PipeSecretCommand::List { id, port } => {
+                let names: Vec<String> = match client
+                    .get(format!("{}:{}/pipes/{}/secrets", server_url, port, id))
+                    .send()
+                    .await
+                {
+                    Ok(response) if response.status().is_success() => {
+                        let data: Value = response.json().await?;
+                        serde_json::from_value(data["data"].clone())?
+                    }
+                    Ok(response) => {
+                        let data: Value = response.json().await?;
+                        println!("failed to list secrets: {}", data["error"]);
+                        return Ok(());
+                    }
+                    Err(_) => match pipe_manager.list_secrets(id).await? {
+                        Some(names) => names,
+                        None => {
+                            println!("pipe '{}' not found", id);
+                            return Ok(());
+                        }
+                    },
+                };
+                if names.is_empty() {
+                    println!("no secrets set for pipe '{}'", id);
+                }
+                for name in names {
+                    println!("  {}", name);
+                }
+            }
+            PipeSecretCommand::Remove { id, name, port } => {
+                match client
+                    .delete(format!(
+                        "{}:{}/pipes/{}/secrets/{}",
+                        server_url, port, id, name
+                    ))
+                    .send()
+                    .await
+                {
+                    Ok(response) if response.status().is_success() => {
+                        println!("secret {} of pipe '{}' removed in running server", name, id);
+                    }
+                    Ok(response) => {
+                        let data: Value = response.json().await?;
+                        println!("failed to remove secret: {}", data["error"]);
+                    }
+                    Err(_) => match pipe_manager.remove_secret(id, name).await {
+                        Ok(true) => {
+                            println!("secret {} of pipe '{}' removed in local files", name, id);
+                        }
+                        Ok(false) => println!("secret {} is not set", name),
+                        Err(e) => println!("failed to remove secret: {}", e),
+                    },
+                }
+            }
+        },
+
+        PipeCommand::Logs {
+            id,
+            follow,
+            level,
+            since,
+            lines,
+            output,
+            port,
+        } => {
+            let query = PipeLogQuery {
+                since: *since,
+                level: *level,
+                limit: Some(*lines),
+            };
+            let logs: Vec<PipeLogRecord> = match client
+                .get(format!("{}:{}/pipes/{}/logs", server_url, port, id))
+                .query(&query)
+                .send()
+                .await
+            {
+                Ok(response) if response.status().is_success() => {
+                    let data: Value = response.json().await?;
+                    serde_json::from_value(data["data"].clone())?
+                }
+                Ok(response) if response.status() == reqwest::StatusCode::NOT_FOUND => {
+                    anyhow::bail!("pipe not found")
+                }
+                _ if *follow => {
+                    anyhow::bail!("can't follow logs, server not running on port {}", port)
+                }
+                _ => pipe_manager
+                    .get_pipe_logs(id, &query)
+                    .await?
+                    .ok_or_else(|| anyhow::anyhow!("pipe not found"))?,
+            };
+            for record in &logs {
+                print_pipe_log(record, output)?;
+            }
+
+            if *follow {
+                let mut response = client
+                    .get(format!("{}:{}/pipes/{}/logs/stream", server_url, port, id))
+                    .query(&[("level", level.map(|level| level.to_string()))])
+                    .send()
+                    .await?
+                    .error_for_status()?;
+
+                // server-sent events, one json record per data line
+                let mut buffer = Vec::new();
+                while let Some(chunk) = response.chunk().await? {
+                    buffer.extend_from_slice(&chunk);
+                    while let Some(end) = buffer.iter().position(|b| *b == b'\n') {
+                        let line: Vec<u8> = buffer.drain(..=end).collect();
+                        let line = String::from_utf8_lossy(&line);
+                        if let Some(data) = line.trim_end().strip_prefix("data:") {
+                            if let Ok(record) = serde_json::from_str(data.trim_start()) {
+                                print_pipe_log(&record, output)?;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
         PipeCommand::Purge { yes, port } => {
             if !yes {
                 print!("are you sure you want to purge all pipes? this action cannot be undone. (y/N): ");
@@ -1330,6 +1804,28 @@ async fn handle_pipe_command(
     Ok(())
 }
 
+fn print_pipe_log(record: &PipeLogRecord, output: &OutputFormat) -> anyhow::Result<()> {
+    match output {
+        OutputFormat::Json => println!("{}", serde_json::to_string(record)?),
+        OutputFormat::Text => {
+            let level = format!("{:<5}", record.level);
+            let level = match record.level {
+                PipeLogLevel::Error => level.red(),
+                PipeLogLevel::Warn => level.yellow(),
+                _ => level.normal(),
+            };
+            println!(
+                "{} {} [{}] {}",
+                record.timestamp.format("%Y-%m-%d %H:%M:%S%.3f"),
+                level,
+                record.stream,
+                record.message
+            );
+        }
+    }
+    Ok(())
+}
+
 // Add this function near the end of the file
 async fn check_ffmpeg() -> anyhow::Result<()> {
     // TODO: this should also check if it can properly encode mp4 etc
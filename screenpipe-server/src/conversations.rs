@@ -0,0 +1,453 @@
+//! Conversations segmented from the audio transcriptions, kept in the `conversations` table.
+//!
+//! Transcriptions are stored per chunk and per device, so one conversation shows up as many
+//! overlapping pieces: the mic picks up what the speakers play, and consecutive chunks of a
+//! device repeat the words at their boundary. The segmenter reads the transcriptions that
+//! aren't in a conversation yet, drops the words another device already captured, stitches
+//! the chunk boundaries, splits on silences longer than `silence_gap` and groups consecutive
+//! transcriptions of a speaker into turns. A conversation is stored once nobody spoke in it
+//! for `settle`, its transcript is searchable through `/conversations`.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use screenpipe_audio::DeviceType;
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::db_types::{AudioResult, ContentType, SearchResult};
+use crate::meetings::IdRange;
+use crate::store::{content_between, Store};
+
+/// Rough speaking rate, for transcriptions without segment times.
+const SECONDS_PER_WORD: f64 = 0.4;
+/// Words a chunk boundary repeats at least before they are stitched, single words repeat
+/// by chance.
+const MIN_OVERLAP_WORDS: usize = 2;
+/// Shorter texts are duplicates only when they are the same.
+const MIN_SIMILAR_WORDS: usize = 3;
+
+/// A transcription, as far as conversations go.
+#[derive(Clone, Debug)]
+pub struct Utterance {
+    pub transcription_id: i64,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    pub device_name: String,
+    pub output: bool,
+    pub speaker_id: Option<i64>,
+    pub speaker_name: Option<String>,
+    pub text: String,
+}
+
+impl Utterance {
+    /// Starts at the transcription timestamp and lasts as long as its segment, or as long as
+    /// saying its words takes when the segment times are unknown.
+    pub fn from_audio(audio: &AudioResult) -> Self {
+        let seconds = match (audio.start_time, audio.end_time) {
+            (Some(start), Some(end)) if end > start => end - start,
+            _ => {
+                (audio.transcription.split_whitespace().count() as f64 * SECONDS_PER_WORD).max(1.0)
+            }
+        };
+        Self {
+            transcription_id: audio.transcription_id,
+            start_time: audio.timestamp,
+            end_time: audio.timestamp + chrono::Duration::milliseconds((seconds * 1000.0) as i64),
+            device_name: audio.device_name.clone(),
+            output: audio.device_type == DeviceType::Output,
+            speaker_id: audio.speaker.as_ref().map(|s| s.id),
+            speaker_name: audio
+                .speaker
+                .as_ref()
+                .map(|s| s.name.clone())
+                .filter(|name| !name.trim().is_empty()),
+            text: audio.transcription.trim().to_string(),
+        }
+    }
+}
+
+/// Consecutive transcriptions of one speaker, or of one device when the speakers aren't
+/// known.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ConversationTurn {
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    pub speaker_id: Option<i64>,
+    pub speaker_name: Option<String>,
+    pub device_name: String,
+    pub output: bool,
+    pub text: String,
+    pub transcription_ids: Vec<i64>,
+}
+
+impl ConversationTurn {
+    /// Who spoke, as shown in the transcript.
+    pub fn speaker(&self) -> String {
+        match (&self.speaker_name, self.speaker_id) {
+            (Some(name), _) => name.clone(),
+            (None, Some(id)) => format!("speaker {}", id),
+            (None, None) => self.device_name.clone(),
+        }
+    }
+
+    fn continues(&self, utterance: &Utterance) -> bool {
+        match (self.speaker_id, utterance.speaker_id) {
+            (Some(a), Some(b)) => a == b,
+            (None, None) => self.device_name == utterance.device_name,
+            _ => false,
+        }
+    }
+}
+
+impl From<Utterance> for ConversationTurn {
+    fn from(utterance: Utterance) -> Self {
+        Self {
+            start_time: utterance.start_time,
+            end_time: utterance.end_time,
+            speaker_id: utterance.speaker_id,
+            speaker_name: utterance.speaker_name,
+            device_name: utterance.device_name,
+            output: utterance.output,
+            text: utterance.text,
+            transcription_ids: vec![utterance.transcription_id],
+        }
+    }
+}
+
+/// A conversation as written to the store.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConversationSpec {
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    pub turns: Vec<ConversationTurn>,
+    /// Transcriptions left out, another device captured the same words.
+    pub duplicates: Vec<i64>,
+}
+
+impl ConversationSpec {
+    /// One `speaker: text` line per turn.
+    pub fn transcript(&self) -> String {
+        self.turns
+            .iter()
+            .map(|turn| format!("{}: {}", turn.speaker(), turn.text))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub fn devices(&self) -> Vec<String> {
+        let mut devices: Vec<String> = self.turns.iter().map(|t| t.device_name.clone()).collect();
+        devices.sort();
+        devices.dedup();
+        devices
+    }
+
+    /// Speakers in the order they first spoke.
+    pub fn speakers(&self) -> Vec<String> {
+        let mut speakers = Vec::new();
+        for turn in &self.turns {
+            let speaker = turn.speaker();
+            if !speakers.contains(&speaker) {
+                speakers.push(speaker);
+            }
+        }
+        speakers
+    }
+
+    /// Transcriptions the turns are made of.
+    pub fn transcription_ids(&self) -> impl Iterator<Item = i64> + '_ {
+        self.turns
+            .iter()
+            .flat_map(|turn| turn.transcription_ids.iter().copied())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct Conversation {
+    pub id: i64,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    pub devices: Vec<String>,
+    pub speakers: Vec<String>,
+    pub transcript: String,
+    pub turn_count: i64,
+    pub transcriptions: Option<IdRange>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ConversationConfig {
+    /// How often new transcriptions are read.
+    pub poll_interval: Duration,
+    /// How far back transcriptions are read at startup.
+    pub lookback: Duration,
+    /// Silence that ends a conversation.
+    pub silence_gap: Duration,
+    /// A conversation is stored once nobody spoke in it for this long, at least
+    /// `silence_gap`. Transcription lags behind capture.
+    pub settle: Duration,
+    /// How far apart two devices capture the same words, about a chunk.
+    pub duplicate_window: Duration,
+    /// Share of the words of the shorter text the other has too, for them to be duplicates.
+    pub duplicate_similarity: f64,
+}
+
+impl Default for ConversationConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(60),
+            lookback: Duration::from_secs(3600),
+            silence_gap: Duration::from_secs(90),
+            settle: Duration::from_secs(180),
+            duplicate_window: Duration::from_secs(30),
+            duplicate_similarity: 0.6,
+        }
+    }
+}
+
+fn chrono_duration(duration: Duration) -> chrono::Duration {
+    chrono::Duration::from_std(duration).unwrap_or_else(|_| chrono::Duration::days(1))
+}
+
+fn normalize(word: &str) -> String {
+    word.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.split_whitespace()
+        .map(normalize)
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+/// Share of the words of the shorter text found in the longer one.
+fn similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (words(a), words(b));
+    let (short, long) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    if short.is_empty() {
+        return 0.0;
+    }
+    if short.len() < MIN_SIMILAR_WORDS {
+        return if short == long { 1.0 } else { 0.0 };
+    }
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for word in &long {
+        *counts.entry(word.as_str()).or_default() += 1;
+    }
+    let common = short
+        .iter()
+        .filter(|word| match counts.get_mut(word.as_str()) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                true
+            }
+            _ => false,
+        })
+        .count();
+    common as f64 / short.len() as f64
+}
+
+/// `next` without the words it repeats from the end of `previous`.
+fn strip_overlap(previous: &str, next: &str) -> String {
+    let previous = words(previous);
+    let tokens: Vec<(&str, String)> = next
+        .split_whitespace()
+        .map(|token| (token, normalize(token)))
+        .filter(|(_, word)| !word.is_empty())
+        .collect();
+    let longest = previous.len().min(tokens.len());
+    let overlap = (MIN_OVERLAP_WORDS..=longest).rev().find(|&len| {
+        previous[previous.len() - len..]
+            .iter()
+            .zip(&tokens[..len])
+            .all(|(a, (_, b))| a == b)
+    });
+    match overlap {
+        Some(len) => tokens[len..]
+            .iter()
+            .map(|(token, _)| *token)
+            .collect::<Vec<_>>()
+            .join(" "),
+        None => next.to_string(),
+    }
+}
+
+/// Which of two devices capturing the same words is kept: the output device, it got the
+/// audio first hand, or else the one that got more of it.
+fn prefer(candidate: &Utterance, kept: &Utterance) -> bool {
+    match (candidate.output, kept.output) {
+        (true, false) => true,
+        (false, true) => false,
+        _ => words(&candidate.text).len() > words(&kept.text).len(),
+    }
+}
+
+/// Groups transcriptions into conversations, see the module docs. Duplicates go with the
+/// conversation closest to them.
+pub fn segment(
+    mut utterances: Vec<Utterance>,
+    config: &ConversationConfig,
+) -> Vec<ConversationSpec> {
+    utterances.sort_by_key(|u| (u.start_time, u.transcription_id));
+    let window = chrono_duration(config.duplicate_window);
+    let gap = chrono_duration(config.silence_gap);
+
+    let mut kept: Vec<Utterance> = Vec::new();
+    let mut duplicates: Vec<(DateTime<Utc>, i64)> = Vec::new();
+    for mut utterance in utterances {
+        let previous = kept
+            .iter()
+            .rev()
+            .find(|k| k.device_name == utterance.device_name);
+        if let Some(previous) = previous {
+            if utterance.start_time - previous.end_time <= window {
+                utterance.text = strip_overlap(&previous.text, &utterance.text);
+            }
+        }
+        if words(&utterance.text).is_empty() {
+            duplicates.push((utterance.start_time, utterance.transcription_id));
+            continue;
+        }
+        let same = kept.iter().rposition(|k| {
+            k.device_name != utterance.device_name
+                && (utterance.start_time - k.start_time).abs() <= window
+                && similarity(&k.text, &utterance.text) >= config.duplicate_similarity
+        });
+        match same {
+            Some(i) if prefer(&utterance, &kept[i]) => {
+                let replaced = std::mem::replace(&mut kept[i], utterance);
+                duplicates.push((replaced.start_time, replaced.transcription_id));
+            }
+            Some(_) => duplicates.push((utterance.start_time, utterance.transcription_id)),
+            None => kept.push(utterance),
+        }
+    }
+    kept.sort_by_key(|u| (u.start_time, u.transcription_id));
+
+    let mut conversations: Vec<ConversationSpec> = Vec::new();
+    for utterance in kept {
+        let continues = conversations
+            .last()
+            .is_some_and(|c| utterance.start_time - c.end_time <= gap);
+        if !continues {
+            conversations.push(ConversationSpec {
+                start_time: utterance.start_time,
+                end_time: utterance.end_time,
+                turns: Vec::new(),
+                duplicates: Vec::new(),
+            });
+        }
+        let conversation = conversations.last_mut().expect("a conversation was pushed");
+        conversation.end_time = conversation.end_time.max(utterance.end_time);
+        match conversation.turns.last_mut() {
+            Some(turn) if turn.continues(&utterance) => {
+                turn.end_time = turn.end_time.max(utterance.end_time);
+                turn.text.push(' ');
+                turn.text.push_str(&utterance.text);
+                turn.transcription_ids.push(utterance.transcription_id);
+            }
+            _ => conversation.turns.push(utterance.into()),
+        }
+    }
+
+    for (time, id) in duplicates {
+        let distance = |c: &ConversationSpec| {
+            if time < c.start_time {
+                c.start_time - time
+            } else {
+                (time - c.end_time).max(chrono::Duration::zero())
+            }
+        };
+        let closest = (0..conversations.len()).min_by_key(|&i| distance(&conversations[i]));
+        if let Some(i) = closest {
+            conversations[i].duplicates.push(id);
+        }
+    }
+    conversations
+}
+
+/// Stores the conversations of the transcriptions since `since` that aren't in one yet,
+/// once they settled at `now`. Returns the new conversations, and where the next pass
+/// reads from: the start of the conversation still going on.
+pub async fn segment_transcriptions(
+    store: &dyn Store,
+    config: &ConversationConfig,
+    since: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Result<(Vec<i64>, DateTime<Utc>)> {
+    let settle = chrono_duration(config.settle.max(config.silence_gap));
+    let segmented: HashSet<i64> = store
+        .conversation_transcription_ids(since, now)
+        .await?
+        .into_iter()
+        .collect();
+    let utterances = content_between(store, ContentType::Audio, since, now)
+        .await?
+        .iter()
+        .filter_map(|result| match result {
+            SearchResult::Audio(audio) if !segmented.contains(&audio.transcription_id) => {
+                Some(Utterance::from_audio(audio))
+            }
+            _ => None,
+        })
+        .collect();
+
+    let mut ids = Vec::new();
+    let mut next_since = now - settle;
+    for conversation in segment(utterances, config) {
+        if now - conversation.end_time < settle {
+            next_since = next_since.min(conversation.start_time);
+            continue;
+        }
+        let id = store.insert_conversation(&conversation).await?;
+        debug!(
+            "conversation {} with {} turns from {} to {}",
+            id,
+            conversation.turns.len(),
+            conversation.start_time,
+            conversation.end_time
+        );
+        ids.push(id);
+    }
+    Ok((ids, next_since))
+}
+
+/// Segments the new transcriptions into conversations as they come in.
+pub struct ConversationSegmenter {
+    store: Arc<dyn Store>,
+    config: ConversationConfig,
+}
+
+impl ConversationSegmenter {
+    pub fn new(store: Arc<dyn Store>, config: ConversationConfig) -> Self {
+        Self { store, config }
+    }
+
+    /// Segments until the task is aborted, starting with the transcriptions of `lookback`.
+    pub fn start(self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut since = Utc::now() - chrono_duration(self.config.lookback);
+            let mut poll = tokio::time::interval(self.config.poll_interval);
+            loop {
+                poll.tick().await;
+                let now = Utc::now();
+                match segment_transcriptions(self.store.as_ref(), &self.config, since, now).await {
+                    Ok((ids, next_since)) => {
+                        if !ids.is_empty() {
+                            info!("segmented {} conversations", ids.len());
+                        }
+                        since = next_since;
+                    }
+                    Err(e) => warn!("failed to segment conversations: {}", e),
+                }
+            }
+        })
+    }
+}
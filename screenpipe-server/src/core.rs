@@ -1,6 +1,7 @@
 use crate::cli::{CliVadEngine, CliVadSensitivity};
 use crate::db_types::Speaker;
-use crate::{DatabaseManager, VideoCapture};
+use crate::store::Store;
+use crate::VideoCapture;
 use anyhow::Result;
 use dashmap::DashMap;
 use futures::future::join_all;
@@ -17,15 +18,29 @@ use screenpipe_vision::core::{RealtimeVisionEvent, WindowOcr};
 use screenpipe_vision::OcrEngine;
 use std::collections::HashMap;
 use std::path::PathBuf;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::runtime::Handle;
 use tokio::task::JoinHandle;
 
+/// Unix time in milliseconds until which captured frames and transcriptions are dropped
+/// instead of stored.
+static CAPTURE_PAUSED_UNTIL: AtomicI64 = AtomicI64::new(0);
+
+/// Stops storing frames and transcriptions for `duration`, pausing again extends the pause.
+pub fn pause_capture(duration: Duration) {
+    let until = chrono::Utc::now().timestamp_millis() + duration.as_millis() as i64;
+    CAPTURE_PAUSED_UNTIL.fetch_max(until, Ordering::SeqCst);
+}
+
+pub fn capture_paused() -> bool {
+    CAPTURE_PAUSED_UNTIL.load(Ordering::SeqCst) > chrono::Utc::now().timestamp_millis()
+}
+
 #[allow(clippy::too_many_arguments)]
 pub async fn start_continuous_recording(
-    db: Arc<DatabaseManager>,
+    db: Arc<dyn Store>,
     output_path: Arc<String>,
     fps: f64,
     audio_chunk_duration: Duration,
@@ -174,7 +189,7 @@ pub async fn start_continuous_recording(
 
 #[allow(clippy::too_many_arguments)]
 async fn record_video(
-    db: Arc<DatabaseManager>,
+    db: Arc<dyn Store>,
     output_path: Arc<String>,
     fps: f64,
     is_running: Arc<AtomicBool>,
@@ -226,7 +241,11 @@ async fn record_video(
     );
 
     while is_running.load(Ordering::SeqCst) {
-        if let Some(frame) = video_capture.ocr_frame_queue.pop() {
+        if let Some(frame) = video_capture
+            .ocr_frame_queue
+            .pop()
+            .filter(|_| !capture_paused())
+        {
             for window_result in &frame.window_ocr_results {
                 match db.insert_frame(&device_name, None).await {
                     Ok(frame_id) => {
@@ -284,7 +303,7 @@ async fn record_video(
 
 #[allow(clippy::too_many_arguments)]
 async fn record_audio(
-    db: Arc<DatabaseManager>,
+    db: Arc<dyn Store>,
     chunk_duration: Duration,
     whisper_sender: crossbeam::channel::Sender<AudioInput>,
     whisper_receiver: crossbeam::channel::Receiver<TranscriptionResult>,
@@ -428,6 +447,13 @@ async fn record_audio(
         });
 
         while let Ok(mut transcription) = whisper_receiver.try_recv() {
+            if capture_paused() {
+                debug!(
+                    "capture paused, dropping transcription of {}",
+                    transcription.input.device
+                );
+                continue;
+            }
             info!(
                 "device {} received transcription {:?}",
                 transcription.input.device, transcription.transcription
@@ -477,7 +503,7 @@ async fn record_audio(
 }
 
 async fn process_audio_result(
-    db: &DatabaseManager,
+    db: &dyn Store,
     result: TranscriptionResult,
     audio_transcription_engine: Arc<AudioTranscriptionEngine>,
     previous_transcript: Option<String>,
@@ -558,7 +584,7 @@ async fn process_audio_result(
 }
 
 async fn get_or_create_speaker_from_embedding(
-    db: &DatabaseManager,
+    db: &dyn Store,
     embedding: &[f32],
 ) -> Result<Speaker, anyhow::Error> {
     let speaker = db.get_speaker_from_embedding(embedding).await?;
@@ -571,7 +597,7 @@ async fn get_or_create_speaker_from_embedding(
 }
 
 pub async fn merge_speakers(
-    db: &DatabaseManager,
+    db: &dyn Store,
     speaker_to_keep_id: i64,
     speaker_to_merge_id: i64,
 ) -> Result<Speaker, anyhow::Error> {
@@ -1,37 +1,71 @@
+use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use image::DynamicImage;
 use libsqlite3_sys::sqlite3_auto_extension;
 use screenpipe_audio::{AudioDevice, DeviceType};
+use screenpipe_core::CronRun;
 use screenpipe_vision::OcrEngine;
 use sqlite_vec::sqlite3_vec_init;
 use sqlx::migrate::MigrateDatabase;
-use sqlx::sqlite::{SqlitePool, SqlitePoolOptions};
+use sqlx::sqlite::{SqliteAutoVacuum, SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
 use sqlx::Column;
 use sqlx::Error as SqlxError;
 use sqlx::Row;
 use sqlx::TypeInfo;
 use sqlx::ValueRef;
+use std::path::Path;
 use std::sync::Arc;
 use std::time::Duration;
 use tracing::{debug, error, warn};
 
 use std::collections::BTreeMap;
-use tokio::time::{timeout, Duration as TokioDuration};
+use std::str::FromStr;
 
 use zerocopy::AsBytes;
 
+use crate::conversations::{Conversation, ConversationSpec, ConversationTurn};
+use crate::db_maintenance::MaintenanceRun;
 use crate::db_types::{
     AudioChunksResponse, AudioEntry, AudioResult, AudioResultRaw, FrameData, OCREntry, OCRResult,
     OCRResultRaw, Speaker, TagContentType,
 };
-use crate::db_types::{ContentType, UiContent};
+use crate::db_types::{ChunkKind, ContentType, OffloadCandidate, UiContent};
+use crate::db_types::{SearchCursor, SearchKind, SpeakerCursor, UnnamedSpeaker};
 use crate::db_types::{SearchResult, TimeSeriesChunk};
+use crate::db_writer::{DbWriter, DbWriterConfig, DbWriterMetrics, WriteOp};
+use crate::meetings::{Meeting, MeetingSpec};
+use crate::rules::{Rule, RuleSpec};
+use crate::store::{
+    conversation_from_row, conversation_turn_from_row, cron_run_from_row, meeting_from_row,
+    rule_from_row, webhook_dead_letter_from_row, webhook_delivery_from_row, webhook_from_row,
+    CaptureStore, ConversationRow, ConversationStore, ConversationTurnRow, CronRunRow,
+    EmbeddingStore, MediaChunkStore, MeetingRow, MeetingStore, PipeCronStore, RuleRow, RuleStore,
+    SearchStore, SpeakerStore, Store, TagStore, TimelineStore, WebhookDeadLetterRow,
+    WebhookDeliveryRow, WebhookRow, WebhookStore,
+};
 use crate::video_utils::VideoMetadata;
+use crate::webhooks::{Webhook, WebhookDeadLetter, WebhookDelivery, WebhookSpec};
 
 use futures::future::try_join_all;
 
+const MEETING_COLUMNS: &str = "id, start_time, end_time, app, title, participants, signals, first_frame_id, last_frame_id, first_transcription_id, last_transcription_id, created_at, updated_at";
+
+/// Points meeting `?1` at the frames and transcriptions captured while it went on.
+const LINK_MEETING_CONTENT: &str = "UPDATE meetings SET
+    first_frame_id = (SELECT MIN(f.id) FROM frames f WHERE f.timestamp >= meetings.start_time AND (meetings.end_time IS NULL OR f.timestamp <= meetings.end_time)),
+    last_frame_id = (SELECT MAX(f.id) FROM frames f WHERE f.timestamp >= meetings.start_time AND (meetings.end_time IS NULL OR f.timestamp <= meetings.end_time)),
+    first_transcription_id = (SELECT MIN(t.id) FROM audio_transcriptions t WHERE t.timestamp >= meetings.start_time AND (meetings.end_time IS NULL OR t.timestamp <= meetings.end_time)),
+    last_transcription_id = (SELECT MAX(t.id) FROM audio_transcriptions t WHERE t.timestamp >= meetings.start_time AND (meetings.end_time IS NULL OR t.timestamp <= meetings.end_time))
+    WHERE id = ?1";
+
+const CONVERSATION_COLUMNS: &str = "conversations.id, conversations.start_time, conversations.end_time, conversations.devices, conversations.speakers, conversations.transcript, conversations.turn_count, conversations.first_transcription_id, conversations.last_transcription_id, conversations.created_at";
+
 pub struct DatabaseManager {
     pub pool: SqlitePool,
+    /// Read-only connections used by search and the other API reads, so they
+    /// never queue behind capture writes.
+    pub(crate) read_pool: SqlitePool,
+    writer: DbWriter,
 }
 
 impl DatabaseManager {
@@ -55,11 +89,18 @@ impl DatabaseManager {
             sqlx::Sqlite::create_database(&connection_string).await?;
         }
 
+        let connect_options = SqliteConnectOptions::from_str(&connection_string)?;
+        // only takes effect on new databases, lets maintenance reclaim free pages
+        let write_options = connect_options
+            .clone()
+            .auto_vacuum(SqliteAutoVacuum::Incremental);
+        // sqlite has a single writer anyway: the capture writer and every other write
+        // share one connection and queue for it instead of failing with SQLITE_BUSY
         let pool = SqlitePoolOptions::new()
-            .max_connections(50)
-            .min_connections(3) // Minimum number of idle connections
-            .acquire_timeout(Duration::from_secs(10))
-            .connect(&connection_string)
+            .max_connections(1)
+            .min_connections(1)
+            .acquire_timeout(Duration::from_secs(30))
+            .connect_with(write_options)
             .await?;
 
         // Enable WAL mode
@@ -77,14 +118,43 @@ impl DatabaseManager {
             .execute(&pool)
             .await?;
 
-        let db_manager = DatabaseManager { pool };
-
         // Run migrations after establishing the connection
-        Self::run_migrations(&db_manager.pool).await?;
+        Self::run_migrations(&pool).await?;
+
+        // in-memory databases only exist on the connections that opened them
+        let in_memory = database_path.contains(":memory:");
+
+        let read_pool = if in_memory {
+            pool.clone()
+        } else {
+            SqlitePoolOptions::new()
+                .max_connections(40)
+                .min_connections(2)
+                .acquire_timeout(Duration::from_secs(10))
+                .connect_with(connect_options.read_only(true))
+                .await?
+        };
+
+        let writer = DbWriter::spawn(pool.clone(), DbWriterConfig::default());
+        let db_manager = DatabaseManager {
+            pool,
+            read_pool,
+            writer,
+        };
 
         Ok(db_manager)
     }
 
+    /// Batch size and latency stats of the background writer.
+    pub fn writer_metrics(&self) -> DbWriterMetrics {
+        self.writer.metrics()
+    }
+
+    /// How long capture has not written anything to the db.
+    pub fn write_idle_for(&self) -> Duration {
+        self.writer.idle_for()
+    }
+
     async fn run_migrations(pool: &SqlitePool) -> Result<(), sqlx::Error> {
         let mut migrator = sqlx::migrate!("./src/migrations");
         migrator.set_ignore_missing(true);
@@ -95,21 +165,17 @@ impl DatabaseManager {
     }
 
     pub async fn insert_audio_chunk(&self, file_path: &str) -> Result<i64, sqlx::Error> {
-        let mut tx = self.pool.begin().await?;
-        let id = sqlx::query("INSERT INTO audio_chunks (file_path, timestamp) VALUES (?1, ?2)")
-            .bind(file_path)
-            .bind(Utc::now())
-            .execute(&mut *tx)
-            .await?
-            .last_insert_rowid();
-        tx.commit().await?;
-        Ok(id)
+        self.writer
+            .write(WriteOp::InsertAudioChunk {
+                file_path: file_path.to_string(),
+            })
+            .await
     }
 
     async fn get_audio_chunk_id(&self, file_path: &str) -> Result<i64, sqlx::Error> {
         let id = sqlx::query_scalar::<_, i64>("SELECT id FROM audio_chunks WHERE file_path = ?1")
             .bind(file_path)
-            .fetch_optional(&self.pool)
+            .fetch_optional(&self.read_pool)
             .await?;
         Ok(id.unwrap_or(0))
     }
@@ -134,32 +200,19 @@ impl DatabaseManager {
         start_time: Option<f64>,
         end_time: Option<f64>,
     ) -> Result<i64, sqlx::Error> {
-        let text_length = transcription.len() as i64;
-        let mut tx = self.pool.begin().await?;
-
-        // Insert the full transcription
-        let id = sqlx::query(
-            "INSERT INTO audio_transcriptions (audio_chunk_id, transcription, offset_index, timestamp, transcription_engine, device, is_input_device, speaker_id, start_time, end_time, text_length) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
-        )
-        .bind(audio_chunk_id)
-        .bind(transcription)
-        .bind(offset_index)
-        .bind(Utc::now())
-        .bind(transcription_engine)
-        .bind(&device.name)
-        .bind(device.device_type == DeviceType::Input)
-        .bind(speaker_id)
-        .bind(start_time)
-        .bind(end_time)
-        .bind(text_length)
-        .execute(&mut *tx)
-        .await?
-        .last_insert_rowid();
-
-        // Commit the transaction for the full transcription
-        tx.commit().await?;
-
-        Ok(id)
+        self.writer
+            .write(WriteOp::InsertAudioTranscription {
+                audio_chunk_id,
+                transcription: transcription.to_string(),
+                offset_index,
+                transcription_engine: transcription_engine.to_string(),
+                device_name: device.name.clone(),
+                is_input_device: device.device_type == DeviceType::Input,
+                speaker_id,
+                start_time,
+                end_time,
+            })
+            .await
     }
 
     pub async fn update_audio_transcription(
@@ -186,6 +239,43 @@ impl DatabaseManager {
         Ok(affected as i64)
     }
 
+    pub async fn set_audio_chunk_timestamp(
+        &self,
+        audio_chunk_id: i64,
+        timestamp: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("UPDATE audio_chunks SET timestamp = ?1 WHERE id = ?2")
+            .bind(timestamp)
+            .bind(audio_chunk_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("UPDATE audio_transcriptions SET timestamp = ?1 WHERE audio_chunk_id = ?2")
+            .bind(timestamp)
+            .bind(audio_chunk_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn insert_ui_monitoring(
+        &self,
+        text_output: &str,
+        app: &str,
+        window: &str,
+        timestamp: DateTime<Utc>,
+    ) -> Result<i64, sqlx::Error> {
+        self.writer
+            .write(WriteOp::InsertUiMonitoring {
+                text_output: text_output.to_string(),
+                app: app.to_string(),
+                window: window.to_string(),
+                timestamp,
+            })
+            .await
+    }
+
     pub async fn insert_speaker(&self, embedding: &[f32]) -> Result<Speaker, SqlxError> {
         let mut tx = self.pool.begin().await?;
 
@@ -229,7 +319,7 @@ impl DatabaseManager {
     pub async fn get_speaker_by_id(&self, speaker_id: i64) -> Result<Speaker, SqlxError> {
         let speaker = sqlx::query_as("SELECT id, name, metadata FROM speakers WHERE id = ?1")
             .bind(speaker_id)
-            .fetch_one(&self.pool)
+            .fetch_one(&self.read_pool)
             .await?;
         Ok(speaker)
     }
@@ -255,7 +345,7 @@ impl DatabaseManager {
         )
         .bind(bytes)
         .bind(speaker_threshold)
-        .fetch_optional(&self.pool)
+        .fetch_optional(&self.read_pool)
         .await?;
 
         Ok(speaker)
@@ -277,142 +367,31 @@ impl DatabaseManager {
         file_path: &str,
         device_name: &str,
     ) -> Result<i64, sqlx::Error> {
-        let mut tx = self.pool.begin().await?;
-        let id = sqlx::query("INSERT INTO video_chunks (file_path, device_name) VALUES (?1, ?2)")
-            .bind(file_path)
-            .bind(device_name)
-            .execute(&mut *tx)
-            .await?
-            .last_insert_rowid();
-        tx.commit().await?;
-        Ok(id)
+        self.writer
+            .write(WriteOp::InsertVideoChunk {
+                file_path: file_path.to_string(),
+                device_name: device_name.to_string(),
+            })
+            .await
     }
 
+    /// Inserts a frame into the latest video chunk of `device_name`, returns 0 if the
+    /// device has no chunk yet.
     pub async fn insert_frame(
         &self,
         device_name: &str,
         timestamp: Option<DateTime<Utc>>,
     ) -> Result<i64, sqlx::Error> {
-        let mut tx = self.pool.begin().await?;
-        debug!("insert_frame Transaction started");
-
-        // Get the most recent video_chunk_id and file_path
-        let video_chunk: Option<(i64, String)> = sqlx::query_as(
-            "SELECT id, file_path FROM video_chunks WHERE device_name = ?1 ORDER BY id DESC LIMIT 1",
-        )
-        .bind(device_name)
-        .fetch_optional(&mut *tx)
-        .await?;
-        debug!("Fetched most recent video_chunk: {:?}", video_chunk);
-
-        // If no video chunk is found, return 0
-        let (video_chunk_id, file_path) = match video_chunk {
-            Some((id, path)) => (id, path),
-            None => {
-                debug!("No video chunk found, rolling back transaction");
-                tx.rollback().await?;
-                return Ok(0);
-            }
-        };
-
-        // Calculate the offset_index
-        let offset_index: i64 = sqlx::query_scalar(
-            "SELECT COALESCE(MAX(offset_index), -1) + 1 FROM frames WHERE video_chunk_id = ?1",
-        )
-        .bind(video_chunk_id)
-        .fetch_one(&mut *tx)
-        .await?;
-        debug!("insert_frame Calculated offset_index: {}", offset_index);
-
-        let timestamp = timestamp.unwrap_or_else(Utc::now);
-
-        // Insert the new frame with file_path as name
-        let id = sqlx::query(
-            "INSERT INTO frames (video_chunk_id, offset_index, timestamp, name) VALUES (?1, ?2, ?3, ?4)",
-        )
-        .bind(video_chunk_id)
-        .bind(offset_index)
-        .bind(timestamp)
-        .bind(file_path)
-        .execute(&mut *tx)
-        .await?
-        .last_insert_rowid();
-        debug!("insert_frame Inserted new frame with id: {}", id);
-
-        // Commit the transaction
-        tx.commit().await?;
-
-        Ok(id)
-    }
-
-    #[allow(clippy::too_many_arguments)]
-    pub async fn insert_ocr_text(
-        &self,
-        frame_id: i64,
-        text: &str,
-        text_json: &str,
-        app_name: &str,
-        window_name: &str,
-        ocr_engine: Arc<OcrEngine>,
-        focused: bool,
-    ) -> Result<(), sqlx::Error> {
-        const MAX_RETRIES: u32 = 3;
-        const TIMEOUT_DURATION: TokioDuration = TokioDuration::from_secs(10);
-
-        for attempt in 1..=MAX_RETRIES {
-            match timeout(
-                TIMEOUT_DURATION,
-                self.insert_ocr_text_old(
-                    frame_id,
-                    text,
-                    text_json,
-                    app_name,
-                    window_name,
-                    Arc::clone(&ocr_engine),
-                    focused,
-                ),
-            )
+        self.writer
+            .write(WriteOp::InsertFrame {
+                device_name: device_name.to_string(),
+                timestamp,
+            })
             .await
-            {
-                Ok(Ok(())) => {
-                    return Ok(());
-                }
-                Ok(Err(e)) => {
-                    error!("Failed to insert OCR text on attempt {}: {}", attempt, e);
-                }
-                Err(_) => {
-                    warn!(
-                        "Timeout occurred on attempt {} while inserting OCR text for frame_id: {}",
-                        attempt, frame_id
-                    );
-                }
-            }
-
-            if attempt < MAX_RETRIES {
-                warn!(
-                    "Retrying to insert OCR text for frame_id: {} (attempt {}/{})",
-                    frame_id,
-                    attempt + 1,
-                    MAX_RETRIES
-                );
-            } else {
-                error!(
-                    "Failed to insert OCR text for frame_id: {} after {} attempts",
-                    frame_id, MAX_RETRIES
-                );
-                return Err(sqlx::Error::PoolTimedOut); // Return error after max retries
-            }
-        }
-
-        error!(
-            "Exiting insert_ocr_text for frame_id: {} with PoolTimedOut error",
-            frame_id
-        );
-        Err(sqlx::Error::PoolTimedOut)
     }
 
     #[allow(clippy::too_many_arguments)]
-    async fn insert_ocr_text_old(
+    pub async fn insert_ocr_text(
         &self,
         frame_id: i64,
         text: &str,
@@ -422,7 +401,6 @@ impl DatabaseManager {
         ocr_engine: Arc<OcrEngine>,
         focused: bool,
     ) -> Result<(), sqlx::Error> {
-        let text_length = text.len() as i64;
         let display_window_name = if window_name.chars().count() > 20 {
             format!("{}...", window_name.chars().take(20).collect::<String>())
         } else {
@@ -439,20 +417,18 @@ impl DatabaseManager {
             if text.len() > 60 { "..." } else { "" },
         );
 
-        let mut tx = self.pool.begin().await?;
-        sqlx::query("INSERT INTO ocr_text (frame_id, text, text_json, app_name, ocr_engine, window_name, focused, text_length) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)")
-            .bind(frame_id)
-            .bind(text)
-            .bind(text_json)
-            .bind(app_name)
-            .bind(format!("{:?}", *ocr_engine))
-            .bind(window_name)
-            .bind(focused)
-            .bind(text_length)
-            .execute(&mut *tx)
+        self.writer
+            .write(WriteOp::InsertOcrText {
+                frame_id,
+                text: text.to_string(),
+                text_json: text_json.to_string(),
+                app_name: app_name.to_string(),
+                window_name: window_name.to_string(),
+                ocr_engine: format!("{:?}", *ocr_engine),
+                focused,
+            })
             .await?;
 
-        tx.commit().await?;
         debug!("OCR text inserted into db successfully");
         Ok(())
     }
@@ -473,243 +449,22 @@ impl DatabaseManager {
         speaker_ids: Option<Vec<i64>>,
         frame_name: Option<&str>,
     ) -> Result<Vec<SearchResult>, sqlx::Error> {
-        let mut results = Vec::new();
-
-        match content_type {
-            ContentType::All => {
-                let (ocr_results, audio_results, ui_results) =
-                    if app_name.is_none() && window_name.is_none() && frame_name.is_none() {
-                        // Run all three queries in parallel
-                        let (ocr, audio, ui) = tokio::try_join!(
-                            self.search_ocr(
-                                query,
-                                limit,
-                                offset,
-                                start_time,
-                                end_time,
-                                app_name,
-                                window_name,
-                                min_length,
-                                max_length,
-                                frame_name,
-                            ),
-                            self.search_audio(
-                                query,
-                                limit,
-                                offset,
-                                start_time,
-                                end_time,
-                                min_length,
-                                max_length,
-                                speaker_ids
-                            ),
-                            self.search_ui_monitoring(
-                                query,
-                                app_name,
-                                window_name,
-                                start_time,
-                                end_time,
-                                limit,
-                                offset,
-                            )
-                        )?;
-                        (ocr, Some(audio), ui)
-                    } else {
-                        // Run only OCR and UI queries in parallel when app/window filters are present
-                        let (ocr, ui) = tokio::try_join!(
-                            self.search_ocr(
-                                query,
-                                limit,
-                                offset,
-                                start_time,
-                                end_time,
-                                app_name,
-                                window_name,
-                                min_length,
-                                max_length,
-                                frame_name,
-                            ),
-                            self.search_ui_monitoring(
-                                query,
-                                app_name,
-                                window_name,
-                                start_time,
-                                end_time,
-                                limit,
-                                offset,
-                            )
-                        )?;
-                        (ocr, None, ui)
-                    };
-
-                results.extend(ocr_results.into_iter().map(SearchResult::OCR));
-                if let Some(audio) = audio_results {
-                    results.extend(audio.into_iter().map(SearchResult::Audio));
-                }
-                results.extend(ui_results.into_iter().map(SearchResult::UI));
-            }
-            ContentType::OCR => {
-                let ocr_results = self
-                    .search_ocr(
-                        query,
-                        limit,
-                        offset,
-                        start_time,
-                        end_time,
-                        app_name,
-                        window_name,
-                        min_length,
-                        max_length,
-                        frame_name,
-                    )
-                    .await?;
-                results.extend(ocr_results.into_iter().map(SearchResult::OCR));
-            }
-            ContentType::Audio => {
-                if app_name.is_none() && window_name.is_none() {
-                    let audio_results = self
-                        .search_audio(
-                            query,
-                            limit,
-                            offset,
-                            start_time,
-                            end_time,
-                            min_length,
-                            max_length,
-                            speaker_ids,
-                        )
-                        .await?;
-                    results.extend(audio_results.into_iter().map(SearchResult::Audio));
-                }
-            }
-            ContentType::UI => {
-                let ui_results = self
-                    .search_ui_monitoring(
-                        query,
-                        app_name,
-                        window_name,
-                        start_time,
-                        end_time,
-                        limit,
-                        offset,
-                    )
-                    .await?;
-                results.extend(ui_results.into_iter().map(SearchResult::UI));
-            }
-            ContentType::AudioAndUi => {
-                let audio_results = self
-                    .search_audio(
-                        query,
-                        limit / 2,
-                        offset,
-                        start_time,
-                        end_time,
-                        min_length,
-                        max_length,
-                        speaker_ids,
-                    )
-                    .await?;
-                let ui_results = self
-                    .search_ui_monitoring(
-                        query,
-                        app_name,
-                        window_name,
-                        start_time,
-                        end_time,
-                        limit / 2,
-                        offset,
-                    )
-                    .await?;
-
-                results.extend(audio_results.into_iter().map(SearchResult::Audio));
-                results.extend(ui_results.into_iter().map(SearchResult::UI));
-            }
-            ContentType::OcrAndUi => {
-                let ocr_results = self
-                    .search_ocr(
-                        query,
-                        limit / 2,
-                        offset,
-                        start_time,
-                        end_time,
-                        app_name,
-                        window_name,
-                        min_length,
-                        max_length,
-                        frame_name,
-                    )
-                    .await?;
-                let ui_results = self
-                    .search_ui_monitoring(
-                        query,
-                        app_name,
-                        window_name,
-                        start_time,
-                        end_time,
-                        limit / 2,
-                        offset,
-                    )
-                    .await?;
-
-                results.extend(ocr_results.into_iter().map(SearchResult::OCR));
-                results.extend(ui_results.into_iter().map(SearchResult::UI));
-            }
-            ContentType::AudioAndOcr => {
-                let audio_results = self
-                    .search_audio(
-                        query,
-                        limit / 2,
-                        offset,
-                        start_time,
-                        end_time,
-                        min_length,
-                        max_length,
-                        speaker_ids,
-                    )
-                    .await?;
-                let ocr_results = self
-                    .search_ocr(
-                        query,
-                        limit / 2,
-                        offset,
-                        start_time,
-                        end_time,
-                        app_name,
-                        window_name,
-                        min_length,
-                        max_length,
-                        frame_name,
-                    )
-                    .await?;
-
-                results.extend(audio_results.into_iter().map(SearchResult::Audio));
-                results.extend(ocr_results.into_iter().map(SearchResult::OCR));
-            }
-        }
-
-        // Sort results by timestamp in descending order
-        results.sort_by(|a, b| {
-            let timestamp_a = match a {
-                SearchResult::OCR(ocr) => ocr.timestamp,
-                SearchResult::Audio(audio) => audio.timestamp,
-                SearchResult::UI(ui) => ui.timestamp,
-            };
-            let timestamp_b = match b {
-                SearchResult::OCR(ocr) => ocr.timestamp,
-                SearchResult::Audio(audio) => audio.timestamp,
-                SearchResult::UI(ui) => ui.timestamp,
-            };
-            timestamp_b.cmp(&timestamp_a)
-        });
-
-        // Apply offset and limit after sorting
-        results = results
-            .into_iter()
-            .skip(offset as usize)
-            .take(limit as usize)
-            .collect();
-
-        Ok(results)
+        SearchStore::search(
+            self,
+            query,
+            content_type,
+            limit,
+            offset,
+            start_time,
+            end_time,
+            app_name,
+            window_name,
+            min_length,
+            max_length,
+            speaker_ids,
+            frame_name,
+        )
+        .await
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -718,6 +473,7 @@ impl DatabaseManager {
         query: &str,
         limit: u32,
         offset: u32,
+        cursor: Option<&SearchCursor>,
         start_time: Option<DateTime<Utc>>,
         end_time: Option<DateTime<Utc>>,
         app_name: Option<&str>,
@@ -726,6 +482,8 @@ impl DatabaseManager {
         max_length: Option<usize>,
         frame_name: Option<&str>,
     ) -> Result<Vec<OCRResult>, sqlx::Error> {
+        let bound = cursor.map(|c| c.bound_for(SearchKind::Ocr));
+
         let base_sql = if query.is_empty() {
             "ocr_text"
         } else {
@@ -765,8 +523,9 @@ impl DatabaseManager {
                 AND (?6 IS NULL OR COALESCE(ocr_text.text_length,LENGTH(ocr_text.text)) >= ?6)
                 AND (?7 IS NULL OR COALESCE(ocr_text.text_length,LENGTH(ocr_text.text)) <= ?7)
                 AND (?8 IS NULL OR frames.name LIKE '%' || ?8 || '%' COLLATE NOCASE)
+                AND (?11 IS NULL OR frames.timestamp < ?11 OR (frames.timestamp = ?11 AND frames.id < ?12))
             GROUP BY ocr_text.frame_id
-            ORDER BY frames.timestamp DESC
+            ORDER BY frames.timestamp DESC, frames.id DESC
             LIMIT ?9 OFFSET ?10
             "#,
             base_sql, where_clause
@@ -783,7 +542,9 @@ impl DatabaseManager {
             .bind(frame_name)
             .bind(limit)
             .bind(offset)
-            .fetch_all(&self.pool)
+            .bind(bound.map(|(timestamp, _)| timestamp))
+            .bind(bound.map(|(_, id)| id))
+            .fetch_all(&self.read_pool)
             .await?;
 
         Ok(raw_results
@@ -819,6 +580,35 @@ impl DatabaseManager {
         max_length: Option<usize>,
         speaker_ids: Option<Vec<i64>>,
     ) -> Result<Vec<AudioResult>, sqlx::Error> {
+        self.search_audio_after(
+            query,
+            limit,
+            offset,
+            None,
+            start_time,
+            end_time,
+            min_length,
+            max_length,
+            speaker_ids,
+        )
+        .await
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn search_audio_after(
+        &self,
+        query: &str,
+        limit: u32,
+        offset: u32,
+        cursor: Option<&SearchCursor>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        min_length: Option<usize>,
+        max_length: Option<usize>,
+        speaker_ids: Option<Vec<i64>>,
+    ) -> Result<Vec<AudioResult>, sqlx::Error> {
+        let bound = cursor.map(|c| c.bound_for(SearchKind::Audio));
+
         let mut json_array: String = "[]".to_string();
         if let Some(ids) = speaker_ids {
             if !ids.is_empty() {
@@ -841,6 +631,7 @@ impl DatabaseManager {
         let sql = format!(
             r#"
             SELECT
+                MAX(audio_transcriptions.id) as transcription_id,
                 audio_transcriptions.audio_chunk_id,
                 audio_transcriptions.transcription,
                 audio_transcriptions.timestamp,
@@ -865,8 +656,9 @@ impl DatabaseManager {
                 AND (?5 IS NULL OR COALESCE(audio_transcriptions.text_length, LENGTH(audio_transcriptions.transcription)) <= ?5)
                 AND (speakers.id IS NULL OR speakers.hallucination = 0)
                 AND (json_array_length(?6) = 0 OR audio_transcriptions.speaker_id IN (SELECT value FROM json_each(?6)))
+                AND (?9 IS NULL OR audio_transcriptions.timestamp < ?9 OR (audio_transcriptions.timestamp = ?9 AND audio_transcriptions.id < ?10))
             GROUP BY audio_transcriptions.audio_chunk_id, audio_transcriptions.offset_index
-            ORDER BY audio_transcriptions.timestamp DESC
+            ORDER BY audio_transcriptions.timestamp DESC, transcription_id DESC
             LIMIT ?7 OFFSET ?8
             "#,
             base_sql, where_clause
@@ -881,7 +673,9 @@ impl DatabaseManager {
             .bind(json_array)
             .bind(limit)
             .bind(offset)
-            .fetch_all(&self.pool)
+            .bind(bound.map(|(timestamp, _)| timestamp))
+            .bind(bound.map(|(_, id)| id))
+            .fetch_all(&self.read_pool)
             .await?;
 
         let futures = raw_results.into_iter().map(|raw| async move {
@@ -894,6 +688,7 @@ impl DatabaseManager {
             };
 
             Ok::<AudioResult, sqlx::Error>(AudioResult {
+                transcription_id: raw.transcription_id,
                 audio_chunk_id: raw.audio_chunk_id,
                 transcription: raw.transcription,
                 timestamp: raw.timestamp,
@@ -934,7 +729,7 @@ impl DatabaseManager {
             "#,
         )
         .bind(frame_id)
-        .fetch_optional(&self.pool)
+        .fetch_optional(&self.read_pool)
         .await
     }
 
@@ -951,6 +746,38 @@ impl DatabaseManager {
         max_length: Option<usize>,
         speaker_ids: Option<Vec<i64>>,
         frame_name: Option<&str>,
+    ) -> Result<usize, sqlx::Error> {
+        self.count_search_results_capped(
+            query,
+            content_type,
+            start_time,
+            end_time,
+            app_name,
+            window_name,
+            min_length,
+            max_length,
+            speaker_ids,
+            frame_name,
+            None,
+        )
+        .await
+    }
+
+    /// Counts matching rows, stopping at `cap` so counts over huge result sets stay cheap.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn count_search_results_capped(
+        &self,
+        query: &str,
+        content_type: ContentType,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>,
+        window_name: Option<&str>,
+        min_length: Option<usize>,
+        max_length: Option<usize>,
+        speaker_ids: Option<Vec<i64>>,
+        frame_name: Option<&str>,
+        cap: Option<usize>,
     ) -> Result<usize, sqlx::Error> {
         let json_array = if let Some(ids) = speaker_ids {
             if !ids.is_empty() {
@@ -962,11 +789,12 @@ impl DatabaseManager {
             "[]".to_string()
         };
 
-        let sql = match content_type {
+        // the inner query lists the distinct matches, the outer one counts up to the cap
+        let matches = match content_type {
             ContentType::OCR => {
                 format!(
                     r#"
-                    SELECT COUNT(DISTINCT frames.id)
+                    SELECT DISTINCT frames.id
                     FROM {table}
                     JOIN frames ON ocr_text.frame_id = frames.id
                     WHERE {match_condition}
@@ -993,7 +821,7 @@ impl DatabaseManager {
             ContentType::Audio => {
                 format!(
                     r#"
-                    SELECT COUNT(DISTINCT audio_transcriptions.audio_chunk_id || '_' || COALESCE(audio_transcriptions.start_time, '') || '_' || COALESCE(audio_transcriptions.end_time, ''))
+                    SELECT DISTINCT audio_transcriptions.audio_chunk_id || '_' || COALESCE(audio_transcriptions.start_time, '') || '_' || COALESCE(audio_transcriptions.end_time, '')
                     FROM {table}
                     WHERE {match_condition}
                         AND (?2 IS NULL OR audio_transcriptions.timestamp >= ?2)
@@ -1017,7 +845,7 @@ impl DatabaseManager {
             ContentType::UI => {
                 format!(
                     r#"
-                    SELECT COUNT(DISTINCT ui_monitoring.id)
+                    SELECT DISTINCT ui_monitoring.id
                     FROM {table}
                     WHERE {match_condition}
                         AND (?2 IS NULL OR ui_monitoring.timestamp >= ?2)
@@ -1042,7 +870,6 @@ impl DatabaseManager {
             ContentType::All => {
                 format!(
                     r#"
-                    SELECT COUNT(*) FROM (
                         -- OCR part
                         SELECT DISTINCT frames.id
                         FROM {ocr_table}
@@ -1078,7 +905,7 @@ impl DatabaseManager {
                             AND (?6 IS NULL OR COALESCE(ui_monitoring.text_length, LENGTH(ui_monitoring.text_output)) >= ?6)
                             AND (?7 IS NULL OR COALESCE(ui_monitoring.text_length, LENGTH(ui_monitoring.text_output)) <= ?7)
                             AND ui_monitoring.text_output != ''
-                    )"#,
+                    "#,
                     ocr_table = if query.is_empty() {
                         "ocr_text"
                     } else {
@@ -1114,8 +941,12 @@ impl DatabaseManager {
             _ => return Ok(0),
         };
 
+        // a negative LIMIT means no limit in sqlite
+        let cap = cap.map(|c| c as i64).unwrap_or(-1);
+
         let count: i64 = match content_type {
             ContentType::Audio => {
+                let sql = format!("SELECT COUNT(*) FROM ({} LIMIT ?7)", matches);
                 sqlx::query_scalar(&sql)
                     .bind(query)
                     .bind(start_time)
@@ -1123,10 +954,12 @@ impl DatabaseManager {
                     .bind(min_length.map(|l| l as i64))
                     .bind(max_length.map(|l| l as i64))
                     .bind(json_array)
-                    .fetch_one(&self.pool)
+                    .bind(cap)
+                    .fetch_one(&self.read_pool)
                     .await?
             }
             _ => {
+                let sql = format!("SELECT COUNT(*) FROM ({} LIMIT ?10)", matches);
                 sqlx::query_scalar(&sql)
                     .bind(query)
                     .bind(start_time)
@@ -1137,7 +970,8 @@ impl DatabaseManager {
                     .bind(min_length.map(|l| l as i64))
                     .bind(max_length.map(|l| l as i64))
                     .bind(json_array)
-                    .fetch_one(&self.pool)
+                    .bind(cap)
+                    .fetch_one(&self.read_pool)
                     .await?
             }
         };
@@ -1157,26 +991,26 @@ impl DatabaseManager {
     > {
         let latest_frame: Option<(DateTime<Utc>,)> =
             sqlx::query_as("SELECT timestamp FROM frames ORDER BY timestamp DESC LIMIT 1")
-                .fetch_optional(&self.pool)
+                .fetch_optional(&self.read_pool)
                 .await?;
 
         let latest_audio: Option<(DateTime<Utc>,)> =
             sqlx::query_as("SELECT timestamp FROM audio_chunks ORDER BY timestamp DESC LIMIT 1")
-                .fetch_optional(&self.pool)
+                .fetch_optional(&self.read_pool)
                 .await?;
 
         // Check if ui_monitoring table exists first
         let latest_ui: Option<(DateTime<Utc>,)> = match sqlx::query_scalar::<_, i32>(
             "SELECT 1 FROM sqlite_master WHERE type='table' AND name='ui_monitoring'",
         )
-        .fetch_optional(&self.pool)
+        .fetch_optional(&self.read_pool)
         .await?
         {
             Some(_) => {
                 sqlx::query_as(
                     "SELECT timestamp FROM ui_monitoring ORDER BY timestamp DESC LIMIT 1",
                 )
-                .fetch_optional(&self.pool)
+                .fetch_optional(&self.read_pool)
                 .await?
             }
             None => {
@@ -1282,7 +1116,7 @@ impl DatabaseManager {
             "#,
         )
         .bind(vision_id)
-        .fetch_all(&self.pool)
+        .fetch_all(&self.read_pool)
         .await
     }
 
@@ -1297,7 +1131,7 @@ impl DatabaseManager {
             "#,
         )
         .bind(audio_chunk_id)
-        .fetch_all(&self.pool)
+        .fetch_all(&self.read_pool)
         .await
     }
 
@@ -1357,7 +1191,7 @@ impl DatabaseManager {
         Ok(())
     }
     pub async fn execute_raw_sql(&self, query: &str) -> Result<serde_json::Value, sqlx::Error> {
-        let rows = sqlx::query(query).fetch_all(&self.pool).await?;
+        let rows = sqlx::query(query).fetch_all(&self.read_pool).await?;
 
         let result: Vec<serde_json::Map<String, serde_json::Value>> = rows
             .iter()
@@ -1451,11 +1285,11 @@ impl DatabaseManager {
             sqlx::query(frames_query)
                 .bind(start)
                 .bind(end)
-                .fetch_all(&self.pool),
+                .fetch_all(&self.read_pool),
             sqlx::query(audio_query)
                 .bind(start)
                 .bind(end)
-                .fetch_all(&self.pool)
+                .fetch_all(&self.read_pool)
         )?;
 
         // Process into structured data with device-aware grouping
@@ -1526,6 +1360,33 @@ impl DatabaseManager {
         limit: u32,
         offset: u32,
     ) -> Result<Vec<UiContent>, sqlx::Error> {
+        self.search_ui_monitoring_after(
+            query,
+            app_name,
+            window_name,
+            start_time,
+            end_time,
+            limit,
+            offset,
+            None,
+        )
+        .await
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn search_ui_monitoring_after(
+        &self,
+        query: &str,
+        app_name: Option<&str>,
+        window_name: Option<&str>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        limit: u32,
+        offset: u32,
+        cursor: Option<&SearchCursor>,
+    ) -> Result<Vec<UiContent>, sqlx::Error> {
+        let bound = cursor.map(|c| c.bound_for(SearchKind::Ui));
+
         let base_sql = if query.is_empty() {
             "ui_monitoring"
         } else {
@@ -1548,7 +1409,8 @@ impl DatabaseManager {
                 ui_monitoring.window,
                 ui_monitoring.initial_traversal_at,
                 video_chunks.file_path,
-                frames.offset_index
+                frames.offset_index,
+                frames.name as frame_name
             FROM {}
             LEFT JOIN frames ON
                 frames.timestamp BETWEEN
@@ -1560,7 +1422,9 @@ impl DatabaseManager {
                 AND (?3 IS NULL OR ui_monitoring.timestamp <= ?3)
                 AND (?4 IS NULL OR ui_monitoring.app LIKE '%' || ?4 || '%')
                 AND (?5 IS NULL OR ui_monitoring.window LIKE '%' || ?5 || '%')
-            ORDER BY ui_monitoring.timestamp DESC
+                AND (?8 IS NULL OR ui_monitoring.timestamp < datetime(?8) OR (ui_monitoring.timestamp = datetime(?8) AND ui_monitoring.id < ?9))
+            GROUP BY ui_monitoring.id
+            ORDER BY ui_monitoring.timestamp DESC, ui_monitoring.id DESC
             LIMIT ?6 OFFSET ?7
             "#,
             base_sql, where_clause
@@ -1574,7 +1438,9 @@ impl DatabaseManager {
             .bind(window_name)
             .bind(limit)
             .bind(offset)
-            .fetch_all(&self.pool)
+            .bind(bound.map(|(timestamp, _)| timestamp))
+            .bind(bound.map(|(_, id)| id))
+            .fetch_all(&self.read_pool)
             .await
     }
 
@@ -1607,7 +1473,7 @@ impl DatabaseManager {
              WHERE ut.ui_monitoring_id = ?",
         )
         .bind(ui_monitoring_id)
-        .fetch_all(&self.pool)
+        .fetch_all(&self.read_pool)
         .await?;
 
         Ok(tags.into_iter().map(|t| t.0).collect())
@@ -1620,10 +1486,11 @@ impl DatabaseManager {
         sqlx::query_as::<_, AudioChunksResponse>(
             r#"
             SELECT
-                ac.*,
+                ac.id as audio_chunk_id,
                 at.start_time,
                 at.end_time,
-                ac.file_path
+                ac.file_path,
+                ac.timestamp
             FROM audio_chunks ac
             JOIN audio_transcriptions at ON ac.id = at.audio_chunk_id
             WHERE at.speaker_id = ?
@@ -1631,7 +1498,7 @@ impl DatabaseManager {
             "#,
         )
         .bind(speaker_id)
-        .fetch_all(&self.pool)
+        .fetch_all(&self.read_pool)
         .await
     }
 
@@ -1642,6 +1509,21 @@ impl DatabaseManager {
         offset: u32,
         speaker_ids: Option<Vec<i64>>,
     ) -> Result<Vec<Speaker>, sqlx::Error> {
+        Ok(self
+            .get_unnamed_speakers_page(limit, offset, None, speaker_ids)
+            .await?
+            .into_iter()
+            .map(|unnamed| unnamed.speaker)
+            .collect())
+    }
+
+    pub async fn get_unnamed_speakers_page(
+        &self,
+        limit: u32,
+        offset: u32,
+        cursor: Option<&SpeakerCursor>,
+        speaker_ids: Option<Vec<i64>>,
+    ) -> Result<Vec<UnnamedSpeaker>, sqlx::Error> {
         let base_query = r#"
             WITH RecentAudioPaths AS (
                 SELECT DISTINCT
@@ -1706,13 +1588,14 @@ impl DatabaseManager {
             JOIN RecentAudioPaths rap ON s.id = rap.speaker_id
             JOIN audio_transcriptions at ON s.id = at.speaker_id
             GROUP BY s.id
-            ORDER BY transcription_count DESC
+            HAVING transcription_count < ? OR (transcription_count = ? AND s.id < ?)
+            ORDER BY transcription_count DESC, s.id DESC
             LIMIT ? OFFSET ?
             "#,
             base_query, speaker_filter
         );
 
-        let mut db_query = sqlx::query_as::<sqlx::Sqlite, Speaker>(&query);
+        let mut db_query = sqlx::query_as::<sqlx::Sqlite, UnnamedSpeaker>(&query);
 
         // Add speaker_id bindings if present
         if let Some(ids) = speaker_ids {
@@ -1721,10 +1604,16 @@ impl DatabaseManager {
             }
         }
 
+        // then the cursor, no cursor starts above every speaker
+        let (after_count, after_id) = cursor
+            .map(|c| (c.transcription_count, c.id))
+            .unwrap_or((i64::MAX, i64::MAX));
+        db_query = db_query.bind(after_count).bind(after_count).bind(after_id);
+
         // Add limit and offset last
         db_query = db_query.bind(limit).bind(offset);
 
-        let res = db_query.fetch_all(&self.pool).await?;
+        let res = db_query.fetch_all(&self.read_pool).await?;
         Ok(res)
     }
 
@@ -1765,7 +1654,7 @@ impl DatabaseManager {
             "SELECT DISTINCT * FROM speakers WHERE name LIKE ? || '%' AND hallucination = 0",
         )
         .bind(name_prefix)
-        .fetch_all(&self.pool)
+        .fetch_all(&self.read_pool)
         .await
     }
 
@@ -1875,7 +1764,7 @@ impl DatabaseManager {
         .bind(speaker_id)
         .bind(threshold)
         .bind(limit)
-        .fetch_all(&self.pool)
+        .fetch_all(&self.read_pool)
         .await
     }
 
@@ -2004,7 +1893,7 @@ impl DatabaseManager {
             .bind(bytes)
             .bind(threshold)
             .bind(limit)
-            .fetch_all(&self.pool)
+            .fetch_all(&self.read_pool)
             .await?;
 
         Ok(raw_results
@@ -2052,90 +1941,1401 @@ impl DatabaseManager {
         Ok(())
     }
 
-    pub async fn repair_database(&self) -> Result<(), anyhow::Error> {
-        debug!("starting aggressive database repair process");
-
-        // Force close any pending transactions
+    /// Returns chunks that are still only on local disk and whose media is older than `cutoff`.
+    /// Results are ordered by id and start after `after_id` so callers can page through them.
+    pub async fn find_offload_candidates(
+        &self,
+        kind: ChunkKind,
+        cutoff: DateTime<Utc>,
+        after_id: i64,
+        limit: i64,
+    ) -> Result<Vec<OffloadCandidate>, sqlx::Error> {
+        let query = match kind {
+            ChunkKind::Video => {
+                r#"
+                SELECT vc.id, vc.file_path
+                FROM video_chunks vc
+                JOIN frames f ON f.video_chunk_id = vc.id
+                WHERE vc.remote_key IS NULL AND vc.id > ?2
+                GROUP BY vc.id
+                HAVING MAX(f.timestamp) < ?1
+                ORDER BY vc.id ASC
+                LIMIT ?3
+                "#
+            }
+            ChunkKind::Audio => {
+                r#"
+                SELECT id, file_path
+                FROM audio_chunks
+                WHERE remote_key IS NULL AND timestamp < ?1 AND id > ?2
+                ORDER BY id ASC
+                LIMIT ?3
+                "#
+            }
+        };
+
+        sqlx::query_as::<_, OffloadCandidate>(query)
+            .bind(cutoff)
+            .bind(after_id)
+            .bind(limit)
+            .fetch_all(&self.read_pool)
+            .await
+    }
+
+    pub async fn mark_chunk_offloaded(
+        &self,
+        kind: ChunkKind,
+        id: i64,
+        remote_key: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(&format!(
+            "UPDATE {} SET remote_key = ?1, offloaded_at = ?2 WHERE id = ?3",
+            kind.table()
+        ))
+        .bind(remote_key)
+        .bind(Utc::now())
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Looks up the remote object key of an offloaded chunk by its original local path.
+    pub async fn get_chunk_remote_key(
+        &self,
+        file_path: &str,
+    ) -> Result<Option<String>, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"
+            SELECT remote_key FROM video_chunks WHERE file_path = ?1 AND remote_key IS NOT NULL
+            UNION ALL
+            SELECT remote_key FROM audio_chunks WHERE file_path = ?1 AND remote_key IS NOT NULL
+            LIMIT 1
+            "#,
+        )
+        .bind(file_path)
+        .fetch_optional(&self.read_pool)
+        .await
+    }
+
+    pub async fn get_audio_chunk_file_path(&self, id: i64) -> Result<Option<String>, sqlx::Error> {
+        sqlx::query_scalar("SELECT file_path FROM audio_chunks WHERE id = ?1")
+            .bind(id)
+            .fetch_optional(&self.read_pool)
+            .await
+    }
+
+    pub async fn insert_pipe_cron_run(&self, run: &CronRun) -> Result<i64, sqlx::Error> {
+        let id = sqlx::query(
+            "INSERT INTO pipe_cron_runs (pipe_id, job, trigger, started_at, ended_at, status, output) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        )
+        .bind(&run.pipe_id)
+        .bind(&run.job)
+        .bind(run.trigger.as_str())
+        .bind(run.started_at)
+        .bind(run.ended_at)
+        .bind(run.status.as_str())
+        .bind(&run.output)
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+        Ok(id)
+    }
+
+    pub async fn get_pipe_cron_runs(
+        &self,
+        pipe_id: &str,
+        job: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<CronRun>, sqlx::Error> {
+        sqlx::query_as::<_, CronRunRow>(
+            r#"
+            SELECT id, pipe_id, job, trigger, started_at, ended_at, status, output
+            FROM pipe_cron_runs
+            WHERE pipe_id = ?1 AND (?2 IS NULL OR job = ?2)
+            ORDER BY started_at DESC, id DESC
+            LIMIT ?3
+            "#,
+        )
+        .bind(pipe_id)
+        .bind(job)
+        .bind(limit)
+        .fetch_all(&self.read_pool)
+        .await?
+        .into_iter()
+        .map(cron_run_from_row)
+        .collect()
+    }
+
+    pub async fn list_webhooks(&self) -> Result<Vec<Webhook>, sqlx::Error> {
+        sqlx::query_as::<_, WebhookRow>(
+            "SELECT id, url, events, filter, secret, headers, enabled, created_at, updated_at FROM webhooks ORDER BY id",
+        )
+        .fetch_all(&self.read_pool)
+        .await?
+        .into_iter()
+        .map(webhook_from_row)
+        .collect()
+    }
+
+    pub async fn get_webhook(&self, id: i64) -> Result<Option<Webhook>, sqlx::Error> {
+        sqlx::query_as::<_, WebhookRow>(
+            "SELECT id, url, events, filter, secret, headers, enabled, created_at, updated_at FROM webhooks WHERE id = ?1",
+        )
+        .bind(id)
+        .fetch_optional(&self.read_pool)
+        .await?
+        .map(webhook_from_row)
+        .transpose()
+    }
+
+    pub async fn insert_webhook(&self, spec: &WebhookSpec) -> Result<i64, sqlx::Error> {
+        let now = Utc::now();
+        let id = sqlx::query(
+            "INSERT INTO webhooks (url, events, filter, secret, headers, enabled, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7)",
+        )
+        .bind(&spec.url)
+        .bind(serde_json::to_string(&spec.events).unwrap_or_else(|_| "[]".to_string()))
+        .bind(&spec.filter)
+        .bind(&spec.secret)
+        .bind(serde_json::to_string(&spec.headers).unwrap_or_else(|_| "{}".to_string()))
+        .bind(spec.enabled)
+        .bind(now)
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+        Ok(id)
+    }
+
+    pub async fn update_webhook(&self, id: i64, spec: &WebhookSpec) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE webhooks
+            SET url = ?2, events = ?3, filter = ?4, secret = COALESCE(?5, secret), headers = ?6,
+                enabled = ?7, updated_at = ?8
+            WHERE id = ?1
+            "#,
+        )
+        .bind(id)
+        .bind(&spec.url)
+        .bind(serde_json::to_string(&spec.events).unwrap_or_else(|_| "[]".to_string()))
+        .bind(&spec.filter)
+        .bind(&spec.secret)
+        .bind(serde_json::to_string(&spec.headers).unwrap_or_else(|_| "{}".to_string()))
+        .bind(spec.enabled)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn delete_webhook(&self, id: i64) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM webhook_deliveries WHERE webhook_id = ?1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM webhook_dead_letters WHERE webhook_id = ?1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        let result = sqlx::query("DELETE FROM webhooks WHERE id = ?1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn insert_webhook_delivery(
+        &self,
+        delivery: &WebhookDelivery,
+    ) -> Result<i64, sqlx::Error> {
+        let id = sqlx::query(
+            "INSERT INTO webhook_deliveries (webhook_id, event, event_offset, status, attempts, response_status, error, duration_ms, delivered_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        )
+        .bind(delivery.webhook_id)
+        .bind(&delivery.event)
+        .bind(delivery.event_offset.map(|o| o as i64))
+        .bind(delivery.status.as_str())
+        .bind(delivery.attempts as i64)
+        .bind(delivery.response_status.map(|s| s as i64))
+        .bind(&delivery.error)
+        .bind(delivery.duration_ms)
+        .bind(delivery.delivered_at)
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+        Ok(id)
+    }
+
+    pub async fn get_webhook_deliveries(
+        &self,
+        webhook_id: i64,
+        limit: u32,
+    ) -> Result<Vec<WebhookDelivery>, sqlx::Error> {
+        sqlx::query_as::<_, WebhookDeliveryRow>(
+            r#"
+            SELECT id, webhook_id, event, event_offset, status, attempts, response_status, error, duration_ms, delivered_at
+            FROM webhook_deliveries
+            WHERE webhook_id = ?1
+            ORDER BY id DESC
+            LIMIT ?2
+            "#,
+        )
+        .bind(webhook_id)
+        .bind(limit)
+        .fetch_all(&self.read_pool)
+        .await?
+        .into_iter()
+        .map(webhook_delivery_from_row)
+        .collect()
+    }
+
+    pub async fn insert_webhook_dead_letter(
+        &self,
+        letter: &WebhookDeadLetter,
+    ) -> Result<i64, sqlx::Error> {
+        let id = sqlx::query(
+            "INSERT INTO webhook_dead_letters (webhook_id, event, event_offset, payload, attempts, error, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        )
+        .bind(letter.webhook_id)
+        .bind(&letter.event)
+        .bind(letter.event_offset.map(|o| o as i64))
+        .bind(letter.payload.to_string())
+        .bind(letter.attempts as i64)
+        .bind(&letter.error)
+        .bind(letter.created_at)
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+        Ok(id)
+    }
+
+    pub async fn get_webhook_dead_letters(
+        &self,
+        webhook_id: i64,
+        limit: u32,
+    ) -> Result<Vec<WebhookDeadLetter>, sqlx::Error> {
+        sqlx::query_as::<_, WebhookDeadLetterRow>(
+            r#"
+            SELECT id, webhook_id, event, event_offset, payload, attempts, error, created_at
+            FROM webhook_dead_letters
+            WHERE webhook_id = ?1
+            ORDER BY id DESC
+            LIMIT ?2
+            "#,
+        )
+        .bind(webhook_id)
+        .bind(limit)
+        .fetch_all(&self.read_pool)
+        .await?
+        .into_iter()
+        .map(webhook_dead_letter_from_row)
+        .collect()
+    }
+
+    pub async fn get_webhook_dead_letter(
+        &self,
+        id: i64,
+    ) -> Result<Option<WebhookDeadLetter>, sqlx::Error> {
+        sqlx::query_as::<_, WebhookDeadLetterRow>(
+            "SELECT id, webhook_id, event, event_offset, payload, attempts, error, created_at FROM webhook_dead_letters WHERE id = ?1",
+        )
+        .bind(id)
+        .fetch_optional(&self.read_pool)
+        .await?
+        .map(webhook_dead_letter_from_row)
+        .transpose()
+    }
+
+    pub async fn delete_webhook_dead_letter(&self, id: i64) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM webhook_dead_letters WHERE id = ?1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn prune_webhook_history(
+        &self,
+        keep: i64,
+        before: DateTime<Utc>,
+    ) -> Result<u64, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let deliveries = sqlx::query(
+            r#"
+            DELETE FROM webhook_deliveries
+            WHERE delivered_at < ?2 OR id IN (
+                SELECT id FROM (
+                    SELECT id, ROW_NUMBER() OVER (PARTITION BY webhook_id ORDER BY id DESC) AS rn
+                    FROM webhook_deliveries
+                ) ranked
+                WHERE rn > ?1
+            )
+            "#,
+        )
+        .bind(keep)
+        .bind(before)
+        .execute(&mut *tx)
+        .await?;
+        let letters = sqlx::query(
+            r#"
+            DELETE FROM webhook_dead_letters
+            WHERE created_at < ?2 OR id IN (
+                SELECT id FROM (
+                    SELECT id, ROW_NUMBER() OVER (PARTITION BY webhook_id ORDER BY id DESC) AS rn
+                    FROM webhook_dead_letters
+                ) ranked
+                WHERE rn > ?1
+            )
+            "#,
+        )
+        .bind(keep)
+        .bind(before)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(deliveries.rows_affected() + letters.rows_affected())
+    }
+
+    pub async fn list_rules(&self) -> Result<Vec<Rule>, sqlx::Error> {
+        sqlx::query_as::<_, RuleRow>(
+            "SELECT id, definition, created_at, updated_at FROM automation_rules ORDER BY id",
+        )
+        .fetch_all(&self.read_pool)
+        .await?
+        .into_iter()
+        .map(rule_from_row)
+        .collect()
+    }
+
+    pub async fn get_rule(&self, id: i64) -> Result<Option<Rule>, sqlx::Error> {
+        sqlx::query_as::<_, RuleRow>(
+            "SELECT id, definition, created_at, updated_at FROM automation_rules WHERE id = ?1",
+        )
+        .bind(id)
+        .fetch_optional(&self.read_pool)
+        .await?
+        .map(rule_from_row)
+        .transpose()
+    }
+
+    pub async fn insert_rule(&self, spec: &RuleSpec) -> Result<i64, sqlx::Error> {
+        let now = Utc::now();
+        let id = sqlx::query(
+            "INSERT INTO automation_rules (definition, created_at, updated_at) VALUES (?1, ?2, ?2)",
+        )
+        .bind(serde_json::to_string(spec).map_err(|e| sqlx::Error::Encode(e.into()))?)
+        .bind(now)
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+        Ok(id)
+    }
+
+    pub async fn update_rule(&self, id: i64, spec: &RuleSpec) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE automation_rules SET definition = ?2, updated_at = ?3 WHERE id = ?1",
+        )
+        .bind(id)
+        .bind(serde_json::to_string(spec).map_err(|e| sqlx::Error::Encode(e.into()))?)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn delete_rule(&self, id: i64) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM automation_rules WHERE id = ?1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn list_meetings(
+        &self,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<Meeting>, sqlx::Error> {
+        sqlx::query_as::<_, MeetingRow>(&format!(
+            "SELECT {} FROM meetings
+             WHERE (?1 IS NULL OR end_time IS NULL OR end_time >= ?1)
+               AND (?2 IS NULL OR start_time <= ?2)
+             ORDER BY start_time DESC, id DESC
+             LIMIT ?3 OFFSET ?4",
+            MEETING_COLUMNS
+        ))
+        .bind(start_time)
+        .bind(end_time)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.read_pool)
+        .await?
+        .into_iter()
+        .map(meeting_from_row)
+        .collect()
+    }
+
+    pub async fn get_meeting(&self, id: i64) -> Result<Option<Meeting>, sqlx::Error> {
+        sqlx::query_as::<_, MeetingRow>(&format!(
+            "SELECT {} FROM meetings WHERE id = ?1",
+            MEETING_COLUMNS
+        ))
+        .bind(id)
+        .fetch_optional(&self.read_pool)
+        .await?
+        .map(meeting_from_row)
+        .transpose()
+    }
+
+    pub async fn insert_meeting(&self, spec: &MeetingSpec) -> Result<i64, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let now = Utc::now();
+        let id = sqlx::query(
+            "INSERT INTO meetings (start_time, end_time, app, title, participants, signals, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7)",
+        )
+        .bind(spec.start_time)
+        .bind(spec.end_time)
+        .bind(&spec.app)
+        .bind(&spec.title)
+        .bind(serde_json::to_string(&spec.participants).map_err(|e| sqlx::Error::Encode(e.into()))?)
+        .bind(serde_json::to_string(&spec.signals).map_err(|e| sqlx::Error::Encode(e.into()))?)
+        .bind(now)
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+        sqlx::query(LINK_MEETING_CONTENT)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    pub async fn update_meeting(&self, id: i64, spec: &MeetingSpec) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let result = sqlx::query(
+            "UPDATE meetings SET start_time = ?2, end_time = ?3, app = ?4, title = ?5, participants = ?6, signals = ?7, updated_at = ?8
+             WHERE id = ?1",
+        )
+        .bind(id)
+        .bind(spec.start_time)
+        .bind(spec.end_time)
+        .bind(&spec.app)
+        .bind(&spec.title)
+        .bind(serde_json::to_string(&spec.participants).map_err(|e| sqlx::Error::Encode(e.into()))?)
+        .bind(serde_json::to_string(&spec.signals).map_err(|e| sqlx::Error::Encode(e.into()))?)
+        .bind(Utc::now())
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(LINK_MEETING_CONTENT)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn delete_meeting(&self, id: i64) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM meetings WHERE id = ?1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn search_conversations(
+        &self,
+        query: &str,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<Conversation>, sqlx::Error> {
+        let where_clause = if query.is_empty() {
+            "WHERE 1=1"
+        } else {
+            "WHERE conversations.id IN (SELECT conversation_id FROM conversations_fts WHERE conversations_fts MATCH ?1)"
+        };
+        sqlx::query_as::<_, ConversationRow>(&format!(
+            "SELECT {} FROM conversations
+             {}
+               AND (?2 IS NULL OR conversations.end_time >= ?2)
+               AND (?3 IS NULL OR conversations.start_time <= ?3)
+             ORDER BY conversations.start_time DESC, conversations.id DESC
+             LIMIT ?4 OFFSET ?5",
+            CONVERSATION_COLUMNS, where_clause
+        ))
+        .bind(query)
+        .bind(start_time)
+        .bind(end_time)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.read_pool)
+        .await?
+        .into_iter()
+        .map(conversation_from_row)
+        .collect()
+    }
+
+    pub async fn get_conversation(&self, id: i64) -> Result<Option<Conversation>, sqlx::Error> {
+        sqlx::query_as::<_, ConversationRow>(&format!(
+            "SELECT {} FROM conversations WHERE id = ?1",
+            CONVERSATION_COLUMNS
+        ))
+        .bind(id)
+        .fetch_optional(&self.read_pool)
+        .await?
+        .map(conversation_from_row)
+        .transpose()
+    }
+
+    pub async fn get_conversation_turns(
+        &self,
+        id: i64,
+    ) -> Result<Vec<ConversationTurn>, sqlx::Error> {
+        sqlx::query_as::<_, ConversationTurnRow>(
+            "SELECT start_time, end_time, speaker_id, speaker_name, device, is_output_device, text, transcription_ids
+             FROM conversation_turns WHERE conversation_id = ?1 ORDER BY position",
+        )
+        .bind(id)
+        .fetch_all(&self.read_pool)
+        .await?
+        .into_iter()
+        .map(conversation_turn_from_row)
+        .collect()
+    }
+
+    pub async fn insert_conversation(&self, spec: &ConversationSpec) -> Result<i64, sqlx::Error> {
+        let transcription_ids: Vec<i64> = spec.transcription_ids().collect();
+        let mut tx = self.pool.begin().await?;
+        let id = sqlx::query(
+            "INSERT INTO conversations (start_time, end_time, devices, speakers, transcript, turn_count, first_transcription_id, last_transcription_id, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        )
+        .bind(spec.start_time)
+        .bind(spec.end_time)
+        .bind(serde_json::to_string(&spec.devices()).map_err(|e| sqlx::Error::Encode(e.into()))?)
+        .bind(serde_json::to_string(&spec.speakers()).map_err(|e| sqlx::Error::Encode(e.into()))?)
+        .bind(spec.transcript())
+        .bind(spec.turns.len() as i64)
+        .bind(transcription_ids.iter().min().copied())
+        .bind(transcription_ids.iter().max().copied())
+        .bind(Utc::now())
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+        for (position, turn) in spec.turns.iter().enumerate() {
+            sqlx::query(
+                "INSERT INTO conversation_turns (conversation_id, position, start_time, end_time, speaker_id, speaker_name, device, is_output_device, text, transcription_ids)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            )
+            .bind(id)
+            .bind(position as i64)
+            .bind(turn.start_time)
+            .bind(turn.end_time)
+            .bind(turn.speaker_id)
+            .bind(&turn.speaker_name)
+            .bind(&turn.device_name)
+            .bind(turn.output)
+            .bind(&turn.text)
+            .bind(serde_json::to_string(&turn.transcription_ids).map_err(|e| sqlx::Error::Encode(e.into()))?)
+            .execute(&mut *tx)
+            .await?;
+        }
+        let links = transcription_ids
+            .iter()
+            .map(|id| (*id, false))
+            .chain(spec.duplicates.iter().map(|id| (*id, true)));
+        for (transcription_id, duplicate) in links {
+            sqlx::query(
+                "INSERT INTO conversation_transcriptions (transcription_id, conversation_id, duplicate)
+                 VALUES (?1, ?2, ?3) ON CONFLICT (transcription_id) DO NOTHING",
+            )
+            .bind(transcription_id)
+            .bind(id)
+            .bind(duplicate)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    pub async fn delete_conversation(&self, id: i64) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        for table in ["conversation_transcriptions", "conversation_turns"] {
+            sqlx::query(&format!("DELETE FROM {} WHERE conversation_id = ?1", table))
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        let result = sqlx::query("DELETE FROM conversations WHERE id = ?1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn conversation_transcription_ids(
+        &self,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<i64>, sqlx::Error> {
+        sqlx::query_scalar(
+            "SELECT ct.transcription_id FROM conversation_transcriptions ct
+             JOIN audio_transcriptions t ON t.id = ct.transcription_id
+             WHERE t.timestamp >= ?1 AND t.timestamp <= ?2",
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .fetch_all(&self.read_pool)
+        .await
+    }
+
+    pub async fn repair_database(&self) -> Result<(), anyhow::Error> {
+        debug!("starting aggressive database repair process");
+
+        // Force close any pending transactions
         let emergency_steps = [
             "PRAGMA locking_mode = EXCLUSIVE;",
             "ROLLBACK;",                    // Force rollback any stuck transactions
             "PRAGMA busy_timeout = 60000;", // Increase timeout to 60s
         ];
 
-        for step in emergency_steps {
-            if let Err(e) = sqlx::query(step).execute(&self.pool).await {
-                warn!("emergency step failed (continuing anyway): {}", e);
-            }
-        }
+        for step in emergency_steps {
+            if let Err(e) = sqlx::query(step).execute(&self.pool).await {
+                warn!("emergency step failed (continuing anyway): {}", e);
+            }
+        }
+
+        // Force checkpoint and cleanup WAL files
+        let wal_cleanup = [
+            "PRAGMA wal_checkpoint(TRUNCATE);",
+            "PRAGMA journal_mode = DELETE;", // Temporarily disable WAL
+            "PRAGMA journal_size_limit = 0;", // Clear journal
+        ];
+
+        for step in wal_cleanup {
+            if let Err(e) = sqlx::query(step).execute(&self.pool).await {
+                warn!("wal cleanup failed (continuing anyway): {}", e);
+            }
+        }
+
+        // Aggressive recovery steps
+        let recovery_steps = [
+            ("PRAGMA synchronous = OFF;", "disable synchronous"),
+            ("PRAGMA cache_size = -2000000;", "increase cache"), // 2GB cache
+            ("VACUUM;", "vacuum database"),
+            ("PRAGMA integrity_check;", "check integrity"),
+            ("PRAGMA foreign_key_check;", "check foreign keys"),
+            ("REINDEX;", "rebuild indexes"),
+            ("ANALYZE;", "update statistics"),
+            ("VACUUM;", "final vacuum"), // Second vacuum after reindex
+        ];
+
+        for (query, step) in recovery_steps {
+            debug!("running aggressive recovery step: {}", step);
+            match sqlx::query(query).execute(&self.pool).await {
+                Ok(_) => debug!("recovery step '{}' succeeded", step),
+                Err(e) => warn!("recovery step '{}' failed: {}", step, e),
+            }
+        }
+
+        // Restore safe settings
+        let restore_steps = [
+            "PRAGMA synchronous = NORMAL;",
+            "PRAGMA journal_mode = WAL;",
+            "PRAGMA wal_autocheckpoint = 1000;",
+            "PRAGMA cache_size = -2000;", // Back to 2MB cache
+            "PRAGMA locking_mode = NORMAL;",
+            "PRAGMA busy_timeout = 5000;", // Back to 5s timeout
+        ];
+
+        for step in restore_steps {
+            if let Err(e) = sqlx::query(step).execute(&self.pool).await {
+                warn!("restore step failed: {}", e);
+            }
+        }
+
+        // Final verification
+        match sqlx::query_scalar::<_, String>("PRAGMA quick_check;")
+            .fetch_one(&self.pool)
+            .await
+        {
+            Ok(result) if result == "ok" => {
+                debug!("database successfully repaired");
+                Ok(())
+            }
+            Ok(result) => {
+                let msg = format!("database still corrupted after repair: {}", result);
+                error!("{}", msg);
+                Err(anyhow::anyhow!(msg))
+            }
+            Err(e) => {
+                let msg = format!("database repair failed catastrophically: {}", e);
+                error!("{}", msg);
+                Err(anyhow::anyhow!(msg))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Store for DatabaseManager {
+    fn backend(&self) -> &'static str {
+        "sqlite"
+    }
+
+    fn writer_metrics(&self) -> Option<DbWriterMetrics> {
+        Some(DatabaseManager::writer_metrics(self))
+    }
+
+    async fn maintenance_runs(&self) -> Option<Vec<MaintenanceRun>> {
+        self.get_latest_maintenance_runs().await.ok()
+    }
+}
+
+#[async_trait]
+impl CaptureStore for DatabaseManager {
+    async fn insert_video_chunk(
+        &self,
+        file_path: &str,
+        device_name: &str,
+    ) -> Result<i64, sqlx::Error> {
+        DatabaseManager::insert_video_chunk(self, file_path, device_name).await
+    }
+
+    async fn insert_frame(
+        &self,
+        device_name: &str,
+        timestamp: Option<DateTime<Utc>>,
+    ) -> Result<i64, sqlx::Error> {
+        DatabaseManager::insert_frame(self, device_name, timestamp).await
+    }
+
+    async fn insert_ocr_text(
+        &self,
+        frame_id: i64,
+        text: &str,
+        text_json: &str,
+        app_name: &str,
+        window_name: &str,
+        ocr_engine: Arc<OcrEngine>,
+        focused: bool,
+    ) -> Result<(), sqlx::Error> {
+        DatabaseManager::insert_ocr_text(
+            self,
+            frame_id,
+            text,
+            text_json,
+            app_name,
+            window_name,
+            ocr_engine,
+            focused,
+        )
+        .await
+    }
+
+    async fn insert_audio_chunk(&self, file_path: &str) -> Result<i64, sqlx::Error> {
+        DatabaseManager::insert_audio_chunk(self, file_path).await
+    }
+
+    async fn get_or_insert_audio_chunk(&self, file_path: &str) -> Result<i64, sqlx::Error> {
+        DatabaseManager::get_or_insert_audio_chunk(self, file_path).await
+    }
+
+    async fn insert_audio_transcription(
+        &self,
+        audio_chunk_id: i64,
+        transcription: &str,
+        offset_index: i64,
+        transcription_engine: &str,
+        device: &AudioDevice,
+        speaker_id: Option<i64>,
+        start_time: Option<f64>,
+        end_time: Option<f64>,
+    ) -> Result<i64, sqlx::Error> {
+        DatabaseManager::insert_audio_transcription(
+            self,
+            audio_chunk_id,
+            transcription,
+            offset_index,
+            transcription_engine,
+            device,
+            speaker_id,
+            start_time,
+            end_time,
+        )
+        .await
+    }
+
+    async fn update_audio_transcription(
+        &self,
+        audio_chunk_id: i64,
+        transcription: &str,
+    ) -> Result<i64, sqlx::Error> {
+        DatabaseManager::update_audio_transcription(self, audio_chunk_id, transcription).await
+    }
+
+    async fn set_audio_chunk_timestamp(
+        &self,
+        audio_chunk_id: i64,
+        timestamp: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error> {
+        DatabaseManager::set_audio_chunk_timestamp(self, audio_chunk_id, timestamp).await
+    }
+
+    async fn insert_ui_monitoring(
+        &self,
+        text_output: &str,
+        app: &str,
+        window: &str,
+        timestamp: DateTime<Utc>,
+    ) -> Result<i64, sqlx::Error> {
+        DatabaseManager::insert_ui_monitoring(self, text_output, app, window, timestamp).await
+    }
+
+    async fn create_video_with_frames(
+        &self,
+        file_path: &str,
+        frames: Vec<DynamicImage>,
+        metadata: VideoMetadata,
+    ) -> Result<Vec<i64>, sqlx::Error> {
+        DatabaseManager::create_video_with_frames(self, file_path, frames, metadata).await
+    }
+
+    async fn update_frame_name(&self, frame_id: i64, name: &str) -> Result<(), sqlx::Error> {
+        DatabaseManager::update_frame_name(self, frame_id, name).await
+    }
+
+    async fn update_video_chunk_frames_names(
+        &self,
+        video_chunk_id: i64,
+        name: &str,
+    ) -> Result<(), sqlx::Error> {
+        DatabaseManager::update_video_chunk_frames_names(self, video_chunk_id, name).await
+    }
+}
+
+#[async_trait]
+impl SearchStore for DatabaseManager {
+    async fn search_ocr(
+        &self,
+        query: &str,
+        limit: u32,
+        offset: u32,
+        cursor: Option<&SearchCursor>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>,
+        window_name: Option<&str>,
+        min_length: Option<usize>,
+        max_length: Option<usize>,
+        frame_name: Option<&str>,
+    ) -> Result<Vec<OCRResult>, sqlx::Error> {
+        DatabaseManager::search_ocr(
+            self,
+            query,
+            limit,
+            offset,
+            cursor,
+            start_time,
+            end_time,
+            app_name,
+            window_name,
+            min_length,
+            max_length,
+            frame_name,
+        )
+        .await
+    }
+
+    async fn search_audio(
+        &self,
+        query: &str,
+        limit: u32,
+        offset: u32,
+        cursor: Option<&SearchCursor>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        min_length: Option<usize>,
+        max_length: Option<usize>,
+        speaker_ids: Option<Vec<i64>>,
+    ) -> Result<Vec<AudioResult>, sqlx::Error> {
+        DatabaseManager::search_audio_after(
+            self,
+            query,
+            limit,
+            offset,
+            cursor,
+            start_time,
+            end_time,
+            min_length,
+            max_length,
+            speaker_ids,
+        )
+        .await
+    }
+
+    async fn search_ui_monitoring(
+        &self,
+        query: &str,
+        app_name: Option<&str>,
+        window_name: Option<&str>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        limit: u32,
+        offset: u32,
+        cursor: Option<&SearchCursor>,
+    ) -> Result<Vec<UiContent>, sqlx::Error> {
+        DatabaseManager::search_ui_monitoring_after(
+            self,
+            query,
+            app_name,
+            window_name,
+            start_time,
+            end_time,
+            limit,
+            offset,
+            cursor,
+        )
+        .await
+    }
+
+    async fn count_search_results_capped(
+        &self,
+        query: &str,
+        content_type: ContentType,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>,
+        window_name: Option<&str>,
+        min_length: Option<usize>,
+        max_length: Option<usize>,
+        speaker_ids: Option<Vec<i64>>,
+        frame_name: Option<&str>,
+        cap: Option<usize>,
+    ) -> Result<usize, sqlx::Error> {
+        DatabaseManager::count_search_results_capped(
+            self,
+            query,
+            content_type,
+            start_time,
+            end_time,
+            app_name,
+            window_name,
+            min_length,
+            max_length,
+            speaker_ids,
+            frame_name,
+            cap,
+        )
+        .await
+    }
+
+    async fn get_frame(&self, frame_id: i64) -> Result<Option<(String, i64)>, sqlx::Error> {
+        DatabaseManager::get_frame(self, frame_id).await
+    }
+
+    async fn get_latest_timestamps(
+        &self,
+    ) -> Result<
+        (
+            Option<DateTime<Utc>>,
+            Option<DateTime<Utc>>,
+            Option<DateTime<Utc>>,
+        ),
+        sqlx::Error,
+    > {
+        DatabaseManager::get_latest_timestamps(self).await
+    }
+
+    async fn execute_raw_sql(&self, query: &str) -> Result<serde_json::Value, sqlx::Error> {
+        DatabaseManager::execute_raw_sql(self, query).await
+    }
+}
+
+#[async_trait]
+impl EmbeddingStore for DatabaseManager {
+    async fn insert_embeddings(&self, frame_id: i64, embedding: String) -> Result<(), sqlx::Error> {
+        DatabaseManager::insert_embeddings(self, frame_id, embedding).await
+    }
+
+    async fn search_similar_embeddings(
+        &self,
+        embedding: Vec<f32>,
+        limit: u32,
+        threshold: f32,
+    ) -> Result<Vec<OCRResult>, sqlx::Error> {
+        DatabaseManager::search_similar_embeddings(self, embedding, limit, threshold).await
+    }
+}
+
+#[async_trait]
+impl TimelineStore for DatabaseManager {
+    async fn find_video_chunks(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<TimeSeriesChunk, sqlx::Error> {
+        DatabaseManager::find_video_chunks(self, start, end).await
+    }
 
-        // Force checkpoint and cleanup WAL files
-        let wal_cleanup = [
-            "PRAGMA wal_checkpoint(TRUNCATE);",
-            "PRAGMA journal_mode = DELETE;", // Temporarily disable WAL
-            "PRAGMA journal_size_limit = 0;", // Clear journal
-        ];
+    async fn get_total_frames(&self, video_path: &Path) -> Result<i64, sqlx::Error> {
+        DatabaseManager::get_total_frames(self, video_path).await
+    }
 
-        for step in wal_cleanup {
-            if let Err(e) = sqlx::query(step).execute(&self.pool).await {
-                warn!("wal cleanup failed (continuing anyway): {}", e);
-            }
-        }
+    async fn get_ordered_videos(&self) -> Result<Vec<String>, sqlx::Error> {
+        DatabaseManager::get_ordered_videos(self).await
+    }
 
-        // Aggressive recovery steps
-        let recovery_steps = [
-            ("PRAGMA synchronous = OFF;", "disable synchronous"),
-            ("PRAGMA cache_size = -2000000;", "increase cache"), // 2GB cache
-            ("VACUUM;", "vacuum database"),
-            ("PRAGMA integrity_check;", "check integrity"),
-            ("PRAGMA foreign_key_check;", "check foreign keys"),
-            ("REINDEX;", "rebuild indexes"),
-            ("ANALYZE;", "update statistics"),
-            ("VACUUM;", "final vacuum"), // Second vacuum after reindex
-        ];
+    async fn get_next_video(
+        &self,
+        current_video_path: &str,
+    ) -> Result<Option<String>, sqlx::Error> {
+        DatabaseManager::get_next_video(self, current_video_path).await
+    }
 
-        for (query, step) in recovery_steps {
-            debug!("running aggressive recovery step: {}", step);
-            match sqlx::query(query).execute(&self.pool).await {
-                Ok(_) => debug!("recovery step '{}' succeeded", step),
-                Err(e) => warn!("recovery step '{}' failed: {}", step, e),
-            }
-        }
+    async fn get_chunked_texts_since(
+        &self,
+        since: DateTime<Utc>,
+        source: &str,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        DatabaseManager::get_chunked_texts_since(self, since, source).await
+    }
+}
 
-        // Restore safe settings
-        let restore_steps = [
-            "PRAGMA synchronous = NORMAL;",
-            "PRAGMA journal_mode = WAL;",
-            "PRAGMA wal_autocheckpoint = 1000;",
-            "PRAGMA cache_size = -2000;", // Back to 2MB cache
-            "PRAGMA locking_mode = NORMAL;",
-            "PRAGMA busy_timeout = 5000;", // Back to 5s timeout
-        ];
+#[async_trait]
+impl TagStore for DatabaseManager {
+    async fn add_tags(
+        &self,
+        id: i64,
+        content_type: TagContentType,
+        tags: Vec<String>,
+    ) -> Result<(), sqlx::Error> {
+        DatabaseManager::add_tags(self, id, content_type, tags).await
+    }
 
-        for step in restore_steps {
-            if let Err(e) = sqlx::query(step).execute(&self.pool).await {
-                warn!("restore step failed: {}", e);
-            }
-        }
+    async fn get_tags(
+        &self,
+        id: i64,
+        content_type: TagContentType,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        DatabaseManager::get_tags(self, id, content_type).await
+    }
 
-        // Final verification
-        match sqlx::query_scalar::<_, String>("PRAGMA quick_check;")
-            .fetch_one(&self.pool)
+    async fn remove_tags(
+        &self,
+        id: i64,
+        content_type: TagContentType,
+        tags: Vec<String>,
+    ) -> Result<(), sqlx::Error> {
+        DatabaseManager::remove_tags(self, id, content_type, tags).await
+    }
+
+    async fn add_tags_to_ui_monitoring(
+        &self,
+        ui_monitoring_id: i64,
+        tag_ids: &[i64],
+    ) -> Result<(), anyhow::Error> {
+        DatabaseManager::add_tags_to_ui_monitoring(self, ui_monitoring_id, tag_ids).await
+    }
+
+    async fn get_ui_monitoring_tags(
+        &self,
+        ui_monitoring_id: i64,
+    ) -> Result<Vec<String>, anyhow::Error> {
+        DatabaseManager::get_ui_monitoring_tags(self, ui_monitoring_id).await
+    }
+}
+
+#[async_trait]
+impl SpeakerStore for DatabaseManager {
+    async fn insert_speaker(&self, embedding: &[f32]) -> Result<Speaker, sqlx::Error> {
+        DatabaseManager::insert_speaker(self, embedding).await
+    }
+
+    async fn update_speaker_metadata(
+        &self,
+        speaker_id: i64,
+        metadata: &str,
+    ) -> Result<i64, sqlx::Error> {
+        DatabaseManager::update_speaker_metadata(self, speaker_id, metadata).await
+    }
+
+    async fn get_speaker_by_id(&self, speaker_id: i64) -> Result<Speaker, sqlx::Error> {
+        DatabaseManager::get_speaker_by_id(self, speaker_id).await
+    }
+
+    async fn get_speaker_from_embedding(
+        &self,
+        embedding: &[f32],
+    ) -> Result<Option<Speaker>, sqlx::Error> {
+        DatabaseManager::get_speaker_from_embedding(self, embedding).await
+    }
+
+    async fn update_speaker_name(&self, speaker_id: i64, name: &str) -> Result<i64, sqlx::Error> {
+        DatabaseManager::update_speaker_name(self, speaker_id, name).await
+    }
+
+    async fn get_audio_chunks_for_speaker(
+        &self,
+        speaker_id: i64,
+    ) -> Result<Vec<AudioChunksResponse>, sqlx::Error> {
+        DatabaseManager::get_audio_chunks_for_speaker(self, speaker_id).await
+    }
+
+    async fn get_unnamed_speakers_page(
+        &self,
+        limit: u32,
+        offset: u32,
+        cursor: Option<&SpeakerCursor>,
+        speaker_ids: Option<Vec<i64>>,
+    ) -> Result<Vec<UnnamedSpeaker>, sqlx::Error> {
+        DatabaseManager::get_unnamed_speakers_page(self, limit, offset, cursor, speaker_ids).await
+    }
+
+    async fn merge_speakers(
+        &self,
+        speaker_to_keep_id: i64,
+        speaker_to_merge_id: i64,
+    ) -> Result<Speaker, sqlx::Error> {
+        DatabaseManager::merge_speakers(self, speaker_to_keep_id, speaker_to_merge_id).await
+    }
+
+    async fn search_speakers(&self, name_prefix: &str) -> Result<Vec<Speaker>, sqlx::Error> {
+        DatabaseManager::search_speakers(self, name_prefix).await
+    }
+
+    async fn delete_speaker(&self, id: i64) -> Result<(), sqlx::Error> {
+        DatabaseManager::delete_speaker(self, id).await
+    }
+
+    async fn get_similar_speakers(
+        &self,
+        speaker_id: i64,
+        limit: u32,
+    ) -> Result<Vec<Speaker>, sqlx::Error> {
+        DatabaseManager::get_similar_speakers(self, speaker_id, limit).await
+    }
+
+    async fn mark_speaker_as_hallucination(&self, id: i64) -> Result<(), sqlx::Error> {
+        DatabaseManager::mark_speaker_as_hallucination(self, id).await
+    }
+}
+
+#[async_trait]
+impl MediaChunkStore for DatabaseManager {
+    async fn find_offload_candidates(
+        &self,
+        kind: ChunkKind,
+        cutoff: DateTime<Utc>,
+        after_id: i64,
+        limit: i64,
+    ) -> Result<Vec<OffloadCandidate>, sqlx::Error> {
+        DatabaseManager::find_offload_candidates(self, kind, cutoff, after_id, limit).await
+    }
+
+    async fn mark_chunk_offloaded(
+        &self,
+        kind: ChunkKind,
+        id: i64,
+        remote_key: &str,
+    ) -> Result<(), sqlx::Error> {
+        DatabaseManager::mark_chunk_offloaded(self, kind, id, remote_key).await
+    }
+
+    async fn get_chunk_remote_key(&self, file_path: &str) -> Result<Option<String>, sqlx::Error> {
+        DatabaseManager::get_chunk_remote_key(self, file_path).await
+    }
+
+    async fn get_audio_chunk_file_path(&self, id: i64) -> Result<Option<String>, sqlx::Error> {
+        DatabaseManager::get_audio_chunk_file_path(self, id).await
+    }
+}
+
+#[async_trait]
+impl PipeCronStore for DatabaseManager {
+    async fn insert_pipe_cron_run(&self, run: &CronRun) -> Result<i64, sqlx::Error> {
+        DatabaseManager::insert_pipe_cron_run(self, run).await
+    }
+
+    async fn get_pipe_cron_runs(
+        &self,
+        pipe_id: &str,
+        job: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<CronRun>, sqlx::Error> {
+        DatabaseManager::get_pipe_cron_runs(self, pipe_id, job, limit).await
+    }
+}
+
+#[async_trait]
+impl WebhookStore for DatabaseManager {
+    async fn list_webhooks(&self) -> Result<Vec<Webhook>, sqlx::Error> {
+        DatabaseManager::list_webhooks(self).await
+    }
+
+    async fn get_webhook(&self, id: i64) -> Result<Option<Webhook>, sqlx::Error> {
+        DatabaseManager::get_webhook(self, id).await
+    }
+
+    async fn insert_webhook(&self, spec: &WebhookSpec) -> Result<i64, sqlx::Error> {
+        DatabaseManager::insert_webhook(self, spec).await
+    }
+
+    async fn update_webhook(&self, id: i64, spec: &WebhookSpec) -> Result<bool, sqlx::Error> {
+        DatabaseManager::update_webhook(self, id, spec).await
+    }
+
+    async fn delete_webhook(&self, id: i64) -> Result<bool, sqlx::Error> {
+        DatabaseManager::delete_webhook(self, id).await
+    }
+
+    async fn insert_webhook_delivery(
+        &self,
+        delivery: &WebhookDelivery,
+    ) -> Result<i64, sqlx::Error> {
+        DatabaseManager::insert_webhook_delivery(self, delivery).await
+    }
+
+    async fn get_webhook_deliveries(
+        &self,
+        webhook_id: i64,
+        limit: u32,
+    ) -> Result<Vec<WebhookDelivery>, sqlx::Error> {
+        DatabaseManager::get_webhook_deliveries(self, webhook_id, limit).await
+    }
+
+    async fn insert_webhook_dead_letter(
+        &self,
+        letter: &WebhookDeadLetter,
+    ) -> Result<i64, sqlx::Error> {
+        DatabaseManager::insert_webhook_dead_letter(self, letter).await
+    }
+
+    async fn get_webhook_dead_letters(
+        &self,
+        webhook_id: i64,
+        limit: u32,
+    ) -> Result<Vec<WebhookDeadLetter>, sqlx::Error> {
+        DatabaseManager::get_webhook_dead_letters(self, webhook_id, limit).await
+    }
+
+    async fn get_webhook_dead_letter(
+        &self,
+        id: i64,
+    ) -> Result<Option<WebhookDeadLetter>, sqlx::Error> {
+        DatabaseManager::get_webhook_dead_letter(self, id).await
+    }
+
+    async fn delete_webhook_dead_letter(&self, id: i64) -> Result<bool, sqlx::Error> {
+        DatabaseManager::delete_webhook_dead_letter(self, id).await
+    }
+
+    async fn prune_webhook_history(
+        &self,
+        keep: i64,
+        before: DateTime<Utc>,
+    ) -> Result<u64, sqlx::Error> {
+        DatabaseManager::prune_webhook_history(self, keep, before).await
+    }
+}
+
+#[async_trait]
+impl RuleStore for DatabaseManager {
+    async fn list_rules(&self) -> Result<Vec<Rule>, sqlx::Error> {
+        DatabaseManager::list_rules(self).await
+    }
+
+    async fn get_rule(&self, id: i64) -> Result<Option<Rule>, sqlx::Error> {
+        DatabaseManager::get_rule(self, id).await
+    }
+
+    async fn insert_rule(&self, spec: &RuleSpec) -> Result<i64, sqlx::Error> {
+        DatabaseManager::insert_rule(self, spec).await
+    }
+
+    async fn update_rule(&self, id: i64, spec: &RuleSpec) -> Result<bool, sqlx::Error> {
+        DatabaseManager::update_rule(self, id, spec).await
+    }
+
+    async fn delete_rule(&self, id: i64) -> Result<bool, sqlx::Error> {
+        DatabaseManager::delete_rule(self, id).await
+    }
+}
+
+#[async_trait]
+impl MeetingStore for DatabaseManager {
+    async fn list_meetings(
+        &self,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<Meeting>, sqlx::Error> {
+        DatabaseManager::list_meetings(self, start_time, end_time, limit, offset).await
+    }
+
+    async fn get_meeting(&self, id: i64) -> Result<Option<Meeting>, sqlx::Error> {
+        DatabaseManager::get_meeting(self, id).await
+    }
+
+    async fn insert_meeting(&self, spec: &MeetingSpec) -> Result<i64, sqlx::Error> {
+        DatabaseManager::insert_meeting(self, spec).await
+    }
+
+    async fn update_meeting(&self, id: i64, spec: &MeetingSpec) -> Result<bool, sqlx::Error> {
+        DatabaseManager::update_meeting(self, id, spec).await
+    }
+
+    async fn delete_meeting(&self, id: i64) -> Result<bool, sqlx::Error> {
+        DatabaseManager::delete_meeting(self, id).await
+    }
+}
+
+#[async_trait]
+impl ConversationStore for DatabaseManager {
+    async fn search_conversations(
+        &self,
+        query: &str,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<Conversation>, sqlx::Error> {
+        DatabaseManager::search_conversations(self, query, start_time, end_time, limit, offset)
             .await
-        {
-            Ok(result) if result == "ok" => {
-                debug!("database successfully repaired");
-                Ok(())
-            }
-            Ok(result) => {
-                let msg = format!("database still corrupted after repair: {}", result);
-                error!("{}", msg);
-                Err(anyhow::anyhow!(msg))
-            }
-            Err(e) => {
-                let msg = format!("database repair failed catastrophically: {}", e);
-                error!("{}", msg);
-                Err(anyhow::anyhow!(msg))
-            }
-        }
+    }
+
+    async fn get_conversation(&self, id: i64) -> Result<Option<Conversation>, sqlx::Error> {
+        DatabaseManager::get_conversation(self, id).await
+    }
+
+    async fn get_conversation_turns(&self, id: i64) -> Result<Vec<ConversationTurn>, sqlx::Error> {
+        DatabaseManager::get_conversation_turns(self, id).await
+    }
+
+    async fn insert_conversation(&self, spec: &ConversationSpec) -> Result<i64, sqlx::Error> {
+        DatabaseManager::insert_conversation(self, spec).await
+    }
+
+    async fn delete_conversation(&self, id: i64) -> Result<bool, sqlx::Error> {
+        DatabaseManager::delete_conversation(self, id).await
+    }
+
+    async fn conversation_transcription_ids(
+        &self,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<i64>, sqlx::Error> {
+        DatabaseManager::conversation_transcription_ids(self, start_time, end_time).await
     }
 }
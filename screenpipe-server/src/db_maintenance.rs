@@ -0,0 +1,340 @@
+//! Scheduled database maintenance.
+//!
+//! Cheap tasks (`PRAGMA optimize`, fts merges) run on their own schedule. Tasks
+//! that hold the write lock for longer only run once capture has been idle for a
+//! while, or when they have been postponed for too long.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::DatabaseManager;
+
+const FTS_TABLES: [&str; 3] = [
+    "ocr_text_fts",
+    "audio_transcriptions_fts",
+    "ui_monitoring_fts",
+];
+
+/// Pages freed per incremental vacuum run, keeps each run short.
+const INCREMENTAL_VACUUM_PAGES: i64 = 2000;
+
+/// Recorded runs kept per task, older ones are pruned by the scheduler.
+const KEPT_RUNS_PER_TASK: i64 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MaintenanceTask {
+    Optimize,
+    Analyze,
+    FtsMerge,
+    FtsOptimize,
+    IncrementalVacuum,
+    WalCheckpoint,
+}
+
+impl MaintenanceTask {
+    pub const ALL: [MaintenanceTask; 6] = [
+        MaintenanceTask::Optimize,
+        MaintenanceTask::Analyze,
+        MaintenanceTask::FtsMerge,
+        MaintenanceTask::FtsOptimize,
+        MaintenanceTask::IncrementalVacuum,
+        MaintenanceTask::WalCheckpoint,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MaintenanceTask::Optimize => "optimize",
+            MaintenanceTask::Analyze => "analyze",
+            MaintenanceTask::FtsMerge => "fts_merge",
+            MaintenanceTask::FtsOptimize => "fts_optimize",
+            MaintenanceTask::IncrementalVacuum => "incremental_vacuum",
+            MaintenanceTask::WalCheckpoint => "wal_checkpoint",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|t| t.as_str() == s)
+    }
+
+    fn interval(&self) -> Duration {
+        match self {
+            MaintenanceTask::Optimize => Duration::from_secs(60 * 60),
+            MaintenanceTask::FtsMerge => Duration::from_secs(60 * 60),
+            MaintenanceTask::WalCheckpoint => Duration::from_secs(30 * 60),
+            MaintenanceTask::IncrementalVacuum => Duration::from_secs(6 * 60 * 60),
+            MaintenanceTask::Analyze => Duration::from_secs(24 * 60 * 60),
+            MaintenanceTask::FtsOptimize => Duration::from_secs(24 * 60 * 60),
+        }
+    }
+
+    /// Whether the task should wait for capture to be idle.
+    fn needs_idle(&self) -> bool {
+        !matches!(self, MaintenanceTask::Optimize | MaintenanceTask::FtsMerge)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct MaintenanceRun {
+    pub id: i64,
+    pub task: String,
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: i64,
+    pub success: bool,
+    pub details: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MaintenanceConfig {
+    /// How often the scheduler checks for due tasks.
+    pub check_interval: Duration,
+    /// Capture is considered idle after this long without db writes.
+    pub idle_threshold: Duration,
+    /// Idle-only tasks run anyway once they are overdue by this much.
+    pub max_postpone: Duration,
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            check_interval: Duration::from_secs(60),
+            idle_threshold: Duration::from_secs(2 * 60),
+            max_postpone: Duration::from_secs(24 * 60 * 60),
+        }
+    }
+}
+
+impl DatabaseManager {
+    /// Latest run of every maintenance task.
+    pub async fn get_latest_maintenance_runs(&self) -> Result<Vec<MaintenanceRun>, sqlx::Error> {
+        sqlx::query_as::<_, MaintenanceRun>(
+            r#"
+            SELECT id, task, started_at, duration_ms, success, details
+            FROM maintenance_runs
+            WHERE id IN (SELECT MAX(id) FROM maintenance_runs GROUP BY task)
+            ORDER BY task
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn insert_maintenance_run(
+        &self,
+        task: MaintenanceTask,
+        started_at: DateTime<Utc>,
+        duration: Duration,
+        success: bool,
+        details: &str,
+    ) -> Result<i64, sqlx::Error> {
+        let id = sqlx::query(
+            "INSERT INTO maintenance_runs (task, started_at, duration_ms, success, details) VALUES (?1, ?2, ?3, ?4, ?5)",
+        )
+        .bind(task.as_str())
+        .bind(started_at)
+        .bind(duration.as_millis() as i64)
+        .bind(success)
+        .bind(details)
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+        Ok(id)
+    }
+
+    /// Deletes all but the latest `keep` runs of every task, returns how many were removed.
+    pub async fn prune_maintenance_runs(&self, keep: i64) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM maintenance_runs
+            WHERE id IN (
+                SELECT id FROM (
+                    SELECT id, ROW_NUMBER() OVER (PARTITION BY task ORDER BY id DESC) AS rn
+                    FROM maintenance_runs
+                )
+                WHERE rn > ?1
+            )
+            "#,
+        )
+        .bind(keep)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+}
+
+/// Runs a single maintenance task now and records the outcome in `maintenance_runs`.
+pub async fn run_maintenance_task(
+    db: &DatabaseManager,
+    task: MaintenanceTask,
+) -> Result<MaintenanceRun, sqlx::Error> {
+    let started_at = Utc::now();
+    let start = Instant::now();
+
+    let result = match task {
+        MaintenanceTask::Optimize => sqlx::query("PRAGMA optimize;")
+            .execute(&db.pool)
+            .await
+            .map(|_| "ok".to_string()),
+        MaintenanceTask::Analyze => sqlx::query("ANALYZE;")
+            .execute(&db.pool)
+            .await
+            .map(|_| "ok".to_string()),
+        MaintenanceTask::FtsMerge => {
+            run_fts_command(db, "INSERT INTO {t}({t}, rank) VALUES('merge', 500)").await
+        }
+        MaintenanceTask::FtsOptimize => {
+            run_fts_command(db, "INSERT INTO {t}({t}) VALUES('optimize')").await
+        }
+        MaintenanceTask::IncrementalVacuum => incremental_vacuum(db).await,
+        MaintenanceTask::WalCheckpoint => wal_checkpoint(db).await,
+    };
+
+    let duration = start.elapsed();
+    let (success, details) = match &result {
+        Ok(details) => (true, details.clone()),
+        Err(e) => (false, e.to_string()),
+    };
+
+    if success {
+        debug!(
+            "db maintenance {} done in {:?}: {}",
+            task.as_str(),
+            duration,
+            details
+        );
+    } else {
+        warn!("db maintenance {} failed: {}", task.as_str(), details);
+    }
+
+    let id = db
+        .insert_maintenance_run(task, started_at, duration, success, &details)
+        .await?;
+
+    Ok(MaintenanceRun {
+        id,
+        task: task.as_str().to_string(),
+        started_at,
+        duration_ms: duration.as_millis() as i64,
+        success,
+        details: Some(details),
+    })
+}
+
+async fn run_fts_command(db: &DatabaseManager, template: &str) -> Result<String, sqlx::Error> {
+    for table in FTS_TABLES {
+        sqlx::query(&template.replace("{t}", table))
+            .execute(&db.pool)
+            .await?;
+    }
+    Ok(FTS_TABLES.join(", "))
+}
+
+async fn incremental_vacuum(db: &DatabaseManager) -> Result<String, sqlx::Error> {
+    let auto_vacuum: i64 = sqlx::query_scalar("PRAGMA auto_vacuum;")
+        .fetch_one(&db.pool)
+        .await?;
+    // 2 = incremental, databases created before it was enabled need a one-time VACUUM
+    if auto_vacuum != 2 {
+        return Ok("skipped: auto_vacuum is not incremental".to_string());
+    }
+
+    let free_pages: i64 = sqlx::query_scalar("PRAGMA freelist_count;")
+        .fetch_one(&db.pool)
+        .await?;
+    sqlx::query(&format!(
+        "PRAGMA incremental_vacuum({});",
+        INCREMENTAL_VACUUM_PAGES
+    ))
+    .execute(&db.pool)
+    .await?;
+
+    Ok(format!(
+        "freed {} of {} free pages",
+        free_pages.min(INCREMENTAL_VACUUM_PAGES),
+        free_pages
+    ))
+}
+
+async fn wal_checkpoint(db: &DatabaseManager) -> Result<String, sqlx::Error> {
+    let row = sqlx::query("PRAGMA wal_checkpoint(TRUNCATE);")
+        .fetch_one(&db.pool)
+        .await?;
+    let busy: i64 = row.try_get(0)?;
+    let log_frames: i64 = row.try_get(1)?;
+    let checkpointed: i64 = row.try_get(2)?;
+    Ok(format!(
+        "busy: {}, wal frames: {}, checkpointed: {}",
+        busy, log_frames, checkpointed
+    ))
+}
+
+/// Spawns the maintenance scheduler. Previous runs are read back from the db so
+/// restarts don't redo work that was done recently.
+pub fn start_db_maintenance(db: Arc<DatabaseManager>, config: MaintenanceConfig) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut last_runs: HashMap<MaintenanceTask, DateTime<Utc>> = HashMap::new();
+        match db.get_latest_maintenance_runs().await {
+            Ok(runs) => {
+                for run in runs {
+                    if let Some(task) = MaintenanceTask::parse(&run.task) {
+                        last_runs.insert(task, run.started_at);
+                    }
+                }
+            }
+            Err(e) => warn!("failed to load previous maintenance runs: {}", e),
+        }
+        // tasks that never ran are due now
+        for task in MaintenanceTask::ALL {
+            last_runs.entry(task).or_insert_with(|| {
+                Utc::now() - chrono::Duration::from_std(task.interval()).unwrap_or_default()
+            });
+        }
+
+        info!("db maintenance scheduler started");
+        let mut interval = tokio::time::interval(config.check_interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            interval.tick().await;
+            let idle = db.write_idle_for() >= config.idle_threshold;
+            let mut ran = false;
+
+            for task in MaintenanceTask::ALL {
+                let since_last = last_runs
+                    .get(&task)
+                    .and_then(|at| (Utc::now() - *at).to_std().ok())
+                    .unwrap_or_default();
+                if since_last < task.interval() {
+                    continue;
+                }
+
+                let overdue = since_last >= task.interval() + config.max_postpone;
+                if task.needs_idle() && !idle && !overdue {
+                    continue;
+                }
+
+                match run_maintenance_task(&db, task).await {
+                    Ok(_) => {}
+                    Err(e) => warn!("failed to record maintenance run: {}", e),
+                }
+                last_runs.insert(task, Utc::now());
+                ran = true;
+            }
+
+            if ran {
+                match db.prune_maintenance_runs(KEPT_RUNS_PER_TASK).await {
+                    Ok(0) => {}
+                    Ok(pruned) => debug!("pruned {} old maintenance runs", pruned),
+                    Err(e) => warn!("failed to prune maintenance runs: {}", e),
+                }
+            }
+        }
+    })
+}
@@ -1,7 +1,9 @@
-use chrono::{DateTime, Utc};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::{DateTime, TimeZone, Utc};
 use screenpipe_audio::DeviceType;
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use std::cmp::{Ordering, Reverse};
 use std::error::Error as StdError;
 use std::fmt::{self, Display};
 
@@ -23,6 +25,147 @@ pub enum SearchResult {
     UI(UiContent),
 }
 
+impl SearchResult {
+    pub fn timestamp(&self) -> DateTime<Utc> {
+        match self {
+            SearchResult::OCR(ocr) => ocr.timestamp,
+            SearchResult::Audio(audio) => audio.timestamp,
+            SearchResult::UI(ui) => ui.timestamp,
+        }
+    }
+
+    /// Position of this result in a search, the next page starts right after it.
+    pub fn cursor(&self) -> SearchCursor {
+        let (kind, id) = match self {
+            SearchResult::OCR(ocr) => (SearchKind::Ocr, ocr.frame_id),
+            SearchResult::Audio(audio) => (SearchKind::Audio, audio.transcription_id),
+            SearchResult::UI(ui) => (SearchKind::Ui, ui.id),
+        };
+        SearchCursor {
+            timestamp: self.timestamp(),
+            kind,
+            id,
+        }
+    }
+}
+
+/// Content kind of a search result, also the tie-breaker between results with
+/// the same timestamp.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SearchKind {
+    Ocr = 0,
+    Audio = 1,
+    Ui = 2,
+}
+
+impl SearchKind {
+    fn from_i64(value: i64) -> Option<Self> {
+        match value {
+            0 => Some(SearchKind::Ocr),
+            1 => Some(SearchKind::Audio),
+            2 => Some(SearchKind::Ui),
+            _ => None,
+        }
+    }
+}
+
+/// Keyset position in newest-first results, ordered by timestamp desc, kind asc, id desc.
+///
+/// Rows of every kind share one ordering so a single cursor can page through merged
+/// ocr, audio and ui results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchCursor {
+    pub timestamp: DateTime<Utc>,
+    pub kind: SearchKind,
+    pub id: i64,
+}
+
+impl SearchCursor {
+    /// Bound for rows of `kind` that come after this cursor: those with
+    /// `timestamp < bound.0`, or `timestamp = bound.0 AND id < bound.1`.
+    pub fn bound_for(&self, kind: SearchKind) -> (DateTime<Utc>, i64) {
+        let id = match kind.cmp(&self.kind) {
+            Ordering::Greater => i64::MAX,
+            Ordering::Less => i64::MIN,
+            Ordering::Equal => self.id,
+        };
+        (self.timestamp, id)
+    }
+
+    /// Sort key, smaller keys come first.
+    pub fn sort_key(&self) -> (Reverse<DateTime<Utc>>, SearchKind, Reverse<i64>) {
+        (Reverse(self.timestamp), self.kind, Reverse(self.id))
+    }
+
+    pub fn encode(&self) -> String {
+        encode_token(
+            'r',
+            &[
+                self.timestamp.timestamp_nanos_opt().unwrap_or_default(),
+                self.kind as i64,
+                self.id,
+            ],
+        )
+    }
+
+    pub fn decode(token: &str) -> Option<Self> {
+        match decode_token('r', token)?.as_slice() {
+            [nanos, kind, id] => Some(SearchCursor {
+                timestamp: Utc.timestamp_nanos(*nanos),
+                kind: SearchKind::from_i64(*kind)?,
+                id: *id,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Keyset position in `/speakers/unnamed`, ordered by transcription count desc, id desc.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpeakerCursor {
+    pub transcription_count: i64,
+    pub id: i64,
+}
+
+impl SpeakerCursor {
+    pub fn encode(&self) -> String {
+        encode_token('s', &[self.transcription_count, self.id])
+    }
+
+    pub fn decode(token: &str) -> Option<Self> {
+        match decode_token('s', token)?.as_slice() {
+            [transcription_count, id] => Some(SpeakerCursor {
+                transcription_count: *transcription_count,
+                id: *id,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Cursors are opaque to clients: a tag and the key parts, url-safe base64 encoded.
+fn encode_token(tag: char, parts: &[i64]) -> String {
+    let parts: Vec<String> = parts.iter().map(|p| p.to_string()).collect();
+    URL_SAFE_NO_PAD.encode(format!("{}:{}", tag, parts.join(":")))
+}
+
+fn decode_token(tag: char, token: &str) -> Option<Vec<i64>> {
+    let decoded = String::from_utf8(URL_SAFE_NO_PAD.decode(token).ok()?).ok()?;
+    let mut parts = decoded.split(':');
+    if parts.next()? != tag.to_string() {
+        return None;
+    }
+    parts.map(|p| p.parse().ok()).collect()
+}
+
+/// One page of keyset-paginated search results.
+#[derive(Debug)]
+pub struct SearchPage {
+    pub results: Vec<SearchResult>,
+    /// Set when more results exist, pass it back to get the next page.
+    pub next_cursor: Option<SearchCursor>,
+}
+
 #[derive(FromRow, Debug)]
 pub struct OCRResultRaw {
     pub frame_id: i64,
@@ -74,6 +217,7 @@ pub enum ContentType {
 
 #[derive(FromRow)]
 pub struct AudioResultRaw {
+    pub transcription_id: i64,
     pub audio_chunk_id: i64,
     pub transcription: String,
     pub timestamp: DateTime<Utc>,
@@ -95,8 +239,26 @@ pub struct Speaker {
     pub metadata: String,
 }
 
+/// Unnamed speaker along with the count `/speakers/unnamed` is ordered by.
+#[derive(Debug, FromRow)]
+pub struct UnnamedSpeaker {
+    #[sqlx(flatten)]
+    pub speaker: Speaker,
+    pub transcription_count: i64,
+}
+
+impl UnnamedSpeaker {
+    pub fn cursor(&self) -> SpeakerCursor {
+        SpeakerCursor {
+            transcription_count: self.transcription_count,
+            id: self.speaker.id,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AudioResult {
+    pub transcription_id: i64,
     pub audio_chunk_id: i64,
     pub transcription: String,
     pub timestamp: DateTime<Utc>,
@@ -111,6 +273,19 @@ pub struct AudioResult {
     pub end_time: Option<f64>,
 }
 
+/// How `/search` computes `pagination.total`.
+#[derive(Debug, Deserialize, PartialEq, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum CountMode {
+    /// Count every match.
+    #[default]
+    Exact,
+    /// Stop counting at a cap, the total is then a lower bound.
+    Approximate,
+    /// Skip counting entirely.
+    None,
+}
+
 #[derive(Debug, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum TagContentType {
@@ -118,6 +293,36 @@ pub enum TagContentType {
     Audio,
 }
 
+/// Kind of media chunk stored on disk (and possibly offloaded).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChunkKind {
+    Video,
+    Audio,
+}
+
+impl ChunkKind {
+    pub fn table(&self) -> &'static str {
+        match self {
+            ChunkKind::Video => "video_chunks",
+            ChunkKind::Audio => "audio_chunks",
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChunkKind::Video => "video",
+            ChunkKind::Audio => "audio",
+        }
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct OffloadCandidate {
+    pub id: i64,
+    pub file_path: String,
+}
+
 #[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
 pub struct UiContent {
     pub id: i64,
@@ -0,0 +1,425 @@
+//! Single writer task for the hot insert paths (frames, ocr, audio, ui).
+//!
+//! SQLite only allows one writer at a time, so instead of every capture loop
+//! opening its own transaction on a shared pool, inserts are queued to one task
+//! that groups whatever is pending into a single transaction. Callers still get
+//! their row id back, only once the batch containing their write has committed.
+//! Batches that hit a busy database (another process, a checkpoint) are retried.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::sqlite::{SqliteConnection, SqlitePool};
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{sleep, timeout_at, Instant};
+use tracing::{debug, error, warn};
+
+const QUEUE_CAPACITY: usize = 4096;
+const MAX_ATTEMPTS: u32 = 3;
+
+#[derive(Debug, Clone)]
+pub struct DbWriterConfig {
+    /// Max number of writes grouped in one transaction.
+    pub max_batch_size: usize,
+    /// How long the writer waits for more writes once it has at least one.
+    pub max_batch_wait: Duration,
+}
+
+impl Default for DbWriterConfig {
+    fn default() -> Self {
+        Self {
+            max_batch_size: 256,
+            max_batch_wait: Duration::from_millis(10),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum WriteOp {
+    InsertVideoChunk {
+        file_path: String,
+        device_name: String,
+    },
+    InsertFrame {
+        device_name: String,
+        timestamp: Option<DateTime<Utc>>,
+    },
+    InsertOcrText {
+        frame_id: i64,
+        text: String,
+        text_json: String,
+        app_name: String,
+        window_name: String,
+        ocr_engine: String,
+        focused: bool,
+    },
+    InsertAudioChunk {
+        file_path: String,
+    },
+    InsertAudioTranscription {
+        audio_chunk_id: i64,
+        transcription: String,
+        offset_index: i64,
+        transcription_engine: String,
+        device_name: String,
+        is_input_device: bool,
+        speaker_id: Option<i64>,
+        start_time: Option<f64>,
+        end_time: Option<f64>,
+    },
+    InsertUiMonitoring {
+        text_output: String,
+        app: String,
+        window: String,
+        timestamp: DateTime<Utc>,
+    },
+}
+
+struct WriteRequest {
+    op: WriteOp,
+    queued_at: Instant,
+    reply: oneshot::Sender<Result<i64, sqlx::Error>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DbWriterMetrics {
+    pub batches: u64,
+    pub writes: u64,
+    pub failed_writes: u64,
+    pub last_batch_size: usize,
+    pub max_batch_size: usize,
+    pub avg_batch_size: f64,
+    pub last_batch_latency_ms: f64,
+    pub max_batch_latency_ms: f64,
+    pub avg_batch_latency_ms: f64,
+    /// Time between a write being queued and its batch committing.
+    pub avg_write_latency_ms: f64,
+    pub queue_depth: usize,
+}
+
+#[derive(Default)]
+struct WriterStats {
+    batches: u64,
+    writes: u64,
+    failed_writes: u64,
+    last_batch_size: usize,
+    max_batch_size: usize,
+    last_batch_latency: Duration,
+    max_batch_latency: Duration,
+    total_batch_latency: Duration,
+    total_write_latency: Duration,
+    last_commit: Option<Instant>,
+}
+
+#[derive(Clone)]
+pub(crate) struct DbWriter {
+    tx: mpsc::Sender<WriteRequest>,
+    stats: Arc<Mutex<WriterStats>>,
+    started_at: Instant,
+}
+
+impl DbWriter {
+    pub(crate) fn spawn(pool: SqlitePool, config: DbWriterConfig) -> Self {
+        let (tx, rx) = mpsc::channel(QUEUE_CAPACITY);
+        let stats = Arc::new(Mutex::new(WriterStats::default()));
+        tokio::spawn(run_writer(pool, rx, stats.clone(), config));
+        Self {
+            tx,
+            stats,
+            started_at: Instant::now(),
+        }
+    }
+
+    /// Time since the last batch committed (or since the writer started).
+    pub(crate) fn idle_for(&self) -> Duration {
+        if self.tx.max_capacity() != self.tx.capacity() {
+            return Duration::ZERO;
+        }
+        let last = self.stats.lock().unwrap().last_commit;
+        last.unwrap_or(self.started_at).elapsed()
+    }
+
+    pub(crate) async fn write(&self, op: WriteOp) -> Result<i64, sqlx::Error> {
+        let (reply, response) = oneshot::channel();
+        self.tx
+            .send(WriteRequest {
+                op,
+                queued_at: Instant::now(),
+                reply,
+            })
+            .await
+            .map_err(|_| sqlx::Error::PoolClosed)?;
+        response.await.map_err(|_| sqlx::Error::PoolClosed)?
+    }
+
+    pub(crate) fn metrics(&self) -> DbWriterMetrics {
+        let stats = self.stats.lock().unwrap();
+        let batches = stats.batches.max(1) as f64;
+        let writes = stats.writes.max(1) as f64;
+        DbWriterMetrics {
+            batches: stats.batches,
+            writes: stats.writes,
+            failed_writes: stats.failed_writes,
+            last_batch_size: stats.last_batch_size,
+            max_batch_size: stats.max_batch_size,
+            avg_batch_size: stats.writes as f64 / batches,
+            last_batch_latency_ms: as_ms(stats.last_batch_latency),
+            max_batch_latency_ms: as_ms(stats.max_batch_latency),
+            avg_batch_latency_ms: as_ms(stats.total_batch_latency) / batches,
+            avg_write_latency_ms: as_ms(stats.total_write_latency) / writes,
+            queue_depth: self.tx.max_capacity() - self.tx.capacity(),
+        }
+    }
+}
+
+fn as_ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+async fn run_writer(
+    pool: SqlitePool,
+    mut rx: mpsc::Receiver<WriteRequest>,
+    stats: Arc<Mutex<WriterStats>>,
+    config: DbWriterConfig,
+) {
+    while let Some(first) = rx.recv().await {
+        let mut batch = vec![first];
+
+        // take whatever is already queued, then give late writers a short window
+        while batch.len() < config.max_batch_size {
+            match rx.try_recv() {
+                Ok(request) => batch.push(request),
+                Err(_) => break,
+            }
+        }
+        let deadline = Instant::now() + config.max_batch_wait;
+        while batch.len() < config.max_batch_size {
+            match timeout_at(deadline, rx.recv()).await {
+                Ok(Some(request)) => batch.push(request),
+                _ => break,
+            }
+        }
+
+        let started = Instant::now();
+        let batch_size = batch.len();
+        let mut failed = 0;
+
+        match execute_with_retry(&pool, &batch).await {
+            Ok(ids) => {
+                let now = Instant::now();
+                let mut write_latency = Duration::ZERO;
+                for (request, id) in batch.into_iter().zip(ids) {
+                    write_latency += now - request.queued_at;
+                    let _ = request.reply.send(Ok(id));
+                }
+                stats.lock().unwrap().total_write_latency += write_latency;
+            }
+            Err(e) => {
+                // replay one by one so a single bad write doesn't fail the others
+                warn!(
+                    "db writer batch of {} failed, retrying writes individually: {}",
+                    batch_size, e
+                );
+                let mut write_latency = Duration::ZERO;
+                for request in batch {
+                    let result = execute_with_retry(&pool, std::slice::from_ref(&request))
+                        .await
+                        .map(|ids| ids[0]);
+                    if let Err(e) = &result {
+                        error!("db write failed: {}", e);
+                        failed += 1;
+                    }
+                    write_latency += Instant::now() - request.queued_at;
+                    let _ = request.reply.send(result);
+                }
+                stats.lock().unwrap().total_write_latency += write_latency;
+            }
+        }
+
+        let latency = started.elapsed();
+        debug!("db writer committed {} writes in {:?}", batch_size, latency);
+
+        let mut stats = stats.lock().unwrap();
+        stats.batches += 1;
+        stats.writes += batch_size as u64;
+        stats.failed_writes += failed;
+        stats.last_batch_size = batch_size;
+        stats.max_batch_size = stats.max_batch_size.max(batch_size);
+        stats.last_batch_latency = latency;
+        stats.max_batch_latency = stats.max_batch_latency.max(latency);
+        stats.total_batch_latency += latency;
+        stats.last_commit = Some(Instant::now());
+    }
+    debug!("db writer stopped");
+}
+
+/// Runs a batch, retrying with a short backoff while the database is busy.
+async fn execute_with_retry(
+    pool: &SqlitePool,
+    batch: &[WriteRequest],
+) -> Result<Vec<i64>, sqlx::Error> {
+    let mut attempt = 1;
+    loop {
+        match execute_batch(pool, batch).await {
+            Err(e) if attempt < MAX_ATTEMPTS && is_busy(&e) => {
+                warn!(
+                    "db writer batch of {} failed on attempt {}/{}, retrying: {}",
+                    batch.len(),
+                    attempt,
+                    MAX_ATTEMPTS,
+                    e
+                );
+                sleep(Duration::from_millis(100 * attempt as u64)).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// SQLITE_BUSY, SQLITE_LOCKED and SQLITE_BUSY_SNAPSHOT, or no connection in time.
+fn is_busy(e: &sqlx::Error) -> bool {
+    match e {
+        sqlx::Error::PoolTimedOut => true,
+        sqlx::Error::Database(e) => matches!(e.code().as_deref(), Some("5" | "6" | "517")),
+        _ => false,
+    }
+}
+
+async fn execute_batch(pool: &SqlitePool, batch: &[WriteRequest]) -> Result<Vec<i64>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let mut ids = Vec::with_capacity(batch.len());
+    for request in batch {
+        ids.push(apply(&mut tx, &request.op).await?);
+    }
+    tx.commit().await?;
+    Ok(ids)
+}
+
+async fn apply(conn: &mut SqliteConnection, op: &WriteOp) -> Result<i64, sqlx::Error> {
+    match op {
+        WriteOp::InsertVideoChunk {
+            file_path,
+            device_name,
+        } => Ok(
+            sqlx::query("INSERT INTO video_chunks (file_path, device_name) VALUES (?1, ?2)")
+                .bind(file_path)
+                .bind(device_name)
+                .execute(&mut *conn)
+                .await?
+                .last_insert_rowid(),
+        ),
+        WriteOp::InsertFrame {
+            device_name,
+            timestamp,
+        } => {
+            // Get the most recent video_chunk_id and file_path
+            let video_chunk: Option<(i64, String)> = sqlx::query_as(
+                "SELECT id, file_path FROM video_chunks WHERE device_name = ?1 ORDER BY id DESC LIMIT 1",
+            )
+            .bind(device_name)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+            // If no video chunk is found, return 0
+            let Some((video_chunk_id, file_path)) = video_chunk else {
+                debug!("no video chunk found for {}, skipping frame", device_name);
+                return Ok(0);
+            };
+
+            let offset_index: i64 = sqlx::query_scalar(
+                "SELECT COALESCE(MAX(offset_index), -1) + 1 FROM frames WHERE video_chunk_id = ?1",
+            )
+            .bind(video_chunk_id)
+            .fetch_one(&mut *conn)
+            .await?;
+
+            // Insert the new frame with file_path as name
+            Ok(sqlx::query(
+                "INSERT INTO frames (video_chunk_id, offset_index, timestamp, name) VALUES (?1, ?2, ?3, ?4)",
+            )
+            .bind(video_chunk_id)
+            .bind(offset_index)
+            .bind(timestamp.unwrap_or_else(Utc::now))
+            .bind(file_path)
+            .execute(&mut *conn)
+            .await?
+            .last_insert_rowid())
+        }
+        WriteOp::InsertOcrText {
+            frame_id,
+            text,
+            text_json,
+            app_name,
+            window_name,
+            ocr_engine,
+            focused,
+        } => Ok(sqlx::query(
+            "INSERT INTO ocr_text (frame_id, text, text_json, app_name, ocr_engine, window_name, focused, text_length) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        )
+        .bind(frame_id)
+        .bind(text)
+        .bind(text_json)
+        .bind(app_name)
+        .bind(ocr_engine)
+        .bind(window_name)
+        .bind(focused)
+        .bind(text.len() as i64)
+        .execute(&mut *conn)
+        .await?
+        .last_insert_rowid()),
+        WriteOp::InsertAudioChunk { file_path } => Ok(sqlx::query(
+            "INSERT INTO audio_chunks (file_path, timestamp) VALUES (?1, ?2)",
+        )
+        .bind(file_path)
+        .bind(Utc::now())
+        .execute(&mut *conn)
+        .await?
+        .last_insert_rowid()),
+        WriteOp::InsertAudioTranscription {
+            audio_chunk_id,
+            transcription,
+            offset_index,
+            transcription_engine,
+            device_name,
+            is_input_device,
+            speaker_id,
+            start_time,
+            end_time,
+        } => Ok(sqlx::query(
+            "INSERT INTO audio_transcriptions (audio_chunk_id, transcription, offset_index, timestamp, transcription_engine, device, is_input_device, speaker_id, start_time, end_time, text_length) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        )
+        .bind(audio_chunk_id)
+        .bind(transcription)
+        .bind(offset_index)
+        .bind(Utc::now())
+        .bind(transcription_engine)
+        .bind(device_name)
+        .bind(is_input_device)
+        .bind(speaker_id)
+        .bind(start_time)
+        .bind(end_time)
+        .bind(transcription.len() as i64)
+        .execute(&mut *conn)
+        .await?
+        .last_insert_rowid()),
+        WriteOp::InsertUiMonitoring {
+            text_output,
+            app,
+            window,
+            timestamp,
+        } => Ok(sqlx::query(
+            "INSERT INTO ui_monitoring (text_output, timestamp, app, window, text_length) VALUES (?1, ?2, ?3, ?4, ?5)",
+        )
+        .bind(text_output)
+        .bind(timestamp)
+        .bind(app)
+        .bind(window)
+        .bind(text_output.len() as i64)
+        .execute(&mut *conn)
+        .await?
+        .last_insert_rowid()),
+    }
+}
@@ -0,0 +1,215 @@
+//! The event bus over a Unix domain socket, for shell scripts and tools that don't speak
+//! http.
+//!
+//! Same protocol as `/ws/events`, one json object per line: the socket writes the events of
+//! the bus, and reads events to send or `{"type": "subscribe", "events": [..], "where": ..}`
+//! to change which ones it writes. `nc -U ~/.screenpipe/events.sock` follows the bus.
+
+use screenpipe_events::{send_event, Event, EventFilter};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::sync::watch;
+
+/// Events queued for a socket client before new ones are dropped.
+pub const DEFAULT_SOCKET_QUEUE: usize = 1000;
+
+/// Messages a client sends to change what it receives, anything else is an event to send.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum EventsControl {
+    /// Replaces the subscription of the connection.
+    Subscribe {
+        #[serde(default)]
+        events: Vec<String>,
+        #[serde(default, rename = "where")]
+        predicates: Option<String>,
+    },
+}
+
+pub(crate) fn control_event(name: &str, data: Value) -> Event {
+    Event {
+        name: name.to_string(),
+        data,
+        offset: None,
+    }
+}
+
+/// Handles a line from a client, returning the reply to send back if there is one. Replies
+/// are named after `prefix`, e.g. `ws_subscribed` or `ws_error`.
+pub(crate) fn handle_client_message(
+    text: &str,
+    filter: &watch::Sender<EventFilter>,
+    prefix: &str,
+) -> Option<Event> {
+    let error = |message: String| {
+        control_event(&format!("{}_error", prefix), json!({ "message": message }))
+    };
+    if let Ok(EventsControl::Subscribe { events, predicates }) =
+        serde_json::from_str::<EventsControl>(text)
+    {
+        return Some(
+            match EventFilter::new(events.clone(), predicates.as_deref()) {
+                Ok(new_filter) => {
+                    let _ = filter.send(new_filter);
+                    control_event(
+                        &format!("{}_subscribed", prefix),
+                        json!({"events": events, "where": predicates}),
+                    )
+                }
+                Err(e) => error(e.to_string()),
+            },
+        );
+    }
+    match serde_json::from_str::<Event>(text) {
+        Ok(event) => send_event(&event.name, event.data)
+            .err()
+            .map(|e| error(e.to_string())),
+        Err(_) => Some(error("unrecognized message".to_string())),
+    }
+}
+
+/// Frames carry screenshots, clients only get them when they ask for them.
+pub(crate) fn strip_images(event: &mut Event) {
+    if event.name == "ocr_result" || event.name == "ui_frame" {
+        if let Some(data) = event.data.as_object_mut() {
+            data.remove("image");
+        }
+    }
+}
+
+#[cfg(unix)]
+pub use unix::start_event_socket;
+
+#[cfg(unix)]
+mod unix {
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    use anyhow::Result;
+    use futures::StreamExt;
+    use screenpipe_events::{subscribe_to_all_events, Event, EventFilter};
+    use serde_json::json;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::{UnixListener, UnixStream};
+    use tokio::sync::{mpsc, watch};
+    use tokio::task::JoinHandle;
+    use tracing::{debug, error, info};
+
+    use super::{control_event, handle_client_message, strip_images, DEFAULT_SOCKET_QUEUE};
+
+    /// Listens on `path` until the task is aborted. A socket file left by a previous run is
+    /// replaced, one another screenpipe is listening on is an error. Only the user can
+    /// connect.
+    pub fn start_event_socket(path: impl AsRef<Path>) -> Result<JoinHandle<()>> {
+        let path = path.as_ref().to_path_buf();
+        if path.exists() {
+            if std::os::unix::net::UnixStream::connect(&path).is_ok() {
+                anyhow::bail!("{} is already in use", path.display());
+            }
+            std::fs::remove_file(&path)?;
+        }
+        // bound in a directory only the user can enter and moved in place once private, so
+        // nobody can connect between the bind and the chmod
+        let parent = path
+            .parent()
+            .filter(|p| !p.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        let staging = tempfile::Builder::new()
+            .prefix(".events-sock")
+            .tempdir_in(parent)?;
+        std::fs::set_permissions(staging.path(), std::fs::Permissions::from_mode(0o700))?;
+        let staged = staging.path().join("events.sock");
+        let listener = UnixListener::bind(&staged)?;
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o600))?;
+        std::fs::rename(&staged, &path)?;
+        info!("event socket listening on {}", path.display());
+
+        Ok(tokio::spawn(accept_connections(listener, path)))
+    }
+
+    async fn accept_connections(listener: UnixListener, path: PathBuf) {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(handle_connection(stream));
+                }
+                Err(e) => {
+                    error!("event socket {} stopped accepting: {}", path.display(), e);
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Bus events go through the connection filter into a bounded queue, the socket is
+    /// written from the queue, like `/ws/events` does.
+    async fn handle_connection(stream: UnixStream) {
+        let (reader, mut writer) = stream.into_split();
+        let mut events = subscribe_to_all_events();
+        let filter = EventFilter::new(Vec::new(), None).expect("an empty filter is valid");
+        let (filter_tx, filter_rx) = watch::channel(filter);
+        let (queue_tx, mut queue_rx) = mpsc::channel::<Event>(DEFAULT_SOCKET_QUEUE);
+        let dropped = Arc::new(AtomicU64::new(0));
+
+        let control_tx = queue_tx.clone();
+        let mut incoming = tokio::spawn(async move {
+            let mut lines = BufReader::new(reader).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let Some(reply) = handle_client_message(&line, &filter_tx, "socket") else {
+                    continue;
+                };
+                if control_tx.send(reply).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let forward_dropped = dropped.clone();
+        let forward = tokio::spawn(async move {
+            while let Some(mut event) = events.next().await {
+                forward_dropped.fetch_add(events.take_dropped(), Ordering::Relaxed);
+                if !filter_rx.borrow().matches(&event) {
+                    continue;
+                }
+                strip_images(&mut event);
+                match queue_tx.try_send(event) {
+                    Ok(()) => {}
+                    Err(mpsc::error::TrySendError::Full(_)) => {
+                        forward_dropped.fetch_add(1, Ordering::Relaxed);
+                    }
+                    Err(mpsc::error::TrySendError::Closed(_)) => break,
+                }
+            }
+        });
+
+        let mut outgoing = tokio::spawn(async move {
+            while let Some(event) = queue_rx.recv().await {
+                let lagged = dropped.swap(0, Ordering::Relaxed);
+                let notice = (lagged > 0)
+                    .then(|| control_event("socket_lagged", json!({"dropped": lagged})));
+                for event in notice.into_iter().chain(std::iter::once(event)) {
+                    let mut line = serde_json::to_vec(&event).unwrap_or_default();
+                    line.push(b'\n');
+                    if writer.write_all(&line).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+
+        tokio::select! {
+            _ = &mut incoming => {}
+            _ = &mut outgoing => {}
+        }
+        incoming.abort();
+        outgoing.abort();
+        forward.abort();
+
+        debug!("event socket connection closed");
+    }
+}
@@ -1,10 +1,36 @@
+use chrono::{DateTime, Utc};
 use ndarray::{Array2, Axis};
 use rust_stemmers::{Algorithm, Stemmer};
-use sqlx::{query_as, sqlite::SqlitePool};
 use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use tracing::debug;
 
+use crate::store::Store;
+use crate::DatabaseManager;
+
+impl DatabaseManager {
+    pub async fn get_chunked_texts_since(
+        &self,
+        since: DateTime<Utc>,
+        source: &str,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        sqlx::query_scalar(
+            "
+            SELECT cti.text
+            FROM chunked_text_index cti
+            JOIN chunked_text_entries cte ON cti.text_id = cte.text_id
+            WHERE cte.timestamp > ?1 AND cte.source = ?2
+            GROUP BY cti.text_id
+            ORDER BY MAX(cte.timestamp) DESC
+        ",
+        )
+        .bind(since)
+        .bind(source)
+        .fetch_all(&self.read_pool)
+        .await
+    }
+}
+
 fn keep_least_similar(chunks: &[String], percentage: f64) -> Vec<usize> {
     if chunks.is_empty() {
         return vec![];
@@ -71,27 +97,11 @@ fn word_count(text: &str) -> usize {
 }
 
 pub async fn filter_texts(
-    timestamp: &str,
+    since: DateTime<Utc>,
     memory_source: &str,
-    pool: &SqlitePool,
+    store: &dyn Store,
 ) -> Result<String, Box<dyn Error + Send + Sync>> {
-    let texts: Vec<String> = query_as(
-        "
-        SELECT cti.text
-        FROM chunked_text_index cti
-        JOIN chunked_text_entries cte ON cti.text_id = cte.text_id
-        WHERE cte.timestamp > ? AND cte.source = ?
-        GROUP BY cti.text_id
-        ORDER BY MAX(cte.timestamp) DESC
-    ",
-    )
-    .bind(timestamp)
-    .bind(memory_source)
-    .fetch_all(pool)
-    .await?
-    .into_iter()
-    .map(|row: (String,)| row.0)
-    .collect();
+    let texts = store.get_chunked_texts_since(since, memory_source).await?;
 
     let initial_text_count = texts.len();
     let initial_word_count: usize = texts.iter().map(|text| word_count(text)).sum();
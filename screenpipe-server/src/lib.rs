@@ -1,28 +1,51 @@
 mod auto_destruct;
 pub mod chunking;
 pub mod cli;
+pub mod conversations;
 pub mod core;
 pub mod db;
+pub mod db_maintenance;
 pub mod db_types;
+pub mod db_writer;
+pub mod event_socket;
 pub mod filtering;
 mod add;
+pub mod meetings;
+pub mod mqtt_bridge;
+pub mod pipe_dev;
 pub mod pipe_manager;
+pub mod pipe_supervisor;
+#[cfg(feature = "wasm")]
+pub mod pipe_wasm;
 mod plugin;
+#[cfg(feature = "postgres")]
+pub mod postgres;
 mod resource_monitor;
+pub mod rules;
+pub mod seed;
 mod server;
+pub mod storage;
+pub mod store;
 mod video;
 pub mod video_cache;
 mod video_db;
 pub mod video_utils;
+pub mod webhooks;
 pub mod text_embeds;
 
 pub use auto_destruct::watch_pid;
 pub use cli::Cli;
+pub use conversations::{ConversationConfig, ConversationSegmenter};
 pub use core::start_continuous_recording;
 pub use db::DatabaseManager;
+#[cfg(unix)]
+pub use event_socket::start_event_socket;
 pub use add::handle_index_command;
+pub use meetings::{MeetingDetectorConfig, MeetingTracker};
+pub use mqtt_bridge::{MqttBridge, MqttConfig};
 pub use pipe_manager::PipeManager;
 pub use resource_monitor::{ResourceMonitor, RestartSignal};
+pub use rules::{RuleEngine, RuleEngineConfig};
 pub use screenpipe_core::Language;
 pub use server::create_router;
 pub use server::health_check;
@@ -31,7 +54,14 @@ pub use server::ContentItem;
 pub use server::HealthCheckResponse;
 pub use server::PaginatedResponse;
 pub use server::Server;
-pub use video::VideoCapture;
+pub use storage::MediaStorage;
+pub use store::{
+    open_store, CaptureStore, ConversationStore, EmbeddingStore, MediaChunkStore, MeetingStore,
+    PipeCronStore, RuleStore, SearchStore, SpeakerStore, Store, StoreCronHistory, StoreUrl,
+    TagStore, TimelineStore, WebhookStore,
+};
+pub use video::{queue_frame, VideoCapture};
+pub use webhooks::{WebhookConfig, WebhookDispatcher};
 pub use axum::Json as JsonResponse;
 pub use server::{
     api_list_monitors,
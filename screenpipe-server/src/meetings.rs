@@ -0,0 +1,727 @@
+//! Meetings detected from what is on screen and what is heard, kept in the `meetings` table.
+//!
+//! The tracker reads newly indexed content like the rules engine does and feeds it to a
+//! [`MeetingDetector`]. A meeting starts on a meeting app showing call controls, or on a
+//! conversation: speech from an output device while the mic picks up speech too, or several
+//! speakers. It ends once its signals have been quiet for `idle_timeout`, or on an end
+//! phrase. Participants are the names shown in the meeting app, and the named speakers.
+//!
+//! Meetings can be corrected, merged and deleted through `/meetings`, the tracker keeps the
+//! corrections of the meeting in progress.
+
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use screenpipe_audio::DeviceType;
+use screenpipe_events::{send_event, MeetingEvent};
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::db_types::{ContentType, SearchKind, SearchResult};
+use crate::store::{content_between, Store};
+
+const MEETING_APPS: &[&str] = &[
+    "zoom", "teams", "meet", "webex", "skype", "slack", "facetime",
+];
+const MEETING_KEYWORDS: &[&str] = &[
+    "meeting",
+    "call",
+    "conference",
+    "joining",
+    "waiting room",
+    "lobby",
+    "participant",
+    "huddle",
+];
+const MEETING_CONTROLS: &[&str] = &[
+    "mute",
+    "unmute",
+    "camera",
+    "share screen",
+    "participants",
+    "leave",
+    "end call",
+];
+const MEETING_END_PHRASES: &[&str] = &[
+    "meeting ended",
+    "call ended",
+    "left the meeting",
+    "host has ended",
+    "meeting has ended",
+];
+/// Words of the meeting app ui that look like names.
+const NOT_NAMES: &[&str] = &[
+    "mute",
+    "unmute",
+    "camera",
+    "video",
+    "share",
+    "screen",
+    "participants",
+    "chat",
+    "recording",
+    "leave",
+    "end",
+    "meeting",
+    "call",
+    "view",
+    "raise",
+    "hand",
+    "reactions",
+    "more",
+    "settings",
+    "security",
+    "apps",
+    "waiting",
+    "room",
+    "join",
+    "people",
+    "stop",
+    "start",
+    "invite",
+];
+const NAME_SUFFIXES: &[&str] = &[
+    "(host)",
+    "(co-host)",
+    "(me)",
+    "(you)",
+    "(guest)",
+    "(external)",
+];
+/// A meeting doesn't start again right after one ended.
+const RESTART_TIMEOUT_SECS: i64 = 10;
+const MAX_PARTICIPANTS: usize = 50;
+
+/// What made the detector think a meeting was going on.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum MeetingSignal {
+    /// A meeting app with call controls on screen.
+    Screen,
+    /// Speech from an output device, other people talking.
+    OutputSpeech,
+    /// Speech on the mic.
+    MicActivity,
+    MultipleSpeakers,
+}
+
+/// A meeting as written to the store.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct MeetingSpec {
+    pub start_time: DateTime<Utc>,
+    /// None while the meeting is in progress.
+    #[serde(default)]
+    pub end_time: Option<DateTime<Utc>>,
+    pub app: String,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub participants: Vec<String>,
+    #[serde(default)]
+    pub signals: Vec<MeetingSignal>,
+}
+
+impl MeetingSpec {
+    pub fn validate(&self) -> Result<()> {
+        if self.app.trim().is_empty() {
+            anyhow::bail!("meeting has no app");
+        }
+        if self.end_time.is_some_and(|end| end < self.start_time) {
+            anyhow::bail!("meeting ends before it starts");
+        }
+        Ok(())
+    }
+}
+
+/// First and last id of the rows captured during a meeting.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct IdRange {
+    pub first: i64,
+    pub last: i64,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct Meeting {
+    pub id: i64,
+    #[serde(flatten)]
+    pub spec: MeetingSpec,
+    pub frames: Option<IdRange>,
+    pub transcriptions: Option<IdRange>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Corrections to a meeting, the fields left out are kept.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MeetingUpdate {
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    pub app: Option<String>,
+    pub title: Option<String>,
+    pub participants: Option<Vec<String>>,
+}
+
+impl MeetingUpdate {
+    pub fn apply(self, spec: &mut MeetingSpec) -> Result<()> {
+        if let Some(start_time) = self.start_time {
+            spec.start_time = start_time;
+        }
+        if let Some(end_time) = self.end_time {
+            spec.end_time = Some(end_time);
+        }
+        if let Some(app) = self.app {
+            spec.app = app;
+        }
+        if let Some(title) = self.title {
+            spec.title = Some(title).filter(|t| !t.is_empty());
+        }
+        if let Some(participants) = self.participants {
+            spec.participants = participants;
+        }
+        spec.validate()
+    }
+}
+
+/// Merges meetings into the earliest of them, which spans them all and gets their
+/// participants. None when one of them doesn't exist.
+pub async fn merge_meetings(store: &dyn Store, ids: &[i64]) -> Result<Option<Meeting>> {
+    let ids: BTreeSet<i64> = ids.iter().copied().collect();
+    if ids.len() < 2 {
+        anyhow::bail!("merging takes at least two meetings");
+    }
+    let mut meetings = Vec::new();
+    for id in &ids {
+        match store.get_meeting(*id).await? {
+            Some(meeting) => meetings.push(meeting),
+            None => return Ok(None),
+        }
+    }
+    meetings.sort_by_key(|m| m.spec.start_time);
+    let (first, rest) = meetings.split_first().expect("at least two meetings");
+    let mut spec = first.spec.clone();
+    for meeting in rest {
+        spec.end_time = match (spec.end_time, meeting.spec.end_time) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            // one still in progress
+            _ => None,
+        };
+        spec.title = spec.title.or_else(|| meeting.spec.title.clone());
+        merge_into(&mut spec.participants, &meeting.spec.participants);
+        merge_into(&mut spec.signals, &meeting.spec.signals);
+    }
+    store.update_meeting(first.id, &spec).await?;
+    for meeting in rest {
+        store.delete_meeting(meeting.id).await?;
+    }
+    Ok(store.get_meeting(first.id).await?)
+}
+
+fn merge_into<T: Clone + PartialEq>(into: &mut Vec<T>, from: &[T]) {
+    for item in from {
+        if !into.contains(item) {
+            into.push(item.clone());
+        }
+    }
+}
+
+/// Captured content, as far as meetings go.
+#[derive(Clone, Debug)]
+pub enum Observation {
+    Screen {
+        timestamp: DateTime<Utc>,
+        app: String,
+        window: String,
+        text: String,
+    },
+    Speech {
+        timestamp: DateTime<Utc>,
+        output: bool,
+        speaker_id: Option<i64>,
+        speaker_name: Option<String>,
+        text: String,
+    },
+}
+
+impl Observation {
+    pub fn from_result(result: &SearchResult) -> Self {
+        match result {
+            SearchResult::OCR(ocr) => Observation::Screen {
+                timestamp: ocr.timestamp,
+                app: ocr.app_name.clone(),
+                window: ocr.window_name.clone(),
+                text: ocr.ocr_text.clone(),
+            },
+            SearchResult::UI(ui) => Observation::Screen {
+                timestamp: ui.timestamp,
+                app: ui.app_name.clone(),
+                window: ui.window_name.clone(),
+                text: ui.text.clone(),
+            },
+            SearchResult::Audio(audio) => Observation::Speech {
+                timestamp: audio.timestamp,
+                output: audio.device_type == DeviceType::Output,
+                speaker_id: audio.speaker.as_ref().map(|s| s.id),
+                speaker_name: audio
+                    .speaker
+                    .as_ref()
+                    .map(|s| s.name.clone())
+                    .filter(|name| !name.trim().is_empty()),
+                text: audio.transcription.clone(),
+            },
+        }
+    }
+
+    pub fn timestamp(&self) -> DateTime<Utc> {
+        match self {
+            Observation::Screen { timestamp, .. } | Observation::Speech { timestamp, .. } => {
+                *timestamp
+            }
+        }
+    }
+
+    fn text(&self) -> &str {
+        match self {
+            Observation::Screen { text, .. } | Observation::Speech { text, .. } => text,
+        }
+    }
+
+    /// The meeting app on screen, if this is one.
+    fn meeting_app(&self) -> Option<&str> {
+        match self {
+            Observation::Screen { app, .. } => {
+                let lower = app.to_lowercase();
+                MEETING_APPS
+                    .iter()
+                    .any(|a| lower.contains(a))
+                    .then_some(app.as_str())
+            }
+            Observation::Speech { .. } => None,
+        }
+    }
+
+    fn signal(&self) -> Option<MeetingSignal> {
+        match self {
+            Observation::Screen { window, text, .. } => {
+                self.meeting_app()?;
+                let window = window.to_lowercase();
+                let text = text.to_lowercase();
+                (MEETING_KEYWORDS.iter().any(|k| window.contains(k))
+                    || MEETING_CONTROLS.iter().any(|c| text.contains(c)))
+                .then_some(MeetingSignal::Screen)
+            }
+            Observation::Speech { output, text, .. } if !text.trim().is_empty() => {
+                Some(if *output {
+                    MeetingSignal::OutputSpeech
+                } else {
+                    MeetingSignal::MicActivity
+                })
+            }
+            Observation::Speech { .. } => None,
+        }
+    }
+}
+
+/// Names shown in a meeting app, e.g. on the video tiles or in the participants list.
+pub fn screen_names(text: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    for line in text.lines() {
+        let mut line = line.trim();
+        for suffix in NAME_SUFFIXES {
+            let at = line.len().saturating_sub(suffix.len());
+            if line.is_char_boundary(at) && line[at..].eq_ignore_ascii_case(suffix) {
+                line = line[..at].trim_end();
+            }
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let looks_like_name = (2..=3).contains(&words.len())
+            && line.len() <= 40
+            && words.iter().all(|word| {
+                let mut chars = word.chars();
+                chars.next().is_some_and(|c| c.is_uppercase())
+                    && chars.all(|c| c.is_lowercase() || c == '-' || c == '\'')
+                    && !NOT_NAMES.contains(&word.to_lowercase().as_str())
+            });
+        if looks_like_name && !names.iter().any(|n| n == line) {
+            names.push(line.to_string());
+        }
+    }
+    names
+}
+
+/// What the detector found, to write to the store.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MeetingChange {
+    Started(MeetingSpec),
+    /// New participants or signals, or just more of the meeting.
+    Updated(MeetingSpec),
+    Ended(MeetingSpec),
+}
+
+#[derive(Clone, Debug)]
+pub struct MeetingDetectorConfig {
+    /// How often newly indexed content is read.
+    pub poll_interval: Duration,
+    /// How far back content is read, indexing lags behind capture.
+    pub lookback: Duration,
+    /// Signals this close together make a meeting.
+    pub signal_window: Duration,
+    /// A meeting ends once its signals have been quiet this long.
+    pub idle_timeout: Duration,
+}
+
+impl Default for MeetingDetectorConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(5),
+            lookback: Duration::from_secs(120),
+            signal_window: Duration::from_secs(120),
+            idle_timeout: Duration::from_secs(180),
+        }
+    }
+}
+
+struct OpenMeeting {
+    spec: MeetingSpec,
+    last_activity: DateTime<Utc>,
+    /// Frames of the meeting app each name was seen in.
+    names: HashMap<String, usize>,
+    frames: usize,
+    speakers: HashSet<i64>,
+    speaker_names: BTreeSet<String>,
+    changed: bool,
+}
+
+impl OpenMeeting {
+    /// Only signals keep the meeting going, the meeting app open after a call doesn't.
+    fn observe(&mut self, observation: &Observation, signal: Option<MeetingSignal>) {
+        self.changed = true;
+        if let Some(signal) = signal {
+            self.last_activity = self.last_activity.max(observation.timestamp());
+            merge_into(&mut self.spec.signals, &[signal]);
+        }
+        match observation {
+            Observation::Screen { window, text, .. } => {
+                self.frames += 1;
+                for name in screen_names(text) {
+                    *self.names.entry(name).or_default() += 1;
+                }
+                if self.spec.title.is_none() && !window.trim().is_empty() {
+                    self.spec.title = Some(window.clone());
+                }
+            }
+            Observation::Speech {
+                speaker_id,
+                speaker_name,
+                ..
+            } => {
+                self.speakers.extend(*speaker_id);
+                self.speaker_names.extend(speaker_name.clone());
+                if self.speakers.len() >= 2 {
+                    merge_into(&mut self.spec.signals, &[MeetingSignal::MultipleSpeakers]);
+                }
+            }
+        }
+        self.spec.participants = self.participants();
+    }
+
+    /// Names seen in a couple of frames at least, ocr misreads rarely happen twice.
+    fn participants(&self) -> Vec<String> {
+        let min_frames = self.frames.min(2);
+        let mut names: Vec<(&String, &usize)> = self
+            .names
+            .iter()
+            .filter(|(_, frames)| **frames >= min_frames)
+            .collect();
+        names.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        let mut participants: Vec<String> = names.into_iter().map(|(n, _)| n.clone()).collect();
+        merge_into(
+            &mut participants,
+            &self.speaker_names.iter().cloned().collect::<Vec<_>>(),
+        );
+        participants.truncate(MAX_PARTICIPANTS);
+        participants
+    }
+}
+
+/// Turns observations, in about the order they were captured, into meetings.
+pub struct MeetingDetector {
+    config: MeetingDetectorConfig,
+    /// Signals of the signal window, while no meeting is in progress.
+    recent: VecDeque<(Observation, MeetingSignal)>,
+    current: Option<OpenMeeting>,
+    last_end: Option<DateTime<Utc>>,
+}
+
+impl MeetingDetector {
+    pub fn new(config: MeetingDetectorConfig) -> Self {
+        Self {
+            config,
+            recent: VecDeque::new(),
+            current: None,
+            last_end: None,
+        }
+    }
+
+    pub fn in_progress(&self) -> bool {
+        self.current.is_some()
+    }
+
+    pub fn observe(&mut self, observation: &Observation) -> Vec<MeetingChange> {
+        let timestamp = observation.timestamp();
+        let mut changes: Vec<MeetingChange> = self.end_idle(timestamp).into_iter().collect();
+        let signal = observation.signal();
+
+        if let Some(meeting) = &mut self.current {
+            let text = observation.text().to_lowercase();
+            if MEETING_END_PHRASES.iter().any(|p| text.contains(p)) {
+                meeting.last_activity = meeting.last_activity.max(timestamp);
+                changes.extend(self.end());
+                return changes;
+            }
+            let same_app = observation
+                .meeting_app()
+                .is_some_and(|app| app == meeting.spec.app);
+            if signal.is_some() || same_app {
+                meeting.observe(observation, signal);
+            }
+            return changes;
+        }
+
+        let Some(signal) = signal else {
+            return changes;
+        };
+        if self
+            .last_end
+            .is_some_and(|end| (timestamp - end).num_seconds() < RESTART_TIMEOUT_SECS)
+        {
+            return changes;
+        }
+        self.recent.push_back((observation.clone(), signal));
+        let window = chrono::Duration::from_std(self.config.signal_window)
+            .unwrap_or_else(|_| chrono::Duration::minutes(2));
+        self.recent
+            .retain(|(o, _)| (timestamp - o.timestamp()).abs() <= window);
+        changes.extend(self.start());
+        changes
+    }
+
+    /// Ends the meeting once nothing happened in it for `idle_timeout` before `now`, and
+    /// reports what changed in it since the last check.
+    pub fn tick(&mut self, now: DateTime<Utc>) -> Vec<MeetingChange> {
+        if let Some(ended) = self.end_idle(now) {
+            return vec![ended];
+        }
+        match &mut self.current {
+            Some(meeting) if meeting.changed => {
+                meeting.changed = false;
+                vec![MeetingChange::Updated(meeting.spec.clone())]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn end_idle(&mut self, now: DateTime<Utc>) -> Option<MeetingChange> {
+        let idle = chrono::Duration::from_std(self.config.idle_timeout)
+            .unwrap_or_else(|_| chrono::Duration::minutes(3));
+        match &self.current {
+            Some(meeting) if now - meeting.last_activity > idle => self.end(),
+            _ => None,
+        }
+    }
+
+    fn end(&mut self) -> Option<MeetingChange> {
+        let mut meeting = self.current.take()?;
+        meeting.spec.end_time = Some(meeting.last_activity.max(meeting.spec.start_time));
+        self.last_end = meeting.spec.end_time;
+        Some(MeetingChange::Ended(meeting.spec))
+    }
+
+    /// Starts a meeting when the recent signals add up to one.
+    fn start(&mut self) -> Option<MeetingChange> {
+        let signals: HashSet<MeetingSignal> = self.recent.iter().map(|(_, s)| *s).collect();
+        let speakers: HashSet<i64> = self
+            .recent
+            .iter()
+            .filter_map(|(o, _)| match o {
+                Observation::Speech { speaker_id, .. } => *speaker_id,
+                _ => None,
+            })
+            .collect();
+        let conversation = signals.contains(&MeetingSignal::OutputSpeech)
+            && signals.contains(&MeetingSignal::MicActivity);
+        if !signals.contains(&MeetingSignal::Screen) && !conversation && speakers.len() < 2 {
+            return None;
+        }
+
+        let recent = std::mem::take(&mut self.recent);
+        let start_time = recent.iter().map(|(o, _)| o.timestamp()).min()?;
+        let app = recent
+            .iter()
+            .find_map(|(o, s)| {
+                (*s == MeetingSignal::Screen)
+                    .then(|| o.meeting_app())
+                    .flatten()
+            })
+            .unwrap_or("audio")
+            .to_string();
+        let mut meeting = OpenMeeting {
+            spec: MeetingSpec {
+                start_time,
+                end_time: None,
+                app,
+                title: None,
+                participants: Vec::new(),
+                signals: Vec::new(),
+            },
+            last_activity: start_time,
+            names: HashMap::new(),
+            frames: 0,
+            speakers: HashSet::new(),
+            speaker_names: BTreeSet::new(),
+            changed: false,
+        };
+        for (observation, signal) in &recent {
+            meeting.observe(observation, Some(*signal));
+        }
+        meeting.spec.signals.sort();
+        meeting.changed = false;
+        let spec = meeting.spec.clone();
+        self.current = Some(meeting);
+        Some(MeetingChange::Started(spec))
+    }
+}
+
+/// Runs the detector on newly indexed content and keeps the `meetings` table up to date.
+pub struct MeetingTracker {
+    store: Arc<dyn Store>,
+    config: MeetingDetectorConfig,
+}
+
+impl MeetingTracker {
+    pub fn new(store: Arc<dyn Store>, config: MeetingDetectorConfig) -> Self {
+        Self { store, config }
+    }
+
+    /// Tracks meetings until the task is aborted. Content captured before the tracker
+    /// started is left alone, meetings a previous run left open are closed.
+    pub fn start(self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            if let Err(e) = self.close_open_meetings().await {
+                warn!("failed to close meetings left open: {}", e);
+            }
+            let started = Utc::now();
+            let lookback = chrono::Duration::from_std(self.config.lookback)
+                .unwrap_or_else(|_| chrono::Duration::minutes(2));
+            let mut detector = MeetingDetector::new(self.config.clone());
+            let mut current_id = None;
+            let mut seen: HashMap<(SearchKind, i64), DateTime<Utc>> = HashMap::new();
+            let mut poll = tokio::time::interval(self.config.poll_interval);
+
+            loop {
+                poll.tick().await;
+                let now = Utc::now();
+                let start = (now - lookback).max(started);
+                let results = match content_between(
+                    self.store.as_ref(),
+                    ContentType::All,
+                    start,
+                    now,
+                )
+                .await
+                {
+                    Ok(results) => results,
+                    Err(e) => {
+                        warn!("failed to read new content for meetings: {}", e);
+                        continue;
+                    }
+                };
+                let mut changes = Vec::new();
+                for result in results {
+                    let cursor = result.cursor();
+                    if seen
+                        .insert((cursor.kind, cursor.id), cursor.timestamp)
+                        .is_some()
+                    {
+                        continue;
+                    }
+                    changes.extend(detector.observe(&Observation::from_result(&result)));
+                }
+                changes.extend(detector.tick(now));
+                for change in changes {
+                    if let Err(e) = self.apply(change, &mut current_id).await {
+                        warn!("failed to save meeting: {}", e);
+                    }
+                }
+                // older content isn't read again
+                seen.retain(|_, timestamp| *timestamp >= start);
+            }
+        })
+    }
+
+    async fn close_open_meetings(&self) -> Result<()> {
+        for meeting in self.store.list_meetings(None, None, 100, 0).await? {
+            if meeting.spec.end_time.is_none() {
+                let mut spec = meeting.spec;
+                spec.end_time = Some(meeting.updated_at.max(spec.start_time));
+                self.store.update_meeting(meeting.id, &spec).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn apply(&self, change: MeetingChange, current_id: &mut Option<i64>) -> Result<()> {
+        let (detected, ended) = match change {
+            MeetingChange::Started(spec) => {
+                let id = self.store.insert_meeting(&spec).await?;
+                *current_id = Some(id);
+                info!("meeting {} started in {}", id, spec.app);
+                send_event(
+                    "meeting_started",
+                    MeetingEvent {
+                        app: spec.app,
+                        timestamp: spec.start_time,
+                    },
+                )?;
+                return Ok(());
+            }
+            MeetingChange::Updated(spec) => (spec, false),
+            MeetingChange::Ended(spec) => (spec, true),
+        };
+        let Some(id) = *current_id else {
+            return Ok(());
+        };
+        if ended {
+            *current_id = None;
+        }
+        // corrections made through /meetings are kept, a deleted meeting stays deleted
+        let Some(meeting) = self.store.get_meeting(id).await? else {
+            debug!("meeting {} was deleted, not tracking it", id);
+            *current_id = None;
+            return Ok(());
+        };
+        let mut spec = meeting.spec;
+        spec.end_time = spec
+            .end_time
+            .or(detected.end_time.map(|end| end.max(spec.start_time)));
+        spec.title = spec.title.or(detected.title);
+        merge_into(&mut spec.participants, &detected.participants);
+        merge_into(&mut spec.signals, &detected.signals);
+        self.store.update_meeting(id, &spec).await?;
+        if ended {
+            info!("meeting {} ended", id);
+            send_event(
+                "meeting_ended",
+                MeetingEvent {
+                    app: spec.app,
+                    timestamp: spec.end_time.unwrap_or_else(Utc::now),
+                },
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,9 @@
+-- Track media chunks that were moved to a remote object store.
+-- file_path keeps the original local path so the db stays the index.
+ALTER TABLE video_chunks ADD COLUMN remote_key TEXT;
+ALTER TABLE video_chunks ADD COLUMN offloaded_at TIMESTAMP;
+ALTER TABLE audio_chunks ADD COLUMN remote_key TEXT;
+ALTER TABLE audio_chunks ADD COLUMN offloaded_at TIMESTAMP;
+
+CREATE INDEX IF NOT EXISTS idx_video_chunks_file_path ON video_chunks (file_path);
+CREATE INDEX IF NOT EXISTS idx_audio_chunks_file_path ON audio_chunks (file_path);
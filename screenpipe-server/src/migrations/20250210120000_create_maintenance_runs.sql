@@ -0,0 +1,11 @@
+-- History of scheduled database maintenance (optimize, analyze, fts merge, vacuum, checkpoint)
+CREATE TABLE IF NOT EXISTS maintenance_runs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    task TEXT NOT NULL,
+    started_at TIMESTAMP NOT NULL,
+    duration_ms INTEGER NOT NULL,
+    success BOOLEAN NOT NULL,
+    details TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_maintenance_runs_task_started_at ON maintenance_runs (task, started_at);
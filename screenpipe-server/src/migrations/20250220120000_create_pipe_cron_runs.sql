@@ -0,0 +1,13 @@
+-- History of pipe cron jobs, scheduled or run by hand
+CREATE TABLE IF NOT EXISTS pipe_cron_runs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    pipe_id TEXT NOT NULL,
+    job TEXT NOT NULL,
+    trigger TEXT NOT NULL,
+    started_at TIMESTAMP NOT NULL,
+    ended_at TIMESTAMP NOT NULL,
+    status TEXT NOT NULL,
+    output TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_pipe_cron_runs_pipe_id_started_at ON pipe_cron_runs (pipe_id, started_at);
@@ -0,0 +1,20 @@
+-- Meetings found by the meeting detector or corrected through /meetings. `participants` and
+-- `signals` are json arrays, the frame and transcription ids are the content captured
+-- between `start_time` and `end_time`
+CREATE TABLE IF NOT EXISTS meetings (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    start_time TIMESTAMP NOT NULL,
+    end_time TIMESTAMP,
+    app TEXT NOT NULL,
+    title TEXT,
+    participants TEXT NOT NULL DEFAULT '[]',
+    signals TEXT NOT NULL DEFAULT '[]',
+    first_frame_id INTEGER,
+    last_frame_id INTEGER,
+    first_transcription_id INTEGER,
+    last_transcription_id INTEGER,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_meetings_start_time ON meetings (start_time);
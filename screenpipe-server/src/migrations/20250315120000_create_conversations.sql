@@ -0,0 +1,60 @@
+-- Conversations segmented from the audio transcriptions of all devices. `devices` and
+-- `speakers` are json arrays, `transcript` is the deduplicated text with one line per
+-- speaker turn
+CREATE TABLE IF NOT EXISTS conversations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    start_time TIMESTAMP NOT NULL,
+    end_time TIMESTAMP NOT NULL,
+    devices TEXT NOT NULL DEFAULT '[]',
+    speakers TEXT NOT NULL DEFAULT '[]',
+    transcript TEXT NOT NULL,
+    turn_count INTEGER NOT NULL,
+    first_transcription_id INTEGER,
+    last_transcription_id INTEGER,
+    created_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_conversations_start_time ON conversations (start_time);
+
+-- Consecutive transcriptions of one speaker, `transcription_ids` is a json array
+CREATE TABLE IF NOT EXISTS conversation_turns (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    conversation_id INTEGER NOT NULL REFERENCES conversations(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    start_time TIMESTAMP NOT NULL,
+    end_time TIMESTAMP NOT NULL,
+    speaker_id INTEGER,
+    speaker_name TEXT,
+    device TEXT NOT NULL,
+    is_output_device BOOLEAN NOT NULL,
+    text TEXT NOT NULL,
+    transcription_ids TEXT NOT NULL DEFAULT '[]'
+);
+
+CREATE INDEX IF NOT EXISTS idx_conversation_turns_conversation_id ON conversation_turns (conversation_id, position);
+
+-- The conversation each transcription went into, `duplicate` when its text was already
+-- captured by another device
+CREATE TABLE IF NOT EXISTS conversation_transcriptions (
+    transcription_id INTEGER PRIMARY KEY,
+    conversation_id INTEGER NOT NULL REFERENCES conversations(id) ON DELETE CASCADE,
+    duplicate BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE INDEX IF NOT EXISTS idx_conversation_transcriptions_conversation_id ON conversation_transcriptions (conversation_id);
+
+CREATE VIRTUAL TABLE IF NOT EXISTS conversations_fts USING fts5(
+    transcript,
+    conversation_id UNINDEXED,
+    tokenize='unicode61'
+);
+
+CREATE TRIGGER IF NOT EXISTS conversations_ai AFTER INSERT ON conversations
+BEGIN
+    INSERT INTO conversations_fts(transcript, conversation_id) VALUES (NEW.transcript, NEW.id);
+END;
+
+CREATE TRIGGER IF NOT EXISTS conversations_ad AFTER DELETE ON conversations
+BEGIN
+    DELETE FROM conversations_fts WHERE conversation_id = OLD.id;
+END;
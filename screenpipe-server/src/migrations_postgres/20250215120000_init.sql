@@ -0,0 +1,150 @@
+-- Schema for the shared PostgreSQL index, mirrors the sqlite schema in ../migrations.
+-- Full-text search uses generated tsvector columns, embeddings use pgvector.
+CREATE EXTENSION IF NOT EXISTS vector;
+
+CREATE TABLE IF NOT EXISTS video_chunks (
+    id BIGSERIAL PRIMARY KEY,
+    file_path TEXT NOT NULL,
+    device_name TEXT NOT NULL DEFAULT '',
+    remote_key TEXT,
+    offloaded_at TIMESTAMPTZ
+);
+CREATE INDEX IF NOT EXISTS idx_video_chunks_device_name ON video_chunks(device_name, id);
+CREATE INDEX IF NOT EXISTS idx_video_chunks_file_path ON video_chunks(file_path);
+
+CREATE TABLE IF NOT EXISTS frames (
+    id BIGSERIAL PRIMARY KEY,
+    video_chunk_id BIGINT NOT NULL REFERENCES video_chunks(id) ON DELETE CASCADE,
+    offset_index BIGINT NOT NULL,
+    timestamp TIMESTAMPTZ NOT NULL,
+    name TEXT
+);
+CREATE INDEX IF NOT EXISTS idx_frames_video_chunk_id ON frames(video_chunk_id, offset_index);
+CREATE INDEX IF NOT EXISTS idx_frames_timestamp ON frames(timestamp);
+
+CREATE TABLE IF NOT EXISTS ocr_text (
+    id BIGSERIAL PRIMARY KEY,
+    frame_id BIGINT NOT NULL REFERENCES frames(id) ON DELETE CASCADE,
+    text TEXT NOT NULL,
+    text_json TEXT NOT NULL DEFAULT '',
+    app_name TEXT NOT NULL DEFAULT '',
+    window_name TEXT NOT NULL DEFAULT '',
+    ocr_engine TEXT NOT NULL DEFAULT 'unknown',
+    focused BOOLEAN NOT NULL DEFAULT FALSE,
+    text_length BIGINT,
+    search_vector TSVECTOR GENERATED ALWAYS AS (
+        to_tsvector('simple', text || ' ' || app_name || ' ' || window_name)
+    ) STORED
+);
+CREATE INDEX IF NOT EXISTS idx_ocr_text_frame_id ON ocr_text(frame_id);
+CREATE INDEX IF NOT EXISTS idx_ocr_text_search_vector ON ocr_text USING GIN (search_vector);
+
+CREATE TABLE IF NOT EXISTS audio_chunks (
+    id BIGSERIAL PRIMARY KEY,
+    file_path TEXT NOT NULL,
+    timestamp TIMESTAMPTZ NOT NULL DEFAULT now(),
+    remote_key TEXT,
+    offloaded_at TIMESTAMPTZ
+);
+CREATE INDEX IF NOT EXISTS idx_audio_chunks_file_path ON audio_chunks(file_path);
+CREATE INDEX IF NOT EXISTS idx_audio_chunks_timestamp ON audio_chunks(timestamp);
+
+CREATE TABLE IF NOT EXISTS speakers (
+    id BIGSERIAL PRIMARY KEY,
+    name TEXT NOT NULL DEFAULT '',
+    metadata TEXT NOT NULL DEFAULT '',
+    hallucination BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE TABLE IF NOT EXISTS speaker_embeddings (
+    id BIGSERIAL PRIMARY KEY,
+    embedding vector(512) NOT NULL,
+    speaker_id BIGINT REFERENCES speakers(id) ON DELETE CASCADE
+);
+CREATE INDEX IF NOT EXISTS idx_speaker_embeddings_speaker_id ON speaker_embeddings(speaker_id);
+CREATE INDEX IF NOT EXISTS idx_speaker_embeddings_embedding
+    ON speaker_embeddings USING hnsw (embedding vector_cosine_ops);
+
+CREATE TABLE IF NOT EXISTS audio_transcriptions (
+    id BIGSERIAL PRIMARY KEY,
+    audio_chunk_id BIGINT NOT NULL REFERENCES audio_chunks(id) ON DELETE CASCADE,
+    offset_index BIGINT NOT NULL,
+    timestamp TIMESTAMPTZ NOT NULL,
+    transcription TEXT NOT NULL,
+    device TEXT NOT NULL DEFAULT '',
+    is_input_device BOOLEAN NOT NULL DEFAULT TRUE,
+    speaker_id BIGINT REFERENCES speakers(id) ON DELETE SET NULL,
+    transcription_engine TEXT NOT NULL DEFAULT 'Whisper',
+    start_time DOUBLE PRECISION,
+    end_time DOUBLE PRECISION,
+    text_length BIGINT,
+    search_vector TSVECTOR GENERATED ALWAYS AS (
+        to_tsvector('simple', transcription || ' ' || device)
+    ) STORED
+);
+CREATE INDEX IF NOT EXISTS idx_audio_transcriptions_audio_chunk_id ON audio_transcriptions(audio_chunk_id, timestamp);
+CREATE INDEX IF NOT EXISTS idx_audio_transcriptions_timestamp ON audio_transcriptions(timestamp);
+CREATE INDEX IF NOT EXISTS idx_audio_transcriptions_speaker_id ON audio_transcriptions(speaker_id, timestamp);
+CREATE INDEX IF NOT EXISTS idx_audio_transcriptions_search_vector ON audio_transcriptions USING GIN (search_vector);
+
+CREATE TABLE IF NOT EXISTS ui_monitoring (
+    id BIGSERIAL PRIMARY KEY,
+    text_output TEXT NOT NULL,
+    timestamp TIMESTAMPTZ NOT NULL DEFAULT now(),
+    app TEXT NOT NULL,
+    "window" TEXT NOT NULL,
+    initial_traversal_at TIMESTAMPTZ,
+    text_length BIGINT,
+    search_vector TSVECTOR GENERATED ALWAYS AS (
+        to_tsvector('simple', text_output || ' ' || app || ' ' || "window")
+    ) STORED
+);
+CREATE INDEX IF NOT EXISTS idx_ui_monitoring_timestamp ON ui_monitoring(timestamp);
+CREATE INDEX IF NOT EXISTS idx_ui_monitoring_search_vector ON ui_monitoring USING GIN (search_vector);
+
+CREATE TABLE IF NOT EXISTS tags (
+    id BIGSERIAL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS vision_tags (
+    vision_id BIGINT NOT NULL REFERENCES frames(id) ON DELETE CASCADE,
+    tag_id BIGINT NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+    PRIMARY KEY (vision_id, tag_id)
+);
+CREATE INDEX IF NOT EXISTS idx_vision_tags_tag_id ON vision_tags(tag_id);
+
+CREATE TABLE IF NOT EXISTS audio_tags (
+    audio_chunk_id BIGINT NOT NULL REFERENCES audio_chunks(id) ON DELETE CASCADE,
+    tag_id BIGINT NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+    PRIMARY KEY (audio_chunk_id, tag_id)
+);
+CREATE INDEX IF NOT EXISTS idx_audio_tags_tag_id ON audio_tags(tag_id);
+
+CREATE TABLE IF NOT EXISTS ui_monitoring_tags (
+    ui_monitoring_id BIGINT NOT NULL REFERENCES ui_monitoring(id) ON DELETE CASCADE,
+    tag_id BIGINT NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+    PRIMARY KEY (ui_monitoring_id, tag_id)
+);
+
+-- dimension depends on the embedding model, so it is left open
+CREATE TABLE IF NOT EXISTS ocr_text_embeddings (
+    id BIGSERIAL PRIMARY KEY,
+    frame_id BIGINT NOT NULL REFERENCES frames(id) ON DELETE CASCADE,
+    embedding vector NOT NULL,
+    created_at TIMESTAMPTZ DEFAULT now()
+);
+CREATE INDEX IF NOT EXISTS idx_ocr_text_embeddings_frame_id ON ocr_text_embeddings(frame_id);
+
+-- speaker metadata is free-form text that is usually, but not always, a json object
+CREATE OR REPLACE FUNCTION screenpipe_json_object(input TEXT) RETURNS JSONB AS $$
+BEGIN
+    IF jsonb_typeof(input::jsonb) = 'object' THEN
+        RETURN input::jsonb;
+    END IF;
+    RETURN '{}'::jsonb;
+EXCEPTION WHEN others THEN
+    RETURN '{}'::jsonb;
+END;
+$$ LANGUAGE plpgsql IMMUTABLE;
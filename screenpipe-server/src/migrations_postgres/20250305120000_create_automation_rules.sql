@@ -0,0 +1,7 @@
+-- Automation rules, `definition` is the rule as json
+CREATE TABLE IF NOT EXISTS automation_rules (
+    id BIGSERIAL PRIMARY KEY,
+    definition TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,20 @@
+-- Meetings found by the meeting detector or corrected through /meetings. `participants` and
+-- `signals` are json arrays, the frame and transcription ids are the content captured
+-- between `start_time` and `end_time`
+CREATE TABLE IF NOT EXISTS meetings (
+    id BIGSERIAL PRIMARY KEY,
+    start_time TIMESTAMPTZ NOT NULL,
+    end_time TIMESTAMPTZ,
+    app TEXT NOT NULL,
+    title TEXT,
+    participants TEXT NOT NULL DEFAULT '[]',
+    signals TEXT NOT NULL DEFAULT '[]',
+    first_frame_id BIGINT,
+    last_frame_id BIGINT,
+    first_transcription_id BIGINT,
+    last_transcription_id BIGINT,
+    created_at TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_meetings_start_time ON meetings (start_time);
@@ -0,0 +1,48 @@
+-- Conversations segmented from the audio transcriptions of all devices. `devices` and
+-- `speakers` are json arrays, `transcript` is the deduplicated text with one line per
+-- speaker turn
+CREATE TABLE IF NOT EXISTS conversations (
+    id BIGSERIAL PRIMARY KEY,
+    start_time TIMESTAMPTZ NOT NULL,
+    end_time TIMESTAMPTZ NOT NULL,
+    devices TEXT NOT NULL DEFAULT '[]',
+    speakers TEXT NOT NULL DEFAULT '[]',
+    transcript TEXT NOT NULL,
+    turn_count BIGINT NOT NULL,
+    first_transcription_id BIGINT,
+    last_transcription_id BIGINT,
+    created_at TIMESTAMPTZ NOT NULL,
+    search_vector TSVECTOR GENERATED ALWAYS AS (
+        to_tsvector('simple', transcript)
+    ) STORED
+);
+
+CREATE INDEX IF NOT EXISTS idx_conversations_start_time ON conversations (start_time);
+CREATE INDEX IF NOT EXISTS idx_conversations_search_vector ON conversations USING GIN (search_vector);
+
+-- Consecutive transcriptions of one speaker, `transcription_ids` is a json array
+CREATE TABLE IF NOT EXISTS conversation_turns (
+    id BIGSERIAL PRIMARY KEY,
+    conversation_id BIGINT NOT NULL REFERENCES conversations(id) ON DELETE CASCADE,
+    position BIGINT NOT NULL,
+    start_time TIMESTAMPTZ NOT NULL,
+    end_time TIMESTAMPTZ NOT NULL,
+    speaker_id BIGINT,
+    speaker_name TEXT,
+    device TEXT NOT NULL,
+    is_output_device BOOLEAN NOT NULL,
+    text TEXT NOT NULL,
+    transcription_ids TEXT NOT NULL DEFAULT '[]'
+);
+
+CREATE INDEX IF NOT EXISTS idx_conversation_turns_conversation_id ON conversation_turns (conversation_id, position);
+
+-- The conversation each transcription went into, `duplicate` when its text was already
+-- captured by another device
+CREATE TABLE IF NOT EXISTS conversation_transcriptions (
+    transcription_id BIGINT PRIMARY KEY,
+    conversation_id BIGINT NOT NULL REFERENCES conversations(id) ON DELETE CASCADE,
+    duplicate BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE INDEX IF NOT EXISTS idx_conversation_transcriptions_conversation_id ON conversation_transcriptions (conversation_id);
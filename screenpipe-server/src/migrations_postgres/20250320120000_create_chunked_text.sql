@@ -0,0 +1,18 @@
+-- Chunked text of frames and transcriptions, read by `filtering::filter_texts`, same as the
+-- sqlite tables
+CREATE TABLE IF NOT EXISTS chunked_text_index (
+    text_id BIGSERIAL PRIMARY KEY,
+    text TEXT NOT NULL UNIQUE
+);
+
+CREATE TABLE IF NOT EXISTS chunked_text_entries (
+    text_id BIGINT NOT NULL REFERENCES chunked_text_index(text_id) ON DELETE CASCADE,
+    frame_id BIGINT REFERENCES frames(id) ON DELETE SET NULL,
+    audio_chunk_id BIGINT REFERENCES audio_chunks(id) ON DELETE SET NULL,
+    timestamp TIMESTAMPTZ NOT NULL,
+    engine TEXT NOT NULL,
+    chunking_engine TEXT NOT NULL,
+    source TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_chunked_text_entries_source_timestamp ON chunked_text_entries (source, timestamp);
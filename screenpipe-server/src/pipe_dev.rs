@@ -0,0 +1,327 @@
+//! `screenpipe pipe dev`: runs a pipe straight from its working directory and restarts it
+//! when its files change. Next.js pipes run their dev server, which reloads by itself, and
+//! only restart when `pipe.json` or `package.json` change.
+//!
+//! The pipe is linked into a throwaway screenpipe directory, so it runs like an installed
+//! pipe (sandbox, crons, logs, its secrets copied from the real directory) without touching
+//! the installed ones. It talks to the live server through `SCREENPIPE_SERVER_URL`, or to a
+//! fixture server of its own serving a throwaway database seeded with sample activity.
+
+use std::future::Future;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::Utc;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use screenpipe_audio::{AudioDevice, DeviceType};
+use screenpipe_core::{
+    get_pipe_secrets, set_pipe_secret, PipeManifest, PipeRunOptions, PIPE_LOG_DIR,
+};
+use screenpipe_vision::OcrEngine;
+use serde_json::Value;
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+
+use crate::db_types::TagContentType;
+use crate::pipe_manager::PipeHost;
+use crate::{DatabaseManager, MediaStorage, PipeManager, Server, Store};
+
+/// Changes closer together than this restart the pipe once.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+/// Written by installs, builds and the pipe itself, never a reason to restart.
+const IGNORED: &[&str] = &[
+    "node_modules",
+    ".next",
+    ".venv",
+    "__pycache__",
+    "target",
+    PIPE_LOG_DIR,
+    "bun.lockb",
+    "bun.lock",
+    "package-lock.json",
+    "deno.lock",
+];
+
+pub struct PipeDevOptions {
+    /// Working directory of the pipe.
+    pub path: PathBuf,
+    /// Screenpipe directory the secrets of the pipe are read from.
+    pub screenpipe_dir: PathBuf,
+    /// Server the pipe talks to, unless `fixtures_port` is set.
+    pub server_url: String,
+    /// Serve a seeded throwaway database on this port and point the pipe at it instead.
+    pub fixtures_port: Option<u16>,
+}
+
+/// Runs the pipe of `options.path` until `shutdown` completes.
+pub async fn run_pipe_dev(
+    options: PipeDevOptions,
+    shutdown: impl Future<Output = ()>,
+) -> Result<()> {
+    let path = tokio::fs::canonicalize(&options.path)
+        .await
+        .map_err(|e| anyhow::anyhow!("can't open {:?}: {}", options.path, e))?;
+    let id = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .ok_or_else(|| anyhow::anyhow!("{:?} is not a pipe directory", path))?;
+    // a broken pipe.json is reported before anything starts
+    PipeManifest::load_from_dir(&path).await?;
+    let is_nextjs = is_nextjs(&path).await;
+
+    let dev_dir = tempfile::Builder::new()
+        .prefix("screenpipe-dev-")
+        .tempdir()?;
+    let pipes_dir = dev_dir.path().join("pipes");
+    tokio::fs::create_dir_all(&pipes_dir).await?;
+    link_dir(&path, &pipes_dir.join(&id))?;
+    for (name, value) in get_pipe_secrets(&options.screenpipe_dir, &id).await? {
+        set_pipe_secret(dev_dir.path(), &id, &name, &value).await?;
+    }
+
+    let server_url = match options.fixtures_port {
+        Some(port) => format!("http://localhost:{}", port),
+        None => options.server_url,
+    };
+    let pipe_manager = Arc::new(
+        PipeManager::new(dev_dir.path().to_path_buf()).with_run_options(PipeRunOptions {
+            dev: true,
+            env: vec![("SCREENPIPE_SERVER_URL".to_string(), server_url.clone())],
+        }),
+    );
+    match options.fixtures_port {
+        Some(port) => start_fixture_server(dev_dir.path(), port, pipe_manager.clone()).await?,
+        None => {
+            let health = reqwest::get(format!("{}/health", server_url)).await;
+            if health.is_err() {
+                warn!(
+                    "no screenpipe server at {}, start one or use --fixtures",
+                    server_url
+                );
+            }
+        }
+    }
+
+    let (changes_tx, mut changes) = mpsc::unbounded_channel();
+    let mut watcher =
+        notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+            Ok(event) if !matches!(event.kind, EventKind::Access(_)) => {
+                let _ = changes_tx.send(event.paths);
+            }
+            Ok(_) => {}
+            Err(e) => warn!("watching pipe files failed: {}", e),
+        })?;
+    watch_pipe_dir(&mut watcher, &path)?;
+
+    info!(
+        "running pipe {} from {:?} against {}, ctrl+c to stop",
+        id, path, server_url
+    );
+    tokio::spawn(pipe_manager.start_pipe_task(id.clone()).await?);
+
+    tokio::pin!(shutdown);
+    loop {
+        let paths = tokio::select! {
+            _ = &mut shutdown => break,
+            paths = changes.recv() => match paths {
+                Some(paths) => paths,
+                None => break,
+            },
+        };
+        let mut changed = restart_reasons(&path, paths, is_nextjs);
+        while let Ok(Some(paths)) = tokio::time::timeout(DEBOUNCE, changes.recv()).await {
+            changed.extend(restart_reasons(&path, paths, is_nextjs));
+        }
+        let Some(first) = changed.first() else {
+            continue;
+        };
+        info!("{} changed, restarting pipe {}", first, id);
+        pipe_manager.stop_pipe(&id).await?;
+        tokio::spawn(pipe_manager.start_pipe_task(id.clone()).await?);
+    }
+
+    info!("stopping pipe {}", id);
+    pipe_manager.stop_pipe(&id).await
+}
+
+/// Paths among `paths`, relative to the pipe directory, whose change restarts the pipe.
+fn restart_reasons(pipe_dir: &Path, paths: Vec<PathBuf>, is_nextjs: bool) -> Vec<String> {
+    paths
+        .iter()
+        .filter_map(|path| path.strip_prefix(pipe_dir).ok())
+        .filter(|relative| match relative.components().next() {
+            Some(first) => {
+                let first = first.as_os_str().to_string_lossy();
+                !first.starts_with('.') && !IGNORED.contains(&first.as_ref())
+            }
+            None => false,
+        })
+        .filter(|relative| {
+            !is_nextjs
+                || *relative == Path::new("pipe.json")
+                || *relative == Path::new("package.json")
+        })
+        .map(|relative| relative.to_string_lossy().into_owned())
+        .collect()
+}
+
+/// Watches the files of the pipe, leaving out the ignored directories which can be huge.
+fn watch_pipe_dir(watcher: &mut RecommendedWatcher, pipe_dir: &Path) -> Result<()> {
+    watcher.watch(pipe_dir, RecursiveMode::NonRecursive)?;
+    for entry in std::fs::read_dir(pipe_dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if entry.file_type()?.is_dir()
+            && !name.starts_with('.')
+            && !IGNORED.contains(&name.as_str())
+        {
+            watcher.watch(&entry.path(), RecursiveMode::Recursive)?;
+        }
+    }
+    Ok(())
+}
+
+async fn is_nextjs(pipe_dir: &Path) -> bool {
+    let Ok(package_json) = tokio::fs::read_to_string(pipe_dir.join("package.json")).await else {
+        return false;
+    };
+    serde_json::from_str::<Value>(&package_json)
+        .map(|package| package["dependencies"].get("next").is_some())
+        .unwrap_or(false)
+}
+
+fn link_dir(target: &Path, link: &Path) -> Result<()> {
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(target, link)?;
+    #[cfg(windows)]
+    std::os::windows::fs::symlink_dir(target, link).map_err(|e| {
+        anyhow::anyhow!(
+            "failed to link the pipe directory ({}), symlinks need developer mode on windows",
+            e
+        )
+    })?;
+    Ok(())
+}
+
+/// Serves a database seeded with [`seed_fixtures`] on `port`, wasm pipes get it too.
+async fn start_fixture_server(
+    dev_dir: &Path,
+    port: u16,
+    pipe_manager: Arc<PipeManager>,
+) -> Result<()> {
+    if !port_check::is_local_ipv4_port_free(port) {
+        anyhow::bail!(
+            "port {} is in use, pick another one with --fixtures-port",
+            port
+        );
+    }
+    let db_path = dev_dir.join("db.sqlite");
+    let store: Arc<dyn Store> = Arc::new(DatabaseManager::new(&db_path.to_string_lossy()).await?);
+    seed_fixtures(store.as_ref()).await?;
+    let media = Arc::new(MediaStorage::local(store.clone()));
+    pipe_manager.set_host(PipeHost {
+        store: store.clone(),
+        media: media.clone(),
+    });
+
+    let server = Server::new(
+        store,
+        SocketAddr::from(([127, 0, 0, 1], port)),
+        dev_dir.to_path_buf(),
+        pipe_manager,
+        true,
+        true,
+        false,
+    )
+    .with_media_storage(media);
+    tokio::spawn(async move {
+        if let Err(e) = server.start(|_| {}, false).await {
+            error!("fixture server stopped: {}", e);
+        }
+    });
+    info!("serving fixtures on http://localhost:{}", port);
+    Ok(())
+}
+
+/// The last hour of a made up workday: screens of a few apps and a short call, enough for
+/// pipes to search without recording anything. There is no media behind the rows.
+async fn seed_fixtures(store: &dyn Store) -> Result<()> {
+    const SCREENS: &[(&str, &str, &str)] = &[
+        (
+            "Code",
+            "pipe.ts - my-pipe",
+            "import { pipe } from \"@screenpipe/js\";\nconst results = await pipe.queryScreenpipe({ q: \"meeting\" });",
+        ),
+        (
+            "Arc",
+            "screenpipe docs - pipes",
+            "pipes are plugins that run on top of your screen and audio data",
+        ),
+        (
+            "Slack",
+            "#general - acme",
+            "alice: standup moved to 10:30\nbob: the release notes are in notion",
+        ),
+        (
+            "Notion",
+            "Release notes",
+            "v0.2.50: faster search, pipe logs, encrypted pipe secrets",
+        ),
+    ];
+    const CALL: &[&str] = &[
+        "let's go over the release plan for this week",
+        "the search changes are merged, we still need the docs",
+        "I can write them tomorrow morning",
+    ];
+
+    let now = Utc::now();
+    let ocr_engine = Arc::new(OcrEngine::Tesseract);
+    store
+        .insert_video_chunk("fixtures/monitor_1.mp4", "monitor_1")
+        .await?;
+    for (i, (app_name, window_name, text)) in SCREENS.iter().cycle().take(24).enumerate() {
+        let timestamp = now - chrono::Duration::minutes(60 - i as i64 * 2);
+        let frame_id = store.insert_frame("monitor_1", Some(timestamp)).await?;
+        store
+            .insert_ocr_text(
+                frame_id,
+                text,
+                "",
+                app_name,
+                window_name,
+                ocr_engine.clone(),
+                true,
+            )
+            .await?;
+        if i == 0 {
+            store
+                .add_tags(
+                    frame_id,
+                    TagContentType::Vision,
+                    vec!["fixture".to_string()],
+                )
+                .await?;
+        }
+    }
+
+    let device = AudioDevice::new("MacBook Pro Microphone".to_string(), DeviceType::Input);
+    let audio_chunk_id = store.insert_audio_chunk("fixtures/call.mp4").await?;
+    for (i, transcription) in CALL.iter().enumerate() {
+        store
+            .insert_audio_transcription(
+                audio_chunk_id,
+                transcription,
+                i as i64,
+                "fixture",
+                &device,
+                None,
+                Some(i as f64 * 5.0),
+                Some(i as f64 * 5.0 + 4.0),
+            )
+            .await?;
+    }
+    Ok(())
+}
@@ -1,16 +1,30 @@
+use crate::pipe_supervisor::{
+    watch_health, ExitReason, PipeRunState, PipeStatus, PipeStatuses, RestartDecision,
+    RestartTracker,
+};
+use crate::storage::MediaStorage;
+use crate::store::Store;
 use anyhow::Result;
 use killport::cli::Mode;
 use killport::killport::{Killport, KillportOperations};
 use killport::signal::KillportSignal;
-use screenpipe_core::{download_pipe, download_pipe_private, PipeState};
+use screenpipe_core::{
+    download_pipe_private, fetch_pipe, find_pipe_version, get_cron_runs, get_pipe_secrets,
+    get_sandbox_report, install_pipe, install_pipe_dependencies, list_pipe_secrets,
+    list_pipe_versions, pipe_versions_dir, read_pipe_lock, read_pipe_logs, remove_pipe_secret,
+    remove_pipe_secrets, restore_pipe_version, run_cron_now, set_pipe_secret,
+    snapshot_pipe_version, write_pipe_lock, CronRun, ManifestError, PipeInstallOptions, PipeLock,
+    PipeLogQuery, PipeLogRecord, PipeManifest, PipeRunOptions, PipeRuntime, PipeState, PipeVersion,
+    PipeVersions, SandboxReport, PIPE_LOCK_FILE, PIPE_SECRETS_FILE, REDACTED_SECRET,
+};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
 use std::future::Future;
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
-use tokio::fs::File;
-use tokio::io::AsyncWriteExt;
+use std::pin::Pin;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc::{self, Sender};
 use tokio::sync::RwLock;
 use tracing::{debug, error, info, warn};
@@ -23,16 +37,44 @@ pub struct PipeInfo {
     pub source: String,
     pub port: Option<u16>,
     pub is_nextjs: bool,
+    #[serde(default)]
+    pub runtime: PipeRuntime,
+    /// Isolation the pipe was last started with, see the `permissions` block of `pipe.json`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sandbox: Option<SandboxReport>,
+    /// What the pipe was installed from, unset for pipes installed before locks existed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lock: Option<PipeLock>,
+    /// Supervisor state, unset until the pipe was started.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<PipeStatus>,
+    /// Names of the secrets set for the pipe, their values never leave the secrets store.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub secrets: Vec<String>,
 }
 
+/// A supervised pipe, `state` is unset while its process isn't running.
 struct PipeHandle {
-    state: PipeState,
+    state: Option<PipeState>,
     kill_tx: Sender<()>,
 }
 
+type RunningPipes = Arc<RwLock<HashMap<String, PipeHandle>>>;
+
+/// Screenpipe's data, for pipes running inside screenpipe (wasm pipes) rather than talking
+/// to its api.
+#[derive(Clone)]
+pub struct PipeHost {
+    pub store: Arc<dyn Store>,
+    pub media: Arc<MediaStorage>,
+}
+
 pub struct PipeManager {
     screenpipe_dir: PathBuf,
-    running_pipes: Arc<RwLock<HashMap<String, PipeHandle>>>,
+    running_pipes: RunningPipes,
+    statuses: PipeStatuses,
+    host: OnceLock<PipeHost>,
+    run_options: PipeRunOptions,
 }
 
 impl PipeManager {
@@ -40,6 +82,26 @@ impl PipeManager {
         PipeManager {
             screenpipe_dir,
             running_pipes: Arc::new(RwLock::new(HashMap::new())),
+            statuses: PipeStatuses::default(),
+            host: OnceLock::new(),
+            run_options: PipeRunOptions::default(),
+        }
+    }
+
+    /// Starts pipe processes with `options`, see `pipe_dev`.
+    pub fn with_run_options(mut self, options: PipeRunOptions) -> Self {
+        self.run_options = options;
+        self
+    }
+
+    pub fn screenpipe_dir(&self) -> &Path {
+        &self.screenpipe_dir
+    }
+
+    /// Gives pipes started from now on access to screenpipe's data, once it's open.
+    pub fn set_host(&self, host: PipeHost) {
+        if self.host.set(host).is_err() {
+            warn!("pipe host already set, keeping the first one");
         }
     }
 
@@ -103,10 +165,22 @@ impl PipeManager {
             return Err(anyhow::anyhow!("existing configuration is not an object"));
         }
 
-        let updated_config_str = serde_json::to_string_pretty(&config)?;
-
-        let mut file = File::create(&config_path).await?;
-        file.write_all(updated_config_str.as_bytes()).await?;
+        // the merged config has to be a valid manifest before it replaces the old one
+        let mut manifest = PipeManifest::validate(&config)?;
+        // values of secret fields go to the secrets store, pipe.json only keeps the field
+        for field in manifest.fields.iter_mut().filter(|field| field.secret) {
+            match field.value.take() {
+                Some(Value::String(value)) if value == REDACTED_SECRET => {}
+                Some(Value::String(value)) if value.is_empty() => {
+                    remove_pipe_secret(&self.screenpipe_dir, id, &field.name).await?;
+                }
+                Some(Value::String(value)) => {
+                    set_pipe_secret(&self.screenpipe_dir, id, &field.name, &value).await?;
+                }
+                _ => {}
+            }
+        }
+        manifest.save(&config_path).await?;
 
         // Handle pipe state changes
         if let Some(enabled) = is_enabled {
@@ -142,11 +216,81 @@ impl PipeManager {
         pipes.iter().find(|pipe| pipe.id == id).cloned()
     }
 
-    async fn load_pipe_info(pipe_id: String, config_path: PathBuf) -> PipeInfo {
-        let config = tokio::fs::read_to_string(&config_path)
+    pub async fn get_pipe_status(&self, id: &str) -> Option<PipeStatus> {
+        self.statuses.get(id).await
+    }
+
+    /// Reads the stored logs of a pipe, `None` when the pipe isn't installed.
+    pub async fn get_pipe_logs(
+        &self,
+        id: &str,
+        query: &PipeLogQuery,
+    ) -> Result<Option<Vec<PipeLogRecord>>> {
+        let pipe_dir = self.screenpipe_dir.join("pipes").join(id);
+        if !pipe_dir.is_dir() {
+            return Ok(None);
+        }
+        Ok(Some(read_pipe_logs(&pipe_dir, query).await?))
+    }
+
+    /// Runs a cron job of a pipe now, `None` when the pipe has no such job.
+    pub async fn run_cron_now(&self, id: &str, job: &str) -> Result<Option<CronRun>> {
+        run_cron_now(id, job, &self.screenpipe_dir).await
+    }
+
+    /// Most recent cron runs of a pipe first, only of `job` when set.
+    pub async fn get_cron_history(
+        &self,
+        id: &str,
+        job: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<CronRun>> {
+        get_cron_runs(id, job, limit).await
+    }
+
+    /// Names of the secrets set for a pipe, `None` when the pipe isn't installed.
+    pub async fn list_secrets(&self, id: &str) -> Result<Option<Vec<String>>> {
+        if self.ensure_pipe_exists(id).is_err() {
+            return Ok(None);
+        }
+        let secrets = get_pipe_secrets(&self.screenpipe_dir, id).await?;
+        Ok(Some(secrets.into_keys().collect()))
+    }
+
+    /// Sets a secret of a pipe, given to it as an environment variable from its next start.
+    pub async fn set_secret(&self, id: &str, name: &str, value: &str) -> Result<()> {
+        self.ensure_pipe_exists(id)?;
+        set_pipe_secret(&self.screenpipe_dir, id, name, value).await
+    }
+
+    /// Removes a secret of a pipe, false when it wasn't set.
+    pub async fn remove_secret(&self, id: &str, name: &str) -> Result<bool> {
+        self.ensure_pipe_exists(id)?;
+        remove_pipe_secret(&self.screenpipe_dir, id, name).await
+    }
+
+    fn ensure_pipe_exists(&self, id: &str) -> Result<()> {
+        if !self.screenpipe_dir.join("pipes").join(id).is_dir() {
+            anyhow::bail!("pipe '{}' does not exist", id);
+        }
+        Ok(())
+    }
+
+    async fn load_pipe_info(
+        pipe_id: String,
+        config_path: PathBuf,
+        secrets: Vec<String>,
+    ) -> PipeInfo {
+        let mut config = tokio::fs::read_to_string(&config_path)
             .await
             .and_then(|s| serde_json::from_str::<Value>(&s).map_err(Into::into))
             .unwrap_or(Value::Null);
+        redact_secret_fields(&mut config, &secrets);
+        let sandbox = get_sandbox_report(&pipe_id).await;
+        let lock = match config_path.parent() {
+            Some(pipe_dir) => read_pipe_lock(pipe_dir).await.ok().flatten(),
+            None => None,
+        };
 
         PipeInfo {
             id: pipe_id,
@@ -169,12 +313,26 @@ impl PipeManager {
                 .get("is_nextjs")
                 .and_then(Value::as_bool)
                 .unwrap_or(false),
+            runtime: config
+                .get("runtime")
+                .and_then(|runtime| serde_json::from_value(runtime.clone()).ok())
+                .unwrap_or_default(),
+            sandbox,
+            lock,
+            status: None,
+            secrets,
         }
     }
 
     pub async fn list_pipes(&self) -> Vec<PipeInfo> {
         let pipe_dir = self.screenpipe_dir.join("pipes");
         let mut pipe_infos = Vec::new();
+        let mut secrets = list_pipe_secrets(&self.screenpipe_dir)
+            .await
+            .unwrap_or_else(|e| {
+                warn!("failed to read pipe secrets: {}", e);
+                Default::default()
+            });
 
         if let Ok(mut entries) = tokio::fs::read_dir(pipe_dir).await {
             while let Ok(Some(entry)) = entries.next_entry().await {
@@ -190,7 +348,11 @@ impl PipeManager {
                         .unwrap_or(false)
                 {
                     let config_path = entry.path().join("pipe.json");
-                    pipe_infos.push(Self::load_pipe_info(pipe_id.into_owned(), config_path).await);
+                    let pipe_secrets = secrets.remove(pipe_id.as_ref()).unwrap_or_default();
+                    let mut info =
+                        Self::load_pipe_info(pipe_id.into_owned(), config_path, pipe_secrets).await;
+                    info.status = self.statuses.get(&info.id).await;
+                    pipe_infos.push(info);
                 }
             }
         }
@@ -198,11 +360,11 @@ impl PipeManager {
         pipe_infos
     }
 
-    pub async fn download_pipe(&self, url: &str) -> Result<String> {
+    pub async fn download_pipe(&self, url: &str, options: &PipeInstallOptions) -> Result<String> {
         // Remove any surrounding quotes and normalize backslashes
         let normalized_url = url.trim_matches('"').replace("\\", "/");
 
-        let pipe_dir = download_pipe(&normalized_url, self.screenpipe_dir.clone()).await?;
+        let pipe_dir = install_pipe(&normalized_url, options, self.screenpipe_dir.clone()).await?;
 
         // update the config with the source url
         self.update_config(
@@ -213,6 +375,9 @@ impl PipeManager {
         )
         .await?;
 
+        self.snapshot_version(&pipe_dir.file_name().unwrap().to_string_lossy())
+            .await;
+
         info!(
             "pipe {} downloaded",
             pipe_dir.file_name().unwrap().to_string_lossy()
@@ -252,6 +417,9 @@ impl PipeManager {
         )
         .await?;
 
+        self.snapshot_version(&pipe_dir.file_name().unwrap().to_string_lossy())
+            .await;
+
         info!(
             "pipe {} downloaded",
             pipe_dir.file_name().unwrap().to_string_lossy()
@@ -278,6 +446,10 @@ impl PipeManager {
         // Then remove the directory
         let pipe_dir = self.screenpipe_dir.join("pipes");
         tokio::fs::remove_dir_all(pipe_dir).await?;
+        let secrets_path = self.screenpipe_dir.join(PIPE_SECRETS_FILE);
+        if secrets_path.exists() {
+            tokio::fs::remove_file(secrets_path).await?;
+        }
 
         debug!("all pipes purged");
         Ok(())
@@ -291,6 +463,11 @@ impl PipeManager {
         let pipe_dir = self.screenpipe_dir.join("pipes").join(id);
         if pipe_dir.exists() {
             tokio::fs::remove_dir_all(pipe_dir).await?;
+            let versions_dir = pipe_versions_dir(&self.screenpipe_dir, id);
+            if versions_dir.exists() {
+                tokio::fs::remove_dir_all(versions_dir).await?;
+            }
+            remove_pipe_secrets(&self.screenpipe_dir, id).await?;
             debug!("deleted pipe: {}", id);
             Ok(())
         } else {
@@ -303,44 +480,18 @@ impl PipeManager {
         if let Some(handle) = pipes.remove(id) {
             info!("stopping pipe: {}", id);
 
-            // Send kill signal and wait for confirmation
-            handle.kill_tx.send(()).await?;
+            // Send kill signal, the supervisor may already be gone if the pipe just gave up
+            let _ = handle.kill_tx.send(()).await;
 
             // Clean up any running cron jobs
-            screenpipe_core::pipes::cleanup_pipe_crons(id).await?;
+            screenpipe_core::cleanup_pipe_crons(id).await;
 
             // Wait a bit for the process to actually terminate
             tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
 
             match handle.state {
-                PipeState::Port(port) => {
-                    tokio::task::spawn_blocking(move || {
-                        let killport = Killport;
-                        let signal: KillportSignal = "SIGKILL".parse().unwrap();
-
-                        match killport.kill_service_by_port(port, signal.clone(), Mode::Auto, false)
-                        {
-                            Ok(killed_services) => {
-                                if killed_services.is_empty() {
-                                    debug!("no services found using port {}", port);
-                                } else {
-                                    for (killable_type, name) in killed_services {
-                                        debug!(
-                                            "successfully killed {} '{}' listening on port {}",
-                                            killable_type, name, port
-                                        );
-                                    }
-                                }
-                            }
-                            Err(e) => {
-                                warn!("error killing port {}: {}", port, e);
-                            }
-                        }
-                    })
-                    .await
-                    .map_err(|e| anyhow::anyhow!("Failed to kill port: {}", e))?;
-                }
-                PipeState::Pid(pid) => {
+                Some(PipeState::Port(port)) => kill_port(port).await?,
+                Some(PipeState::Pid(pid)) => {
                     // Force kill the process if it's still running
                     #[cfg(unix)]
                     {
@@ -364,76 +515,84 @@ impl PipeManager {
                         }
                     }
                 }
+                // backing off between restarts, nothing is running
+                None => {}
             }
 
             // Clean up cron jobs
-            screenpipe_core::pipes::cleanup_pipe_crons(id).await?;
+            screenpipe_core::cleanup_pipe_crons(id).await;
+            screenpipe_core::cleanup_pipe_sandbox(id).await;
 
             info!("stopped pipe: {}", id);
         }
         Ok(())
     }
 
-    pub async fn start_pipe_task(&self, id: String) -> Result<impl Future<Output = Result<()>>> {
-        let screenpipe_dir = self.screenpipe_dir.clone();
-        let running_pipes = self.running_pipes.clone();
-        let id_for_map = id.clone();
-
-        Ok(async move {
-            match screenpipe_core::run_pipe(&id, screenpipe_dir.clone()).await {
-                Ok((mut child, pipe_state)) => {
-                    let (kill_tx, mut kill_rx) = mpsc::channel::<()>(1);
-
-                    running_pipes.write().await.insert(
-                        id_for_map.clone(),
-                        PipeHandle {
-                            state: pipe_state,
-                            kill_tx: kill_tx.clone(),
-                        },
-                    );
-
-                    match pipe_state {
-                        PipeState::Port(port) => {
-                            info!("started pipe: {} on port {}", id, port);
-                        }
-                        PipeState::Pid(pid) => {
-                            info!("started pipe: {} on pid {}", id, pid);
-                        }
-                    }
+    /// Starts `id` without enabling it in `pipe.json`, so it isn't started again with
+    /// screenpipe. False when it is already running.
+    pub async fn start_pipe(&self, id: &str) -> Result<bool> {
+        if !self.screenpipe_dir.join("pipes").join(id).exists() {
+            anyhow::bail!("pipe '{}' does not exist", id);
+        }
+        if self.running_pipes.read().await.contains_key(id) {
+            return Ok(false);
+        }
+        let future = self.start_pipe_task(id.to_string()).await?;
+        tokio::spawn(future);
+        info!("pipe {} started", id);
+        Ok(true)
+    }
 
-                    tokio::select! {
-                        status = child.wait() => {
-                            match status {
-                                Ok(status) if !status.success() => {
-                                    println!("pipe {} exited with status: {}", id, status);
-                                    running_pipes.write().await.remove(&id_for_map);
-                                    anyhow::bail!("pipe exited with non-zero status: {}", status);
-                                }
-                                Err(e) => {
-                                    println!("error waiting for pipe {}: {}", id, e);
-                                    running_pipes.write().await.remove(&id_for_map);
-                                    anyhow::bail!("error waiting for pipe: {}", e);
-                                }
-                                Ok(_) => Ok(())
-                            }
-                        }
-                        _ = kill_rx.recv() => {
-                            // Kill received through channel
-                            let _ = child.kill().await;
-                            running_pipes.write().await.remove(&id_for_map);
-                            Ok(())
-                        }
-                    }
-                }
-                Err(e) => {
-                    println!("failed to start pipe {}: {}", id, e);
-                    Err(e)
-                }
+    /// Starts `id` under supervision, the returned future resolves once the pipe is stopped,
+    /// disabled or gave up restarting.
+    pub async fn start_pipe_task(&self, id: String) -> Result<impl Future<Output = Result<()>>> {
+        let (kill_tx, kill_rx) = mpsc::channel::<()>(1);
+        {
+            let mut running_pipes = self.running_pipes.write().await;
+            if running_pipes.contains_key(&id) {
+                anyhow::bail!("pipe '{}' is already running", id);
             }
-        })
+            running_pipes.insert(
+                id.clone(),
+                PipeHandle {
+                    state: None,
+                    kill_tx: kill_tx.clone(),
+                },
+            );
+        }
+
+        let config_path = self
+            .screenpipe_dir
+            .join("pipes")
+            .join(&id)
+            .join("pipe.json");
+        let restart = PipeManifest::load(&config_path)
+            .await
+            .map(|manifest| manifest.restart)
+            .unwrap_or_default();
+        self.statuses.reset(&id, restart.policy).await;
+
+        Ok(supervise_pipe(
+            id,
+            self.screenpipe_dir.clone(),
+            self.host.get().cloned(),
+            self.run_options.clone(),
+            self.running_pipes.clone(),
+            self.statuses.clone(),
+            kill_tx,
+            kill_rx,
+        ))
     }
 
-    pub async fn update_pipe_version(&self, id: &str, source: &str) -> Result<()> {
+    /// Replaces the files of an installed pipe with the ones at `source`, keeping its
+    /// `pipe.json`. Without a source the locked version is fetched again and anything but the
+    /// locked files is refused, which makes upgrades reproducible and rolls back local edits.
+    pub async fn update_pipe_version(
+        &self,
+        id: &str,
+        source: Option<&str>,
+        options: &PipeInstallOptions,
+    ) -> Result<()> {
         debug!("updating pipe: {}", id);
         let pipe_dir = self.screenpipe_dir.join("pipes").join(id);
 
@@ -442,46 +601,64 @@ impl PipeManager {
         let config = tokio::fs::read_to_string(&pipe_json_path).await?;
         let mut config: Value = serde_json::from_str(&config)?;
 
+        let locked = read_pipe_lock(&pipe_dir).await?;
+        let (fetch_source, options) = match (source, &locked) {
+            (Some(source), _) => (source.to_string(), options.clone()),
+            (None, Some(locked)) => (locked.resolved.clone(), locked.install_options()),
+            (None, None) => anyhow::bail!(
+                "pipe {} has no {}, a source is needed to update it",
+                id,
+                PIPE_LOCK_FILE
+            ),
+        };
+
         // Create temp directory outside of pipes dir
         let tmp_dir = std::env::temp_dir().join(format!("screenpipe_update_{}", id));
-        tokio::fs::create_dir_all(&tmp_dir).await?;
+        if tmp_dir.exists() {
+            tokio::fs::remove_dir_all(&tmp_dir).await?;
+        }
         debug!("created temp dir: {:?}", tmp_dir);
 
-        // Download new version to temp directory
-        let tmp_pipe_dir = download_pipe_private(id, source, tmp_dir.clone()).await?;
-        debug!("downloaded new version to temp dir: {:?}", tmp_pipe_dir);
-
-        // Verify temp directory exists and contains the pipe files
-        if !tmp_pipe_dir.exists() {
-            error!("temp pipe directory not found: {:?}", tmp_pipe_dir);
-            return Err(anyhow::anyhow!(
-                "temp pipe directory not found: {:?}",
-                tmp_pipe_dir
-            ));
+        // Download new version to temp directory, it's checked before anything is replaced
+        let mut lock = match fetch_pipe(&fetch_source, &options, &tmp_dir).await {
+            Ok(lock) => lock,
+            Err(e) => {
+                if tmp_dir.exists() {
+                    tokio::fs::remove_dir_all(&tmp_dir).await?;
+                }
+                return Err(e);
+            }
+        };
+        if let (None, Some(locked)) = (source, &locked) {
+            lock.source = locked.source.clone();
         }
-
-        // Get version from new pipe.json in temp dir
-        let new_pipe_package_json_path = tmp_pipe_dir.join("package.json");
-        let new_config = tokio::fs::read_to_string(&new_pipe_package_json_path)
-            .await
-            .map_err(|e| anyhow::anyhow!("failed to read new package.json: {}", e))?;
-        let new_config: Value = serde_json::from_str(&new_config)
-            .map_err(|e| anyhow::anyhow!("failed to parse new package.json: {}", e))?;
-
-        // Update version in existing config
-        if let Some(new_version) = new_config.get("version").and_then(Value::as_str) {
-            if let Some(obj) = config.as_object_mut() {
-                obj.insert(
-                    "version".to_string(),
-                    Value::String(new_version.to_string()),
-                );
-                // Write updated config back to file
-                let updated_config = serde_json::to_string_pretty(&config)
-                    .map_err(|e| anyhow::anyhow!("failed to serialize updated config: {}", e))?;
-                tokio::fs::write(&pipe_json_path, updated_config)
-                    .await
-                    .map_err(|e| anyhow::anyhow!("failed to write updated config: {}", e))?;
-                debug!("updated version in pipe.json to: {}", new_version);
+        debug!("downloaded new version to temp dir: {:?}", tmp_dir);
+
+        // Get version from new package.json in temp dir
+        let new_pipe_package_json_path = tmp_dir.join("package.json");
+        if new_pipe_package_json_path.exists() {
+            let new_config = tokio::fs::read_to_string(&new_pipe_package_json_path)
+                .await
+                .map_err(|e| anyhow::anyhow!("failed to read new package.json: {}", e))?;
+            let new_config: Value = serde_json::from_str(&new_config)
+                .map_err(|e| anyhow::anyhow!("failed to parse new package.json: {}", e))?;
+
+            // Update version in existing config
+            if let Some(new_version) = new_config.get("version").and_then(Value::as_str) {
+                if let Some(obj) = config.as_object_mut() {
+                    obj.insert(
+                        "version".to_string(),
+                        Value::String(new_version.to_string()),
+                    );
+                    // Write updated config back to file
+                    let updated_config = serde_json::to_string_pretty(&config).map_err(|e| {
+                        anyhow::anyhow!("failed to serialize updated config: {}", e)
+                    })?;
+                    tokio::fs::write(&pipe_json_path, updated_config)
+                        .await
+                        .map_err(|e| anyhow::anyhow!("failed to write updated config: {}", e))?;
+                    debug!("updated version in pipe.json to: {}", new_version);
+                }
             }
         }
 
@@ -508,7 +685,7 @@ impl PipeManager {
         debug!("moved old files to trash");
 
         // 4. Move new files from temp to pipe dir
-        let mut entries = tokio::fs::read_dir(&tmp_pipe_dir).await?;
+        let mut entries = tokio::fs::read_dir(&tmp_dir).await?;
         while let Some(entry) = entries.next_entry().await? {
             let file_name = entry.file_name();
             let src_path = entry.path();
@@ -535,6 +712,10 @@ impl PipeManager {
             debug!("moved: {:?}", file_name);
         }
 
+        install_pipe_dependencies(&pipe_dir).await?;
+        write_pipe_lock(&pipe_dir, &lock).await?;
+        self.snapshot_version(id).await;
+
         // Clean up temp directory
         tokio::fs::remove_dir_all(&tmp_dir).await?;
         debug!("cleaned up temp dir");
@@ -553,6 +734,74 @@ impl PipeManager {
         info!("pipe {} updated successfully", id);
         Ok(())
     }
+
+    pub async fn list_pipe_versions(&self, id: &str) -> Result<PipeVersions> {
+        list_pipe_versions(&self.screenpipe_dir, id).await
+    }
+
+    /// Stops the pipe, puts back `version` (see [`restore_pipe_version`]) and starts it again
+    /// if it is enabled.
+    pub async fn rollback_pipe(&self, id: &str, version: Option<&str>) -> Result<PipeVersion> {
+        let pipe_dir = self.screenpipe_dir.join("pipes").join(id);
+        if !pipe_dir.exists() {
+            anyhow::bail!("pipe '{}' does not exist", id);
+        }
+        // fail before stopping anything when there is nothing to go back to
+        find_pipe_version(&self.screenpipe_dir, id, version).await?;
+
+        self.stop_pipe(id)
+            .await
+            .map_err(|e| anyhow::anyhow!("failed to stop pipe: {}", e))?;
+
+        let restored = self.restore_pipe(id, version).await?;
+
+        let config =
+            Self::load_pipe_info(id.to_string(), pipe_dir.join("pipe.json"), Vec::new()).await;
+        if config.enabled {
+            let future = self.start_pipe_task(id.to_string()).await?;
+            tokio::spawn(future);
+            debug!("restarted pipe");
+        }
+
+        info!("pipe {} rolled back to version {}", id, restored.version);
+        Ok(restored)
+    }
+
+    /// Puts back `version` of a pipe that isn't running, without starting it.
+    pub async fn restore_pipe(&self, id: &str, version: Option<&str>) -> Result<PipeVersion> {
+        let restored = restore_pipe_version(&self.screenpipe_dir, id, version).await?;
+        install_pipe_dependencies(&self.screenpipe_dir.join("pipes").join(id)).await?;
+        Ok(restored)
+    }
+
+    /// Keeps what was just installed in the pipe's version history, a failure there doesn't
+    /// fail the install.
+    async fn snapshot_version(&self, id: &str) {
+        if let Err(e) = snapshot_pipe_version(&self.screenpipe_dir, id).await {
+            warn!("failed to snapshot version of pipe {}: {}", id, e);
+        }
+    }
+}
+
+/// Hides the values of the secret fields of `config`, set in the secrets store or left in
+/// `pipe.json` by an older screenpipe.
+fn redact_secret_fields(config: &mut Value, secrets: &[String]) {
+    let Some(fields) = config.get_mut("fields").and_then(Value::as_array_mut) else {
+        return;
+    };
+    for field in fields {
+        if field.get("secret").and_then(Value::as_bool) != Some(true) {
+            continue;
+        }
+        let is_set = field
+            .get("name")
+            .and_then(Value::as_str)
+            .is_some_and(|name| secrets.iter().any(|secret| secret == name));
+        let has_value = field.get("value").is_some_and(|value| !value.is_null());
+        if is_set || has_value {
+            field["value"] = Value::String(REDACTED_SECRET.to_string());
+        }
+    }
 }
 
 // Helper function to recursively copy directories
@@ -582,3 +831,295 @@ async fn copy_dir_all(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> Result<()
 
     Ok(())
 }
+
+/// Runs a pipe and restarts it according to its restart policy until it's stopped, disabled
+/// or out of restarts.
+#[allow(clippy::too_many_arguments)]
+async fn supervise_pipe(
+    id: String,
+    screenpipe_dir: PathBuf,
+    host: Option<PipeHost>,
+    run_options: PipeRunOptions,
+    running_pipes: RunningPipes,
+    statuses: PipeStatuses,
+    kill_tx: Sender<()>,
+    mut kill_rx: mpsc::Receiver<()>,
+) -> Result<()> {
+    let config_path = screenpipe_dir.join("pipes").join(&id).join("pipe.json");
+    let client = reqwest::Client::new();
+    let mut tracker: Option<RestartTracker> = None;
+
+    // only touch our own handle, the pipe may have been restarted under a new one
+    let set_handle_state = |state: Option<PipeState>| {
+        let running_pipes = running_pipes.clone();
+        let kill_tx = kill_tx.clone();
+        let id = id.clone();
+        async move {
+            let mut running_pipes = running_pipes.write().await;
+            match running_pipes.get_mut(&id) {
+                Some(handle) if handle.kill_tx.same_channel(&kill_tx) => handle.state = state,
+                _ => {}
+            }
+        }
+    };
+
+    let result = loop {
+        // the manifest is read on every start so changes apply on the next restart
+        let manifest = PipeManifest::load(&config_path).await.ok();
+        if !run_options.dev && manifest.as_ref().is_some_and(|manifest| !manifest.enabled) {
+            statuses.transition(&id, PipeRunState::Disabled, None).await;
+            break Ok(());
+        }
+        let restart = manifest
+            .as_ref()
+            .map(|manifest| manifest.restart.clone())
+            .unwrap_or_default();
+        let runtime = manifest
+            .as_ref()
+            .map(|manifest| manifest.runtime)
+            .unwrap_or_default();
+        let health_check = manifest.and_then(|manifest| manifest.health_check);
+        statuses.set_restart_policy(&id, restart.policy).await;
+        match tracker.as_mut() {
+            Some(tracker) => tracker.set_config(restart),
+            None => tracker = Some(RestartTracker::new(restart)),
+        }
+
+        statuses.transition(&id, PipeRunState::Starting, None).await;
+        let started = Instant::now();
+        let start = start_pipe(&id, &screenpipe_dir, runtime, host.clone(), &run_options);
+        let reason = match start.await {
+            Ok((mut pipe, pipe_state)) => {
+                set_handle_state(pipe_state).await;
+                match pipe_state {
+                    Some(PipeState::Port(port)) => {
+                        info!("started pipe: {} on port {}", id, port);
+                    }
+                    Some(PipeState::Pid(pid)) => {
+                        info!("started pipe: {} on pid {}", id, pid);
+                    }
+                    None => info!("started pipe: {} in the {} runtime", id, runtime),
+                }
+                statuses.transition(&id, PipeRunState::Running, None).await;
+
+                let health = async {
+                    match (pipe_state, &health_check) {
+                        (Some(PipeState::Port(port)), Some(check)) => {
+                            watch_health(&client, &statuses, &id, port, check).await
+                        }
+                        _ => std::future::pending().await,
+                    }
+                };
+
+                let reason = tokio::select! {
+                    reason = pipe.wait(&id) => reason,
+                    error = health => {
+                        pipe.kill().await;
+                        ExitReason::Unhealthy { error }
+                    }
+                    _ = kill_rx.recv() => {
+                        // Kill received through channel
+                        pipe.kill().await;
+                        ExitReason::Stopped
+                    }
+                };
+                set_handle_state(None).await;
+                // the runtime's children outlive it and keep the port, a restart couldn't bind
+                // it again. Stopping the pipe cleans up in `stop_pipe`
+                if reason != ExitReason::Stopped {
+                    if let Some(PipeState::Port(port)) = pipe_state {
+                        if let Err(e) = kill_port(port).await {
+                            warn!("pipe {}: {}", id, e);
+                        }
+                    }
+                }
+                reason
+            }
+            Err(e) => {
+                error!("failed to start pipe {}: {}", id, e);
+                // a broken pipe.json won't get better by retrying
+                if e.downcast_ref::<ManifestError>().is_some() {
+                    let reason = ExitReason::FailedToStart {
+                        error: e.to_string(),
+                    };
+                    statuses
+                        .record_exit(&id, reason.clone(), Duration::ZERO)
+                        .await;
+                    statuses
+                        .transition(&id, PipeRunState::Crashed, Some(&reason))
+                        .await;
+                    break Err(e);
+                }
+                ExitReason::FailedToStart {
+                    error: e.to_string(),
+                }
+            }
+        };
+
+        let uptime = started.elapsed();
+        statuses.record_exit(&id, reason.clone(), uptime).await;
+        if reason == ExitReason::Stopped {
+            statuses
+                .transition(&id, stopped_state(&config_path).await, Some(&reason))
+                .await;
+            break Ok(());
+        }
+
+        let decision = tracker
+            .as_mut()
+            .expect("tracker is set before the pipe starts")
+            .on_exit(&reason, uptime, Instant::now());
+        match decision {
+            RestartDecision::Exited => {
+                info!("pipe {} {}", id, reason);
+                statuses
+                    .transition(&id, PipeRunState::Exited, Some(&reason))
+                    .await;
+                break Ok(());
+            }
+            RestartDecision::Crashed => {
+                error!("pipe {} {}, not restarting it", id, reason);
+                statuses
+                    .transition(&id, PipeRunState::Crashed, Some(&reason))
+                    .await;
+                break Err(anyhow::anyhow!("pipe {} crashed: {}", id, reason));
+            }
+            RestartDecision::Restart(delay) => {
+                warn!("pipe {} {}, restarting in {:?}", id, reason, delay);
+                statuses.schedule_restart(&id, delay).await;
+                statuses
+                    .transition(&id, PipeRunState::BackingOff, Some(&reason))
+                    .await;
+                tokio::select! {
+                    _ = tokio::time::sleep(delay) => {}
+                    _ = kill_rx.recv() => {
+                        statuses
+                            .transition(&id, stopped_state(&config_path).await, None)
+                            .await;
+                        break Ok(());
+                    }
+                }
+            }
+        }
+    };
+
+    let mut running_pipes = running_pipes.write().await;
+    if running_pipes
+        .get(&id)
+        .is_some_and(|handle| handle.kill_tx.same_channel(&kill_tx))
+    {
+        running_pipes.remove(&id);
+    }
+    result
+}
+
+/// Kills whatever still listens on `port`, the pipe's runtime and the processes it spawned.
+async fn kill_port(port: u16) -> Result<()> {
+    tokio::task::spawn_blocking(move || {
+        let killport = Killport;
+        let signal: KillportSignal = "SIGKILL".parse().unwrap();
+
+        match killport.kill_service_by_port(port, signal.clone(), Mode::Auto, false) {
+            Ok(killed_services) => {
+                if killed_services.is_empty() {
+                    debug!("no services found using port {}", port);
+                } else {
+                    for (killable_type, name) in killed_services {
+                        debug!(
+                            "successfully killed {} '{}' listening on port {}",
+                            killable_type, name, port
+                        );
+                    }
+                }
+            }
+            Err(e) => {
+                warn!("error killing port {}: {}", port, e);
+            }
+        }
+    })
+    .await
+    .map_err(|e| anyhow::anyhow!("Failed to kill port: {}", e))
+}
+
+/// A started pipe, a process or a wasm module run by screenpipe itself.
+enum RunningPipe {
+    Process(tokio::process::Child),
+    #[cfg_attr(not(feature = "wasm"), allow(dead_code))]
+    Wasm(Pin<Box<dyn Future<Output = Result<()>> + Send>>),
+}
+
+impl RunningPipe {
+    /// Waits for the pipe to exit on its own.
+    async fn wait(&mut self, id: &str) -> ExitReason {
+        match self {
+            RunningPipe::Process(child) => match child.wait().await {
+                Ok(status) => ExitReason::Exited {
+                    code: status.code(),
+                },
+                Err(e) => {
+                    error!("error waiting for pipe {}: {}", id, e);
+                    ExitReason::Exited { code: None }
+                }
+            },
+            RunningPipe::Wasm(run) => match run.await {
+                Ok(()) => ExitReason::Exited { code: Some(0) },
+                Err(e) => {
+                    #[cfg(feature = "wasm")]
+                    let code = crate::pipe_wasm::exit_code(&e);
+                    #[cfg(not(feature = "wasm"))]
+                    let code = None;
+                    if code.is_none() {
+                        error!("wasm pipe {} failed: {:#}", id, e);
+                    }
+                    ExitReason::Exited { code }
+                }
+            },
+        }
+    }
+
+    async fn kill(&mut self) {
+        match self {
+            RunningPipe::Process(child) => {
+                let _ = child.kill().await;
+            }
+            // dropping the future stops the module
+            RunningPipe::Wasm(_) => {}
+        }
+    }
+}
+
+/// Starts `id` with its runtime. Wasm pipes have no [`PipeState`], there is no process to
+/// kill for them.
+async fn start_pipe(
+    id: &str,
+    screenpipe_dir: &Path,
+    runtime: PipeRuntime,
+    host: Option<PipeHost>,
+    run_options: &PipeRunOptions,
+) -> Result<(RunningPipe, Option<PipeState>)> {
+    if runtime == PipeRuntime::Wasm {
+        #[cfg(feature = "wasm")]
+        {
+            let run = crate::pipe_wasm::start_wasm_pipe(id, screenpipe_dir, host).await?;
+            return Ok((RunningPipe::Wasm(Box::pin(run)), None));
+        }
+        #[cfg(not(feature = "wasm"))]
+        {
+            let _ = host;
+            anyhow::bail!(
+                "wasm support is not enabled, rebuild screenpipe with the `wasm` feature"
+            );
+        }
+    }
+    let (child, pipe_state) =
+        screenpipe_core::run_pipe_with(id, screenpipe_dir.to_path_buf(), run_options).await?;
+    Ok((RunningPipe::Process(child), Some(pipe_state)))
+}
+
+/// State of a pipe stopped through the api, disabled unless it's still enabled.
+async fn stopped_state(config_path: &Path) -> PipeRunState {
+    match PipeManifest::load(config_path).await {
+        Ok(manifest) if manifest.enabled => PipeRunState::Stopped,
+        _ => PipeRunState::Disabled,
+    }
+}
@@ -0,0 +1,342 @@
+//! Keeps pipes running: restarts them according to the `restart` block of their
+//! `pipe.json` with exponential backoff, probes pipes serving on a port over http and keeps
+//! a short history of why they exited.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use screenpipe_core::{HealthCheckConfig, RestartConfig, RestartPolicy};
+use screenpipe_events::send_event;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use tracing::{debug, warn};
+
+/// Event sent on the event bus whenever a pipe changes state.
+pub const PIPE_STATE_EVENT: &str = "pipe_state_changed";
+
+const MAX_EXIT_HISTORY: usize = 20;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum PipeRunState {
+    Starting,
+    Running,
+    /// Waiting to be restarted after an exit.
+    BackingOff,
+    /// Exited with a failure and won't be restarted.
+    Crashed,
+    /// Exited cleanly and won't be restarted.
+    Exited,
+    /// Stopped through the api while still enabled, e.g. during an update.
+    Stopped,
+    Disabled,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ExitReason {
+    /// The process exited, `code` is unset when it was killed by a signal.
+    Exited {
+        code: Option<i32>,
+    },
+    FailedToStart {
+        error: String,
+    },
+    Unhealthy {
+        error: String,
+    },
+    Stopped,
+}
+
+impl ExitReason {
+    pub fn is_failure(&self) -> bool {
+        match self {
+            ExitReason::Exited { code } => *code != Some(0),
+            ExitReason::FailedToStart { .. } | ExitReason::Unhealthy { .. } => true,
+            ExitReason::Stopped => false,
+        }
+    }
+}
+
+impl fmt::Display for ExitReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExitReason::Exited { code: Some(code) } => write!(f, "exited with code {}", code),
+            ExitReason::Exited { code: None } => write!(f, "killed by a signal"),
+            ExitReason::FailedToStart { error } => write!(f, "failed to start: {}", error),
+            ExitReason::Unhealthy { error } => write!(f, "health check failed: {}", error),
+            ExitReason::Stopped => write!(f, "stopped"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct PipeExit {
+    pub at: DateTime<Utc>,
+    pub reason: ExitReason,
+    pub uptime_secs: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct HealthProbe {
+    pub at: DateTime<Utc>,
+    pub healthy: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// What the supervisor knows about a pipe, reported by `/pipes/info/:id`.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct PipeStatus {
+    pub state: PipeRunState,
+    pub restart_policy: RestartPolicy,
+    /// Restarts since the pipe was enabled.
+    pub restarts: u32,
+    pub started_at: Option<DateTime<Utc>>,
+    pub next_restart_at: Option<DateTime<Utc>>,
+    pub last_health_check: Option<HealthProbe>,
+    /// Most recent exits, oldest first.
+    pub exits: VecDeque<PipeExit>,
+}
+
+impl PipeStatus {
+    fn new(restart_policy: RestartPolicy) -> Self {
+        PipeStatus {
+            state: PipeRunState::Starting,
+            restart_policy,
+            restarts: 0,
+            started_at: None,
+            next_restart_at: None,
+            last_health_check: None,
+            exits: VecDeque::new(),
+        }
+    }
+}
+
+/// Payload of [`PIPE_STATE_EVENT`].
+#[derive(Serialize)]
+pub struct PipeStateEvent {
+    pub pipe_id: String,
+    pub state: PipeRunState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<ExitReason>,
+    pub restarts: u32,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Supervisor state of every pipe started since launch.
+#[derive(Clone, Default)]
+pub struct PipeStatuses(Arc<RwLock<HashMap<String, PipeStatus>>>);
+
+impl PipeStatuses {
+    pub async fn get(&self, pipe_id: &str) -> Option<PipeStatus> {
+        self.0.read().await.get(pipe_id).cloned()
+    }
+
+    /// Starts a fresh status for a newly enabled pipe.
+    pub(crate) async fn reset(&self, pipe_id: &str, restart_policy: RestartPolicy) {
+        self.0
+            .write()
+            .await
+            .insert(pipe_id.to_string(), PipeStatus::new(restart_policy));
+    }
+
+    /// Moves the pipe to `state` and announces it on the event bus.
+    pub(crate) async fn transition(
+        &self,
+        pipe_id: &str,
+        state: PipeRunState,
+        reason: Option<&ExitReason>,
+    ) {
+        let restarts = {
+            let mut statuses = self.0.write().await;
+            let status = statuses
+                .entry(pipe_id.to_string())
+                .or_insert_with(|| PipeStatus::new(RestartPolicy::default()));
+            status.state = state;
+            match state {
+                PipeRunState::Running => status.started_at = Some(Utc::now()),
+                PipeRunState::BackingOff => {}
+                _ => status.next_restart_at = None,
+            }
+            status.restarts
+        };
+
+        debug!("pipe {} is now {:?}", pipe_id, state);
+        let _ = send_event(
+            PIPE_STATE_EVENT,
+            PipeStateEvent {
+                pipe_id: pipe_id.to_string(),
+                state,
+                reason: reason.cloned(),
+                restarts,
+                timestamp: Utc::now(),
+            },
+        );
+    }
+
+    pub(crate) async fn set_restart_policy(&self, pipe_id: &str, restart_policy: RestartPolicy) {
+        if let Some(status) = self.0.write().await.get_mut(pipe_id) {
+            status.restart_policy = restart_policy;
+        }
+    }
+
+    pub(crate) async fn record_exit(&self, pipe_id: &str, reason: ExitReason, uptime: Duration) {
+        if let Some(status) = self.0.write().await.get_mut(pipe_id) {
+            status.started_at = None;
+            status.exits.push_back(PipeExit {
+                at: Utc::now(),
+                reason,
+                uptime_secs: uptime.as_secs(),
+            });
+            while status.exits.len() > MAX_EXIT_HISTORY {
+                status.exits.pop_front();
+            }
+        }
+    }
+
+    pub(crate) async fn schedule_restart(&self, pipe_id: &str, delay: Duration) {
+        if let Some(status) = self.0.write().await.get_mut(pipe_id) {
+            status.restarts += 1;
+            status.next_restart_at = chrono::Duration::from_std(delay)
+                .ok()
+                .map(|delay| Utc::now() + delay);
+        }
+    }
+
+    pub(crate) async fn record_health(&self, pipe_id: &str, error: Option<String>) {
+        if let Some(status) = self.0.write().await.get_mut(pipe_id) {
+            status.last_health_check = Some(HealthProbe {
+                at: Utc::now(),
+                healthy: error.is_none(),
+                error,
+            });
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RestartDecision {
+    Restart(Duration),
+    /// Failed and out of restarts, or the policy doesn't restart failures.
+    Crashed,
+    /// Exited cleanly and the policy doesn't restart it.
+    Exited,
+}
+
+/// Restart bookkeeping of one supervised pipe.
+pub struct RestartTracker {
+    config: RestartConfig,
+    recent_restarts: VecDeque<Instant>,
+    backoff: Duration,
+}
+
+impl RestartTracker {
+    pub fn new(config: RestartConfig) -> Self {
+        let backoff = Duration::from_millis(config.initial_backoff_ms);
+        RestartTracker {
+            config,
+            recent_restarts: VecDeque::new(),
+            backoff,
+        }
+    }
+
+    /// Picks up a changed `restart` block, keeping the restarts counted so far.
+    pub fn set_config(&mut self, config: RestartConfig) {
+        if config != self.config {
+            self.backoff = Duration::from_millis(config.initial_backoff_ms);
+            self.config = config;
+        }
+    }
+
+    pub fn on_exit(
+        &mut self,
+        reason: &ExitReason,
+        uptime: Duration,
+        now: Instant,
+    ) -> RestartDecision {
+        let failed = reason.is_failure();
+        let restart = match self.config.policy {
+            RestartPolicy::Never => false,
+            RestartPolicy::OnFailure => failed,
+            RestartPolicy::Always => true,
+        };
+        if !restart {
+            return if failed {
+                RestartDecision::Crashed
+            } else {
+                RestartDecision::Exited
+            };
+        }
+
+        // a run that outlived the longest backoff starts over from the initial delay
+        let max_backoff = Duration::from_millis(self.config.max_backoff_ms);
+        if uptime >= max_backoff {
+            self.backoff = Duration::from_millis(self.config.initial_backoff_ms);
+        }
+
+        let window = Duration::from_secs(self.config.window_secs);
+        while self
+            .recent_restarts
+            .front()
+            .is_some_and(|restart| now.duration_since(*restart) >= window)
+        {
+            self.recent_restarts.pop_front();
+        }
+        if self.recent_restarts.len() >= self.config.max_restarts as usize {
+            return RestartDecision::Crashed;
+        }
+
+        self.recent_restarts.push_back(now);
+        let delay = self.backoff;
+        self.backoff = (self.backoff * 2).min(max_backoff);
+        RestartDecision::Restart(delay)
+    }
+}
+
+/// Probes `check.path` on `port` until `check.failure_threshold` probes in a row failed,
+/// then returns the last error.
+pub(crate) async fn watch_health(
+    client: &reqwest::Client,
+    statuses: &PipeStatuses,
+    pipe_id: &str,
+    port: u16,
+    check: &HealthCheckConfig,
+) -> String {
+    tokio::time::sleep(Duration::from_secs(check.initial_delay_secs)).await;
+
+    let url = format!("http://localhost:{}{}", port, check.path);
+    let mut interval = tokio::time::interval(Duration::from_secs(check.interval_secs));
+    let mut failures = 0;
+    loop {
+        interval.tick().await;
+        let error = match client
+            .get(&url)
+            .timeout(Duration::from_secs(check.timeout_secs))
+            .send()
+            .await
+        {
+            Ok(response) if response.status().is_success() => None,
+            Ok(response) => Some(format!("{} returned {}", url, response.status())),
+            Err(e) => Some(format!("{} failed: {}", url, e)),
+        };
+        statuses.record_health(pipe_id, error.clone()).await;
+
+        match error {
+            None => failures = 0,
+            Some(error) => {
+                failures += 1;
+                warn!(
+                    "health check {}/{} of pipe {} failed: {}",
+                    failures, check.failure_threshold, pipe_id, error
+                );
+                if failures >= check.failure_threshold {
+                    return error;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,756 @@
+//! Runs pipes built to WebAssembly (`"runtime": "wasm"` in `pipe.json`) inside screenpipe
+//! with wasmtime and WASI preview 1. They start without a process and without ambient
+//! access: the pipe directory is mounted read-only at `/pipe` (`pipe.json` and the kv data
+//! can't be touched behind the host's back), the paths of `permissions.read` and
+//! `permissions.write` at their own path, and everything else goes through the functions of
+//! the `screenpipe` import module, each one gated by the `permissions` of `pipe.json`:
+//!
+//! - `search(ptr, len)` needs `content_types` covering the searched content
+//! - `frame(id: i64)` needs `content_types` with `ocr` or `all`
+//! - `subscribe(ptr, len)` needs the event in `events.subscribe`
+//! - `send_event(name_ptr, name_len, data_ptr, data_len)` needs the event in `events.send`
+//! - `kv_get(ptr, len)`, `kv_set(key_ptr, key_len, value_ptr, value_len)` and
+//!   `kv_delete(ptr, len)` need `storage`, the data is kept in the pipe directory
+//! - `http_fetch(ptr, len)` needs the host in `network`, there's no network without it.
+//!   Response bodies are capped at 10 MB
+//!
+//! Requests and responses are json, except for `frame` which answers with the jpeg of the
+//! frame. A call returns the length of its response, which the pipe copies into its memory
+//! with `response_read(ptr)`, or minus the length of an error message read the same way.
+//!
+//! After `_initialize` and `_start`, a pipe that subscribed to events or has crons keeps
+//! running: events are delivered to its `on_event(len)` export and cron jobs to its
+//! `on_cron(len) -> i32` export (0 for success), the payload read with `response_read` too.
+//! Every call into the pipe gets `limits.fuel` instructions and its memory is capped at
+//! `limits.memory_mb`, running out of either ends the pipe.
+
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use screenpipe_core::{
+    generate_cron_secret, get_pipe_secrets, schedule_pipe_crons, stream_pipe_output,
+    ContentPermission, CronContext, CronHandler, PipeCron, PipeManifest, PipePermissions,
+    PipeRuntime, PIPE_KV_FILE,
+};
+use screenpipe_events::EventSubscription;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, info, warn};
+use wasmtime::{
+    Caller, Config, Engine, Extern, Instance, Linker, Memory, Module, Store, StoreLimits,
+    StoreLimitsBuilder, TypedFunc, WasmResults,
+};
+use wasmtime_wasi::pipe::AsyncWriteStream;
+use wasmtime_wasi::preview1::{self, WasiP1Ctx};
+use wasmtime_wasi::{AsyncStdoutStream, DirPerms, FilePerms, I32Exit, WasiCtxBuilder};
+
+use crate::db_types::ContentType;
+use crate::pipe_manager::PipeHost;
+use crate::server::MAX_SEARCH_LIMIT;
+use crate::video_utils::extract_frame_from_video;
+
+/// Instructions a call into a pipe may run when `limits.fuel` is unset.
+pub const DEFAULT_WASM_FUEL: u64 = 1_000_000_000;
+/// Memory of a pipe when `limits.memory_mb` is unset.
+pub const DEFAULT_WASM_MEMORY_MB: u64 = 64;
+/// Where the pipe directory is mounted for the pipe, also in its `PIPE_DIR` variable.
+pub const GUEST_PIPE_DIR: &str = "/pipe";
+
+const HOST_MODULE: &str = "screenpipe";
+/// Instructions run between yields to the executor, a busy pipe doesn't hold a thread.
+const FUEL_YIELD_INTERVAL: u64 = 10_000;
+const OUTPUT_BUFFER_BYTES: usize = 64 * 1024;
+const HTTP_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_HTTP_BODY_BYTES: usize = 10 * 1024 * 1024;
+/// Most a pipe may keep in its key-value store.
+const MAX_KV_BYTES: usize = 1024 * 1024;
+
+static ENGINE: LazyLock<Engine> = LazyLock::new(|| {
+    let mut config = Config::new();
+    config.async_support(true).consume_fuel(true);
+    Engine::new(&config).expect("failed to create the wasm engine")
+});
+
+struct WasmState {
+    wasi: WasiP1Ctx,
+    limits: StoreLimits,
+    host: HostContext,
+}
+
+/// What the host functions of one pipe work with.
+struct HostContext {
+    pipe: String,
+    pipe_dir: PathBuf,
+    permissions: PipePermissions,
+    host: Option<PipeHost>,
+    http: reqwest::Client,
+    subscriptions: HashSet<String>,
+    /// Response of the last host call, or payload of the last callback, for `response_read`.
+    response: Vec<u8>,
+}
+
+/// Error handed to the pipe, as opposed to a trap ending it.
+type HostResult = std::result::Result<Vec<u8>, String>;
+
+type CronCall = (PipeCron, oneshot::Sender<Result<Option<String>>>);
+
+/// Starts the wasm pipe `pipe`. Loading and instantiating happen here, the returned future
+/// runs the pipe until it exits and stops it when dropped.
+pub async fn start_wasm_pipe(
+    pipe: &str,
+    screenpipe_dir: &Path,
+    host: Option<PipeHost>,
+) -> Result<impl Future<Output = Result<()>> + Send + 'static> {
+    let pipe_dir = screenpipe_dir.join("pipes").join(pipe);
+    let manifest = PipeManifest::load(&pipe_dir.join("pipe.json")).await?;
+    let permissions = manifest
+        .permissions
+        .clone()
+        .map(PipePermissions::with_expanded_paths)
+        .unwrap_or_default();
+    let main = PipeRuntime::Wasm.find_main(&pipe_dir, manifest.entrypoints.main.as_deref())?;
+    debug!("[{}] loading {:?}", pipe, main);
+    let module = tokio::task::spawn_blocking(move || Module::from_file(&ENGINE, main)).await??;
+
+    let (stdout, stdout_reader) = tokio::io::duplex(OUTPUT_BUFFER_BYTES);
+    let (stderr, stderr_reader) = tokio::io::duplex(OUTPUT_BUFFER_BYTES);
+    stream_pipe_output(pipe, &pipe_dir, stdout_reader, stderr_reader).await;
+
+    let mut wasi = WasiCtxBuilder::new();
+    wasi.stdout(AsyncStdoutStream::new(AsyncWriteStream::new(
+        OUTPUT_BUFFER_BYTES,
+        stdout,
+    )))
+    .stderr(AsyncStdoutStream::new(AsyncWriteStream::new(
+        OUTPUT_BUFFER_BYTES,
+        stderr,
+    )))
+    .arg(pipe)
+    .env("PIPE_ID", pipe)
+    .env("PIPE_DIR", GUEST_PIPE_DIR)
+    .preopened_dir(&pipe_dir, GUEST_PIPE_DIR, DirPerms::READ, FilePerms::READ)?;
+    for name in &permissions.env {
+        if let Ok(value) = std::env::var(name) {
+            wasi.env(name, value);
+        }
+    }
+    for (name, value) in get_pipe_secrets(screenpipe_dir, pipe).await? {
+        wasi.env(name, value);
+    }
+    let mounts = permissions
+        .read
+        .iter()
+        .map(|path| (path, DirPerms::READ, FilePerms::READ))
+        .chain(
+            permissions
+                .write
+                .iter()
+                .map(|path| (path, DirPerms::all(), FilePerms::all())),
+        );
+    for (path, dir_perms, file_perms) in mounts {
+        if let Err(e) = wasi.preopened_dir(path, path.to_string_lossy(), dir_perms, file_perms) {
+            warn!("[{}] failed to mount {:?}: {}", pipe, path, e);
+        }
+    }
+
+    let fuel = permissions.limits.fuel.unwrap_or(DEFAULT_WASM_FUEL);
+    let memory_mb = permissions
+        .limits
+        .memory_mb
+        .unwrap_or(DEFAULT_WASM_MEMORY_MB);
+    let http = reqwest::Client::builder()
+        // every hop has to be checked against the allowlist, redirects are the pipe's job
+        .redirect(reqwest::redirect::Policy::none())
+        .timeout(HTTP_TIMEOUT)
+        .build()?;
+    let mut store = Store::new(
+        &ENGINE,
+        WasmState {
+            wasi: wasi.build_p1(),
+            limits: StoreLimitsBuilder::new()
+                .memory_size(usize::try_from(memory_mb * 1024 * 1024).unwrap_or(usize::MAX))
+                .build(),
+            host: HostContext {
+                pipe: pipe.to_string(),
+                pipe_dir: pipe_dir.clone(),
+                permissions: permissions.clone(),
+                host,
+                http,
+                subscriptions: HashSet::new(),
+                response: Vec::new(),
+            },
+        },
+    );
+    store.limiter(|state| &mut state.limits);
+    store.fuel_async_yield_interval(Some(FUEL_YIELD_INTERVAL))?;
+    // start functions run while instantiating
+    store.set_fuel(fuel)?;
+
+    let mut linker = Linker::new(&ENGINE);
+    preview1::add_to_linker_async(&mut linker, |state: &mut WasmState| &mut state.wasi)?;
+    add_host_functions(&mut linker)?;
+    let instance = linker.instantiate_async(&mut store, &module).await?;
+
+    let pipe = WasmPipe {
+        pipe: pipe.to_string(),
+        pipe_dir,
+        permissions,
+        store,
+        instance,
+        fuel,
+    };
+    // subscribed before the pipe runs, events sent while it starts aren't missed
+    let events = screenpipe_events::subscribe_to_all_events();
+    Ok(pipe.run(manifest.crons, events))
+}
+
+/// Exit code a wasm pipe ended with, unset when it trapped.
+pub fn exit_code(error: &anyhow::Error) -> Option<i32> {
+    error.downcast_ref::<I32Exit>().map(|exit| exit.0)
+}
+
+struct WasmPipe {
+    pipe: String,
+    pipe_dir: PathBuf,
+    permissions: PipePermissions,
+    store: Store<WasmState>,
+    instance: Instance,
+    fuel: u64,
+}
+
+impl WasmPipe {
+    async fn run(mut self, crons: Vec<PipeCron>, mut events: EventSubscription) -> Result<()> {
+        for export in ["_initialize", "_start"] {
+            let Ok(func) = self
+                .instance
+                .get_typed_func::<(), ()>(&mut self.store, export)
+            else {
+                continue;
+            };
+            self.store.set_fuel(self.fuel)?;
+            if let Err(e) = func.call_async(&mut self.store, ()).await {
+                // exiting ends the pipe like the process of other runtimes
+                return match exit_code(&e) {
+                    Some(0) => Ok(()),
+                    _ => Err(e),
+                };
+            }
+        }
+
+        let subscribed = !self.store.data().host.subscriptions.is_empty();
+        if !subscribed && crons.is_empty() {
+            return Ok(());
+        }
+        let on_event = self
+            .instance
+            .get_typed_func::<i32, ()>(&mut self.store, "on_event")
+            .ok();
+        if subscribed && on_event.is_none() {
+            anyhow::bail!("the pipe subscribed to events but exports no on_event");
+        }
+        let on_cron = self
+            .instance
+            .get_typed_func::<i32, i32>(&mut self.store, "on_cron")
+            .ok();
+        if !crons.is_empty() && on_cron.is_none() {
+            anyhow::bail!("the pipe has crons but exports no on_cron");
+        }
+
+        let (calls, mut cron_calls) = mpsc::channel::<CronCall>(1);
+        if !crons.is_empty() {
+            let context = CronContext {
+                pipe: self.pipe.clone(),
+                pipe_dir: self.pipe_dir.clone(),
+                base_url: None,
+                secret: generate_cron_secret(),
+                permissions: Some(self.permissions.clone()),
+                env: Vec::new(),
+                runtime: PipeRuntime::Wasm,
+                handler: Some(Arc::new(WasmCronHandler { calls })),
+            };
+            schedule_pipe_crons(context, &crons).await;
+        }
+        info!("[{}] wasm pipe waiting for events and crons", self.pipe);
+
+        loop {
+            tokio::select! {
+                event = events.next() => {
+                    let Some(event) = event else {
+                        anyhow::bail!("the event bus closed");
+                    };
+                    let subscriptions = &self.store.data().host.subscriptions;
+                    if !subscriptions.contains(&event.name) && !subscriptions.contains("*") {
+                        continue;
+                    }
+                    if let Some(on_event) = &on_event {
+                        let payload = json!({ "name": event.name, "data": event.data });
+                        self.call(on_event, &payload).await?;
+                    }
+                }
+                Some((cron, reply)) = cron_calls.recv() => {
+                    let Some(on_cron) = &on_cron else { continue };
+                    let payload = json!({
+                        "job": cron.job(),
+                        "path": cron.path,
+                        "script": cron.script,
+                        "schedule": cron.schedule,
+                    });
+                    match self.call(on_cron, &payload).await {
+                        Ok(0) => {
+                            let _ = reply.send(Ok(None));
+                        }
+                        Ok(status) => {
+                            let _ = reply.send(Err(anyhow::anyhow!("on_cron returned {}", status)));
+                        }
+                        Err(e) => {
+                            let _ = reply.send(Err(anyhow::anyhow!("the pipe failed: {:#}", e)));
+                            return Err(e);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Calls `func` of the pipe with `payload`, which it reads with `response_read`.
+    async fn call<R: WasmResults>(
+        &mut self,
+        func: &TypedFunc<i32, R>,
+        payload: &Value,
+    ) -> Result<R> {
+        let payload = serde_json::to_vec(payload)?;
+        let len = i32::try_from(payload.len())?;
+        self.store.data_mut().host.response = payload;
+        self.store.set_fuel(self.fuel)?;
+        func.call_async(&mut self.store, len).await
+    }
+}
+
+/// Hands the cron jobs of a wasm pipe to its `on_cron` export.
+#[derive(Debug)]
+struct WasmCronHandler {
+    calls: mpsc::Sender<CronCall>,
+}
+
+#[async_trait]
+impl CronHandler for WasmCronHandler {
+    async fn run(&self, cron: &PipeCron) -> Result<Option<String>> {
+        let (reply, result) = oneshot::channel();
+        self.calls
+            .send((cron.clone(), reply))
+            .await
+            .map_err(|_| anyhow::anyhow!("pipe is not running"))?;
+        result
+            .await
+            .map_err(|_| anyhow::anyhow!("pipe stopped during the run"))?
+    }
+}
+
+fn add_host_functions(linker: &mut Linker<WasmState>) -> Result<()> {
+    linker.func_wrap(
+        HOST_MODULE,
+        "response_read",
+        |mut caller: Caller<'_, WasmState>, ptr: i32| -> Result<i32> {
+            let memory = guest_memory(&mut caller)?;
+            let (memory_data, state) = memory.data_and_store_mut(&mut caller);
+            let response = &state.host.response;
+            let start = usize::try_from(ptr)?;
+            memory_data
+                .get_mut(start..start + response.len())
+                .ok_or_else(|| anyhow::anyhow!("response_read out of bounds"))?
+                .copy_from_slice(response);
+            Ok(i32::try_from(response.len())?)
+        },
+    )?;
+
+    linker.func_wrap_async(
+        HOST_MODULE,
+        "search",
+        |mut caller: Caller<'_, WasmState>, (ptr, len): (i32, i32)| {
+            Box::new(async move {
+                let request = read_guest(&mut caller, ptr, len)?;
+                let result = search(&caller.data().host, &request).await;
+                respond(&mut caller, "search", result)
+            })
+        },
+    )?;
+
+    linker.func_wrap_async(
+        HOST_MODULE,
+        "frame",
+        |mut caller: Caller<'_, WasmState>, (frame_id,): (i64,)| {
+            Box::new(async move {
+                let result = frame(&caller.data().host, frame_id).await;
+                respond(&mut caller, "frame", result)
+            })
+        },
+    )?;
+
+    linker.func_wrap(
+        HOST_MODULE,
+        "subscribe",
+        |mut caller: Caller<'_, WasmState>, ptr: i32, len: i32| -> Result<i32> {
+            let event = read_guest_str(&mut caller, ptr, len)?;
+            let host = &mut caller.data_mut().host;
+            let result = if host.permissions.events.can_subscribe(&event) {
+                host.subscriptions.insert(event);
+                Ok(Vec::new())
+            } else {
+                Err(denied("subscribing to", &event, "events.subscribe"))
+            };
+            respond(&mut caller, "subscribe", result)
+        },
+    )?;
+
+    linker.func_wrap(
+        HOST_MODULE,
+        "send_event",
+        |mut caller: Caller<'_, WasmState>,
+         name_ptr: i32,
+         name_len: i32,
+         data_ptr: i32,
+         data_len: i32|
+         -> Result<i32> {
+            let event = read_guest_str(&mut caller, name_ptr, name_len)?;
+            let data = read_guest(&mut caller, data_ptr, data_len)?;
+            let result = if caller.data().host.permissions.events.can_send(&event) {
+                serde_json::from_slice::<Value>(&data)
+                    .map_err(|e| format!("invalid event data: {}", e))
+                    .and_then(|data| {
+                        screenpipe_events::send_event(event, data)
+                            .map(|_| Vec::new())
+                            .map_err(|e| e.to_string())
+                    })
+            } else {
+                Err(denied("sending", &event, "events.send"))
+            };
+            respond(&mut caller, "send_event", result)
+        },
+    )?;
+
+    linker.func_wrap_async(
+        HOST_MODULE,
+        "kv_get",
+        |mut caller: Caller<'_, WasmState>, (ptr, len): (i32, i32)| {
+            Box::new(async move {
+                let key = read_guest_str(&mut caller, ptr, len)?;
+                let result = kv_get(&caller.data().host, &key).await;
+                respond(&mut caller, "kv_get", result)
+            })
+        },
+    )?;
+
+    linker.func_wrap_async(
+        HOST_MODULE,
+        "kv_set",
+        |mut caller: Caller<'_, WasmState>,
+         (key_ptr, key_len, value_ptr, value_len): (i32, i32, i32, i32)| {
+            Box::new(async move {
+                let key = read_guest_str(&mut caller, key_ptr, key_len)?;
+                let value = read_guest(&mut caller, value_ptr, value_len)?;
+                let result = kv_set(&caller.data().host, key, &value).await;
+                respond(&mut caller, "kv_set", result)
+            })
+        },
+    )?;
+
+    linker.func_wrap_async(
+        HOST_MODULE,
+        "kv_delete",
+        |mut caller: Caller<'_, WasmState>, (ptr, len): (i32, i32)| {
+            Box::new(async move {
+                let key = read_guest_str(&mut caller, ptr, len)?;
+                let result = kv_delete(&caller.data().host, &key).await;
+                respond(&mut caller, "kv_delete", result)
+            })
+        },
+    )?;
+
+    linker.func_wrap_async(
+        HOST_MODULE,
+        "http_fetch",
+        |mut caller: Caller<'_, WasmState>, (ptr, len): (i32, i32)| {
+            Box::new(async move {
+                let request = read_guest(&mut caller, ptr, len)?;
+                let result = http_fetch(&caller.data().host, &request).await;
+                respond(&mut caller, "http_fetch", result)
+            })
+        },
+    )?;
+
+    Ok(())
+}
+
+fn guest_memory(caller: &mut Caller<'_, WasmState>) -> Result<Memory> {
+    caller
+        .get_export("memory")
+        .and_then(Extern::into_memory)
+        .ok_or_else(|| anyhow::anyhow!("the pipe exports no memory"))
+}
+
+fn read_guest(caller: &mut Caller<'_, WasmState>, ptr: i32, len: i32) -> Result<Vec<u8>> {
+    let memory = guest_memory(caller)?;
+    let start = usize::try_from(ptr)?;
+    let len = usize::try_from(len)?;
+    memory
+        .data(&caller)
+        .get(start..start + len)
+        .map(<[u8]>::to_vec)
+        .ok_or_else(|| anyhow::anyhow!("host call reads out of bounds"))
+}
+
+fn read_guest_str(caller: &mut Caller<'_, WasmState>, ptr: i32, len: i32) -> Result<String> {
+    Ok(String::from_utf8(read_guest(caller, ptr, len)?)?)
+}
+
+/// Keeps the response of a host call for `response_read` and returns its length to the
+/// pipe, negated for errors.
+fn respond(caller: &mut Caller<'_, WasmState>, function: &str, result: HostResult) -> Result<i32> {
+    let host = &mut caller.data_mut().host;
+    let (response, sign) = match result {
+        Ok(response) => (response, 1),
+        Err(error) => {
+            debug!("[{}] {} failed: {}", host.pipe, function, error);
+            (error.into_bytes(), -1)
+        }
+    };
+    let len = i32::try_from(response.len())?;
+    host.response = response;
+    Ok(sign * len)
+}
+
+fn denied(action: &str, what: &str, permission: &str) -> String {
+    format!(
+        "permission denied: {} {} needs it in permissions.{} of pipe.json",
+        action, what, permission
+    )
+}
+
+fn store_host(host: &HostContext) -> std::result::Result<&PipeHost, String> {
+    host.host
+        .as_ref()
+        .ok_or_else(|| "screenpipe's data isn't available to pipes yet".to_string())
+}
+
+/// Whether the `content_types` permission covers `content_type`.
+fn can_read(permissions: &PipePermissions, content_type: &ContentType) -> bool {
+    let allowed = |permission: ContentPermission| {
+        permissions
+            .content_types
+            .iter()
+            .any(|p| *p == ContentPermission::All || *p == permission)
+    };
+    match content_type {
+        ContentType::All => {
+            allowed(ContentPermission::Ocr)
+                && allowed(ContentPermission::Audio)
+                && allowed(ContentPermission::Ui)
+        }
+        ContentType::OCR => allowed(ContentPermission::Ocr),
+        ContentType::Audio => allowed(ContentPermission::Audio),
+        ContentType::UI => allowed(ContentPermission::Ui),
+        ContentType::AudioAndUi => {
+            allowed(ContentPermission::Audio) && allowed(ContentPermission::Ui)
+        }
+        ContentType::OcrAndUi => allowed(ContentPermission::Ocr) && allowed(ContentPermission::Ui),
+        ContentType::AudioAndOcr => {
+            allowed(ContentPermission::Audio) && allowed(ContentPermission::Ocr)
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SearchRequest {
+    #[serde(default)]
+    q: String,
+    #[serde(default)]
+    content_type: ContentType,
+    #[serde(default = "default_search_limit")]
+    limit: u32,
+    #[serde(default)]
+    offset: u32,
+    start_time: Option<DateTime<Utc>>,
+    end_time: Option<DateTime<Utc>>,
+    app_name: Option<String>,
+    window_name: Option<String>,
+    min_length: Option<usize>,
+    max_length: Option<usize>,
+    speaker_ids: Option<Vec<i64>>,
+    frame_name: Option<String>,
+}
+
+fn default_search_limit() -> u32 {
+    20
+}
+
+async fn search(host: &HostContext, request: &[u8]) -> HostResult {
+    let request: SearchRequest =
+        serde_json::from_slice(request).map_err(|e| format!("invalid search: {}", e))?;
+    if !can_read(&host.permissions, &request.content_type) {
+        return Err(denied(
+            "searching",
+            &format!("{:?} content", request.content_type),
+            "content_types",
+        ));
+    }
+    let results = store_host(host)?
+        .store
+        .search(
+            &request.q,
+            request.content_type,
+            request.limit.min(MAX_SEARCH_LIMIT),
+            request.offset,
+            request.start_time,
+            request.end_time,
+            request.app_name.as_deref(),
+            request.window_name.as_deref(),
+            request.min_length,
+            request.max_length,
+            request.speaker_ids,
+            request.frame_name.as_deref(),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+    serde_json::to_vec(&results).map_err(|e| e.to_string())
+}
+
+async fn frame(host: &HostContext, frame_id: i64) -> HostResult {
+    if !can_read(&host.permissions, &ContentType::OCR) {
+        return Err(denied("reading", "frames", "content_types"));
+    }
+    let store_host = store_host(host)?;
+    let (file_path, offset_index) = store_host
+        .store
+        .get_frame(frame_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("frame {} not found", frame_id))?;
+    let video = store_host
+        .media
+        .resolve(&file_path)
+        .await
+        .map_err(|e| e.to_string())?;
+    let image = extract_frame_from_video(&video.to_string_lossy(), offset_index)
+        .await
+        .map_err(|e| e.to_string())?;
+    let bytes = tokio::fs::read(&image).await.map_err(|e| e.to_string());
+    let _ = tokio::fs::remove_file(&image).await;
+    bytes
+}
+
+async fn read_kv(host: &HostContext) -> std::result::Result<Map<String, Value>, String> {
+    match tokio::fs::read(host.pipe_dir.join(PIPE_KV_FILE)).await {
+        Ok(content) => serde_json::from_slice(&content).map_err(|e| e.to_string()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Map::new()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+async fn write_kv(host: &HostContext, kv: &Map<String, Value>) -> std::result::Result<(), String> {
+    let content = serde_json::to_vec_pretty(kv).map_err(|e| e.to_string())?;
+    if content.len() > MAX_KV_BYTES {
+        return Err(format!(
+            "storage would take {} bytes, pipes may keep at most {}",
+            content.len(),
+            MAX_KV_BYTES
+        ));
+    }
+    tokio::fs::write(host.pipe_dir.join(PIPE_KV_FILE), content)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+async fn kv_get(host: &HostContext, key: &str) -> HostResult {
+    if !host.permissions.storage {
+        return Err(denied("reading", "storage", "storage"));
+    }
+    let value = read_kv(host).await?.remove(key).unwrap_or(Value::Null);
+    serde_json::to_vec(&value).map_err(|e| e.to_string())
+}
+
+async fn kv_set(host: &HostContext, key: String, value: &[u8]) -> HostResult {
+    if !host.permissions.storage {
+        return Err(denied("writing", "storage", "storage"));
+    }
+    let value: Value =
+        serde_json::from_slice(value).map_err(|e| format!("invalid value: {}", e))?;
+    let mut kv = read_kv(host).await?;
+    kv.insert(key, value);
+    write_kv(host, &kv).await?;
+    Ok(Vec::new())
+}
+
+async fn kv_delete(host: &HostContext, key: &str) -> HostResult {
+    if !host.permissions.storage {
+        return Err(denied("writing", "storage", "storage"));
+    }
+    let mut kv = read_kv(host).await?;
+    if kv.remove(key).is_some() {
+        write_kv(host, &kv).await?;
+    }
+    Ok(Vec::new())
+}
+
+#[derive(Deserialize)]
+struct FetchRequest {
+    url: String,
+    #[serde(default = "default_method")]
+    method: String,
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    body: Option<String>,
+}
+
+fn default_method() -> String {
+    "GET".to_string()
+}
+
+async fn http_fetch(host: &HostContext, request: &[u8]) -> HostResult {
+    let request: FetchRequest =
+        serde_json::from_slice(request).map_err(|e| format!("invalid request: {}", e))?;
+    let url = reqwest::Url::parse(&request.url).map_err(|e| format!("invalid url: {}", e))?;
+    let (Some(domain), Some(port)) = (url.host_str(), url.port_or_known_default()) else {
+        return Err(format!("{} has no host", url));
+    };
+    if !host.permissions.allowlist_allows(domain, port) {
+        return Err(denied("connecting to", domain, "network"));
+    }
+    let method = reqwest::Method::from_bytes(request.method.to_uppercase().as_bytes())
+        .map_err(|e| e.to_string())?;
+    let mut builder = host.http.request(method, url);
+    for (name, value) in &request.headers {
+        builder = builder.header(name, value);
+    }
+    if let Some(body) = request.body {
+        builder = builder.body(body);
+    }
+    let mut response = builder.send().await.map_err(|e| e.to_string())?;
+    let status = response.status().as_u16();
+    let headers: HashMap<&str, &str> = response
+        .headers()
+        .iter()
+        .filter_map(|(name, value)| Some((name.as_str(), value.to_str().ok()?)))
+        .collect();
+    let headers = json!(headers);
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+        if body.len() + chunk.len() > MAX_HTTP_BODY_BYTES {
+            return Err(format!(
+                "response body is larger than {} MB",
+                MAX_HTTP_BODY_BYTES / 1024 / 1024
+            ));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    let body = String::from_utf8_lossy(&body);
+    serde_json::to_vec(&json!({ "status": status, "headers": headers, "body": body }))
+        .map_err(|e| e.to_string())
+}
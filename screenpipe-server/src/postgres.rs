@@ -0,0 +1,2198 @@
+//! PostgreSQL implementation of [`Store`], for a shared index written by several recorders.
+//!
+//! Full-text search runs on generated `tsvector` columns (`websearch_to_tsquery`
+//! syntax instead of fts5 `MATCH`), speaker and text embeddings are pgvector
+//! columns compared with cosine distance.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::future::try_join_all;
+use image::DynamicImage;
+use pgvector::Vector;
+use screenpipe_audio::{AudioDevice, DeviceType};
+use screenpipe_core::CronRun;
+use screenpipe_vision::OcrEngine;
+use sqlx::postgres::{PgPool, PgPoolOptions};
+use sqlx::{Column, Row, TypeInfo, ValueRef};
+use tracing::{debug, error};
+
+use crate::conversations::{Conversation, ConversationSpec, ConversationTurn};
+use crate::db_types::{
+    AudioChunksResponse, AudioEntry, AudioResult, AudioResultRaw, ChunkKind, ContentType,
+    FrameData, OCREntry, OCRResult, OCRResultRaw, OffloadCandidate, SearchCursor, SearchKind,
+    Speaker, SpeakerCursor, TagContentType, TimeSeriesChunk, UiContent, UnnamedSpeaker,
+};
+use crate::meetings::{Meeting, MeetingSpec};
+use crate::rules::{Rule, RuleSpec};
+use crate::store::{
+    conversation_from_row, conversation_turn_from_row, cron_run_from_row, meeting_from_row,
+    rule_from_row, webhook_dead_letter_from_row, webhook_delivery_from_row, webhook_from_row,
+    CaptureStore, ConversationRow, ConversationStore, ConversationTurnRow, CronRunRow,
+    EmbeddingStore, MediaChunkStore, MeetingRow, MeetingStore, PipeCronStore, RuleRow, RuleStore,
+    SearchStore, SpeakerStore, Store, TagStore, TimelineStore, WebhookDeadLetterRow,
+    WebhookDeliveryRow, WebhookRow, WebhookStore,
+};
+use crate::video_utils::VideoMetadata;
+use crate::webhooks::{Webhook, WebhookDeadLetter, WebhookDelivery, WebhookSpec};
+
+/// Same thresholds as the sqlite store, both are cosine distances.
+const SPEAKER_MATCH_THRESHOLD: f64 = 0.5;
+const SIMILAR_SPEAKER_THRESHOLD: f64 = 0.8;
+
+const MEETING_COLUMNS: &str = "id, start_time, end_time, app, title, participants, signals, first_frame_id, last_frame_id, first_transcription_id, last_transcription_id, created_at, updated_at";
+
+/// Points meeting `$1` at the frames and transcriptions captured while it went on.
+const LINK_MEETING_CONTENT: &str = "UPDATE meetings SET
+    first_frame_id = (SELECT MIN(f.id) FROM frames f WHERE f.timestamp >= meetings.start_time AND (meetings.end_time IS NULL OR f.timestamp <= meetings.end_time)),
+    last_frame_id = (SELECT MAX(f.id) FROM frames f WHERE f.timestamp >= meetings.start_time AND (meetings.end_time IS NULL OR f.timestamp <= meetings.end_time)),
+    first_transcription_id = (SELECT MIN(t.id) FROM audio_transcriptions t WHERE t.timestamp >= meetings.start_time AND (meetings.end_time IS NULL OR t.timestamp <= meetings.end_time)),
+    last_transcription_id = (SELECT MAX(t.id) FROM audio_transcriptions t WHERE t.timestamp >= meetings.start_time AND (meetings.end_time IS NULL OR t.timestamp <= meetings.end_time))
+    WHERE id = $1";
+
+const CONVERSATION_COLUMNS: &str = "id, start_time, end_time, devices, speakers, transcript, turn_count, first_transcription_id, last_transcription_id, created_at";
+
+/// Last 3 transcriptions of every speaker, used as `audio_samples` in speaker metadata.
+const SPEAKER_SAMPLES_CTE: &str = r#"
+    recent_audio AS (
+        SELECT
+            at.speaker_id,
+            ac.file_path,
+            at.transcription,
+            at.start_time,
+            at.end_time,
+            ROW_NUMBER() OVER (PARTITION BY at.speaker_id ORDER BY at.timestamp DESC) AS rn
+        FROM audio_transcriptions at
+        JOIN audio_chunks ac ON at.audio_chunk_id = ac.id
+        WHERE at.speaker_id IS NOT NULL
+    ),
+    speaker_samples AS (
+        SELECT
+            speaker_id,
+            jsonb_agg(DISTINCT jsonb_build_object(
+                'path', file_path,
+                'transcript', transcription,
+                'start_time', start_time,
+                'end_time', end_time
+            )) AS audio_samples
+        FROM recent_audio
+        WHERE rn <= 3
+        GROUP BY speaker_id
+    )
+"#;
+
+const SPEAKER_METADATA: &str = "(screenpipe_json_object(s.metadata) || jsonb_build_object('audio_samples', ss.audio_samples))::text";
+
+pub struct PostgresStore {
+    pub pool: PgPool,
+}
+
+impl PostgresStore {
+    pub async fn new(url: &str) -> Result<Self, sqlx::Error> {
+        debug!("connecting to postgres store");
+        let pool = PgPoolOptions::new()
+            .max_connections(20)
+            .min_connections(2)
+            .acquire_timeout(Duration::from_secs(10))
+            .connect(url)
+            .await?;
+
+        sqlx::migrate!("./src/migrations_postgres")
+            .run(&pool)
+            .await?;
+
+        Ok(Self { pool })
+    }
+
+    async fn add_tag_ids(
+        &self,
+        tags: Vec<String>,
+        link_query: &str,
+        id: i64,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        for tag in tags {
+            let tag_id: i64 = sqlx::query_scalar(
+                "INSERT INTO tags (name) VALUES ($1) ON CONFLICT (name) DO UPDATE SET name = EXCLUDED.name RETURNING id",
+            )
+            .bind(&tag)
+            .fetch_one(&mut *tx)
+            .await?;
+
+            sqlx::query(link_query)
+                .bind(id)
+                .bind(tag_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn count_ocr(
+        &self,
+        query: &str,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>,
+        window_name: Option<&str>,
+        min_length: Option<usize>,
+        max_length: Option<usize>,
+        frame_name: Option<&str>,
+        cap: Option<i64>,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*) FROM (
+            SELECT DISTINCT frames.id
+            FROM ocr_text
+            JOIN frames ON ocr_text.frame_id = frames.id
+            WHERE ($1 = '' OR ocr_text.search_vector @@ websearch_to_tsquery('simple', $1))
+                AND ($2::timestamptz IS NULL OR frames.timestamp >= $2)
+                AND ($3::timestamptz IS NULL OR frames.timestamp <= $3)
+                AND ($4::text IS NULL OR ocr_text.app_name ILIKE '%' || $4 || '%')
+                AND ($5::text IS NULL OR ocr_text.window_name ILIKE '%' || $5 || '%')
+                AND ($6::bigint IS NULL OR COALESCE(ocr_text.text_length, length(ocr_text.text)) >= $6)
+                AND ($7::bigint IS NULL OR COALESCE(ocr_text.text_length, length(ocr_text.text)) <= $7)
+                AND ($8::text IS NULL OR frames.name ILIKE '%' || $8 || '%')
+            LIMIT $9::bigint
+            ) matches
+            "#,
+        )
+        .bind(query)
+        .bind(start_time)
+        .bind(end_time)
+        .bind(app_name)
+        .bind(window_name)
+        .bind(min_length.map(|l| l as i64))
+        .bind(max_length.map(|l| l as i64))
+        .bind(frame_name)
+        .bind(cap)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn count_audio(
+        &self,
+        query: &str,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        min_length: Option<usize>,
+        max_length: Option<usize>,
+        speaker_ids: &[i64],
+        skip_empty: bool,
+        cap: Option<i64>,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*) FROM (
+            SELECT 1
+            FROM audio_transcriptions
+            WHERE ($1 = '' OR audio_transcriptions.search_vector @@ websearch_to_tsquery('simple', $1))
+                AND ($2::timestamptz IS NULL OR audio_transcriptions.timestamp >= $2)
+                AND ($3::timestamptz IS NULL OR audio_transcriptions.timestamp <= $3)
+                AND ($4::bigint IS NULL OR COALESCE(audio_transcriptions.text_length, length(audio_transcriptions.transcription)) >= $4)
+                AND ($5::bigint IS NULL OR COALESCE(audio_transcriptions.text_length, length(audio_transcriptions.transcription)) <= $5)
+                AND (cardinality($6::bigint[]) = 0 OR audio_transcriptions.speaker_id = ANY($6))
+                AND (NOT $7 OR audio_transcriptions.transcription != '')
+            LIMIT $8::bigint
+            ) matches
+            "#,
+        )
+        .bind(query)
+        .bind(start_time)
+        .bind(end_time)
+        .bind(min_length.map(|l| l as i64))
+        .bind(max_length.map(|l| l as i64))
+        .bind(speaker_ids)
+        .bind(skip_empty)
+        .bind(cap)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn count_ui(
+        &self,
+        query: &str,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>,
+        window_name: Option<&str>,
+        min_length: Option<usize>,
+        max_length: Option<usize>,
+        skip_empty: bool,
+        cap: Option<i64>,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*) FROM (
+            SELECT 1
+            FROM ui_monitoring
+            WHERE ($1 = '' OR ui_monitoring.search_vector @@ websearch_to_tsquery('simple', $1))
+                AND ($2::timestamptz IS NULL OR ui_monitoring.timestamp >= $2)
+                AND ($3::timestamptz IS NULL OR ui_monitoring.timestamp <= $3)
+                AND ($4::text IS NULL OR ui_monitoring.app ILIKE '%' || $4 || '%')
+                AND ($5::text IS NULL OR ui_monitoring."window" ILIKE '%' || $5 || '%')
+                AND ($6::bigint IS NULL OR COALESCE(ui_monitoring.text_length, length(ui_monitoring.text_output)) >= $6)
+                AND ($7::bigint IS NULL OR COALESCE(ui_monitoring.text_length, length(ui_monitoring.text_output)) <= $7)
+                AND (NOT $8 OR ui_monitoring.text_output != '')
+            LIMIT $9::bigint
+            ) matches
+            "#,
+        )
+        .bind(query)
+        .bind(start_time)
+        .bind(end_time)
+        .bind(app_name)
+        .bind(window_name)
+        .bind(min_length.map(|l| l as i64))
+        .bind(max_length.map(|l| l as i64))
+        .bind(skip_empty)
+        .bind(cap)
+        .fetch_one(&self.pool)
+        .await
+    }
+}
+
+fn to_vector(embedding: &[f32]) -> Vector {
+    Vector::from(embedding.to_vec())
+}
+
+fn ocr_result_from_raw(raw: OCRResultRaw) -> OCRResult {
+    OCRResult {
+        frame_id: raw.frame_id,
+        ocr_text: raw.ocr_text,
+        text_json: raw.text_json,
+        timestamp: raw.timestamp,
+        frame_name: raw.frame_name,
+        file_path: raw.file_path,
+        offset_index: raw.offset_index,
+        app_name: raw.app_name,
+        ocr_engine: raw.ocr_engine,
+        window_name: raw.window_name,
+        tags: raw
+            .tags
+            .map(|t| t.split(',').map(String::from).collect())
+            .unwrap_or_default(),
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct TimelineFrameRow {
+    id: i64,
+    timestamp: DateTime<Utc>,
+    offset_index: i64,
+    text: Option<String>,
+    app_name: Option<String>,
+    window_name: Option<String>,
+    screen_device: String,
+    video_path: String,
+}
+
+#[derive(sqlx::FromRow)]
+struct TimelineAudioRow {
+    timestamp: DateTime<Utc>,
+    transcription: String,
+    audio_device: String,
+    is_input_device: bool,
+    audio_path: String,
+    duration_secs: f64,
+}
+
+#[async_trait]
+impl Store for PostgresStore {
+    fn backend(&self) -> &'static str {
+        "postgres"
+    }
+}
+
+#[async_trait]
+impl CaptureStore for PostgresStore {
+    async fn insert_video_chunk(
+        &self,
+        file_path: &str,
+        device_name: &str,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            "INSERT INTO video_chunks (file_path, device_name) VALUES ($1, $2) RETURNING id",
+        )
+        .bind(file_path)
+        .bind(device_name)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    async fn insert_frame(
+        &self,
+        device_name: &str,
+        timestamp: Option<DateTime<Utc>>,
+    ) -> Result<i64, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let video_chunk: Option<(i64, String)> = sqlx::query_as(
+            "SELECT id, file_path FROM video_chunks WHERE device_name = $1 ORDER BY id DESC LIMIT 1",
+        )
+        .bind(device_name)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some((video_chunk_id, file_path)) = video_chunk else {
+            debug!("no video chunk found for {}, skipping frame", device_name);
+            return Ok(0);
+        };
+
+        // serialize offset allocation per chunk across recorders
+        sqlx::query("SELECT pg_advisory_xact_lock($1)")
+            .bind(video_chunk_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let id = sqlx::query_scalar(
+            r#"
+            INSERT INTO frames (video_chunk_id, offset_index, timestamp, name)
+            VALUES (
+                $1,
+                (SELECT COALESCE(MAX(offset_index), -1) + 1 FROM frames WHERE video_chunk_id = $1),
+                $2,
+                $3
+            )
+            RETURNING id
+            "#,
+        )
+        .bind(video_chunk_id)
+        .bind(timestamp.unwrap_or_else(Utc::now))
+        .bind(file_path)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    async fn insert_ocr_text(
+        &self,
+        frame_id: i64,
+        text: &str,
+        text_json: &str,
+        app_name: &str,
+        window_name: &str,
+        ocr_engine: Arc<OcrEngine>,
+        focused: bool,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO ocr_text (frame_id, text, text_json, app_name, ocr_engine, window_name, focused, text_length) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+        )
+        .bind(frame_id)
+        .bind(text)
+        .bind(text_json)
+        .bind(app_name)
+        .bind(format!("{:?}", *ocr_engine))
+        .bind(window_name)
+        .bind(focused)
+        .bind(text.len() as i64)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_audio_chunk(&self, file_path: &str) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            "INSERT INTO audio_chunks (file_path, timestamp) VALUES ($1, $2) RETURNING id",
+        )
+        .bind(file_path)
+        .bind(Utc::now())
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    async fn get_or_insert_audio_chunk(&self, file_path: &str) -> Result<i64, sqlx::Error> {
+        let id: Option<i64> =
+            sqlx::query_scalar("SELECT id FROM audio_chunks WHERE file_path = $1 LIMIT 1")
+                .bind(file_path)
+                .fetch_optional(&self.pool)
+                .await?;
+        match id {
+            Some(id) => Ok(id),
+            None => self.insert_audio_chunk(file_path).await,
+        }
+    }
+
+    async fn insert_audio_transcription(
+        &self,
+        audio_chunk_id: i64,
+        transcription: &str,
+        offset_index: i64,
+        transcription_engine: &str,
+        device: &AudioDevice,
+        speaker_id: Option<i64>,
+        start_time: Option<f64>,
+        end_time: Option<f64>,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"
+            INSERT INTO audio_transcriptions (
+                audio_chunk_id, transcription, offset_index, timestamp, transcription_engine,
+                device, is_input_device, speaker_id, start_time, end_time, text_length
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            RETURNING id
+            "#,
+        )
+        .bind(audio_chunk_id)
+        .bind(transcription)
+        .bind(offset_index)
+        .bind(Utc::now())
+        .bind(transcription_engine)
+        .bind(&device.name)
+        .bind(device.device_type == DeviceType::Input)
+        .bind(speaker_id)
+        .bind(start_time)
+        .bind(end_time)
+        .bind(transcription.len() as i64)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    async fn update_audio_transcription(
+        &self,
+        audio_chunk_id: i64,
+        transcription: &str,
+    ) -> Result<i64, sqlx::Error> {
+        let affected = sqlx::query(
+            "UPDATE audio_transcriptions SET transcription = $1, text_length = $2 WHERE audio_chunk_id = $3",
+        )
+        .bind(transcription)
+        .bind(transcription.len() as i64)
+        .bind(audio_chunk_id)
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        Ok(affected as i64)
+    }
+
+    async fn set_audio_chunk_timestamp(
+        &self,
+        audio_chunk_id: i64,
+        timestamp: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("UPDATE audio_chunks SET timestamp = $1 WHERE id = $2")
+            .bind(timestamp)
+            .bind(audio_chunk_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("UPDATE audio_transcriptions SET timestamp = $1 WHERE audio_chunk_id = $2")
+            .bind(timestamp)
+            .bind(audio_chunk_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await
+    }
+
+    async fn insert_ui_monitoring(
+        &self,
+        text_output: &str,
+        app: &str,
+        window: &str,
+        timestamp: DateTime<Utc>,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"INSERT INTO ui_monitoring (text_output, timestamp, app, "window", text_length) VALUES ($1, $2, $3, $4, $5) RETURNING id"#,
+        )
+        .bind(text_output)
+        .bind(timestamp)
+        .bind(app)
+        .bind(window)
+        .bind(text_output.len() as i64)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    async fn create_video_with_frames(
+        &self,
+        file_path: &str,
+        frames: Vec<DynamicImage>,
+        metadata: VideoMetadata,
+    ) -> Result<Vec<i64>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let device_name = metadata
+            .device_name
+            .clone()
+            .unwrap_or_else(|| "imported_files".to_string());
+
+        let video_chunk_id: i64 = sqlx::query_scalar(
+            "INSERT INTO video_chunks (device_name, file_path) VALUES ($1, $2) RETURNING id",
+        )
+        .bind(device_name)
+        .bind(file_path)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let mut frame_ids = Vec::with_capacity(frames.len());
+        for i in 0..frames.len() {
+            let frame_timestamp = metadata.creation_time
+                + chrono::Duration::milliseconds((i as f64 * (1000.0 / metadata.fps)) as i64);
+
+            let frame_id: i64 = sqlx::query_scalar(
+                "INSERT INTO frames (video_chunk_id, offset_index, timestamp, name) VALUES ($1, $2, $3, $4) RETURNING id",
+            )
+            .bind(video_chunk_id)
+            .bind(i as i64)
+            .bind(frame_timestamp)
+            .bind(metadata.name.as_deref().unwrap_or(file_path))
+            .fetch_one(&mut *tx)
+            .await?;
+            frame_ids.push(frame_id);
+        }
+
+        tx.commit().await?;
+        Ok(frame_ids)
+    }
+
+    async fn update_frame_name(&self, frame_id: i64, name: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE frames SET name = $1 WHERE id = $2")
+            .bind(name)
+            .bind(frame_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn update_video_chunk_frames_names(
+        &self,
+        video_chunk_id: i64,
+        name: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE frames SET name = $1 WHERE video_chunk_id = $2")
+            .bind(name)
+            .bind(video_chunk_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SearchStore for PostgresStore {
+    async fn search_ocr(
+        &self,
+        query: &str,
+        limit: u32,
+        offset: u32,
+        cursor: Option<&SearchCursor>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>,
+        window_name: Option<&str>,
+        min_length: Option<usize>,
+        max_length: Option<usize>,
+        frame_name: Option<&str>,
+    ) -> Result<Vec<OCRResult>, sqlx::Error> {
+        let bound = cursor.map(|c| c.bound_for(SearchKind::Ocr));
+        let raw_results: Vec<OCRResultRaw> = sqlx::query_as(
+            r#"
+            SELECT
+                ocr_text.frame_id,
+                ocr_text.text AS ocr_text,
+                ocr_text.text_json,
+                frames.timestamp,
+                COALESCE(frames.name, '') AS frame_name,
+                video_chunks.file_path,
+                frames.offset_index,
+                ocr_text.app_name,
+                ocr_text.ocr_engine,
+                ocr_text.window_name,
+                string_agg(tags.name, ',') AS tags
+            FROM ocr_text
+            JOIN frames ON ocr_text.frame_id = frames.id
+            JOIN video_chunks ON frames.video_chunk_id = video_chunks.id
+            LEFT JOIN vision_tags ON frames.id = vision_tags.vision_id
+            LEFT JOIN tags ON vision_tags.tag_id = tags.id
+            WHERE ($1 = '' OR ocr_text.search_vector @@ websearch_to_tsquery('simple', $1))
+                AND ($2::timestamptz IS NULL OR frames.timestamp >= $2)
+                AND ($3::timestamptz IS NULL OR frames.timestamp <= $3)
+                AND ($4::text IS NULL OR ocr_text.app_name ILIKE '%' || $4 || '%')
+                AND ($5::text IS NULL OR ocr_text.window_name ILIKE '%' || $5 || '%')
+                AND ($6::bigint IS NULL OR COALESCE(ocr_text.text_length, length(ocr_text.text)) >= $6)
+                AND ($7::bigint IS NULL OR COALESCE(ocr_text.text_length, length(ocr_text.text)) <= $7)
+                AND ($8::text IS NULL OR frames.name ILIKE '%' || $8 || '%')
+                AND ($11::timestamptz IS NULL OR frames.timestamp < $11
+                    OR (frames.timestamp = $11 AND frames.id < $12))
+            GROUP BY ocr_text.id, frames.id, video_chunks.id
+            ORDER BY frames.timestamp DESC, frames.id DESC
+            LIMIT $9 OFFSET $10
+            "#,
+        )
+        .bind(query)
+        .bind(start_time)
+        .bind(end_time)
+        .bind(app_name)
+        .bind(window_name)
+        .bind(min_length.map(|l| l as i64))
+        .bind(max_length.map(|l| l as i64))
+        .bind(frame_name)
+        .bind(limit as i64)
+        .bind(offset as i64)
+        .bind(bound.map(|(timestamp, _)| timestamp))
+        .bind(bound.map(|(_, id)| id))
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(raw_results.into_iter().map(ocr_result_from_raw).collect())
+    }
+
+    async fn search_audio(
+        &self,
+        query: &str,
+        limit: u32,
+        offset: u32,
+        cursor: Option<&SearchCursor>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        min_length: Option<usize>,
+        max_length: Option<usize>,
+        speaker_ids: Option<Vec<i64>>,
+    ) -> Result<Vec<AudioResult>, sqlx::Error> {
+        let bound = cursor.map(|c| c.bound_for(SearchKind::Audio));
+        let raw_results: Vec<AudioResultRaw> = sqlx::query_as(
+            r#"
+            SELECT
+                audio_transcriptions.id AS transcription_id,
+                audio_transcriptions.audio_chunk_id,
+                audio_transcriptions.transcription,
+                audio_transcriptions.timestamp,
+                audio_chunks.file_path,
+                audio_transcriptions.offset_index,
+                audio_transcriptions.transcription_engine,
+                string_agg(tags.name, ',') AS tags,
+                audio_transcriptions.device AS device_name,
+                audio_transcriptions.is_input_device,
+                audio_transcriptions.speaker_id,
+                audio_transcriptions.start_time,
+                audio_transcriptions.end_time
+            FROM audio_transcriptions
+            JOIN audio_chunks ON audio_transcriptions.audio_chunk_id = audio_chunks.id
+            LEFT JOIN speakers ON audio_transcriptions.speaker_id = speakers.id
+            LEFT JOIN audio_tags ON audio_chunks.id = audio_tags.audio_chunk_id
+            LEFT JOIN tags ON audio_tags.tag_id = tags.id
+            WHERE ($1 = '' OR audio_transcriptions.search_vector @@ websearch_to_tsquery('simple', $1))
+                AND ($2::timestamptz IS NULL OR audio_transcriptions.timestamp >= $2)
+                AND ($3::timestamptz IS NULL OR audio_transcriptions.timestamp <= $3)
+                AND ($4::bigint IS NULL OR COALESCE(audio_transcriptions.text_length, length(audio_transcriptions.transcription)) >= $4)
+                AND ($5::bigint IS NULL OR COALESCE(audio_transcriptions.text_length, length(audio_transcriptions.transcription)) <= $5)
+                AND (speakers.id IS NULL OR NOT speakers.hallucination)
+                AND (cardinality($6::bigint[]) = 0 OR audio_transcriptions.speaker_id = ANY($6))
+                AND ($9::timestamptz IS NULL OR audio_transcriptions.timestamp < $9
+                    OR (audio_transcriptions.timestamp = $9 AND audio_transcriptions.id < $10))
+            GROUP BY audio_transcriptions.id, audio_chunks.id
+            ORDER BY audio_transcriptions.timestamp DESC, audio_transcriptions.id DESC
+            LIMIT $7 OFFSET $8
+            "#,
+        )
+        .bind(query)
+        .bind(start_time)
+        .bind(end_time)
+        .bind(min_length.map(|l| l as i64))
+        .bind(max_length.map(|l| l as i64))
+        .bind(speaker_ids.unwrap_or_default())
+        .bind(limit as i64)
+        .bind(offset as i64)
+        .bind(bound.map(|(timestamp, _)| timestamp))
+        .bind(bound.map(|(_, id)| id))
+        .fetch_all(&self.pool)
+        .await?;
+
+        let futures = raw_results.into_iter().map(|raw| async move {
+            let speaker = match raw.speaker_id {
+                Some(id) => self.get_speaker_by_id(id).await.ok(),
+                None => None,
+            };
+
+            Ok::<AudioResult, sqlx::Error>(AudioResult {
+                transcription_id: raw.transcription_id,
+                audio_chunk_id: raw.audio_chunk_id,
+                transcription: raw.transcription,
+                timestamp: raw.timestamp,
+                file_path: raw.file_path,
+                offset_index: raw.offset_index,
+                transcription_engine: raw.transcription_engine,
+                tags: raw
+                    .tags
+                    .map(|t| t.split(',').map(String::from).collect())
+                    .unwrap_or_default(),
+                device_name: raw.device_name,
+                device_type: if raw.is_input_device {
+                    DeviceType::Input
+                } else {
+                    DeviceType::Output
+                },
+                speaker,
+                start_time: raw.start_time,
+                end_time: raw.end_time,
+            })
+        });
+
+        try_join_all(futures).await
+    }
+
+    async fn search_ui_monitoring(
+        &self,
+        query: &str,
+        app_name: Option<&str>,
+        window_name: Option<&str>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        limit: u32,
+        offset: u32,
+        cursor: Option<&SearchCursor>,
+    ) -> Result<Vec<UiContent>, sqlx::Error> {
+        let bound = cursor.map(|c| c.bound_for(SearchKind::Ui));
+        sqlx::query_as(
+            r#"
+            SELECT
+                ui_monitoring.id,
+                ui_monitoring.text_output,
+                ui_monitoring.timestamp,
+                ui_monitoring.app,
+                ui_monitoring."window",
+                ui_monitoring.initial_traversal_at,
+                COALESCE(frame.file_path, '') AS file_path,
+                COALESCE(frame.offset_index, 0) AS offset_index,
+                frame.name AS frame_name
+            FROM ui_monitoring
+            LEFT JOIN LATERAL (
+                SELECT video_chunks.file_path, frames.offset_index, frames.name
+                FROM frames
+                JOIN video_chunks ON frames.video_chunk_id = video_chunks.id
+                WHERE frames.timestamp BETWEEN ui_monitoring.timestamp - INTERVAL '1 second'
+                    AND ui_monitoring.timestamp + INTERVAL '1 second'
+                ORDER BY frames.timestamp DESC
+                LIMIT 1
+            ) frame ON TRUE
+            WHERE ($1 = '' OR ui_monitoring.search_vector @@ websearch_to_tsquery('simple', $1))
+                AND ($2::timestamptz IS NULL OR ui_monitoring.timestamp >= $2)
+                AND ($3::timestamptz IS NULL OR ui_monitoring.timestamp <= $3)
+                AND ($4::text IS NULL OR ui_monitoring.app ILIKE '%' || $4 || '%')
+                AND ($5::text IS NULL OR ui_monitoring."window" ILIKE '%' || $5 || '%')
+                AND ($8::timestamptz IS NULL OR ui_monitoring.timestamp < $8
+                    OR (ui_monitoring.timestamp = $8 AND ui_monitoring.id < $9))
+            ORDER BY ui_monitoring.timestamp DESC, ui_monitoring.id DESC
+            LIMIT $6 OFFSET $7
+            "#,
+        )
+        .bind(query)
+        .bind(start_time)
+        .bind(end_time)
+        .bind(app_name)
+        .bind(window_name)
+        .bind(limit as i64)
+        .bind(offset as i64)
+        .bind(bound.map(|(timestamp, _)| timestamp))
+        .bind(bound.map(|(_, id)| id))
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn count_search_results_capped(
+        &self,
+        query: &str,
+        content_type: ContentType,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>,
+        window_name: Option<&str>,
+        min_length: Option<usize>,
+        max_length: Option<usize>,
+        speaker_ids: Option<Vec<i64>>,
+        frame_name: Option<&str>,
+        cap: Option<usize>,
+    ) -> Result<usize, sqlx::Error> {
+        let speaker_ids = speaker_ids.unwrap_or_default();
+        // NULL is LIMIT ALL
+        let cap = cap.map(|c| c as i64);
+
+        let count = match content_type {
+            ContentType::OCR => {
+                self.count_ocr(
+                    query,
+                    start_time,
+                    end_time,
+                    app_name,
+                    window_name,
+                    min_length,
+                    max_length,
+                    frame_name,
+                    cap,
+                )
+                .await?
+            }
+            ContentType::Audio => {
+                self.count_audio(
+                    query,
+                    start_time,
+                    end_time,
+                    min_length,
+                    max_length,
+                    &speaker_ids,
+                    false,
+                    cap,
+                )
+                .await?
+            }
+            ContentType::UI => {
+                self.count_ui(
+                    query,
+                    start_time,
+                    end_time,
+                    app_name,
+                    window_name,
+                    min_length,
+                    max_length,
+                    false,
+                    cap,
+                )
+                .await?
+            }
+            ContentType::All => {
+                let (ocr, audio, ui) = tokio::try_join!(
+                    self.count_ocr(
+                        query,
+                        start_time,
+                        end_time,
+                        app_name,
+                        window_name,
+                        min_length,
+                        max_length,
+                        frame_name,
+                        cap,
+                    ),
+                    self.count_audio(
+                        query,
+                        start_time,
+                        end_time,
+                        min_length,
+                        max_length,
+                        &speaker_ids,
+                        true,
+                        cap,
+                    ),
+                    self.count_ui(
+                        query,
+                        start_time,
+                        end_time,
+                        app_name,
+                        window_name,
+                        min_length,
+                        max_length,
+                        true,
+                        cap,
+                    )
+                )?;
+                let total = ocr + audio + ui;
+                cap.map_or(total, |cap| total.min(cap))
+            }
+            _ => return Ok(0),
+        };
+
+        Ok(count as usize)
+    }
+
+    async fn get_frame(&self, frame_id: i64) -> Result<Option<(String, i64)>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT video_chunks.file_path, frames.offset_index
+            FROM frames
+            JOIN video_chunks ON frames.video_chunk_id = video_chunks.id
+            WHERE frames.id = $1
+            "#,
+        )
+        .bind(frame_id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn get_latest_timestamps(
+        &self,
+    ) -> Result<
+        (
+            Option<DateTime<Utc>>,
+            Option<DateTime<Utc>>,
+            Option<DateTime<Utc>>,
+        ),
+        sqlx::Error,
+    > {
+        sqlx::query_as(
+            r#"
+            SELECT
+                (SELECT MAX(timestamp) FROM frames),
+                (SELECT MAX(timestamp) FROM audio_chunks),
+                (SELECT MAX(timestamp) FROM ui_monitoring)
+            "#,
+        )
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    async fn execute_raw_sql(&self, query: &str) -> Result<serde_json::Value, sqlx::Error> {
+        let rows = sqlx::query(query).fetch_all(&self.pool).await?;
+
+        let result = rows
+            .iter()
+            .map(|row| {
+                let mut map = serde_json::Map::new();
+                for (i, column) in row.columns().iter().enumerate() {
+                    let Ok(value) = row.try_get_raw(i) else {
+                        continue;
+                    };
+                    let json_value = if value.is_null() {
+                        serde_json::Value::Null
+                    } else {
+                        match value.type_info().name() {
+                            "TEXT" | "VARCHAR" | "NAME" | "BPCHAR" => {
+                                serde_json::Value::String(row.try_get(i).unwrap_or_default())
+                            }
+                            "INT2" => serde_json::Value::from(
+                                row.try_get::<i16, _>(i).unwrap_or_default(),
+                            ),
+                            "INT4" => serde_json::Value::from(
+                                row.try_get::<i32, _>(i).unwrap_or_default(),
+                            ),
+                            "INT8" => serde_json::Value::from(
+                                row.try_get::<i64, _>(i).unwrap_or_default(),
+                            ),
+                            "FLOAT4" => serde_json::Value::from(
+                                row.try_get::<f32, _>(i).unwrap_or_default(),
+                            ),
+                            "FLOAT8" => serde_json::Value::from(
+                                row.try_get::<f64, _>(i).unwrap_or_default(),
+                            ),
+                            "BOOL" => serde_json::Value::Bool(row.try_get(i).unwrap_or_default()),
+                            "TIMESTAMPTZ" => row
+                                .try_get::<DateTime<Utc>, _>(i)
+                                .map(|t| serde_json::Value::String(t.to_rfc3339()))
+                                .unwrap_or(serde_json::Value::Null),
+                            _ => serde_json::Value::Null,
+                        }
+                    };
+                    map.insert(column.name().to_string(), json_value);
+                }
+                serde_json::Value::Object(map)
+            })
+            .collect();
+
+        Ok(serde_json::Value::Array(result))
+    }
+}
+
+#[async_trait]
+impl EmbeddingStore for PostgresStore {
+    async fn insert_embeddings(&self, frame_id: i64, embedding: String) -> Result<(), sqlx::Error> {
+        // the embedding comes as a json array
+        let embedding: Vec<f32> =
+            serde_json::from_str(&embedding).map_err(|e| sqlx::Error::Decode(e.into()))?;
+        sqlx::query("INSERT INTO ocr_text_embeddings (frame_id, embedding) VALUES ($1, $2)")
+            .bind(frame_id)
+            .bind(to_vector(&embedding))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn search_similar_embeddings(
+        &self,
+        embedding: Vec<f32>,
+        limit: u32,
+        threshold: f32,
+    ) -> Result<Vec<OCRResult>, sqlx::Error> {
+        debug!("searching similar embeddings with threshold {}", threshold);
+
+        let raw_results: Vec<OCRResultRaw> = sqlx::query_as(
+            r#"
+            WITH embedding_matches AS (
+                SELECT frame_id, MIN(embedding <=> $1) AS distance
+                FROM ocr_text_embeddings
+                WHERE embedding <=> $1 < $2
+                GROUP BY frame_id
+                ORDER BY distance ASC
+                LIMIT $3
+            )
+            SELECT
+                ocr_text.frame_id,
+                ocr_text.text AS ocr_text,
+                ocr_text.text_json,
+                frames.timestamp,
+                COALESCE(frames.name, '') AS frame_name,
+                video_chunks.file_path,
+                frames.offset_index,
+                ocr_text.app_name,
+                ocr_text.ocr_engine,
+                ocr_text.window_name,
+                string_agg(tags.name, ',') AS tags
+            FROM embedding_matches
+            JOIN ocr_text ON embedding_matches.frame_id = ocr_text.frame_id
+            JOIN frames ON ocr_text.frame_id = frames.id
+            JOIN video_chunks ON frames.video_chunk_id = video_chunks.id
+            LEFT JOIN vision_tags ON frames.id = vision_tags.vision_id
+            LEFT JOIN tags ON vision_tags.tag_id = tags.id
+            GROUP BY ocr_text.id, frames.id, video_chunks.id, embedding_matches.distance
+            ORDER BY embedding_matches.distance ASC
+            "#,
+        )
+        .bind(to_vector(&embedding))
+        .bind(threshold as f64)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(raw_results.into_iter().map(ocr_result_from_raw).collect())
+    }
+}
+
+#[async_trait]
+impl TimelineStore for PostgresStore {
+    async fn find_video_chunks(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<TimeSeriesChunk, sqlx::Error> {
+        let frames_query = r#"
+            WITH minute_groups AS (
+                SELECT
+                    f.id,
+                    f.timestamp,
+                    f.offset_index,
+                    ot.text,
+                    ot.app_name,
+                    ot.window_name,
+                    vc.device_name AS screen_device,
+                    vc.file_path AS video_path,
+                    ROW_NUMBER() OVER (
+                        PARTITION BY date_trunc('minute', f.timestamp), ot.app_name, vc.device_name
+                        ORDER BY f.timestamp DESC
+                    ) AS rn
+                FROM frames f
+                JOIN video_chunks vc ON f.video_chunk_id = vc.id
+                LEFT JOIN ocr_text ot ON f.id = ot.frame_id
+                WHERE f.timestamp >= $1 AND f.timestamp <= $2
+            )
+            SELECT id, timestamp, offset_index, text, app_name, window_name, screen_device, video_path
+            FROM minute_groups
+            WHERE rn = 1
+            ORDER BY timestamp DESC, offset_index DESC
+        "#;
+
+        let audio_query = r#"
+            SELECT
+                at.timestamp,
+                at.transcription,
+                at.device AS audio_device,
+                at.is_input_device,
+                ac.file_path AS audio_path,
+                COALESCE(at.end_time - at.start_time, 0)::float8 AS duration_secs
+            FROM audio_transcriptions at
+            JOIN audio_chunks ac ON at.audio_chunk_id = ac.id
+            WHERE at.timestamp >= $1 AND at.timestamp <= $2
+            ORDER BY at.timestamp DESC
+        "#;
+
+        let (frame_rows, audio_rows) = tokio::try_join!(
+            sqlx::query_as::<_, TimelineFrameRow>(frames_query)
+                .bind(start)
+                .bind(end)
+                .fetch_all(&self.pool),
+            sqlx::query_as::<_, TimelineAudioRow>(audio_query)
+                .bind(start)
+                .bind(end)
+                .fetch_all(&self.pool)
+        )?;
+
+        let mut frames_map: BTreeMap<(DateTime<Utc>, i64), FrameData> = BTreeMap::new();
+
+        for row in frame_rows {
+            let frame_data = frames_map
+                .entry((row.timestamp, row.offset_index))
+                .or_insert_with(|| FrameData {
+                    frame_id: row.id,
+                    timestamp: row.timestamp,
+                    offset_index: row.offset_index,
+                    ocr_entries: Vec::new(),
+                    audio_entries: Vec::new(),
+                });
+
+            if let Some(text) = row.text {
+                frame_data.ocr_entries.push(OCREntry {
+                    text,
+                    app_name: row.app_name.unwrap_or_default(),
+                    window_name: row.window_name.unwrap_or_default(),
+                    device_name: row.screen_device,
+                    video_file_path: row.video_path,
+                });
+            }
+        }
+
+        for row in audio_rows {
+            // attach to the closest frame at or before the transcription
+            let key = frames_map
+                .range(..=(row.timestamp, i64::MAX))
+                .next_back()
+                .or_else(|| frames_map.iter().next())
+                .map(|(key, _)| *key);
+
+            if let Some(frame_data) = key.and_then(|key| frames_map.get_mut(&key)) {
+                frame_data.audio_entries.push(AudioEntry {
+                    transcription: row.transcription,
+                    device_name: row.audio_device,
+                    is_input: row.is_input_device,
+                    audio_file_path: row.audio_path,
+                    duration_secs: row.duration_secs,
+                });
+            }
+        }
+
+        Ok(TimeSeriesChunk {
+            frames: frames_map.into_values().rev().collect(),
+            start_time: start,
+            end_time: end,
+        })
+    }
+
+    async fn get_total_frames(&self, video_path: &Path) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            "SELECT COUNT(*) FROM frames JOIN video_chunks ON frames.video_chunk_id = video_chunks.id WHERE video_chunks.file_path = $1",
+        )
+        .bind(video_path.to_string_lossy().to_string())
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    async fn get_ordered_videos(&self) -> Result<Vec<String>, sqlx::Error> {
+        sqlx::query_scalar("SELECT file_path FROM video_chunks ORDER BY id ASC")
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    async fn get_next_video(
+        &self,
+        current_video_path: &str,
+    ) -> Result<Option<String>, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"
+            SELECT file_path
+            FROM video_chunks
+            WHERE id > (SELECT id FROM video_chunks WHERE file_path = $1 ORDER BY id LIMIT 1)
+            ORDER BY id ASC
+            LIMIT 1
+            "#,
+        )
+        .bind(current_video_path)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn get_chunked_texts_since(
+        &self,
+        since: DateTime<Utc>,
+        source: &str,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"
+            SELECT cti.text
+            FROM chunked_text_index cti
+            JOIN chunked_text_entries cte ON cti.text_id = cte.text_id
+            WHERE cte.timestamp > $1 AND cte.source = $2
+            GROUP BY cti.text_id, cti.text
+            ORDER BY MAX(cte.timestamp) DESC
+            "#,
+        )
+        .bind(since)
+        .bind(source)
+        .fetch_all(&self.pool)
+        .await
+    }
+}
+
+#[async_trait]
+impl TagStore for PostgresStore {
+    async fn add_tags(
+        &self,
+        id: i64,
+        content_type: TagContentType,
+        tags: Vec<String>,
+    ) -> Result<(), sqlx::Error> {
+        let link_query = match content_type {
+            TagContentType::Vision => {
+                "INSERT INTO vision_tags (vision_id, tag_id) VALUES ($1, $2) ON CONFLICT DO NOTHING"
+            }
+            TagContentType::Audio => {
+                "INSERT INTO audio_tags (audio_chunk_id, tag_id) VALUES ($1, $2) ON CONFLICT DO NOTHING"
+            }
+        };
+        self.add_tag_ids(tags, link_query, id).await
+    }
+
+    async fn get_tags(
+        &self,
+        id: i64,
+        content_type: TagContentType,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let query = match content_type {
+            TagContentType::Vision => {
+                "SELECT t.name FROM tags t JOIN vision_tags vt ON t.id = vt.tag_id WHERE vt.vision_id = $1 ORDER BY t.name"
+            }
+            TagContentType::Audio => {
+                "SELECT t.name FROM tags t JOIN audio_tags at ON t.id = at.tag_id WHERE at.audio_chunk_id = $1 ORDER BY t.name"
+            }
+        };
+        sqlx::query_scalar(query)
+            .bind(id)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    async fn remove_tags(
+        &self,
+        id: i64,
+        content_type: TagContentType,
+        tags: Vec<String>,
+    ) -> Result<(), sqlx::Error> {
+        let query = match content_type {
+            TagContentType::Vision => {
+                "DELETE FROM vision_tags WHERE vision_id = $1 AND tag_id IN (SELECT id FROM tags WHERE name = ANY($2))"
+            }
+            TagContentType::Audio => {
+                "DELETE FROM audio_tags WHERE audio_chunk_id = $1 AND tag_id IN (SELECT id FROM tags WHERE name = ANY($2))"
+            }
+        };
+        sqlx::query(query)
+            .bind(id)
+            .bind(tags)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn add_tags_to_ui_monitoring(
+        &self,
+        ui_monitoring_id: i64,
+        tag_ids: &[i64],
+    ) -> Result<(), anyhow::Error> {
+        sqlx::query(
+            "INSERT INTO ui_monitoring_tags (ui_monitoring_id, tag_id) SELECT $1, UNNEST($2::bigint[]) ON CONFLICT DO NOTHING",
+        )
+        .bind(ui_monitoring_id)
+        .bind(tag_ids)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_ui_monitoring_tags(
+        &self,
+        ui_monitoring_id: i64,
+    ) -> Result<Vec<String>, anyhow::Error> {
+        Ok(sqlx::query_scalar(
+            "SELECT t.name FROM tags t JOIN ui_monitoring_tags ut ON t.id = ut.tag_id WHERE ut.ui_monitoring_id = $1",
+        )
+        .bind(ui_monitoring_id)
+        .fetch_all(&self.pool)
+        .await?)
+    }
+}
+
+#[async_trait]
+impl SpeakerStore for PostgresStore {
+    async fn insert_speaker(&self, embedding: &[f32]) -> Result<Speaker, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let id: i64 = sqlx::query_scalar("INSERT INTO speakers DEFAULT VALUES RETURNING id")
+            .fetch_one(&mut *tx)
+            .await?;
+
+        sqlx::query("INSERT INTO speaker_embeddings (embedding, speaker_id) VALUES ($1, $2)")
+            .bind(to_vector(embedding))
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        Ok(Speaker {
+            id,
+            name: String::new(),
+            metadata: String::new(),
+        })
+    }
+
+    async fn update_speaker_metadata(
+        &self,
+        speaker_id: i64,
+        metadata: &str,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query("UPDATE speakers SET metadata = $1 WHERE id = $2")
+            .bind(metadata)
+            .bind(speaker_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(speaker_id)
+    }
+
+    async fn get_speaker_by_id(&self, speaker_id: i64) -> Result<Speaker, sqlx::Error> {
+        sqlx::query_as("SELECT id, name, metadata FROM speakers WHERE id = $1")
+            .bind(speaker_id)
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    async fn get_speaker_from_embedding(
+        &self,
+        embedding: &[f32],
+    ) -> Result<Option<Speaker>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT s.id, s.name, s.metadata
+            FROM speaker_embeddings se
+            JOIN speakers s ON s.id = se.speaker_id
+            WHERE se.embedding <=> $1 < $2
+            ORDER BY se.embedding <=> $1
+            LIMIT 1
+            "#,
+        )
+        .bind(to_vector(embedding))
+        .bind(SPEAKER_MATCH_THRESHOLD)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn update_speaker_name(&self, speaker_id: i64, name: &str) -> Result<i64, sqlx::Error> {
+        sqlx::query("UPDATE speakers SET name = $1 WHERE id = $2")
+            .bind(name)
+            .bind(speaker_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(speaker_id)
+    }
+
+    async fn get_audio_chunks_for_speaker(
+        &self,
+        speaker_id: i64,
+    ) -> Result<Vec<AudioChunksResponse>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT
+                ac.id AS audio_chunk_id,
+                at.start_time,
+                at.end_time,
+                ac.file_path,
+                ac.timestamp
+            FROM audio_chunks ac
+            JOIN audio_transcriptions at ON ac.id = at.audio_chunk_id
+            WHERE at.speaker_id = $1
+            ORDER BY at.start_time
+            "#,
+        )
+        .bind(speaker_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn get_unnamed_speakers_page(
+        &self,
+        limit: u32,
+        offset: u32,
+        cursor: Option<&SpeakerCursor>,
+        speaker_ids: Option<Vec<i64>>,
+    ) -> Result<Vec<UnnamedSpeaker>, sqlx::Error> {
+        let query = format!(
+            r#"
+            WITH {samples},
+            transcription_counts AS (
+                SELECT speaker_id, COUNT(*) AS transcription_count
+                FROM audio_transcriptions
+                WHERE speaker_id IS NOT NULL
+                GROUP BY speaker_id
+            )
+            SELECT s.id, s.name, {metadata} AS metadata, tc.transcription_count
+            FROM speakers s
+            JOIN speaker_samples ss ON ss.speaker_id = s.id
+            JOIN transcription_counts tc ON tc.speaker_id = s.id
+            WHERE s.name = ''
+                AND NOT s.hallucination
+                AND (cardinality($1::bigint[]) = 0 OR s.id = ANY($1))
+                AND ($4::bigint IS NULL OR (tc.transcription_count, s.id) < ($4, $5::bigint))
+            ORDER BY tc.transcription_count DESC, s.id DESC
+            LIMIT $2 OFFSET $3
+            "#,
+            samples = SPEAKER_SAMPLES_CTE,
+            metadata = SPEAKER_METADATA,
+        );
+
+        sqlx::query_as(&query)
+            .bind(speaker_ids.unwrap_or_default())
+            .bind(limit as i64)
+            .bind(offset as i64)
+            .bind(cursor.map(|c| c.transcription_count))
+            .bind(cursor.map(|c| c.id))
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    async fn merge_speakers(
+        &self,
+        speaker_to_keep_id: i64,
+        speaker_to_merge_id: i64,
+    ) -> Result<Speaker, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        for query in [
+            "UPDATE audio_transcriptions SET speaker_id = $1 WHERE speaker_id = $2",
+            "UPDATE speaker_embeddings SET speaker_id = $1 WHERE speaker_id = $2",
+        ] {
+            sqlx::query(query)
+                .bind(speaker_to_keep_id)
+                .bind(speaker_to_merge_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        sqlx::query("DELETE FROM speakers WHERE id = $1")
+            .bind(speaker_to_merge_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        self.get_speaker_by_id(speaker_to_keep_id).await
+    }
+
+    async fn search_speakers(&self, name_prefix: &str) -> Result<Vec<Speaker>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT id, name, metadata FROM speakers WHERE name ILIKE $1 || '%' AND NOT hallucination",
+        )
+        .bind(name_prefix)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn delete_speaker(&self, id: i64) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let operations = [
+            (
+                "DELETE FROM audio_chunks WHERE id IN (SELECT audio_chunk_id FROM audio_transcriptions WHERE speaker_id = $1 AND start_time IS NULL)",
+                "audio chunks",
+            ),
+            (
+                "DELETE FROM audio_transcriptions WHERE speaker_id = $1",
+                "audio transcriptions",
+            ),
+            (
+                "DELETE FROM speaker_embeddings WHERE speaker_id = $1",
+                "speaker embeddings",
+            ),
+            ("DELETE FROM speakers WHERE id = $1", "speaker"),
+        ];
+
+        for (query, operation) in operations {
+            if let Err(e) = sqlx::query(query).bind(id).execute(&mut *tx).await {
+                error!("Failed to delete {} for speaker {}: {}", operation, id, e);
+                tx.rollback().await?;
+                return Err(e);
+            }
+        }
+
+        tx.commit().await
+    }
+
+    async fn get_similar_speakers(
+        &self,
+        speaker_id: i64,
+        limit: u32,
+    ) -> Result<Vec<Speaker>, sqlx::Error> {
+        let query = format!(
+            r#"
+            WITH {samples},
+            target AS (
+                SELECT embedding FROM speaker_embeddings WHERE speaker_id = $1 LIMIT 1
+            ),
+            matches AS (
+                SELECT se.speaker_id, MIN(se.embedding <=> (SELECT embedding FROM target)) AS distance
+                FROM speaker_embeddings se
+                WHERE se.speaker_id != $1
+                GROUP BY se.speaker_id
+            )
+            SELECT s.id, s.name, {metadata} AS metadata
+            FROM matches m
+            JOIN speakers s ON s.id = m.speaker_id
+            JOIN speaker_samples ss ON ss.speaker_id = s.id
+            WHERE m.distance < $2 AND NOT s.hallucination
+            ORDER BY m.distance
+            LIMIT $3
+            "#,
+            samples = SPEAKER_SAMPLES_CTE,
+            metadata = SPEAKER_METADATA,
+        );
+
+        sqlx::query_as(&query)
+            .bind(speaker_id)
+            .bind(SIMILAR_SPEAKER_THRESHOLD)
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    async fn mark_speaker_as_hallucination(&self, id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE speakers SET hallucination = TRUE WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MediaChunkStore for PostgresStore {
+    async fn find_offload_candidates(
+        &self,
+        kind: ChunkKind,
+        cutoff: DateTime<Utc>,
+        after_id: i64,
+        limit: i64,
+    ) -> Result<Vec<OffloadCandidate>, sqlx::Error> {
+        let query = match kind {
+            ChunkKind::Video => {
+                r#"
+                SELECT vc.id, vc.file_path
+                FROM video_chunks vc
+                JOIN frames f ON f.video_chunk_id = vc.id
+                WHERE vc.remote_key IS NULL AND vc.id > $2
+                GROUP BY vc.id
+                HAVING MAX(f.timestamp) < $1
+                ORDER BY vc.id ASC
+                LIMIT $3
+                "#
+            }
+            ChunkKind::Audio => {
+                r#"
+                SELECT id, file_path
+                FROM audio_chunks
+                WHERE remote_key IS NULL AND timestamp < $1 AND id > $2
+                ORDER BY id ASC
+                LIMIT $3
+                "#
+            }
+        };
+
+        sqlx::query_as(query)
+            .bind(cutoff)
+            .bind(after_id)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    async fn mark_chunk_offloaded(
+        &self,
+        kind: ChunkKind,
+        id: i64,
+        remote_key: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(&format!(
+            "UPDATE {} SET remote_key = $1, offloaded_at = $2 WHERE id = $3",
+            kind.table()
+        ))
+        .bind(remote_key)
+        .bind(Utc::now())
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_chunk_remote_key(&self, file_path: &str) -> Result<Option<String>, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"
+            SELECT remote_key FROM video_chunks WHERE file_path = $1 AND remote_key IS NOT NULL
+            UNION ALL
+            SELECT remote_key FROM audio_chunks WHERE file_path = $1 AND remote_key IS NOT NULL
+            LIMIT 1
+            "#,
+        )
+        .bind(file_path)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn get_audio_chunk_file_path(&self, id: i64) -> Result<Option<String>, sqlx::Error> {
+        sqlx::query_scalar("SELECT file_path FROM audio_chunks WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+}
+
+#[async_trait]
+impl PipeCronStore for PostgresStore {
+    async fn insert_pipe_cron_run(&self, run: &CronRun) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            "INSERT INTO pipe_cron_runs (pipe_id, job, trigger, started_at, ended_at, status, output) VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING id",
+        )
+        .bind(&run.pipe_id)
+        .bind(&run.job)
+        .bind(run.trigger.as_str())
+        .bind(run.started_at)
+        .bind(run.ended_at)
+        .bind(run.status.as_str())
+        .bind(&run.output)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    async fn get_pipe_cron_runs(
+        &self,
+        pipe_id: &str,
+        job: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<CronRun>, sqlx::Error> {
+        sqlx::query_as::<_, CronRunRow>(
+            r#"
+            SELECT id, pipe_id, job, trigger, started_at, ended_at, status, output
+            FROM pipe_cron_runs
+            WHERE pipe_id = $1 AND ($2::TEXT IS NULL OR job = $2)
+            ORDER BY started_at DESC, id DESC
+            LIMIT $3
+            "#,
+        )
+        .bind(pipe_id)
+        .bind(job)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(cron_run_from_row)
+        .collect()
+    }
+}
+
+#[async_trait]
+impl WebhookStore for PostgresStore {
+    async fn list_webhooks(&self) -> Result<Vec<Webhook>, sqlx::Error> {
+        sqlx::query_as::<_, WebhookRow>(
+            "SELECT id, url, events, filter, secret, headers, enabled, created_at, updated_at FROM webhooks ORDER BY id",
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(webhook_from_row)
+        .collect()
+    }
+
+    async fn get_webhook(&self, id: i64) -> Result<Option<Webhook>, sqlx::Error> {
+        sqlx::query_as::<_, WebhookRow>(
+            "SELECT id, url, events, filter, secret, headers, enabled, created_at, updated_at FROM webhooks WHERE id = $1",
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?
+        .map(webhook_from_row)
+        .transpose()
+    }
+
+    async fn insert_webhook(&self, spec: &WebhookSpec) -> Result<i64, sqlx::Error> {
+        let now = Utc::now();
+        sqlx::query_scalar(
+            "INSERT INTO webhooks (url, events, filter, secret, headers, enabled, created_at, updated_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $7) RETURNING id",
+        )
+        .bind(&spec.url)
+        .bind(serde_json::to_string(&spec.events).unwrap_or_else(|_| "[]".to_string()))
+        .bind(&spec.filter)
+        .bind(&spec.secret)
+        .bind(serde_json::to_string(&spec.headers).unwrap_or_else(|_| "{}".to_string()))
+        .bind(spec.enabled)
+        .bind(now)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    async fn update_webhook(&self, id: i64, spec: &WebhookSpec) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE webhooks
+            SET url = $2, events = $3, filter = $4, secret = COALESCE($5, secret), headers = $6,
+                enabled = $7, updated_at = $8
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(&spec.url)
+        .bind(serde_json::to_string(&spec.events).unwrap_or_else(|_| "[]".to_string()))
+        .bind(&spec.filter)
+        .bind(&spec.secret)
+        .bind(serde_json::to_string(&spec.headers).unwrap_or_else(|_| "{}".to_string()))
+        .bind(spec.enabled)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn delete_webhook(&self, id: i64) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM webhook_deliveries WHERE webhook_id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM webhook_dead_letters WHERE webhook_id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        let result = sqlx::query("DELETE FROM webhooks WHERE id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn insert_webhook_delivery(
+        &self,
+        delivery: &WebhookDelivery,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            "INSERT INTO webhook_deliveries (webhook_id, event, event_offset, status, attempts, response_status, error, duration_ms, delivered_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) RETURNING id",
+        )
+        .bind(delivery.webhook_id)
+        .bind(&delivery.event)
+        .bind(delivery.event_offset.map(|o| o as i64))
+        .bind(delivery.status.as_str())
+        .bind(delivery.attempts as i64)
+        .bind(delivery.response_status.map(|s| s as i64))
+        .bind(&delivery.error)
+        .bind(delivery.duration_ms)
+        .bind(delivery.delivered_at)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    async fn get_webhook_deliveries(
+        &self,
+        webhook_id: i64,
+        limit: u32,
+    ) -> Result<Vec<WebhookDelivery>, sqlx::Error> {
+        sqlx::query_as::<_, WebhookDeliveryRow>(
+            r#"
+            SELECT id, webhook_id, event, event_offset, status, attempts, response_status, error, duration_ms, delivered_at
+            FROM webhook_deliveries
+            WHERE webhook_id = $1
+            ORDER BY id DESC
+            LIMIT $2
+            "#,
+        )
+        .bind(webhook_id)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(webhook_delivery_from_row)
+        .collect()
+    }
+
+    async fn insert_webhook_dead_letter(
+        &self,
+        letter: &WebhookDeadLetter,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            "INSERT INTO webhook_dead_letters (webhook_id, event, event_offset, payload, attempts, error, created_at) VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING id",
+        )
+        .bind(letter.webhook_id)
+        .bind(&letter.event)
+        .bind(letter.event_offset.map(|o| o as i64))
+        .bind(letter.payload.to_string())
+        .bind(letter.attempts as i64)
+        .bind(&letter.error)
+        .bind(letter.created_at)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    async fn get_webhook_dead_letters(
+        &self,
+        webhook_id: i64,
+        limit: u32,
+    ) -> Result<Vec<WebhookDeadLetter>, sqlx::Error> {
+        sqlx::query_as::<_, WebhookDeadLetterRow>(
+            r#"
+            SELECT id, webhook_id, event, event_offset, payload, attempts, error, created_at
+            FROM webhook_dead_letters
+            WHERE webhook_id = $1
+            ORDER BY id DESC
+            LIMIT $2
+            "#,
+        )
+        .bind(webhook_id)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(webhook_dead_letter_from_row)
+        .collect()
+    }
+
+    async fn get_webhook_dead_letter(
+        &self,
+        id: i64,
+    ) -> Result<Option<WebhookDeadLetter>, sqlx::Error> {
+        sqlx::query_as::<_, WebhookDeadLetterRow>(
+            "SELECT id, webhook_id, event, event_offset, payload, attempts, error, created_at FROM webhook_dead_letters WHERE id = $1",
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?
+        .map(webhook_dead_letter_from_row)
+        .transpose()
+    }
+
+    async fn delete_webhook_dead_letter(&self, id: i64) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM webhook_dead_letters WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn prune_webhook_history(
+        &self,
+        keep: i64,
+        before: DateTime<Utc>,
+    ) -> Result<u64, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let deliveries = sqlx::query(
+            r#"
+            DELETE FROM webhook_deliveries
+            WHERE delivered_at < $2 OR id IN (
+                SELECT id FROM (
+                    SELECT id, ROW_NUMBER() OVER (PARTITION BY webhook_id ORDER BY id DESC) AS rn
+                    FROM webhook_deliveries
+                ) ranked
+                WHERE rn > $1
+            )
+            "#,
+        )
+        .bind(keep)
+        .bind(before)
+        .execute(&mut *tx)
+        .await?;
+        let letters = sqlx::query(
+            r#"
+            DELETE FROM webhook_dead_letters
+            WHERE created_at < $2 OR id IN (
+                SELECT id FROM (
+                    SELECT id, ROW_NUMBER() OVER (PARTITION BY webhook_id ORDER BY id DESC) AS rn
+                    FROM webhook_dead_letters
+                ) ranked
+                WHERE rn > $1
+            )
+            "#,
+        )
+        .bind(keep)
+        .bind(before)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(deliveries.rows_affected() + letters.rows_affected())
+    }
+}
+
+#[async_trait]
+impl RuleStore for PostgresStore {
+    async fn list_rules(&self) -> Result<Vec<Rule>, sqlx::Error> {
+        sqlx::query_as::<_, RuleRow>(
+            "SELECT id, definition, created_at, updated_at FROM automation_rules ORDER BY id",
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(rule_from_row)
+        .collect()
+    }
+
+    async fn get_rule(&self, id: i64) -> Result<Option<Rule>, sqlx::Error> {
+        sqlx::query_as::<_, RuleRow>(
+            "SELECT id, definition, created_at, updated_at FROM automation_rules WHERE id = $1",
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?
+        .map(rule_from_row)
+        .transpose()
+    }
+
+    async fn insert_rule(&self, spec: &RuleSpec) -> Result<i64, sqlx::Error> {
+        let now = Utc::now();
+        sqlx::query_scalar(
+            "INSERT INTO automation_rules (definition, created_at, updated_at) VALUES ($1, $2, $2) RETURNING id",
+        )
+        .bind(serde_json::to_string(spec).map_err(|e| sqlx::Error::Encode(e.into()))?)
+        .bind(now)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    async fn update_rule(&self, id: i64, spec: &RuleSpec) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE automation_rules SET definition = $2, updated_at = $3 WHERE id = $1",
+        )
+        .bind(id)
+        .bind(serde_json::to_string(spec).map_err(|e| sqlx::Error::Encode(e.into()))?)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn delete_rule(&self, id: i64) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM automation_rules WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[async_trait]
+impl MeetingStore for PostgresStore {
+    async fn list_meetings(
+        &self,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<Meeting>, sqlx::Error> {
+        sqlx::query_as::<_, MeetingRow>(&format!(
+            "SELECT {} FROM meetings
+             WHERE ($1::TIMESTAMPTZ IS NULL OR end_time IS NULL OR end_time >= $1)
+               AND ($2::TIMESTAMPTZ IS NULL OR start_time <= $2)
+             ORDER BY start_time DESC, id DESC
+             LIMIT $3 OFFSET $4",
+            MEETING_COLUMNS
+        ))
+        .bind(start_time)
+        .bind(end_time)
+        .bind(limit as i64)
+        .bind(offset as i64)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(meeting_from_row)
+        .collect()
+    }
+
+    async fn get_meeting(&self, id: i64) -> Result<Option<Meeting>, sqlx::Error> {
+        sqlx::query_as::<_, MeetingRow>(&format!(
+            "SELECT {} FROM meetings WHERE id = $1",
+            MEETING_COLUMNS
+        ))
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?
+        .map(meeting_from_row)
+        .transpose()
+    }
+
+    async fn insert_meeting(&self, spec: &MeetingSpec) -> Result<i64, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let id: i64 = sqlx::query_scalar(
+            "INSERT INTO meetings (start_time, end_time, app, title, participants, signals, created_at, updated_at)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $7) RETURNING id",
+        )
+        .bind(spec.start_time)
+        .bind(spec.end_time)
+        .bind(&spec.app)
+        .bind(&spec.title)
+        .bind(serde_json::to_string(&spec.participants).map_err(|e| sqlx::Error::Encode(e.into()))?)
+        .bind(serde_json::to_string(&spec.signals).map_err(|e| sqlx::Error::Encode(e.into()))?)
+        .bind(Utc::now())
+        .fetch_one(&mut *tx)
+        .await?;
+        sqlx::query(LINK_MEETING_CONTENT)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    async fn update_meeting(&self, id: i64, spec: &MeetingSpec) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let result = sqlx::query(
+            "UPDATE meetings SET start_time = $2, end_time = $3, app = $4, title = $5, participants = $6, signals = $7, updated_at = $8
+             WHERE id = $1",
+        )
+        .bind(id)
+        .bind(spec.start_time)
+        .bind(spec.end_time)
+        .bind(&spec.app)
+        .bind(&spec.title)
+        .bind(serde_json::to_string(&spec.participants).map_err(|e| sqlx::Error::Encode(e.into()))?)
+        .bind(serde_json::to_string(&spec.signals).map_err(|e| sqlx::Error::Encode(e.into()))?)
+        .bind(Utc::now())
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(LINK_MEETING_CONTENT)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn delete_meeting(&self, id: i64) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM meetings WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[async_trait]
+impl ConversationStore for PostgresStore {
+    async fn search_conversations(
+        &self,
+        query: &str,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<Conversation>, sqlx::Error> {
+        sqlx::query_as::<_, ConversationRow>(&format!(
+            "SELECT {} FROM conversations
+             WHERE ($1 = '' OR search_vector @@ websearch_to_tsquery('simple', $1))
+               AND ($2::TIMESTAMPTZ IS NULL OR end_time >= $2)
+               AND ($3::TIMESTAMPTZ IS NULL OR start_time <= $3)
+             ORDER BY start_time DESC, id DESC
+             LIMIT $4 OFFSET $5",
+            CONVERSATION_COLUMNS
+        ))
+        .bind(query)
+        .bind(start_time)
+        .bind(end_time)
+        .bind(limit as i64)
+        .bind(offset as i64)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(conversation_from_row)
+        .collect()
+    }
+
+    async fn get_conversation(&self, id: i64) -> Result<Option<Conversation>, sqlx::Error> {
+        sqlx::query_as::<_, ConversationRow>(&format!(
+            "SELECT {} FROM conversations WHERE id = $1",
+            CONVERSATION_COLUMNS
+        ))
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?
+        .map(conversation_from_row)
+        .transpose()
+    }
+
+    async fn get_conversation_turns(&self, id: i64) -> Result<Vec<ConversationTurn>, sqlx::Error> {
+        sqlx::query_as::<_, ConversationTurnRow>(
+            "SELECT start_time, end_time, speaker_id, speaker_name, device, is_output_device, text, transcription_ids
+             FROM conversation_turns WHERE conversation_id = $1 ORDER BY position",
+        )
+        .bind(id)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(conversation_turn_from_row)
+        .collect()
+    }
+
+    async fn insert_conversation(&self, spec: &ConversationSpec) -> Result<i64, sqlx::Error> {
+        let transcription_ids: Vec<i64> = spec.transcription_ids().collect();
+        let mut tx = self.pool.begin().await?;
+        let id: i64 = sqlx::query_scalar(
+            "INSERT INTO conversations (start_time, end_time, devices, speakers, transcript, turn_count, first_transcription_id, last_transcription_id, created_at)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) RETURNING id",
+        )
+        .bind(spec.start_time)
+        .bind(spec.end_time)
+        .bind(serde_json::to_string(&spec.devices()).map_err(|e| sqlx::Error::Encode(e.into()))?)
+        .bind(serde_json::to_string(&spec.speakers()).map_err(|e| sqlx::Error::Encode(e.into()))?)
+        .bind(spec.transcript())
+        .bind(spec.turns.len() as i64)
+        .bind(transcription_ids.iter().min().copied())
+        .bind(transcription_ids.iter().max().copied())
+        .bind(Utc::now())
+        .fetch_one(&mut *tx)
+        .await?;
+        for (position, turn) in spec.turns.iter().enumerate() {
+            sqlx::query(
+                "INSERT INTO conversation_turns (conversation_id, position, start_time, end_time, speaker_id, speaker_name, device, is_output_device, text, transcription_ids)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)",
+            )
+            .bind(id)
+            .bind(position as i64)
+            .bind(turn.start_time)
+            .bind(turn.end_time)
+            .bind(turn.speaker_id)
+            .bind(&turn.speaker_name)
+            .bind(&turn.device_name)
+            .bind(turn.output)
+            .bind(&turn.text)
+            .bind(serde_json::to_string(&turn.transcription_ids).map_err(|e| sqlx::Error::Encode(e.into()))?)
+            .execute(&mut *tx)
+            .await?;
+        }
+        let links = transcription_ids
+            .iter()
+            .map(|id| (*id, false))
+            .chain(spec.duplicates.iter().map(|id| (*id, true)));
+        for (transcription_id, duplicate) in links {
+            sqlx::query(
+                "INSERT INTO conversation_transcriptions (transcription_id, conversation_id, duplicate)
+                 VALUES ($1, $2, $3) ON CONFLICT (transcription_id) DO NOTHING",
+            )
+            .bind(transcription_id)
+            .bind(id)
+            .bind(duplicate)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    async fn delete_conversation(&self, id: i64) -> Result<bool, sqlx::Error> {
+        // turns and transcription links cascade
+        let result = sqlx::query("DELETE FROM conversations WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn conversation_transcription_ids(
+        &self,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<i64>, sqlx::Error> {
+        sqlx::query_scalar(
+            "SELECT ct.transcription_id FROM conversation_transcriptions ct
+             JOIN audio_transcriptions t ON t.id = ct.transcription_id
+             WHERE t.timestamp >= $1 AND t.timestamp <= $2",
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .fetch_all(&self.pool)
+        .await
+    }
+}
@@ -0,0 +1,765 @@
+//! Automation rules: conditions on captured content and events, and what to do when they
+//! match.
+//!
+//! Rules are written in json or yaml:
+//!
+//! ```yaml
+//! name: standup notes
+//! when:
+//!   app: "*slack*"
+//!   text: standup
+//!   time: { after: "09:00", before: "10:30", days: [mon, tue, wed, thu, fri] }
+//! debounce_secs: 600
+//! actions:
+//!   - type: tag
+//!     tags: [standup]
+//!   - type: emit
+//!     event: standup_seen
+//! ```
+//!
+//! Captured content (OCR, transcriptions, UI text) is read back from the store once indexed,
+//! events come from the bus. A rule fires at most once per `debounce_secs` of content time,
+//! so a dry run over past content fires like the live rule would have.
+
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, Datelike, Local, NaiveTime, Utc, Weekday};
+use futures::StreamExt;
+use regex::Regex;
+use screenpipe_events::{glob_match, send_event, subscribe_to_all_events, Event, EventFilter};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::core::pause_capture;
+use crate::db_types::{ContentType, SearchResult, TagContentType};
+use crate::store::{content_between, Store};
+use crate::webhooks::WebhookDispatcher;
+use crate::PipeManager;
+
+/// Minutes of content a dry run reads at once.
+const DRY_RUN_WINDOW_MINUTES: i64 = 60;
+/// Rules an event can go through by emitting events other rules listen to, so rules that
+/// emit each other's events don't fire forever.
+pub const MAX_EMIT_HOPS: u64 = 8;
+
+/// Bumped whenever rules change, the running engine reloads them before its next check.
+static RULES_VERSION: AtomicU64 = AtomicU64::new(0);
+
+/// Makes the running engine reload its rules, to call after changing them in the store.
+pub fn rules_changed() {
+    RULES_VERSION.fetch_add(1, Ordering::SeqCst);
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleSource {
+    Ocr,
+    Audio,
+    Ui,
+    Event,
+}
+
+/// Every condition set has to hold for a rule to fire.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct RuleCondition {
+    /// What the rule looks at: captured content by default, events when `events` is set.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<RuleSource>,
+    /// Event name globs.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<String>,
+    /// Predicates on event data, like `speaker.id == 3`.
+    #[serde(default, rename = "where", skip_serializing_if = "Option::is_none")]
+    pub predicates: Option<String>,
+    /// App name glob, case insensitive.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app: Option<String>,
+    /// Window name glob, case insensitive.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window: Option<String>,
+    /// Text the content contains, case insensitive.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// Regex the text matches.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    /// Name of the speaker, case insensitive.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speaker: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time: Option<TimeWindow>,
+}
+
+/// Local time of day the content has to be captured at.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct TimeWindow {
+    /// `HH:MM`, inclusive.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after: Option<String>,
+    /// `HH:MM`, exclusive. Earlier than `after` for a window over midnight.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub before: Option<String>,
+    /// Every day when empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub days: Vec<Weekday>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RuleAction {
+    /// Tags the matching frame or audio chunk.
+    Tag { tags: Vec<String> },
+    /// Sends an event with the match as data.
+    Emit { event: String },
+    /// Starts a pipe that isn't running (leaving it disabled), or runs one of its cron
+    /// jobs now.
+    RunPipe {
+        pipe: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        job: Option<String>,
+    },
+    /// Posts the match to a webhook registered through `/webhooks`.
+    Webhook { webhook_id: i64 },
+    /// Stops storing frames and transcriptions for a while.
+    PauseCapture { seconds: u64 },
+}
+
+impl RuleAction {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RuleAction::Tag { .. } => "tag",
+            RuleAction::Emit { .. } => "emit",
+            RuleAction::RunPipe { .. } => "run_pipe",
+            RuleAction::Webhook { .. } => "webhook",
+            RuleAction::PauseCapture { .. } => "pause_capture",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct RuleSpec {
+    pub name: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub when: RuleCondition,
+    /// Seconds the rule stays quiet after firing.
+    #[serde(default)]
+    pub debounce_secs: u64,
+    pub actions: Vec<RuleAction>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl RuleSpec {
+    /// Reads and validates a rule written in json or yaml.
+    pub fn parse(source: &str) -> Result<Self> {
+        let spec: RuleSpec = if source.trim_start().starts_with('{') {
+            serde_json::from_str(source)?
+        } else {
+            serde_yaml::from_str(source)?
+        };
+        spec.validate()?;
+        Ok(spec)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        CompiledRule::new(0, self.clone()).map(|_| ())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Rule {
+    pub id: i64,
+    #[serde(flatten)]
+    pub spec: RuleSpec,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// What a rule is checked against: a piece of captured content or an event.
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct RuleInput {
+    pub source: RuleSource,
+    /// Frame id for ocr, audio chunk id for audio, ui_monitoring id for ui.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    pub timestamp: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speaker: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl RuleInput {
+    /// The usual fields of the payload stand in for app, window, text and speaker.
+    pub fn from_event(event: &Event, timestamp: DateTime<Utc>) -> Self {
+        let field = |names: &[&str]| {
+            names
+                .iter()
+                .find_map(|name| event.data.get(*name).and_then(Value::as_str))
+                .map(str::to_string)
+        };
+        let speaker = field(&["speaker"])
+            .or_else(|| event.data["speaker"]["name"].as_str().map(str::to_string));
+        Self {
+            source: RuleSource::Event,
+            id: None,
+            timestamp,
+            app: field(&["app_name", "app"]),
+            window: field(&["window_name", "window"]),
+            text: field(&["text", "transcription", "text_output"]),
+            speaker,
+            event: Some(event.name.clone()),
+            data: Some(event.data.clone()),
+        }
+    }
+}
+
+/// The input for a search result, with a key that tells apart rows of the same source.
+fn content_input(result: &SearchResult) -> ((RuleSource, i64), RuleInput) {
+    match result {
+        SearchResult::OCR(ocr) => (
+            (RuleSource::Ocr, ocr.frame_id),
+            RuleInput {
+                source: RuleSource::Ocr,
+                id: Some(ocr.frame_id),
+                timestamp: ocr.timestamp,
+                app: Some(ocr.app_name.clone()),
+                window: Some(ocr.window_name.clone()),
+                text: Some(ocr.ocr_text.clone()),
+                speaker: None,
+                event: None,
+                data: None,
+            },
+        ),
+        SearchResult::Audio(audio) => (
+            (RuleSource::Audio, audio.transcription_id),
+            RuleInput {
+                source: RuleSource::Audio,
+                id: Some(audio.audio_chunk_id),
+                timestamp: audio.timestamp,
+                app: None,
+                window: None,
+                text: Some(audio.transcription.clone()),
+                speaker: audio.speaker.as_ref().map(|s| s.name.clone()),
+                event: None,
+                data: None,
+            },
+        ),
+        SearchResult::UI(ui) => (
+            (RuleSource::Ui, ui.id),
+            RuleInput {
+                source: RuleSource::Ui,
+                id: Some(ui.id),
+                timestamp: ui.timestamp,
+                app: Some(ui.app_name.clone()),
+                window: Some(ui.window_name.clone()),
+                text: Some(ui.text.clone()),
+                speaker: None,
+                event: None,
+                data: None,
+            },
+        ),
+    }
+}
+
+/// A rule that matched, with the actions it runs.
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct RuleFiring {
+    pub rule_id: i64,
+    pub rule: String,
+    #[serde(rename = "match")]
+    pub input: RuleInput,
+    pub actions: Vec<RuleAction>,
+}
+
+impl RuleFiring {
+    /// Data of the events and webhook posts of the firing.
+    pub fn payload(&self) -> Value {
+        json!({
+            "rule_id": self.rule_id,
+            "rule": self.rule,
+            "match": self.input,
+            "hops": self.hops(),
+        })
+    }
+
+    /// Rules fired so far in the chain of emitted events that led here, this one included.
+    pub fn hops(&self) -> u64 {
+        let data = self.input.data.as_ref();
+        // only events emitted by a rule carry hops
+        let previous = data
+            .filter(|data| data.get("rule_id").is_some())
+            .and_then(|data| data.get("hops"))
+            .and_then(Value::as_u64)
+            .unwrap_or(0);
+        previous + 1
+    }
+}
+
+struct CompiledRule {
+    id: i64,
+    spec: RuleSpec,
+    sources: Vec<RuleSource>,
+    filter: EventFilter,
+    app: Option<String>,
+    window: Option<String>,
+    text: Option<String>,
+    pattern: Option<Regex>,
+    speaker: Option<String>,
+    after: Option<NaiveTime>,
+    before: Option<NaiveTime>,
+    days: Vec<Weekday>,
+}
+
+impl CompiledRule {
+    fn new(id: i64, spec: RuleSpec) -> Result<Self> {
+        let when = &spec.when;
+        if spec.name.trim().is_empty() {
+            anyhow::bail!("rule has no name");
+        }
+        if spec.actions.is_empty() {
+            anyhow::bail!("rule {} has no actions", spec.name);
+        }
+        let sources = match (&when.sources, when.events.is_empty()) {
+            (sources, _) if !sources.is_empty() => sources.clone(),
+            (_, true) => vec![RuleSource::Ocr, RuleSource::Audio, RuleSource::Ui],
+            (_, false) => vec![RuleSource::Event],
+        };
+        let filter = EventFilter::new(when.events.clone(), when.predicates.as_deref())?;
+
+        for action in &spec.actions {
+            match action {
+                // it would trigger itself forever
+                RuleAction::Emit { event }
+                    if sources.contains(&RuleSource::Event)
+                        && (when.events.is_empty()
+                            || when.events.iter().any(|p| glob_match(p, event))) =>
+                {
+                    anyhow::bail!("rule {} emits {}, an event it listens to", spec.name, event);
+                }
+                RuleAction::Emit { event } if event.trim().is_empty() => {
+                    anyhow::bail!("rule {} emits an event without a name", spec.name);
+                }
+                RuleAction::Tag { tags } if tags.is_empty() => {
+                    anyhow::bail!("rule {} has a tag action without tags", spec.name);
+                }
+                _ => {}
+            }
+        }
+
+        let (after, before, days) = match &when.time {
+            Some(time) => (
+                time.after.as_deref().map(parse_time).transpose()?,
+                time.before.as_deref().map(parse_time).transpose()?,
+                time.days.clone(),
+            ),
+            None => (None, None, Vec::new()),
+        };
+        let lowercase = |s: &Option<String>| s.as_ref().map(|s| s.to_lowercase());
+        Ok(Self {
+            id,
+            sources,
+            filter,
+            app: lowercase(&when.app),
+            window: lowercase(&when.window),
+            text: lowercase(&when.text),
+            pattern: when
+                .pattern
+                .as_deref()
+                .map(Regex::new)
+                .transpose()
+                .map_err(|e| anyhow::anyhow!("invalid pattern in rule {}: {}", spec.name, e))?,
+            speaker: lowercase(&when.speaker),
+            after,
+            before,
+            days,
+            spec,
+        })
+    }
+
+    fn matches(&self, input: &RuleInput) -> bool {
+        if !self.sources.contains(&input.source) {
+            return false;
+        }
+        if let (Some(name), Some(data)) = (&input.event, &input.data) {
+            let event = Event {
+                name: name.clone(),
+                data: data.clone(),
+                offset: None,
+            };
+            if !self.filter.matches(&event) {
+                return false;
+            }
+        }
+        let glob = |pattern: &Option<String>, value: &Option<String>| match pattern {
+            Some(pattern) => value
+                .as_ref()
+                .is_some_and(|v| glob_match(pattern, &v.to_lowercase())),
+            None => true,
+        };
+        if !glob(&self.app, &input.app) || !glob(&self.window, &input.window) {
+            return false;
+        }
+        if let Some(text) = &self.text {
+            if !input
+                .text
+                .as_ref()
+                .is_some_and(|t| t.to_lowercase().contains(text))
+            {
+                return false;
+            }
+        }
+        if let Some(pattern) = &self.pattern {
+            if !input.text.as_ref().is_some_and(|t| pattern.is_match(t)) {
+                return false;
+            }
+        }
+        if let Some(speaker) = &self.speaker {
+            if !input
+                .speaker
+                .as_ref()
+                .is_some_and(|s| s.to_lowercase() == *speaker)
+            {
+                return false;
+            }
+        }
+        self.in_time_window(input.timestamp)
+    }
+
+    fn in_time_window(&self, timestamp: DateTime<Utc>) -> bool {
+        let local = timestamp.with_timezone(&Local);
+        if !self.days.is_empty() && !self.days.contains(&local.weekday()) {
+            return false;
+        }
+        let time = local.time();
+        match (self.after, self.before) {
+            (Some(after), Some(before)) if after > before => time >= after || time < before,
+            (after, before) => {
+                !matches!(after, Some(after) if time < after)
+                    && !matches!(before, Some(before) if time >= before)
+            }
+        }
+    }
+}
+
+fn parse_time(time: &str) -> Result<NaiveTime> {
+    NaiveTime::from_str(time)
+        .or_else(|_| NaiveTime::parse_from_str(time, "%H:%M"))
+        .map_err(|_| anyhow::anyhow!("invalid time of day {}, expected HH:MM", time))
+}
+
+/// Enabled rules with when each last fired.
+#[derive(Default)]
+struct RuleSet {
+    rules: Vec<CompiledRule>,
+    last_fired: HashMap<i64, DateTime<Utc>>,
+}
+
+impl RuleSet {
+    /// Replaces the rules, keeping the debounce state of the ones still there.
+    fn load(&mut self, rules: Vec<Rule>) {
+        self.rules = rules
+            .into_iter()
+            .filter(|rule| rule.spec.enabled)
+            .filter_map(|rule| match CompiledRule::new(rule.id, rule.spec) {
+                Ok(rule) => Some(rule),
+                Err(e) => {
+                    warn!("skipping rule {}: {}", rule.id, e);
+                    None
+                }
+            })
+            .collect();
+        let ids: HashSet<i64> = self.rules.iter().map(|rule| rule.id).collect();
+        self.last_fired.retain(|id, _| ids.contains(id));
+    }
+
+    fn has_source(&self, source: RuleSource) -> bool {
+        self.rules.iter().any(|rule| rule.sources.contains(&source))
+    }
+
+    fn has_content_rules(&self) -> bool {
+        [RuleSource::Ocr, RuleSource::Audio, RuleSource::Ui]
+            .into_iter()
+            .any(|source| self.has_source(source))
+    }
+
+    fn evaluate(&mut self, input: &RuleInput) -> Vec<RuleFiring> {
+        let mut firings = Vec::new();
+        for rule in &self.rules {
+            if !rule.matches(input) {
+                continue;
+            }
+            let debounce = chrono::Duration::seconds(rule.spec.debounce_secs as i64);
+            // content can come in out of order, audio is indexed late
+            if let Some(last) = self.last_fired.get(&rule.id) {
+                if (input.timestamp - *last).abs() < debounce {
+                    continue;
+                }
+            }
+            self.last_fired.insert(rule.id, input.timestamp);
+            firings.push(RuleFiring {
+                rule_id: rule.id,
+                rule: rule.spec.name.clone(),
+                input: input.clone(),
+                actions: rule.spec.actions.clone(),
+            });
+        }
+        firings
+    }
+}
+
+/// Captured content indexed with a timestamp between `start` and `end`, oldest first.
+async fn fetch_content(
+    store: &dyn Store,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Vec<((RuleSource, i64), RuleInput)>> {
+    let results = content_between(store, ContentType::All, start, end).await?;
+    Ok(results.iter().map(content_input).collect())
+}
+
+/// Where `spec` would have fired on the content captured between `start` and `end`, without
+/// running its actions. Events aren't kept with their time, rules on events never fire here.
+pub async fn dry_run(
+    store: &dyn Store,
+    id: i64,
+    spec: &RuleSpec,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    limit: usize,
+) -> Result<Vec<RuleFiring>> {
+    let mut rules = RuleSet {
+        rules: vec![CompiledRule::new(id, spec.clone())?],
+        ..Default::default()
+    };
+    let mut seen = HashSet::new();
+    let mut firings = Vec::new();
+    let mut window_start = start;
+    while window_start < end && firings.len() < limit {
+        let window_end =
+            (window_start + chrono::Duration::minutes(DRY_RUN_WINDOW_MINUTES)).min(end);
+        for (key, input) in fetch_content(store, window_start, window_end).await? {
+            // windows share their bounds
+            if seen.insert(key) {
+                firings.extend(rules.evaluate(&input));
+            }
+        }
+        window_start = window_end;
+    }
+    firings.truncate(limit);
+    Ok(firings)
+}
+
+#[derive(Clone, Debug)]
+pub struct RuleEngineConfig {
+    /// How often newly indexed content is checked.
+    pub poll_interval: Duration,
+    /// How far back each check looks, content is indexed a while after it is captured
+    /// (audio once its chunk is transcribed).
+    pub lookback: Duration,
+}
+
+impl Default for RuleEngineConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(5),
+            lookback: Duration::from_secs(120),
+        }
+    }
+}
+
+pub struct RuleEngine {
+    store: Arc<dyn Store>,
+    pipe_manager: Option<Arc<PipeManager>>,
+    webhooks: WebhookDispatcher,
+    config: RuleEngineConfig,
+}
+
+impl RuleEngine {
+    /// Without a pipe manager the `run_pipe` actions fail. `webhook` actions go out through
+    /// `webhooks`, the dispatcher of the server.
+    pub fn new(
+        store: Arc<dyn Store>,
+        pipe_manager: Option<Arc<PipeManager>>,
+        webhooks: WebhookDispatcher,
+        config: RuleEngineConfig,
+    ) -> Self {
+        Self {
+            store,
+            pipe_manager,
+            webhooks,
+            config,
+        }
+    }
+
+    /// Checks the rules against new content and events until the task is aborted. Content
+    /// captured before the engine started is left alone.
+    pub fn start(self) -> JoinHandle<()> {
+        let mut events = subscribe_to_all_events();
+        let engine = Arc::new(self);
+        tokio::spawn(async move {
+            let started = Utc::now();
+            let lookback = chrono::Duration::from_std(engine.config.lookback)
+                .unwrap_or_else(|_| chrono::Duration::minutes(2));
+            let mut rules = RuleSet::default();
+            let mut loaded_version = None;
+            let mut seen: HashMap<(RuleSource, i64), DateTime<Utc>> = HashMap::new();
+            let mut poll = tokio::time::interval(engine.config.poll_interval);
+
+            loop {
+                tokio::select! {
+                    event = events.next() => {
+                        let Some(event) = event else { break };
+                        engine.reload(&mut rules, &mut loaded_version).await;
+                        if !rules.has_source(RuleSource::Event) {
+                            continue;
+                        }
+                        let input = RuleInput::from_event(&event, Utc::now());
+                        for firing in rules.evaluate(&input) {
+                            engine.fire(firing);
+                        }
+                    }
+                    _ = poll.tick() => {
+                        engine.reload(&mut rules, &mut loaded_version).await;
+                        if !rules.has_content_rules() {
+                            continue;
+                        }
+                        let now = Utc::now();
+                        let start = (now - lookback).max(started);
+                        match fetch_content(engine.store.as_ref(), start, now).await {
+                            Ok(inputs) => {
+                                for (key, input) in inputs {
+                                    if seen.insert(key, input.timestamp).is_some() {
+                                        continue;
+                                    }
+                                    for firing in rules.evaluate(&input) {
+                                        engine.fire(firing);
+                                    }
+                                }
+                            }
+                            Err(e) => warn!("failed to read new content for rules: {}", e),
+                        }
+                        // older content isn't read again
+                        seen.retain(|_, timestamp| *timestamp >= start);
+                    }
+                }
+            }
+        })
+    }
+
+    async fn reload(&self, rules: &mut RuleSet, loaded_version: &mut Option<u64>) {
+        let version = RULES_VERSION.load(Ordering::SeqCst);
+        if *loaded_version == Some(version) {
+            return;
+        }
+        match self.store.list_rules().await {
+            Ok(loaded) => {
+                rules.load(loaded);
+                *loaded_version = Some(version);
+                debug!("loaded {} enabled rules", rules.rules.len());
+            }
+            // tried again on the next check
+            Err(e) => warn!("failed to load rules: {}", e),
+        }
+    }
+
+    fn fire(self: &Arc<Self>, firing: RuleFiring) {
+        info!(
+            "rule {} ({}) fired on {:?} content",
+            firing.rule_id, firing.rule, firing.input.source
+        );
+        let engine = self.clone();
+        tokio::spawn(async move {
+            for action in &firing.actions {
+                if let Err(e) = engine.run_action(action, &firing).await {
+                    warn!(
+                        "{} action of rule {} failed: {}",
+                        action.kind(),
+                        firing.rule,
+                        e
+                    );
+                }
+            }
+        });
+    }
+
+    async fn run_action(&self, action: &RuleAction, firing: &RuleFiring) -> Result<()> {
+        match action {
+            RuleAction::Tag { tags } => {
+                let content_type = match firing.input.source {
+                    RuleSource::Ocr => TagContentType::Vision,
+                    RuleSource::Audio => TagContentType::Audio,
+                    // ui text and events can't be tagged
+                    _ => return Ok(()),
+                };
+                if let Some(id) = firing.input.id {
+                    self.store.add_tags(id, content_type, tags.clone()).await?;
+                }
+            }
+            RuleAction::Emit { event } => {
+                if firing.hops() >= MAX_EMIT_HOPS {
+                    anyhow::bail!(
+                        "not emitting {}, the event went through {} rules already",
+                        event,
+                        firing.hops()
+                    );
+                }
+                send_event(event.clone(), firing.payload())?
+            }
+            RuleAction::RunPipe { pipe, job } => {
+                let pipe_manager = self
+                    .pipe_manager
+                    .as_ref()
+                    .ok_or_else(|| anyhow::anyhow!("pipes are not available"))?;
+                match job {
+                    Some(job) => {
+                        if pipe_manager.run_cron_now(pipe, job).await?.is_none() {
+                            anyhow::bail!("pipe {} has no cron job {}", pipe, job);
+                        }
+                    }
+                    None => {
+                        if !pipe_manager.start_pipe(pipe).await? {
+                            debug!("pipe {} is already running", pipe);
+                        }
+                    }
+                }
+            }
+            RuleAction::Webhook { webhook_id } => {
+                let webhook = self
+                    .store
+                    .get_webhook(*webhook_id)
+                    .await?
+                    .ok_or_else(|| anyhow::anyhow!("webhook {} not found", webhook_id))?;
+                let event = Event {
+                    name: "rule_fired".to_string(),
+                    data: firing.payload(),
+                    offset: None,
+                };
+                self.webhooks.deliver(&webhook, &event).await;
+            }
+            RuleAction::PauseCapture { seconds } => pause_capture(Duration::from_secs(*seconds)),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,1014 @@
+//! `screenpipe seed`: a database and media directory filled with days of made up activity,
+//! to test pipes, search and the UI without anyone's real recordings.
+//!
+//! A workday (in UTC) is a series of sessions in a few apps, broken up by lunch and
+//! meetings. Each session writes a UI monitoring row when it starts and a frame with its OCR
+//! text every `frame_interval`; meetings also get transcriptions of several speakers, on the
+//! microphone for the user and on the speakers for the others. Frames are rendered with a
+//! small built-in bitmap font and encoded through the recorder's ffmpeg pipeline, meeting
+//! audio is synthesized and encoded like recorded chunks.
+//!
+//! Everything comes from one generator seeded with `seed`: the same config gives the same
+//! database and media.
+
+use std::collections::BTreeMap;
+use std::f32::consts::PI;
+use std::io::Cursor;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::Result;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use image::{ImageFormat, Rgb, RgbImage};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use screenpipe_audio::{encode_single_audio, AudioDevice, DeviceType};
+use screenpipe_core::find_ffmpeg_path;
+use screenpipe_vision::OcrEngine;
+use serde::Serialize;
+use serde_json::json;
+use tokio::process::{Child, ChildStdin};
+use tracing::info;
+
+use crate::db_types::TagContentType;
+use crate::video::{
+    finish_ffmpeg_process, spawn_ffmpeg_loggers, start_ffmpeg_process, write_frame_to_ffmpeg,
+};
+use crate::Store;
+
+const MONITOR: &str = "monitor_1";
+const MICROPHONE: &str = "MacBook Pro Microphone";
+const SPEAKERS_DEVICE: &str = "MacBook Pro Speakers";
+const TRANSCRIPTION_ENGINE: &str = "WhisperLargeV3Turbo";
+/// Frames per video chunk, a minute at the recorder's default 1 fps.
+const FRAMES_PER_CHUNK: usize = 60;
+const AUDIO_CHUNK_SECS: f64 = 30.0;
+const SAMPLE_RATE: u32 = 16000;
+const SPEAKER_EMBEDDING_LEN: usize = 512;
+
+const FRAME_WIDTH: u32 = 1280;
+const FRAME_HEIGHT: u32 = 800;
+const TITLE_HEIGHT: u32 = 40;
+/// Pixels per dot of the 3x5 font in the body of frames.
+const TEXT_SCALE: u32 = 3;
+const LINE_HEIGHT: u32 = 24;
+const BODY_LINES: usize = 30;
+
+pub struct SeedConfig {
+    /// Days of activity, the last one is the day of `until`.
+    pub days: u32,
+    /// Nothing happens after this.
+    pub until: DateTime<Utc>,
+    pub seed: u64,
+    /// Seconds between two frames.
+    pub frame_interval: u32,
+    /// Render and encode frames and audio. Without it only the database is filled and its
+    /// media paths point to files that don't exist.
+    pub media: bool,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct SeedSummary {
+    pub video_chunks: usize,
+    pub frames: usize,
+    pub audio_chunks: usize,
+    pub transcriptions: usize,
+    pub speakers: usize,
+    pub ui_monitoring: usize,
+    pub tags: usize,
+}
+
+/// Fills `store` with `config.days` of activity, media goes to `media_dir`.
+pub async fn seed_database(
+    store: &dyn Store,
+    media_dir: &Path,
+    config: &SeedConfig,
+) -> Result<SeedSummary> {
+    if config.frame_interval == 0 {
+        anyhow::bail!("frame interval must be at least one second");
+    }
+    if config.media {
+        if find_ffmpeg_path().is_none() {
+            anyhow::bail!("ffmpeg not found, install it or seed without media");
+        }
+        tokio::fs::create_dir_all(media_dir).await?;
+    }
+
+    let mut seeder = Seeder {
+        store,
+        media_dir,
+        config,
+        rng: StdRng::seed_from_u64(config.seed),
+        speakers: Vec::new(),
+        ocr_engine: Arc::new(OcrEngine::Tesseract),
+        ffmpeg: None,
+        chunk_frames: 0,
+        last_frame: None,
+        summary: SeedSummary::default(),
+    };
+    seeder.insert_speakers().await?;
+    let last_day = config.until.date_naive();
+    for offset in (0..config.days).rev() {
+        let date = last_day - Duration::days(offset as i64);
+        info!("seeding {}", date);
+        let sessions = plan_day(&mut seeder.rng, date, &seeder.speakers);
+        for session in sessions {
+            if session.start > config.until {
+                break;
+            }
+            seeder.write_session(session).await?;
+        }
+    }
+    seeder.finish_video_chunk().await;
+    Ok(seeder.summary)
+}
+
+struct Seeder<'a> {
+    store: &'a dyn Store,
+    media_dir: &'a Path,
+    config: &'a SeedConfig,
+    rng: StdRng,
+    speakers: Vec<SeededSpeaker>,
+    ocr_engine: Arc<OcrEngine>,
+    /// Encoder of the current video chunk, when rendering media.
+    ffmpeg: Option<(Child, ChildStdin)>,
+    chunk_frames: usize,
+    last_frame: Option<DateTime<Utc>>,
+    summary: SeedSummary,
+}
+
+struct SeededSpeaker {
+    id: i64,
+    name: &'static str,
+    /// Base frequency of the synthesized voice, in hertz.
+    pitch: f32,
+}
+
+impl Seeder<'_> {
+    /// The user (always first) and the people they meet, one left unnamed like speakers
+    /// nobody labeled yet.
+    async fn insert_speakers(&mut self) -> Result<()> {
+        for name in [
+            Some("Sam Rivera"),
+            Some("Alice Martin"),
+            Some("Bob Chen"),
+            Some("Carla Diaz"),
+            Some("Dev Patel"),
+            None,
+        ] {
+            // random directions in 512 dimensions are far apart, no speaker matches another
+            let mut embedding: Vec<f32> = (0..SPEAKER_EMBEDDING_LEN)
+                .map(|_| self.rng.gen_range(-1.0..1.0))
+                .collect();
+            let norm = embedding.iter().map(|v| v * v).sum::<f32>().sqrt();
+            embedding.iter_mut().for_each(|v| *v /= norm);
+
+            let speaker = self.store.insert_speaker(&embedding).await?;
+            if let Some(name) = name {
+                self.store.update_speaker_name(speaker.id, name).await?;
+            }
+            self.speakers.push(SeededSpeaker {
+                id: speaker.id,
+                name: name.unwrap_or("Guest"),
+                pitch: self.rng.gen_range(95.0..230.0),
+            });
+        }
+        self.summary.speakers = self.speakers.len();
+        Ok(())
+    }
+
+    async fn write_session(&mut self, mut session: Session) -> Result<()> {
+        let app = session.app;
+        self.store
+            .insert_ui_monitoring(
+                &format!("{}\n{}", session.window, session.lines.join("\n")),
+                app.name,
+                &session.window,
+                session.start,
+            )
+            .await?;
+        self.summary.ui_monitoring += 1;
+        if let Some(meeting) = &session.meeting {
+            self.write_meeting_audio(session.start, meeting).await?;
+        }
+
+        let interval = Duration::seconds(self.config.frame_interval as i64);
+        let mut timestamp = session.start;
+        let mut scroll = 0;
+        while timestamp < session.end && timestamp <= self.config.until {
+            let lines = match &session.meeting {
+                Some(meeting) => {
+                    let elapsed = (timestamp - session.start).num_seconds() as f64;
+                    meeting_screen(&session.window, meeting, elapsed, &self.speakers)
+                }
+                None => {
+                    edit_lines(&mut self.rng, app, &mut session.lines, &mut scroll);
+                    session
+                        .lines
+                        .iter()
+                        .skip(scroll)
+                        .take(BODY_LINES)
+                        .cloned()
+                        .collect()
+                }
+            };
+            let frame_id = self
+                .write_frame(timestamp, app, &session.window, &lines)
+                .await?;
+
+            if timestamp == session.start {
+                let tag = match session.meeting {
+                    Some(_) => Some("meeting"),
+                    None if self.rng.gen_bool(0.05) => Some(
+                        *["important", "todo", "follow-up"]
+                            .choose(&mut self.rng)
+                            .unwrap(),
+                    ),
+                    None => None,
+                };
+                if let Some(tag) = tag {
+                    self.store
+                        .add_tags(frame_id, TagContentType::Vision, vec![tag.to_string()])
+                        .await?;
+                    self.summary.tags += 1;
+                }
+            }
+            timestamp += interval;
+        }
+        Ok(())
+    }
+
+    /// Inserts a frame with its OCR text, in a new video chunk when the current one is full
+    /// or the screen was idle.
+    async fn write_frame(
+        &mut self,
+        timestamp: DateTime<Utc>,
+        app: &App,
+        window: &str,
+        lines: &[String],
+    ) -> Result<i64> {
+        let idle_after = Duration::seconds(self.config.frame_interval as i64 * 3);
+        let idle = self
+            .last_frame
+            .is_none_or(|last| timestamp - last > idle_after);
+        if idle || self.chunk_frames >= FRAMES_PER_CHUNK {
+            self.start_video_chunk(timestamp).await?;
+        }
+        if let Some((_, stdin)) = self.ffmpeg.as_mut() {
+            let mut png = Vec::new();
+            render_frame(app, window, lines)
+                .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+            write_frame_to_ffmpeg(stdin, &png).await?;
+        }
+        self.chunk_frames += 1;
+        self.last_frame = Some(timestamp);
+
+        let frame_id = self.store.insert_frame(MONITOR, Some(timestamp)).await?;
+        let text_json = lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| !line.is_empty())
+            .map(|(i, line)| {
+                json!({
+                    "text": line,
+                    "confidence": "96.00",
+                    "line_position": format!("level4page_num1block_num1par_num1line_num{}", i),
+                })
+            })
+            .collect::<Vec<_>>();
+        self.store
+            .insert_ocr_text(
+                frame_id,
+                &lines.join("\n"),
+                &serde_json::to_string(&text_json)?,
+                app.name,
+                window,
+                self.ocr_engine.clone(),
+                true,
+            )
+            .await?;
+        self.summary.frames += 1;
+        Ok(frame_id)
+    }
+
+    async fn start_video_chunk(&mut self, timestamp: DateTime<Utc>) -> Result<()> {
+        self.finish_video_chunk().await;
+        let path = self
+            .media_dir
+            .join(format!(
+                "{}_{}.mp4",
+                MONITOR,
+                timestamp.format("%Y-%m-%d_%H-%M-%S")
+            ))
+            .to_string_lossy()
+            .into_owned();
+        self.store.insert_video_chunk(&path, MONITOR).await?;
+        if self.config.media {
+            let mut child = start_ffmpeg_process(&path, 1.0).await?;
+            let stdin = child
+                .stdin
+                .take()
+                .ok_or_else(|| anyhow::anyhow!("failed to open ffmpeg stdin"))?;
+            spawn_ffmpeg_loggers(child.stderr.take(), child.stdout.take());
+            self.ffmpeg = Some((child, stdin));
+        }
+        self.chunk_frames = 0;
+        self.summary.video_chunks += 1;
+        Ok(())
+    }
+
+    async fn finish_video_chunk(&mut self) {
+        if let Some((child, stdin)) = self.ffmpeg.take() {
+            finish_ffmpeg_process(child, Some(stdin)).await;
+        }
+    }
+
+    /// Writes the meeting starting at `start` as audio chunks of the microphone (the user)
+    /// and of the speakers (everyone else), like the recorder splits them.
+    async fn write_meeting_audio(&mut self, start: DateTime<Utc>, meeting: &Meeting) -> Result<()> {
+        for device in [
+            AudioDevice::new(MICROPHONE.to_string(), DeviceType::Input),
+            AudioDevice::new(SPEAKERS_DEVICE.to_string(), DeviceType::Output),
+        ] {
+            let user = device.device_type == DeviceType::Input;
+            let mut chunks: BTreeMap<u64, Vec<&Utterance>> = BTreeMap::new();
+            for utterance in &meeting.utterances {
+                if (utterance.speaker == 0) == user {
+                    let index = (utterance.start / AUDIO_CHUNK_SECS) as u64;
+                    chunks.entry(index).or_default().push(utterance);
+                }
+            }
+
+            for (index, utterances) in chunks {
+                let offset = index as f64 * AUDIO_CHUNK_SECS;
+                let chunk_start = start + Duration::milliseconds((offset * 1000.0) as i64);
+                if chunk_start > self.config.until {
+                    break;
+                }
+                let path = self.media_dir.join(format!(
+                    "{}_{}.mp4",
+                    device.name.replace(['/', '\\'], "_"),
+                    chunk_start.format("%Y-%m-%d_%H-%M-%S")
+                ));
+                if self.config.media {
+                    let samples = synthesize_speech(&utterances, offset, &self.speakers);
+                    let encode_path = path.clone();
+                    tokio::task::spawn_blocking(move || {
+                        let data: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+                        encode_single_audio(&data, SAMPLE_RATE, 1, &encode_path)
+                    })
+                    .await??;
+                }
+
+                let audio_chunk_id = self
+                    .store
+                    .insert_audio_chunk(&path.to_string_lossy())
+                    .await?;
+                for (i, utterance) in utterances.iter().enumerate() {
+                    self.store
+                        .insert_audio_transcription(
+                            audio_chunk_id,
+                            &utterance.text,
+                            i as i64,
+                            TRANSCRIPTION_ENGINE,
+                            &device,
+                            Some(self.speakers[utterance.speaker].id),
+                            Some(utterance.start - offset),
+                            Some(utterance.end - offset),
+                        )
+                        .await?;
+                }
+                self.store
+                    .set_audio_chunk_timestamp(audio_chunk_id, chunk_start)
+                    .await?;
+                self.store
+                    .add_tags(
+                        audio_chunk_id,
+                        TagContentType::Audio,
+                        vec!["meeting".to_string()],
+                    )
+                    .await?;
+                self.summary.audio_chunks += 1;
+                self.summary.transcriptions += utterances.len();
+                self.summary.tags += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+// activity
+
+struct Session {
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    app: &'static App,
+    window: String,
+    /// Whole content of the window, frames show the part scrolled to.
+    lines: Vec<String>,
+    meeting: Option<Meeting>,
+}
+
+struct Meeting {
+    /// Indexes in the speakers, the user first.
+    participants: Vec<usize>,
+    utterances: Vec<Utterance>,
+}
+
+struct Utterance {
+    speaker: usize,
+    /// Seconds since the start of the meeting.
+    start: f64,
+    end: f64,
+    text: String,
+}
+
+/// Sessions of `date`: a workday with lunch, a standup and maybe another meeting, or on
+/// weekends sometimes an evening.
+fn plan_day(rng: &mut StdRng, date: NaiveDate, speakers: &[SeededSpeaker]) -> Vec<Session> {
+    let midnight = date.and_hms_opt(0, 0, 0).unwrap().and_utc();
+    let at = |minutes: i64| midnight + Duration::minutes(minutes);
+
+    let (start, end, mut breaks) = if date.weekday().number_from_monday() >= 6 {
+        if !rng.gen_bool(0.4) {
+            return Vec::new();
+        }
+        let start = at(20 * 60 + rng.gen_range(0..60));
+        (
+            start,
+            start + Duration::minutes(rng.gen_range(20..90)),
+            Vec::new(),
+        )
+    } else {
+        let lunch = at(12 * 60 + 15 + rng.gen_range(0..30));
+        let standup = at(10 * 60);
+        let mut breaks = vec![
+            (lunch, lunch + Duration::minutes(45), None),
+            (
+                standup,
+                standup + Duration::minutes(15),
+                Some(plan_meeting(rng, "{Project} standup", 15, speakers)),
+            ),
+        ];
+        if rng.gen_bool(0.7) {
+            let title = *[
+                "{Project} planning",
+                "{Project} review",
+                "1:1 {first}",
+                "{Project} sync",
+            ]
+            .choose(rng)
+            .unwrap();
+            let minutes = *[30, 45].choose(rng).unwrap();
+            let start = at(rng.gen_range(14..17) * 60);
+            breaks.push((
+                start,
+                start + Duration::minutes(minutes),
+                Some(plan_meeting(rng, title, minutes, speakers)),
+            ));
+        }
+        (
+            at(8 * 60 + 30 + rng.gen_range(0..60)),
+            at(17 * 60 + 30 + rng.gen_range(0..90)),
+            breaks,
+        )
+    };
+    breaks.sort_by_key(|(start, _, _)| *start);
+    breaks.push((end, end, None));
+
+    let mut sessions = Vec::new();
+    let mut now = start;
+    for (break_start, break_end, meeting) in breaks {
+        while now < break_start {
+            let session_end = (now + Duration::minutes(rng.gen_range(2..25))).min(break_start);
+            sessions.push(plan_session(rng, now, session_end));
+            now = session_end;
+        }
+        if let Some((title, meeting)) = meeting {
+            sessions.push(Session {
+                start: break_start,
+                end: break_end,
+                app: &MEETING_APP,
+                window: title,
+                lines: Vec::new(),
+                meeting: Some(meeting),
+            });
+        }
+        now = now.max(break_end);
+    }
+    sessions
+}
+
+fn plan_session(rng: &mut StdRng, start: DateTime<Utc>, end: DateTime<Utc>) -> Session {
+    let total: u32 = APPS.iter().map(|app| app.weight).sum();
+    let mut pick = rng.gen_range(0..total);
+    let app = APPS
+        .iter()
+        .find(|app| {
+            if pick < app.weight {
+                return true;
+            }
+            pick -= app.weight;
+            false
+        })
+        .unwrap();
+    let window = fill(app.windows.choose(rng).unwrap(), rng);
+    let lines = (0..rng.gen_range(20..60))
+        .map(|_| fill(app.lines.choose(rng).unwrap(), rng))
+        .collect();
+    Session {
+        start,
+        end,
+        app,
+        window,
+        lines,
+        meeting: None,
+    }
+}
+
+fn plan_meeting(
+    rng: &mut StdRng,
+    title: &str,
+    minutes: i64,
+    speakers: &[SeededSpeaker],
+) -> (String, Meeting) {
+    let mut participants = vec![0];
+    let others: Vec<usize> = (1..speakers.len()).collect();
+    let count = rng.gen_range(1..=3);
+    participants.extend(others.choose_multiple(rng, count).copied());
+
+    let mut utterances = Vec::new();
+    let mut now = rng.gen_range(1.0..5.0);
+    while now < (minutes * 60) as f64 - 10.0 {
+        let speaker = if rng.gen_bool(0.3) {
+            0
+        } else {
+            *participants[1..].choose(rng).unwrap()
+        };
+        let text = fill(CONVERSATION.choose(rng).unwrap(), rng);
+        let end = now + text.split_whitespace().count() as f64 * 0.4 + 0.6;
+        utterances.push(Utterance {
+            speaker,
+            start: now,
+            end,
+            text,
+        });
+        now = end + rng.gen_range(0.3..3.0);
+    }
+    (
+        fill(title, rng),
+        Meeting {
+            participants,
+            utterances,
+        },
+    )
+}
+
+/// Types, scrolls or receives a line, depending on the app.
+fn edit_lines(rng: &mut StdRng, app: &App, lines: &mut Vec<String>, scroll: &mut usize) {
+    match app.kind {
+        AppKind::Chat | AppKind::Terminal => {
+            if rng.gen_bool(0.3) {
+                lines.push(fill(app.lines.choose(rng).unwrap(), rng));
+            }
+            *scroll = lines.len().saturating_sub(BODY_LINES);
+        }
+        AppKind::Editor | AppKind::Notes => {
+            if rng.gen_bool(0.3) {
+                let at = (*scroll + rng.gen_range(0..BODY_LINES)).min(lines.len());
+                lines.insert(at, fill(app.lines.choose(rng).unwrap(), rng));
+            }
+            if rng.gen_bool(0.1) {
+                *scroll = (*scroll + rng.gen_range(1..10)).min(lines.len().saturating_sub(1));
+            }
+        }
+        AppKind::Browser | AppKind::Mail | AppKind::Meeting => {
+            if rng.gen_bool(0.2) {
+                *scroll = (*scroll + rng.gen_range(1..10)).min(lines.len().saturating_sub(1));
+            }
+        }
+    }
+}
+
+/// What a meeting window shows `elapsed` seconds in: the participants and a caption of the
+/// last thing said.
+fn meeting_screen(
+    title: &str,
+    meeting: &Meeting,
+    elapsed: f64,
+    speakers: &[SeededSpeaker],
+) -> Vec<String> {
+    let mut lines = vec![title.to_string(), String::new()];
+    lines.extend(
+        meeting
+            .participants
+            .iter()
+            .map(|&speaker| speakers[speaker].name.to_string()),
+    );
+    lines.push(String::new());
+    if let Some(utterance) = meeting
+        .utterances
+        .iter()
+        .rev()
+        .find(|utterance| utterance.start <= elapsed)
+    {
+        lines.push(format!(
+            "{}: {}",
+            speakers[utterance.speaker].name, utterance.text
+        ));
+        lines.push(String::new());
+    }
+    lines.push("Mute   Stop Video   Participants   Chat   Share Screen   Leave".to_string());
+    lines
+}
+
+// content
+
+#[derive(Clone, Copy)]
+enum AppKind {
+    Editor,
+    Browser,
+    Chat,
+    Notes,
+    Terminal,
+    Mail,
+    Meeting,
+}
+
+struct App {
+    name: &'static str,
+    kind: AppKind,
+    /// Share of the work sessions spent in the app.
+    weight: u32,
+    background: [u8; 3],
+    text: [u8; 3],
+    windows: &'static [&'static str],
+    lines: &'static [&'static str],
+}
+
+const APPS: &[App] = &[
+    App {
+        name: "Code",
+        kind: AppKind::Editor,
+        weight: 30,
+        background: [30, 30, 30],
+        text: [212, 212, 212],
+        windows: &["{noun}.rs - {project}", "{noun}_{verb}.ts - {project}"],
+        lines: &[
+            "use crate::{noun}::{Noun};",
+            "pub async fn {verb}_{noun}(&self, id: i64) -> Result<{Noun}> {",
+            "    let {noun} = self.store.get_{noun}(id).await?;",
+            "    if {noun}.is_empty() {",
+            "        return Err(anyhow!(\"no {noun} for {project}\"));",
+            "    self.{verb}_{noun}s(&{noun}).await?;",
+            "    // TODO: {verb} the {noun}s in batches",
+            "    Ok({noun})",
+            "}",
+            "",
+        ],
+    },
+    App {
+        name: "Arc",
+        kind: AppKind::Browser,
+        weight: 20,
+        background: [250, 250, 250],
+        text: [32, 33, 36],
+        windows: &[
+            "{Project} docs - {noun} api",
+            "Pull request #{n}: {verb} {noun}s - GitHub",
+            "{noun} {verb} error - Stack Overflow",
+            "Grafana - {project} {noun}s",
+        ],
+        lines: &["{sentence}", "{sentence}", ""],
+    },
+    App {
+        name: "Slack",
+        kind: AppKind::Chat,
+        weight: 20,
+        background: [26, 29, 33],
+        text: [209, 210, 211],
+        windows: &["#{channel} - acme"],
+        lines: &["{person}: {sentence}"],
+    },
+    App {
+        name: "Notion",
+        kind: AppKind::Notes,
+        weight: 12,
+        background: [255, 255, 255],
+        text: [55, 53, 47],
+        windows: &["{Project} {doc}"],
+        lines: &[
+            "- {sentence}",
+            "- [ ] {verb} the {noun}s ({first})",
+            "## {Noun}s",
+        ],
+    },
+    App {
+        name: "iTerm2",
+        kind: AppKind::Terminal,
+        weight: 12,
+        background: [0, 0, 0],
+        text: [200, 200, 200],
+        windows: &["~/code/{project} - zsh"],
+        lines: &[
+            "$ cargo test -p {project}",
+            "test {noun}::tests::{verb}_{noun} ... ok",
+            "test result: ok. {n} passed; 0 failed",
+            "$ git commit -m \"{verb} {noun}s in {project}\"",
+            "$ git checkout -b {verb}-{noun}",
+        ],
+    },
+    App {
+        name: "Mail",
+        kind: AppKind::Mail,
+        weight: 6,
+        background: [246, 246, 246],
+        text: [30, 30, 30],
+        windows: &["Inbox - acme"],
+        lines: &[
+            "{person}   re: {project} {noun} export",
+            "{person}   {Project} weekly update",
+            "Acme billing   invoice #{n}",
+            "{person}   invitation: {Project} review",
+        ],
+    },
+];
+
+static MEETING_APP: App = App {
+    name: "zoom.us",
+    kind: AppKind::Meeting,
+    weight: 0,
+    background: [36, 36, 36],
+    text: [255, 255, 255],
+    windows: &[],
+    lines: &[],
+};
+
+const PEOPLE: &[&str] = &[
+    "Alice Martin",
+    "Bob Chen",
+    "Carla Diaz",
+    "Dev Patel",
+    "Emma Brown",
+    "Farid Haddad",
+];
+const PROJECTS: &[&str] = &["billing", "search", "onboarding", "analytics", "mobile"];
+const NOUNS: &[&str] = &[
+    "invoice",
+    "customer",
+    "report",
+    "query",
+    "index",
+    "session",
+    "payment",
+    "dashboard",
+    "release",
+    "migration",
+    "cache",
+    "webhook",
+];
+const VERBS: &[&str] = &[
+    "fetch", "update", "sync", "validate", "render", "retry", "export", "merge", "parse",
+    "schedule",
+];
+const CHANNELS: &[&str] = &["general", "engineering", "design", "support", "releases"];
+const DOCS: &[&str] = &["roadmap", "meeting notes", "retro", "spec", "onboarding"];
+const SENTENCES: &[&str] = &[
+    "the {noun} {verb} is slower than expected on {project}",
+    "can someone review the {project} {noun} changes before friday",
+    "{first} shipped the new {noun} flow for {project}",
+    "we should {verb} the {noun}s before the next release",
+    "customers keep asking about the {noun} export",
+    "the {noun} migration finished, {n} rows updated",
+    "{project} dashboard shows {n} errors since yesterday",
+    "let's {verb} the {noun} on every deploy",
+];
+const CONVERSATION: &[&str] = &[
+    "ok let's start with the {project} update",
+    "I finished the {noun} part yesterday, today I'm on the {verb} job",
+    "any blockers on {project}",
+    "the {noun} tests are flaky, I'll look into it",
+    "can we {verb} the {noun}s before the demo",
+    "let's take that offline",
+    "I'll write it up in notion after the call",
+    "we had {n} {noun}s failing this morning",
+    "sounds good, thanks everyone",
+];
+
+/// Fills the `{key}` placeholders of `template` with made up words, a key used twice gets
+/// the same word and a capitalized key the capitalized word. Other braces are left alone.
+fn fill(template: &str, rng: &mut StdRng) -> String {
+    let mut filled = String::with_capacity(template.len());
+    let mut picked: Vec<(String, String)> = Vec::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        filled.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        let key = after.find('}').map(|close| &after[..close]);
+        let lower = key.map(str::to_lowercase).unwrap_or_default();
+        let value = match picked.iter().find(|(key, _)| *key == lower) {
+            Some((_, value)) => Some(value.clone()),
+            None => placeholder(&lower, rng).inspect(|value| {
+                picked.push((lower.clone(), value.clone()));
+            }),
+        };
+        match (key, value) {
+            (Some(key), Some(value)) => {
+                if key.starts_with(char::is_uppercase) {
+                    let mut chars = value.chars();
+                    filled.extend(chars.next().map(|c| c.to_ascii_uppercase()));
+                    filled.push_str(chars.as_str());
+                } else {
+                    filled.push_str(&value);
+                }
+                rest = &after[key.len() + 1..];
+            }
+            _ => {
+                filled.push('{');
+                rest = after;
+            }
+        }
+    }
+    filled.push_str(rest);
+    filled
+}
+
+fn placeholder(key: &str, rng: &mut StdRng) -> Option<String> {
+    let words = match key {
+        "person" => PEOPLE,
+        "first" => {
+            let person = PEOPLE.choose(rng).unwrap();
+            return Some(person.split(' ').next().unwrap().to_string());
+        }
+        "project" => PROJECTS,
+        "noun" => NOUNS,
+        "verb" => VERBS,
+        "channel" => CHANNELS,
+        "doc" => DOCS,
+        "n" => return Some(rng.gen_range(2..500).to_string()),
+        "sentence" => return Some(fill(SENTENCES.choose(rng).unwrap(), rng)),
+        _ => return None,
+    };
+    Some(words.choose(rng).unwrap().to_string())
+}
+
+// media
+
+fn render_frame(app: &App, window: &str, lines: &[String]) -> RgbImage {
+    let mut image = RgbImage::from_pixel(FRAME_WIDTH, FRAME_HEIGHT, Rgb(app.background));
+    let title_bar =
+        Rgb([0, 1, 2].map(|i| ((app.background[i] as u32 * 5 + app.text[i] as u32) / 6) as u8));
+    for y in 0..TITLE_HEIGHT {
+        for x in 0..FRAME_WIDTH {
+            image.put_pixel(x, y, title_bar);
+        }
+    }
+    draw_text(
+        &mut image,
+        16,
+        13,
+        &format!("{} - {}", app.name, window),
+        2,
+        Rgb(app.text),
+    );
+    for (i, line) in lines.iter().take(BODY_LINES).enumerate() {
+        draw_text(
+            &mut image,
+            24,
+            TITLE_HEIGHT + 16 + i as u32 * LINE_HEIGHT,
+            line,
+            TEXT_SCALE,
+            Rgb(app.text),
+        );
+    }
+    image
+}
+
+/// Draws `text` with the 3x5 font, `scale` pixels per dot, clipped to the image.
+fn draw_text(image: &mut RgbImage, x: u32, y: u32, text: &str, scale: u32, color: Rgb<u8>) {
+    for (i, c) in text.chars().enumerate() {
+        let left = x + i as u32 * 4 * scale;
+        if left + 3 * scale > image.width() {
+            break;
+        }
+        let glyph = glyph(c);
+        for row in 0..5 {
+            for col in 0..3 {
+                if glyph >> ((4 - row) * 3 + (2 - col)) & 1 == 0 {
+                    continue;
+                }
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        let (px, py) = (left + col * scale + dx, y + row * scale + dy);
+                        if py < image.height() {
+                            image.put_pixel(px, py, color);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// 3x5 glyph of `c`, one octal digit per row from the top, the high bit on the left.
+/// Letters are lowercase only.
+fn glyph(c: char) -> u16 {
+    match c.to_ascii_lowercase() {
+        'a' => 0o25755,
+        'b' => 0o65656,
+        'c' => 0o34443,
+        'd' => 0o65556,
+        'e' => 0o74647,
+        'f' => 0o74644,
+        'g' => 0o34553,
+        'h' => 0o55755,
+        'i' => 0o72227,
+        'j' => 0o11152,
+        'k' => 0o55655,
+        'l' => 0o44447,
+        'm' => 0o57755,
+        'n' => 0o65555,
+        'o' => 0o25552,
+        'p' => 0o65644,
+        'q' => 0o25563,
+        'r' => 0o65655,
+        's' => 0o34216,
+        't' => 0o72222,
+        'u' => 0o55557,
+        'v' => 0o55552,
+        'w' => 0o55775,
+        'x' => 0o55255,
+        'y' => 0o55222,
+        'z' => 0o71247,
+        '0' => 0o75557,
+        '1' => 0o26227,
+        '2' => 0o61247,
+        '3' => 0o61216,
+        '4' => 0o55711,
+        '5' => 0o74616,
+        '6' => 0o34757,
+        '7' => 0o71222,
+        '8' => 0o75757,
+        '9' => 0o75716,
+        ' ' => 0,
+        '.' => 0o00002,
+        ',' => 0o00024,
+        ':' => 0o02020,
+        ';' => 0o02024,
+        '-' => 0o00700,
+        '_' => 0o00007,
+        '/' => 0o11244,
+        '\\' => 0o44211,
+        '#' => 0o57575,
+        '@' => 0o25743,
+        '!' => 0o22202,
+        '?' => 0o61202,
+        '(' => 0o12221,
+        ')' => 0o42224,
+        '[' => 0o32223,
+        ']' => 0o62226,
+        '{' => 0o32623,
+        '}' => 0o62326,
+        '<' => 0o12421,
+        '>' => 0o42124,
+        '=' => 0o07070,
+        '+' => 0o02720,
+        '*' => 0o05250,
+        '&' => 0o25257,
+        '\'' => 0o22000,
+        '"' => 0o55000,
+        '$' => 0o37276,
+        '~' => 0o03600,
+        _ => 0o75557,
+    }
+}
+
+/// Voiced syllables at the pitch of each speaker, where the utterances of an audio chunk
+/// starting `offset` seconds into the meeting are.
+fn synthesize_speech(
+    utterances: &[&Utterance],
+    offset: f64,
+    speakers: &[SeededSpeaker],
+) -> Vec<f32> {
+    let duration = utterances
+        .iter()
+        .map(|utterance| utterance.end - offset)
+        .fold(AUDIO_CHUNK_SECS, f64::max);
+    let mut samples = vec![0.0; (duration * SAMPLE_RATE as f64) as usize];
+    for utterance in utterances {
+        let pitch = speakers[utterance.speaker].pitch;
+        let from = ((utterance.start - offset) * SAMPLE_RATE as f64) as usize;
+        let to = (((utterance.end - offset) * SAMPLE_RATE as f64) as usize).min(samples.len());
+        for (i, sample) in samples[from..to].iter_mut().enumerate() {
+            let t = i as f32 / SAMPLE_RATE as f32;
+            // five syllables a second, the voice has a few harmonics
+            let envelope = (t * 5.0 * PI).sin().abs();
+            let voice: f32 = (1..=3)
+                .map(|harmonic| (2.0 * PI * pitch * harmonic as f32 * t).sin() / harmonic as f32)
+                .sum();
+            *sample += 0.2 * envelope * voice;
+        }
+    }
+    samples
+}
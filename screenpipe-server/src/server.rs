@@ -5,31 +5,51 @@ use axum::{
         Json, Path, Query, State,
     },
     http::StatusCode,
-    response::{IntoResponse, Json as JsonResponse, Response},
-    routing::{get, post},
+    response::{
+        sse::{Event as SseEvent, KeepAlive, Sse},
+        AppendHeaders, IntoResponse, Json as JsonResponse, Response,
+    },
+    routing::{delete, get, post},
     serve, Router,
 };
 use tokio_util::io::ReaderStream;
 
 use tokio::fs::File;
+use tokio::sync::broadcast::error::RecvError;
 
 use futures::{
     future::{try_join, try_join_all},
     SinkExt, StreamExt,
 };
 use image::ImageFormat::{self};
-use screenpipe_events::{send_event, subscribe_to_all_events, Event as ScreenpipeEvent};
+use screenpipe_core::{
+    subscribe_pipe_logs, IntegrityError, ManifestError, PipeInstallOptions, PipeLogQuery,
+};
+use screenpipe_events::{
+    subscribe_to_all_events, subscribe_to_all_events_from, Event as ScreenpipeEvent, EventFilter,
+    EventRegistry, EventSchema, EventSubscription,
+};
 
 use crate::{
-    db_types::{ContentType, FrameData, SearchResult, Speaker, TagContentType},
+    db_maintenance::MaintenanceRun,
+    db_types::{
+        ContentType, CountMode, FrameData, SearchCursor, SearchKind, SearchPage, SearchResult,
+        Speaker, SpeakerCursor, TagContentType,
+    },
+    db_writer::DbWriterMetrics,
+    event_socket::{control_event, handle_client_message, strip_images},
+    meetings::{merge_meetings, MeetingUpdate},
     pipe_manager::PipeManager,
+    rules::{dry_run, rules_changed, RuleSpec},
+    storage::{MediaStorage, StorageError},
+    store::Store,
     video::{finish_ffmpeg_process, start_ffmpeg_process, write_frame_to_ffmpeg, MAX_FPS},
     video_cache::{AudioEntry, DeviceFrame, FrameCache, FrameMetadata, TimeSeriesFrame},
     video_utils::{
         extract_frame_from_video, merge_videos, validate_media, MergeVideosRequest,
         MergeVideosResponse, ValidateMediaParams,
     },
-    DatabaseManager,
+    webhooks::{redeliver_dead_letter, seal_webhook_secret, webhooks_changed, WebhookSpec},
 };
 use crate::{plugin::ApiPluginLayer, video_utils::extract_frame};
 use chrono::{DateTime, Utc};
@@ -72,7 +92,7 @@ use std::str::FromStr;
 use crate::text_embeds::generate_embedding;
 
 pub struct AppState {
-    pub db: Arc<DatabaseManager>,
+    pub db: Arc<dyn Store>,
     // pub device_manager: Arc<DeviceManager>,
     pub app_start_time: DateTime<Utc>,
     pub screenpipe_dir: PathBuf,
@@ -82,6 +102,7 @@ pub struct AppState {
     pub ui_monitoring_enabled: bool,
     pub frame_cache: Option<Arc<FrameCache>>,
     pub frame_image_cache: Option<Arc<Mutex<LruCache<i64, (String, Instant)>>>>,
+    pub media_storage: Arc<MediaStorage>,
 }
 
 // Update the SearchQuery struct
@@ -123,8 +144,25 @@ pub(crate) struct PaginationQuery {
     #[serde(default)]
     #[serde(deserialize_with = "deserialize_number_from_string")]
     offset: u32,
+    /// `next_cursor` of the previous page, can't be combined with `offset`
+    #[serde(default)]
+    cursor: Option<String>,
+    #[serde(default)]
+    count: CountMode,
 }
 
+/// `count=approximate` stops counting here.
+const APPROXIMATE_COUNT_CAP: usize = 10_000;
+
+/// Most results a search returns at once, larger limits are lowered to it.
+pub(crate) const MAX_SEARCH_LIMIT: u32 = 1000;
+
+/// Response header carrying the cursor of the next `/speakers/unnamed` page.
+const NEXT_CURSOR_HEADER: &str = "x-next-cursor";
+
+/// Events queued per `/ws/events` connection unless the client asks for another size.
+const DEFAULT_EVENTS_QUEUE: usize = 1000;
+
 fn deserialize_number_from_string<'de, D>(deserializer: D) -> Result<u32, D::Error>
 where
     D: serde::Deserializer<'de>,
@@ -144,7 +182,13 @@ pub struct PaginatedResponse<T> {
 pub struct PaginationInfo {
     pub limit: u32,
     pub offset: u32,
+    /// -1 when the request asked for `count=none`
     pub total: i64,
+    /// set when `total` hit the approximate count cap and is only a lower bound
+    #[serde(default)]
+    pub total_approximate: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -271,6 +315,12 @@ pub struct HealthCheckResponse {
     pub ui_status: String,
     pub message: String,
     pub verbose_instructions: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub db_backend: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub db_writer: Option<DbWriterMetrics>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maintenance: Option<Vec<MaintenanceRun>>,
 }
 
 // Update the search function
@@ -282,11 +332,12 @@ pub(crate) async fn search(
     (StatusCode, JsonResponse<serde_json::Value>),
 > {
     info!(
-        "received search request: query='{}', content_type={:?}, limit={}, offset={}, start_time={:?}, end_time={:?}, app_name={:?}, window_name={:?}, min_length={:?}, max_length={:?}, speaker_ids={:?}, frame_name={:?}",
+        "received search request: query='{}', content_type={:?}, limit={}, offset={}, cursor={:?}, start_time={:?}, end_time={:?}, app_name={:?}, window_name={:?}, min_length={:?}, max_length={:?}, speaker_ids={:?}, frame_name={:?}",
         query.q.as_deref().unwrap_or(""),
         query.content_type,
         query.pagination.limit,
         query.pagination.offset,
+        query.pagination.cursor,
         query.start_time,
         query.end_time,
         query.app_name,
@@ -298,39 +349,97 @@ pub(crate) async fn search(
     );
 
     let query_str = query.q.as_deref().unwrap_or("");
+    let limit = query.pagination.limit.min(MAX_SEARCH_LIMIT);
 
     let content_type = query.content_type.clone();
 
-    let (results, total) = try_join(
-        state.db.search(
-            query_str,
-            content_type.clone(),
-            query.pagination.limit,
-            query.pagination.offset,
-            query.start_time,
-            query.end_time,
-            query.app_name.as_deref(),
-            query.window_name.as_deref(),
-            query.min_length,
-            query.max_length,
-            query.speaker_ids.clone(),
-            query.frame_name.as_deref(),
-        ),
-        state.db.count_search_results(
-            query_str,
-            content_type,
-            query.start_time,
-            query.end_time,
-            query.app_name.as_deref(),
-            query.window_name.as_deref(),
-            query.min_length,
-            query.max_length,
-            query.speaker_ids.clone(),
-            query.frame_name.as_deref(),
-        ),
-    )
-    .await
-    .map_err(|e| {
+    let cursor = match query.pagination.cursor.as_deref() {
+        Some(token) => Some(SearchCursor::decode(token).ok_or_else(|| {
+            (
+                StatusCode::BAD_REQUEST,
+                JsonResponse(json!({"error": "invalid cursor"})),
+            )
+        })?),
+        None => None,
+    };
+    if cursor.is_some() && query.pagination.offset > 0 {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            JsonResponse(json!({"error": "cursor and offset can't be combined"})),
+        ));
+    }
+
+    // offset requests keep the old behavior, everything else pages by cursor
+    let page = async {
+        if query.pagination.offset > 0 {
+            let results = state
+                .db
+                .search(
+                    query_str,
+                    content_type.clone(),
+                    limit,
+                    query.pagination.offset,
+                    query.start_time,
+                    query.end_time,
+                    query.app_name.as_deref(),
+                    query.window_name.as_deref(),
+                    query.min_length,
+                    query.max_length,
+                    query.speaker_ids.clone(),
+                    query.frame_name.as_deref(),
+                )
+                .await?;
+            Ok(SearchPage {
+                results,
+                next_cursor: None,
+            })
+        } else {
+            state
+                .db
+                .search_page(
+                    query_str,
+                    content_type.clone(),
+                    limit,
+                    cursor,
+                    query.start_time,
+                    query.end_time,
+                    query.app_name.as_deref(),
+                    query.window_name.as_deref(),
+                    query.min_length,
+                    query.max_length,
+                    query.speaker_ids.clone(),
+                    query.frame_name.as_deref(),
+                )
+                .await
+        }
+    };
+
+    let count = async {
+        let cap = match query.pagination.count {
+            CountMode::None => return Ok(None),
+            CountMode::Exact => None,
+            CountMode::Approximate => Some(APPROXIMATE_COUNT_CAP),
+        };
+        state
+            .db
+            .count_search_results_capped(
+                query_str,
+                content_type.clone(),
+                query.start_time,
+                query.end_time,
+                query.app_name.as_deref(),
+                query.window_name.as_deref(),
+                query.min_length,
+                query.max_length,
+                query.speaker_ids.clone(),
+                query.frame_name.as_deref(),
+                cap,
+            )
+            .await
+            .map(Some)
+    };
+
+    let (page, total) = try_join(page, count).await.map_err(|e| {
         error!("failed to perform search operations: {}", e);
         (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -338,7 +447,8 @@ pub(crate) async fn search(
         )
     })?;
 
-    let mut content_items: Vec<ContentItem> = results
+    let mut content_items: Vec<ContentItem> = page
+        .results
         .iter()
         .map(|result| match result {
             SearchResult::OCR(ocr) => ContentItem::OCR(OCRContent {
@@ -386,17 +496,30 @@ pub(crate) async fn search(
             .iter()
             .filter_map(|item| {
                 if let ContentItem::OCR(ocr_content) = item {
-                    Some(extract_frame(
-                        &ocr_content.file_path,
-                        ocr_content.offset_index,
-                    ))
+                    let storage = state.media_storage.clone();
+                    let file_path = ocr_content.file_path.clone();
+                    let offset_index = ocr_content.offset_index;
+                    Some(async move {
+                        let local_path = storage.resolve(&file_path).await?;
+                        extract_frame(&local_path.to_string_lossy(), offset_index).await
+                    })
                 } else {
                     None
                 }
             })
             .collect();
 
-        let frames = try_join_all(frame_futures).await.unwrap(); // TODO: handle error
+        let frames = try_join_all(frame_futures).await.map_err(|e| {
+            error!("failed to extract frames for search results: {}", e);
+            let status = e
+                .downcast_ref::<StorageError>()
+                .map(storage_error_status)
+                .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (
+                status,
+                JsonResponse(json!({"error": format!("failed to extract frames: {}", e)})),
+            )
+        })?;
 
         for (item, frame) in content_items.iter_mut().zip(frames.into_iter()) {
             if let ContentItem::OCR(ref mut ocr_content) = item {
@@ -405,13 +528,16 @@ pub(crate) async fn search(
         }
     }
 
-    info!("search completed: found {} results", total);
+    info!("search completed: found {:?} results", total);
     Ok(JsonResponse(PaginatedResponse {
         data: content_items,
         pagination: PaginationInfo {
-            limit: query.pagination.limit,
+            limit,
             offset: query.pagination.offset,
-            total: total as i64,
+            total: total.map_or(-1, |total| total as i64),
+            total_approximate: query.pagination.count == CountMode::Approximate
+                && total >= Some(APPROXIMATE_COUNT_CAP),
+            next_cursor: page.next_cursor.map(|cursor| cursor.encode()),
         },
     }))
 }
@@ -659,12 +785,18 @@ pub async fn health_check(State(state): State<Arc<AppState>>) -> JsonResponse<He
         ui_status: ui_status.to_string(),
         message,
         verbose_instructions,
+        db_backend: Some(state.db.backend().to_string()),
+        db_writer: state.db.writer_metrics(),
+        maintenance: state.db.maintenance_runs().await,
     })
 }
 // Request and response structs
 #[derive(Deserialize)]
 struct DownloadPipeRequest {
     url: String,
+    /// `sha256`, `signature` and `public_key` the pipe is checked against.
+    #[serde(flatten)]
+    options: PipeInstallOptions,
 }
 
 #[derive(Deserialize)]
@@ -688,7 +820,39 @@ struct UpdatePipeConfigRequest {
 #[derive(Deserialize)]
 struct UpdatePipeVersionRequest {
     pipe_id: String,
-    source: String,
+    /// Reinstalls the locked version when unset.
+    source: Option<String>,
+    #[serde(flatten)]
+    options: PipeInstallOptions,
+}
+
+#[derive(Deserialize)]
+struct RollbackPipeRequest {
+    pipe_id: String,
+    /// Snapshot number or package version, the previous version when unset.
+    version: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SetPipeSecretRequest {
+    name: String,
+    value: String,
+}
+
+#[derive(Deserialize)]
+struct RunCronRequest {
+    job: String,
+}
+
+#[derive(Deserialize)]
+struct CronHistoryQuery {
+    job: Option<String>,
+    #[serde(default = "default_cron_history_limit")]
+    limit: u32,
+}
+
+fn default_cron_history_limit() -> u32 {
+    50
 }
 
 // Handler functions
@@ -697,7 +861,11 @@ async fn download_pipe_handler(
     JsonResponse(payload): JsonResponse<DownloadPipeRequest>,
 ) -> Result<JsonResponse<serde_json::Value>, (StatusCode, JsonResponse<Value>)> {
     debug!("Downloading pipe: {}", payload.url);
-    match state.pipe_manager.download_pipe(&payload.url).await {
+    match state
+        .pipe_manager
+        .download_pipe(&payload.url, &payload.options)
+        .await
+    {
         Ok(pipe_dir) => Ok(JsonResponse(json!({
             "data": {
                 "pipe_id": pipe_dir,
@@ -707,10 +875,16 @@ async fn download_pipe_handler(
         }))),
         Err(e) => {
             error!("Failed to download pipe: {}", e);
+            let status = if e.is::<ManifestError>() || e.is::<IntegrityError>() {
+                StatusCode::BAD_REQUEST
+            } else {
+                StatusCode::INTERNAL_SERVER_ERROR
+            };
             Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
+                status,
                 JsonResponse(json!({
                     "error": format!("failed to download pipe: {}", e),
+                    "details": manifest_issues(&e),
                     "success": false
                 })),
             ))
@@ -718,6 +892,13 @@ async fn download_pipe_handler(
     }
 }
 
+/// Per-field problems of an invalid `pipe.json`, null for other errors.
+fn manifest_issues(e: &anyhow::Error) -> Value {
+    e.downcast_ref::<ManifestError>()
+        .map(|e| json!(e.issues))
+        .unwrap_or(Value::Null)
+}
+
 async fn download_pipe_private_handler(
     State(state): State<Arc<AppState>>,
     JsonResponse(payload): JsonResponse<DownloadPipePrivateRequest>,
@@ -833,6 +1014,7 @@ async fn update_pipe_config_handler(
             StatusCode::BAD_REQUEST,
             JsonResponse(json!({
                 "error": format!("failed to update pipe config: {}", e),
+                "details": manifest_issues(&e),
                 "success": false
             })),
         )),
@@ -846,7 +1028,11 @@ async fn update_pipe_version_handler(
     debug!("Updating pipe version for: {}", payload.pipe_id);
     match state
         .pipe_manager
-        .update_pipe_version(&payload.pipe_id, &payload.source)
+        .update_pipe_version(
+            &payload.pipe_id,
+            payload.source.as_deref(),
+            &payload.options,
+        )
         .await
     {
         Ok(_) => Ok(JsonResponse(json!({
@@ -866,6 +1052,34 @@ async fn update_pipe_version_handler(
     }
 }
 
+async fn rollback_pipe_handler(
+    State(state): State<Arc<AppState>>,
+    JsonResponse(payload): JsonResponse<RollbackPipeRequest>,
+) -> Result<JsonResponse<Value>, (StatusCode, JsonResponse<Value>)> {
+    debug!("Rolling back pipe: {}", payload.pipe_id);
+    match state
+        .pipe_manager
+        .rollback_pipe(&payload.pipe_id, payload.version.as_deref())
+        .await
+    {
+        Ok(version) => Ok(JsonResponse(json!({
+            "data": {
+                "pipe_id": payload.pipe_id,
+                "version": version,
+                "message": "pipe rolled back"
+            },
+            "success": true
+        }))),
+        Err(e) => Err((
+            StatusCode::BAD_REQUEST,
+            JsonResponse(json!({
+                "error": format!("failed to roll back pipe: {}", e),
+                "success": false
+            })),
+        )),
+    }
+}
+
 async fn get_pipe_info_handler(
     State(state): State<Arc<AppState>>,
     Path(pipe_id): Path<String>,
@@ -886,6 +1100,253 @@ async fn get_pipe_info_handler(
     }
 }
 
+async fn get_pipe_logs_handler(
+    State(state): State<Arc<AppState>>,
+    Path(pipe_id): Path<String>,
+    Query(query): Query<PipeLogQuery>,
+) -> Result<JsonResponse<Value>, (StatusCode, JsonResponse<Value>)> {
+    match state.pipe_manager.get_pipe_logs(&pipe_id, &query).await {
+        Ok(Some(logs)) => Ok(JsonResponse(json!({
+            "data": logs,
+            "success": true
+        }))),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            JsonResponse(json!({
+                "error": "pipe not found",
+                "success": false
+            })),
+        )),
+        Err(e) => {
+            error!("failed to read logs of pipe {}: {}", pipe_id, e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({
+                    "error": format!("failed to read pipe logs: {}", e),
+                    "success": false
+                })),
+            ))
+        }
+    }
+}
+
+// live tail of a pipe's logs as server-sent events, `since` is ignored
+async fn stream_pipe_logs_handler(
+    State(state): State<Arc<AppState>>,
+    Path(pipe_id): Path<String>,
+    Query(query): Query<PipeLogQuery>,
+) -> Result<
+    Sse<impl futures::Stream<Item = Result<SseEvent, axum::Error>>>,
+    (StatusCode, JsonResponse<Value>),
+> {
+    if state.pipe_manager.get_pipe_info(&pipe_id).await.is_none() {
+        return Err((
+            StatusCode::NOT_FOUND,
+            JsonResponse(json!({
+                "error": "pipe not found",
+                "success": false
+            })),
+        ));
+    }
+
+    let level = query.level;
+    let stream = futures::stream::unfold(
+        subscribe_pipe_logs(&pipe_id),
+        move |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(record) if level.is_none_or(|level| record.level >= level) => {
+                        return Some((SseEvent::default().json_data(&record), receiver));
+                    }
+                    Ok(_) => continue,
+                    Err(RecvError::Lagged(skipped)) => {
+                        let event = SseEvent::default()
+                            .event("lagged")
+                            .data(skipped.to_string());
+                        return Some((Ok(event), receiver));
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        },
+    );
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+async fn run_pipe_cron_handler(
+    State(state): State<Arc<AppState>>,
+    Path(pipe_id): Path<String>,
+    JsonResponse(payload): JsonResponse<RunCronRequest>,
+) -> Result<JsonResponse<Value>, (StatusCode, JsonResponse<Value>)> {
+    match state
+        .pipe_manager
+        .run_cron_now(&pipe_id, &payload.job)
+        .await
+    {
+        Ok(Some(run)) => Ok(JsonResponse(json!({
+            "data": run,
+            "success": true
+        }))),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            JsonResponse(json!({
+                "error": format!("pipe {} has no cron job {}", pipe_id, payload.job),
+                "success": false
+            })),
+        )),
+        Err(e) => {
+            error!("failed to run cron job of pipe {}: {}", pipe_id, e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({
+                    "error": format!("failed to run cron job: {}", e),
+                    "success": false
+                })),
+            ))
+        }
+    }
+}
+
+async fn get_pipe_cron_history_handler(
+    State(state): State<Arc<AppState>>,
+    Path(pipe_id): Path<String>,
+    Query(query): Query<CronHistoryQuery>,
+) -> Result<JsonResponse<Value>, (StatusCode, JsonResponse<Value>)> {
+    match state
+        .pipe_manager
+        .get_cron_history(&pipe_id, query.job.as_deref(), query.limit)
+        .await
+    {
+        Ok(runs) => Ok(JsonResponse(json!({
+            "data": runs,
+            "success": true
+        }))),
+        Err(e) => {
+            error!("failed to get cron history of pipe {}: {}", pipe_id, e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({
+                    "error": format!("failed to get cron history: {}", e),
+                    "success": false
+                })),
+            ))
+        }
+    }
+}
+
+async fn get_pipe_versions_handler(
+    State(state): State<Arc<AppState>>,
+    Path(pipe_id): Path<String>,
+) -> Result<JsonResponse<Value>, (StatusCode, JsonResponse<Value>)> {
+    match state.pipe_manager.list_pipe_versions(&pipe_id).await {
+        Ok(versions) => Ok(JsonResponse(json!({
+            "data": versions,
+            "success": true
+        }))),
+        Err(e) => {
+            error!("failed to list versions of pipe {}: {}", pipe_id, e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({
+                    "error": format!("failed to list pipe versions: {}", e),
+                    "success": false
+                })),
+            ))
+        }
+    }
+}
+
+// names of the secrets of a pipe, values are never sent back
+async fn list_pipe_secrets_handler(
+    State(state): State<Arc<AppState>>,
+    Path(pipe_id): Path<String>,
+) -> Result<JsonResponse<Value>, (StatusCode, JsonResponse<Value>)> {
+    match state.pipe_manager.list_secrets(&pipe_id).await {
+        Ok(Some(names)) => Ok(JsonResponse(json!({
+            "data": names,
+            "success": true
+        }))),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            JsonResponse(json!({
+                "error": "pipe not found",
+                "success": false
+            })),
+        )),
+        Err(e) => {
+            error!("failed to read secrets of pipe {}: {}", pipe_id, e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({
+                    "error": format!("failed to read pipe secrets: {}", e),
+                    "success": false
+                })),
+            ))
+        }
+    }
+}
+
+async fn set_pipe_secret_handler(
+    State(state): State<Arc<AppState>>,
+    Path(pipe_id): Path<String>,
+    JsonResponse(payload): JsonResponse<SetPipeSecretRequest>,
+) -> Result<JsonResponse<Value>, (StatusCode, JsonResponse<Value>)> {
+    debug!("Setting secret {} of pipe {}", payload.name, pipe_id);
+    match state
+        .pipe_manager
+        .set_secret(&pipe_id, &payload.name, &payload.value)
+        .await
+    {
+        Ok(()) => Ok(JsonResponse(json!({
+            "data": {
+                "pipe_id": pipe_id,
+                "name": payload.name,
+                "message": "secret set, restart the pipe to apply it"
+            },
+            "success": true
+        }))),
+        Err(e) => Err((
+            StatusCode::BAD_REQUEST,
+            JsonResponse(json!({
+                "error": format!("failed to set pipe secret: {}", e),
+                "success": false
+            })),
+        )),
+    }
+}
+
+async fn remove_pipe_secret_handler(
+    State(state): State<Arc<AppState>>,
+    Path((pipe_id, name)): Path<(String, String)>,
+) -> Result<JsonResponse<Value>, (StatusCode, JsonResponse<Value>)> {
+    debug!("Removing secret {} of pipe {}", name, pipe_id);
+    match state.pipe_manager.remove_secret(&pipe_id, &name).await {
+        Ok(true) => Ok(JsonResponse(json!({
+            "data": {
+                "pipe_id": pipe_id,
+                "name": name,
+                "message": "secret removed"
+            },
+            "success": true
+        }))),
+        Ok(false) => Err((
+            StatusCode::NOT_FOUND,
+            JsonResponse(json!({
+                "error": format!("secret {} is not set", name),
+                "success": false
+            })),
+        )),
+        Err(e) => Err((
+            StatusCode::BAD_REQUEST,
+            JsonResponse(json!({
+                "error": format!("failed to remove pipe secret: {}", e),
+                "success": false
+            })),
+        )),
+    }
+}
+
 async fn list_pipes_handler(State(state): State<Arc<AppState>>) -> JsonResponse<Value> {
     let pipes = state.pipe_manager.list_pipes().await;
     JsonResponse(json!({
@@ -895,7 +1356,7 @@ async fn list_pipes_handler(State(state): State<Arc<AppState>>) -> JsonResponse<
 }
 
 pub struct Server {
-    db: Arc<DatabaseManager>,
+    db: Arc<dyn Store>,
     addr: SocketAddr,
     // device_manager: Arc<DeviceManager>,
     screenpipe_dir: PathBuf,
@@ -903,12 +1364,13 @@ pub struct Server {
     vision_disabled: bool,
     audio_disabled: bool,
     ui_monitoring_enabled: bool,
+    media_storage: Option<Arc<MediaStorage>>,
 }
 
 impl Server {
     #[allow(clippy::too_many_arguments)]
     pub fn new(
-        db: Arc<DatabaseManager>,
+        db: Arc<dyn Store>,
         addr: SocketAddr,
         // device_manager: Arc<DeviceManager>,
         screenpipe_dir: PathBuf,
@@ -926,9 +1388,16 @@ impl Server {
             vision_disabled,
             audio_disabled,
             ui_monitoring_enabled,
+            media_storage: None,
         }
     }
 
+    /// Serves media through `storage` instead of reading chunk files from local disk only.
+    pub fn with_media_storage(mut self, storage: Arc<MediaStorage>) -> Self {
+        self.media_storage = Some(storage);
+        self
+    }
+
     pub async fn start<F>(
         self,
         api_plugin: F,
@@ -937,6 +1406,10 @@ impl Server {
     where
         F: Fn(&axum::http::Request<axum::body::Body>) + Clone + Send + Sync + 'static,
     {
+        let media_storage = self
+            .media_storage
+            .unwrap_or_else(|| Arc::new(MediaStorage::local(self.db.clone())));
+
         let app_state = Arc::new(AppState {
             db: self.db.clone(),
             // device_manager: self.device_manager.clone(),
@@ -950,7 +1423,8 @@ impl Server {
                 Some(Arc::new(
                     FrameCache::new(self.screenpipe_dir.clone().join("data"), self.db.clone())
                         .await
-                        .unwrap(),
+                        .unwrap()
+                        .with_storage(media_storage.clone()),
                 ))
             } else {
                 None
@@ -962,6 +1436,7 @@ impl Server {
             } else {
                 None
             },
+            media_storage,
         });
 
         let app = create_router()
@@ -1395,12 +1870,17 @@ pub struct StreamFramesRequest {
     #[serde(rename = "order")]
     #[serde(default = "Order::default")]
     order: Order,
+    // resume after the frame this cursor came from
+    #[serde(default)]
+    cursor: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct StreamTimeSeriesResponse {
     pub timestamp: DateTime<Utc>,
     pub devices: Vec<DeviceFrameResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -1432,7 +1912,17 @@ pub struct AudioData {
 
 impl From<TimeSeriesFrame> for StreamTimeSeriesResponse {
     fn from(frame: TimeSeriesFrame) -> Self {
+        let cursor = frame.frame_data.first().map(|device_frame| {
+            SearchCursor {
+                timestamp: frame.timestamp,
+                kind: SearchKind::Ocr,
+                id: device_frame.frame_id,
+            }
+            .encode()
+        });
+
         StreamTimeSeriesResponse {
+            cursor,
             timestamp: frame.timestamp,
             devices: frame
                 .frame_data
@@ -1472,7 +1962,11 @@ impl From<TimeSeriesFrame> for StreamTimeSeriesResponse {
 #[derive(Deserialize, Debug)]
 pub struct GetUnnamedSpeakersRequest {
     limit: u32,
+    #[serde(default)]
     offset: u32,
+    // value of the x-next-cursor header of the previous page
+    #[serde(default)]
+    cursor: Option<String>,
     // comma separated list of speaker ids to include
     #[serde(
         deserialize_with = "from_comma_separated_array",
@@ -1509,10 +2003,31 @@ where
 async fn get_unnamed_speakers_handler(
     State(state): State<Arc<AppState>>,
     Query(request): Query<GetUnnamedSpeakersRequest>,
-) -> Result<JsonResponse<Vec<Speaker>>, (StatusCode, JsonResponse<Value>)> {
+) -> Result<
+    (
+        AppendHeaders<Option<(&'static str, String)>>,
+        JsonResponse<Vec<Speaker>>,
+    ),
+    (StatusCode, JsonResponse<Value>),
+> {
+    let cursor = match request.cursor.as_deref() {
+        Some(token) => Some(SpeakerCursor::decode(token).ok_or_else(|| {
+            (
+                StatusCode::BAD_REQUEST,
+                JsonResponse(json!({"error": "invalid cursor"})),
+            )
+        })?),
+        None => None,
+    };
+
     let speakers = state
         .db
-        .get_unnamed_speakers(request.limit, request.offset, request.speaker_ids)
+        .get_unnamed_speakers_page(
+            request.limit,
+            request.offset,
+            cursor.as_ref(),
+            request.speaker_ids,
+        )
         .await
         .map_err(|e| {
             (
@@ -1521,9 +2036,18 @@ async fn get_unnamed_speakers_handler(
             )
         })?;
 
+    // a full page means there may be more
+    let next_cursor = match speakers.last() {
+        Some(last) if speakers.len() == request.limit as usize => {
+            Some((NEXT_CURSOR_HEADER, last.cursor().encode()))
+        }
+        _ => None,
+    };
+
     // convert metadata to json
     let speakers = speakers
         .into_iter()
+        .map(|unnamed| unnamed.speaker)
         .map(|speaker| {
             let mut metadata: Value = serde_json::from_str(&speaker.metadata).unwrap();
             if let Some(audio_samples) = metadata.get("audio_samples").and_then(|v| v.as_array()) {
@@ -1536,7 +2060,7 @@ async fn get_unnamed_speakers_handler(
         })
         .collect();
 
-    Ok(JsonResponse(speakers))
+    Ok((AppendHeaders(next_cursor), JsonResponse(speakers)))
 }
 
 async fn update_speaker_handler(
@@ -1608,12 +2132,17 @@ async fn delete_speaker_handler(
     // delete all audio chunks from the file system
     for audio_chunk in audio_chunks {
         if audio_chunk.start_time.is_some() && audio_chunk.end_time.is_some() {
-            std::fs::remove_file(audio_chunk.file_path).map_err(|e| {
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    JsonResponse(json!({"error": e.to_string()})),
-                )
-            })?;
+            match std::fs::remove_file(audio_chunk.file_path) {
+                Ok(()) => {}
+                // already offloaded to the remote media store
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => {
+                    return Err((
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        JsonResponse(json!({"error": e.to_string()})),
+                    ))
+                }
+            }
         }
     }
 
@@ -1782,6 +2311,15 @@ pub struct AudioDeviceControlResponse {
 #[derive(Deserialize)]
 struct EventsQuery {
     images: Option<bool>,
+    /// Replay logged events from this offset before live ones.
+    from: Option<u64>,
+    /// Comma separated glob patterns of event names, e.g. `meeting_*,transcription`.
+    events: Option<String>,
+    /// Predicates on the event data, e.g. `app_name == "Slack" && window_name ~= "general"`.
+    #[serde(rename = "where")]
+    predicates: Option<String>,
+    /// Events queued for the connection before new ones are dropped.
+    queue: Option<usize>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -1919,37 +2457,630 @@ pub struct VisionDeviceControlResponse {
 //     }))
 // }
 
+/// Schemas of the events screenpipe knows about, to generate types from.
+async fn get_event_schemas_handler() -> JsonResponse<Vec<EventSchema>> {
+    JsonResponse(EventRegistry::instance().catalogue())
+}
+
+#[derive(Deserialize)]
+struct WebhookLogQuery {
+    #[serde(default = "default_webhook_log_limit")]
+    limit: u32,
+}
+
+fn default_webhook_log_limit() -> u32 {
+    50
+}
+
+fn db_error(e: sqlx::Error) -> (StatusCode, JsonResponse<Value>) {
+    error!("database query failed: {}", e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        JsonResponse(json!({"error": e.to_string(), "success": false})),
+    )
+}
+
+fn webhook_not_found(id: i64) -> (StatusCode, JsonResponse<Value>) {
+    (
+        StatusCode::NOT_FOUND,
+        JsonResponse(json!({"error": format!("webhook {} not found", id), "success": false})),
+    )
+}
+
+fn bad_request(e: anyhow::Error) -> (StatusCode, JsonResponse<Value>) {
+    (
+        StatusCode::BAD_REQUEST,
+        JsonResponse(json!({"error": e.to_string(), "success": false})),
+    )
+}
+
+fn seal_error(e: anyhow::Error) -> (StatusCode, JsonResponse<Value>) {
+    error!("failed to seal webhook secret: {}", e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        JsonResponse(json!({"error": e.to_string(), "success": false})),
+    )
+}
+
+async fn list_webhooks_handler(
+    State(state): State<Arc<AppState>>,
+) -> Result<JsonResponse<Value>, (StatusCode, JsonResponse<Value>)> {
+    let webhooks = state.db.list_webhooks().await.map_err(db_error)?;
+    Ok(JsonResponse(json!({"data": webhooks, "success": true})))
+}
+
+async fn create_webhook_handler(
+    State(state): State<Arc<AppState>>,
+    Json(mut spec): Json<WebhookSpec>,
+) -> Result<(StatusCode, JsonResponse<Value>), (StatusCode, JsonResponse<Value>)> {
+    spec.validate().map_err(bad_request)?;
+    seal_webhook_secret(&mut spec).await.map_err(seal_error)?;
+    let id = state.db.insert_webhook(&spec).await.map_err(db_error)?;
+    webhooks_changed();
+    let webhook = state.db.get_webhook(id).await.map_err(db_error)?;
+    Ok((
+        StatusCode::CREATED,
+        JsonResponse(json!({"data": webhook, "success": true})),
+    ))
+}
+
+async fn get_webhook_handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> Result<JsonResponse<Value>, (StatusCode, JsonResponse<Value>)> {
+    match state.db.get_webhook(id).await.map_err(db_error)? {
+        Some(webhook) => Ok(JsonResponse(json!({"data": webhook, "success": true}))),
+        None => Err(webhook_not_found(id)),
+    }
+}
+
+async fn update_webhook_handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    Json(mut spec): Json<WebhookSpec>,
+) -> Result<JsonResponse<Value>, (StatusCode, JsonResponse<Value>)> {
+    spec.validate().map_err(bad_request)?;
+    seal_webhook_secret(&mut spec).await.map_err(seal_error)?;
+    if !state.db.update_webhook(id, &spec).await.map_err(db_error)? {
+        return Err(webhook_not_found(id));
+    }
+    webhooks_changed();
+    let webhook = state.db.get_webhook(id).await.map_err(db_error)?;
+    Ok(JsonResponse(json!({"data": webhook, "success": true})))
+}
+
+async fn delete_webhook_handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> Result<JsonResponse<Value>, (StatusCode, JsonResponse<Value>)> {
+    if !state.db.delete_webhook(id).await.map_err(db_error)? {
+        return Err(webhook_not_found(id));
+    }
+    webhooks_changed();
+    Ok(JsonResponse(json!({"success": true})))
+}
+
+async fn get_webhook_deliveries_handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    Query(query): Query<WebhookLogQuery>,
+) -> Result<JsonResponse<Value>, (StatusCode, JsonResponse<Value>)> {
+    let deliveries = state
+        .db
+        .get_webhook_deliveries(id, query.limit)
+        .await
+        .map_err(db_error)?;
+    Ok(JsonResponse(json!({"data": deliveries, "success": true})))
+}
+
+async fn get_webhook_dead_letters_handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    Query(query): Query<WebhookLogQuery>,
+) -> Result<JsonResponse<Value>, (StatusCode, JsonResponse<Value>)> {
+    let letters = state
+        .db
+        .get_webhook_dead_letters(id, query.limit)
+        .await
+        .map_err(db_error)?;
+    Ok(JsonResponse(json!({"data": letters, "success": true})))
+}
+
+async fn retry_webhook_dead_letter_handler(
+    State(state): State<Arc<AppState>>,
+    Path((id, letter_id)): Path<(i64, i64)>,
+) -> Result<JsonResponse<Value>, (StatusCode, JsonResponse<Value>)> {
+    let webhook = state
+        .db
+        .get_webhook(id)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| webhook_not_found(id))?;
+    let letter = state
+        .db
+        .get_webhook_dead_letter(letter_id)
+        .await
+        .map_err(db_error)?
+        .filter(|letter| letter.webhook_id == id)
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                JsonResponse(json!({
+                    "error": format!("webhook {} has no dead letter {}", id, letter_id),
+                    "success": false
+                })),
+            )
+        })?;
+    match redeliver_dead_letter(state.db.as_ref(), &webhook, &letter).await {
+        Ok(delivery) => Ok(JsonResponse(json!({"data": delivery, "success": true}))),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": e.to_string(), "success": false})),
+        )),
+    }
+}
+
+#[derive(Deserialize)]
+struct RuleDryRunQuery {
+    start_time: Option<DateTime<Utc>>,
+    end_time: Option<DateTime<Utc>>,
+    #[serde(default = "default_rule_dry_run_limit")]
+    limit: usize,
+}
+
+fn default_rule_dry_run_limit() -> usize {
+    100
+}
+
+fn rule_not_found(id: i64) -> (StatusCode, JsonResponse<Value>) {
+    (
+        StatusCode::NOT_FOUND,
+        JsonResponse(json!({"error": format!("rule {} not found", id), "success": false})),
+    )
+}
+
+async fn list_rules_handler(
+    State(state): State<Arc<AppState>>,
+) -> Result<JsonResponse<Value>, (StatusCode, JsonResponse<Value>)> {
+    let rules = state.db.list_rules().await.map_err(db_error)?;
+    Ok(JsonResponse(json!({"data": rules, "success": true})))
+}
+
+/// Takes the rule as json or yaml.
+async fn create_rule_handler(
+    State(state): State<Arc<AppState>>,
+    body: String,
+) -> Result<(StatusCode, JsonResponse<Value>), (StatusCode, JsonResponse<Value>)> {
+    let spec = RuleSpec::parse(&body).map_err(bad_request)?;
+    let id = state.db.insert_rule(&spec).await.map_err(db_error)?;
+    rules_changed();
+    let rule = state.db.get_rule(id).await.map_err(db_error)?;
+    Ok((
+        StatusCode::CREATED,
+        JsonResponse(json!({"data": rule, "success": true})),
+    ))
+}
+
+async fn get_rule_handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> Result<JsonResponse<Value>, (StatusCode, JsonResponse<Value>)> {
+    match state.db.get_rule(id).await.map_err(db_error)? {
+        Some(rule) => Ok(JsonResponse(json!({"data": rule, "success": true}))),
+        None => Err(rule_not_found(id)),
+    }
+}
+
+async fn update_rule_handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    body: String,
+) -> Result<JsonResponse<Value>, (StatusCode, JsonResponse<Value>)> {
+    let spec = RuleSpec::parse(&body).map_err(bad_request)?;
+    if !state.db.update_rule(id, &spec).await.map_err(db_error)? {
+        return Err(rule_not_found(id));
+    }
+    rules_changed();
+    let rule = state.db.get_rule(id).await.map_err(db_error)?;
+    Ok(JsonResponse(json!({"data": rule, "success": true})))
+}
+
+async fn delete_rule_handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> Result<JsonResponse<Value>, (StatusCode, JsonResponse<Value>)> {
+    if !state.db.delete_rule(id).await.map_err(db_error)? {
+        return Err(rule_not_found(id));
+    }
+    rules_changed();
+    Ok(JsonResponse(json!({"success": true})))
+}
+
+async fn run_rule_dry_run(
+    state: &AppState,
+    id: i64,
+    spec: &RuleSpec,
+    query: &RuleDryRunQuery,
+) -> Result<JsonResponse<Value>, (StatusCode, JsonResponse<Value>)> {
+    let end = query.end_time.unwrap_or_else(Utc::now);
+    let start = query
+        .start_time
+        .unwrap_or_else(|| end - chrono::Duration::days(1));
+    match dry_run(state.db.as_ref(), id, spec, start, end, query.limit).await {
+        Ok(firings) => Ok(JsonResponse(json!({"data": firings, "success": true}))),
+        Err(e) => {
+            error!("dry run of rule {} failed: {}", spec.name, e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": e.to_string(), "success": false})),
+            ))
+        }
+    }
+}
+
+/// Where an unsaved rule, sent as json or yaml, would have fired.
+async fn dry_run_rule_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<RuleDryRunQuery>,
+    body: String,
+) -> Result<JsonResponse<Value>, (StatusCode, JsonResponse<Value>)> {
+    let spec = RuleSpec::parse(&body).map_err(bad_request)?;
+    run_rule_dry_run(&state, 0, &spec, &query).await
+}
+
+async fn dry_run_saved_rule_handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    Query(query): Query<RuleDryRunQuery>,
+) -> Result<JsonResponse<Value>, (StatusCode, JsonResponse<Value>)> {
+    let rule = state
+        .db
+        .get_rule(id)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| rule_not_found(id))?;
+    run_rule_dry_run(&state, id, &rule.spec, &query).await
+}
+
+#[derive(Deserialize)]
+struct ConversationsQuery {
+    /// Full-text query on the transcripts, empty for all conversations.
+    #[serde(default)]
+    q: String,
+    start_time: Option<DateTime<Utc>>,
+    end_time: Option<DateTime<Utc>>,
+    #[serde(default = "default_conversations_limit")]
+    limit: u32,
+    #[serde(default)]
+    offset: u32,
+}
+
+fn default_conversations_limit() -> u32 {
+    20
+}
+
+fn conversation_not_found(id: i64) -> (StatusCode, JsonResponse<Value>) {
+    (
+        StatusCode::NOT_FOUND,
+        JsonResponse(json!({"error": format!("conversation {} not found", id), "success": false})),
+    )
+}
+
+/// Conversations overlapping the time range whose transcript matches `q`, latest first.
+async fn list_conversations_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ConversationsQuery>,
+) -> Result<JsonResponse<Value>, (StatusCode, JsonResponse<Value>)> {
+    let conversations = state
+        .db
+        .search_conversations(
+            query.q.trim(),
+            query.start_time,
+            query.end_time,
+            query.limit,
+            query.offset,
+        )
+        .await
+        .map_err(db_error)?;
+    Ok(JsonResponse(
+        json!({"data": conversations, "success": true}),
+    ))
+}
+
+/// The conversation with its speaker turns.
+async fn get_conversation_handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> Result<JsonResponse<Value>, (StatusCode, JsonResponse<Value>)> {
+    let conversation = state
+        .db
+        .get_conversation(id)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| conversation_not_found(id))?;
+    let turns = state
+        .db
+        .get_conversation_turns(id)
+        .await
+        .map_err(db_error)?;
+    Ok(JsonResponse(json!({
+        "data": {"conversation": conversation, "turns": turns},
+        "success": true
+    })))
+}
+
+async fn delete_conversation_handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> Result<JsonResponse<Value>, (StatusCode, JsonResponse<Value>)> {
+    if !state.db.delete_conversation(id).await.map_err(db_error)? {
+        return Err(conversation_not_found(id));
+    }
+    Ok(JsonResponse(json!({"success": true})))
+}
+
+#[derive(Deserialize)]
+struct MeetingsQuery {
+    start_time: Option<DateTime<Utc>>,
+    end_time: Option<DateTime<Utc>>,
+    #[serde(default = "default_meetings_limit")]
+    limit: u32,
+    #[serde(default)]
+    offset: u32,
+}
+
+fn default_meetings_limit() -> u32 {
+    20
+}
+
+#[derive(Deserialize)]
+struct MergeMeetingsRequest {
+    ids: Vec<i64>,
+}
+
+/// Transcriptions returned for a meeting at most.
+const MEETING_TRANSCRIPT_LIMIT: u32 = 5000;
+
+fn meeting_not_found(id: i64) -> (StatusCode, JsonResponse<Value>) {
+    (
+        StatusCode::NOT_FOUND,
+        JsonResponse(json!({"error": format!("meeting {} not found", id), "success": false})),
+    )
+}
+
+/// Meetings overlapping the time range, latest first.
+async fn list_meetings_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<MeetingsQuery>,
+) -> Result<JsonResponse<Value>, (StatusCode, JsonResponse<Value>)> {
+    let meetings = state
+        .db
+        .list_meetings(query.start_time, query.end_time, query.limit, query.offset)
+        .await
+        .map_err(db_error)?;
+    Ok(JsonResponse(json!({"data": meetings, "success": true})))
+}
+
+async fn get_meeting_handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> Result<JsonResponse<Value>, (StatusCode, JsonResponse<Value>)> {
+    match state.db.get_meeting(id).await.map_err(db_error)? {
+        Some(meeting) => Ok(JsonResponse(json!({"data": meeting, "success": true}))),
+        None => Err(meeting_not_found(id)),
+    }
+}
+
+/// Corrects the times, app, title or participants of a meeting.
+async fn update_meeting_handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    JsonResponse(update): JsonResponse<MeetingUpdate>,
+) -> Result<JsonResponse<Value>, (StatusCode, JsonResponse<Value>)> {
+    let meeting = state
+        .db
+        .get_meeting(id)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| meeting_not_found(id))?;
+    let mut spec = meeting.spec;
+    update.apply(&mut spec).map_err(bad_request)?;
+    if !state.db.update_meeting(id, &spec).await.map_err(db_error)? {
+        return Err(meeting_not_found(id));
+    }
+    let meeting = state.db.get_meeting(id).await.map_err(db_error)?;
+    Ok(JsonResponse(json!({"data": meeting, "success": true})))
+}
+
+async fn delete_meeting_handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> Result<JsonResponse<Value>, (StatusCode, JsonResponse<Value>)> {
+    if !state.db.delete_meeting(id).await.map_err(db_error)? {
+        return Err(meeting_not_found(id));
+    }
+    Ok(JsonResponse(json!({"success": true})))
+}
+
+async fn merge_meetings_handler(
+    State(state): State<Arc<AppState>>,
+    JsonResponse(request): JsonResponse<MergeMeetingsRequest>,
+) -> Result<JsonResponse<Value>, (StatusCode, JsonResponse<Value>)> {
+    let mut ids = request.ids;
+    ids.sort_unstable();
+    ids.dedup();
+    if ids.len() < 2 {
+        return Err(bad_request(anyhow::anyhow!(
+            "merging takes at least two meetings"
+        )));
+    }
+    match merge_meetings(state.db.as_ref(), &ids).await {
+        Ok(Some(meeting)) => Ok(JsonResponse(json!({"data": meeting, "success": true}))),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            JsonResponse(json!({"error": "meeting not found", "success": false})),
+        )),
+        Err(e) => {
+            error!("failed to merge meetings {:?}: {}", ids, e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": e.to_string(), "success": false})),
+            ))
+        }
+    }
+}
+
+/// What was said during a meeting, oldest first.
+async fn get_meeting_transcript_handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> Result<JsonResponse<Value>, (StatusCode, JsonResponse<Value>)> {
+    let meeting = state
+        .db
+        .get_meeting(id)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| meeting_not_found(id))?;
+    let results = state
+        .db
+        .search(
+            "",
+            ContentType::Audio,
+            MEETING_TRANSCRIPT_LIMIT,
+            0,
+            Some(meeting.spec.start_time),
+            Some(meeting.spec.end_time.unwrap_or_else(Utc::now)),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .await
+        .map_err(db_error)?;
+    let mut transcript: Vec<_> = results
+        .into_iter()
+        .filter_map(|result| match result {
+            SearchResult::Audio(audio) => Some(audio),
+            _ => None,
+        })
+        .collect();
+    transcript.sort_by_key(|audio| (audio.timestamp, audio.transcription_id));
+    let transcript: Vec<Value> = transcript
+        .into_iter()
+        .map(|audio| {
+            json!({
+                "transcription_id": audio.transcription_id,
+                "timestamp": audio.timestamp,
+                "device_name": audio.device_name,
+                "device_type": audio.device_type,
+                "speaker": audio.speaker,
+                "text": audio.transcription,
+            })
+        })
+        .collect();
+    Ok(JsonResponse(
+        json!({"data": {"meeting": meeting, "transcript": transcript}, "success": true}),
+    ))
+}
+
 // websocket events handler
 async fn ws_events_handler(ws: WebSocketUpgrade, query: Query<EventsQuery>) -> Response {
-    ws.on_upgrade(|socket| handle_socket(socket, query))
+    let bad_request = |e: anyhow::Error| {
+        (
+            StatusCode::BAD_REQUEST,
+            JsonResponse(json!({"error": e.to_string()})),
+        )
+            .into_response()
+    };
+    let filter = match EventFilter::new(
+        split_event_patterns(query.events.as_deref()),
+        query.predicates.as_deref(),
+    ) {
+        Ok(filter) => filter,
+        Err(e) => return bad_request(e),
+    };
+    // subscribed before the upgrade so a missing event log is a plain http error
+    let stream = match query.from {
+        Some(from) => match subscribe_to_all_events_from(from) {
+            Ok(stream) => stream,
+            Err(e) => return bad_request(e),
+        },
+        None => subscribe_to_all_events(),
+    };
+    ws.on_upgrade(|socket| handle_socket(socket, query, stream, filter))
 }
 
-async fn handle_socket(socket: WebSocket, query: Query<EventsQuery>) {
+fn split_event_patterns(events: Option<&str>) -> Vec<String> {
+    events
+        .map(|events| events.split(',').map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+/// Bus events go through the connection filter into a bounded queue, the socket is
+/// written from the queue. When the queue is full events are dropped and the client gets a
+/// `ws_lagged` event with how many once it drains, same when the bus dropped some.
+async fn handle_socket(
+    socket: WebSocket,
+    query: Query<EventsQuery>,
+    mut stream: EventSubscription<Value>,
+    filter: EventFilter,
+) {
     let (mut sender, mut receiver) = socket.split();
+    let (filter_tx, filter_rx) = tokio::sync::watch::channel(filter);
+    let (queue_tx, mut queue_rx) = mpsc::channel::<ScreenpipeEvent>(
+        query.queue.unwrap_or(DEFAULT_EVENTS_QUEUE).max(1),
+    );
+    let dropped = Arc::new(std::sync::atomic::AtomicU64::new(0));
 
-    let incoming = tokio::spawn(async move {
+    let control_tx = queue_tx.clone();
+    let mut incoming = tokio::spawn(async move {
         while let Some(Ok(msg)) = receiver.next().await {
-            if let Message::Text(t) = msg {
-                if let Ok(event) = serde_json::from_str::<ScreenpipeEvent>(&t) {
-                    let _ = send_event(&event.name, event.data);
+            let Message::Text(t) = msg else {
+                continue;
+            };
+            let Some(reply) = handle_client_message(&t, &filter_tx, "ws") else {
+                continue;
+            };
+            if control_tx.send(reply).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let images = query.images.unwrap_or(false);
+    let forward_dropped = dropped.clone();
+    let forward = tokio::spawn(async move {
+        while let Some(mut event) = stream.next().await {
+            forward_dropped.fetch_add(stream.take_dropped(), Ordering::Relaxed);
+            if !filter_rx.borrow().matches(&event) {
+                continue;
+            }
+            if !images {
+                strip_images(&mut event);
+            }
+            match queue_tx.try_send(event) {
+                Ok(()) => {}
+                Err(mpsc::error::TrySendError::Full(_)) => {
+                    forward_dropped.fetch_add(1, Ordering::Relaxed);
                 }
+                Err(mpsc::error::TrySendError::Closed(_)) => break,
             }
         }
     });
-    // Handle the WebSocket connection here
-    // You can add your logic to handle messages, upgrades, etc.
 
-    let outgoing = tokio::spawn(async move {
-        let mut stream = subscribe_to_all_events();
+    let mut outgoing = tokio::spawn(async move {
         loop {
             tokio::select! {
-                event = stream.next() => {
-                    if let Some(mut event) = event {
-                        if !query.images.unwrap_or(false) && (event.name == "ocr_result" || event.name == "ui_frame") {
-                            if let Some(data) = event.data.as_object_mut() {
-                                data.remove("image");
-                            }
-                        }
+                event = queue_rx.recv() => {
+                    let Some(event) = event else {
+                        break;
+                    };
+                    let lagged = dropped.swap(0, Ordering::Relaxed);
+                    let notice = (lagged > 0)
+                        .then(|| control_event("ws_lagged", json!({"dropped": lagged})));
+                    for event in notice.into_iter().chain(std::iter::once(event)) {
                         if let Err(e) = sender
                             .send(Message::Text(
                                 serde_json::to_string(&event).unwrap_or_default(),
@@ -1957,7 +3088,7 @@ async fn handle_socket(socket: WebSocket, query: Query<EventsQuery>) {
                             .await
                         {
                             tracing::error!("Failed to send websocket message: {}", e);
-                            break;
+                            return;
                         }
                     }
                 }
@@ -1970,9 +3101,12 @@ async fn handle_socket(socket: WebSocket, query: Query<EventsQuery>) {
 
     // Wait for either task to finish
     tokio::select! {
-        _ = incoming => {}
-        _ = outgoing => {}
+        _ = &mut incoming => {}
+        _ = &mut outgoing => {}
     }
+    incoming.abort();
+    outgoing.abort();
+    forward.abort();
 
     debug!("WebSocket connection closed");
 }
@@ -2013,11 +3147,13 @@ pub fn create_router() -> Router<Arc<AppState>> {
         .expose_headers([
             axum::http::header::CONTENT_TYPE,
             axum::http::header::CACHE_CONTROL,
+            axum::http::HeaderName::from_static(NEXT_CURSOR_HEADER),
         ]); // Important for SSE
 
     let router = Router::new()
         .route("/search", get(search))
         .route("/audio/list", get(api_list_audio_devices))
+        .route("/audio/chunks/:chunk_id", get(get_audio_chunk_file))
         .route("/vision/list", get(api_list_monitors))
         .route(
             "/tags/:content_type/:id",
@@ -2025,6 +3161,22 @@ pub fn create_router() -> Router<Arc<AppState>> {
         )
         .route("/pipes/info/:pipe_id", get(get_pipe_info_handler))
         .route("/pipes/list", get(list_pipes_handler))
+        .route("/pipes/:pipe_id/logs", get(get_pipe_logs_handler))
+        .route("/pipes/:pipe_id/logs/stream", get(stream_pipe_logs_handler))
+        .route("/pipes/:pipe_id/crons/run", post(run_pipe_cron_handler))
+        .route(
+            "/pipes/:pipe_id/crons/history",
+            get(get_pipe_cron_history_handler),
+        )
+        .route("/pipes/:pipe_id/versions", get(get_pipe_versions_handler))
+        .route(
+            "/pipes/:pipe_id/secrets",
+            get(list_pipe_secrets_handler).post(set_pipe_secret_handler),
+        )
+        .route(
+            "/pipes/:pipe_id/secrets/:name",
+            delete(remove_pipe_secret_handler),
+        )
         .route("/pipes/download", post(download_pipe_handler))
         .route(
             "/pipes/download-private",
@@ -2034,6 +3186,7 @@ pub fn create_router() -> Router<Arc<AppState>> {
         .route("/pipes/disable", post(stop_pipe_handler))
         .route("/pipes/update", post(update_pipe_config_handler))
         .route("/pipes/update-version", post(update_pipe_version_handler))
+        .route("/pipes/rollback", post(rollback_pipe_handler))
         .route("/pipes/delete", post(delete_pipe_handler))
         .route("/health", get(health_check))
         .route("/ws/health", get(ws_health_handler))
@@ -2055,6 +3208,55 @@ pub fn create_router() -> Router<Arc<AppState>> {
         // .route("/audio/start", post(start_audio_device))
         // .route("/audio/stop", post(stop_audio_device))
         .route("/ws/events", get(ws_events_handler))
+        .route("/events/schema", get(get_event_schemas_handler))
+        .route(
+            "/webhooks",
+            get(list_webhooks_handler).post(create_webhook_handler),
+        )
+        .route(
+            "/webhooks/:id",
+            get(get_webhook_handler)
+                .put(update_webhook_handler)
+                .delete(delete_webhook_handler),
+        )
+        .route(
+            "/webhooks/:id/deliveries",
+            get(get_webhook_deliveries_handler),
+        )
+        .route(
+            "/webhooks/:id/dead-letters",
+            get(get_webhook_dead_letters_handler),
+        )
+        .route(
+            "/webhooks/:id/dead-letters/:letter_id/retry",
+            post(retry_webhook_dead_letter_handler),
+        )
+        .route("/rules", get(list_rules_handler).post(create_rule_handler))
+        .route("/rules/dry-run", post(dry_run_rule_handler))
+        .route(
+            "/rules/:id",
+            get(get_rule_handler)
+                .put(update_rule_handler)
+                .delete(delete_rule_handler),
+        )
+        .route("/rules/:id/dry-run", post(dry_run_saved_rule_handler))
+        .route("/meetings", get(list_meetings_handler))
+        .route("/meetings/merge", post(merge_meetings_handler))
+        .route(
+            "/meetings/:id",
+            get(get_meeting_handler)
+                .patch(update_meeting_handler)
+                .delete(delete_meeting_handler),
+        )
+        .route(
+            "/meetings/:id/transcript",
+            get(get_meeting_transcript_handler),
+        )
+        .route("/conversations", get(list_conversations_handler))
+        .route(
+            "/conversations/:id",
+            get(get_conversation_handler).delete(delete_conversation_handler),
+        )
         .route("/semantic-search", get(semantic_search_handler))
         .route("/frames/:frame_id", get(get_frame_data))
         // .route("/vision/start", post(start_vision_device))
@@ -2104,7 +3306,21 @@ pub async fn get_frame_data(
         // If not in cache or cache disabled, get from database
         match state.db.get_frame(frame_id).await {
             Ok(Some((file_path, offset_index))) => {
-                match extract_frame_from_video(&file_path, offset_index).await {
+                let local_path = match state.media_storage.resolve(&file_path).await {
+                    Ok(path) => path,
+                    Err(e) => {
+                        error!("failed to resolve video chunk for frame {}: {}", frame_id, e);
+                        return Err((
+                            storage_error_status(&e),
+                            JsonResponse(json!({
+                                "error": e.to_string(),
+                                "frame_id": frame_id,
+                                "file_path": file_path
+                            })),
+                        ));
+                    }
+                };
+                match extract_frame_from_video(&local_path.to_string_lossy(), offset_index).await {
                     Ok(frame_path) => {
                         // Store in cache if enabled and we can get the lock
                         if let Some(cache) = &state.frame_image_cache {
@@ -2187,23 +3403,103 @@ async fn serve_file(path: &str) -> Result<Response, (StatusCode, JsonResponse<Va
     }
 }
 
+fn storage_error_status(e: &StorageError) -> StatusCode {
+    match e {
+        StorageError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+        StorageError::NotFound(_) => StatusCode::NOT_FOUND,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+pub(crate) async fn get_audio_chunk_file(
+    State(state): State<Arc<AppState>>,
+    Path(chunk_id): Path<i64>,
+) -> Result<Response, (StatusCode, JsonResponse<Value>)> {
+    let file_path = match state.db.get_audio_chunk_file_path(chunk_id).await {
+        Ok(Some(file_path)) => file_path,
+        Ok(None) => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                JsonResponse(json!({"error": "audio chunk not found", "chunk_id": chunk_id})),
+            ))
+        }
+        Err(e) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("database error: {}", e)})),
+            ))
+        }
+    };
+
+    let local_path = state.media_storage.resolve(&file_path).await.map_err(|e| {
+        error!("failed to resolve audio chunk {}: {}", chunk_id, e);
+        (
+            storage_error_status(&e),
+            JsonResponse(json!({
+                "error": e.to_string(),
+                "chunk_id": chunk_id,
+                "file_path": file_path
+            })),
+        )
+    })?;
+
+    let file = File::open(&local_path).await.map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("failed to open file: {}", e)})),
+        )
+    })?;
+
+    Response::builder()
+        .header("content-type", "audio/mp4")
+        .body(Body::from_stream(ReaderStream::new(file)))
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("failed to create response: {}", e)})),
+            )
+        })
+}
+
 // Add these new functions before stream_frames_handler
 async fn fetch_and_process_frames(
-    db: Arc<DatabaseManager>,
-    start_time: DateTime<Utc>,
-    end_time: DateTime<Utc>,
+    db: Arc<dyn Store>,
+    mut start_time: DateTime<Utc>,
+    mut end_time: DateTime<Utc>,
     frame_tx: mpsc::Sender<TimeSeriesFrame>,
     is_descending: bool,
+    after: Option<SearchCursor>,
 ) -> Result<(), anyhow::Error> {
+    // only the part of the range past the cursor is left to stream
+    if let Some(cursor) = &after {
+        if is_descending {
+            end_time = end_time.min(cursor.timestamp);
+        } else {
+            start_time = start_time.max(cursor.timestamp);
+        }
+    }
+
     let mut chunks = db.find_video_chunks(start_time, end_time).await?;
 
-    // Sort chunks based on order
+    // Sort chunks based on order, frame id breaks timestamp ties like the cursor does
     if is_descending {
         chunks
             .frames
-            .sort_by_key(|a| std::cmp::Reverse((a.timestamp, a.offset_index)));
+            .sort_by_key(|a| std::cmp::Reverse((a.timestamp, a.frame_id)));
     } else {
-        chunks.frames.sort_by_key(|a| (a.timestamp, a.offset_index));
+        chunks.frames.sort_by_key(|a| (a.timestamp, a.frame_id));
+    }
+
+    if let Some(cursor) = after {
+        let key = (cursor.timestamp, cursor.id);
+        chunks.frames.retain(|frame| {
+            let frame_key = (frame.timestamp, frame.frame_id);
+            if is_descending {
+                frame_key < key
+            } else {
+                frame_key > key
+            }
+        });
     }
 
     for chunk in chunks.frames {
@@ -2273,6 +3569,17 @@ async fn handle_stream_frames_socket(socket: WebSocket, state: Arc<AppState>) {
                             request.start_time, request.end_time
                         );
 
+                        let after = match request.cursor.as_deref() {
+                            Some(token) => match SearchCursor::decode(token) {
+                                Some(cursor) => Some(cursor),
+                                None => {
+                                    error!("invalid stream cursor: {}", token);
+                                    continue;
+                                }
+                            },
+                            None => None,
+                        };
+
                         let frame_tx = frame_tx.clone();
                         let db = db.clone();
 
@@ -2283,6 +3590,7 @@ async fn handle_stream_frames_socket(socket: WebSocket, state: Arc<AppState>) {
                                 request.end_time,
                                 frame_tx,
                                 request.order == Order::Descending,
+                                after,
                             )
                             .await
                             {
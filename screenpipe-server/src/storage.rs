@@ -0,0 +1,609 @@
+//! Storage backends for media chunk files (video and audio).
+//!
+//! Chunks are always written to the local data dir first. Chunks older than a
+//! configurable age can be offloaded to a remote object store; the database
+//! keeps the original `file_path` and records the remote key, so search keeps
+//! working offline and media is fetched back on demand into a local LRU cache.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use hmac::{Hmac, Mac};
+use lru::LruCache;
+use reqwest::header::{HeaderMap, HeaderValue};
+use reqwest::{Client, StatusCode, Url};
+use sha2::{Digest, Sha256};
+use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tokio_util::io::ReaderStream;
+use tracing::{debug, error, info, warn};
+
+use crate::db_types::ChunkKind;
+use crate::store::Store;
+
+const OFFLOAD_BATCH_SIZE: i64 = 100;
+
+#[derive(Debug)]
+pub enum StorageError {
+    /// The chunk is neither on local disk nor known to the remote store.
+    NotFound(String),
+    /// The chunk lives in the remote store but it could not be reached.
+    Unavailable(String),
+    Io(std::io::Error),
+    Database(sqlx::Error),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::NotFound(path) => write!(f, "media chunk not found: {}", path),
+            StorageError::Unavailable(msg) => write!(f, "remote media store unavailable: {}", msg),
+            StorageError::Io(e) => write!(f, "media storage io error: {}", e),
+            StorageError::Database(e) => write!(f, "media storage database error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+impl From<std::io::Error> for StorageError {
+    fn from(e: std::io::Error) -> Self {
+        StorageError::Io(e)
+    }
+}
+
+impl From<sqlx::Error> for StorageError {
+    fn from(e: sqlx::Error) -> Self {
+        StorageError::Database(e)
+    }
+}
+
+/// A place chunk files can be copied to and fetched back from.
+#[async_trait]
+pub trait ChunkStore: Send + Sync {
+    fn name(&self) -> &str;
+    async fn put(&self, key: &str, local_path: &Path) -> Result<(), StorageError>;
+    async fn get(&self, key: &str, dest: &Path) -> Result<(), StorageError>;
+    async fn exists(&self, key: &str) -> Result<bool, StorageError>;
+    async fn delete(&self, key: &str) -> Result<(), StorageError>;
+}
+
+/// Chunk store backed by a directory, e.g. a mounted NAS or external drive.
+pub struct LocalChunkStore {
+    root: PathBuf,
+}
+
+impl LocalChunkStore {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    fn path_for(&self, key: &str) -> PathBuf {
+        self.root.join(key)
+    }
+}
+
+#[async_trait]
+impl ChunkStore for LocalChunkStore {
+    fn name(&self) -> &str {
+        "local"
+    }
+
+    async fn put(&self, key: &str, local_path: &Path) -> Result<(), StorageError> {
+        let dest = self.path_for(key);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::copy(local_path, &dest).await?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str, dest: &Path) -> Result<(), StorageError> {
+        let src = self.path_for(key);
+        if !fs::try_exists(&src).await? {
+            return Err(StorageError::NotFound(key.to_string()));
+        }
+        fs::copy(&src, dest).await?;
+        Ok(())
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, StorageError> {
+        Ok(fs::try_exists(self.path_for(key)).await?)
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        match fs::remove_file(self.path_for(key)).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct S3Config {
+    /// e.g. `https://s3.eu-west-1.amazonaws.com` or `http://localhost:9000` for minio
+    pub endpoint: String,
+    pub bucket: String,
+    pub region: String,
+    pub access_key: String,
+    pub secret_key: String,
+    /// Prepended to every object key, e.g. `laptop/`.
+    pub prefix: String,
+}
+
+/// Chunk store for any S3-compatible API (AWS, MinIO, R2, ...), using path-style
+/// addressing and SigV4 request signing.
+pub struct S3ChunkStore {
+    config: S3Config,
+    client: Client,
+}
+
+type HmacSha256 = Hmac<Sha256>;
+
+const EMPTY_PAYLOAD_SHA256: &str =
+    "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+impl S3ChunkStore {
+    pub fn new(config: S3Config) -> Result<Self, StorageError> {
+        Url::parse(&config.endpoint).map_err(|e| {
+            StorageError::Unavailable(format!("invalid s3 endpoint {}: {}", config.endpoint, e))
+        })?;
+        let client = Client::builder()
+            .timeout(std::time::Duration::from_secs(60))
+            .connect_timeout(std::time::Duration::from_secs(5))
+            .build()
+            .map_err(|e| StorageError::Unavailable(e.to_string()))?;
+        Ok(Self { config, client })
+    }
+
+    fn object_url(&self, key: &str) -> Url {
+        let full_key = format!("{}{}", self.config.prefix, key);
+        let encoded_key = full_key
+            .split('/')
+            .map(uri_encode)
+            .collect::<Vec<_>>()
+            .join("/");
+        let base = self.config.endpoint.trim_end_matches('/');
+        // endpoint was validated in new()
+        Url::parse(&format!("{}/{}/{}", base, self.config.bucket, encoded_key)).unwrap()
+    }
+
+    fn signed_headers(&self, method: &str, url: &Url, payload_sha256: &str) -> HeaderMap {
+        let now = Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+
+        let canonical_headers = format!(
+            "host:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n",
+            host, payload_sha256, amz_date
+        );
+        let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+        let canonical_request = format!(
+            "{}\n{}\n\n{}\n{}\n{}",
+            method,
+            url.path(),
+            canonical_headers,
+            signed_headers,
+            payload_sha256
+        );
+
+        let scope = format!("{}/{}/s3/aws4_request", date, self.config.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{:x}",
+            amz_date,
+            scope,
+            Sha256::digest(canonical_request.as_bytes())
+        );
+
+        let k_date = hmac_sha256(
+            format!("AWS4{}", self.config.secret_key).as_bytes(),
+            date.as_bytes(),
+        );
+        let k_region = hmac_sha256(&k_date, self.config.region.as_bytes());
+        let k_service = hmac_sha256(&k_region, b"s3");
+        let k_signing = hmac_sha256(&k_service, b"aws4_request");
+        let signature = hmac_sha256(&k_signing, string_to_sign.as_bytes())
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.config.access_key, scope, signed_headers, signature
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-amz-date", HeaderValue::from_str(&amz_date).unwrap());
+        headers.insert(
+            "x-amz-content-sha256",
+            HeaderValue::from_str(payload_sha256).unwrap(),
+        );
+        if let Ok(value) = HeaderValue::from_str(&authorization) {
+            headers.insert(reqwest::header::AUTHORIZATION, value);
+        }
+        headers
+    }
+
+    fn unavailable(&self, e: impl fmt::Display) -> StorageError {
+        StorageError::Unavailable(format!("{} ({})", e, self.config.endpoint))
+    }
+
+    async fn error_from_response(&self, key: &str, res: reqwest::Response) -> StorageError {
+        let status = res.status();
+        if status == StatusCode::NOT_FOUND {
+            return StorageError::NotFound(key.to_string());
+        }
+        let body = res.text().await.unwrap_or_default();
+        self.unavailable(format!(
+            "unexpected status {} for {}: {}",
+            status, key, body
+        ))
+    }
+}
+
+#[async_trait]
+impl ChunkStore for S3ChunkStore {
+    fn name(&self) -> &str {
+        "s3"
+    }
+
+    async fn put(&self, key: &str, local_path: &Path) -> Result<(), StorageError> {
+        // SigV4 signs the payload hash, so the file is read twice: once to hash it and
+        // once streamed as the body, never held in memory as a whole
+        let (payload_sha256, size) = sha256_file(local_path).await?;
+        let url = self.object_url(key);
+        let headers = self.signed_headers("PUT", &url, &payload_sha256);
+        let file = fs::File::open(local_path).await?;
+
+        let res = self
+            .client
+            .put(url)
+            .headers(headers)
+            .header(reqwest::header::CONTENT_LENGTH, size)
+            .body(reqwest::Body::wrap_stream(ReaderStream::new(file)))
+            .send()
+            .await
+            .map_err(|e| self.unavailable(e))?;
+
+        if !res.status().is_success() {
+            return Err(self.error_from_response(key, res).await);
+        }
+        Ok(())
+    }
+
+    async fn get(&self, key: &str, dest: &Path) -> Result<(), StorageError> {
+        let url = self.object_url(key);
+        let headers = self.signed_headers("GET", &url, EMPTY_PAYLOAD_SHA256);
+
+        let mut res = self
+            .client
+            .get(url)
+            .headers(headers)
+            .send()
+            .await
+            .map_err(|e| self.unavailable(e))?;
+
+        if !res.status().is_success() {
+            return Err(self.error_from_response(key, res).await);
+        }
+
+        let mut file = fs::File::create(dest).await?;
+        while let Some(chunk) = res.chunk().await.map_err(|e| self.unavailable(e))? {
+            file.write_all(&chunk).await?;
+        }
+        file.flush().await?;
+        Ok(())
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, StorageError> {
+        let url = self.object_url(key);
+        let headers = self.signed_headers("HEAD", &url, EMPTY_PAYLOAD_SHA256);
+
+        let res = self
+            .client
+            .head(url)
+            .headers(headers)
+            .send()
+            .await
+            .map_err(|e| self.unavailable(e))?;
+
+        match res.status() {
+            s if s.is_success() => Ok(true),
+            StatusCode::NOT_FOUND => Ok(false),
+            _ => Err(self.error_from_response(key, res).await),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        let url = self.object_url(key);
+        let headers = self.signed_headers("DELETE", &url, EMPTY_PAYLOAD_SHA256);
+
+        let res = self
+            .client
+            .delete(url)
+            .headers(headers)
+            .send()
+            .await
+            .map_err(|e| self.unavailable(e))?;
+
+        if !res.status().is_success() && res.status() != StatusCode::NOT_FOUND {
+            return Err(self.error_from_response(key, res).await);
+        }
+        Ok(())
+    }
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("hmac accepts keys of any size");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Hex sha256 and size of the file at `path`, read in small blocks.
+async fn sha256_file(path: &Path) -> Result<(String, u64), StorageError> {
+    let mut file = fs::File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut size = 0u64;
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        size += read as u64;
+    }
+    Ok((format!("{:x}", hasher.finalize()), size))
+}
+
+/// Percent-encodes a single path segment as required by SigV4.
+fn uri_encode(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct OffloadStats {
+    pub offloaded: usize,
+    pub skipped: usize,
+    pub bytes: u64,
+}
+
+struct ChunkCache {
+    entries: LruCache<String, u64>,
+    total_bytes: u64,
+    max_bytes: u64,
+}
+
+/// Entry point the rest of the server uses to get at chunk files, wherever they live.
+pub struct MediaStorage {
+    db: Arc<dyn Store>,
+    remote: Option<Arc<dyn ChunkStore>>,
+    cache_dir: PathBuf,
+    cache: Mutex<ChunkCache>,
+}
+
+impl MediaStorage {
+    /// Storage that only ever reads chunks from local disk.
+    pub fn local(db: Arc<dyn Store>) -> Self {
+        Self {
+            db,
+            remote: None,
+            cache_dir: std::env::temp_dir().join("screenpipe_chunks"),
+            cache: Mutex::new(ChunkCache {
+                entries: LruCache::unbounded(),
+                total_bytes: 0,
+                max_bytes: 0,
+            }),
+        }
+    }
+
+    /// Storage backed by a remote store, fetching offloaded chunks into `cache_dir`
+    /// and keeping at most `max_cache_bytes` of them around.
+    pub async fn with_remote(
+        db: Arc<dyn Store>,
+        remote: Arc<dyn ChunkStore>,
+        cache_dir: PathBuf,
+        max_cache_bytes: u64,
+    ) -> Result<Self, StorageError> {
+        // fetched chunks from a previous run are not tracked, start from a clean cache
+        if fs::try_exists(&cache_dir).await? {
+            fs::remove_dir_all(&cache_dir).await?;
+        }
+        fs::create_dir_all(&cache_dir).await?;
+
+        info!(
+            "media storage: offloading to {} store, local cache at {:?} ({} MB)",
+            remote.name(),
+            cache_dir,
+            max_cache_bytes / 1024 / 1024
+        );
+
+        Ok(Self {
+            db,
+            remote: Some(remote),
+            cache_dir,
+            cache: Mutex::new(ChunkCache {
+                entries: LruCache::unbounded(),
+                total_bytes: 0,
+                max_bytes: max_cache_bytes,
+            }),
+        })
+    }
+
+    pub fn has_remote(&self) -> bool {
+        self.remote.is_some()
+    }
+
+    /// Returns a local path for the chunk recorded at `file_path`, fetching it from
+    /// the remote store if it was offloaded.
+    pub async fn resolve(&self, file_path: &str) -> Result<PathBuf, StorageError> {
+        let local = PathBuf::from(file_path);
+        if fs::try_exists(&local).await.unwrap_or(false) {
+            return Ok(local);
+        }
+
+        let key = match self.db.get_chunk_remote_key(file_path).await? {
+            Some(key) => key,
+            None => return Err(StorageError::NotFound(file_path.to_string())),
+        };
+
+        let remote = self.remote.as_ref().ok_or_else(|| {
+            StorageError::Unavailable(format!(
+                "{} was offloaded as {} but no remote store is configured",
+                file_path, key
+            ))
+        })?;
+
+        let cached = self.cache_path(&key);
+        {
+            let mut cache = self.cache.lock().await;
+            if cache.entries.get(&key).is_some() && fs::try_exists(&cached).await? {
+                debug!("media cache hit for {}", key);
+                return Ok(cached);
+            }
+        }
+
+        debug!("fetching offloaded chunk {} from {}", key, remote.name());
+        let partial = cached.with_extension(format!("{}.part", uuid::Uuid::new_v4()));
+        if let Err(e) = remote.get(&key, &partial).await {
+            let _ = fs::remove_file(&partial).await;
+            error!("failed to fetch offloaded chunk {}: {}", key, e);
+            return Err(e);
+        }
+        fs::rename(&partial, &cached).await?;
+
+        let size = fs::metadata(&cached).await?.len();
+        self.track_cached(key, size).await;
+        Ok(cached)
+    }
+
+    fn cache_path(&self, key: &str) -> PathBuf {
+        self.cache_dir.join(key.replace('/', "_"))
+    }
+
+    async fn track_cached(&self, key: String, size: u64) {
+        let mut cache = self.cache.lock().await;
+        if let Some(old) = cache.entries.put(key.clone(), size) {
+            cache.total_bytes -= old;
+        }
+        cache.total_bytes += size;
+
+        while cache.total_bytes > cache.max_bytes && cache.entries.len() > 1 {
+            let Some((evicted, evicted_size)) = cache.entries.pop_lru() else {
+                break;
+            };
+            cache.total_bytes -= evicted_size;
+            if let Err(e) = fs::remove_file(self.cache_path(&evicted)).await {
+                warn!("failed to evict cached chunk {}: {}", evicted, e);
+            }
+        }
+    }
+
+    /// Moves every chunk whose media is older than `older_than` to the remote store.
+    /// The local file is only removed once the upload succeeded and the db recorded the key.
+    pub async fn offload_older_than(
+        &self,
+        older_than: Duration,
+    ) -> Result<OffloadStats, StorageError> {
+        let remote = self
+            .remote
+            .as_ref()
+            .ok_or_else(|| StorageError::Unavailable("no remote store configured".to_string()))?;
+        let cutoff = Utc::now() - older_than;
+        let mut stats = OffloadStats::default();
+
+        for kind in [ChunkKind::Video, ChunkKind::Audio] {
+            let mut after_id = 0;
+            loop {
+                let candidates = self
+                    .db
+                    .find_offload_candidates(kind, cutoff, after_id, OFFLOAD_BATCH_SIZE)
+                    .await?;
+                if candidates.is_empty() {
+                    break;
+                }
+
+                for candidate in candidates {
+                    after_id = candidate.id;
+                    let path = Path::new(&candidate.file_path);
+                    let size = match fs::metadata(path).await {
+                        Ok(metadata) => metadata.len(),
+                        Err(_) => {
+                            debug!("skipping offload of missing chunk {}", candidate.file_path);
+                            stats.skipped += 1;
+                            continue;
+                        }
+                    };
+
+                    let key = object_key(kind, path);
+                    remote.put(&key, path).await?;
+                    self.db
+                        .mark_chunk_offloaded(kind, candidate.id, &key)
+                        .await?;
+                    fs::remove_file(path).await?;
+
+                    debug!(
+                        "offloaded {} chunk {} as {}",
+                        kind.as_str(),
+                        candidate.file_path,
+                        key
+                    );
+                    stats.offloaded += 1;
+                    stats.bytes += size;
+                }
+            }
+        }
+
+        Ok(stats)
+    }
+
+    /// Periodically offloads old chunks in the background.
+    pub fn start_offload_task(
+        self: Arc<Self>,
+        older_than: Duration,
+        interval: std::time::Duration,
+    ) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                match self.offload_older_than(older_than).await {
+                    Ok(stats) if stats.offloaded > 0 => info!(
+                        "offloaded {} chunks ({} MB), skipped {}",
+                        stats.offloaded,
+                        stats.bytes / 1024 / 1024,
+                        stats.skipped
+                    ),
+                    Ok(_) => debug!("no chunks to offload"),
+                    Err(e) => warn!("media offload run failed: {}", e),
+                }
+                tokio::time::sleep(interval).await;
+            }
+        })
+    }
+}
+
+fn object_key(kind: ChunkKind, path: &Path) -> String {
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string_lossy().replace(['/', '\\'], "_"));
+    format!("{}/{}", kind.as_str(), file_name)
+}
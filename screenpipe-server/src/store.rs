@@ -0,0 +1,1120 @@
+//! Backend-agnostic access to the screenpipe index.
+//!
+//! Everything the recorders, the api and the indexer do with the database goes
+//! through [`Store`], which combines one trait per domain ([`CaptureStore`],
+//! [`SearchStore`], [`WebhookStore`], ...) so a backend is written a domain at a
+//! time. The default backend is the local SQLite database
+//! ([`DatabaseManager`]); with the `postgres` feature a shared PostgreSQL
+//! database can be used instead, picked from the connection url.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use image::DynamicImage;
+use screenpipe_audio::AudioDevice;
+use screenpipe_core::{CronHistory, CronRun};
+use screenpipe_vision::OcrEngine;
+
+use crate::conversations::{Conversation, ConversationSpec, ConversationTurn};
+use crate::db_maintenance::MaintenanceRun;
+use crate::db_types::{
+    AudioChunksResponse, AudioResult, ChunkKind, ContentType, OCRResult, OffloadCandidate,
+    SearchCursor, SearchPage, SearchResult, Speaker, SpeakerCursor, TagContentType,
+    TimeSeriesChunk, UiContent, UnnamedSpeaker,
+};
+use crate::db_writer::DbWriterMetrics;
+use crate::meetings::{IdRange, Meeting, MeetingSpec};
+use crate::rules::{Rule, RuleSpec};
+use crate::video_utils::VideoMetadata;
+use crate::webhooks::{Webhook, WebhookDeadLetter, WebhookDelivery, WebhookSpec};
+use crate::DatabaseManager;
+
+/// The whole index, implemented by each backend.
+#[async_trait]
+pub trait Store:
+    CaptureStore
+    + SearchStore
+    + EmbeddingStore
+    + TimelineStore
+    + TagStore
+    + SpeakerStore
+    + MediaChunkStore
+    + PipeCronStore
+    + WebhookStore
+    + RuleStore
+    + MeetingStore
+    + ConversationStore
+{
+    /// Short backend name, reported in logs and /health.
+    fn backend(&self) -> &'static str;
+
+    /// Write queue of the local SQLite database, reported in /health.
+    fn writer_metrics(&self) -> Option<DbWriterMetrics> {
+        None
+    }
+
+    /// Latest maintenance runs, for the backends that run their own.
+    async fn maintenance_runs(&self) -> Option<Vec<MaintenanceRun>> {
+        None
+    }
+}
+
+/// Writes of the recorders: video and audio chunks, frames, OCR, transcriptions and UI text.
+#[async_trait]
+pub trait CaptureStore: Send + Sync {
+    async fn insert_video_chunk(
+        &self,
+        file_path: &str,
+        device_name: &str,
+    ) -> Result<i64, sqlx::Error>;
+
+    /// Inserts a frame into the latest video chunk of `device_name`, returns 0 if the
+    /// device has no chunk yet.
+    async fn insert_frame(
+        &self,
+        device_name: &str,
+        timestamp: Option<DateTime<Utc>>,
+    ) -> Result<i64, sqlx::Error>;
+
+    #[allow(clippy::too_many_arguments)]
+    async fn insert_ocr_text(
+        &self,
+        frame_id: i64,
+        text: &str,
+        text_json: &str,
+        app_name: &str,
+        window_name: &str,
+        ocr_engine: Arc<OcrEngine>,
+        focused: bool,
+    ) -> Result<(), sqlx::Error>;
+
+    async fn insert_audio_chunk(&self, file_path: &str) -> Result<i64, sqlx::Error>;
+
+    async fn get_or_insert_audio_chunk(&self, file_path: &str) -> Result<i64, sqlx::Error>;
+
+    #[allow(clippy::too_many_arguments)]
+    async fn insert_audio_transcription(
+        &self,
+        audio_chunk_id: i64,
+        transcription: &str,
+        offset_index: i64,
+        transcription_engine: &str,
+        device: &AudioDevice,
+        speaker_id: Option<i64>,
+        start_time: Option<f64>,
+        end_time: Option<f64>,
+    ) -> Result<i64, sqlx::Error>;
+
+    async fn update_audio_transcription(
+        &self,
+        audio_chunk_id: i64,
+        transcription: &str,
+    ) -> Result<i64, sqlx::Error>;
+
+    /// Moves an audio chunk and its transcriptions to `timestamp`, for audio that wasn't
+    /// recorded live (generated or imported).
+    async fn set_audio_chunk_timestamp(
+        &self,
+        audio_chunk_id: i64,
+        timestamp: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error>;
+
+    async fn insert_ui_monitoring(
+        &self,
+        text_output: &str,
+        app: &str,
+        window: &str,
+        timestamp: DateTime<Utc>,
+    ) -> Result<i64, sqlx::Error>;
+
+    async fn create_video_with_frames(
+        &self,
+        file_path: &str,
+        frames: Vec<DynamicImage>,
+        metadata: VideoMetadata,
+    ) -> Result<Vec<i64>, sqlx::Error>;
+
+    async fn update_frame_name(&self, frame_id: i64, name: &str) -> Result<(), sqlx::Error>;
+
+    async fn update_video_chunk_frames_names(
+        &self,
+        video_chunk_id: i64,
+        name: &str,
+    ) -> Result<(), sqlx::Error>;
+}
+
+/// Full-text search over the captured content.
+///
+/// The sub-searches return rows newest first, ordered by timestamp then id, and
+/// when `cursor` is set only the rows that come after it (see `SearchCursor::bound_for`).
+#[async_trait]
+pub trait SearchStore: Send + Sync {
+    #[allow(clippy::too_many_arguments)]
+    async fn search_ocr(
+        &self,
+        query: &str,
+        limit: u32,
+        offset: u32,
+        cursor: Option<&SearchCursor>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>,
+        window_name: Option<&str>,
+        min_length: Option<usize>,
+        max_length: Option<usize>,
+        frame_name: Option<&str>,
+    ) -> Result<Vec<OCRResult>, sqlx::Error>;
+
+    #[allow(clippy::too_many_arguments)]
+    async fn search_audio(
+        &self,
+        query: &str,
+        limit: u32,
+        offset: u32,
+        cursor: Option<&SearchCursor>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        min_length: Option<usize>,
+        max_length: Option<usize>,
+        speaker_ids: Option<Vec<i64>>,
+    ) -> Result<Vec<AudioResult>, sqlx::Error>;
+
+    #[allow(clippy::too_many_arguments)]
+    async fn search_ui_monitoring(
+        &self,
+        query: &str,
+        app_name: Option<&str>,
+        window_name: Option<&str>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        limit: u32,
+        offset: u32,
+        cursor: Option<&SearchCursor>,
+    ) -> Result<Vec<UiContent>, sqlx::Error>;
+
+    /// Searches every requested content type and returns the newest results first.
+    #[allow(clippy::too_many_arguments)]
+    async fn search(
+        &self,
+        query: &str,
+        content_type: ContentType,
+        limit: u32,
+        offset: u32,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>,
+        window_name: Option<&str>,
+        min_length: Option<usize>,
+        max_length: Option<usize>,
+        speaker_ids: Option<Vec<i64>>,
+        frame_name: Option<&str>,
+    ) -> Result<Vec<SearchResult>, sqlx::Error> {
+        // every source has to provide enough rows to fill the page after skipping
+        let results = search_merged(
+            self,
+            query,
+            &content_type,
+            limit.saturating_add(offset),
+            None,
+            start_time,
+            end_time,
+            app_name,
+            window_name,
+            min_length,
+            max_length,
+            speaker_ids,
+            frame_name,
+        )
+        .await?;
+
+        Ok(results
+            .into_iter()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .collect())
+    }
+
+    /// Like [`Store::search`] but paginated with a cursor instead of an offset, so deep
+    /// pages cost the same as the first one.
+    #[allow(clippy::too_many_arguments)]
+    async fn search_page(
+        &self,
+        query: &str,
+        content_type: ContentType,
+        limit: u32,
+        cursor: Option<SearchCursor>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>,
+        window_name: Option<&str>,
+        min_length: Option<usize>,
+        max_length: Option<usize>,
+        speaker_ids: Option<Vec<i64>>,
+        frame_name: Option<&str>,
+    ) -> Result<SearchPage, sqlx::Error> {
+        // one extra row tells whether there is a next page
+        let mut results = search_merged(
+            self,
+            query,
+            &content_type,
+            limit.saturating_add(1),
+            cursor.as_ref(),
+            start_time,
+            end_time,
+            app_name,
+            window_name,
+            min_length,
+            max_length,
+            speaker_ids,
+            frame_name,
+        )
+        .await?;
+
+        let next_cursor = if results.len() > limit as usize {
+            results.truncate(limit as usize);
+            results.last().map(SearchResult::cursor)
+        } else {
+            None
+        };
+
+        Ok(SearchPage {
+            results,
+            next_cursor,
+        })
+    }
+
+    /// Counts search matches. With a `cap`, counting stops once that many are found.
+    #[allow(clippy::too_many_arguments)]
+    async fn count_search_results_capped(
+        &self,
+        query: &str,
+        content_type: ContentType,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>,
+        window_name: Option<&str>,
+        min_length: Option<usize>,
+        max_length: Option<usize>,
+        speaker_ids: Option<Vec<i64>>,
+        frame_name: Option<&str>,
+        cap: Option<usize>,
+    ) -> Result<usize, sqlx::Error>;
+
+    #[allow(clippy::too_many_arguments)]
+    async fn count_search_results(
+        &self,
+        query: &str,
+        content_type: ContentType,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>,
+        window_name: Option<&str>,
+        min_length: Option<usize>,
+        max_length: Option<usize>,
+        speaker_ids: Option<Vec<i64>>,
+        frame_name: Option<&str>,
+    ) -> Result<usize, sqlx::Error> {
+        self.count_search_results_capped(
+            query,
+            content_type,
+            start_time,
+            end_time,
+            app_name,
+            window_name,
+            min_length,
+            max_length,
+            speaker_ids,
+            frame_name,
+            None,
+        )
+        .await
+    }
+
+    async fn get_frame(&self, frame_id: i64) -> Result<Option<(String, i64)>, sqlx::Error>;
+
+    /// Latest frame, audio chunk and ui capture timestamps.
+    async fn get_latest_timestamps(
+        &self,
+    ) -> Result<
+        (
+            Option<DateTime<Utc>>,
+            Option<DateTime<Utc>>,
+            Option<DateTime<Utc>>,
+        ),
+        sqlx::Error,
+    >;
+
+    /// Runs a query in the backend's own sql dialect and returns the rows as json objects.
+    /// Sqlite runs it on the read-only connections, so it can't hold up capture writes.
+    async fn execute_raw_sql(&self, query: &str) -> Result<serde_json::Value, sqlx::Error>;
+}
+
+/// Text embeddings of frames, for similarity search.
+#[async_trait]
+pub trait EmbeddingStore: Send + Sync {
+    async fn insert_embeddings(&self, frame_id: i64, embedding: String) -> Result<(), sqlx::Error>;
+
+    async fn search_similar_embeddings(
+        &self,
+        embedding: Vec<f32>,
+        limit: u32,
+        threshold: f32,
+    ) -> Result<Vec<OCRResult>, sqlx::Error>;
+}
+
+/// Video chunks and frames in recording order, for the timeline and the indexer.
+#[async_trait]
+pub trait TimelineStore: Send + Sync {
+    async fn find_video_chunks(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<TimeSeriesChunk, sqlx::Error>;
+
+    async fn get_total_frames(&self, video_path: &Path) -> Result<i64, sqlx::Error>;
+
+    /// Video chunk paths in the order they were recorded.
+    async fn get_ordered_videos(&self) -> Result<Vec<String>, sqlx::Error>;
+
+    async fn get_next_video(&self, current_video_path: &str)
+        -> Result<Option<String>, sqlx::Error>;
+
+    /// Distinct chunked texts of `source` indexed after `since`, latest first.
+    async fn get_chunked_texts_since(
+        &self,
+        since: DateTime<Utc>,
+        source: &str,
+    ) -> Result<Vec<String>, sqlx::Error>;
+}
+
+/// Tags on captured content.
+#[async_trait]
+pub trait TagStore: Send + Sync {
+    async fn add_tags(
+        &self,
+        id: i64,
+        content_type: TagContentType,
+        tags: Vec<String>,
+    ) -> Result<(), sqlx::Error>;
+
+    async fn get_tags(
+        &self,
+        id: i64,
+        content_type: TagContentType,
+    ) -> Result<Vec<String>, sqlx::Error>;
+
+    async fn remove_tags(
+        &self,
+        id: i64,
+        content_type: TagContentType,
+        tags: Vec<String>,
+    ) -> Result<(), sqlx::Error>;
+
+    async fn add_tags_to_ui_monitoring(
+        &self,
+        ui_monitoring_id: i64,
+        tag_ids: &[i64],
+    ) -> Result<(), anyhow::Error>;
+
+    async fn get_ui_monitoring_tags(
+        &self,
+        ui_monitoring_id: i64,
+    ) -> Result<Vec<String>, anyhow::Error>;
+}
+
+/// Speakers recognized from their voice embeddings.
+#[async_trait]
+pub trait SpeakerStore: Send + Sync {
+    async fn insert_speaker(&self, embedding: &[f32]) -> Result<Speaker, sqlx::Error>;
+
+    async fn update_speaker_metadata(
+        &self,
+        speaker_id: i64,
+        metadata: &str,
+    ) -> Result<i64, sqlx::Error>;
+
+    async fn get_speaker_by_id(&self, speaker_id: i64) -> Result<Speaker, sqlx::Error>;
+
+    /// Closest known speaker to `embedding`, if any is close enough.
+    async fn get_speaker_from_embedding(
+        &self,
+        embedding: &[f32],
+    ) -> Result<Option<Speaker>, sqlx::Error>;
+
+    async fn update_speaker_name(&self, speaker_id: i64, name: &str) -> Result<i64, sqlx::Error>;
+
+    async fn get_audio_chunks_for_speaker(
+        &self,
+        speaker_id: i64,
+    ) -> Result<Vec<AudioChunksResponse>, sqlx::Error>;
+
+    /// Unnamed speakers with the most transcriptions first, after `cursor` when set.
+    async fn get_unnamed_speakers_page(
+        &self,
+        limit: u32,
+        offset: u32,
+        cursor: Option<&SpeakerCursor>,
+        speaker_ids: Option<Vec<i64>>,
+    ) -> Result<Vec<UnnamedSpeaker>, sqlx::Error>;
+
+    async fn get_unnamed_speakers(
+        &self,
+        limit: u32,
+        offset: u32,
+        speaker_ids: Option<Vec<i64>>,
+    ) -> Result<Vec<Speaker>, sqlx::Error> {
+        Ok(self
+            .get_unnamed_speakers_page(limit, offset, None, speaker_ids)
+            .await?
+            .into_iter()
+            .map(|unnamed| unnamed.speaker)
+            .collect())
+    }
+
+    async fn merge_speakers(
+        &self,
+        speaker_to_keep_id: i64,
+        speaker_to_merge_id: i64,
+    ) -> Result<Speaker, sqlx::Error>;
+
+    async fn search_speakers(&self, name_prefix: &str) -> Result<Vec<Speaker>, sqlx::Error>;
+
+    async fn delete_speaker(&self, id: i64) -> Result<(), sqlx::Error>;
+
+    async fn get_similar_speakers(
+        &self,
+        speaker_id: i64,
+        limit: u32,
+    ) -> Result<Vec<Speaker>, sqlx::Error>;
+
+    async fn mark_speaker_as_hallucination(&self, id: i64) -> Result<(), sqlx::Error>;
+}
+
+/// Where the media of the chunks lives, for offloading.
+#[async_trait]
+pub trait MediaChunkStore: Send + Sync {
+    /// Chunks still only on local disk whose media is older than `cutoff`, ordered by id
+    /// and starting after `after_id`.
+    async fn find_offload_candidates(
+        &self,
+        kind: ChunkKind,
+        cutoff: DateTime<Utc>,
+        after_id: i64,
+        limit: i64,
+    ) -> Result<Vec<OffloadCandidate>, sqlx::Error>;
+
+    async fn mark_chunk_offloaded(
+        &self,
+        kind: ChunkKind,
+        id: i64,
+        remote_key: &str,
+    ) -> Result<(), sqlx::Error>;
+
+    async fn get_chunk_remote_key(&self, file_path: &str) -> Result<Option<String>, sqlx::Error>;
+
+    async fn get_audio_chunk_file_path(&self, id: i64) -> Result<Option<String>, sqlx::Error>;
+}
+
+/// History of the pipe cron runs.
+#[async_trait]
+pub trait PipeCronStore: Send + Sync {
+    async fn insert_pipe_cron_run(&self, run: &CronRun) -> Result<i64, sqlx::Error>;
+
+    /// Most recent runs of the crons of `pipe_id` first, only of `job` when set.
+    async fn get_pipe_cron_runs(
+        &self,
+        pipe_id: &str,
+        job: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<CronRun>, sqlx::Error>;
+}
+
+/// Webhooks with their deliveries and dead letters.
+#[async_trait]
+pub trait WebhookStore: Send + Sync {
+    async fn list_webhooks(&self) -> Result<Vec<Webhook>, sqlx::Error>;
+
+    async fn get_webhook(&self, id: i64) -> Result<Option<Webhook>, sqlx::Error>;
+
+    async fn insert_webhook(&self, spec: &WebhookSpec) -> Result<i64, sqlx::Error>;
+
+    /// Replaces the webhook, keeping its secret when `spec` has none. False when there is
+    /// no webhook `id`.
+    async fn update_webhook(&self, id: i64, spec: &WebhookSpec) -> Result<bool, sqlx::Error>;
+
+    /// Deletes the webhook with its deliveries and dead letters.
+    async fn delete_webhook(&self, id: i64) -> Result<bool, sqlx::Error>;
+
+    async fn insert_webhook_delivery(&self, delivery: &WebhookDelivery)
+        -> Result<i64, sqlx::Error>;
+
+    /// Most recent deliveries of `webhook_id` first.
+    async fn get_webhook_deliveries(
+        &self,
+        webhook_id: i64,
+        limit: u32,
+    ) -> Result<Vec<WebhookDelivery>, sqlx::Error>;
+
+    async fn insert_webhook_dead_letter(
+        &self,
+        letter: &WebhookDeadLetter,
+    ) -> Result<i64, sqlx::Error>;
+
+    /// Most recent dead letters of `webhook_id` first.
+    async fn get_webhook_dead_letters(
+        &self,
+        webhook_id: i64,
+        limit: u32,
+    ) -> Result<Vec<WebhookDeadLetter>, sqlx::Error>;
+
+    async fn get_webhook_dead_letter(
+        &self,
+        id: i64,
+    ) -> Result<Option<WebhookDeadLetter>, sqlx::Error>;
+
+    async fn delete_webhook_dead_letter(&self, id: i64) -> Result<bool, sqlx::Error>;
+
+    /// Deletes deliveries and dead letters older than `before` or past the latest `keep` of
+    /// their webhook, returns how many.
+    async fn prune_webhook_history(
+        &self,
+        keep: i64,
+        before: DateTime<Utc>,
+    ) -> Result<u64, sqlx::Error>;
+}
+
+/// Automation rules.
+#[async_trait]
+pub trait RuleStore: Send + Sync {
+    async fn list_rules(&self) -> Result<Vec<Rule>, sqlx::Error>;
+
+    async fn get_rule(&self, id: i64) -> Result<Option<Rule>, sqlx::Error>;
+
+    async fn insert_rule(&self, spec: &RuleSpec) -> Result<i64, sqlx::Error>;
+
+    /// False when there is no rule `id`.
+    async fn update_rule(&self, id: i64, spec: &RuleSpec) -> Result<bool, sqlx::Error>;
+
+    async fn delete_rule(&self, id: i64) -> Result<bool, sqlx::Error>;
+}
+
+/// Meetings with the content captured during them.
+#[async_trait]
+pub trait MeetingStore: Send + Sync {
+    /// Meetings overlapping `start_time`..`end_time`, latest first.
+    async fn list_meetings(
+        &self,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<Meeting>, sqlx::Error>;
+
+    async fn get_meeting(&self, id: i64) -> Result<Option<Meeting>, sqlx::Error>;
+
+    /// Links the frames and transcriptions captured during the meeting.
+    async fn insert_meeting(&self, spec: &MeetingSpec) -> Result<i64, sqlx::Error>;
+
+    /// Links the content of the meeting again. False when there is no meeting `id`.
+    async fn update_meeting(&self, id: i64, spec: &MeetingSpec) -> Result<bool, sqlx::Error>;
+
+    async fn delete_meeting(&self, id: i64) -> Result<bool, sqlx::Error>;
+}
+
+/// Conversations segmented from the transcriptions.
+#[async_trait]
+pub trait ConversationStore: Send + Sync {
+    /// Conversations overlapping `start_time`..`end_time` whose transcript matches `query`,
+    /// latest first. An empty query matches them all.
+    async fn search_conversations(
+        &self,
+        query: &str,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<Conversation>, sqlx::Error>;
+
+    async fn get_conversation(&self, id: i64) -> Result<Option<Conversation>, sqlx::Error>;
+
+    /// Turns of the conversation, in order.
+    async fn get_conversation_turns(&self, id: i64) -> Result<Vec<ConversationTurn>, sqlx::Error>;
+
+    /// Stores the conversation with its turns, its transcriptions and duplicates are
+    /// segmented from then on.
+    async fn insert_conversation(&self, spec: &ConversationSpec) -> Result<i64, sqlx::Error>;
+
+    /// Leaves the transcriptions of the conversation to the segmenter again.
+    async fn delete_conversation(&self, id: i64) -> Result<bool, sqlx::Error>;
+
+    /// Transcriptions of `start_time`..`end_time` already in a conversation.
+    async fn conversation_transcription_ids(
+        &self,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<i64>, sqlx::Error>;
+}
+
+/// Columns of a `pipe_cron_runs` row, in table order.
+pub(crate) type CronRunRow = (
+    i64,
+    String,
+    String,
+    String,
+    DateTime<Utc>,
+    DateTime<Utc>,
+    String,
+    Option<String>,
+);
+
+pub(crate) fn cron_run_from_row(row: CronRunRow) -> Result<CronRun, sqlx::Error> {
+    let (id, pipe_id, job, trigger, started_at, ended_at, status, output) = row;
+    Ok(CronRun {
+        id: Some(id),
+        pipe_id,
+        job,
+        trigger: trigger
+            .parse()
+            .map_err(|e: anyhow::Error| sqlx::Error::Decode(e.into()))?,
+        started_at,
+        ended_at,
+        status: status
+            .parse()
+            .map_err(|e: anyhow::Error| sqlx::Error::Decode(e.into()))?,
+        output,
+    })
+}
+
+/// Columns of a `webhooks` row, in table order.
+pub(crate) type WebhookRow = (
+    i64,
+    String,
+    String,
+    Option<String>,
+    Option<String>,
+    String,
+    bool,
+    DateTime<Utc>,
+    DateTime<Utc>,
+);
+
+pub(crate) fn webhook_from_row(row: WebhookRow) -> Result<Webhook, sqlx::Error> {
+    let (id, url, events, filter, secret, headers, enabled, created_at, updated_at) = row;
+    Ok(Webhook {
+        id,
+        url,
+        events: serde_json::from_str(&events).map_err(|e| sqlx::Error::Decode(e.into()))?,
+        filter,
+        secret,
+        headers: serde_json::from_str(&headers).map_err(|e| sqlx::Error::Decode(e.into()))?,
+        enabled,
+        created_at,
+        updated_at,
+    })
+}
+
+/// Columns of a `webhook_deliveries` row, in table order.
+pub(crate) type WebhookDeliveryRow = (
+    i64,
+    i64,
+    String,
+    Option<i64>,
+    String,
+    i64,
+    Option<i64>,
+    Option<String>,
+    i64,
+    DateTime<Utc>,
+);
+
+pub(crate) fn webhook_delivery_from_row(
+    row: WebhookDeliveryRow,
+) -> Result<WebhookDelivery, sqlx::Error> {
+    let (
+        id,
+        webhook_id,
+        event,
+        event_offset,
+        status,
+        attempts,
+        response_status,
+        error,
+        duration_ms,
+        delivered_at,
+    ) = row;
+    Ok(WebhookDelivery {
+        id: Some(id),
+        webhook_id,
+        event,
+        event_offset: event_offset.map(|o| o as u64),
+        status: status
+            .parse()
+            .map_err(|e: anyhow::Error| sqlx::Error::Decode(e.into()))?,
+        attempts: attempts as u32,
+        response_status: response_status.map(|s| s as u16),
+        error,
+        duration_ms,
+        delivered_at,
+    })
+}
+
+/// Columns of a `webhook_dead_letters` row, in table order.
+pub(crate) type WebhookDeadLetterRow = (
+    i64,
+    i64,
+    String,
+    Option<i64>,
+    String,
+    i64,
+    String,
+    DateTime<Utc>,
+);
+
+pub(crate) fn webhook_dead_letter_from_row(
+    row: WebhookDeadLetterRow,
+) -> Result<WebhookDeadLetter, sqlx::Error> {
+    let (id, webhook_id, event, event_offset, payload, attempts, error, created_at) = row;
+    Ok(WebhookDeadLetter {
+        id: Some(id),
+        webhook_id,
+        event,
+        event_offset: event_offset.map(|o| o as u64),
+        payload: serde_json::from_str(&payload).map_err(|e| sqlx::Error::Decode(e.into()))?,
+        attempts: attempts as u32,
+        error,
+        created_at,
+    })
+}
+
+/// Columns of an `automation_rules` row, in table order.
+pub(crate) type RuleRow = (i64, String, DateTime<Utc>, DateTime<Utc>);
+
+pub(crate) fn rule_from_row(row: RuleRow) -> Result<Rule, sqlx::Error> {
+    let (id, definition, created_at, updated_at) = row;
+    Ok(Rule {
+        id,
+        spec: serde_json::from_str(&definition).map_err(|e| sqlx::Error::Decode(e.into()))?,
+        created_at,
+        updated_at,
+    })
+}
+
+/// Columns of a `meetings` row, in table order.
+pub(crate) type MeetingRow = (
+    i64,
+    DateTime<Utc>,
+    Option<DateTime<Utc>>,
+    String,
+    Option<String>,
+    String,
+    String,
+    Option<i64>,
+    Option<i64>,
+    Option<i64>,
+    Option<i64>,
+    DateTime<Utc>,
+    DateTime<Utc>,
+);
+
+pub(crate) fn meeting_from_row(row: MeetingRow) -> Result<Meeting, sqlx::Error> {
+    let (
+        id,
+        start_time,
+        end_time,
+        app,
+        title,
+        participants,
+        signals,
+        first_frame_id,
+        last_frame_id,
+        first_transcription_id,
+        last_transcription_id,
+        created_at,
+        updated_at,
+    ) = row;
+    let range = |first: Option<i64>, last: Option<i64>| {
+        first.zip(last).map(|(first, last)| IdRange { first, last })
+    };
+    Ok(Meeting {
+        id,
+        spec: MeetingSpec {
+            start_time,
+            end_time,
+            app,
+            title,
+            participants: serde_json::from_str(&participants)
+                .map_err(|e| sqlx::Error::Decode(e.into()))?,
+            signals: serde_json::from_str(&signals).map_err(|e| sqlx::Error::Decode(e.into()))?,
+        },
+        frames: range(first_frame_id, last_frame_id),
+        transcriptions: range(first_transcription_id, last_transcription_id),
+        created_at,
+        updated_at,
+    })
+}
+
+/// Columns of a `conversations` row, in table order.
+pub(crate) type ConversationRow = (
+    i64,
+    DateTime<Utc>,
+    DateTime<Utc>,
+    String,
+    String,
+    String,
+    i64,
+    Option<i64>,
+    Option<i64>,
+    DateTime<Utc>,
+);
+
+pub(crate) fn conversation_from_row(row: ConversationRow) -> Result<Conversation, sqlx::Error> {
+    let (
+        id,
+        start_time,
+        end_time,
+        devices,
+        speakers,
+        transcript,
+        turn_count,
+        first_transcription_id,
+        last_transcription_id,
+        created_at,
+    ) = row;
+    Ok(Conversation {
+        id,
+        start_time,
+        end_time,
+        devices: serde_json::from_str(&devices).map_err(|e| sqlx::Error::Decode(e.into()))?,
+        speakers: serde_json::from_str(&speakers).map_err(|e| sqlx::Error::Decode(e.into()))?,
+        transcript,
+        turn_count,
+        transcriptions: first_transcription_id
+            .zip(last_transcription_id)
+            .map(|(first, last)| IdRange { first, last }),
+        created_at,
+    })
+}
+
+/// Columns of a `conversation_turns` row from `start_time` on.
+pub(crate) type ConversationTurnRow = (
+    DateTime<Utc>,
+    DateTime<Utc>,
+    Option<i64>,
+    Option<String>,
+    String,
+    bool,
+    String,
+    String,
+);
+
+pub(crate) fn conversation_turn_from_row(
+    row: ConversationTurnRow,
+) -> Result<ConversationTurn, sqlx::Error> {
+    let (start_time, end_time, speaker_id, speaker_name, device_name, output, text, ids) = row;
+    Ok(ConversationTurn {
+        start_time,
+        end_time,
+        speaker_id,
+        speaker_name,
+        device_name,
+        output,
+        text,
+        transcription_ids: serde_json::from_str(&ids).map_err(|e| sqlx::Error::Decode(e.into()))?,
+    })
+}
+
+/// Rows read per query when going through captured content.
+const CONTENT_PAGE: u32 = 500;
+
+/// Content of `content_type` captured between `start` and `end`, oldest first.
+pub(crate) async fn content_between(
+    store: &dyn Store,
+    content_type: ContentType,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Vec<SearchResult>, sqlx::Error> {
+    let mut results = Vec::new();
+    let mut cursor = None;
+    loop {
+        let page = store
+            .search_page(
+                "",
+                content_type.clone(),
+                CONTENT_PAGE,
+                cursor,
+                Some(start),
+                Some(end),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await?;
+        results.extend(page.results);
+        match page.next_cursor {
+            Some(next) => cursor = Some(next),
+            None => break,
+        }
+    }
+    results.sort_by_key(|result| result.timestamp());
+    Ok(results)
+}
+
+/// Keeps the history of pipe cron runs in the store, registered with
+/// [`screenpipe_core::set_cron_history`] at startup.
+pub struct StoreCronHistory(pub Arc<dyn Store>);
+
+#[async_trait]
+impl CronHistory for StoreCronHistory {
+    async fn record_cron_run(&self, run: &CronRun) -> anyhow::Result<()> {
+        self.0.insert_pipe_cron_run(run).await?;
+        Ok(())
+    }
+
+    async fn cron_runs(
+        &self,
+        pipe_id: &str,
+        job: Option<&str>,
+        limit: u32,
+    ) -> anyhow::Result<Vec<CronRun>> {
+        Ok(self.0.get_pipe_cron_runs(pipe_id, job, limit).await?)
+    }
+}
+
+/// Runs the sub-searches `content_type` covers, each returning up to `per_source_limit`
+/// rows after `cursor`, and merges them into one newest-first list.
+#[allow(clippy::too_many_arguments)]
+async fn search_merged<S: SearchStore + ?Sized>(
+    store: &S,
+    query: &str,
+    content_type: &ContentType,
+    per_source_limit: u32,
+    cursor: Option<&SearchCursor>,
+    start_time: Option<DateTime<Utc>>,
+    end_time: Option<DateTime<Utc>>,
+    app_name: Option<&str>,
+    window_name: Option<&str>,
+    min_length: Option<usize>,
+    max_length: Option<usize>,
+    speaker_ids: Option<Vec<i64>>,
+    frame_name: Option<&str>,
+) -> Result<Vec<SearchResult>, sqlx::Error> {
+    // audio has no app or window, filtering on them leaves it out
+    let no_window_filter = app_name.is_none() && window_name.is_none();
+    let (ocr, audio, ui) = match content_type {
+        ContentType::All => (true, no_window_filter && frame_name.is_none(), true),
+        ContentType::OCR => (true, false, false),
+        ContentType::Audio => (false, no_window_filter, false),
+        ContentType::UI => (false, false, true),
+        ContentType::AudioAndUi => (false, true, true),
+        ContentType::OcrAndUi => (true, false, true),
+        ContentType::AudioAndOcr => (true, true, false),
+    };
+
+    let (ocr_results, audio_results, ui_results) = tokio::try_join!(
+        async move {
+            if !ocr {
+                return Ok(Vec::new());
+            }
+            store
+                .search_ocr(
+                    query,
+                    per_source_limit,
+                    0,
+                    cursor,
+                    start_time,
+                    end_time,
+                    app_name,
+                    window_name,
+                    min_length,
+                    max_length,
+                    frame_name,
+                )
+                .await
+        },
+        async move {
+            if !audio {
+                return Ok(Vec::new());
+            }
+            store
+                .search_audio(
+                    query,
+                    per_source_limit,
+                    0,
+                    cursor,
+                    start_time,
+                    end_time,
+                    min_length,
+                    max_length,
+                    speaker_ids,
+                )
+                .await
+        },
+        async move {
+            if !ui {
+                return Ok(Vec::new());
+            }
+            store
+                .search_ui_monitoring(
+                    query,
+                    app_name,
+                    window_name,
+                    start_time,
+                    end_time,
+                    per_source_limit,
+                    0,
+                    cursor,
+                )
+                .await
+        }
+    )?;
+
+    let mut results: Vec<SearchResult> = ocr_results
+        .into_iter()
+        .map(SearchResult::OCR)
+        .chain(audio_results.into_iter().map(SearchResult::Audio))
+        .chain(ui_results.into_iter().map(SearchResult::UI))
+        .collect();
+    results.sort_by_key(|result| result.cursor().sort_key());
+
+    Ok(results)
+}
+
+/// Backend picked from a database url.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StoreUrl<'a> {
+    /// Path of a local SQLite database (or `:memory:`).
+    Sqlite(&'a str),
+    /// `postgres://` or `postgresql://` connection url.
+    Postgres(&'a str),
+}
+
+impl<'a> StoreUrl<'a> {
+    pub fn parse(url: &'a str) -> Self {
+        if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+            StoreUrl::Postgres(url)
+        } else {
+            let path = url.strip_prefix("sqlite://").unwrap_or(url);
+            StoreUrl::Sqlite(path.strip_prefix("sqlite:").unwrap_or(path))
+        }
+    }
+}
+
+/// Opens the store for `url`: a `postgres://` url uses PostgreSQL (requires the
+/// `postgres` feature), anything else is treated as a SQLite database path.
+pub async fn open_store(url: &str) -> Result<Arc<dyn Store>, sqlx::Error> {
+    match StoreUrl::parse(url) {
+        StoreUrl::Sqlite(path) => Ok(Arc::new(DatabaseManager::new(path).await?)),
+        #[cfg(feature = "postgres")]
+        StoreUrl::Postgres(url) => Ok(Arc::new(crate::postgres::PostgresStore::new(url).await?)),
+        #[cfg(not(feature = "postgres"))]
+        StoreUrl::Postgres(_) => Err(sqlx::Error::Configuration(
+            "postgres support is not enabled, rebuild screenpipe with the `postgres` feature"
+                .into(),
+        )),
+    }
+}
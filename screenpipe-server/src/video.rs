@@ -1,3 +1,4 @@
+use crate::core::capture_paused;
 use chrono::Utc;
 use crossbeam::queue::ArrayQueue;
 use image::ImageFormat::{self};
@@ -73,51 +74,8 @@ impl VideoCapture {
 
         // In the _queue_thread
         let _queue_thread = tokio::spawn(async move {
-            // Helper function to push to queue and handle errors
-            fn push_to_queue(
-                queue: &ArrayQueue<Arc<CaptureResult>>,
-                result: &Arc<CaptureResult>,
-                queue_name: &str,
-            ) -> bool {
-                if queue.push(Arc::clone(result)).is_err() {
-                    if queue.pop().is_none() {
-                        error!("{} queue is in an inconsistent state", queue_name);
-                        return false;
-                    }
-                    if queue.push(Arc::clone(result)).is_err() {
-                        error!(
-                            "Failed to push to {} queue after removing oldest frame",
-                            queue_name
-                        );
-                        return false;
-                    }
-                    debug!("{} queue was full, dropped oldest frame", queue_name);
-                }
-                true
-            }
             while let Some(result) = result_receiver.recv().await {
-                let frame_number = result.frame_number;
-                debug!("Received frame {} for queueing", frame_number);
-
-                let result = Arc::new(result);
-
-                let video_pushed = push_to_queue(&capture_video_frame_queue, &result, "Video");
-                let ocr_pushed = push_to_queue(&capture_ocr_frame_queue, &result, "OCR");
-
-                if !video_pushed || !ocr_pushed {
-                    error!(
-                        "Failed to push frame {} to one or more queues",
-                        frame_number
-                    );
-                    continue; // Skip to next iteration instead of crashing
-                }
-
-                debug!(
-                    "Frame {} pushed to queues. Queue lengths: {}, {}",
-                    frame_number,
-                    capture_video_frame_queue.len(),
-                    capture_ocr_frame_queue.len()
-                );
+                queue_frame(&capture_video_frame_queue, &capture_ocr_frame_queue, result);
             }
         });
 
@@ -143,6 +101,65 @@ impl VideoCapture {
     }
 }
 
+/// Queues a captured frame for the video chunk and OCR. Frames captured while capture is
+/// paused are dropped here, before anything is encoded. False when the frame wasn't queued.
+pub fn queue_frame(
+    video_frame_queue: &ArrayQueue<Arc<CaptureResult>>,
+    ocr_frame_queue: &ArrayQueue<Arc<CaptureResult>>,
+    result: CaptureResult,
+) -> bool {
+    // Helper function to push to queue and handle errors
+    fn push_to_queue(
+        queue: &ArrayQueue<Arc<CaptureResult>>,
+        result: &Arc<CaptureResult>,
+        queue_name: &str,
+    ) -> bool {
+        if queue.push(Arc::clone(result)).is_err() {
+            if queue.pop().is_none() {
+                error!("{} queue is in an inconsistent state", queue_name);
+                return false;
+            }
+            if queue.push(Arc::clone(result)).is_err() {
+                error!(
+                    "Failed to push to {} queue after removing oldest frame",
+                    queue_name
+                );
+                return false;
+            }
+            debug!("{} queue was full, dropped oldest frame", queue_name);
+        }
+        true
+    }
+
+    let frame_number = result.frame_number;
+    if capture_paused() {
+        debug!("capture paused, dropping frame {}", frame_number);
+        return false;
+    }
+    debug!("Received frame {} for queueing", frame_number);
+
+    let result = Arc::new(result);
+
+    let video_pushed = push_to_queue(video_frame_queue, &result, "Video");
+    let ocr_pushed = push_to_queue(ocr_frame_queue, &result, "OCR");
+
+    if !video_pushed || !ocr_pushed {
+        error!(
+            "Failed to push frame {} to one or more queues",
+            frame_number
+        );
+        return false;
+    }
+
+    debug!(
+        "Frame {} pushed to queues. Queue lengths: {}, {}",
+        frame_number,
+        video_frame_queue.len(),
+        ocr_frame_queue.len()
+    );
+    true
+}
+
 pub async fn start_ffmpeg_process(output_file: &str, fps: f64) -> Result<Child, anyhow::Error> {
     // Overriding fps with max fps if over the max and warning user
     let fps = if fps > MAX_FPS {
@@ -304,7 +321,7 @@ fn create_output_file(output_path: &str, monitor_id: u32) -> String {
         .to_string()
 }
 
-fn spawn_ffmpeg_loggers(stderr: Option<ChildStderr>, stdout: Option<ChildStdout>) {
+pub(crate) fn spawn_ffmpeg_loggers(stderr: Option<ChildStderr>, stdout: Option<ChildStdout>) {
     if let Some(stderr) = stderr {
         tokio::spawn(log_ffmpeg_output(BufReader::new(stderr), "stderr"));
     }
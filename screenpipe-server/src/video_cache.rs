@@ -16,7 +16,8 @@ use tokio::sync::{mpsc, oneshot};
 use tracing::{debug, error};
 
 use crate::db_types::{FrameData, OCREntry};
-use crate::DatabaseManager;
+use crate::storage::MediaStorage;
+use crate::store::Store;
 
 type FrameChannel = mpsc::Sender<TimeSeriesFrame>;
 
@@ -432,11 +433,12 @@ async fn run_cache_manager(mut cache: FrameDiskCache, mut rx: mpsc::Receiver<Cac
 pub struct FrameCache {
     pub screenpipe_dir: PathBuf,
     cache_tx: mpsc::Sender<CacheMessage>,
-    db: Arc<DatabaseManager>,
+    db: Arc<dyn Store>,
+    storage: Arc<MediaStorage>,
 }
 
 impl FrameCache {
-    pub async fn new(screenpipe_dir: PathBuf, db: Arc<DatabaseManager>) -> Result<Self> {
+    pub async fn new(screenpipe_dir: PathBuf, db: Arc<dyn Store>) -> Result<Self> {
         let cache_config = CacheConfig {
             cache_dir: cache_dir().unwrap().join("screenpipe").join("frames"),
             ..Default::default()
@@ -452,10 +454,17 @@ impl FrameCache {
         Ok(Self {
             screenpipe_dir,
             cache_tx,
+            storage: Arc::new(MediaStorage::local(db.clone())),
             db,
         })
     }
 
+    /// Reads video chunks through `storage` so offloaded chunks are fetched on demand.
+    pub fn with_storage(mut self, storage: Arc<MediaStorage>) -> Self {
+        self.storage = storage;
+        self
+    }
+
     async fn extract_frames_batch(
         &self,
         start_time: DateTime<Utc>,
@@ -552,9 +561,26 @@ impl FrameCache {
 
             for (file_path, tasks) in extraction_queue {
                 debug!("extracting {} frames from {}", tasks.len(), file_path);
+                let local_path = match self.storage.resolve(&file_path).await {
+                    Ok(path) => path,
+                    Err(e) => {
+                        error!("failed to resolve video chunk {}: {}", file_path, e);
+                        frame_tx
+                            .send(TimeSeriesFrame {
+                                timestamp: tasks[0].0.timestamp,
+                                frame_data: Vec::new(),
+                                error: Some(e.to_string()),
+                            })
+                            .await?;
+                        continue;
+                    }
+                };
+                // chunks fetched from the remote store are complete by definition
+                let fetched = local_path != std::path::Path::new(&file_path);
                 let extracted = extract_frame(
                     ffmpeg.clone(),
-                    file_path,
+                    local_path.to_string_lossy().to_string(),
+                    !fetched,
                     tasks,
                     frame_tx.clone(),
                     self.cache_tx.clone(),
@@ -645,11 +671,12 @@ impl FrameCache {
 async fn extract_frame(
     ffmpeg: PathBuf,
     video_file_path: String,
+    check_complete: bool,
     tasks: Vec<(FrameData, OCREntry)>,
     frame_tx: FrameChannel,
     cache_tx: mpsc::Sender<CacheMessage>,
 ) -> Result<usize> {
-    if !is_video_file_complete(&ffmpeg, &video_file_path).await? {
+    if check_complete && !is_video_file_complete(&ffmpeg, &video_file_path).await? {
         debug!("skipping incomplete video file: {}", video_file_path);
         return Ok(0);
     }
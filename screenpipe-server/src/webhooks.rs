@@ -0,0 +1,648 @@
+//! Webhook subscriptions: events from the bus POSTed to a url.
+//!
+//! Webhooks are stored in the database and picked by event name patterns and payload
+//! predicates (see [`EventFilter`]). Each webhook has its own queue and worker, so a slow
+//! endpoint only delays its own deliveries. Payloads are signed with the webhook secret,
+//! which is stored sealed with the pipe secrets key (see [`set_webhook_secrets_dir`]).
+//! Failed deliveries are retried with exponential backoff and end up in the dead-letter
+//! table once the attempts run out. Deliveries and dead letters are pruned by count and age.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use hmac::{Hmac, Mac};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE, USER_AGENT};
+use reqwest::{Client, StatusCode, Url};
+use screenpipe_core::{open_secret, seal_secret};
+use screenpipe_events::{subscribe_to_all_events, Event, EventFilter};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::store::Store;
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub const SIGNATURE_HEADER: &str = "x-screenpipe-signature";
+pub const TIMESTAMP_HEADER: &str = "x-screenpipe-timestamp";
+pub const EVENT_HEADER: &str = "x-screenpipe-event";
+pub const DELIVERY_HEADER: &str = "x-screenpipe-delivery";
+
+/// Bumped whenever webhooks change, running dispatchers reload them on their next event.
+static WEBHOOKS_VERSION: AtomicU64 = AtomicU64::new(0);
+
+/// Screenpipe directory whose pipe secrets key seals webhook secrets.
+static SECRETS_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Seals webhook secrets with the pipe secrets key of `screenpipe_dir`. Until it is set
+/// webhooks with a secret can't be stored or signed.
+pub fn set_webhook_secrets_dir(screenpipe_dir: PathBuf) {
+    let _ = SECRETS_DIR.set(screenpipe_dir);
+}
+
+/// Makes running dispatchers reload their webhooks, to call after changing them in the store.
+pub fn webhooks_changed() {
+    WEBHOOKS_VERSION.fetch_add(1, Ordering::SeqCst);
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Webhook {
+    pub id: i64,
+    pub url: String,
+    /// Event name patterns (`*` and `?` globs), empty for every event.
+    pub events: Vec<String>,
+    /// Predicates on the event data, like `app_name == "Slack"`.
+    pub filter: Option<String>,
+    /// Key payloads are signed with, never sent back by the api.
+    #[serde(skip_serializing, default)]
+    pub secret: Option<String>,
+    pub headers: BTreeMap<String, String>,
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A webhook as created or updated through the api.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct WebhookSpec {
+    pub url: String,
+    #[serde(default)]
+    pub events: Vec<String>,
+    #[serde(default)]
+    pub filter: Option<String>,
+    #[serde(default)]
+    pub secret: Option<String>,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl WebhookSpec {
+    /// Fails on a url that isn't http(s), a filter that doesn't parse or a bad header.
+    pub fn validate(&self) -> Result<()> {
+        let url = Url::parse(&self.url)
+            .map_err(|e| anyhow::anyhow!("invalid url {}: {}", self.url, e))?;
+        if url.scheme() != "http" && url.scheme() != "https" {
+            anyhow::bail!("webhook url must be http or https, got {}", url.scheme());
+        }
+        EventFilter::new(self.events.clone(), self.filter.as_deref())?;
+        for (name, value) in &self.headers {
+            HeaderName::from_str(name)
+                .map_err(|_| anyhow::anyhow!("invalid header name: {}", name))?;
+            HeaderValue::from_str(value)
+                .map_err(|_| anyhow::anyhow!("invalid value for header {}", name))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    Delivered,
+    /// Every attempt failed, the payload is in the dead-letter table.
+    DeadLettered,
+}
+
+impl DeliveryStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryStatus::Delivered => "delivered",
+            DeliveryStatus::DeadLettered => "dead_lettered",
+        }
+    }
+}
+
+impl FromStr for DeliveryStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "delivered" => Ok(DeliveryStatus::Delivered),
+            "dead_lettered" => Ok(DeliveryStatus::DeadLettered),
+            _ => Err(anyhow::anyhow!("unknown webhook delivery status: {}", s)),
+        }
+    }
+}
+
+/// Outcome of handing one event to a webhook.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct WebhookDelivery {
+    /// Set once stored.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    pub webhook_id: i64,
+    pub event: String,
+    /// Offset of the event in the event log, when it is enabled.
+    pub event_offset: Option<u64>,
+    pub status: DeliveryStatus,
+    pub attempts: u32,
+    /// Status code of the last response.
+    pub response_status: Option<u16>,
+    /// Why the last attempt failed.
+    pub error: Option<String>,
+    pub duration_ms: i64,
+    pub delivered_at: DateTime<Utc>,
+}
+
+/// A payload that couldn't be delivered, kept to be sent again.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct WebhookDeadLetter {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    pub webhook_id: i64,
+    pub event: String,
+    pub event_offset: Option<u64>,
+    /// The body as it was sent.
+    pub payload: Value,
+    pub attempts: u32,
+    pub error: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug)]
+pub struct WebhookConfig {
+    /// Attempts per event, the first one included.
+    pub max_attempts: u32,
+    /// Wait before the second attempt, doubled after each failure.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub request_timeout: Duration,
+    /// Events waiting per webhook, further ones are dropped and counted in one delivery.
+    pub queue_size: usize,
+    /// Deliveries and dead letters kept per webhook, older ones are pruned.
+    pub kept_history: i64,
+    /// Deliveries and dead letters older than this are pruned.
+    pub history_max_age: Duration,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            request_timeout: Duration::from_secs(10),
+            queue_size: 1000,
+            kept_history: 1000,
+            history_max_age: Duration::from_secs(30 * 24 * 60 * 60),
+        }
+    }
+}
+
+/// How often deliveries and dead letters are pruned.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Replaces the secret of `spec` with its sealed value, before it is stored. Fails when
+/// there is a secret but no secrets dir was set, see [`set_webhook_secrets_dir`].
+pub async fn seal_webhook_secret(spec: &mut WebhookSpec) -> Result<()> {
+    let Some(secret) = spec.secret.as_mut() else {
+        return Ok(());
+    };
+    let dir = SECRETS_DIR
+        .get()
+        .ok_or_else(|| anyhow::anyhow!("no secrets key to seal the webhook secret with"))?;
+    *secret = seal_secret(dir, secret).await?;
+    Ok(())
+}
+
+/// `sha256=<hex>` HMAC of `<timestamp>.<body>` with the webhook secret, the value of the
+/// `x-screenpipe-signature` header.
+pub fn sign_payload(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("hmac accepts keys of any size");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    let signature = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+    format!("sha256={}", signature)
+}
+
+/// Body POSTed for `event`, its `id` is the same on every attempt.
+fn payload_for(event: &Event) -> Value {
+    json!({
+        "id": uuid::Uuid::new_v4().to_string(),
+        "event": event.name,
+        "data": event.data,
+        "offset": event.offset,
+        "timestamp": Utc::now(),
+    })
+}
+
+/// Why an attempt failed, and whether another one could succeed.
+struct AttemptError {
+    status: Option<StatusCode>,
+    message: String,
+    retryable: bool,
+}
+
+struct Worker {
+    webhook: Webhook,
+    filter: EventFilter,
+    queue: mpsc::Sender<Event>,
+    /// Events dropped because the queue was full, recorded by the worker.
+    dropped: Arc<AtomicU64>,
+}
+
+/// Cheap to clone, clones share the store, config and http client.
+#[derive(Clone)]
+pub struct WebhookDispatcher {
+    store: Arc<dyn Store>,
+    config: WebhookConfig,
+    client: Client,
+}
+
+impl WebhookDispatcher {
+    pub fn new(store: Arc<dyn Store>, config: WebhookConfig) -> Result<Self> {
+        let client = Client::builder()
+            .timeout(config.request_timeout)
+            .connect_timeout(Duration::from_secs(5))
+            .build()?;
+        Ok(Self {
+            store,
+            config,
+            client,
+        })
+    }
+
+    /// Consumes the event bus until the task is aborted. Events sent after this returns
+    /// are seen.
+    pub fn start(self) -> JoinHandle<()> {
+        let mut events = subscribe_to_all_events();
+        let dispatcher = Arc::new(self);
+        let pruner = dispatcher.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+            loop {
+                interval.tick().await;
+                pruner.prune_history().await;
+            }
+        });
+        tokio::spawn(async move {
+            let mut workers: HashMap<i64, Worker> = HashMap::new();
+            let mut loaded_version = None;
+            while let Some(mut event) = events.next().await {
+                let version = WEBHOOKS_VERSION.load(Ordering::SeqCst);
+                if loaded_version != Some(version) {
+                    match dispatcher.store.list_webhooks().await {
+                        Ok(webhooks) => {
+                            dispatcher.sync_workers(&mut workers, webhooks);
+                            loaded_version = Some(version);
+                        }
+                        // tried again on the next event
+                        Err(e) => warn!("failed to load webhooks: {}", e),
+                    }
+                }
+                if workers.is_empty() {
+                    continue;
+                }
+
+                // screenshots are far too big to post around
+                if event.name == "ocr_result" || event.name == "ui_frame" {
+                    if let Some(data) = event.data.as_object_mut() {
+                        data.remove("image");
+                    }
+                }
+                for worker in workers.values() {
+                    if !worker.filter.matches(&event) {
+                        continue;
+                    }
+                    match worker.queue.try_send(event.clone()) {
+                        Ok(()) => {}
+                        Err(mpsc::error::TrySendError::Full(_)) => {
+                            worker.dropped.fetch_add(1, Ordering::Relaxed);
+                        }
+                        Err(mpsc::error::TrySendError::Closed(_)) => {}
+                    }
+                }
+            }
+        })
+    }
+
+    /// Starts workers for new or changed webhooks, dropping the queue of removed ones lets
+    /// their worker finish what is queued and exit.
+    fn sync_workers(self: &Arc<Self>, workers: &mut HashMap<i64, Worker>, webhooks: Vec<Webhook>) {
+        let webhooks: HashMap<i64, Webhook> = webhooks
+            .into_iter()
+            .filter(|w| w.enabled)
+            .map(|w| (w.id, w))
+            .collect();
+        workers.retain(|id, worker| webhooks.get(id) == Some(&worker.webhook));
+
+        for (id, webhook) in webhooks {
+            if workers.contains_key(&id) {
+                continue;
+            }
+            let filter = match EventFilter::new(webhook.events.clone(), webhook.filter.as_deref()) {
+                Ok(filter) => filter,
+                Err(e) => {
+                    warn!("webhook {} has an invalid filter, skipped: {}", id, e);
+                    continue;
+                }
+            };
+            let (queue, mut rx) = mpsc::channel::<Event>(self.config.queue_size.max(1));
+            let dropped = Arc::new(AtomicU64::new(0));
+            let dispatcher = self.clone();
+            let worker_webhook = webhook.clone();
+            let worker_dropped = dropped.clone();
+            tokio::spawn(async move {
+                while let Some(event) = rx.recv().await {
+                    dispatcher.deliver(&worker_webhook, &event).await;
+                    let dropped = worker_dropped.swap(0, Ordering::Relaxed);
+                    if dropped > 0 {
+                        dispatcher.record_dropped(worker_webhook.id, dropped).await;
+                    }
+                }
+                debug!("webhook {} worker stopped", worker_webhook.id);
+            });
+            debug!("webhook {} worker started for {}", id, webhook.url);
+            workers.insert(
+                id,
+                Worker {
+                    webhook,
+                    filter,
+                    queue,
+                    dropped,
+                },
+            );
+        }
+    }
+
+    /// Posts `event` to `webhook` with retries, recording the delivery and dead-lettering
+    /// it if every attempt fails.
+    pub async fn deliver(&self, webhook: &Webhook, event: &Event) {
+        let payload = payload_for(event);
+        let started = Instant::now();
+        let mut backoff = self.config.initial_backoff;
+        let max_attempts = self.config.max_attempts.max(1);
+
+        for attempt in 1..=max_attempts {
+            match send(&self.client, webhook, &event.name, &payload).await {
+                Ok(status) => {
+                    self.record(WebhookDelivery {
+                        id: None,
+                        webhook_id: webhook.id,
+                        event: event.name.clone(),
+                        event_offset: event.offset,
+                        status: DeliveryStatus::Delivered,
+                        attempts: attempt,
+                        response_status: Some(status.as_u16()),
+                        error: None,
+                        duration_ms: started.elapsed().as_millis() as i64,
+                        delivered_at: Utc::now(),
+                    })
+                    .await;
+                    return;
+                }
+                Err(e) if e.retryable && attempt < max_attempts => {
+                    debug!(
+                        "webhook {} attempt {} failed, retrying in {:?}: {}",
+                        webhook.id, attempt, backoff, e.message
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(self.config.max_backoff);
+                }
+                Err(e) => {
+                    self.record_failure(
+                        webhook.id,
+                        event,
+                        payload,
+                        attempt,
+                        e.status,
+                        e.message,
+                        started.elapsed(),
+                    )
+                    .await;
+                    return;
+                }
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn record_failure(
+        &self,
+        webhook_id: i64,
+        event: &Event,
+        payload: Value,
+        attempts: u32,
+        status: Option<StatusCode>,
+        error: String,
+        elapsed: Duration,
+    ) {
+        warn!(
+            "webhook {} gave up on {} after {} attempts: {}",
+            webhook_id, event.name, attempts, error
+        );
+        let letter = WebhookDeadLetter {
+            id: None,
+            webhook_id,
+            event: event.name.clone(),
+            event_offset: event.offset,
+            payload,
+            attempts,
+            error: error.clone(),
+            created_at: Utc::now(),
+        };
+        if let Err(e) = self.store.insert_webhook_dead_letter(&letter).await {
+            warn!(
+                "failed to store dead letter of webhook {}: {}",
+                webhook_id, e
+            );
+        }
+        self.record(WebhookDelivery {
+            id: None,
+            webhook_id,
+            event: event.name.clone(),
+            event_offset: event.offset,
+            status: DeliveryStatus::DeadLettered,
+            attempts,
+            response_status: status.map(|s| s.as_u16()),
+            error: Some(error),
+            duration_ms: elapsed.as_millis() as i64,
+            delivered_at: Utc::now(),
+        })
+        .await;
+    }
+
+    /// One delivery for the `count` events dropped while the queue of the webhook was full.
+    async fn record_dropped(&self, webhook_id: i64, count: u64) {
+        warn!(
+            "webhook {} queue was full, {} events dropped",
+            webhook_id, count
+        );
+        self.record(WebhookDelivery {
+            id: None,
+            webhook_id,
+            event: "dropped".to_string(),
+            event_offset: None,
+            status: DeliveryStatus::DeadLettered,
+            attempts: 0,
+            response_status: None,
+            error: Some(format!("webhook queue is full, {} events dropped", count)),
+            duration_ms: 0,
+            delivered_at: Utc::now(),
+        })
+        .await;
+    }
+
+    async fn prune_history(&self) {
+        let before = chrono::Duration::from_std(self.config.history_max_age)
+            .ok()
+            .and_then(|max_age| Utc::now().checked_sub_signed(max_age))
+            .unwrap_or(DateTime::<Utc>::MIN_UTC);
+        match self
+            .store
+            .prune_webhook_history(self.config.kept_history, before)
+            .await
+        {
+            Ok(0) => {}
+            Ok(pruned) => debug!("pruned {} webhook deliveries and dead letters", pruned),
+            Err(e) => warn!("failed to prune webhook history: {}", e),
+        }
+    }
+
+    async fn record(&self, delivery: WebhookDelivery) {
+        if let Err(e) = self.store.insert_webhook_delivery(&delivery).await {
+            warn!(
+                "failed to store delivery of webhook {}: {}",
+                delivery.webhook_id, e
+            );
+        }
+    }
+}
+
+/// Sends a dead letter once more, removing it when it goes through.
+pub async fn redeliver_dead_letter(
+    store: &dyn Store,
+    webhook: &Webhook,
+    letter: &WebhookDeadLetter,
+) -> Result<WebhookDelivery> {
+    let client = Client::builder()
+        .timeout(WebhookConfig::default().request_timeout)
+        .build()?;
+    let started = Instant::now();
+    let result = send(&client, webhook, &letter.event, &letter.payload).await;
+    let delivery = WebhookDelivery {
+        id: None,
+        webhook_id: webhook.id,
+        event: letter.event.clone(),
+        event_offset: letter.event_offset,
+        status: if result.is_ok() {
+            DeliveryStatus::Delivered
+        } else {
+            DeliveryStatus::DeadLettered
+        },
+        attempts: letter.attempts + 1,
+        response_status: match &result {
+            Ok(status) => Some(status.as_u16()),
+            Err(e) => e.status.map(|s| s.as_u16()),
+        },
+        error: result.as_ref().err().map(|e| e.message.clone()),
+        duration_ms: started.elapsed().as_millis() as i64,
+        delivered_at: Utc::now(),
+    };
+    let id = store.insert_webhook_delivery(&delivery).await?;
+    if let (Ok(_), Some(letter_id)) = (&result, letter.id) {
+        store.delete_webhook_dead_letter(letter_id).await?;
+        info!(
+            "dead letter {} of webhook {} delivered",
+            letter_id, webhook.id
+        );
+    }
+    Ok(WebhookDelivery {
+        id: Some(id),
+        ..delivery
+    })
+}
+
+async fn send(
+    client: &Client,
+    webhook: &Webhook,
+    event: &str,
+    payload: &Value,
+) -> Result<StatusCode, AttemptError> {
+    let body = serde_json::to_vec(payload).map_err(|e| AttemptError {
+        status: None,
+        message: e.to_string(),
+        retryable: false,
+    })?;
+    let timestamp = Utc::now().timestamp();
+
+    let mut headers = HeaderMap::new();
+    for (name, value) in &webhook.headers {
+        if let (Ok(name), Ok(value)) = (HeaderName::from_str(name), HeaderValue::from_str(value)) {
+            headers.insert(name, value);
+        }
+    }
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    headers.insert(USER_AGENT, HeaderValue::from_static("screenpipe-webhooks"));
+    if let Ok(value) = HeaderValue::from_str(event) {
+        headers.insert(EVENT_HEADER, value);
+    }
+    if let Some(Ok(value)) = payload["id"].as_str().map(HeaderValue::from_str) {
+        headers.insert(DELIVERY_HEADER, value);
+    }
+    headers.insert(TIMESTAMP_HEADER, HeaderValue::from(timestamp));
+    if let Some(secret) = webhook.secret.as_deref().filter(|s| !s.is_empty()) {
+        let secret = match SECRETS_DIR.get() {
+            Some(dir) => open_secret(dir, secret).await.map_err(|e| e.to_string()),
+            None => Err("no secrets key to open the webhook secret with".to_string()),
+        }
+        .map_err(|message| AttemptError {
+            status: None,
+            message,
+            retryable: false,
+        })?;
+        let signature = sign_payload(&secret, timestamp, &body);
+        if let Ok(value) = HeaderValue::from_str(&signature) {
+            headers.insert(SIGNATURE_HEADER, value);
+        }
+    }
+
+    let response = client
+        .post(&webhook.url)
+        .headers(headers)
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| AttemptError {
+            status: None,
+            message: e.to_string(),
+            retryable: true,
+        })?;
+
+    let status = response.status();
+    if status.is_success() {
+        return Ok(status);
+    }
+    let text = response.text().await.unwrap_or_default();
+    Err(AttemptError {
+        status: Some(status),
+        message: format!("{}: {}", status, text.chars().take(500).collect::<String>()),
+        // other client errors won't go away by sending the same payload again
+        retryable: status.is_server_error()
+            || status == StatusCode::TOO_MANY_REQUESTS
+            || status == StatusCode::REQUEST_TIMEOUT,
+    })
+}
@@ -0,0 +1,173 @@
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Duration, Utc};
+    use screenpipe_audio::{AudioDevice, DeviceType};
+    use screenpipe_server::conversations::{
+        segment, segment_transcriptions, ConversationConfig, Utterance,
+    };
+    use screenpipe_server::DatabaseManager;
+
+    fn utterance(
+        id: i64,
+        start_time: DateTime<Utc>,
+        seconds: i64,
+        device: &str,
+        speaker_id: Option<i64>,
+        text: &str,
+    ) -> Utterance {
+        Utterance {
+            transcription_id: id,
+            start_time,
+            end_time: start_time + Duration::seconds(seconds),
+            device_name: device.to_string(),
+            output: device == "speakers",
+            speaker_id,
+            speaker_name: speaker_id.map(|id| format!("person {}", id)),
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_segment_dedupes_devices_and_splits_on_silence() {
+        let start = Utc::now() - Duration::hours(1);
+        let at = |seconds: i64| start + Duration::seconds(seconds);
+        let conversations = segment(
+            vec![
+                utterance(
+                    1,
+                    at(0),
+                    10,
+                    "speakers",
+                    Some(1),
+                    "can everyone hear me okay",
+                ),
+                // the mic picking up the speakers
+                utterance(2, at(2), 10, "mic", None, "can everyone hear me ok"),
+                utterance(3, at(12), 5, "mic", Some(2), "yes loud and clear"),
+                // the next chunk repeats the end of the previous one
+                utterance(4, at(17), 8, "mic", Some(2), "and clear let's get started"),
+                utterance(5, at(30), 10, "speakers", Some(1), "first the budget"),
+                // ten minutes later
+                utterance(6, at(630), 5, "mic", Some(2), "did you see the game"),
+            ],
+            &ConversationConfig::default(),
+        );
+
+        assert_eq!(conversations.len(), 2);
+        let first = &conversations[0];
+        assert_eq!(first.start_time, at(0));
+        assert_eq!(first.end_time, at(40));
+        assert_eq!(first.duplicates, vec![2]);
+        assert_eq!(
+            first.transcript(),
+            "person 1: can everyone hear me okay\n\
+             person 2: yes loud and clear let's get started\n\
+             person 1: first the budget"
+        );
+        assert_eq!(first.turns[1].transcription_ids, vec![3, 4]);
+        assert_eq!(first.devices(), vec!["mic", "speakers"]);
+        assert_eq!(first.speakers(), vec!["person 1", "person 2"]);
+
+        assert_eq!(conversations[1].turns.len(), 1);
+        assert_eq!(
+            conversations[1].transcription_ids().collect::<Vec<_>>(),
+            vec![6]
+        );
+    }
+
+    #[test]
+    fn test_segment_keeps_short_replies_on_other_devices() {
+        let start = Utc::now() - Duration::hours(1);
+        let conversations = segment(
+            vec![
+                utterance(1, start, 2, "speakers", None, "yes"),
+                utterance(2, start + Duration::seconds(1), 2, "mic", None, "no"),
+                utterance(3, start + Duration::seconds(2), 2, "mic", None, "Yes."),
+            ],
+            &ConversationConfig::default(),
+        );
+        assert_eq!(conversations.len(), 1);
+        assert_eq!(conversations[0].duplicates, vec![3]);
+        assert_eq!(conversations[0].transcript(), "speakers: yes\nmic: no");
+    }
+
+    #[tokio::test]
+    async fn test_segment_transcriptions_stores_searchable_conversations() {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+        let speakers = AudioDevice::new("conversation_speakers".to_string(), DeviceType::Output);
+        let mic = AudioDevice::new("conversation_mic".to_string(), DeviceType::Input);
+        for (chunk, device, text) in [
+            (
+                "conversation_output.mp4",
+                &speakers,
+                "let's move the planning meeting to thursday",
+            ),
+            (
+                "conversation_mic.mp4",
+                &mic,
+                "let's move the planning meeting to thursday",
+            ),
+            ("conversation_mic_2.mp4", &mic, "sounds good to me"),
+        ] {
+            let chunk_id = db.insert_audio_chunk(chunk).await.unwrap();
+            db.insert_audio_transcription(chunk_id, text, 0, "Whisper", device, None, None, None)
+                .await
+                .unwrap();
+        }
+
+        let config = ConversationConfig::default();
+        let since = Utc::now() - Duration::hours(1);
+        // still going on
+        let (ids, next_since) = segment_transcriptions(&db, &config, since, Utc::now())
+            .await
+            .unwrap();
+        assert!(ids.is_empty());
+        assert!(next_since > since);
+
+        let later = Utc::now() + Duration::minutes(10);
+        let (ids, _) = segment_transcriptions(&db, &config, since, later)
+            .await
+            .unwrap();
+        assert_eq!(ids.len(), 1);
+        let conversation = db.get_conversation(ids[0]).await.unwrap().unwrap();
+        assert_eq!(
+            conversation.transcript,
+            "conversation_speakers: let's move the planning meeting to thursday\n\
+             conversation_mic: sounds good to me"
+        );
+        assert_eq!(conversation.turn_count, 2);
+        let turns = db.get_conversation_turns(ids[0]).await.unwrap();
+        assert!(turns[0].output);
+        assert_eq!(turns[1].text, "sounds good to me");
+
+        // the duplicate is segmented too, nothing is left
+        let (ids_again, _) = segment_transcriptions(&db, &config, since, later)
+            .await
+            .unwrap();
+        assert!(ids_again.is_empty());
+
+        let found = db
+            .search_conversations("planning", None, None, 10, 0)
+            .await
+            .unwrap();
+        assert_eq!(found.len(), 1);
+        assert!(db
+            .search_conversations("budget", None, None, 10, 0)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(db
+            .search_conversations("", Some(later), None, 10, 0)
+            .await
+            .unwrap()
+            .is_empty());
+
+        // deleted conversations are segmented again
+        assert!(db.delete_conversation(ids[0]).await.unwrap());
+        assert!(!db.delete_conversation(ids[0]).await.unwrap());
+        let (ids, _) = segment_transcriptions(&db, &config, since, later)
+            .await
+            .unwrap();
+        assert_eq!(ids.len(), 1);
+    }
+}
@@ -5,6 +5,7 @@ mod tests {
     use chrono::Utc;
     use screenpipe_audio::{AudioDevice, DeviceType};
     use screenpipe_server::{
+        db_maintenance::{run_maintenance_task, MaintenanceTask},
         db_types::{ContentType, SearchResult},
         DatabaseManager,
     };
@@ -765,6 +766,51 @@ mod tests {
         assert_eq!(unnamed_speakers[2].id, 1);
     }
 
+    #[tokio::test]
+    async fn test_get_unnamed_speakers_page_with_cursor() {
+        let db = setup_test_db().await;
+
+        // speakers 2 and 3 tie on transcription count, as do 1 and 4
+        for (n, count) in [1, 2, 2, 1].into_iter().enumerate() {
+            let speaker = db.insert_speaker(&vec![n as f32; 512]).await.unwrap();
+            for i in 0..count {
+                let audio_chunk_id = db
+                    .insert_audio_chunk(&format!("audio{}{}", n, i))
+                    .await
+                    .unwrap();
+                db.insert_audio_transcription(
+                    audio_chunk_id,
+                    "test transcription",
+                    0,
+                    "",
+                    &AudioDevice::new("test".to_string(), DeviceType::Output),
+                    Some(speaker.id),
+                    None,
+                    None,
+                )
+                .await
+                .unwrap();
+            }
+        }
+
+        let mut ids = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = db
+                .get_unnamed_speakers_page(1, 0, cursor.as_ref(), None)
+                .await
+                .unwrap();
+            let Some(last) = page.last() else {
+                break;
+            };
+            cursor = Some(last.cursor());
+            ids.extend(page.iter().map(|unnamed| unnamed.speaker.id));
+        }
+
+        // most transcriptions first, ties broken by the newest speaker
+        assert_eq!(ids, vec![3, 2, 4, 1]);
+    }
+
     #[tokio::test]
     async fn test_merge_speakers() {
         let db = setup_test_db().await;
@@ -1041,4 +1087,100 @@ mod tests {
 
         assert_eq!(count, 2, "Should count both matching frames");
     }
+
+    #[tokio::test]
+    async fn test_concurrent_frame_inserts_are_batched() {
+        let db = Arc::new(setup_test_db().await);
+        db.insert_video_chunk("test_video.mp4", "test_device")
+            .await
+            .unwrap();
+
+        let handles: Vec<_> = (0..50)
+            .map(|i| {
+                let db = db.clone();
+                tokio::spawn(async move {
+                    let frame_id = db.insert_frame("test_device", None).await.unwrap();
+                    db.insert_ocr_text(
+                        frame_id,
+                        &format!("frame {}", i),
+                        "",
+                        "test",
+                        "",
+                        Arc::new(OcrEngine::Tesseract),
+                        false,
+                    )
+                    .await
+                    .unwrap();
+                    frame_id
+                })
+            })
+            .collect();
+
+        let mut frame_ids = Vec::new();
+        for handle in handles {
+            frame_ids.push(handle.await.unwrap());
+        }
+        frame_ids.sort();
+        frame_ids.dedup();
+        assert_eq!(frame_ids.len(), 50);
+
+        let offsets: Vec<i64> =
+            sqlx::query_scalar("SELECT offset_index FROM frames ORDER BY offset_index ASC")
+                .fetch_all(&db.pool)
+                .await
+                .unwrap();
+        assert_eq!(offsets, (0..50).collect::<Vec<i64>>());
+
+        db.insert_ui_monitoring("ui text", "test", "window", Utc::now())
+            .await
+            .unwrap();
+
+        let metrics = db.writer_metrics();
+        // 1 chunk + 50 frames + 50 ocr rows + 1 ui row
+        assert_eq!(metrics.writes, 102);
+        assert_eq!(metrics.failed_writes, 0);
+        assert!(metrics.batches <= metrics.writes);
+    }
+
+    #[tokio::test]
+    async fn test_maintenance_tasks_run_and_are_recorded() {
+        let db = setup_test_db().await;
+        let _ = db
+            .insert_video_chunk("test_video.mp4", "test_device")
+            .await
+            .unwrap();
+        let frame_id = db.insert_frame("test_device", None).await.unwrap();
+        db.insert_ocr_text(
+            frame_id,
+            "maintenance test",
+            "",
+            "test",
+            "",
+            Arc::new(OcrEngine::Tesseract),
+            false,
+        )
+        .await
+        .unwrap();
+
+        for task in MaintenanceTask::ALL {
+            let run = run_maintenance_task(&db, task).await.unwrap();
+            assert!(run.success, "{} failed: {:?}", run.task, run.details);
+        }
+        // running a task again only keeps the latest run per task in the summary
+        run_maintenance_task(&db, MaintenanceTask::Optimize)
+            .await
+            .unwrap();
+
+        let runs = db.get_latest_maintenance_runs().await.unwrap();
+        assert_eq!(runs.len(), MaintenanceTask::ALL.len());
+        assert!(runs.iter().all(|r| r.success));
+
+        // only the older optimize run goes
+        assert_eq!(db.prune_maintenance_runs(1).await.unwrap(), 1);
+        assert_eq!(db.prune_maintenance_runs(1).await.unwrap(), 0);
+        assert_eq!(
+            db.get_latest_maintenance_runs().await.unwrap().len(),
+            MaintenanceTask::ALL.len()
+        );
+    }
 }
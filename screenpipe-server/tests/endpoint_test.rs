@@ -8,12 +8,15 @@ mod tests {
     use chrono::{Duration, Utc};
     use lru::LruCache;
     use screenpipe_audio::{AudioDevice, DeviceType};
+    use screenpipe_core::{set_secrets_key_source, SecretsKeySource};
     use screenpipe_server::db_types::ContentType;
     use screenpipe_server::db_types::SearchResult;
     use screenpipe_server::video_cache::FrameCache;
+    use screenpipe_server::webhooks::set_webhook_secrets_dir;
     use screenpipe_server::PipeManager;
     use screenpipe_server::{
-        create_router, AppState, ContentItem, DatabaseManager, PaginatedResponse,
+        create_router, AppState, ContentItem, DatabaseManager, MediaStorage, PaginatedResponse,
+        Store,
     };
     use screenpipe_vision::OcrEngine; // Adjust this import based on your actual module structure
     use serde::Deserialize;
@@ -29,6 +32,12 @@ mod tests {
         error: String,
     }
     async fn setup_test_app() -> (Router, Arc<AppState>) {
+        let (app, state, _) = setup_test_app_with_db().await;
+        (app, state)
+    }
+
+    /// Also returns the sqlite database behind the app, for tests that write to it directly.
+    async fn setup_test_app_with_db() -> (Router, Arc<AppState>, Arc<DatabaseManager>) {
         let db = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
 
         let app_state = Arc::new(AppState {
@@ -39,18 +48,21 @@ mod tests {
             vision_disabled: false,
             audio_disabled: false,
             frame_cache: Some(Arc::new(
-                FrameCache::new(PathBuf::from(""), db).await.unwrap(),
+                FrameCache::new(PathBuf::from(""), db.clone())
+                    .await
+                    .unwrap(),
             )),
             ui_monitoring_enabled: false,
             frame_image_cache: Some(Arc::new(Mutex::new(LruCache::new(
                 NonZeroUsize::new(100).unwrap(),
             )))),
+            media_storage: Arc::new(MediaStorage::local(db.clone())),
         });
 
         let router = create_router();
         let app = router.with_state(app_state.clone());
 
-        (app, app_state)
+        (app, app_state, db)
     }
 
     #[tokio::test]
@@ -161,6 +173,90 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_search_cursor_pagination() {
+        let (app, state) = setup_test_app().await;
+        let db = &state.db;
+
+        for i in 0..3 {
+            let audio_chunk_id = db
+                .insert_audio_chunk(&format!("test_audio{}.wav", i))
+                .await
+                .unwrap();
+            db.insert_audio_transcription(
+                audio_chunk_id,
+                &format!("cursor transcription {}", i),
+                i,
+                "",
+                &AudioDevice::new("test".to_string(), DeviceType::Input),
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        }
+
+        let search = |uri: String| {
+            let app = app.clone();
+            async move {
+                let response = app
+                    .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                    .await
+                    .unwrap();
+                let status = response.status();
+                let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                (status, body)
+            }
+        };
+
+        let (status, body) = search("/search?content_type=audio&limit=2&count=none".into()).await;
+        assert_eq!(status, StatusCode::OK);
+        let first: PaginatedResponse<ContentItem> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(first.data.len(), 2);
+        assert_eq!(first.pagination.total, -1);
+        let next_cursor = first
+            .pagination
+            .next_cursor
+            .expect("first page has a next cursor");
+
+        let (status, body) = search(format!(
+            "/search?content_type=audio&limit=2&count=approximate&cursor={}",
+            next_cursor
+        ))
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let second: PaginatedResponse<ContentItem> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(second.data.len(), 1);
+        assert_eq!(second.pagination.total, 3);
+        assert!(!second.pagination.total_approximate);
+        assert!(second.pagination.next_cursor.is_none());
+
+        let chunk_offsets = |page: &PaginatedResponse<ContentItem>| -> Vec<i64> {
+            page.data
+                .iter()
+                .map(|item| match item {
+                    ContentItem::Audio(audio) => audio.offset_index,
+                    _ => panic!("Expected audio item"),
+                })
+                .collect()
+        };
+        let mut seen = chunk_offsets(&first);
+        seen.extend(chunk_offsets(&second));
+        seen.sort();
+        assert_eq!(seen, vec![0, 1, 2]);
+
+        let (status, _) = search("/search?content_type=audio&cursor=garbage".into()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _) = search(format!(
+            "/search?content_type=audio&offset=1&cursor={}",
+            next_cursor
+        ))
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
     #[tokio::test]
     #[ignore]
     async fn test_count_search_results() {
@@ -355,8 +451,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_search_with_time_constraints() {
-        let (_, state) = setup_test_app().await;
-        let db = &state.db;
+        let (_, _, db) = setup_test_app_with_db().await;
 
         // insert test data with different timestamps
         let _ = db
@@ -538,8 +633,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_recent_tasks_no_bleeding() {
-        let (_, state) = setup_test_app().await;
-        let db = &state.db;
+        let (_, _, db) = setup_test_app_with_db().await;
 
         // Setup test data with different timestamps
         let now = Utc::now();
@@ -732,4 +826,161 @@ mod tests {
             }
         }
     }
+
+    #[tokio::test]
+    async fn test_event_schemas() {
+        let (app, _) = setup_test_app().await;
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/events/schema")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let schemas: Vec<serde_json::Value> = serde_json::from_slice(&body).unwrap();
+        let meeting = schemas
+            .iter()
+            .find(|s| s["name"] == "meeting_started")
+            .unwrap();
+        assert_eq!(meeting["version"], 1);
+        assert_eq!(meeting["schema"]["type"], "object");
+    }
+
+    #[tokio::test]
+    async fn test_webhooks_endpoints() {
+        let (app, _) = setup_test_app().await;
+        // secrets are sealed before they are stored
+        let secrets_dir = tempfile::tempdir().unwrap();
+        set_secrets_key_source(SecretsKeySource::File);
+        set_webhook_secrets_dir(secrets_dir.path().to_path_buf());
+        let post_webhook = |body: serde_json::Value| {
+            Request::builder()
+                .method("POST")
+                .uri("/webhooks")
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+
+        let response = app
+            .clone()
+            .oneshot(post_webhook(serde_json::json!({ "url": "not a url" })))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = app
+            .clone()
+            .oneshot(post_webhook(serde_json::json!({
+                "url": "http://localhost:9999/hook",
+                "events": ["meeting_*"],
+                "secret": "s3cret",
+            })))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let created: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let id = created["data"]["id"].as_i64().unwrap();
+        assert!(created["data"].get("secret").is_none());
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/webhooks/{}/deliveries", id))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("DELETE")
+                    .uri(format!("/webhooks/{}", id))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/webhooks/{}", id))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_rules_endpoints() {
+        let (app, _) = setup_test_app().await;
+        let post_rule = |uri: &str, body: &str| {
+            Request::builder()
+                .method("POST")
+                .uri(uri)
+                .header("content-type", "application/yaml")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        let rule = "name: focus\nwhen:\n  app: slack\nactions:\n  - type: pause_capture\n    seconds: 60\n";
+
+        let response = app
+            .clone()
+            .oneshot(post_rule("/rules", "name: nothing to do\nactions: []"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = app
+            .clone()
+            .oneshot(post_rule("/rules/dry-run", rule))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .clone()
+            .oneshot(post_rule("/rules", rule))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let created: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let id = created["data"]["id"].as_i64().unwrap();
+        assert_eq!(created["data"]["name"], "focus");
+        assert_eq!(created["data"]["actions"][0]["type"], "pause_capture");
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("DELETE")
+                    .uri(format!("/rules/{}", id))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .oneshot(post_rule(&format!("/rules/{}/dry-run", id), ""))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
 }
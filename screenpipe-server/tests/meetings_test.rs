@@ -0,0 +1,224 @@
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Duration, Utc};
+    use screenpipe_server::meetings::{
+        merge_meetings, screen_names, MeetingChange, MeetingDetector, MeetingDetectorConfig,
+        MeetingSignal, MeetingSpec, MeetingUpdate, Observation,
+    };
+    use screenpipe_server::DatabaseManager;
+
+    fn screen(timestamp: DateTime<Utc>, app: &str, window: &str, text: &str) -> Observation {
+        Observation::Screen {
+            timestamp,
+            app: app.to_string(),
+            window: window.to_string(),
+            text: text.to_string(),
+        }
+    }
+
+    fn speech(
+        timestamp: DateTime<Utc>,
+        output: bool,
+        speaker: Option<(i64, &str)>,
+        text: &str,
+    ) -> Observation {
+        Observation::Speech {
+            timestamp,
+            output,
+            speaker_id: speaker.map(|(id, _)| id),
+            speaker_name: speaker.map(|(_, name)| name.to_string()),
+            text: text.to_string(),
+        }
+    }
+
+    fn spec(start_time: DateTime<Utc>, minutes: i64, participants: &[&str]) -> MeetingSpec {
+        MeetingSpec {
+            start_time,
+            end_time: Some(start_time + Duration::minutes(minutes)),
+            app: "zoom.us".to_string(),
+            title: None,
+            participants: participants.iter().map(|p| p.to_string()).collect(),
+            signals: vec![MeetingSignal::Screen],
+        }
+    }
+
+    const ZOOM_FRAME: &str = "Mute\nStop Video\nAlice Martin (Host)\nBob Stone\nShare Screen";
+
+    #[test]
+    fn test_screen_names() {
+        assert_eq!(
+            screen_names("Alice Martin (Host)\nBob Stone\nMute\nShare Screen\nRecording...\nbob"),
+            vec!["Alice Martin".to_string(), "Bob Stone".to_string()]
+        );
+        assert!(screen_names("Participants (3)\nRaise Hand").is_empty());
+    }
+
+    #[test]
+    fn test_meeting_app_on_screen_starts_and_idle_ends_a_meeting() {
+        let mut detector = MeetingDetector::new(MeetingDetectorConfig::default());
+        let start = Utc::now() - Duration::hours(1);
+
+        // the meeting app alone isn't a meeting
+        assert!(detector
+            .observe(&screen(start, "zoom.us", "Zoom", "Home\nSchedule"))
+            .is_empty());
+        let changes = detector.observe(&screen(start, "zoom.us", "Zoom Meeting", ZOOM_FRAME));
+        let [MeetingChange::Started(started)] = changes.as_slice() else {
+            panic!("expected a start, got {:?}", changes);
+        };
+        assert_eq!(started.app, "zoom.us");
+        assert_eq!(started.title.as_deref(), Some("Zoom Meeting"));
+        assert!(detector.in_progress());
+
+        let later = start + Duration::minutes(1);
+        detector.observe(&screen(later, "zoom.us", "Zoom Meeting", ZOOM_FRAME));
+        detector.observe(&speech(later, true, Some((7, "Carol")), "sounds good"));
+        let changes = detector.tick(later);
+        let [MeetingChange::Updated(updated)] = changes.as_slice() else {
+            panic!("expected an update, got {:?}", changes);
+        };
+        assert_eq!(
+            updated.participants,
+            vec!["Alice Martin", "Bob Stone", "Carol"]
+        );
+        assert!(updated.signals.contains(&MeetingSignal::OutputSpeech));
+        assert!(detector.tick(later).is_empty());
+
+        let changes = detector.tick(later + Duration::minutes(10));
+        let [MeetingChange::Ended(ended)] = changes.as_slice() else {
+            panic!("expected an end, got {:?}", changes);
+        };
+        assert_eq!(ended.end_time, Some(later));
+        assert!(!detector.in_progress());
+    }
+
+    #[test]
+    fn test_conversation_starts_and_end_phrase_ends_a_meeting() {
+        let mut detector = MeetingDetector::new(MeetingDetectorConfig::default());
+        let start = Utc::now() - Duration::hours(1);
+
+        // someone talking in a video isn't a meeting
+        assert!(detector
+            .observe(&speech(start, true, None, "welcome to the channel"))
+            .is_empty());
+        let changes = detector.observe(&speech(
+            start + Duration::seconds(20),
+            false,
+            None,
+            "can you hear me",
+        ));
+        let [MeetingChange::Started(started)] = changes.as_slice() else {
+            panic!("expected a start, got {:?}", changes);
+        };
+        assert_eq!(started.app, "audio");
+        assert_eq!(started.start_time, start);
+        assert_eq!(
+            started.signals,
+            vec![MeetingSignal::OutputSpeech, MeetingSignal::MicActivity]
+        );
+
+        let end = start + Duration::minutes(2);
+        let changes = detector.observe(&screen(end, "Microsoft Teams", "Teams", "Call ended"));
+        let [MeetingChange::Ended(ended)] = changes.as_slice() else {
+            panic!("expected an end, got {:?}", changes);
+        };
+        assert_eq!(ended.end_time, Some(end));
+        // not right back into another one
+        assert!(detector
+            .observe(&speech(end, false, None, "bye"))
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_meeting_crud_links_content() {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+        db.insert_video_chunk("test_video.mp4", "test_device")
+            .await
+            .unwrap();
+        let start = Utc::now() - Duration::hours(2);
+        let mut frame_ids = Vec::new();
+        for minutes in [-5, 1, 20, 45] {
+            frame_ids.push(
+                db.insert_frame("test_device", Some(start + Duration::minutes(minutes)))
+                    .await
+                    .unwrap(),
+            );
+        }
+
+        let mut meeting_spec = spec(start, 30, &["Alice Martin"]);
+        let id = db.insert_meeting(&meeting_spec).await.unwrap();
+        let meeting = db.get_meeting(id).await.unwrap().unwrap();
+        assert_eq!(meeting.spec, meeting_spec);
+        let frames = meeting.frames.unwrap();
+        assert_eq!((frames.first, frames.last), (frame_ids[1], frame_ids[2]));
+        assert!(meeting.transcriptions.is_none());
+
+        MeetingUpdate {
+            end_time: Some(start + Duration::hours(1)),
+            title: Some("weekly sync".to_string()),
+            ..Default::default()
+        }
+        .apply(&mut meeting_spec)
+        .unwrap();
+        assert!(db.update_meeting(id, &meeting_spec).await.unwrap());
+        let meeting = db.get_meeting(id).await.unwrap().unwrap();
+        assert_eq!(meeting.spec.title.as_deref(), Some("weekly sync"));
+        assert_eq!(meeting.frames.unwrap().last, frame_ids[3]);
+
+        let in_range = db
+            .list_meetings(Some(start + Duration::minutes(50)), None, 10, 0)
+            .await
+            .unwrap();
+        assert_eq!(in_range.len(), 1);
+        let after = db
+            .list_meetings(Some(start + Duration::hours(2)), None, 10, 0)
+            .await
+            .unwrap();
+        assert!(after.is_empty());
+
+        assert!(MeetingUpdate {
+            end_time: Some(start - Duration::minutes(1)),
+            ..Default::default()
+        }
+        .apply(&mut meeting_spec)
+        .is_err());
+
+        assert!(db.delete_meeting(id).await.unwrap());
+        assert!(!db.delete_meeting(id).await.unwrap());
+        assert!(db.get_meeting(id).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_merge_meetings() {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+        let start = Utc::now() - Duration::hours(2);
+        let first = db
+            .insert_meeting(&spec(start, 10, &["Alice Martin"]))
+            .await
+            .unwrap();
+        let second = db
+            .insert_meeting(&spec(
+                start + Duration::minutes(12),
+                20,
+                &["Bob Stone", "Alice Martin"],
+            ))
+            .await
+            .unwrap();
+
+        assert!(merge_meetings(&db, &[first]).await.is_err());
+        assert!(merge_meetings(&db, &[first, second + 1])
+            .await
+            .unwrap()
+            .is_none());
+
+        let merged = merge_meetings(&db, &[second, first])
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(merged.id, first);
+        assert_eq!(merged.spec.start_time, start);
+        assert_eq!(merged.spec.end_time, Some(start + Duration::minutes(32)));
+        assert_eq!(merged.spec.participants, vec!["Alice Martin", "Bob Stone"]);
+        assert!(db.get_meeting(second).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,97 @@
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::Path;
+    use std::time::Duration;
+
+    use screenpipe_server::pipe_dev::{run_pipe_dev, PipeDevOptions};
+    use serde_json::json;
+
+    async fn count_runs(runs: &Path) -> usize {
+        tokio::fs::read_to_string(runs)
+            .await
+            .map(|runs| runs.lines().count())
+            .unwrap_or(0)
+    }
+
+    #[tokio::test]
+    async fn test_pipe_dev_restarts_on_changes() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let runs = temp_dir.path().join("runs");
+        let pipe_dir = temp_dir.path().join("my-pipe");
+        tokio::fs::create_dir_all(pipe_dir.join("src"))
+            .await
+            .unwrap();
+        tokio::fs::create_dir_all(pipe_dir.join("node_modules"))
+            .await
+            .unwrap();
+        // enabled is ignored in dev
+        tokio::fs::write(
+            pipe_dir.join("pipe.json"),
+            json!({ "enabled": false, "runtime": "executable" }).to_string(),
+        )
+        .await
+        .unwrap();
+        let main = pipe_dir.join("pipe");
+        tokio::fs::write(
+            &main,
+            format!(
+                "#!/bin/sh\necho \"$SCREENPIPE_SERVER_URL\" >> {}\nsleep 60\n",
+                runs.display()
+            ),
+        )
+        .await
+        .unwrap();
+        std::fs::set_permissions(&main, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let (stop_tx, stop_rx) = tokio::sync::oneshot::channel::<()>();
+        let dev = tokio::spawn(run_pipe_dev(
+            PipeDevOptions {
+                path: pipe_dir.clone(),
+                screenpipe_dir: temp_dir.path().join(".screenpipe"),
+                server_url: "http://localhost:3039".to_string(),
+                fixtures_port: None,
+            },
+            async {
+                let _ = stop_rx.await;
+            },
+        ));
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(count_runs(&runs).await, 1);
+
+        // installs and the pipe's own data don't restart it
+        tokio::fs::write(pipe_dir.join("node_modules").join("dep.js"), "")
+            .await
+            .unwrap();
+        tokio::fs::write(pipe_dir.join(".kv.json"), "{}")
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(count_runs(&runs).await, 1);
+
+        // changes close together restart it once
+        tokio::fs::write(pipe_dir.join("src").join("a.ts"), "")
+            .await
+            .unwrap();
+        tokio::fs::write(pipe_dir.join("src").join("b.ts"), "")
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        assert_eq!(count_runs(&runs).await, 2);
+        assert_eq!(
+            tokio::fs::read_to_string(&runs)
+                .await
+                .unwrap()
+                .lines()
+                .next(),
+            Some("http://localhost:3039")
+        );
+
+        stop_tx.send(()).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), dev)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+    }
+}
@@ -0,0 +1,106 @@
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use screenpipe_core::{RestartConfig, RestartPolicy};
+    use screenpipe_server::pipe_supervisor::{ExitReason, RestartDecision, RestartTracker};
+
+    fn config(policy: RestartPolicy) -> RestartConfig {
+        RestartConfig {
+            policy,
+            max_restarts: 3,
+            window_secs: 60,
+            initial_backoff_ms: 100,
+            max_backoff_ms: 1000,
+        }
+    }
+
+    const CRASH: ExitReason = ExitReason::Exited { code: Some(1) };
+    const CLEAN: ExitReason = ExitReason::Exited { code: Some(0) };
+
+    #[test]
+    fn test_restart_policies() {
+        let now = Instant::now();
+        let short = Duration::from_millis(10);
+
+        let mut never = RestartTracker::new(config(RestartPolicy::Never));
+        assert_eq!(never.on_exit(&CRASH, short, now), RestartDecision::Crashed);
+        assert_eq!(never.on_exit(&CLEAN, short, now), RestartDecision::Exited);
+
+        let mut on_failure = RestartTracker::new(config(RestartPolicy::OnFailure));
+        assert_eq!(
+            on_failure.on_exit(&CLEAN, short, now),
+            RestartDecision::Exited
+        );
+        assert!(matches!(
+            on_failure.on_exit(&CRASH, short, now),
+            RestartDecision::Restart(_)
+        ));
+        let unhealthy = ExitReason::Unhealthy {
+            error: "timeout".to_string(),
+        };
+        assert!(matches!(
+            on_failure.on_exit(&unhealthy, short, now),
+            RestartDecision::Restart(_)
+        ));
+
+        let mut always = RestartTracker::new(config(RestartPolicy::Always));
+        assert!(matches!(
+            always.on_exit(&CLEAN, short, now),
+            RestartDecision::Restart(_)
+        ));
+    }
+
+    #[test]
+    fn test_restart_backoff_and_window() {
+        let start = Instant::now();
+        let short = Duration::from_millis(10);
+        let mut tracker = RestartTracker::new(config(RestartPolicy::OnFailure));
+
+        // backoff doubles on every restart in a row
+        let delays: Vec<_> = (0..3)
+            .map(|i| tracker.on_exit(&CRASH, short, start + Duration::from_secs(i)))
+            .collect();
+        assert_eq!(
+            delays,
+            vec![
+                RestartDecision::Restart(Duration::from_millis(100)),
+                RestartDecision::Restart(Duration::from_millis(200)),
+                RestartDecision::Restart(Duration::from_millis(400)),
+            ]
+        );
+
+        // out of restarts within the window
+        assert_eq!(
+            tracker.on_exit(&CRASH, short, start + Duration::from_secs(3)),
+            RestartDecision::Crashed
+        );
+
+        // once the window moved past the first restarts the pipe may restart again, and a run
+        // longer than the max backoff starts over from the initial delay
+        assert_eq!(
+            tracker.on_exit(
+                &CRASH,
+                Duration::from_secs(5),
+                start + Duration::from_secs(61)
+            ),
+            RestartDecision::Restart(Duration::from_millis(100))
+        );
+    }
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let now = Instant::now();
+        let mut tracker = RestartTracker::new(RestartConfig {
+            max_restarts: 10,
+            ..config(RestartPolicy::OnFailure)
+        });
+        let mut last = Duration::ZERO;
+        for _ in 0..10 {
+            if let RestartDecision::Restart(delay) = tracker.on_exit(&CRASH, Duration::ZERO, now) {
+                last = delay;
+            }
+        }
+        assert_eq!(last, Duration::from_millis(1000));
+    }
+}
@@ -0,0 +1,268 @@
+#[cfg(all(test, feature = "wasm"))]
+mod tests {
+    use std::path::Path;
+    use std::time::Duration;
+
+    use futures::StreamExt;
+    use screenpipe_core::{CronRunStatus, PipeLogQuery, PIPE_KV_FILE};
+    use screenpipe_server::pipe_supervisor::PipeRunState;
+    use screenpipe_server::PipeManager;
+    use serde_json::{json, Value};
+
+    /// Imports of the `screenpipe` host module and wasi used by the test pipes, wasmtime
+    /// compiles the text format as it is.
+    const IMPORTS: &str = r#"
+        (import "wasi_snapshot_preview1" "fd_write"
+            (func $fd_write (param i32 i32 i32 i32) (result i32)))
+        (import "wasi_snapshot_preview1" "path_open"
+            (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+        (import "screenpipe" "response_read" (func $response_read (param i32) (result i32)))
+        (import "screenpipe" "search" (func $search (param i32 i32) (result i32)))
+        (import "screenpipe" "subscribe" (func $subscribe (param i32 i32) (result i32)))
+        (import "screenpipe" "send_event" (func $send_event (param i32 i32 i32 i32) (result i32)))
+        (import "screenpipe" "kv_set" (func $kv_set (param i32 i32 i32 i32) (result i32)))
+        (import "screenpipe" "http_fetch" (func $http_fetch (param i32 i32) (result i32)))
+        (memory (export "memory") 1)
+    "#;
+
+    async fn write_pipe(screenpipe_dir: &Path, id: &str, config: Value, wat: &str) {
+        let pipe_dir = screenpipe_dir.join("pipes").join(id);
+        tokio::fs::create_dir_all(&pipe_dir).await.unwrap();
+        tokio::fs::write(pipe_dir.join("pipe.json"), config.to_string())
+            .await
+            .unwrap();
+        tokio::fs::write(
+            pipe_dir.join("pipe.wasm"),
+            format!("(module {} {})", IMPORTS, wat),
+        )
+        .await
+        .unwrap();
+    }
+
+    async fn read_kv(screenpipe_dir: &Path, id: &str) -> Value {
+        let kv = screenpipe_dir.join("pipes").join(id).join(PIPE_KV_FILE);
+        serde_json::from_str(&tokio::fs::read_to_string(kv).await.unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_start_pipe_leaves_it_disabled() {
+        let screenpipe_dir = tempfile::tempdir().unwrap();
+        write_pipe(
+            screenpipe_dir.path(),
+            "oneshot",
+            json!({ "enabled": false, "runtime": "wasm" }),
+            r#"(func (export "_start"))"#,
+        )
+        .await;
+
+        let pipe_manager = PipeManager::new(screenpipe_dir.path().to_path_buf());
+        assert!(pipe_manager.start_pipe("oneshot").await.unwrap());
+        let mut state = None;
+        for _ in 0..100 {
+            state = pipe_manager
+                .get_pipe_status("oneshot")
+                .await
+                .map(|status| status.state);
+            if state == Some(PipeRunState::Exited) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(state, Some(PipeRunState::Exited));
+
+        let config: Value = serde_json::from_str(
+            &tokio::fs::read_to_string(screenpipe_dir.path().join("pipes/oneshot/pipe.json"))
+                .await
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(config["enabled"], false);
+        assert!(pipe_manager.start_pipe("missing").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_wasm_pipe_permissions() {
+        let screenpipe_dir = tempfile::tempdir().unwrap();
+        write_pipe(
+            screenpipe_dir.path(),
+            "capabilities",
+            json!({
+                "enabled": true,
+                "runtime": "wasm",
+                "permissions": { "storage": true, "events": { "send": ["wasm_test"] } }
+            }),
+            r#"
+            (data (i32.const 0) "count")
+            (data (i32.const 16) "42")
+            (data (i32.const 32) "wasm_test")
+            (data (i32.const 48) "{\"ok\":true}")
+            (data (i32.const 64) "{\"url\":\"http://example.com/\"}")
+            (data (i32.const 128) "fetch")
+            (data (i32.const 144) "\"denied\"")
+            (data (i32.const 160) "\b0\00\00\00\06\00\00\00")
+            (data (i32.const 176) "hello\n")
+            (data (i32.const 256) "{\"q\":\"\"}")
+            (data (i32.const 272) "search")
+            (data (i32.const 288) "pipe.json")
+            (data (i32.const 304) "write")
+            (func (export "_start")
+                (drop (call $kv_set (i32.const 0) (i32.const 5) (i32.const 16) (i32.const 2)))
+                (drop (call $send_event
+                    (i32.const 32) (i32.const 9) (i32.const 48) (i32.const 11)))
+                ;; neither the network nor the search are granted
+                (if (i32.lt_s (call $http_fetch (i32.const 64) (i32.const 29)) (i32.const 0))
+                    (then (drop (call $kv_set
+                        (i32.const 128) (i32.const 5) (i32.const 144) (i32.const 8)))))
+                (if (i32.lt_s (call $search (i32.const 256) (i32.const 8)) (i32.const 0))
+                    (then (drop (call $kv_set
+                        (i32.const 272) (i32.const 6) (i32.const 144) (i32.const 8)))))
+                ;; the pipe dir (fd 3) is read-only, truncating pipe.json for writing fails
+                (if (i32.ne (call $path_open (i32.const 3) (i32.const 0) (i32.const 288)
+                        (i32.const 9) (i32.const 8) (i64.const 64) (i64.const 0) (i32.const 0)
+                        (i32.const 320))
+                        (i32.const 0))
+                    (then (drop (call $kv_set
+                        (i32.const 304) (i32.const 5) (i32.const 144) (i32.const 8)))))
+                (drop (call $fd_write (i32.const 1) (i32.const 160) (i32.const 1) (i32.const 200))))
+            "#,
+        )
+        .await;
+
+        let mut events = screenpipe_events::subscribe_to_event::<Value>("wasm_test");
+        let pipe_manager = PipeManager::new(screenpipe_dir.path().to_path_buf());
+        let run = pipe_manager
+            .start_pipe_task("capabilities".to_string())
+            .await
+            .unwrap();
+        tokio::time::timeout(Duration::from_secs(30), run)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            pipe_manager
+                .get_pipe_status("capabilities")
+                .await
+                .unwrap()
+                .state,
+            PipeRunState::Exited
+        );
+
+        let event = tokio::time::timeout(Duration::from_secs(5), events.next())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.data, json!({ "ok": true }));
+        assert_eq!(
+            read_kv(screenpipe_dir.path(), "capabilities").await,
+            json!({ "count": 42, "fetch": "denied", "search": "denied", "write": "denied" })
+        );
+
+        // stdout ends up in the pipe logs like the output of processes
+        let mut logged = false;
+        for _ in 0..50 {
+            let logs = pipe_manager
+                .get_pipe_logs("capabilities", &PipeLogQuery::default())
+                .await
+                .unwrap()
+                .unwrap();
+            if logs.iter().any(|record| record.message == "hello") {
+                logged = true;
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert!(logged);
+    }
+
+    #[tokio::test]
+    async fn test_wasm_pipe_events_and_crons() {
+        let screenpipe_dir = tempfile::tempdir().unwrap();
+        write_pipe(
+            screenpipe_dir.path(),
+            "listener",
+            json!({
+                "enabled": true,
+                "runtime": "wasm",
+                "permissions": { "storage": true, "events": { "subscribe": ["wasm_ping"] } },
+                "crons": [{ "path": "/tick", "schedule": "0 0 0 1 1 *" }]
+            }),
+            r#"
+            (data (i32.const 0) "wasm_ping")
+            (data (i32.const 16) "event")
+            (data (i32.const 32) "cron")
+            (func (export "_start")
+                (drop (call $subscribe (i32.const 0) (i32.const 9))))
+            (func (export "on_event") (param $len i32)
+                (drop (call $response_read (i32.const 1024)))
+                (drop (call $kv_set (i32.const 16) (i32.const 5) (i32.const 1024) (local.get $len))))
+            (func (export "on_cron") (param $len i32) (result i32)
+                (drop (call $response_read (i32.const 2048)))
+                (drop (call $kv_set (i32.const 32) (i32.const 4) (i32.const 2048) (local.get $len)))
+                (i32.const 0))
+            "#,
+        )
+        .await;
+
+        let pipe_manager = PipeManager::new(screenpipe_dir.path().to_path_buf());
+        let run = pipe_manager
+            .start_pipe_task("listener".to_string())
+            .await
+            .unwrap();
+        let run = tokio::spawn(run);
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
+        screenpipe_events::send_event("wasm_ping", json!({ "n": 1 })).unwrap();
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        let cron = pipe_manager
+            .run_cron_now("listener", "/tick")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(cron.status, CronRunStatus::Success);
+
+        let kv = read_kv(screenpipe_dir.path(), "listener").await;
+        assert_eq!(
+            kv["event"],
+            json!({ "name": "wasm_ping", "data": { "n": 1 } })
+        );
+        assert_eq!(kv["cron"]["job"], "/tick");
+
+        pipe_manager.stop_pipe("listener").await.unwrap();
+        tokio::time::timeout(Duration::from_secs(5), run)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_wasm_pipe_out_of_fuel() {
+        let screenpipe_dir = tempfile::tempdir().unwrap();
+        write_pipe(
+            screenpipe_dir.path(),
+            "busy",
+            json!({
+                "enabled": true,
+                "runtime": "wasm",
+                "permissions": { "limits": { "fuel": 100000 } },
+                "restart": { "policy": "never" }
+            }),
+            r#"(func (export "_start") (loop $forever (br $forever)))"#,
+        )
+        .await;
+
+        let pipe_manager = PipeManager::new(screenpipe_dir.path().to_path_buf());
+        let run = pipe_manager
+            .start_pipe_task("busy".to_string())
+            .await
+            .unwrap();
+        let result = tokio::time::timeout(Duration::from_secs(30), run)
+            .await
+            .unwrap();
+        assert!(result.is_err());
+        assert_eq!(
+            pipe_manager.get_pipe_status("busy").await.unwrap().state,
+            PipeRunState::Crashed
+        );
+    }
+}
@@ -0,0 +1,181 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use chrono::{Duration, Utc};
+    use screenpipe_events::Event;
+    use screenpipe_server::rules::{
+        dry_run, RuleAction, RuleFiring, RuleInput, RuleSource, RuleSpec, MAX_EMIT_HOPS,
+    };
+    use screenpipe_server::DatabaseManager;
+    use screenpipe_vision::OcrEngine;
+
+    const SLACK_RULE: &str = r#"
+name: invoices in slack
+when:
+  app: slack
+  text: invoice
+debounce_secs: 60
+actions:
+  - type: tag
+    tags: [invoice]
+  - type: emit
+    event: invoice_seen
+"#;
+
+    #[test]
+    fn test_parse_yaml_and_json_rules() {
+        let spec = RuleSpec::parse(SLACK_RULE).unwrap();
+        assert_eq!(spec.name, "invoices in slack");
+        assert!(spec.enabled);
+        assert_eq!(spec.when.app.as_deref(), Some("slack"));
+        assert_eq!(spec.debounce_secs, 60);
+        assert_eq!(
+            spec.actions,
+            vec![
+                RuleAction::Tag {
+                    tags: vec!["invoice".to_string()]
+                },
+                RuleAction::Emit {
+                    event: "invoice_seen".to_string()
+                },
+            ]
+        );
+
+        let json = serde_json::to_string(&spec).unwrap();
+        assert_eq!(RuleSpec::parse(&json).unwrap(), spec);
+
+        let spec = RuleSpec::parse(
+            r#"{
+                "name": "standup",
+                "when": {
+                    "sources": ["audio"],
+                    "speaker": "alice",
+                    "time": { "after": "09:00", "before": "10:00", "days": ["Mon", "Fri"] }
+                },
+                "actions": [{ "type": "pause_capture", "seconds": 600 }]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(spec.when.sources, vec![RuleSource::Audio]);
+        assert_eq!(spec.when.time.unwrap().days.len(), 2);
+    }
+
+    #[test]
+    fn test_invalid_rules_are_rejected() {
+        for source in [
+            // no actions
+            "name: empty\nactions: []",
+            // unknown condition
+            "name: typo\nwhen:\n  ap: slack\nactions:\n  - type: emit\n    event: x",
+            // listens to the event it emits
+            "name: loop\nwhen:\n  events: [rule_*]\nactions:\n  - type: emit\n    event: rule_loop",
+            "name: bad time\nwhen:\n  time:\n    after: '25:00'\nactions:\n  - type: emit\n    event: x",
+            "name: bad regex\nwhen:\n  pattern: '('\nactions:\n  - type: emit\n    event: x",
+            "name: no tags\nactions:\n  - type: tag\n    tags: []",
+            "name: bad predicate\nwhen:\n  events: [meeting_*]\n  where: 'app =='\nactions:\n  - type: pause_capture\n    seconds: 1",
+        ] {
+            assert!(RuleSpec::parse(source).is_err(), "accepted {}", source);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rule_crud() {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+        let mut spec = RuleSpec::parse(SLACK_RULE).unwrap();
+        let id = db.insert_rule(&spec).await.unwrap();
+
+        spec.enabled = false;
+        assert!(db.update_rule(id, &spec).await.unwrap());
+        let rule = db.get_rule(id).await.unwrap().unwrap();
+        assert_eq!(rule.spec, spec);
+        assert_eq!(db.list_rules().await.unwrap().len(), 1);
+
+        assert!(db.delete_rule(id).await.unwrap());
+        assert!(!db.delete_rule(id).await.unwrap());
+        assert!(!db.update_rule(id, &spec).await.unwrap());
+        assert!(db.get_rule(id).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_matches_past_content_with_debounce() {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+        db.insert_video_chunk("test_video.mp4", "test_device")
+            .await
+            .unwrap();
+        let start = Utc::now() - Duration::hours(3);
+        for (offset, app, text) in [
+            (Duration::minutes(10), "Slack", "invoice #1 is due"),
+            (
+                Duration::minutes(10) + Duration::seconds(20),
+                "Slack",
+                "invoice #1 again",
+            ),
+            (Duration::minutes(30), "Chrome", "invoice #2"),
+            (Duration::minutes(90), "Slack", "lunch?"),
+            (Duration::minutes(150), "Slack", "Invoice #3"),
+        ] {
+            let frame_id = db
+                .insert_frame("test_device", Some(start + offset))
+                .await
+                .unwrap();
+            db.insert_ocr_text(
+                frame_id,
+                text,
+                "",
+                app,
+                "general",
+                Arc::new(OcrEngine::Tesseract),
+                false,
+            )
+            .await
+            .unwrap();
+        }
+
+        let spec = RuleSpec::parse(SLACK_RULE).unwrap();
+        let firings = dry_run(&db, 0, &spec, start, Utc::now(), 100)
+            .await
+            .unwrap();
+        let texts: Vec<_> = firings
+            .iter()
+            .map(|f| f.input.text.clone().unwrap())
+            .collect();
+        // the second frame falls within the debounce of the first
+        assert_eq!(texts, vec!["invoice #1 is due", "Invoice #3"]);
+        assert!(firings.iter().all(|f| f.input.source == RuleSource::Ocr));
+        assert_eq!(firings[0].rule, "invoices in slack");
+
+        let firings = dry_run(&db, 0, &spec, start, Utc::now(), 1).await.unwrap();
+        assert_eq!(firings.len(), 1);
+    }
+
+    #[test]
+    fn test_emitted_events_count_hops() {
+        let firing_on = |data: serde_json::Value| RuleFiring {
+            rule_id: 1,
+            rule: "chain".to_string(),
+            input: RuleInput::from_event(
+                &Event {
+                    name: "chained".to_string(),
+                    data,
+                    offset: None,
+                },
+                Utc::now(),
+            ),
+            actions: vec![RuleAction::Emit {
+                event: "next".to_string(),
+            }],
+        };
+
+        // an event no rule emitted starts a chain
+        let first = firing_on(serde_json::json!({ "hops": 5 }));
+        assert_eq!(first.hops(), 1);
+
+        // each rule the emitted event goes through adds one
+        let mut firing = first;
+        for hops in 2..=MAX_EMIT_HOPS {
+            firing = firing_on(firing.payload());
+            assert_eq!(firing.hops(), hops);
+        }
+    }
+}
@@ -0,0 +1,104 @@
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Utc};
+    use screenpipe_server::{
+        db_types::{ContentType, SearchResult},
+        seed::{seed_database, SeedConfig},
+        DatabaseManager,
+    };
+
+    fn config(seed: u64) -> SeedConfig {
+        SeedConfig {
+            days: 3,
+            // a friday evening, the days before are workdays
+            until: "2024-05-17T19:00:00Z".parse().unwrap(),
+            seed,
+            frame_interval: 60,
+            media: false,
+        }
+    }
+
+    async fn seed(seed: u64) -> (DatabaseManager, serde_json::Value) {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+        let media_dir = tempfile::tempdir().unwrap();
+        let summary = seed_database(&db, media_dir.path(), &config(seed))
+            .await
+            .unwrap();
+        (db, serde_json::to_value(summary).unwrap())
+    }
+
+    async fn search_all(db: &DatabaseManager, content_type: ContentType) -> Vec<SearchResult> {
+        db.search(
+            "",
+            content_type,
+            10000,
+            0,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_seed_is_deterministic() {
+        let (first, first_summary) = seed(7).await;
+        let (second, second_summary) = seed(7).await;
+        assert_eq!(first_summary, second_summary);
+        for content_type in [ContentType::OCR, ContentType::Audio, ContentType::UI] {
+            assert_eq!(
+                serde_json::to_value(search_all(&first, content_type.clone()).await).unwrap(),
+                serde_json::to_value(search_all(&second, content_type).await).unwrap()
+            );
+        }
+
+        let (_, other_summary) = seed(8).await;
+        assert_ne!(first_summary, other_summary);
+    }
+
+    #[tokio::test]
+    async fn test_seed_fills_every_table() {
+        let (db, summary) = seed(42).await;
+        for key in [
+            "video_chunks",
+            "frames",
+            "audio_chunks",
+            "transcriptions",
+            "ui_monitoring",
+            "tags",
+        ] {
+            assert!(summary[key].as_u64().unwrap() > 0, "no {}", key);
+        }
+        assert_eq!(summary["speakers"], 6);
+
+        let until: DateTime<Utc> = config(42).until;
+        let audio = search_all(&db, ContentType::Audio).await;
+        assert_eq!(audio.len() as u64, summary["transcriptions"].as_u64().unwrap());
+        for result in &audio {
+            let SearchResult::Audio(audio) = result else {
+                panic!("expected audio result");
+            };
+            // transcriptions are dated like the meetings, not like the seeding
+            assert!(audio.timestamp <= until);
+            assert!(audio.timestamp > until - chrono::Duration::days(3));
+            assert!(audio.speaker.is_some());
+        }
+        assert!(audio.iter().any(|result| matches!(
+            result,
+            SearchResult::Audio(audio) if audio.tags.contains(&"meeting".to_string())
+        )));
+
+        let ocr = search_all(&db, ContentType::OCR).await;
+        assert_eq!(ocr.len() as u64, summary["frames"].as_u64().unwrap());
+        assert!(ocr.iter().all(|result| matches!(
+            result,
+            SearchResult::OCR(ocr) if ocr.timestamp <= until && !ocr.app_name.is_empty()
+        )));
+    }
+}
@@ -0,0 +1,141 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use chrono::{Duration, Utc};
+    use screenpipe_server::db_types::ChunkKind;
+    use screenpipe_server::storage::{
+        LocalChunkStore, MediaStorage, S3ChunkStore, S3Config, StorageError,
+    };
+    use screenpipe_server::DatabaseManager;
+    use tempfile::tempdir;
+
+    async fn setup_test_db() -> Arc<DatabaseManager> {
+        Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_offload_old_chunks_and_fetch_back() {
+        let db = setup_test_db().await;
+        let dir = tempdir().unwrap();
+        let chunk_path = dir.path().join("monitor_1_2024-01-01_00-00-00.mp4");
+        tokio::fs::write(&chunk_path, b"fake video").await.unwrap();
+        let chunk_path = chunk_path.to_string_lossy().to_string();
+
+        db.insert_video_chunk(&chunk_path, "monitor_1")
+            .await
+            .unwrap();
+        db.insert_frame("monitor_1", Some(Utc::now() - Duration::days(10)))
+            .await
+            .unwrap();
+
+        let remote = Arc::new(LocalChunkStore::new(dir.path().join("remote")));
+        let storage =
+            MediaStorage::with_remote(db.clone(), remote, dir.path().join("cache"), 1024 * 1024)
+                .await
+                .unwrap();
+
+        let stats = storage.offload_older_than(Duration::days(7)).await.unwrap();
+        assert_eq!(stats.offloaded, 1);
+        assert!(!std::path::Path::new(&chunk_path).exists());
+        assert!(dir
+            .path()
+            .join("remote/video/monitor_1_2024-01-01_00-00-00.mp4")
+            .exists());
+
+        // a second run must not pick up the same chunk again
+        let stats = storage.offload_older_than(Duration::days(7)).await.unwrap();
+        assert_eq!(stats.offloaded, 0);
+
+        let resolved = storage.resolve(&chunk_path).await.unwrap();
+        assert_ne!(resolved.to_string_lossy(), chunk_path);
+        assert_eq!(tokio::fs::read(&resolved).await.unwrap(), b"fake video");
+    }
+
+    #[tokio::test]
+    async fn test_recent_chunks_stay_local() {
+        let db = setup_test_db().await;
+        let dir = tempdir().unwrap();
+        let chunk_path = dir.path().join("monitor_1_recent.mp4");
+        tokio::fs::write(&chunk_path, b"fake video").await.unwrap();
+        let chunk_path = chunk_path.to_string_lossy().to_string();
+
+        db.insert_video_chunk(&chunk_path, "monitor_1")
+            .await
+            .unwrap();
+        db.insert_frame("monitor_1", None).await.unwrap();
+
+        let remote = Arc::new(LocalChunkStore::new(dir.path().join("remote")));
+        let storage =
+            MediaStorage::with_remote(db.clone(), remote, dir.path().join("cache"), 1024 * 1024)
+                .await
+                .unwrap();
+
+        let stats = storage.offload_older_than(Duration::days(7)).await.unwrap();
+        assert_eq!(stats.offloaded, 0);
+        assert_eq!(
+            storage
+                .resolve(&chunk_path)
+                .await
+                .unwrap()
+                .to_string_lossy(),
+            chunk_path
+        );
+    }
+
+    #[tokio::test]
+    async fn test_offloaded_chunk_fails_clearly_when_store_unreachable() {
+        let db = setup_test_db().await;
+        let dir = tempdir().unwrap();
+        let chunk_path = dir.path().join("gone.mp4").to_string_lossy().to_string();
+
+        let chunk_id = db
+            .insert_video_chunk(&chunk_path, "monitor_1")
+            .await
+            .unwrap();
+        db.mark_chunk_offloaded(ChunkKind::Video, chunk_id, "video/gone.mp4")
+            .await
+            .unwrap();
+
+        let remote = S3ChunkStore::new(S3Config {
+            endpoint: "http://127.0.0.1:1".to_string(),
+            bucket: "screenpipe".to_string(),
+            region: "us-east-1".to_string(),
+            access_key: "minioadmin".to_string(),
+            secret_key: "minioadmin".to_string(),
+            prefix: String::new(),
+        })
+        .unwrap();
+        let storage = MediaStorage::with_remote(
+            db.clone(),
+            Arc::new(remote),
+            dir.path().join("cache"),
+            1024 * 1024,
+        )
+        .await
+        .unwrap();
+
+        match storage.resolve(&chunk_path).await {
+            Err(StorageError::Unavailable(_)) => {}
+            other => panic!("expected unavailable error, got {:?}", other),
+        }
+
+        // without a remote store configured the error must still say why
+        let local_only = MediaStorage::local(db.clone());
+        match local_only.resolve(&chunk_path).await {
+            Err(StorageError::Unavailable(msg)) => assert!(msg.contains("video/gone.mp4")),
+            other => panic!("expected unavailable error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unknown_chunk_is_not_found() {
+        let db = setup_test_db().await;
+        let storage = MediaStorage::local(db);
+
+        match storage.resolve("/nonexistent/chunk.mp4").await {
+            Err(StorageError::NotFound(_)) => {}
+            other => panic!("expected not found error, got {:?}", other),
+        }
+    }
+}
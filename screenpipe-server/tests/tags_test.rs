@@ -13,8 +13,8 @@ use tokio::sync::Mutex;
 use tower::ServiceExt;
 
 use screenpipe_server::{
-    create_router, video_cache::FrameCache, AppState, ContentItem, DatabaseManager,
-    PaginatedResponse, PipeManager,
+    create_router, video_cache::FrameCache, AppState, ContentItem, DatabaseManager, MediaStorage,
+    PaginatedResponse, PipeManager, Store,
 };
 
 // Add this function to initialize the logger
@@ -33,12 +33,15 @@ async fn setup_test_app() -> (Router, Arc<AppState>) {
         screenpipe_dir: PathBuf::from(""),
         pipe_manager: Arc::new(PipeManager::new(PathBuf::from(""))),
         frame_cache: Some(Arc::new(
-            FrameCache::new(PathBuf::from(""), db).await.unwrap(),
+            FrameCache::new(PathBuf::from(""), db.clone())
+                .await
+                .unwrap(),
         )),
         ui_monitoring_enabled: false,
         frame_image_cache: Some(Arc::new(Mutex::new(LruCache::new(
             NonZeroUsize::new(100).unwrap(),
         )))),
+        media_storage: Arc::new(MediaStorage::local(db.clone())),
     });
 
     let app = create_router().with_state(app_state.clone());
@@ -357,7 +360,7 @@ async fn test_search_by_multiple_tags() {
     }
 }
 
-async fn insert_test_data(db: &Arc<DatabaseManager>) {
+async fn insert_test_data(db: &Arc<dyn Store>) {
     // Insert test video chunk
     let _video_chunk_id = db
         .insert_video_chunk("test_video_file.mp4", "test_device")
@@ -0,0 +1,42 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use crossbeam::queue::ArrayQueue;
+    use image::DynamicImage;
+    use screenpipe_server::core::pause_capture;
+    use screenpipe_server::queue_frame;
+    use screenpipe_vision::CaptureResult;
+
+    fn frame(frame_number: u64) -> CaptureResult {
+        CaptureResult {
+            image: DynamicImage::new_rgb8(2, 2),
+            frame_number,
+            timestamp: Instant::now(),
+            window_ocr_results: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_paused_frames_never_reach_the_video_chunk() {
+        let video_queue = Arc::new(ArrayQueue::new(10));
+        let ocr_queue = Arc::new(ArrayQueue::new(10));
+
+        assert!(queue_frame(&video_queue, &ocr_queue, frame(1)));
+
+        pause_capture(Duration::from_millis(300));
+        for n in 2..5 {
+            assert!(!queue_frame(&video_queue, &ocr_queue, frame(n)));
+        }
+        assert_eq!(video_queue.len(), 1);
+        assert_eq!(ocr_queue.len(), 1);
+
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        assert!(queue_frame(&video_queue, &ocr_queue, frame(5)));
+        let queued: Vec<u64> = std::iter::from_fn(|| video_queue.pop())
+            .map(|f| f.frame_number)
+            .collect();
+        assert_eq!(queued, vec![1, 5]);
+    }
+}
@@ -0,0 +1,264 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use axum::body::Bytes;
+    use axum::extract::State;
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::post;
+    use axum::Router;
+    use chrono::Utc;
+    use screenpipe_core::{set_secrets_key_source, SecretsKeySource};
+    use screenpipe_events::send_event;
+    use screenpipe_server::webhooks::{
+        redeliver_dead_letter, seal_webhook_secret, set_webhook_secrets_dir, sign_payload,
+        DeliveryStatus, WebhookConfig, WebhookDeadLetter, WebhookDelivery, WebhookDispatcher,
+        WebhookSpec, SIGNATURE_HEADER, TIMESTAMP_HEADER,
+    };
+    use screenpipe_server::DatabaseManager;
+    use serde_json::{json, Value};
+    use tokio::sync::Mutex;
+
+    /// Local stand-in for a webhook receiver.
+    #[derive(Clone, Default)]
+    struct Receiver {
+        requests: Arc<Mutex<Vec<(HeaderMap, Bytes)>>>,
+        hits: Arc<AtomicUsize>,
+        failing: Arc<AtomicBool>,
+    }
+
+    async fn receive(
+        State(receiver): State<Receiver>,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> StatusCode {
+        receiver.hits.fetch_add(1, Ordering::SeqCst);
+        if receiver.failing.load(Ordering::SeqCst) {
+            return StatusCode::SERVICE_UNAVAILABLE;
+        }
+        receiver.requests.lock().await.push((headers, body));
+        StatusCode::OK
+    }
+
+    async fn start_receiver() -> (String, Receiver) {
+        let receiver = Receiver::default();
+        let app = Router::new()
+            .route("/hook", post(receive))
+            .with_state(receiver.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        (url, receiver)
+    }
+
+    fn test_config() -> WebhookConfig {
+        WebhookConfig {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(50),
+            ..Default::default()
+        }
+    }
+
+    async fn wait_for_deliveries(db: &DatabaseManager, webhook_id: i64) -> Vec<WebhookDelivery> {
+        for _ in 0..100 {
+            let deliveries = db.get_webhook_deliveries(webhook_id, 10).await.unwrap();
+            if !deliveries.is_empty() {
+                return deliveries;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("webhook {} got no delivery", webhook_id);
+    }
+
+    #[tokio::test]
+    async fn test_matching_events_are_signed_and_delivered() {
+        let db = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
+        let (url, receiver) = start_receiver().await;
+        let secrets_dir = tempfile::tempdir().unwrap();
+        set_secrets_key_source(SecretsKeySource::File);
+        set_webhook_secrets_dir(secrets_dir.path().to_path_buf());
+        let mut spec = WebhookSpec {
+            url,
+            events: vec!["webhook_test_*".to_string()],
+            filter: Some(r#"app == "Slack""#.to_string()),
+            secret: Some("s3cret".to_string()),
+            headers: BTreeMap::from([("x-api-key".to_string(), "key".to_string())]),
+            enabled: true,
+        };
+        seal_webhook_secret(&mut spec).await.unwrap();
+        let webhook_id = db.insert_webhook(&spec).await.unwrap();
+        // the database only has the sealed secret
+        let stored = db.get_webhook(webhook_id).await.unwrap().unwrap();
+        assert!(!stored.secret.unwrap().contains("s3cret"));
+        let dispatcher = WebhookDispatcher::new(db.clone(), test_config())
+            .unwrap()
+            .start();
+
+        send_event("webhook_test_other_app", json!({ "app": "Arc" })).unwrap();
+        send_event("unrelated_webhook_event", json!({ "app": "Slack" })).unwrap();
+        send_event(
+            "webhook_test_message",
+            json!({ "app": "Slack", "text": "hi" }),
+        )
+        .unwrap();
+
+        let deliveries = wait_for_deliveries(&db, webhook_id).await;
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].status, DeliveryStatus::Delivered);
+        assert_eq!(deliveries[0].event, "webhook_test_message");
+        assert_eq!(deliveries[0].attempts, 1);
+        assert_eq!(deliveries[0].response_status, Some(200));
+
+        let requests = receiver.requests.lock().await;
+        assert_eq!(requests.len(), 1);
+        let (headers, body) = &requests[0];
+        let timestamp: i64 = headers[TIMESTAMP_HEADER].to_str().unwrap().parse().unwrap();
+        assert_eq!(
+            headers[SIGNATURE_HEADER].to_str().unwrap(),
+            sign_payload("s3cret", timestamp, body)
+        );
+        assert_eq!(headers["x-api-key"], "key");
+        let payload: Value = serde_json::from_slice(body).unwrap();
+        assert_eq!(payload["event"], "webhook_test_message");
+        assert_eq!(payload["data"]["text"], "hi");
+
+        dispatcher.abort();
+    }
+
+    #[tokio::test]
+    async fn test_failed_deliveries_are_retried_then_dead_lettered() {
+        let db = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
+        let (url, receiver) = start_receiver().await;
+        receiver.failing.store(true, Ordering::SeqCst);
+        let webhook_id = db
+            .insert_webhook(&WebhookSpec {
+                url,
+                events: vec!["webhook_failing_test".to_string()],
+                filter: None,
+                secret: None,
+                headers: BTreeMap::new(),
+                enabled: true,
+            })
+            .await
+            .unwrap();
+        let dispatcher = WebhookDispatcher::new(db.clone(), test_config())
+            .unwrap()
+            .start();
+
+        send_event("webhook_failing_test", json!({ "n": 1 })).unwrap();
+
+        let deliveries = wait_for_deliveries(&db, webhook_id).await;
+        assert_eq!(deliveries[0].status, DeliveryStatus::DeadLettered);
+        assert_eq!(deliveries[0].attempts, 3);
+        assert_eq!(deliveries[0].response_status, Some(503));
+        assert_eq!(receiver.hits.load(Ordering::SeqCst), 3);
+
+        let letters = db.get_webhook_dead_letters(webhook_id, 10).await.unwrap();
+        assert_eq!(letters.len(), 1);
+        assert_eq!(letters[0].payload["event"], "webhook_failing_test");
+        assert_eq!(letters[0].payload["data"]["n"], 1);
+
+        // the receiver is back, the dead letter goes through and is removed
+        receiver.failing.store(false, Ordering::SeqCst);
+        let webhook = db.get_webhook(webhook_id).await.unwrap().unwrap();
+        let delivery = redeliver_dead_letter(db.as_ref(), &webhook, &letters[0])
+            .await
+            .unwrap();
+        assert_eq!(delivery.status, DeliveryStatus::Delivered);
+        assert!(db
+            .get_webhook_dead_letters(webhook_id, 10)
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(receiver.requests.lock().await.len(), 1);
+
+        dispatcher.abort();
+    }
+
+    #[tokio::test]
+    async fn test_webhook_history_is_pruned() {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+        let delivery = |webhook_id, age_days| WebhookDelivery {
+            id: None,
+            webhook_id,
+            event: "pruned".to_string(),
+            event_offset: None,
+            status: DeliveryStatus::Delivered,
+            attempts: 1,
+            response_status: Some(200),
+            error: None,
+            duration_ms: 1,
+            delivered_at: Utc::now() - chrono::Duration::days(age_days),
+        };
+        for _ in 0..3 {
+            db.insert_webhook_delivery(&delivery(1, 0)).await.unwrap();
+        }
+        db.insert_webhook_delivery(&delivery(2, 40)).await.unwrap();
+        db.insert_webhook_delivery(&delivery(2, 0)).await.unwrap();
+        db.insert_webhook_dead_letter(&WebhookDeadLetter {
+            id: None,
+            webhook_id: 2,
+            event: "pruned".to_string(),
+            event_offset: None,
+            payload: json!({}),
+            attempts: 1,
+            error: "gone".to_string(),
+            created_at: Utc::now() - chrono::Duration::days(40),
+        })
+        .await
+        .unwrap();
+
+        // the oldest delivery of webhook 1 is past the count, webhook 2 keeps its recent one
+        let pruned = db
+            .prune_webhook_history(2, Utc::now() - chrono::Duration::days(30))
+            .await
+            .unwrap();
+        assert_eq!(pruned, 3);
+        assert_eq!(db.get_webhook_deliveries(1, 10).await.unwrap().len(), 2);
+        assert_eq!(db.get_webhook_deliveries(2, 10).await.unwrap().len(), 1);
+        assert!(db.get_webhook_dead_letters(2, 10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_webhook_crud() {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+        let mut spec = WebhookSpec {
+            url: "http://localhost:9999/hook".to_string(),
+            events: vec!["meeting_*".to_string()],
+            filter: None,
+            secret: Some("first".to_string()),
+            headers: BTreeMap::new(),
+            enabled: true,
+        };
+        let id = db.insert_webhook(&spec).await.unwrap();
+
+        spec.url = "https://example.com/hook".to_string();
+        spec.secret = None;
+        assert!(db.update_webhook(id, &spec).await.unwrap());
+        let webhook = db.get_webhook(id).await.unwrap().unwrap();
+        assert_eq!(webhook.url, "https://example.com/hook");
+        assert_eq!(webhook.events, vec!["meeting_*".to_string()]);
+        // no secret in an update keeps the current one
+        assert_eq!(webhook.secret.as_deref(), Some("first"));
+        assert!(serde_json::to_value(&webhook)
+            .unwrap()
+            .get("secret")
+            .is_none());
+
+        assert!(db.delete_webhook(id).await.unwrap());
+        assert!(!db.delete_webhook(id).await.unwrap());
+        assert!(db.list_webhooks().await.unwrap().is_empty());
+
+        spec.url = "ftp://example.com".to_string();
+        assert!(spec.validate().is_err());
+        spec.url = "https://example.com".to_string();
+        spec.filter = Some("app ==".to_string());
+        assert!(spec.validate().is_err());
+    }
+}